@@ -27,4 +27,5 @@ mod verify;
 #[cfg(test)]
 mod test_vectors;
 
+pub use params::Params;
 pub use verify::{is_valid_solution, Error};
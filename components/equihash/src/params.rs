@@ -1,23 +1,52 @@
-#[derive(Clone, Copy)]
-pub(crate) struct Params {
+/// Equihash parameters `(n, k)`.
+///
+/// Zcash uses `(200, 9)` on mainnet and testnet, and `(48, 5)` on regtest networks, where
+/// blocks must be mined quickly.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Params {
     pub(crate) n: u32,
     pub(crate) k: u32,
 }
 
 impl Params {
+    /// The Equihash parameters used by Zcash mainnet and testnet.
+    pub const ZCASH_MAINNET: Params = Params { n: 200, k: 9 };
+
+    /// The Equihash parameters used by Zcash regtest networks.
+    pub const ZCASH_REGTEST: Params = Params { n: 48, k: 5 };
+
     /// Returns `None` if the parameters are invalid.
-    pub(crate) fn new(n: u32, k: u32) -> Option<Self> {
+    pub fn new(n: u32, k: u32) -> Option<Self> {
         // We place the following requirements on the parameters:
         // - n is a multiple of 8, so the hash output has an exact byte length.
         // - k >= 3 so the encoded solutions have an exact byte length.
         // - k < n, so the collision bit length is at least 1.
         // - n is a multiple of k + 1, so we have an integer collision bit length.
-        if (n % 8 == 0) && (k >= 3) && (k < n) && (n % (k + 1) == 0) {
+        // - the collision bit length is at most 24, so that each encoded index (which has
+        //   one more bit) can be expanded within a 32-bit accumulator.
+        if (n % 8 == 0) && (k >= 3) && (k < n) && (n % (k + 1) == 0) && (n / (k + 1) <= 24) {
             Some(Params { n, k })
         } else {
             None
         }
     }
+
+    /// Returns the parameter `n`, the bit length of each hash output.
+    pub fn n(&self) -> u32 {
+        self.n
+    }
+
+    /// Returns the parameter `k`; each solution contains `2^k` indices.
+    pub fn k(&self) -> u32 {
+        self.k
+    }
+
+    /// Returns the length in bytes of an encoded solution for these parameters.
+    pub fn solution_len(&self) -> usize {
+        // Division is exact because k >= 3.
+        ((1 << self.k) * (self.collision_bit_length() + 1)) / 8
+    }
+
     pub(crate) fn indices_per_hash_output(&self) -> u32 {
         512 / self.n
     }
@@ -245,16 +245,26 @@ pub fn is_valid_solution(
     nonce: &[u8],
     soln: &[u8],
 ) -> Result<(), Error> {
-    let p = Params::new(n, k).ok_or(Error(Kind::InvalidParams))?;
-    let indices = indices_from_minimal(p, soln).ok_or(Error(Kind::InvalidParams))?;
+    Params::new(n, k)
+        .ok_or(Error(Kind::InvalidParams))?
+        .is_valid_solution(input, nonce, soln)
+}
+
+impl Params {
+    /// Checks whether `soln` is a valid solution for `(input, nonce)` with these
+    /// parameters.
+    pub fn is_valid_solution(&self, input: &[u8], nonce: &[u8], soln: &[u8]) -> Result<(), Error> {
+        let indices = indices_from_minimal(*self, soln).ok_or(Error(Kind::InvalidParams))?;
 
-    // Recursive validation is faster
-    is_valid_solution_recursive(p, input, nonce, &indices)
+        // Recursive validation is faster
+        is_valid_solution_recursive(*self, input, nonce, &indices)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::{is_valid_solution, is_valid_solution_iterative, is_valid_solution_recursive};
+    use crate::params::Params;
     use crate::test_vectors::{INVALID_TEST_VECTORS, VALID_TEST_VECTORS};
 
     #[test]
@@ -285,6 +295,17 @@ mod tests {
         }
     }
 
+    #[test]
+    fn zcash_params() {
+        assert_eq!(Params::new(200, 9), Some(Params::ZCASH_MAINNET));
+        assert_eq!(Params::new(48, 5), Some(Params::ZCASH_REGTEST));
+        assert_eq!(Params::ZCASH_MAINNET.solution_len(), 1344);
+        assert_eq!(Params::ZCASH_REGTEST.solution_len(), 36);
+
+        // Parameters whose indices cannot be represented are rejected.
+        assert_eq!(Params::new(200, 3), None);
+    }
+
     #[test]
     fn all_bits_matter() {
         // Initialize the state according to one of the valid test vectors.
@@ -305,6 +326,10 @@ mod tests {
 
         // Prove that the solution is valid.
         is_valid_solution(n, k, input, &nonce, soln).unwrap();
+        Params::new(n, k)
+            .unwrap()
+            .is_valid_solution(input, &nonce, soln)
+            .unwrap();
 
         // Changing any single bit of the encoded solution should make it invalid.
         for i in 0..soln.len() * 8 {
@@ -175,6 +175,23 @@ impl fmt::Display for ZcashAddress {
     }
 }
 
+#[cfg(feature = "serde")]
+impl serde::Serialize for ZcashAddress {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ZcashAddress {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error as _;
+
+        let encoded = String::deserialize(deserializer)?;
+        encoded.parse().map_err(D::Error::custom)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use assert_matches::assert_matches;
@@ -376,4 +393,17 @@ mod tests {
             Err(ParseError::NotZcash),
         );
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let addr = ZcashAddress {
+            net: Network::Main,
+            kind: AddressKind::P2pkh([0; 20]),
+        };
+        let json = serde_json::to_string(&addr).unwrap();
+        assert_eq!(json, "\"t1Hsc1LR8yKnbbe3twRp88p6vFfC5t7DLbs\"");
+        assert_eq!(serde_json::from_str::<ZcashAddress>(&json).unwrap(), addr);
+        assert!(serde_json::from_str::<ZcashAddress>("\"not an address\"").is_err());
+    }
 }
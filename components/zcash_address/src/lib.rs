@@ -146,6 +146,9 @@ pub use zcash_protocol::consensus::NetworkType as Network;
 use zcash_protocol::{PoolType, ShieldedProtocol};
 
 /// A Zcash address.
+///
+/// When the `serde` feature flag is enabled, a `ZcashAddress` is serialized as its canonical
+/// string encoding (see [`ZcashAddress::encode`]).
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct ZcashAddress {
     net: Network,
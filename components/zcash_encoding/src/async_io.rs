@@ -0,0 +1,220 @@
+//! Encoding and decoding using asynchronous readers and writers.
+//!
+//! The functions in this module produce and consume exactly the same encodings as their
+//! synchronous counterparts, but operate on [`AsyncRead`] and [`AsyncWrite`] streams so that
+//! values can be streamed to and from network connections without first being buffered in
+//! their entirety.
+
+use futures_util::{
+    future::BoxFuture,
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+};
+use std::future::Future;
+use std::io;
+
+use crate::{CompactSize, Optional, Vector};
+
+#[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+impl CompactSize {
+    /// Reads an integer encoded in compact form from an asynchronous reader.
+    pub async fn read_async<R: AsyncRead + Unpin>(mut reader: R) -> io::Result<u64> {
+        let mut buf = [0; 9];
+        reader.read_exact(&mut buf[..1]).await?;
+        let len = match buf[0] {
+            flag if flag < 253 => 1,
+            253 => 3,
+            254 => 5,
+            _ => 9,
+        };
+        reader.read_exact(&mut buf[1..len]).await?;
+        Self::read(&buf[..len])
+    }
+
+    /// Reads an integer encoded in compact form from an asynchronous reader and performs
+    /// checked conversion to the target type.
+    pub async fn read_t_async<R: AsyncRead + Unpin, T: TryFrom<u64>>(reader: R) -> io::Result<T> {
+        let n = Self::read_async(reader).await?;
+        <T>::try_from(n).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "CompactSize value exceeds range of target type.",
+            )
+        })
+    }
+
+    /// Writes the provided `usize` value to the provided asynchronous writer in compact form.
+    pub async fn write_async<W: AsyncWrite + Unpin>(mut writer: W, size: usize) -> io::Result<()> {
+        let mut buf = Vec::with_capacity(9);
+        Self::write(&mut buf, size)?;
+        writer.write_all(&buf).await
+    }
+}
+
+#[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+impl Vector {
+    /// Reads a vector from an asynchronous reader, assuming the encoding written by
+    /// [`Vector::write`], using the provided function to decode each element of the vector.
+    ///
+    /// Elements are decoded one at a time as they are read from the stream.
+    pub async fn read_async<R, E, F>(mut reader: R, mut func: F) -> io::Result<Vec<E>>
+    where
+        R: AsyncRead + Unpin,
+        F: for<'r> FnMut(&'r mut R) -> BoxFuture<'r, io::Result<E>>,
+    {
+        let count: usize = CompactSize::read_t_async(&mut reader).await?;
+        // The length prefix has not been authenticated, so we avoid preallocating based
+        // on it.
+        let mut items = vec![];
+        for _ in 0..count {
+            items.push(func(&mut reader).await?);
+        }
+        Ok(items)
+    }
+
+    /// Writes a slice of values to an asynchronous writer, using the same encoding as
+    /// [`Vector::write`]. Each element is encoded by the provided function.
+    pub async fn write_async<W, E, F>(mut writer: W, vec: &[E], mut func: F) -> io::Result<()>
+    where
+        W: AsyncWrite + Unpin,
+        F: for<'w> FnMut(&'w mut W, &'w E) -> BoxFuture<'w, io::Result<()>>,
+    {
+        CompactSize::write_async(&mut writer, vec.len()).await?;
+        for e in vec {
+            func(&mut writer, e).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg_attr(docsrs, doc(cfg(feature = "async")))]
+impl Optional {
+    /// Reads an optional value from an asynchronous reader, assuming the encoding written by
+    /// [`Optional::write`], using the provided function to decode the contained element if
+    /// present.
+    pub async fn read_async<R, T, F, Fut>(mut reader: R, func: F) -> io::Result<Option<T>>
+    where
+        R: AsyncRead + Unpin,
+        F: FnOnce(R) -> Fut,
+        Fut: Future<Output = io::Result<T>>,
+    {
+        let mut flag = [0; 1];
+        reader.read_exact(&mut flag).await?;
+        match flag[0] {
+            0 => Ok(None),
+            1 => Ok(Some(func(reader).await?)),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "non-canonical Option<T>",
+            )),
+        }
+    }
+
+    /// Writes an optional value to an asynchronous writer, using the same encoding as
+    /// [`Optional::write`]. The contents of the option, if any, are encoded by the provided
+    /// function.
+    pub async fn write_async<W, T, F, Fut>(mut writer: W, val: Option<T>, func: F) -> io::Result<()>
+    where
+        W: AsyncWrite + Unpin,
+        F: FnOnce(W, T) -> Fut,
+        Fut: Future<Output = io::Result<()>>,
+    {
+        match val {
+            None => writer.write_all(&[0]).await,
+            Some(e) => {
+                writer.write_all(&[1]).await?;
+                func(writer, e).await
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use byteorder::WriteBytesExt;
+    use futures_util::{
+        future::FutureExt,
+        io::{AsyncReadExt, AsyncWriteExt},
+    };
+    use std::future::Future;
+
+    use crate::{CompactSize, Optional, Vector};
+
+    /// Drives a future that never waits on I/O, as is the case when reading from a slice or
+    /// writing to a `Vec`.
+    fn run<F: Future>(f: F) -> F::Output {
+        f.now_or_never().expect("future completes without blocking")
+    }
+
+    #[test]
+    fn compact_size() {
+        for value in [0, 1, 252, 253, 65535, 65536, 33554432] {
+            let mut expected = vec![];
+            CompactSize::write(&mut expected, value).unwrap();
+
+            let mut data = vec![];
+            run(CompactSize::write_async(&mut data, value)).unwrap();
+            assert_eq!(data, expected);
+            assert_eq!(
+                run(CompactSize::read_async(&data[..])).unwrap(),
+                value as u64
+            );
+        }
+
+        // Non-canonical and truncated encodings are rejected.
+        assert!(run(CompactSize::read_async(&[253, 252, 0][..])).is_err());
+        assert!(run(CompactSize::read_async(&[254, 1, 0][..])).is_err());
+        assert!(run(CompactSize::read_async(&[254, 1, 0, 0, 2][..])).is_err());
+    }
+
+    #[test]
+    fn vector() {
+        for value in [vec![], vec![0], vec![5; 8], vec![7; 260]] {
+            let mut expected = vec![];
+            Vector::write(&mut expected, &value, |w, e| w.write_u8(*e)).unwrap();
+
+            let mut data = vec![];
+            run(Vector::write_async(&mut data, &value, |w, e| {
+                async move { w.write_all(&[*e]).await }.boxed()
+            }))
+            .unwrap();
+            assert_eq!(data, expected);
+
+            let decoded = run(Vector::read_async(&data[..], |r| {
+                async move {
+                    let mut b = [0; 1];
+                    r.read_exact(&mut b).await?;
+                    Ok(b[0])
+                }
+                .boxed()
+            }))
+            .unwrap();
+            assert_eq!(decoded, value);
+        }
+    }
+
+    #[test]
+    fn optional() {
+        for value in [None, Some(0), Some(5)] {
+            let mut expected = vec![];
+            Optional::write(&mut expected, value, |w, e| w.write_u8(e)).unwrap();
+
+            let mut data = vec![];
+            run(Optional::write_async(&mut data, value, |w, e| async move {
+                w.write_all(&[e]).await
+            }))
+            .unwrap();
+            assert_eq!(data, expected);
+
+            let decoded = run(Optional::read_async(&data[..], |mut r| async move {
+                let mut b = [0; 1];
+                r.read_exact(&mut b).await?;
+                Ok(b[0])
+            }))
+            .unwrap();
+            assert_eq!(decoded, value);
+        }
+
+        // Invalid flag bytes are rejected.
+        assert!(run(Optional::read_async(&[2][..], |_| async { Ok(0) })).is_err());
+    }
+}
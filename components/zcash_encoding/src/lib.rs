@@ -2,7 +2,12 @@
 //!
 //! `zcash_encoding` is a library that provides common encoding and decoding operations
 //! for stable binary encodings used throughout the Zcash ecosystem.
+//!
+//! When the `async` feature flag is enabled, each of [`CompactSize`], [`Vector`], and
+//! [`Optional`] additionally provides functions that operate on asynchronous readers and
+//! writers.
 
+#![cfg_attr(docsrs, feature(doc_cfg))]
 // Catch documentation errors caused by code changes.
 #![deny(rustdoc::broken_intra_doc_links)]
 #![deny(missing_docs)]
@@ -13,6 +18,9 @@ use nonempty::NonEmpty;
 use std::io::{self, Read, Write};
 use std::iter::FromIterator;
 
+#[cfg(feature = "async")]
+mod async_io;
+
 /// The maximum allowed value representable as a `[CompactSize]`
 pub const MAX_COMPACT_SIZE: u32 = 0x02000000;
 
@@ -75,6 +83,16 @@ impl CompactSize {
         })
     }
 
+    /// Returns the number of bytes needed to encode the given size in compact form.
+    pub fn serialized_size(size: usize) -> usize {
+        match size {
+            s if s < 253 => 1,
+            s if s <= 0xFFFF => 3,
+            s if s <= 0xFFFFFFFF => 5,
+            _ => 9,
+        }
+    }
+
     /// Writes the provided `usize` value to the provided Writer in compact form.
     pub fn write<W: Write>(mut writer: W, size: usize) -> io::Result<()> {
         match size {
@@ -46,6 +46,40 @@ impl PoolType {
     pub const TRANSPARENT: PoolType = PoolType::Transparent;
     pub const SAPLING: PoolType = PoolType::Shielded(ShieldedProtocol::Sapling);
     pub const ORCHARD: PoolType = PoolType::Shielded(ShieldedProtocol::Orchard);
+
+    /// Returns the stable numeric code that identifies this pool.
+    ///
+    /// | Pool        | Code |
+    /// |-------------|------|
+    /// | Transparent | 0    |
+    /// | Sapling     | 2    |
+    /// | Orchard     | 3    |
+    ///
+    /// These codes are used wherever a pool is identified by a number outside of a protocol
+    /// message, for example in the `pool` and `output_pool` columns of the views exposed by
+    /// `zcash_client_sqlite`. They coincide with the [ZIP 316] typecodes of the corresponding
+    /// unified address receivers, with the transparent pool using the P2PKH typecode. They
+    /// will not change; any new pool will be assigned a new code.
+    ///
+    /// [ZIP 316]: https://zips.z.cash/zip-0316#encoding-of-unified-addresses
+    pub const fn code(self) -> u32 {
+        match self {
+            PoolType::Transparent => 0,
+            PoolType::Shielded(ShieldedProtocol::Sapling) => 2,
+            PoolType::Shielded(ShieldedProtocol::Orchard) => 3,
+        }
+    }
+
+    /// Returns the pool identified by the given [code](Self::code), or `None` if the code
+    /// does not identify a known pool.
+    pub const fn from_code(code: u32) -> Option<PoolType> {
+        match code {
+            0 => Some(PoolType::Transparent),
+            2 => Some(PoolType::Shielded(ShieldedProtocol::Sapling)),
+            3 => Some(PoolType::Shielded(ShieldedProtocol::Orchard)),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for PoolType {
@@ -57,3 +91,17 @@ impl fmt::Display for PoolType {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::PoolType;
+
+    #[test]
+    fn pool_code_round_trip() {
+        for pool in [PoolType::TRANSPARENT, PoolType::SAPLING, PoolType::ORCHARD] {
+            assert_eq!(PoolType::from_code(pool.code()), Some(pool));
+        }
+        assert_eq!(PoolType::from_code(1), None);
+        assert_eq!(PoolType::from_code(4), None);
+    }
+}
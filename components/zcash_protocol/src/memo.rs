@@ -46,6 +46,10 @@ impl fmt::Display for Error {
 impl error::Error for Error {}
 
 /// The unencrypted memo bytes received alongside a shielded note in a Zcash transaction.
+///
+/// When the `serde` feature flag is enabled, `MemoBytes` is serialized as a lowercase hex
+/// string encoding the memo with its trailing null padding removed (see
+/// [`MemoBytes::as_slice`]).
 #[derive(Clone)]
 pub struct MemoBytes(pub(crate) Box<[u8; 512]>);
 
@@ -124,6 +128,24 @@ impl MemoBytes {
     }
 }
 
+#[cfg(feature = "serde")]
+impl serde::Serialize for MemoBytes {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(self.as_slice()))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for MemoBytes {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error as _;
+
+        let encoded = String::deserialize(deserializer)?;
+        let bytes = hex::decode(encoded).map_err(D::Error::custom)?;
+        MemoBytes::from_bytes(&bytes).map_err(D::Error::custom)
+    }
+}
+
 /// Type-safe wrapper around String to enforce memo length requirements.
 #[derive(Clone, PartialEq, Eq)]
 pub struct TextMemo(String);
@@ -144,7 +166,15 @@ impl Deref for TextMemo {
 }
 
 /// An unencrypted memo received alongside a shielded note in a Zcash transaction.
+///
+/// When the `serde` feature flag is enabled, a `Memo` is serialized using the encoding of
+/// its corresponding [`MemoBytes`].
 #[derive(Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "MemoBytes", into = "MemoBytes")
+)]
 pub enum Memo {
     /// An empty memo field.
     Empty,
@@ -419,4 +449,25 @@ mod tests {
         assert_eq!(encoded[1..], bytes[..]);
         assert_eq!(MemoBytes::from_bytes(encoded).unwrap().try_into(), Ok(memo));
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let memo = Memo::from_str("zcash").unwrap();
+        let json = serde_json::to_string(&memo).unwrap();
+        assert_eq!(json, "\"7a63617368\"");
+        assert_eq!(serde_json::from_str::<Memo>(&json).unwrap(), memo);
+
+        let json = serde_json::to_string(&MemoBytes::empty()).unwrap();
+        assert_eq!(json, "\"f6\"");
+        assert_eq!(
+            serde_json::from_str::<MemoBytes>(&json).unwrap(),
+            MemoBytes::empty()
+        );
+        assert_eq!(serde_json::from_str::<Memo>(&json).unwrap(), Memo::Empty);
+
+        let too_long = format!("\"{}\"", "00".repeat(513));
+        assert!(serde_json::from_str::<MemoBytes>(&too_long).is_err());
+        assert!(serde_json::from_str::<MemoBytes>("\"not hex\"").is_err());
+    }
 }
@@ -16,8 +16,16 @@ pub const MAX_BALANCE: i64 = MAX_MONEY as i64;
 /// and this is preserved as an invariant internally. (A [`Transaction`] containing serialized
 /// invalid ZatBalances would also be rejected by the network consensus rules.)
 ///
+/// When the `serde` feature flag is enabled, a ZatBalance is serialized as a signed integer
+/// number of zatoshis; deserialization fails if the value is outside the valid range.
+///
 /// [`Transaction`]: https://docs.rs/zcash_primitives/latest/zcash_primitives/transaction/struct.Transaction.html
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Ord)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "i64", into = "i64")
+)]
 pub struct ZatBalance(i64);
 
 memuse::impl_no_dynamic_usage!(ZatBalance);
@@ -226,7 +234,15 @@ impl Mul<usize> for ZatBalance {
 ///
 /// A Zatoshis can only be constructed from an integer that is within the valid monetary
 /// range of `{0..MAX_MONEY}` (where `MAX_MONEY` = 21,000,000 × 10⁸ zatoshis).
+///
+/// When the `serde` feature flag is enabled, a Zatoshis is serialized as an unsigned integer
+/// number of zatoshis; deserialization fails if the value is outside the valid range.
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Ord)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "u64", into = "u64")
+)]
 pub struct Zatoshis(u64);
 
 impl Zatoshis {
@@ -518,4 +534,23 @@ mod tests {
         let v = ZatBalance(-MAX_BALANCE);
         assert_eq!(v - ZatBalance(1), None)
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        use super::Zatoshis;
+
+        let balance = ZatBalance(-MAX_BALANCE);
+        let json = serde_json::to_string(&balance).unwrap();
+        assert_eq!(json, "-2100000000000000");
+        assert_eq!(serde_json::from_str::<ZatBalance>(&json).unwrap(), balance);
+        assert!(serde_json::from_str::<ZatBalance>("-2100000000000001").is_err());
+
+        let value = Zatoshis(12345);
+        let json = serde_json::to_string(&value).unwrap();
+        assert_eq!(json, "12345");
+        assert_eq!(serde_json::from_str::<Zatoshis>(&json).unwrap(), value);
+        assert!(serde_json::from_str::<Zatoshis>("2100000000000001").is_err());
+        assert!(serde_json::from_str::<Zatoshis>("-1").is_err());
+    }
 }
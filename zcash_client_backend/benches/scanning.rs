@@ -0,0 +1,229 @@
+//! Benchmarks of compact block scanning and of note commitment tree witness updates.
+//!
+//! The blocks and note commitments are generated from a fixed seed, so that every run of
+//! these benchmarks operates on the same dataset.
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use group::{ff::PrimeField, GroupEncoding};
+use incrementalmerkletree::{Position, Retention};
+use rand_core::{RngCore, SeedableRng};
+use rand_xorshift::XorShiftRng;
+use sapling::{
+    note_encryption::{sapling_note_encryption, SaplingDomain},
+    util::generate_random_rseed,
+    value::NoteValue,
+    zip32::DiversifiableFullViewingKey,
+    NOTE_COMMITMENT_TREE_DEPTH,
+};
+use shardtree::{store::memory::MemoryShardStore, ShardTree};
+use zcash_client_backend::{
+    data_api::SAPLING_SHARD_HEIGHT,
+    keys::UnifiedSpendingKey,
+    proto::compact_formats::{ChainMetadata, CompactBlock, CompactSaplingOutput, CompactTx},
+    scanning::{scan_block, Nullifiers, ScanningKeys},
+};
+use zcash_note_encryption::{Domain, COMPACT_NOTE_SIZE};
+use zcash_primitives::{
+    consensus::{BlockHeight, Network, NetworkUpgrade, Parameters},
+    memo::MemoBytes,
+    transaction::components::sapling::zip212_enforcement,
+    zip32::AccountId,
+};
+
+#[cfg(unix)]
+use pprof::criterion::{Output, PProfProfiler};
+
+const SEED: [u8; 16] = [
+    0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06, 0xbc, 0xe5,
+];
+
+/// Constructs a compact block containing `output_count` single-output transactions, one in
+/// every `ours_interval` of which pays `ours`, while the rest pay `theirs`.
+fn synthetic_block(
+    rng: &mut XorShiftRng,
+    height: BlockHeight,
+    output_count: usize,
+    ours_interval: usize,
+    ours: &DiversifiableFullViewingKey,
+    theirs: &DiversifiableFullViewingKey,
+) -> CompactBlock {
+    let network = Network::TestNetwork;
+    let zip212_enforcement = zip212_enforcement(&network, height);
+
+    let vtx = (0..output_count)
+        .map(|i| {
+            let dfvk = if i % ours_interval == 0 { ours } else { theirs };
+            let rseed = generate_random_rseed(zip212_enforcement, &mut *rng);
+            let note = sapling::Note::from_parts(
+                dfvk.default_address().1,
+                NoteValue::from_raw(10000),
+                rseed,
+            );
+            let encryptor = sapling_note_encryption(
+                Some(dfvk.fvk().ovk),
+                note.clone(),
+                *MemoBytes::empty().as_array(),
+                &mut *rng,
+            );
+
+            let mut hash = vec![0; 32];
+            rng.fill_bytes(&mut hash);
+            CompactTx {
+                index: i as u64,
+                hash,
+                outputs: vec![CompactSaplingOutput {
+                    cmu: note.cmu().to_bytes().to_vec(),
+                    ephemeral_key: SaplingDomain::epk_bytes(encryptor.epk()).0.to_vec(),
+                    ciphertext: encryptor.encrypt_note_plaintext().as_ref()[..COMPACT_NOTE_SIZE]
+                        .to_vec(),
+                }],
+                ..Default::default()
+            }
+        })
+        .collect();
+
+    let mut hash = vec![0; 32];
+    rng.fill_bytes(&mut hash);
+    CompactBlock {
+        height: height.into(),
+        hash,
+        prev_hash: vec![0; 32],
+        vtx,
+        chain_metadata: Some(ChainMetadata {
+            sapling_commitment_tree_size: output_count as u32,
+            orchard_commitment_tree_size: 0,
+        }),
+        ..Default::default()
+    }
+}
+
+fn bench_trial_decryption(c: &mut Criterion) {
+    let network = Network::TestNetwork;
+    let height = network
+        .activation_height(NetworkUpgrade::Canopy)
+        .expect("Canopy is active on testnet");
+    let mut rng = XorShiftRng::from_seed(SEED);
+
+    let ufvk = UnifiedSpendingKey::from_seed(&network, &[0; 32], AccountId::ZERO)
+        .expect("valid seed")
+        .to_unified_full_viewing_key();
+    let ours = ufvk.sapling().expect("USK has a Sapling key").clone();
+    let theirs = UnifiedSpendingKey::from_seed(&network, &[1; 32], AccountId::ZERO)
+        .expect("valid seed")
+        .to_unified_full_viewing_key()
+        .sapling()
+        .expect("USK has a Sapling key")
+        .clone();
+    let scanning_keys = ScanningKeys::from_account_ufvks([(AccountId::ZERO, ufvk)]);
+    let nullifiers = Nullifiers::empty();
+
+    let mut group = c.benchmark_group("scan-block");
+    for output_count in [10, 100, 1000] {
+        let block = synthetic_block(&mut rng, height, output_count, 100, &ours, &theirs);
+        group.throughput(Throughput::Elements(output_count as u64));
+        group.bench_with_input(
+            BenchmarkId::new("sapling-outputs", output_count),
+            &block,
+            |b, block| {
+                b.iter_batched(
+                    || block.clone(),
+                    |block| {
+                        scan_block(&network, block, &scanning_keys, &nullifiers, None)
+                            .expect("synthetic block is valid")
+                    },
+                    BatchSize::SmallInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+type SaplingTree = ShardTree<
+    MemoryShardStore<sapling::Node, BlockHeight>,
+    { NOTE_COMMITMENT_TREE_DEPTH },
+    SAPLING_SHARD_HEIGHT,
+>;
+
+/// Returns `count` note commitments, every `mark_interval`th of which is marked for witnessing,
+/// and the last of which is checkpointed.
+fn synthetic_commitments(
+    rng: &mut XorShiftRng,
+    count: usize,
+    mark_interval: usize,
+) -> Vec<(sapling::Node, Retention<BlockHeight>)> {
+    (0..count)
+        .map(|i| {
+            let cmu = sapling::note::ExtractedNoteCommitment::from_bytes(
+                &bls12_381::Scalar::from(rng.next_u64()).to_repr(),
+            )
+            .expect("a canonical scalar encoding is a valid note commitment");
+            let retention = if i + 1 == count {
+                Retention::Checkpoint {
+                    id: BlockHeight::from(1),
+                    is_marked: i % mark_interval == 0,
+                }
+            } else if i % mark_interval == 0 {
+                Retention::Marked
+            } else {
+                Retention::Ephemeral
+            };
+            (sapling::Node::from_cmu(&cmu), retention)
+        })
+        .collect()
+}
+
+fn bench_witness_updates(c: &mut Criterion) {
+    let mut rng = XorShiftRng::from_seed(SEED);
+    let mark_interval = 100;
+
+    let mut group = c.benchmark_group("sapling-witnesses");
+    for count in [1000, 10000] {
+        let commitments = synthetic_commitments(&mut rng, count, mark_interval);
+        group.throughput(Throughput::Elements(count as u64));
+
+        group.bench_with_input(
+            BenchmarkId::new("insert", count),
+            &commitments,
+            |b, commitments| {
+                b.iter_batched(
+                    || {
+                        (
+                            SaplingTree::new(MemoryShardStore::empty(), 100),
+                            commitments.clone(),
+                        )
+                    },
+                    |(mut tree, commitments)| {
+                        tree.batch_insert(Position::from(0), commitments.into_iter())
+                            .expect("tree has capacity")
+                    },
+                    BatchSize::SmallInput,
+                )
+            },
+        );
+
+        let mut tree = SaplingTree::new(MemoryShardStore::empty(), 100);
+        tree.batch_insert(Position::from(0), commitments.into_iter())
+            .expect("tree has capacity");
+        let checkpoint = BlockHeight::from(1);
+        group.bench_function(BenchmarkId::new("witness-all", count), |b| {
+            b.iter(|| {
+                for position in (0..count).step_by(mark_interval) {
+                    tree.witness_at_checkpoint_id(Position::from(position as u64), &checkpoint)
+                        .expect("position is marked");
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+#[cfg(unix)]
+criterion_group! {
+    name = benches;
+    config = Criterion::default().with_profiler(PProfProfiler::new(100, Output::Flamegraph(None)));
+    targets = bench_trial_decryption, bench_witness_updates
+}
+#[cfg(not(unix))]
+criterion_group!(benches, bench_trial_decryption, bench_witness_updates);
+criterion_main!(benches);
@@ -0,0 +1,255 @@
+//! Signatures that prove knowledge of the incoming viewing key for a shielded address.
+//!
+//! A Sapling payment address `(d, pk_d)` satisfies `pk_d = [ivk] g_d`, where `g_d` is derived
+//! from the diversifier `d` and `ivk` is the incoming viewing key for the scope under which
+//! the address was derived. An address signature is a Schnorr signature over the base `g_d`
+//! with public key `pk_d`; producing one requires knowledge of `ivk`, and verifying one
+//! requires only the address and the signed message.
+//!
+//! An address signature is therefore a viewing-key proof, not a proof of spend authority.
+//! Anyone holding an incoming or full viewing key for the address, such as an auditor or a
+//! watch-only wallet, can produce one, and it does not show that the signer can spend funds
+//! received at the address.
+//!
+//! Nonces are derived deterministically from the signing key, the address, and the message,
+//! so signing does not require a source of randomness.
+//!
+//! Only Sapling receivers are currently supported. A Unified Address can be used if it
+//! contains a Sapling receiver and no Orchard receiver; Unified Addresses with an Orchard
+//! receiver are rejected, because a signature by the Sapling receiver alone would say nothing
+//! about who controls the Orchard receiver.
+
+use std::fmt;
+
+use blake2b_simd::Params as Blake2bParams;
+use group::{ff::PrimeField, GroupEncoding};
+use sapling::PaymentAddress;
+
+use crate::{address::Address, keys::UnifiedSpendingKey};
+
+/// The personalization used to derive signature nonces.
+const NONCE_PERSONALIZATION: &[u8; 16] = b"Zcash_AddrSigNnc";
+
+/// The personalization used to derive signature challenges.
+const CHALLENGE_PERSONALIZATION: &[u8; 16] = b"Zcash_AddrSigHsh";
+
+/// Errors that can occur in the creation or verification of an address signature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The address does not contain a receiver type that supports message signing.
+    UnsupportedAddress,
+    /// The address is a Unified Address with an Orchard receiver, which is not yet supported.
+    OrchardNotSupported,
+    /// The address was not derived from the provided spending key.
+    AddressNotRecognized,
+    /// The signature is not valid for the given address and message.
+    InvalidSignature,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::UnsupportedAddress => write!(
+                f,
+                "Message signing is only supported for addresses with a Sapling receiver."
+            ),
+            Error::OrchardNotSupported => write!(
+                f,
+                "Message signing is not yet supported for addresses with an Orchard receiver."
+            ),
+            Error::AddressNotRecognized => write!(
+                f,
+                "The address was not derived from the provided spending key."
+            ),
+            Error::InvalidSignature => write!(f, "The address signature is invalid."),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// A signature proving knowledge of the incoming viewing key for a shielded address.
+#[derive(Clone, PartialEq, Eq)]
+pub struct AddressSignature([u8; 64]);
+
+impl fmt::Debug for AddressSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "AddressSignature({})", hex::encode(self.0))
+    }
+}
+
+impl AddressSignature {
+    /// Parses an address signature from its byte encoding.
+    pub fn from_bytes(bytes: [u8; 64]) -> Self {
+        AddressSignature(bytes)
+    }
+
+    /// Returns the byte encoding of this signature.
+    pub fn to_bytes(&self) -> [u8; 64] {
+        self.0
+    }
+}
+
+/// Signs `msg` with the incoming viewing key for `address`, which must have been derived from
+/// `usk`.
+///
+/// Both external and internal (change) addresses of the spending key can be used. Only the
+/// incoming viewing key is used; see the [module documentation](self) for what the resulting
+/// signature does and does not prove.
+pub fn sign_message(
+    usk: &UnifiedSpendingKey,
+    address: &Address,
+    msg: &[u8],
+) -> Result<AddressSignature, Error> {
+    let addr = sapling_receiver(address)?;
+    let dfvk = usk.sapling().to_diversifiable_full_viewing_key();
+    let (_, scope) = dfvk
+        .decrypt_diversifier(addr)
+        .ok_or(Error::AddressNotRecognized)?;
+    let ivk = dfvk.to_ivk(scope).0;
+    let g_d = addr.diversifier().g_d().ok_or(Error::UnsupportedAddress)?;
+
+    let r = hash_to_scalar(
+        NONCE_PERSONALIZATION,
+        &[&ivk.to_repr(), &addr.to_bytes(), msg],
+    );
+    let r_bytes = (g_d * r).to_bytes();
+    let c = challenge(addr, &r_bytes, msg);
+    let s = r + c * ivk;
+
+    let mut sig = [0; 64];
+    sig[..32].copy_from_slice(&r_bytes);
+    sig[32..].copy_from_slice(&s.to_repr());
+    Ok(AddressSignature(sig))
+}
+
+/// Verifies that `signature` is a valid signature over `msg` by a holder of the incoming
+/// viewing key for `address`.
+pub fn verify_address_signature(
+    address: &Address,
+    msg: &[u8],
+    signature: &AddressSignature,
+) -> Result<(), Error> {
+    let addr = sapling_receiver(address)?;
+    let g_d = addr.diversifier().g_d().ok_or(Error::UnsupportedAddress)?;
+    let pk_d = Option::<jubjub::SubgroupPoint>::from(jubjub::SubgroupPoint::from_bytes(
+        addr.to_bytes()[11..].try_into().unwrap(),
+    ))
+    .ok_or(Error::UnsupportedAddress)?;
+
+    let r_bytes: [u8; 32] = signature.0[..32].try_into().unwrap();
+    let r = Option::<jubjub::SubgroupPoint>::from(jubjub::SubgroupPoint::from_bytes(&r_bytes))
+        .ok_or(Error::InvalidSignature)?;
+    let s =
+        Option::<jubjub::Fr>::from(jubjub::Fr::from_repr(signature.0[32..].try_into().unwrap()))
+            .ok_or(Error::InvalidSignature)?;
+    let c = challenge(addr, &r_bytes, msg);
+
+    if g_d * s == r + pk_d * c {
+        Ok(())
+    } else {
+        Err(Error::InvalidSignature)
+    }
+}
+
+fn sapling_receiver(address: &Address) -> Result<&PaymentAddress, Error> {
+    match address {
+        Address::Sapling(addr) => Ok(addr),
+        Address::Unified(ua) if ua.has_orchard() => Err(Error::OrchardNotSupported),
+        Address::Unified(ua) => ua.sapling().ok_or(Error::UnsupportedAddress),
+        Address::Transparent(_) => Err(Error::UnsupportedAddress),
+    }
+}
+
+fn challenge(addr: &PaymentAddress, r_bytes: &[u8; 32], msg: &[u8]) -> jubjub::Fr {
+    hash_to_scalar(CHALLENGE_PERSONALIZATION, &[&addr.to_bytes(), r_bytes, msg])
+}
+
+fn hash_to_scalar(personalization: &[u8; 16], parts: &[&[u8]]) -> jubjub::Fr {
+    let mut state = Blake2bParams::new()
+        .hash_length(64)
+        .personal(personalization)
+        .to_state();
+    for part in parts {
+        state.update(part);
+    }
+    jubjub::Fr::from_bytes_wide(state.finalize().as_array())
+}
+
+#[cfg(test)]
+mod tests {
+    use zcash_primitives::consensus::MAIN_NETWORK;
+
+    use super::{sign_message, verify_address_signature, Error};
+    use crate::{address::Address, keys::UnifiedSpendingKey};
+
+    #[cfg(feature = "orchard")]
+    use crate::address::UnifiedAddress;
+
+    #[test]
+    fn sign_and_verify() {
+        let usk =
+            UnifiedSpendingKey::from_seed(&MAIN_NETWORK, &[0; 32], zip32::AccountId::ZERO).unwrap();
+        let dfvk = usk.sapling().to_diversifiable_full_viewing_key();
+        let (_, addr) = dfvk.default_address();
+        let address = Address::Sapling(addr);
+        let change = Address::Sapling(dfvk.change_address().1);
+
+        let sig = sign_message(&usk, &address, b"hello").unwrap();
+        assert_eq!(verify_address_signature(&address, b"hello", &sig), Ok(()));
+        assert_eq!(
+            verify_address_signature(&address, b"goodbye", &sig),
+            Err(Error::InvalidSignature)
+        );
+        assert_eq!(
+            verify_address_signature(&change, b"hello", &sig),
+            Err(Error::InvalidSignature)
+        );
+
+        let change_sig = sign_message(&usk, &change, b"hello").unwrap();
+        assert_eq!(
+            verify_address_signature(&change, b"hello", &change_sig),
+            Ok(())
+        );
+
+        let other_usk =
+            UnifiedSpendingKey::from_seed(&MAIN_NETWORK, &[1; 32], zip32::AccountId::ZERO).unwrap();
+        assert_eq!(
+            sign_message(&other_usk, &address, b"hello"),
+            Err(Error::AddressNotRecognized)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "orchard")]
+    fn orchard_receivers_are_rejected() {
+        let usk =
+            UnifiedSpendingKey::from_seed(&MAIN_NETWORK, &[0; 32], zip32::AccountId::ZERO).unwrap();
+        let (_, sapling) = usk
+            .sapling()
+            .to_diversifiable_full_viewing_key()
+            .default_address();
+        let orchard = orchard::keys::FullViewingKey::from(usk.orchard())
+            .address_at(0u32, orchard::keys::Scope::External);
+
+        let sapling_only =
+            Address::Unified(UnifiedAddress::from_receivers(None, Some(sapling), None).unwrap());
+        let sig = sign_message(&usk, &sapling_only, b"hello").unwrap();
+        assert_eq!(
+            verify_address_signature(&sapling_only, b"hello", &sig),
+            Ok(())
+        );
+
+        let with_orchard = Address::Unified(
+            UnifiedAddress::from_receivers(Some(orchard), Some(sapling), None).unwrap(),
+        );
+        assert_eq!(
+            sign_message(&usk, &with_orchard, b"hello"),
+            Err(Error::OrchardNotSupported)
+        );
+        assert_eq!(
+            verify_address_signature(&with_orchard, b"hello", &sig),
+            Err(Error::OrchardNotSupported)
+        );
+    }
+}
@@ -0,0 +1,168 @@
+//! Reporting of the CPU-specific implementations used in hot cryptographic paths.
+//!
+//! Trial decryption of notes is dominated by ChaCha20 (from the `chacha20` crate), and the
+//! derivation of note commitments, nullifiers, and transaction identifiers is dominated by
+//! BLAKE2b (from the `blake2b_simd` crate). Both crates detect the features of the CPU at
+//! runtime and dispatch to vectorized implementations where they are available:
+//!
+//! - On x86 and x86-64, BLAKE2b uses AVX2 or SSE4.1, and ChaCha20 uses AVX2 or SSE2.
+//! - On other architectures, both use their portable implementations. In particular, the
+//!   versions of these crates used here do not provide NEON implementations that can be
+//!   enabled by a feature flag, so none is forwarded from this crate.
+//!
+//! No configuration is required to obtain the vectorized implementations. [`capabilities`]
+//! reports the CPU features that were detected and the implementations that those crates
+//! select as a result, so that integrators can verify what is in use on a given device.
+
+use std::fmt;
+
+/// An implementation of the BLAKE2b hash function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Blake2bBackend {
+    /// The AVX2 implementation.
+    Avx2,
+    /// The SSE4.1 implementation.
+    Sse41,
+    /// The portable implementation.
+    Portable,
+}
+
+/// An implementation of the ChaCha20 stream cipher used in note decryption.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChaCha20Backend {
+    /// The AVX2 implementation.
+    Avx2,
+    /// The SSE2 implementation.
+    Sse2,
+    /// The portable implementation.
+    Portable,
+}
+
+/// The CPU features detected at runtime, and the implementations selected as a result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    avx2: bool,
+    sse41: bool,
+    sse2: bool,
+    neon: bool,
+    blake2b: Blake2bBackend,
+    chacha20: ChaCha20Backend,
+}
+
+impl Capabilities {
+    /// Returns whether the CPU supports AVX2.
+    pub fn avx2(&self) -> bool {
+        self.avx2
+    }
+
+    /// Returns whether the CPU supports SSE4.1.
+    pub fn sse41(&self) -> bool {
+        self.sse41
+    }
+
+    /// Returns whether the CPU supports SSE2.
+    pub fn sse2(&self) -> bool {
+        self.sse2
+    }
+
+    /// Returns whether the CPU supports NEON.
+    pub fn neon(&self) -> bool {
+        self.neon
+    }
+
+    /// Returns the implementation of BLAKE2b that is in use.
+    pub fn blake2b(&self) -> Blake2bBackend {
+        self.blake2b
+    }
+
+    /// Returns the implementation of ChaCha20 that is in use for note decryption.
+    pub fn chacha20(&self) -> ChaCha20Backend {
+        self.chacha20
+    }
+}
+
+impl fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let features = [
+            ("avx2", self.avx2),
+            ("sse4.1", self.sse41),
+            ("sse2", self.sse2),
+            ("neon", self.neon),
+        ]
+        .into_iter()
+        .filter_map(|(name, detected)| detected.then_some(name))
+        .collect::<Vec<_>>();
+
+        write!(
+            f,
+            "CPU features: [{}]; BLAKE2b: {:?}; ChaCha20: {:?}",
+            features.join(", "),
+            self.blake2b,
+            self.chacha20
+        )
+    }
+}
+
+/// Detects the features of the CPU, and reports the implementations that are selected as a
+/// result in note decryption and hashing.
+pub fn capabilities() -> Capabilities {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    let (avx2, sse41, sse2) = (
+        is_x86_feature_detected!("avx2"),
+        is_x86_feature_detected!("sse4.1"),
+        is_x86_feature_detected!("sse2"),
+    );
+    #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+    let (avx2, sse41, sse2) = (false, false, false);
+
+    #[cfg(target_arch = "aarch64")]
+    let neon = std::arch::is_aarch64_feature_detected!("neon");
+    #[cfg(not(target_arch = "aarch64"))]
+    let neon = false;
+
+    // These follow the selection logic of `blake2b_simd` and `chacha20` respectively.
+    let blake2b = if avx2 {
+        Blake2bBackend::Avx2
+    } else if sse41 {
+        Blake2bBackend::Sse41
+    } else {
+        Blake2bBackend::Portable
+    };
+    let chacha20 = if avx2 {
+        ChaCha20Backend::Avx2
+    } else if sse2 {
+        ChaCha20Backend::Sse2
+    } else {
+        ChaCha20Backend::Portable
+    };
+
+    Capabilities {
+        avx2,
+        sse41,
+        sse2,
+        neon,
+        blake2b,
+        chacha20,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{capabilities, Blake2bBackend, ChaCha20Backend};
+
+    #[test]
+    fn backends_match_detected_features() {
+        let caps = capabilities();
+        assert_eq!(caps.blake2b() == Blake2bBackend::Avx2, caps.avx2());
+        assert_eq!(caps.chacha20() == ChaCha20Backend::Avx2, caps.avx2());
+        if caps.blake2b() == Blake2bBackend::Sse41 {
+            assert!(caps.sse41());
+        }
+        if !(caps.avx2() || caps.sse41()) {
+            assert_eq!(caps.blake2b(), Blake2bBackend::Portable);
+        }
+
+        #[cfg(target_arch = "x86_64")]
+        assert!(caps.sse2());
+    }
+}
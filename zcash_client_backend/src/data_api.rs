@@ -61,21 +61,25 @@ use std::{
     hash::Hash,
     io,
     num::{NonZeroU32, TryFromIntError},
+    ops::Range,
 };
 
 use incrementalmerkletree::{frontier::Frontier, Retention};
 use nonempty::NonEmpty;
 use secrecy::SecretVec;
 use shardtree::{error::ShardTreeError, store::ShardStore, ShardTree};
-use zip32::fingerprint::SeedFingerprint;
+use zip32::{fingerprint::SeedFingerprint, DiversifierIndex, Scope};
 
 use self::{
+    audit::AuditBundle,
     chain::{ChainState, CommitmentTreeRoot},
     scanning::ScanRange,
+    scheduled::ScheduledPayment,
 };
 use crate::{
-    address::UnifiedAddress,
+    address::{Address, UnifiedAddress},
     decrypt::DecryptedOutput,
+    derivation::AccountDerivation,
     keys::{
         UnifiedAddressRequest, UnifiedFullViewingKey, UnifiedIncomingViewingKey, UnifiedSpendingKey,
     },
@@ -96,15 +100,20 @@ use zcash_primitives::{
 #[cfg(feature = "transparent-inputs")]
 use {
     crate::wallet::TransparentAddressMetadata,
-    zcash_primitives::{legacy::TransparentAddress, transaction::components::OutPoint},
+    zcash_primitives::{
+        legacy::TransparentAddress,
+        transaction::components::{OutPoint, TxOut},
+    },
 };
 
 #[cfg(any(test, feature = "test-dependencies"))]
 use zcash_primitives::consensus::NetworkUpgrade;
 
+pub mod audit;
 pub mod chain;
 pub mod error;
 pub mod scanning;
+pub mod scheduled;
 pub mod wallet;
 
 /// The height of subtree roots in the Sapling note commitment tree.
@@ -127,6 +136,39 @@ pub enum NullifierQuery {
     All,
 }
 
+/// A policy for ignoring shielded notes of very small value ("dust").
+///
+/// Anyone who knows a wallet's address can send it large numbers of tiny notes. Spending such
+/// notes costs more in fees than they are worth, and spending them together with the wallet's
+/// other notes may allow the sender to link the wallet's transactions. A [`DustFilter`] allows
+/// these notes to be excluded from note selection and from reported balances.
+///
+/// Notes received as change (that is, notes received under the internal key scope) are never
+/// treated as dust, because they cannot have been sent by a third party.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DustFilter {
+    /// Include all notes, regardless of their value.
+    #[default]
+    IncludeDust,
+    /// Ignore externally-received notes having value strictly less than the given amount.
+    MinNoteValue(NonNegativeAmount),
+}
+
+impl DustFilter {
+    /// Returns the minimum value of an externally-received note that this filter admits.
+    pub fn min_note_value(&self) -> NonNegativeAmount {
+        match self {
+            DustFilter::IncludeDust => NonNegativeAmount::ZERO,
+            DustFilter::MinNoteValue(value) => *value,
+        }
+    }
+
+    /// Returns whether a note having the given value should be ignored under this filter.
+    pub fn is_dust(&self, value: NonNegativeAmount, is_change: bool) -> bool {
+        !is_change && value < self.min_note_value()
+    }
+}
+
 /// Balance information for a value within a single pool in an account.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Balance {
@@ -164,12 +206,16 @@ impl Balance {
     /// Adds the specified value to the spendable total, checking for overflow.
     pub fn add_spendable_value(&mut self, value: NonNegativeAmount) -> Result<(), BalanceError> {
         self.check_total_adding(value)?;
-        self.spendable_value = (self.spendable_value + value).unwrap();
+        self.spendable_value = (self.spendable_value + value).ok_or(BalanceError::Overflow)?;
         Ok(())
     }
 
-    /// Returns the value in the account of shielded change notes that do not yet have sufficient
-    /// confirmations to be spendable.
+    /// Returns the value in the account of shielded change notes that are not yet spendable,
+    /// either because they do not yet have sufficient confirmations or because witnesses cannot
+    /// yet be constructed for them without additional scanning.
+    ///
+    /// Change notes are the outputs of the wallet's own transactions, so unlike value received
+    /// from other parties, they are not at risk of being withheld by a sender.
     pub fn change_pending_confirmation(&self) -> NonNegativeAmount {
         self.change_pending_confirmation
     }
@@ -180,13 +226,16 @@ impl Balance {
         value: NonNegativeAmount,
     ) -> Result<(), BalanceError> {
         self.check_total_adding(value)?;
-        self.change_pending_confirmation = (self.change_pending_confirmation + value).unwrap();
+        self.change_pending_confirmation =
+            (self.change_pending_confirmation + value).ok_or(BalanceError::Overflow)?;
         Ok(())
     }
 
-    /// Returns the value in the account of all remaining received notes that either do not have
-    /// sufficient confirmations to be spendable, or for which witnesses cannot yet be constructed
-    /// without additional scanning.
+    /// Returns the value in the account of notes received from other parties that either do not
+    /// have sufficient confirmations to be spendable, or for which witnesses cannot yet be
+    /// constructed without additional scanning.
+    ///
+    /// This does not include the value of change notes; see [`Self::change_pending_confirmation`].
     pub fn value_pending_spendability(&self) -> NonNegativeAmount {
         self.value_pending_spendability
     }
@@ -197,7 +246,8 @@ impl Balance {
         value: NonNegativeAmount,
     ) -> Result<(), BalanceError> {
         self.check_total_adding(value)?;
-        self.value_pending_spendability = (self.value_pending_spendability + value).unwrap();
+        self.value_pending_spendability =
+            (self.value_pending_spendability + value).ok_or(BalanceError::Overflow)?;
         Ok(())
     }
 
@@ -225,7 +275,13 @@ pub struct AccountBalance {
     /// possible operation on a transparent balance is to shield it, it is possible to create a
     /// zero-conf transaction to perform that shielding, and the resulting shielded notes will be
     /// subject to normal confirmation rules.
+    ///
+    /// This does not include the value of coinbase outputs that have not yet matured.
     unshielded: NonNegativeAmount,
+
+    /// The value of unspent transparent outputs of coinbase transactions belonging to the
+    /// account, which cannot be shielded until they have matured.
+    unshielded_pending_maturity: NonNegativeAmount,
 }
 
 impl AccountBalance {
@@ -234,10 +290,14 @@ impl AccountBalance {
         sapling_balance: Balance::ZERO,
         orchard_balance: Balance::ZERO,
         unshielded: NonNegativeAmount::ZERO,
+        unshielded_pending_maturity: NonNegativeAmount::ZERO,
     };
 
     fn check_total(&self) -> Result<NonNegativeAmount, BalanceError> {
-        (self.sapling_balance.total() + self.orchard_balance.total() + self.unshielded)
+        (self.sapling_balance.total()
+            + self.orchard_balance.total()
+            + self.unshielded
+            + self.unshielded_pending_maturity)
             .ok_or(BalanceError::Overflow)
     }
 
@@ -275,7 +335,11 @@ impl AccountBalance {
         Ok(result)
     }
 
-    /// Returns the total value of unspent transparent transaction outputs belonging to the wallet.
+    /// Returns the total value of unspent transparent transaction outputs belonging to the
+    /// wallet that may be shielded.
+    ///
+    /// This does not include the value of immature coinbase outputs; see
+    /// [`Self::unshielded_pending_maturity`].
     pub fn unshielded(&self) -> NonNegativeAmount {
         self.unshielded
     }
@@ -288,9 +352,33 @@ impl AccountBalance {
         Ok(())
     }
 
+    /// Returns the total value of unspent transparent outputs of coinbase transactions
+    /// belonging to the wallet that have not yet matured (see [`COINBASE_MATURITY`]), and so
+    /// cannot yet be shielded.
+    ///
+    /// [`COINBASE_MATURITY`]: crate::wallet::COINBASE_MATURITY
+    pub fn unshielded_pending_maturity(&self) -> NonNegativeAmount {
+        self.unshielded_pending_maturity
+    }
+
+    /// Adds the specified value to the total of immature coinbase outputs, checking for
+    /// overflow of the total account balance.
+    pub fn add_unshielded_pending_maturity_value(
+        &mut self,
+        value: NonNegativeAmount,
+    ) -> Result<(), BalanceError> {
+        self.unshielded_pending_maturity =
+            (self.unshielded_pending_maturity + value).ok_or(BalanceError::Overflow)?;
+        self.check_total()?;
+        Ok(())
+    }
+
     /// Returns the total value of funds belonging to the account.
     pub fn total(&self) -> NonNegativeAmount {
-        (self.sapling_balance.total() + self.orchard_balance.total() + self.unshielded)
+        (self.sapling_balance.total()
+            + self.orchard_balance.total()
+            + self.unshielded
+            + self.unshielded_pending_maturity)
             .expect("Account balance cannot overflow MAX_MONEY")
     }
 
@@ -301,16 +389,19 @@ impl AccountBalance {
             .expect("Account balance cannot overflow MAX_MONEY")
     }
 
-    /// Returns the total value of change and/or shielding transaction outputs that are awaiting
-    /// sufficient confirmations for spendability.
+    /// Returns the total value of change and/or shielding transaction outputs that are not yet
+    /// spendable, either because they are awaiting sufficient confirmations or because
+    /// additional scanning is required.
     pub fn change_pending_confirmation(&self) -> NonNegativeAmount {
         (self.sapling_balance.change_pending_confirmation
             + self.orchard_balance.change_pending_confirmation)
             .expect("Account balance cannot overflow MAX_MONEY")
     }
 
-    /// Returns the value of shielded funds that are not yet spendable because additional scanning
-    /// is required before it will be possible to derive witnesses for the associated notes.
+    /// Returns the value of shielded funds received from other parties that are not yet
+    /// spendable, either because they are awaiting sufficient confirmations or because
+    /// additional scanning is required before it will be possible to derive witnesses for the
+    /// associated notes.
     pub fn value_pending_spendability(&self) -> NonNegativeAmount {
         (self.sapling_balance.value_pending_spendability
             + self.orchard_balance.value_pending_spendability)
@@ -318,6 +409,140 @@ impl AccountBalance {
     }
 }
 
+/// The balance of an account as of the end of a given block, as returned by
+/// [`WalletRead::get_balance_at_height`].
+///
+/// The balance includes every output received by the account in a transaction mined at or below
+/// the height, that was not spent by a transaction mined at or below the height.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HistoricalBalance {
+    height: BlockHeight,
+    sapling: NonNegativeAmount,
+    orchard: NonNegativeAmount,
+    unshielded: NonNegativeAmount,
+}
+
+impl HistoricalBalance {
+    /// Constructs a new historical balance from its constituent parts.
+    pub fn from_parts(
+        height: BlockHeight,
+        sapling: NonNegativeAmount,
+        orchard: NonNegativeAmount,
+        unshielded: NonNegativeAmount,
+    ) -> Result<Self, BalanceError> {
+        (sapling + orchard + unshielded).ok_or(BalanceError::Overflow)?;
+        Ok(HistoricalBalance {
+            height,
+            sapling,
+            orchard,
+            unshielded,
+        })
+    }
+
+    /// Returns the height as of the end of which the balance was computed.
+    pub fn height(&self) -> BlockHeight {
+        self.height
+    }
+
+    /// Returns the value of Sapling outputs belonging to the account as of the height.
+    pub fn sapling_balance(&self) -> NonNegativeAmount {
+        self.sapling
+    }
+
+    /// Returns the value of Orchard outputs belonging to the account as of the height.
+    pub fn orchard_balance(&self) -> NonNegativeAmount {
+        self.orchard
+    }
+
+    /// Returns the value of transparent outputs belonging to the account as of the height.
+    pub fn unshielded(&self) -> NonNegativeAmount {
+        self.unshielded
+    }
+
+    /// Returns the total value of funds belonging to the account as of the height.
+    pub fn total(&self) -> NonNegativeAmount {
+        (self.sapling + self.orchard + self.unshielded)
+            .expect("Historical balance cannot overflow MAX_MONEY")
+    }
+}
+
+/// The length of the periods into which [`WalletRead::get_account_statistics`] divides the
+/// history of an account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StatisticsGranularity {
+    /// Calendar days, in UTC.
+    Day,
+    /// Calendar months, in UTC.
+    Month,
+}
+
+/// Aggregate statistics for the transactions of an account that were mined in blocks whose
+/// timestamps fall within a single period.
+///
+/// Each transaction is counted as received if it increased the balance of the account, and as
+/// sent if it decreased it. The value sent by a transaction excludes the fee paid by it, which
+/// is reported separately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeriodStatistics {
+    period_start: time::Date,
+    received_value: NonNegativeAmount,
+    received_count: u64,
+    sent_value: NonNegativeAmount,
+    sent_count: u64,
+    fees_paid: NonNegativeAmount,
+}
+
+impl PeriodStatistics {
+    /// Constructs statistics for the period beginning on the given date.
+    pub fn from_parts(
+        period_start: time::Date,
+        received_value: NonNegativeAmount,
+        received_count: u64,
+        sent_value: NonNegativeAmount,
+        sent_count: u64,
+        fees_paid: NonNegativeAmount,
+    ) -> Self {
+        PeriodStatistics {
+            period_start,
+            received_value,
+            received_count,
+            sent_value,
+            sent_count,
+            fees_paid,
+        }
+    }
+
+    /// Returns the first day of the period.
+    pub fn period_start(&self) -> time::Date {
+        self.period_start
+    }
+
+    /// Returns the total value received by the account in the period.
+    pub fn received_value(&self) -> NonNegativeAmount {
+        self.received_value
+    }
+
+    /// Returns the number of transactions in the period that increased the account's balance.
+    pub fn received_count(&self) -> u64 {
+        self.received_count
+    }
+
+    /// Returns the total value sent by the account in the period, excluding fees.
+    pub fn sent_value(&self) -> NonNegativeAmount {
+        self.sent_value
+    }
+
+    /// Returns the number of transactions in the period that decreased the account's balance.
+    pub fn sent_count(&self) -> u64 {
+        self.sent_count
+    }
+
+    /// Returns the total fees paid by the account's transactions in the period.
+    pub fn fees_paid(&self) -> NonNegativeAmount {
+        self.fees_paid
+    }
+}
+
 /// The kinds of accounts supported by `zcash_client_backend`.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum AccountSource {
@@ -331,6 +556,26 @@ pub enum AccountSource {
     Imported,
 }
 
+impl AccountSource {
+    /// Returns the derivation of the account's keys from its seed, or `None` if the account
+    /// was imported.
+    ///
+    /// The returned [`AccountDerivation`] provides the ZIP 32 and BIP 44 paths of the
+    /// account's keys and addresses, which can be used to check the account against other
+    /// wallets or hardware devices that hold the same seed.
+    pub fn derivation<P: zcash_primitives::consensus::Parameters>(
+        &self,
+        params: &P,
+    ) -> Option<AccountDerivation> {
+        match self {
+            AccountSource::Derived { account_index, .. } => {
+                Some(AccountDerivation::new(params, *account_index))
+            }
+            AccountSource::Imported => None,
+        }
+    }
+}
+
 /// A set of capabilities that a client account must provide.
 pub trait Account<AccountId: Copy> {
     /// Returns the unique identifier for the account.
@@ -767,6 +1012,24 @@ pub trait WalletRead {
         account: Self::AccountId,
     ) -> Result<Option<UnifiedAddress>, Self::Error>;
 
+    /// Returns usage information for each of the addresses that the wallet has generated for the
+    /// specified account, in order of increasing diversifier index.
+    ///
+    /// Returns an empty vector if the account identifier does not correspond to a known account.
+    fn address_usage(&self, account: Self::AccountId) -> Result<Vec<AddressUsage>, Self::Error>;
+
+    /// Returns the account and key scope from which the given address was derived, if it
+    /// belongs to any account in the wallet.
+    ///
+    /// This recognizes transparent, Sapling, and unified addresses, including addresses
+    /// derived using an account's internal (change) key scope. It may be used to attribute
+    /// outputs to accounts during scanning, or by applications to detect that an address
+    /// supplied by the user as a payment recipient in fact belongs to the user's own wallet.
+    fn find_account_for_address(
+        &self,
+        address: &Address,
+    ) -> Result<Option<(Self::AccountId, Scope)>, Self::Error>;
+
     /// Returns the birthday height for the given account, or an error if the account is not known
     /// to the wallet.
     fn get_account_birthday(&self, account: Self::AccountId) -> Result<BlockHeight, Self::Error>;
@@ -784,6 +1047,51 @@ pub trait WalletRead {
         min_confirmations: u32,
     ) -> Result<Option<WalletSummary<Self::AccountId>>, Self::Error>;
 
+    /// Returns the wallet balances and sync status for an account given the specified minimum
+    /// number of confirmations, excluding any shielded notes that are rejected by the given
+    /// [`DustFilter`]. Returns `Ok(None)` if the wallet has no balance data available.
+    fn get_wallet_summary_with_dust_filter(
+        &self,
+        min_confirmations: u32,
+        dust_filter: DustFilter,
+    ) -> Result<Option<WalletSummary<Self::AccountId>>, Self::Error>;
+
+    /// Returns the balance of the given account as of the end of the block at `height`.
+    ///
+    /// The balance is computed from the heights at which the account's outputs were received
+    /// and spent, rather than from the current state of the wallet, so that historical balances
+    /// can be reconstructed (for example, for accounting purposes) without rescanning the
+    /// chain. Returns `Ok(None)` if the wallet has not fully scanned the chain up to `height`,
+    /// in which case the balance could not be determined.
+    fn get_balance_at_height(
+        &self,
+        account: Self::AccountId,
+        height: BlockHeight,
+    ) -> Result<Option<HistoricalBalance>, Self::Error>;
+
+    /// Returns aggregate statistics for the mined transactions of the given account, bucketed
+    /// by the periods of the given granularity in which their blocks were mined.
+    ///
+    /// Periods are determined by block timestamps, and are returned in chronological order.
+    /// Periods in which the account had no mined transactions are omitted.
+    fn get_account_statistics(
+        &self,
+        account: Self::AccountId,
+        granularity: StatisticsGranularity,
+    ) -> Result<Vec<PeriodStatistics>, Self::Error>;
+
+    /// Returns the scheduled payments whose next payment is due at or before `now`, in order of
+    /// their next due times.
+    ///
+    /// Callers should convert each returned payment into a transaction request with
+    /// [`ScheduledPayment::to_transaction_request`], and record the payment with the wallet
+    /// once the transaction has been created, so that it is not returned again until its
+    /// following due time.
+    fn payments_due(
+        &self,
+        now: time::OffsetDateTime,
+    ) -> Result<Vec<ScheduledPayment<Self::AccountId>>, Self::Error>;
+
     /// Returns the height of the chain as known to the wallet as of the most recent call to
     /// [`WalletWrite::update_chain_tip`].
     ///
@@ -859,6 +1167,23 @@ pub trait WalletRead {
         &self,
     ) -> Result<HashMap<Self::AccountId, UnifiedFullViewingKey>, Self::Error>;
 
+    /// Returns the unified full viewing keys of the accounts whose notes should be detected
+    /// when scanning the blocks in the given range.
+    ///
+    /// Wallets may allow accounts that are no longer in active use to be excluded from
+    /// scanning, in order to avoid the cost of trial-decrypting every block with those
+    /// accounts' keys. Wallets that track scan progress separately for each account may also
+    /// omit the keys of accounts for which every block in the range has already been scanned,
+    /// so that adding an account does not require the blocks after its birthday to be
+    /// trial-decrypted again for the existing accounts. The default implementation returns the
+    /// keys of all accounts, as [`Self::get_unified_full_viewing_keys`] does.
+    fn get_unified_full_viewing_keys_for_scanning(
+        &self,
+        _range: &Range<BlockHeight>,
+    ) -> Result<HashMap<Self::AccountId, UnifiedFullViewingKey>, Self::Error> {
+        self.get_unified_full_viewing_keys()
+    }
+
     /// Returns the memo for a note.
     ///
     /// Returns `Ok(None)` if the note is known to the wallet but memo data has not yet been
@@ -869,6 +1194,30 @@ pub trait WalletRead {
     /// Returns a transaction.
     fn get_transaction(&self, txid: TxId) -> Result<Option<Transaction>, Self::Error>;
 
+    /// Returns the outstanding requests for data that the wallet needs from the network in
+    /// order to fully describe its transactions.
+    ///
+    /// For example, the wallet needs to learn whether the transactions that it created have
+    /// been mined, and it cannot compute the fee paid by a transaction that spends
+    /// transparent outputs that it did not receive until it has the transactions that
+    /// created those outputs. Callers should service each request as described by
+    /// [`TransactionDataRequest`]. A request is no longer returned once the wallet has the
+    /// data that it requires, so each transaction need only be retrieved once.
+    fn transaction_data_requests(&self) -> Result<Vec<TransactionDataRequest>, Self::Error>;
+
+    /// Returns an [`AuditBundle`] containing the decrypted shielded outputs that affected the
+    /// given account in transactions mined within the specified range of block heights.
+    ///
+    /// The resulting bundle can be provided to an auditor, who can check it against the
+    /// chain using [`AuditBundle::verify`] without needing access to a viewing key for the
+    /// account. Transactions that have not been mined, or for which the wallet does not have
+    /// the full transaction data, are not included.
+    fn export_account_audit_bundle(
+        &self,
+        account: Self::AccountId,
+        height_range: Range<BlockHeight>,
+    ) -> Result<AuditBundle, Self::Error>;
+
     /// Returns the nullifiers for Sapling notes that the wallet is tracking, along with their
     /// associated account IDs, that are either unspent or have not yet been confirmed as spent (in
     /// that a spending transaction known to the wallet has not yet been included in a block).
@@ -909,6 +1258,73 @@ pub trait WalletRead {
     ) -> Result<HashMap<TransparentAddress, NonNegativeAmount>, Self::Error> {
         Ok(HashMap::new())
     }
+
+    /// Returns the outpoints of the transparent outputs belonging to the wallet that are not
+    /// known to have been spent in a mined transaction, along with the accounts that received
+    /// them.
+    ///
+    /// These are used in block scanning to detect transactions that spend the wallet's
+    /// transparent outputs, when the compact block source provides transparent inputs.
+    #[cfg(feature = "transparent-inputs")]
+    fn get_unspent_transparent_outpoints(
+        &self,
+    ) -> Result<Vec<(Self::AccountId, OutPoint)>, Self::Error> {
+        Ok(vec![])
+    }
+}
+
+/// A request for transaction data that the wallet requires, and that must be retrieved from
+/// the network.
+///
+/// Requests are returned by [`WalletRead::transaction_data_requests`]. Separating the
+/// description of the data that a wallet needs from its retrieval allows a wallet backend to
+/// remain independent of the transport (for example, a `lightwalletd` server) that is used
+/// to service its requests.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TransactionDataRequest {
+    /// The status of the transaction with the given ID is required.
+    ///
+    /// The request is satisfied by determining whether the transaction has been mined in the
+    /// main chain, and if so at what height, and passing the result to
+    /// [`WalletWrite::set_transaction_status`].
+    GetStatus(TxId),
+    /// The full data of the transaction with the given ID is required.
+    ///
+    /// The request is satisfied by retrieving the transaction (for example, from a
+    /// `lightwalletd` server via its `GetTransaction` method) and passing it to
+    /// [`wallet::decrypt_and_store_transaction`]. This is used, among other things, to
+    /// retrieve the transactions that created the transparent outputs spent by the
+    /// wallet's transactions.
+    Enhancement(TxId),
+    /// The transactions that involve the given transparent address, and that were mined in
+    /// the given range of blocks, are required.
+    ///
+    /// The request is satisfied by retrieving each such transaction (for example, from a
+    /// `lightwalletd` server via its `GetTaddressTxids` method) and passing it to
+    /// [`wallet::decrypt_and_store_transaction`]. If `block_range_end` is `None`, the range
+    /// extends to the chain tip. Once the range has been searched, its end height should be
+    /// passed to [`WalletWrite::notify_address_checked`] so that the same blocks are not
+    /// requested again.
+    #[cfg(feature = "transparent-inputs")]
+    SpendsFromAddress {
+        address: TransparentAddress,
+        block_range_start: BlockHeight,
+        block_range_end: Option<BlockHeight>,
+    },
+}
+
+/// The status of a transaction, as reported in response to a
+/// [`TransactionDataRequest::GetStatus`] request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransactionStatus {
+    /// The transaction ID was not recognized by the node.
+    TxidNotRecognized,
+    /// The transaction is known to the node, but is in the mempool or has not otherwise been
+    /// mined in the main chain (it may have been mined in a block that was subsequently
+    /// reorganized away).
+    NotInMainChain,
+    /// The transaction was mined in the main chain, in the block at the given height.
+    Mined(BlockHeight),
 }
 
 /// The relevance of a seed to a given wallet.
@@ -926,6 +1342,63 @@ pub enum SeedRelevance<A: Copy> {
     NoAccounts,
 }
 
+/// Information about the on-chain use of one of the addresses that the wallet has generated for
+/// an account.
+///
+/// An address is considered to have been used when the wallet has observed a mined transaction
+/// that pays to any of its receivers.
+#[derive(Debug, Clone)]
+pub struct AddressUsage {
+    address: UnifiedAddress,
+    diversifier_index: DiversifierIndex,
+    first_used_height: Option<BlockHeight>,
+    last_used_height: Option<BlockHeight>,
+}
+
+impl AddressUsage {
+    /// Constructs a new [`AddressUsage`] value from its constituent parts.
+    pub fn from_parts(
+        address: UnifiedAddress,
+        diversifier_index: DiversifierIndex,
+        first_used_height: Option<BlockHeight>,
+        last_used_height: Option<BlockHeight>,
+    ) -> Self {
+        Self {
+            address,
+            diversifier_index,
+            first_used_height,
+            last_used_height,
+        }
+    }
+
+    /// Returns the address.
+    pub fn address(&self) -> &UnifiedAddress {
+        &self.address
+    }
+
+    /// Returns the diversifier index at which the address was derived.
+    pub fn diversifier_index(&self) -> DiversifierIndex {
+        self.diversifier_index
+    }
+
+    /// Returns the height of the earliest mined transaction observed to pay to the address, or
+    /// `None` if the address has not been used.
+    pub fn first_used_height(&self) -> Option<BlockHeight> {
+        self.first_used_height
+    }
+
+    /// Returns the height of the most recent mined transaction observed to pay to the address,
+    /// or `None` if the address has not been used.
+    pub fn last_used_height(&self) -> Option<BlockHeight> {
+        self.last_used_height
+    }
+
+    /// Returns whether the wallet has observed a mined transaction paying to the address.
+    pub fn is_used(&self) -> bool {
+        self.first_used_height.is_some()
+    }
+}
+
 /// Metadata describing the sizes of the zcash note commitment trees as of a particular block.
 #[derive(Debug, Clone, Copy)]
 pub struct BlockMetadata {
@@ -1127,12 +1600,61 @@ impl<A> ScannedBlock<A> {
     }
 }
 
+/// The nullifiers revealed by the transactions of a single block.
+///
+/// Unlike a [`ScannedBlock`], this does not require the block to have been scanned, or
+/// even for its note commitments to be available; it is typically obtained from a light
+/// wallet server's `GetBlockRangeNullifiers` method. It is used by
+/// [`WalletWrite::put_block_nullifiers`] to detect spends of the wallet's notes ahead of
+/// scanning.
+pub struct BlockNullifiers {
+    block_height: BlockHeight,
+    sapling: Vec<(TxId, u16, Vec<sapling::Nullifier>)>,
+    #[cfg(feature = "orchard")]
+    orchard: Vec<(TxId, u16, Vec<orchard::note::Nullifier>)>,
+}
+
+impl BlockNullifiers {
+    /// Constructs a new `BlockNullifiers` from its constituent parts.
+    pub fn from_parts(
+        block_height: BlockHeight,
+        sapling: Vec<(TxId, u16, Vec<sapling::Nullifier>)>,
+        #[cfg(feature = "orchard")] orchard: Vec<(TxId, u16, Vec<orchard::note::Nullifier>)>,
+    ) -> Self {
+        Self {
+            block_height,
+            sapling,
+            #[cfg(feature = "orchard")]
+            orchard,
+        }
+    }
+
+    /// Returns the height of the block.
+    pub fn block_height(&self) -> BlockHeight {
+        self.block_height
+    }
+
+    /// Returns the Sapling nullifiers revealed by each transaction in the block, keyed by
+    /// transaction ID and the index of the transaction within the block.
+    pub fn sapling(&self) -> &[(TxId, u16, Vec<sapling::Nullifier>)] {
+        &self.sapling
+    }
+
+    /// Returns the Orchard nullifiers revealed by each transaction in the block, keyed by
+    /// transaction ID and the index of the transaction within the block.
+    #[cfg(feature = "orchard")]
+    pub fn orchard(&self) -> &[(TxId, u16, Vec<orchard::note::Nullifier>)] {
+        &self.orchard
+    }
+}
+
 /// A transaction that was detected during scanning of the blockchain,
 /// including its decrypted Sapling and/or Orchard outputs.
 ///
 /// The purpose of this struct is to permit atomic updates of the
 /// wallet database when transactions are successfully decrypted.
 pub struct DecryptedTransaction<'a, AccountId> {
+    mined_height: Option<BlockHeight>,
     tx: &'a Transaction,
     sapling_outputs: Vec<DecryptedOutput<sapling::Note, AccountId>>,
     #[cfg(feature = "orchard")]
@@ -1142,6 +1664,7 @@ pub struct DecryptedTransaction<'a, AccountId> {
 impl<'a, AccountId> DecryptedTransaction<'a, AccountId> {
     /// Constructs a new [`DecryptedTransaction`] from its constituent parts.
     pub fn new(
+        mined_height: Option<BlockHeight>,
         tx: &'a Transaction,
         sapling_outputs: Vec<DecryptedOutput<sapling::Note, AccountId>>,
         #[cfg(feature = "orchard")] orchard_outputs: Vec<
@@ -1149,6 +1672,7 @@ impl<'a, AccountId> DecryptedTransaction<'a, AccountId> {
         >,
     ) -> Self {
         Self {
+            mined_height,
             tx,
             sapling_outputs,
             #[cfg(feature = "orchard")]
@@ -1156,6 +1680,11 @@ impl<'a, AccountId> DecryptedTransaction<'a, AccountId> {
         }
     }
 
+    /// Returns the height of the block in which the transaction was mined, if it is known to
+    /// have been mined.
+    pub fn mined_height(&self) -> Option<BlockHeight> {
+        self.mined_height
+    }
     /// Returns the raw transaction data.
     pub fn tx(&self) -> &Transaction {
         self.tx
@@ -1334,7 +1863,7 @@ impl AccountBirthday {
     ///
     /// This API is intended primarily to be used in testing contexts; under normal circumstances,
     /// [`AccountBirthday::from_treestate`] should be used instead.
-    #[cfg(any(test, feature = "test-dependencies"))]
+    #[cfg(any(test, feature = "test-dependencies", feature = "demo"))]
     pub fn from_parts(prior_chain_state: ChainState, recover_until: Option<BlockHeight>) -> Self {
         Self {
             prior_chain_state,
@@ -1502,12 +2031,53 @@ pub trait WalletWrite: WalletRead {
         blocks: Vec<ScannedBlock<Self::AccountId>>,
     ) -> Result<(), Self::Error>;
 
+    /// Records the nullifiers revealed in the given blocks, which need not have been scanned.
+    ///
+    /// Any of the wallet's notes whose nullifiers appear in `blocks` are immediately marked as
+    /// spent by the revealing transaction, so that the wallet's balance reflects those spends
+    /// without waiting for the blocks to be scanned. Implementations should also retain the
+    /// nullifiers so that notes discovered by subsequently scanning earlier blocks are detected
+    /// as spent.
+    ///
+    /// Transactions in which spends are detected in this fashion are not associated with their
+    /// mined height until the blocks containing them are scanned.
+    fn put_block_nullifiers(&mut self, blocks: Vec<BlockNullifiers>) -> Result<(), Self::Error>;
+
     /// Adds a transparent UTXO received by the wallet to the data store.
     fn put_received_transparent_utxo(
         &mut self,
         output: &WalletTransparentOutput,
     ) -> Result<Self::UtxoRef, Self::Error>;
 
+    /// Adds a transparent UTXO that was obtained from a source other than the light wallet
+    /// server, such as a block explorer, to the data store.
+    ///
+    /// `height` is the height of the block in which the transaction that created the output
+    /// was mined. Once stored, the output contributes to the wallet's transparent balance and
+    /// may be selected as an input to shielding transactions.
+    ///
+    /// Returns an error if the output's script does not pay to a transparent address, or if
+    /// that address does not belong to the wallet.
+    #[cfg(feature = "transparent-inputs")]
+    fn put_utxo(
+        &mut self,
+        outpoint: OutPoint,
+        txout: TxOut,
+        height: BlockHeight,
+    ) -> Result<Self::UtxoRef, Self::Error>;
+
+    /// Records that the transactions involving the given transparent address have been
+    /// retrieved for all blocks up to and including `as_of_height`.
+    ///
+    /// Subsequent [`TransactionDataRequest::SpendsFromAddress`] requests for the address will
+    /// start above this height.
+    #[cfg(feature = "transparent-inputs")]
+    fn notify_address_checked(
+        &mut self,
+        address: &TransparentAddress,
+        as_of_height: BlockHeight,
+    ) -> Result<(), Self::Error>;
+
     /// Caches a decrypted transaction in the persistent wallet store.
     fn store_decrypted_tx(
         &mut self,
@@ -1535,6 +2105,26 @@ pub trait WalletWrite: WalletRead {
     ///
     /// There may be restrictions on heights to which it is possible to truncate.
     fn truncate_to_height(&mut self, block_height: BlockHeight) -> Result<(), Self::Error>;
+
+    /// Removes the data obtained by trial-decrypting the blocks in the given range, and
+    /// re-queues the range for scanning.
+    ///
+    /// This is intended for use when a fix to note detection or decryption means that the
+    /// results of a previous scan of the range can no longer be trusted. Unlike
+    /// [`WalletWrite::truncate_to_height`], this does not alter the wallet's view of the
+    /// chain tip, and does not remove transactions. Unspent notes that were detected in the
+    /// range no longer contribute to the wallet's balance until the range has been scanned
+    /// again, and the full data of the transactions whose memos they carried is requested
+    /// again via [`TransactionDataRequest::Enhancement`] so that the memos are restored.
+    fn truncate_scanned_data(&mut self, range: Range<BlockHeight>) -> Result<(), Self::Error>;
+
+    /// Records the status of a transaction, in response to a
+    /// [`TransactionDataRequest::GetStatus`] request.
+    fn set_transaction_status(
+        &mut self,
+        txid: TxId,
+        status: TransactionStatus,
+    ) -> Result<(), Self::Error>;
 }
 
 /// This trait describes a capability for manipulating wallet note commitment trees.
@@ -1613,8 +2203,8 @@ pub mod testing {
     use incrementalmerkletree::Address;
     use secrecy::{ExposeSecret, SecretVec};
     use shardtree::{error::ShardTreeError, store::memory::MemoryShardStore, ShardTree};
-    use std::{collections::HashMap, convert::Infallible, num::NonZeroU32};
-    use zip32::fingerprint::SeedFingerprint;
+    use std::{collections::HashMap, convert::Infallible, num::NonZeroU32, ops::Range};
+    use zip32::{fingerprint::SeedFingerprint, Scope};
 
     use zcash_primitives::{
         block::BlockHash,
@@ -1624,26 +2214,38 @@ pub mod testing {
     };
 
     use crate::{
-        address::UnifiedAddress,
+        address::{Address as WalletAddress, UnifiedAddress},
         keys::{UnifiedAddressRequest, UnifiedFullViewingKey, UnifiedSpendingKey},
         wallet::{Note, NoteId, ReceivedNote, WalletTransparentOutput},
         ShieldedProtocol,
     };
 
     use super::{
+        audit::AuditBundle,
         chain::{ChainState, CommitmentTreeRoot},
         scanning::ScanRange,
-        AccountBirthday, BlockMetadata, DecryptedTransaction, InputSource, NullifierQuery,
-        ScannedBlock, SeedRelevance, SentTransaction, SpendableNotes, WalletCommitmentTrees,
-        WalletRead, WalletSummary, WalletWrite, SAPLING_SHARD_HEIGHT,
+        scheduled::ScheduledPayment,
+        AccountBirthday, AddressUsage, BlockMetadata, DecryptedTransaction, DustFilter,
+        HistoricalBalance, InputSource, NullifierQuery, PeriodStatistics, ScannedBlock,
+        SeedRelevance, SentTransaction, SpendableNotes, StatisticsGranularity,
+        TransactionDataRequest, TransactionStatus, WalletCommitmentTrees, WalletRead,
+        WalletSummary, WalletWrite, SAPLING_SHARD_HEIGHT,
     };
 
     #[cfg(feature = "transparent-inputs")]
-    use {crate::wallet::TransparentAddressMetadata, zcash_primitives::legacy::TransparentAddress};
+    use {
+        crate::wallet::TransparentAddressMetadata,
+        zcash_primitives::{
+            legacy::TransparentAddress,
+            transaction::components::{OutPoint, TxOut},
+        },
+    };
 
     #[cfg(feature = "orchard")]
     use super::ORCHARD_SHARD_HEIGHT;
 
+    pub mod conformance;
+
     pub struct MockWalletDb {
         pub network: Network,
         pub sapling_tree: ShardTree<
@@ -1749,6 +2351,20 @@ pub mod testing {
             Ok(None)
         }
 
+        fn address_usage(
+            &self,
+            _account: Self::AccountId,
+        ) -> Result<Vec<AddressUsage>, Self::Error> {
+            Ok(vec![])
+        }
+
+        fn find_account_for_address(
+            &self,
+            _address: &WalletAddress,
+        ) -> Result<Option<(Self::AccountId, Scope)>, Self::Error> {
+            Ok(None)
+        }
+
         fn get_account_birthday(
             &self,
             _account: Self::AccountId,
@@ -1767,6 +2383,37 @@ pub mod testing {
             Ok(None)
         }
 
+        fn get_wallet_summary_with_dust_filter(
+            &self,
+            _min_confirmations: u32,
+            _dust_filter: DustFilter,
+        ) -> Result<Option<WalletSummary<Self::AccountId>>, Self::Error> {
+            Ok(None)
+        }
+
+        fn get_balance_at_height(
+            &self,
+            _account: Self::AccountId,
+            _height: BlockHeight,
+        ) -> Result<Option<HistoricalBalance>, Self::Error> {
+            Ok(None)
+        }
+
+        fn get_account_statistics(
+            &self,
+            _account: Self::AccountId,
+            _granularity: StatisticsGranularity,
+        ) -> Result<Vec<PeriodStatistics>, Self::Error> {
+            Ok(vec![])
+        }
+
+        fn payments_due(
+            &self,
+            _now: time::OffsetDateTime,
+        ) -> Result<Vec<ScheduledPayment<Self::AccountId>>, Self::Error> {
+            Ok(vec![])
+        }
+
         fn chain_height(&self) -> Result<Option<BlockHeight>, Self::Error> {
             Ok(None)
         }
@@ -1830,6 +2477,22 @@ pub mod testing {
             Ok(None)
         }
 
+        fn transaction_data_requests(&self) -> Result<Vec<TransactionDataRequest>, Self::Error> {
+            Ok(vec![])
+        }
+
+        fn export_account_audit_bundle(
+            &self,
+            account: Self::AccountId,
+            height_range: Range<BlockHeight>,
+        ) -> Result<AuditBundle, Self::Error> {
+            Ok(AuditBundle::from_decrypted_transactions(
+                account,
+                height_range,
+                vec![],
+            ))
+        }
+
         fn get_sapling_nullifiers(
             &self,
             _query: NullifierQuery,
@@ -1895,6 +2558,13 @@ pub mod testing {
             Ok(())
         }
 
+        fn put_block_nullifiers(
+            &mut self,
+            _blocks: Vec<BlockNullifiers>,
+        ) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
         fn update_chain_tip(&mut self, _tip_height: BlockHeight) -> Result<(), Self::Error> {
             Ok(())
         }
@@ -1917,6 +2587,18 @@ pub mod testing {
             Ok(())
         }
 
+        fn truncate_scanned_data(&mut self, _range: Range<BlockHeight>) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn set_transaction_status(
+            &mut self,
+            _txid: TxId,
+            _status: TransactionStatus,
+        ) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
         /// Adds a transparent UTXO received by the wallet to the data store.
         fn put_received_transparent_utxo(
             &mut self,
@@ -1924,6 +2606,25 @@ pub mod testing {
         ) -> Result<Self::UtxoRef, Self::Error> {
             Ok(0)
         }
+
+        #[cfg(feature = "transparent-inputs")]
+        fn put_utxo(
+            &mut self,
+            _outpoint: OutPoint,
+            _txout: TxOut,
+            _height: BlockHeight,
+        ) -> Result<Self::UtxoRef, Self::Error> {
+            Ok(0)
+        }
+
+        #[cfg(feature = "transparent-inputs")]
+        fn notify_address_checked(
+            &mut self,
+            _address: &TransparentAddress,
+            _as_of_height: BlockHeight,
+        ) -> Result<(), Self::Error> {
+            Ok(())
+        }
     }
 
     impl WalletCommitmentTrees for MockWalletDb {
@@ -0,0 +1,502 @@
+//! Audit bundles for selective disclosure of an account's transaction history.
+//!
+//! An [`AuditBundle`] packages the decrypted shielded outputs that affected a single account
+//! within a range of block heights, so that they can be provided to an auditor without sharing
+//! a viewing key. Each disclosed note is accompanied by the data required to recompute its
+//! note commitment, which allows the auditor to check the bundle against the outputs of the
+//! mined transactions.
+//!
+//! The serialized form of a bundle ends with an unkeyed BLAKE2b digest of its contents, which is
+//! checked when the bundle is read in order to detect accidental corruption. Because anyone can
+//! recompute the digest, it says nothing about who produced the bundle; the assurance that an
+//! auditor obtains comes from checking the disclosed notes against the chain with
+//! [`AuditBundle::verify`].
+
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::ops::Range;
+
+use blake2b_simd::Params as Blake2bParams;
+use zcash_encoding::Vector;
+use zcash_primitives::{
+    consensus::BlockHeight,
+    memo::MemoBytes,
+    transaction::{Transaction, TxId},
+};
+
+use crate::{decrypt::TransferType, wallet::Note, ShieldedProtocol};
+
+use super::DecryptedTransaction;
+
+/// The version byte of the current audit bundle encoding.
+const AUDIT_BUNDLE_VERSION: u8 = 1;
+
+/// The personalization used for the audit bundle digest.
+const AUDIT_BUNDLE_PERSONALIZATION: &[u8; 16] = b"ZcashAuditBundle";
+
+const SAPLING_TAG: u8 = 0;
+const ORCHARD_TAG: u8 = 1;
+
+/// Errors that can occur in verifying an [`AuditBundle`] against the chain.
+#[derive(Debug)]
+pub enum VerificationError<E> {
+    /// An error occurred in retrieving a transaction from the source of chain data.
+    Source(E),
+    /// A transaction included in the bundle could not be found in the chain.
+    TransactionNotFound(TxId),
+    /// The transaction was mined at a height other than the one recorded in the bundle.
+    HeightMismatch {
+        txid: TxId,
+        expected: BlockHeight,
+        actual: BlockHeight,
+    },
+    /// The transaction was mined at a height outside of the bundle's height range.
+    HeightOutOfRange(TxId, BlockHeight),
+    /// The transaction does not contain an output at the given index in the given shielded pool.
+    OutputNotFound(TxId, ShieldedProtocol, u32),
+    /// The disclosed note does not match the note commitment of the corresponding output.
+    CommitmentMismatch(TxId, ShieldedProtocol, u32),
+}
+
+impl<E: fmt::Display> fmt::Display for VerificationError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerificationError::Source(e) => {
+                write!(f, "Error retrieving transaction data: {}", e)
+            }
+            VerificationError::TransactionNotFound(txid) => {
+                write!(f, "Transaction {} was not found in the chain.", txid)
+            }
+            VerificationError::HeightMismatch {
+                txid,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Transaction {} was mined at height {}, but the bundle records height {}.",
+                txid, actual, expected
+            ),
+            VerificationError::HeightOutOfRange(txid, height) => write!(
+                f,
+                "Transaction {} was mined at height {}, which is outside the audited range.",
+                txid, height
+            ),
+            VerificationError::OutputNotFound(txid, protocol, index) => write!(
+                f,
+                "Transaction {} has no {:?} output at index {}.",
+                txid, protocol, index
+            ),
+            VerificationError::CommitmentMismatch(txid, protocol, index) => write!(
+                f,
+                "The disclosed note does not match the commitment of {:?} output {} of transaction {}.",
+                protocol, index, txid
+            ),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for VerificationError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            VerificationError::Source(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// A shielded output disclosed as part of an [`AuditBundle`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditedOutput {
+    index: u32,
+    transfer_type: TransferType,
+    note: Note,
+    memo: MemoBytes,
+}
+
+impl AuditedOutput {
+    /// Returns the index of the output within the shielded outputs of the Sapling bundle or
+    /// the actions of the Orchard bundle, depending upon the protocol of [`Self::note`].
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// Returns the relationship of this output to the audited account.
+    pub fn transfer_type(&self) -> TransferType {
+        self.transfer_type
+    }
+
+    /// Returns the note created by this output.
+    pub fn note(&self) -> &Note {
+        &self.note
+    }
+
+    /// Returns the memo associated with this output.
+    pub fn memo(&self) -> &MemoBytes {
+        &self.memo
+    }
+
+    fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(&[match self.note.protocol() {
+            ShieldedProtocol::Sapling => SAPLING_TAG,
+            ShieldedProtocol::Orchard => ORCHARD_TAG,
+        }])?;
+        writer.write_all(&self.index.to_le_bytes())?;
+        writer.write_all(&[transfer_type_code(self.transfer_type)])?;
+        self.note.write_parts(&mut writer)?;
+        writer.write_all(self.memo.as_array())
+    }
+
+    fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        let protocol = match read_u8(&mut reader)? {
+            SAPLING_TAG => ShieldedProtocol::Sapling,
+            ORCHARD_TAG => ShieldedProtocol::Orchard,
+            _ => return Err(invalid_data("Invalid shielded protocol.")),
+        };
+        let index = u32::from_le_bytes(read_array(&mut reader)?);
+        let transfer_type = match read_u8(&mut reader)? {
+            0 => TransferType::Incoming,
+            1 => TransferType::WalletInternal,
+            2 => TransferType::Outgoing,
+            _ => return Err(invalid_data("Invalid transfer type.")),
+        };
+        let note = Note::read_parts(protocol, &mut reader)?;
+
+        let memo = MemoBytes::from_bytes(&read_array::<_, 512>(&mut reader)?)
+            .map_err(|_| invalid_data("Invalid memo."))?;
+
+        Ok(AuditedOutput {
+            index,
+            transfer_type,
+            note,
+            memo,
+        })
+    }
+}
+
+/// A transaction disclosed as part of an [`AuditBundle`], along with the outputs of that
+/// transaction that are relevant to the audited account.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditedTransaction {
+    txid: TxId,
+    mined_height: BlockHeight,
+    outputs: Vec<AuditedOutput>,
+}
+
+impl AuditedTransaction {
+    /// Returns the ID of the transaction.
+    pub fn txid(&self) -> TxId {
+        self.txid
+    }
+
+    /// Returns the height at which the transaction was mined.
+    pub fn mined_height(&self) -> BlockHeight {
+        self.mined_height
+    }
+
+    /// Returns the disclosed outputs of the transaction.
+    pub fn outputs(&self) -> &[AuditedOutput] {
+        &self.outputs
+    }
+
+    /// Checks that each disclosed output corresponds to the note commitment of the output at
+    /// the same index in the given transaction.
+    pub fn verify<E>(&self, tx: &Transaction) -> Result<(), VerificationError<E>> {
+        if tx.txid() != self.txid {
+            return Err(VerificationError::TransactionNotFound(self.txid));
+        }
+
+        for output in &self.outputs {
+            let protocol = output.note.protocol();
+            let not_found = || VerificationError::OutputNotFound(self.txid, protocol, output.index);
+
+            let commitment_matches = match &output.note {
+                Note::Sapling(note) => {
+                    let description = tx
+                        .sapling_bundle()
+                        .and_then(|b| b.shielded_outputs().get(output.index as usize))
+                        .ok_or_else(not_found)?;
+                    note.cmu().to_bytes() == description.cmu().to_bytes()
+                }
+                #[cfg(feature = "orchard")]
+                Note::Orchard(note) => {
+                    let action = tx
+                        .orchard_bundle()
+                        .and_then(|b| b.actions().get(output.index as usize))
+                        .ok_or_else(not_found)?;
+                    orchard::note::ExtractedNoteCommitment::from(note.commitment()).to_bytes()
+                        == action.cmx().to_bytes()
+                }
+            };
+
+            if !commitment_matches {
+                return Err(VerificationError::CommitmentMismatch(
+                    self.txid,
+                    protocol,
+                    output.index,
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        self.txid.write(&mut writer)?;
+        writer.write_all(&u32::from(self.mined_height).to_le_bytes())?;
+        Vector::write(&mut writer, &self.outputs, |w, output| output.write(w))
+    }
+
+    fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        let txid = TxId::read(&mut reader)?;
+        let mined_height = BlockHeight::from(u32::from_le_bytes(read_array(&mut reader)?));
+        let outputs = Vector::read(&mut reader, AuditedOutput::read)?;
+
+        Ok(AuditedTransaction {
+            txid,
+            mined_height,
+            outputs,
+        })
+    }
+}
+
+/// A package of the decrypted shielded outputs that affected a single account within a range
+/// of block heights.
+///
+/// Audit bundles are produced by [`WalletRead::export_account_audit_bundle`], and can be
+/// checked by an auditor against the chain using [`AuditBundle::verify`].
+///
+/// [`WalletRead::export_account_audit_bundle`]: super::WalletRead::export_account_audit_bundle
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditBundle {
+    height_range: Range<BlockHeight>,
+    transactions: Vec<AuditedTransaction>,
+}
+
+impl AuditBundle {
+    /// Constructs an audit bundle from transactions that have been decrypted using the
+    /// viewing key of the audited account.
+    ///
+    /// Each transaction is provided along with the height at which it was mined. Only the
+    /// outputs decrypted by `account` are included in the bundle, and transactions that have
+    /// no such outputs or that were mined outside of `height_range` are omitted.
+    pub fn from_decrypted_transactions<'a, AccountId: Copy + Eq + 'a>(
+        account: AccountId,
+        height_range: Range<BlockHeight>,
+        transactions: impl IntoIterator<Item = (BlockHeight, DecryptedTransaction<'a, AccountId>)>,
+    ) -> Self {
+        let transactions = transactions
+            .into_iter()
+            .filter(|(mined_height, _)| height_range.contains(mined_height))
+            .filter_map(|(mined_height, d_tx)| {
+                let sapling_outputs = d_tx
+                    .sapling_outputs()
+                    .iter()
+                    .filter(|output| output.account() == &account)
+                    .map(|output| AuditedOutput {
+                        index: output.index() as u32,
+                        transfer_type: output.transfer_type(),
+                        note: Note::Sapling(output.note().clone()),
+                        memo: output.memo().clone(),
+                    });
+
+                #[cfg(feature = "orchard")]
+                let orchard_outputs = d_tx
+                    .orchard_outputs()
+                    .iter()
+                    .filter(|output| output.account() == &account)
+                    .map(|output| AuditedOutput {
+                        index: output.index() as u32,
+                        transfer_type: output.transfer_type(),
+                        note: Note::Orchard(*output.note()),
+                        memo: output.memo().clone(),
+                    });
+
+                #[cfg(not(feature = "orchard"))]
+                let orchard_outputs = std::iter::empty();
+
+                let outputs = sapling_outputs.chain(orchard_outputs).collect::<Vec<_>>();
+                if outputs.is_empty() {
+                    None
+                } else {
+                    Some(AuditedTransaction {
+                        txid: d_tx.tx().txid(),
+                        mined_height,
+                        outputs,
+                    })
+                }
+            })
+            .collect();
+
+        AuditBundle {
+            height_range,
+            transactions,
+        }
+    }
+
+    /// Returns the range of block heights covered by this bundle.
+    pub fn height_range(&self) -> &Range<BlockHeight> {
+        &self.height_range
+    }
+
+    /// Returns the transactions disclosed by this bundle.
+    pub fn transactions(&self) -> &[AuditedTransaction] {
+        &self.transactions
+    }
+
+    /// Returns the unkeyed BLAKE2b-256 digest of the contents of this bundle.
+    ///
+    /// This digest is appended to the serialized form of the bundle as a checksum. It does not
+    /// authenticate the bundle.
+    pub fn digest(&self) -> [u8; 32] {
+        let mut contents = vec![];
+        self.write_contents(&mut contents)
+            .expect("Writing to a Vec cannot fail.");
+
+        Blake2bParams::new()
+            .hash_length(32)
+            .personal(AUDIT_BUNDLE_PERSONALIZATION)
+            .hash(&contents)
+            .as_bytes()
+            .try_into()
+            .expect("BLAKE2b output has the requested length.")
+    }
+
+    /// Checks each transaction in the bundle against the chain.
+    ///
+    /// `get_transaction` is used to retrieve each disclosed transaction along with the height
+    /// at which it was mined, and should return `Ok(None)` if the transaction is not in the
+    /// main chain. Verification fails if any transaction was mined at a height other than the
+    /// one recorded in the bundle, or if any disclosed note does not match the note commitment
+    /// of its output.
+    pub fn verify<F, E>(&self, mut get_transaction: F) -> Result<(), VerificationError<E>>
+    where
+        F: FnMut(TxId) -> Result<Option<(BlockHeight, Transaction)>, E>,
+    {
+        for audited_tx in &self.transactions {
+            let txid = audited_tx.txid;
+            let (mined_height, tx) = get_transaction(txid)
+                .map_err(VerificationError::Source)?
+                .ok_or(VerificationError::TransactionNotFound(txid))?;
+
+            if !self.height_range.contains(&mined_height) {
+                return Err(VerificationError::HeightOutOfRange(txid, mined_height));
+            }
+            if mined_height != audited_tx.mined_height {
+                return Err(VerificationError::HeightMismatch {
+                    txid,
+                    expected: audited_tx.mined_height,
+                    actual: mined_height,
+                });
+            }
+
+            audited_tx.verify(&tx)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the serialized form of this bundle, followed by its digest.
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        self.write_contents(&mut writer)?;
+        writer.write_all(&self.digest())
+    }
+
+    /// Reads a bundle from its serialized form, checking that the contents of the bundle match
+    /// the trailing digest.
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        let version = read_u8(&mut reader)?;
+        if version != AUDIT_BUNDLE_VERSION {
+            return Err(invalid_data(&format!(
+                "Unknown audit bundle version {}.",
+                version
+            )));
+        }
+
+        let start = BlockHeight::from(u32::from_le_bytes(read_array(&mut reader)?));
+        let end = BlockHeight::from(u32::from_le_bytes(read_array(&mut reader)?));
+        let transactions = Vector::read(&mut reader, AuditedTransaction::read)?;
+        let digest: [u8; 32] = read_array(&mut reader)?;
+
+        let bundle = AuditBundle {
+            height_range: start..end,
+            transactions,
+        };
+
+        if bundle.digest() != digest {
+            return Err(invalid_data(
+                "Audit bundle digest does not match its contents.",
+            ));
+        }
+
+        Ok(bundle)
+    }
+
+    fn write_contents<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(&[AUDIT_BUNDLE_VERSION])?;
+        writer.write_all(&u32::from(self.height_range.start).to_le_bytes())?;
+        writer.write_all(&u32::from(self.height_range.end).to_le_bytes())?;
+        Vector::write(&mut writer, &self.transactions, |w, tx| tx.write(w))
+    }
+}
+
+fn transfer_type_code(transfer_type: TransferType) -> u8 {
+    match transfer_type {
+        TransferType::Incoming => 0,
+        TransferType::WalletInternal => 1,
+        TransferType::Outgoing => 2,
+    }
+}
+
+fn read_u8<R: Read>(reader: R) -> io::Result<u8> {
+    read_array::<_, 1>(reader).map(|[b]| b)
+}
+
+fn read_array<R: Read, const N: usize>(mut reader: R) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use zcash_primitives::{consensus::BlockHeight, memo::MemoBytes, transaction::TxId};
+
+    use super::{AuditBundle, AuditedOutput, AuditedTransaction};
+    use crate::{decrypt::TransferType, wallet::Note};
+
+    #[test]
+    fn serialization_roundtrip() {
+        let (_, recipient) =
+            sapling::zip32::ExtendedSpendingKey::master(&[0; 32]).default_address();
+
+        let bundle = AuditBundle {
+            height_range: BlockHeight::from(100)..BlockHeight::from(200),
+            transactions: vec![AuditedTransaction {
+                txid: TxId::from_bytes([7; 32]),
+                mined_height: BlockHeight::from(150),
+                outputs: vec![AuditedOutput {
+                    index: 1,
+                    transfer_type: TransferType::Incoming,
+                    note: Note::Sapling(sapling::Note::from_parts(
+                        recipient,
+                        sapling::value::NoteValue::from_raw(10000),
+                        sapling::Rseed::AfterZip212([3; 32]),
+                    )),
+                    memo: MemoBytes::empty(),
+                }],
+            }],
+        };
+
+        let mut encoded = vec![];
+        bundle.write(&mut encoded).unwrap();
+        assert_eq!(AuditBundle::read(&encoded[..]).unwrap(), bundle);
+
+        // Corruption of the contents of the bundle is detected by the digest.
+        encoded[5] ^= 1;
+        assert!(AuditBundle::read(&encoded[..]).is_err());
+    }
+}
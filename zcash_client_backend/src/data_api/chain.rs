@@ -87,10 +87,10 @@
 //!                 }
 //!                 Err(Error::Scan(err)) if err.is_continuity_error() => {
 //!                     // Pick a height to rewind to, which must be at least one block before
-//!                     // the height at which the error occurred, but may be an earlier height
-//!                     // determined based on heuristics such as the platform, available bandwidth,
-//!                     // size of recent CompactBlocks, etc.
-//!                     let rewind_height = err.at_height().saturating_sub(10);
+//!                     // the earliest block that is inconsistent with the wallet's view of the
+//!                     // chain. The suggested height may be adjusted based on heuristics such as
+//!                     // the platform, available bandwidth, size of recent CompactBlocks, etc.
+//!                     let rewind_height = err.suggested_rewind_height().unwrap();
 //!
 //!                     // Rewind to the chosen height.
 //!                     wallet_db.truncate_to_height(rewind_height).map_err(Error::Wallet)?;
@@ -151,9 +151,10 @@
 //! # }
 //! ```
 
+use std::fmt;
 use std::ops::Range;
 
-use incrementalmerkletree::frontier::Frontier;
+use incrementalmerkletree::{frontier::Frontier, Hashable, Level};
 use subtle::ConditionallySelectable;
 use zcash_primitives::{
     block::BlockHash,
@@ -164,8 +165,12 @@ use crate::{
     data_api::{NullifierQuery, WalletWrite},
     proto::compact_formats::CompactBlock,
     scanning::{scan_block_with_runners, BatchRunners, Nullifiers, ScanningKeys},
+    ShieldedProtocol,
 };
 
+#[cfg(feature = "transparent-inputs")]
+use zcash_primitives::transaction::components::OutPoint;
+
 #[cfg(feature = "sync")]
 use {
     super::scanning::ScanPriority, crate::data_api::scanning::ScanRange, async_trait::async_trait,
@@ -227,6 +232,54 @@ pub trait BlockSource {
     ) -> Result<(), error::Error<WalletErrT, Self::Error>>
     where
         F: FnMut(CompactBlock) -> Result<(), error::Error<WalletErrT, Self::Error>>;
+
+    /// Applies the provided callback to each of the blocks in the given range, in the given
+    /// order.
+    ///
+    /// This allows the ranges of a prioritized scan queue to be serviced from the same source
+    /// regardless of their position in the chain; for example, the range at the chain tip can
+    /// be read before historic ranges, or a range can be read from its most recent block
+    /// backwards. As for [`Self::with_blocks`], an error is returned if the blocks in the range
+    /// are not available from the source.
+    ///
+    /// The default implementation is in terms of [`Self::with_blocks`]; for
+    /// [`BlockOrder::Descending`] it buffers all of the blocks in the range before supplying
+    /// them, so implementations that can read blocks in descending order should override it.
+    fn with_blocks_in<F, WalletErrT>(
+        &self,
+        range: Range<BlockHeight>,
+        order: BlockOrder,
+        with_block: F,
+    ) -> Result<(), error::Error<WalletErrT, Self::Error>>
+    where
+        F: FnMut(CompactBlock) -> Result<(), error::Error<WalletErrT, Self::Error>>,
+    {
+        if range.is_empty() {
+            return Ok(());
+        }
+        let limit = usize::try_from(u32::from(range.end) - u32::from(range.start)).ok();
+
+        match order {
+            BlockOrder::Ascending => self.with_blocks(Some(range.start), limit, with_block),
+            BlockOrder::Descending => {
+                let mut blocks = vec![];
+                self.with_blocks(Some(range.start), limit, |block| {
+                    blocks.push(block);
+                    Ok(())
+                })?;
+                blocks.into_iter().rev().try_for_each(with_block)
+            }
+        }
+    }
+}
+
+/// The order in which [`BlockSource::with_blocks_in`] supplies blocks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockOrder {
+    /// Blocks are supplied in order of increasing height.
+    Ascending,
+    /// Blocks are supplied in order of decreasing height.
+    Descending,
 }
 
 /// `BlockCache` is a trait that extends `BlockSource` and defines methods for managing
@@ -496,6 +549,118 @@ impl ScanSummary {
     }
 }
 
+/// The default number of blocks below the chain tip that [`ChainTipTracker`] treats as
+/// being at risk of a chain reorganization.
+///
+/// `zcashd` does not reorganize the chain by more than 99 blocks.
+pub const DEFAULT_REORG_DEPTH: u32 = 100;
+
+/// The change to the chain tip that was reported by [`ChainTipTracker::observe`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TipChange {
+    /// The observed tip is the same as the tip that was previously observed.
+    Unchanged,
+    /// The chain tip has advanced from the given height, or was observed for the first time
+    /// if no height is given.
+    Advanced { previous: Option<BlockHeight> },
+    /// The observed tip is not a descendant of the previously observed tip: either its height
+    /// is no greater than that of the previous tip and its hash differs, or its height is
+    /// lower. The blocks above the common ancestor of the two tips are no longer in the main
+    /// chain.
+    Reorg { previous: BlockHeight },
+}
+
+/// Tracks the tip of the chain as reported by a block source, and the height below which
+/// blocks are treated as stable.
+///
+/// The stable height is the tip height less the reorg depth given at construction. It only
+/// ever advances: a chain reorganization that lowers the tip does not lower the stable height,
+/// and an observation of the same tip does not change it. Applications that poll a light
+/// wallet server for the latest block should pass each response to [`Self::observe`], then
+/// use [`Self::update_wallet`] to inform the wallet of the new tip (which determines the
+/// priority of the ranges returned by [`WalletRead::suggest_scan_ranges`]), and
+/// [`Self::is_expired`] to determine when unmined transactions can no longer be mined.
+///
+/// [`WalletRead::suggest_scan_ranges`]: crate::data_api::WalletRead::suggest_scan_ranges
+#[derive(Clone, Debug)]
+pub struct ChainTipTracker {
+    reorg_depth: u32,
+    tip: Option<(BlockHeight, BlockHash)>,
+    stable_height: Option<BlockHeight>,
+}
+
+impl ChainTipTracker {
+    /// Constructs a tracker that has not yet observed a chain tip, and that treats the
+    /// `reorg_depth` blocks below the tip as being at risk of a chain reorganization.
+    pub fn new(reorg_depth: u32) -> Self {
+        Self {
+            reorg_depth,
+            tip: None,
+            stable_height: None,
+        }
+    }
+
+    /// Returns the height and hash of the most recently observed chain tip.
+    pub fn tip(&self) -> Option<(BlockHeight, BlockHash)> {
+        self.tip
+    }
+
+    /// Returns the height of the most recently observed chain tip.
+    pub fn tip_height(&self) -> Option<BlockHeight> {
+        self.tip.map(|(height, _)| height)
+    }
+
+    /// Returns the greatest height that has been at least the reorg depth below an observed
+    /// chain tip.
+    pub fn stable_height(&self) -> Option<BlockHeight> {
+        self.stable_height
+    }
+
+    /// Records the latest block reported by the block source, and returns how the chain tip
+    /// has changed since the previous observation.
+    pub fn observe(&mut self, height: BlockHeight, hash: BlockHash) -> TipChange {
+        let change = match self.tip {
+            None => TipChange::Advanced { previous: None },
+            Some((prev_height, prev_hash)) if prev_height == height && prev_hash == hash => {
+                TipChange::Unchanged
+            }
+            Some((prev_height, _)) if prev_height < height => TipChange::Advanced {
+                previous: Some(prev_height),
+            },
+            Some((prev_height, _)) => TipChange::Reorg {
+                previous: prev_height,
+            },
+        };
+
+        self.tip = Some((height, hash));
+        let stable_height = height.saturating_sub(self.reorg_depth);
+        self.stable_height = Some(
+            self.stable_height
+                .map_or(stable_height, |h| std::cmp::max(h, stable_height)),
+        );
+
+        change
+    }
+
+    /// Notifies the wallet of the most recently observed chain tip, if any, via
+    /// [`WalletWrite::update_chain_tip`].
+    pub fn update_wallet<DbT: WalletWrite>(&self, wallet_db: &mut DbT) -> Result<(), DbT::Error> {
+        match self.tip_height() {
+            Some(height) => wallet_db.update_chain_tip(height),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns whether a transaction with the given expiry height that has not been mined in
+    /// a block at or below the stable height can no longer be mined.
+    ///
+    /// A transaction with an expiry height of zero never expires.
+    pub fn is_expired(&self, expiry_height: BlockHeight) -> bool {
+        u32::from(expiry_height) != 0
+            && matches!(self.stable_height, Some(stable_height) if expiry_height <= stable_height)
+    }
+}
+
 /// The final note commitment tree state for each shielded pool, as of a particular block height.
 #[derive(Debug, Clone)]
 pub struct ChainState {
@@ -565,8 +730,181 @@ impl ChainState {
     {
         &self.final_orchard_tree
     }
+
+    /// Checks a sequence of Sapling subtree roots, such as those returned by a light wallet
+    /// server's `GetSubtreeRoots` method, against [`Self::final_sapling_tree`].
+    ///
+    /// `start_index` is the index of the subtree corresponding to the first element of
+    /// `roots`. Each of the frontier's ommers at or above the shard level is the root of a
+    /// run of complete subtrees, so every root in `roots` is checked by recomputing the
+    /// ommer above it from the roots of the other subtrees in its run. A root that cannot
+    /// be checked in this way, because the roots of other subtrees in its run are missing
+    /// from `roots`, is reported as a mismatch; `roots` should therefore start at index 0
+    /// and end with the last subtree completed as of [`Self::block_height`], such as when
+    /// this is the height at which the last element of `roots` was completed. In addition,
+    /// no root may claim to complete a subtree that has not been completed as of
+    /// [`Self::block_height`].
+    pub fn verify_sapling_subtree_roots(
+        &self,
+        start_index: u64,
+        roots: &[CommitmentTreeRoot<sapling::Node>],
+    ) -> Result<(), SubtreeRootMismatch> {
+        verify_subtree_roots(
+            &self.final_sapling_tree,
+            super::SAPLING_SHARD_HEIGHT,
+            self.block_height,
+            start_index,
+            roots,
+        )
+        .map_err(|index| SubtreeRootMismatch {
+            protocol: ShieldedProtocol::Sapling,
+            index,
+        })
+    }
+
+    /// Checks a sequence of Orchard subtree roots, such as those returned by a light wallet
+    /// server's `GetSubtreeRoots` method, against [`Self::final_orchard_tree`].
+    ///
+    /// See [`Self::verify_sapling_subtree_roots`] for details of the checks performed.
+    #[cfg(feature = "orchard")]
+    pub fn verify_orchard_subtree_roots(
+        &self,
+        start_index: u64,
+        roots: &[CommitmentTreeRoot<orchard::tree::MerkleHashOrchard>],
+    ) -> Result<(), SubtreeRootMismatch> {
+        verify_subtree_roots(
+            &self.final_orchard_tree,
+            super::ORCHARD_SHARD_HEIGHT,
+            self.block_height,
+            start_index,
+            roots,
+        )
+        .map_err(|index| SubtreeRootMismatch {
+            protocol: ShieldedProtocol::Orchard,
+            index,
+        })
+    }
 }
 
+/// Verifies `roots` against the given frontier, returning the index of the first subtree
+/// whose root is inconsistent with it or cannot be checked.
+fn verify_subtree_roots<H: Hashable + Clone + PartialEq, const DEPTH: u8>(
+    frontier: &Frontier<H, DEPTH>,
+    shard_height: u8,
+    block_height: BlockHeight,
+    start_index: u64,
+    roots: &[CommitmentTreeRoot<H>],
+) -> Result<(), u64> {
+    let frontier = frontier.value();
+
+    // The number of subtrees that are complete as of the end of `block_height`.
+    let complete_subtrees = frontier.map_or(0, |f| (u64::from(f.position()) + 1) >> shard_height);
+
+    for (i, root) in roots.iter().enumerate() {
+        let index = start_index + i as u64;
+        if index >= complete_subtrees || root.subtree_end_height() > block_height {
+            return Err(index);
+        }
+    }
+
+    let end_index = start_index + roots.len() as u64;
+    let mut verified = vec![false; roots.len()];
+    if let Some(f) = frontier {
+        let position = u64::from(f.position());
+        let subtree_mask = (1u64 << shard_height) - 1;
+        let subtree_index = position >> shard_height;
+
+        if position & subtree_mask == subtree_mask && subtree_index < end_index {
+            // The frontier's leaf completes its subtree.
+            if subtree_index >= start_index {
+                let i = (subtree_index - start_index) as usize;
+                if f.root(Some(Level::from(shard_height))) != *roots[i].root_hash() {
+                    return Err(subtree_index);
+                }
+                verified[i] = true;
+            }
+        }
+
+        // Every subtree that precedes the one containing the frontier's leaf lies beneath
+        // exactly one of the frontier's ommers at or above the shard level: for each set bit
+        // `k` of the subtree index, there is an ommer at level `shard_height + k` that is the
+        // root of the `2^k` subtrees below the range covered by the higher ommers. Ommers are
+        // stored in order of increasing level, one for each set bit of the position.
+        let mut ommers = f
+            .ommers()
+            .iter()
+            .skip((position & subtree_mask).count_ones() as usize);
+        let mut range_end = subtree_index;
+        for k in 0..(DEPTH - shard_height) {
+            if subtree_index & (1 << k) == 0 {
+                continue;
+            }
+            let ommer = match ommers.next() {
+                Some(ommer) => ommer,
+                None => break,
+            };
+            let range_start = range_end - (1 << k);
+            if range_start >= start_index && range_end <= end_index {
+                let range =
+                    (range_start - start_index) as usize..(range_end - start_index) as usize;
+                let mut layer = roots[range.clone()]
+                    .iter()
+                    .map(|root| root.root_hash().clone())
+                    .collect::<Vec<_>>();
+                for level in shard_height..(shard_height + k) {
+                    layer = layer
+                        .chunks(2)
+                        .map(|pair| H::combine(Level::from(level), &pair[0], &pair[1]))
+                        .collect();
+                }
+                if layer[0] != *ommer {
+                    return Err(range_start);
+                }
+                verified[range].iter_mut().for_each(|v| *v = true);
+            }
+            range_end = range_start;
+        }
+    }
+
+    // A root that is hashed together with subtrees that are not in `roots` cannot be checked.
+    match verified.iter().position(|v| !v) {
+        Some(i) => Err(start_index + i as u64),
+        None => Ok(()),
+    }
+}
+
+/// An error indicating that a subtree root obtained from a light wallet server is not
+/// consistent with the final note commitment tree state of a [`ChainState`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubtreeRootMismatch {
+    protocol: ShieldedProtocol,
+    index: u64,
+}
+
+impl SubtreeRootMismatch {
+    /// Returns the shielded protocol of the note commitment tree to which the root belongs.
+    pub fn protocol(&self) -> ShieldedProtocol {
+        self.protocol
+    }
+
+    /// Returns the index of the subtree whose root failed verification.
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+}
+
+impl fmt::Display for SubtreeRootMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The root of {:?} subtree {} is inconsistent with the note commitment tree frontier",
+            self.protocol, self.index
+        )
+    }
+}
+
+impl std::error::Error for SubtreeRootMismatch {}
+
 /// Scans at most `limit` blocks from the provided block source for in order to find transactions
 /// received by the accounts tracked in the provided wallet database.
 ///
@@ -595,9 +933,12 @@ where
 {
     assert_eq!(from_height, from_state.block_height + 1);
 
-    // Fetch the UnifiedFullViewingKeys we are tracking
+    // Fetch the UnifiedFullViewingKeys of the accounts we are scanning for
+    let scan_limit_height = BlockHeight::from(
+        u32::from(from_height).saturating_add(u32::try_from(limit).unwrap_or(u32::MAX)),
+    );
     let account_ufvks = data_db
-        .get_unified_full_viewing_keys()
+        .get_unified_full_viewing_keys_for_scanning(&(from_height..scan_limit_height))
         .map_err(Error::Wallet)?;
     let scanning_keys = ScanningKeys::from_account_ufvks(account_ufvks);
     let mut runners = BatchRunners::<_, (), ()>::for_keys(100, &scanning_keys);
@@ -625,6 +966,12 @@ where
             .get_orchard_nullifiers(NullifierQuery::Unspent)
             .map_err(Error::Wallet)?,
     );
+    #[cfg(feature = "transparent-inputs")]
+    nullifiers.extend_transparent(
+        data_db
+            .get_unspent_transparent_outpoints()
+            .map_err(Error::Wallet)?,
+    );
 
     let mut scanned_blocks = vec![];
     let mut scan_summary = ScanSummary::for_range(from_height..from_height);
@@ -681,6 +1028,18 @@ where
                 }));
             }
 
+            #[cfg(feature = "transparent-inputs")]
+            {
+                let transparent_spent: Vec<&OutPoint> = scanned_block
+                    .transactions
+                    .iter()
+                    .flat_map(|tx| tx.transparent_spends().iter().map(|spend| spend.nf()))
+                    .collect();
+
+                nullifiers
+                    .retain_transparent(|(_, outpoint)| !transparent_spent.contains(&outpoint));
+            }
+
             prior_block_metadata = Some(scanned_block.to_block_metadata());
             scanned_blocks.push(scanned_block);
 
@@ -721,3 +1080,131 @@ pub mod testing {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use incrementalmerkletree::{frontier::Frontier, Hashable, Level};
+    use zcash_primitives::{block::BlockHash, consensus::BlockHeight, merkle_tree::HashSer};
+
+    use super::{verify_subtree_roots, ChainTipTracker, CommitmentTreeRoot, TipChange};
+
+    #[test]
+    fn chain_tip_tracker_reports_changes() {
+        let mut tracker = ChainTipTracker::new(10);
+        assert_eq!(tracker.tip(), None);
+        assert_eq!(tracker.stable_height(), None);
+        assert!(!tracker.is_expired(BlockHeight::from(1)));
+
+        let h = BlockHeight::from;
+        assert_eq!(
+            tracker.observe(h(100), BlockHash([1; 32])),
+            TipChange::Advanced { previous: None }
+        );
+        assert_eq!(tracker.stable_height(), Some(h(90)));
+        assert_eq!(
+            tracker.observe(h(100), BlockHash([1; 32])),
+            TipChange::Unchanged
+        );
+        assert_eq!(
+            tracker.observe(h(105), BlockHash([2; 32])),
+            TipChange::Advanced {
+                previous: Some(h(100))
+            }
+        );
+        assert_eq!(tracker.stable_height(), Some(h(95)));
+
+        // A reorg to a different block at the same height, or to a lower tip, does not lower
+        // the stable height.
+        assert_eq!(
+            tracker.observe(h(105), BlockHash([3; 32])),
+            TipChange::Reorg { previous: h(105) }
+        );
+        assert_eq!(
+            tracker.observe(h(103), BlockHash([4; 32])),
+            TipChange::Reorg { previous: h(105) }
+        );
+        assert_eq!(tracker.tip(), Some((h(103), BlockHash([4; 32]))));
+        assert_eq!(tracker.stable_height(), Some(h(95)));
+
+        assert!(tracker.is_expired(h(95)));
+        assert!(!tracker.is_expired(h(96)));
+        assert!(!tracker.is_expired(h(0)));
+    }
+
+    #[test]
+    fn subtree_roots_verified_against_frontier() {
+        // Use subtrees of four leaves, so that the frontier has ommers above the shard level.
+        const SHARD_HEIGHT: u8 = 2;
+        let leaf = |i: u8| {
+            let mut bytes = [0u8; 32];
+            bytes[0] = i;
+            sapling::Node::read(&bytes[..]).unwrap()
+        };
+        let subtree_root = |i: u8| {
+            let pair = |j: u8| sapling::Node::combine(Level::from(0), &leaf(j), &leaf(j + 1));
+            sapling::Node::combine(Level::from(1), &pair(4 * i), &pair(4 * i + 2))
+        };
+        let frontier = |leaves: u8| {
+            let mut frontier =
+                Frontier::<sapling::Node, { sapling::NOTE_COMMITMENT_TREE_DEPTH }>::empty();
+            for i in 0..leaves {
+                frontier.append(leaf(i));
+            }
+            frontier
+        };
+        let h = BlockHeight::from(100);
+        let roots = |count: u8| {
+            (0..count)
+                .map(|i| CommitmentTreeRoot::from_parts(h, subtree_root(i)))
+                .collect::<Vec<_>>()
+        };
+
+        // Six complete subtrees, and a partial seventh.
+        let partial = frontier(26);
+        assert_eq!(
+            verify_subtree_roots(&partial, SHARD_HEIGHT, h, 0, &roots(6)),
+            Ok(())
+        );
+
+        // Seven complete subtrees, the last of which is completed by the frontier's leaf.
+        let complete = frontier(28);
+        assert_eq!(
+            verify_subtree_roots(&complete, SHARD_HEIGHT, h, 0, &roots(7)),
+            Ok(())
+        );
+
+        // A tampered root is detected wherever it appears, not only beside the frontier.
+        for i in 0..6 {
+            let mut tampered = roots(6);
+            tampered[i] = CommitmentTreeRoot::from_parts(h, leaf(200));
+            assert!(verify_subtree_roots(&partial, SHARD_HEIGHT, h, 0, &tampered).is_err());
+        }
+
+        // Swapping two roots changes the ommer above them.
+        let mut swapped = roots(6);
+        swapped.swap(0, 3);
+        assert_eq!(
+            verify_subtree_roots(&partial, SHARD_HEIGHT, h, 0, &swapped),
+            Err(0)
+        );
+
+        // Omitting a root leaves the other root beneath the same ommer unverified.
+        assert_eq!(
+            verify_subtree_roots(&partial, SHARD_HEIGHT, h, 0, &roots(5)),
+            Err(4)
+        );
+
+        // Roots may not claim to complete a subtree that is not yet complete, or to have been
+        // completed after the frontier's block.
+        assert_eq!(
+            verify_subtree_roots(&partial, SHARD_HEIGHT, h, 0, &roots(7)),
+            Err(6)
+        );
+        let mut late = roots(6);
+        late[5] = CommitmentTreeRoot::from_parts(h + 1, subtree_root(5));
+        assert_eq!(
+            verify_subtree_roots(&partial, SHARD_HEIGHT, h, 0, &late),
+            Err(5)
+        );
+    }
+}
@@ -3,6 +3,8 @@
 use std::error;
 use std::fmt::{self, Debug, Display};
 
+use zcash_primitives::consensus::BlockHeight;
+
 use crate::scanning::ScanError;
 
 /// Errors related to chain validation and scanning.
@@ -21,6 +23,25 @@ pub enum Error<WalletError, BlockSourceError> {
     Scan(ScanError),
 }
 
+impl<WE, BE> Error<WE, BE> {
+    /// Returns whether this error is the result of a failed chain continuity check, meaning
+    /// that the wallet's view of the chain has diverged from the blocks being scanned.
+    pub fn is_continuity_error(&self) -> bool {
+        matches!(self, Error::Scan(e) if e.is_continuity_error())
+    }
+
+    /// Returns the height to which the wallet and block cache should be truncated in order to
+    /// recover from this error, or `None` if this is not a continuity error.
+    ///
+    /// See [`ScanError::suggested_rewind_height`] for details.
+    pub fn suggested_rewind_height(&self) -> Option<BlockHeight> {
+        match self {
+            Error::Scan(e) => e.suggested_rewind_height(),
+            _ => None,
+        }
+    }
+}
+
 impl<WE: fmt::Display, BE: fmt::Display> fmt::Display for Error<WE, BE> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match &self {
@@ -1,5 +1,6 @@
 //! Types for wallet error handling.
 
+use std::convert::Infallible;
 use std::error;
 use std::fmt::{self, Debug, Display};
 
@@ -12,7 +13,10 @@ use zcash_primitives::transaction::{
 };
 
 use crate::address::UnifiedAddress;
-use crate::data_api::wallet::input_selection::InputSelectorError;
+use crate::data_api::wallet::{
+    input_selection::InputSelectorError, FeeGuardrailViolation, PrivacyPolicy, PrivacyViolation,
+    RecipientRejection,
+};
 use crate::proposal::ProposalError;
 use crate::PoolType;
 
@@ -23,7 +27,14 @@ use crate::wallet::NoteId;
 
 /// Errors that can occur as a consequence of wallet operations.
 #[derive(Debug)]
-pub enum Error<DataSourceError, CommitmentTreeError, SelectionError, FeeError> {
+pub enum Error<
+    DataSourceError,
+    CommitmentTreeError,
+    SelectionError,
+    FeeError,
+    SignerError = Infallible,
+    PolicyError = Infallible,
+> {
     /// An error occurred retrieving data from the underlying data source
     DataSource(DataSourceError),
 
@@ -85,16 +96,63 @@ pub enum Error<DataSourceError, CommitmentTreeError, SelectionError, FeeError> {
     /// An error occurred parsing the address from a payment request.
     Address(ConversionError<&'static str>),
 
+    /// The proposed transaction violates the configured fee guardrails.
+    FeeGuardrail(FeeGuardrailViolation),
+
+    /// The proposal was vetoed by a [`SpendPolicyHook`].
+    ///
+    /// [`SpendPolicyHook`]: crate::data_api::wallet::SpendPolicyHook
+    SpendPolicy(PolicyError),
+
+    /// The [`Signer`] was unable to authorize a spend in the transaction.
+    ///
+    /// [`Signer`]: crate::data_api::wallet::Signer
+    Signer(SignerError),
+
+    /// The proposal would reveal information that is not permitted by the requested
+    /// privacy policy.
+    PrivacyPolicy {
+        policy: PrivacyPolicy,
+        violation: PrivacyViolation,
+    },
+
+    /// The payment at the given index of a batch transfer cannot be made in a transaction
+    /// having no more than the requested number of logical actions, even when it is the only
+    /// payment in the transaction.
+    ActionLimitExceeded {
+        payment_index: usize,
+        actions: usize,
+        limit: usize,
+    },
+
+    /// The proposal spends inputs that are already reserved by another prepared proposal.
+    ///
+    /// The fields contain the identifiers of the conflicting shielded notes and the outpoints
+    /// of the conflicting transparent outputs.
+    ConflictingProposal {
+        notes: Vec<NoteId>,
+        outpoints: Vec<transparent::OutPoint>,
+    },
+
+    /// The payment at the given index of a transaction request is to an address that is
+    /// structurally valid, but is known to be unusable.
+    InvalidRecipient {
+        payment_index: usize,
+        reason: RecipientRejection,
+    },
+
     #[cfg(feature = "transparent-inputs")]
     AddressNotRecognized(TransparentAddress),
 }
 
-impl<DE, CE, SE, FE> fmt::Display for Error<DE, CE, SE, FE>
+impl<DE, CE, SE, FE, SgE, PE> fmt::Display for Error<DE, CE, SE, FE, SgE, PE>
 where
     DE: fmt::Display,
     CE: fmt::Display,
     SE: fmt::Display,
     FE: fmt::Display,
+    SgE: fmt::Display,
+    PE: fmt::Display,
 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -152,6 +210,43 @@ where
             Error::Address(e) => {
                 write!(f, "An error occurred decoding the address from a payment request: {}.", e)
             }
+            Error::FeeGuardrail(e) => {
+                write!(f, "The proposed transaction was rejected by the fee guardrails: {}", e)
+            }
+            Error::SpendPolicy(e) => {
+                write!(f, "The proposal was rejected by the spending policy: {}", e)
+            }
+            Error::Signer(e) => {
+                write!(f, "The signer could not authorize a spend: {}", e)
+            }
+            Error::PrivacyPolicy { policy, violation } => write!(
+                f,
+                "The proposal is not permitted by the {:?} privacy policy: {}",
+                policy, violation
+            ),
+            Error::ActionLimitExceeded {
+                payment_index,
+                actions,
+                limit,
+            } => write!(
+                f,
+                "Payment {} requires a transaction with {} logical actions, which exceeds the limit of {}.",
+                payment_index, actions, limit
+            ),
+            Error::ConflictingProposal { notes, outpoints } => write!(
+                f,
+                "The proposal spends {} note(s) and {} transparent output(s) already reserved by another proposal.",
+                notes.len(),
+                outpoints.len()
+            ),
+            Error::InvalidRecipient {
+                payment_index,
+                reason,
+            } => write!(
+                f,
+                "Payment {} cannot be made to its recipient: {}",
+                payment_index, reason
+            ),
             #[cfg(feature = "transparent-inputs")]
             Error::AddressNotRecognized(_) => {
                 write!(f, "The specified transparent address was not recognized as belonging to the wallet.")
@@ -160,12 +255,14 @@ where
     }
 }
 
-impl<DE, CE, SE, FE> error::Error for Error<DE, CE, SE, FE>
+impl<DE, CE, SE, FE, SgE, PE> error::Error for Error<DE, CE, SE, FE, SgE, PE>
 where
     DE: Debug + Display + error::Error + 'static,
     CE: Debug + Display + error::Error + 'static,
     SE: Debug + Display + error::Error + 'static,
     FE: Debug + Display + 'static,
+    SgE: Debug + Display + error::Error + 'static,
+    PE: Debug + Display + error::Error + 'static,
 {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match &self {
@@ -174,30 +271,50 @@ where
             Error::NoteSelection(e) => Some(e),
             Error::Proposal(e) => Some(e),
             Error::Builder(e) => Some(e),
+            Error::FeeGuardrail(e) => Some(e),
+            Error::SpendPolicy(e) => Some(e),
+            Error::Signer(e) => Some(e),
+            Error::PrivacyPolicy { violation, .. } => Some(violation),
+            Error::InvalidRecipient { reason, .. } => Some(reason),
             _ => None,
         }
     }
 }
 
-impl<DE, CE, SE, FE> From<builder::Error<FE>> for Error<DE, CE, SE, FE> {
+impl<DE, CE, SE, FE, SgE, PE> Error<DE, CE, SE, FE, SgE, PE> {
+    /// Converts an error produced while building a transaction whose spends are authorized
+    /// by a [`Signer`], separating failures of the signer from other build errors.
+    ///
+    /// [`Signer`]: crate::data_api::wallet::Signer
+    pub(crate) fn from_signed_build(e: builder::Error<FE, SgE>) -> Self {
+        match e.authorization_error() {
+            Ok(e) => Error::Signer(e),
+            Err(e) => Error::Builder(e),
+        }
+    }
+}
+
+impl<DE, CE, SE, FE, SgE, PE> From<builder::Error<FE>> for Error<DE, CE, SE, FE, SgE, PE> {
     fn from(e: builder::Error<FE>) -> Self {
         Error::Builder(e)
     }
 }
 
-impl<DE, CE, SE, FE> From<BalanceError> for Error<DE, CE, SE, FE> {
+impl<DE, CE, SE, FE, SgE, PE> From<BalanceError> for Error<DE, CE, SE, FE, SgE, PE> {
     fn from(e: BalanceError) -> Self {
         Error::BalanceError(e)
     }
 }
 
-impl<DE, CE, SE, FE> From<ConversionError<&'static str>> for Error<DE, CE, SE, FE> {
+impl<DE, CE, SE, FE, SgE, PE> From<ConversionError<&'static str>>
+    for Error<DE, CE, SE, FE, SgE, PE>
+{
     fn from(value: ConversionError<&'static str>) -> Self {
         Error::Address(value)
     }
 }
 
-impl<DE, CE, SE, FE> From<InputSelectorError<DE, SE>> for Error<DE, CE, SE, FE> {
+impl<DE, CE, SE, FE, SgE, PE> From<InputSelectorError<DE, SE>> for Error<DE, CE, SE, FE, SgE, PE> {
     fn from(e: InputSelectorError<DE, SE>) -> Self {
         match e {
             InputSelectorError::DataSource(e) => Error::DataSource(e),
@@ -216,19 +333,19 @@ impl<DE, CE, SE, FE> From<InputSelectorError<DE, SE>> for Error<DE, CE, SE, FE>
     }
 }
 
-impl<DE, CE, SE, FE> From<sapling::builder::Error> for Error<DE, CE, SE, FE> {
+impl<DE, CE, SE, FE, SgE, PE> From<sapling::builder::Error> for Error<DE, CE, SE, FE, SgE, PE> {
     fn from(e: sapling::builder::Error) -> Self {
         Error::Builder(builder::Error::SaplingBuild(e))
     }
 }
 
-impl<DE, CE, SE, FE> From<transparent::builder::Error> for Error<DE, CE, SE, FE> {
+impl<DE, CE, SE, FE, SgE, PE> From<transparent::builder::Error> for Error<DE, CE, SE, FE, SgE, PE> {
     fn from(e: transparent::builder::Error) -> Self {
         Error::Builder(builder::Error::TransparentBuild(e))
     }
 }
 
-impl<DE, CE, SE, FE> From<ShardTreeError<CE>> for Error<DE, CE, SE, FE> {
+impl<DE, CE, SE, FE, SgE, PE> From<ShardTreeError<CE>> for Error<DE, CE, SE, FE, SgE, PE> {
     fn from(e: ShardTreeError<CE>) -> Self {
         Error::CommitmentTree(e)
     }
@@ -0,0 +1,193 @@
+//! Metadata for payments that recur on a fixed schedule.
+//!
+//! A [`ScheduledPayment`] records a ZIP 321 [`Payment`] together with the time at which it is
+//! first due and the interval at which it recurs. Wallets that persist scheduled payments return
+//! those that have fallen due from [`WalletRead::payments_due`]; the application then converts
+//! each of them into a [`TransactionRequest`] with
+//! [`ScheduledPayment::to_transaction_request`], proposes and creates the transaction as usual,
+//! and records that the payment was made so that the schedule advances to its next due time.
+//!
+//! Due times are computed from the first due time of the schedule, rather than from the previous
+//! due time, so that a monthly payment that is first due on the 31st of a month is due on the
+//! last day of each shorter month and returns to the 31st thereafter.
+//!
+//! [`WalletRead::payments_due`]: super::WalletRead::payments_due
+
+use std::num::NonZeroU32;
+
+use time::{util::days_in_year_month, Date, Duration, Month, OffsetDateTime};
+use zip321::{Payment, TransactionRequest, Zip321Error};
+
+/// The identifier of a [`ScheduledPayment`] within a wallet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ScheduledPaymentId(u64);
+
+impl From<u64> for ScheduledPaymentId {
+    fn from(id: u64) -> Self {
+        ScheduledPaymentId(id)
+    }
+}
+
+impl From<ScheduledPaymentId> for u64 {
+    fn from(id: ScheduledPaymentId) -> Self {
+        id.0
+    }
+}
+
+/// The interval at which a [`ScheduledPayment`] recurs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PaymentInterval {
+    /// The payment recurs every given number of days.
+    Days(NonZeroU32),
+    /// The payment recurs every given number of calendar months, on the same day of the month
+    /// as its first due time (or on the last day of the month, for months that are too short).
+    Months(NonZeroU32),
+}
+
+impl PaymentInterval {
+    /// Returns the time that is `n` intervals after `start`, or `None` if that time cannot be
+    /// represented.
+    pub fn nth_after(&self, start: OffsetDateTime, n: u32) -> Option<OffsetDateTime> {
+        match self {
+            PaymentInterval::Days(days) => {
+                start.checked_add(Duration::days(i64::from(days.get()) * i64::from(n)))
+            }
+            PaymentInterval::Months(months) => {
+                let start_date = start.date();
+                let month_index = i64::from(start_date.year()) * 12
+                    + i64::from(u8::from(start_date.month()) - 1)
+                    + i64::from(months.get()) * i64::from(n);
+                let year = i32::try_from(month_index.div_euclid(12)).ok()?;
+                let month =
+                    Month::try_from(u8::try_from(month_index.rem_euclid(12) + 1).ok()?).ok()?;
+                let day = std::cmp::min(start_date.day(), days_in_year_month(year, month));
+                let date = Date::from_calendar_date(year, month, day).ok()?;
+                Some(start.replace_date(date))
+            }
+        }
+    }
+}
+
+/// A payment that recurs on a fixed schedule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScheduledPayment<AccountId> {
+    id: ScheduledPaymentId,
+    account_id: AccountId,
+    payment: Payment,
+    interval: PaymentInterval,
+    first_due: OffsetDateTime,
+    payments_made: u32,
+}
+
+impl<AccountId: Copy> ScheduledPayment<AccountId> {
+    /// Constructs a scheduled payment from its constituent parts.
+    pub fn from_parts(
+        id: ScheduledPaymentId,
+        account_id: AccountId,
+        payment: Payment,
+        interval: PaymentInterval,
+        first_due: OffsetDateTime,
+        payments_made: u32,
+    ) -> Self {
+        ScheduledPayment {
+            id,
+            account_id,
+            payment,
+            interval,
+            first_due,
+            payments_made,
+        }
+    }
+
+    /// Returns the identifier of the scheduled payment.
+    pub fn id(&self) -> ScheduledPaymentId {
+        self.id
+    }
+
+    /// Returns the account from which the payment is to be made.
+    pub fn account_id(&self) -> AccountId {
+        self.account_id
+    }
+
+    /// Returns the payment that is made on each due time.
+    pub fn payment(&self) -> &Payment {
+        &self.payment
+    }
+
+    /// Returns the interval at which the payment recurs.
+    pub fn interval(&self) -> PaymentInterval {
+        self.interval
+    }
+
+    /// Returns the time at which the first payment was due.
+    pub fn first_due(&self) -> OffsetDateTime {
+        self.first_due
+    }
+
+    /// Returns the number of payments that have been recorded as made.
+    pub fn payments_made(&self) -> u32 {
+        self.payments_made
+    }
+
+    /// Returns the time at which the next payment is due, or `None` if that time cannot be
+    /// represented.
+    pub fn next_due(&self) -> Option<OffsetDateTime> {
+        self.interval.nth_after(self.first_due, self.payments_made)
+    }
+
+    /// Returns the ZIP 321 transaction request for the next payment.
+    pub fn to_transaction_request(&self) -> Result<TransactionRequest, Zip321Error> {
+        TransactionRequest::new(vec![self.payment.clone()])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU32;
+
+    use time::{Date, Month, OffsetDateTime, Time};
+
+    use super::PaymentInterval;
+
+    fn at_noon(year: i32, month: Month, day: u8) -> OffsetDateTime {
+        Date::from_calendar_date(year, month, day)
+            .unwrap()
+            .with_time(Time::from_hms(12, 0, 0).unwrap())
+            .assume_utc()
+    }
+
+    #[test]
+    fn monthly_interval_clamps_to_month_end() {
+        let interval = PaymentInterval::Months(NonZeroU32::new(1).unwrap());
+        let start = at_noon(2023, Month::January, 31);
+
+        assert_eq!(interval.nth_after(start, 0), Some(start));
+        assert_eq!(
+            interval.nth_after(start, 1),
+            Some(at_noon(2023, Month::February, 28))
+        );
+        assert_eq!(
+            interval.nth_after(start, 2),
+            Some(at_noon(2023, Month::March, 31))
+        );
+        assert_eq!(
+            interval.nth_after(start, 13),
+            Some(at_noon(2024, Month::February, 29))
+        );
+    }
+
+    #[test]
+    fn daily_interval() {
+        let interval = PaymentInterval::Days(NonZeroU32::new(7).unwrap());
+        let start = at_noon(2023, Month::December, 27);
+
+        assert_eq!(
+            interval.nth_after(start, 1),
+            Some(at_noon(2024, Month::January, 3))
+        );
+        assert_eq!(
+            interval.nth_after(start, 3),
+            Some(at_noon(2024, Month::January, 17))
+        );
+    }
+}
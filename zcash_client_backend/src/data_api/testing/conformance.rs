@@ -0,0 +1,334 @@
+//! A conformance test suite for wallet backends.
+//!
+//! The tests in this module exercise a wallet through the [`WalletRead`], [`WalletWrite`], and
+//! [`WalletCommitmentTrees`] traits. Each test scans a synthetic chain of compact blocks, and
+//! checks that the wallet's view of the chain, its balances, and its note commitment trees
+//! remain consistent with that chain as notes are received and spent, and as the chain is
+//! reorganized. A wallet backend can run these tests against its own implementation of the
+//! traits to demonstrate that it behaves equivalently to the reference implementation in
+//! `zcash_client_sqlite`.
+//!
+//! Each test must be given a newly initialized wallet for [`Network::TestNetwork`] that contains
+//! no accounts. Tests panic if any check fails.
+
+use std::{collections::BTreeMap, convert::Infallible};
+
+use rand_core::{OsRng, RngCore};
+use sapling::{zip32::DiversifiableFullViewingKey, Nullifier};
+use secrecy::SecretVec;
+use shardtree::error::ShardTreeError;
+use subtle::ConditionallySelectable;
+use zcash_primitives::{
+    block::BlockHash,
+    consensus::{BlockHeight, Network, NetworkUpgrade, Parameters},
+    transaction::components::amount::NonNegativeAmount,
+};
+
+use crate::{
+    data_api::{
+        chain::{error::Error, scan_cached_blocks, BlockSource, ChainState},
+        AccountBalance, AccountBirthday, NullifierQuery, WalletCommitmentTrees, WalletRead,
+        WalletWrite,
+    },
+    proto::compact_formats::CompactBlock,
+    scanning::testing::fake_compact_block,
+};
+
+/// A synthetic chain of compact blocks, along with the state of the chain as of the end of each
+/// block.
+struct FakeChain {
+    prior_state: ChainState,
+    blocks: BTreeMap<BlockHeight, (CompactBlock, ChainState)>,
+}
+
+impl FakeChain {
+    /// Constructs a chain containing no blocks, that begins after the given chain state.
+    fn new(prior_state: ChainState) -> Self {
+        Self {
+            prior_state,
+            blocks: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the state of the chain as of the end of its last block.
+    fn tip_state(&self) -> &ChainState {
+        self.blocks
+            .values()
+            .next_back()
+            .map_or(&self.prior_state, |(_, state)| state)
+    }
+
+    /// Returns the state of the chain as of the end of the block at the given height.
+    fn state_at(&self, height: BlockHeight) -> &ChainState {
+        if height == self.prior_state.block_height() {
+            &self.prior_state
+        } else {
+            &self.blocks[&height].1
+        }
+    }
+
+    /// Appends a block containing a transaction that reveals the nullifier `nf` and pays `value`
+    /// to the default address of `dfvk`, and returns the height of the new block.
+    fn push_block(
+        &mut self,
+        nf: Nullifier,
+        dfvk: &DiversifiableFullViewingKey,
+        value: NonNegativeAmount,
+    ) -> BlockHeight {
+        let prior_state = self.tip_state().clone();
+        let height = prior_state.block_height() + 1;
+        let sapling_tree_size = u32::try_from(prior_state.final_sapling_tree().tree_size())
+            .expect("Sapling note commitment tree size fits in a u32");
+
+        // Synthetic blocks contain no Orchard actions, so the Orchard tree remains empty.
+        let cb = fake_compact_block(
+            height,
+            prior_state.block_hash(),
+            nf,
+            dfvk,
+            value,
+            false,
+            Some((sapling_tree_size, 0)),
+        );
+
+        let final_sapling_tree = cb.vtx.iter().flat_map(|tx| tx.outputs.iter()).fold(
+            prior_state.final_sapling_tree().clone(),
+            |mut acc, output| {
+                acc.append(sapling::Node::from_cmu(&output.cmu().unwrap()));
+                acc
+            },
+        );
+        let state = ChainState::new(
+            height,
+            cb.hash(),
+            final_sapling_tree,
+            #[cfg(feature = "orchard")]
+            prior_state.final_orchard_tree().clone(),
+        );
+
+        self.blocks.insert(height, (cb, state));
+        height
+    }
+
+    /// Removes all blocks above the given height from the chain.
+    fn truncate_to_height(&mut self, height: BlockHeight) {
+        self.blocks
+            .retain(|block_height, _| *block_height <= height);
+    }
+}
+
+impl BlockSource for FakeChain {
+    type Error = Infallible;
+
+    fn with_blocks<F, WalletErrT>(
+        &self,
+        from_height: Option<BlockHeight>,
+        limit: Option<usize>,
+        mut with_block: F,
+    ) -> Result<(), Error<WalletErrT, Self::Error>>
+    where
+        F: FnMut(CompactBlock) -> Result<(), Error<WalletErrT, Self::Error>>,
+    {
+        for (block, _) in self
+            .blocks
+            .range(from_height.unwrap_or_else(|| BlockHeight::from_u32(0))..)
+            .map(|(_, entry)| entry)
+            .take(limit.unwrap_or(usize::MAX))
+        {
+            with_block(block.clone())?;
+        }
+
+        Ok(())
+    }
+}
+
+fn random_nullifier() -> Nullifier {
+    let mut nf = [0; 32];
+    OsRng.fill_bytes(&mut nf);
+    Nullifier(nf)
+}
+
+/// Adds an account having its birthday at Sapling activation to the wallet.
+///
+/// Returns the identifier of the account, its Sapling viewing key, and an empty chain that
+/// begins at the account's birthday height.
+fn add_account<DbT: WalletWrite>(
+    db: &mut DbT,
+) -> (
+    <DbT as WalletRead>::AccountId,
+    DiversifiableFullViewingKey,
+    FakeChain,
+) {
+    let prior_state = ChainState::empty(
+        Network::TestNetwork
+            .activation_height(NetworkUpgrade::Sapling)
+            .unwrap()
+            - 1,
+        BlockHash([0; 32]),
+    );
+    let seed = SecretVec::new(vec![0; 32]);
+    let (account, usk) = db
+        .create_account(
+            &seed,
+            &AccountBirthday::from_parts(prior_state.clone(), None),
+        )
+        .unwrap();
+
+    (
+        account,
+        usk.sapling().to_diversifiable_full_viewing_key(),
+        FakeChain::new(prior_state),
+    )
+}
+
+/// Updates the wallet's view of the chain tip to the tip of `chain`, and then scans the blocks of
+/// `chain` from `from_height` to its tip.
+fn scan_to_tip<DbT>(db: &mut DbT, chain: &FakeChain, from_height: BlockHeight)
+where
+    DbT: WalletWrite,
+    <DbT as WalletRead>::AccountId: ConditionallySelectable + Default + Send + 'static,
+{
+    let tip_height = chain.tip_state().block_height();
+    db.update_chain_tip(tip_height).unwrap();
+    scan_cached_blocks(
+        &Network::TestNetwork,
+        chain,
+        db,
+        from_height,
+        chain.state_at(from_height - 1),
+        (u32::from(tip_height) - u32::from(from_height) + 1) as usize,
+    )
+    .unwrap();
+}
+
+/// Checks that the wallet has scanned the whole of `chain`, and that the root of its Sapling note
+/// commitment tree as of the tip of `chain` is correct.
+///
+/// Returns the balance of the given account, after checking that it is internally consistent.
+fn check_invariants<DbT>(
+    db: &mut DbT,
+    chain: &FakeChain,
+    account: <DbT as WalletRead>::AccountId,
+) -> AccountBalance
+where
+    DbT: WalletRead + WalletCommitmentTrees,
+{
+    let tip = chain.tip_state();
+    assert_eq!(
+        db.get_block_hash(tip.block_height()).unwrap(),
+        Some(tip.block_hash())
+    );
+    assert_eq!(db.get_block_hash(tip.block_height() + 1).unwrap(), None);
+
+    let summary = db
+        .get_wallet_summary(1)
+        .unwrap()
+        .expect("the wallet has a summary once its chain tip is known");
+    assert_eq!(summary.chain_tip_height(), tip.block_height());
+    assert_eq!(summary.fully_scanned_height(), tip.block_height());
+
+    let balance = *summary
+        .account_balances()
+        .get(&account)
+        .expect("the account has a balance");
+    assert_eq!(
+        balance.sapling_balance().total()
+            + balance.orchard_balance().total()
+            + balance.unshielded()
+            + balance.unshielded_pending_maturity(),
+        Some(balance.total())
+    );
+    assert!(balance.sapling_balance().spendable_value() <= balance.sapling_balance().total());
+
+    let root = db
+        .with_sapling_tree_mut::<_, _, ShardTreeError<<DbT as WalletCommitmentTrees>::Error>>(
+            |tree| tree.root_at_checkpoint_id(&tip.block_height()),
+        )
+        .unwrap();
+    assert_eq!(root, tip.final_sapling_tree().root());
+
+    balance
+}
+
+/// Tests that notes received by an account, and spends of those notes, are reflected in the
+/// account's balance.
+pub fn receive_and_spend<DbT>(db: &mut DbT)
+where
+    DbT: WalletWrite + WalletCommitmentTrees,
+    <DbT as WalletRead>::AccountId: ConditionallySelectable + Default + Send + 'static,
+{
+    let (account, dfvk, mut chain) = add_account(db);
+
+    // Receive a note.
+    let value = NonNegativeAmount::const_from_u64(50000);
+    let h1 = chain.push_block(random_nullifier(), &dfvk, value);
+    scan_to_tip(db, &chain, h1);
+
+    let balance = check_invariants(db, &chain, account);
+    assert_eq!(balance.total(), value);
+    assert_eq!(balance.sapling_balance().spendable_value(), value);
+
+    let unspent = db.get_sapling_nullifiers(NullifierQuery::Unspent).unwrap();
+    assert_eq!(unspent.len(), 1);
+    let (nf_account, nf) = unspent[0];
+    assert_eq!(nf_account, account);
+
+    // Spend the note in a transaction that pays a second note to the account.
+    let value2 = NonNegativeAmount::const_from_u64(20000);
+    let h2 = chain.push_block(nf, &dfvk, value2);
+    scan_to_tip(db, &chain, h2);
+
+    let balance = check_invariants(db, &chain, account);
+    assert_eq!(balance.total(), value2);
+    assert_eq!(balance.sapling_balance().spendable_value(), value2);
+
+    let unspent = db.get_sapling_nullifiers(NullifierQuery::Unspent).unwrap();
+    assert_eq!(unspent.len(), 1);
+    assert_ne!(unspent[0].1, nf);
+}
+
+/// Tests that the wallet follows a reorganization of the chain, after which notes received in
+/// blocks that are no longer part of the chain are not spendable.
+pub fn reorg<DbT>(db: &mut DbT)
+where
+    DbT: WalletWrite + WalletCommitmentTrees,
+    <DbT as WalletRead>::AccountId: ConditionallySelectable + Default + Send + 'static,
+{
+    let (account, dfvk, mut chain) = add_account(db);
+
+    let value = NonNegativeAmount::const_from_u64(50000);
+    let h1 = chain.push_block(random_nullifier(), &dfvk, value);
+    chain.push_block(
+        random_nullifier(),
+        &dfvk,
+        NonNegativeAmount::const_from_u64(60000),
+    );
+    chain.push_block(
+        random_nullifier(),
+        &dfvk,
+        NonNegativeAmount::const_from_u64(70000),
+    );
+    scan_to_tip(db, &chain, h1);
+
+    let balance = check_invariants(db, &chain, account);
+    assert_eq!(
+        balance.sapling_balance().spendable_value(),
+        NonNegativeAmount::const_from_u64(180000)
+    );
+
+    // Replace the blocks above `h1` with a shorter fork.
+    db.truncate_to_height(h1).unwrap();
+    chain.truncate_to_height(h1);
+    let fork_value = NonNegativeAmount::const_from_u64(10000);
+    let h2 = chain.push_block(random_nullifier(), &dfvk, fork_value);
+    scan_to_tip(db, &chain, h2);
+
+    // Notes received in the fork are spendable; those received in the blocks it replaced are not.
+    // Backends may continue to report the latter as part of the total balance, as the
+    // transactions that created them may yet be mined.
+    let balance = check_invariants(db, &chain, account);
+    assert_eq!(
+        balance.sapling_balance().spendable_value(),
+        (value + fork_value).unwrap()
+    );
+}
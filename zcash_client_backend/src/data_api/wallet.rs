@@ -34,12 +34,14 @@ to a wallet-internal shielded address, as described in [ZIP 316](https://zips.z.
 //! [`propose_transfer`]: crate::data_api::wallet::propose_transfer
 
 use nonempty::NonEmpty;
-use rand_core::OsRng;
+use rand_core::{CryptoRng, OsRng, RngCore};
 use sapling::{
     note_encryption::{try_sapling_note_decryption, PreparedIncomingViewingKey},
     prover::{OutputProver, SpendProver},
 };
-use std::num::NonZeroU32;
+use std::{cmp::max, collections::BTreeSet, convert::Infallible, fmt, num::NonZeroU32};
+use zcash_address::ConversionError;
+use zcash_encoding::CompactSize;
 
 use super::InputSource;
 use crate::{
@@ -50,20 +52,32 @@ use crate::{
     },
     decrypt_transaction,
     fees::{self, DustOutputPolicy},
-    keys::UnifiedSpendingKey,
+    keys::{UnifiedFullViewingKey, UnifiedSpendingKey},
     proposal::{self, Proposal, ProposalError},
     wallet::{Note, OvkPolicy, Recipient},
     zip321::{self, Payment},
     PoolType, ShieldedProtocol,
 };
+use zcash_primitives::legacy::TransparentAddress;
 use zcash_primitives::transaction::{
-    builder::{BuildConfig, BuildResult, Builder},
-    components::{amount::NonNegativeAmount, sapling::zip212_enforcement},
-    fees::{zip317::FeeError as Zip317FeeError, FeeRule, StandardFeeRule},
+    builder::{
+        self, BuildConfig, BuildResult, Builder, OrchardPartiallyAuthorized,
+        SaplingPartiallyAuthorized, SpendAuthorizer,
+    },
+    components::{
+        amount::{Amount, BalanceError, NonNegativeAmount},
+        sapling::zip212_enforcement,
+        OutPoint,
+    },
+    fees::{
+        transparent::OutputView,
+        zip317::{self, FeeError as Zip317FeeError},
+        FeeRule, StandardFeeRule,
+    },
     Transaction, TxId,
 };
 use zcash_protocol::{
-    consensus::{self, BlockHeight, NetworkUpgrade},
+    consensus::{self, BlockHeight, BranchId, NetworkType},
     memo::MemoBytes,
 };
 use zip32::Scope;
@@ -71,23 +85,29 @@ use zip32::Scope;
 #[cfg(feature = "transparent-inputs")]
 use {
     input_selection::ShieldingSelector,
-    std::convert::Infallible,
     zcash_keys::encoding::AddressCodec,
-    zcash_primitives::legacy::TransparentAddress,
-    zcash_primitives::transaction::components::{OutPoint, TxOut},
+    zcash_primitives::legacy::keys::{NonHardenedChildIndex, TransparentKeyScope},
+    zcash_primitives::transaction::{components::TxOut, sighash::SignatureHash},
 };
 
+pub mod batch;
 pub mod input_selection;
+pub mod reservation;
+pub mod sweep;
 use input_selection::{
     GreedyInputSelector, GreedyInputSelectorError, InputSelector, InputSelectorError,
 };
 
 /// Scans a [`Transaction`] for any information that can be decrypted by the accounts in
 /// the wallet, and saves it to the wallet.
+///
+/// `mined_height` is the height of the block in which the transaction was mined, if known;
+/// for example, as reported by the server from which the transaction was retrieved.
 pub fn decrypt_and_store_transaction<ParamsT, DbT>(
     params: &ParamsT,
     data: &mut DbT,
     tx: &Transaction,
+    mined_height: Option<BlockHeight>,
 ) -> Result<(), DbT::Error>
 where
     ParamsT: consensus::Parameters,
@@ -96,15 +116,20 @@ where
     // Fetch the UnifiedFullViewingKeys we are tracking
     let ufvks = data.get_unified_full_viewing_keys()?;
 
-    // Height is block height for mined transactions, and the "mempool height" (chain height + 1)
-    // for mempool transactions.
-    let height = data
-        .get_tx_height(tx.txid())?
-        .or(data.chain_height()?.map(|max_height| max_height + 1))
-        .or_else(|| params.activation_height(NetworkUpgrade::Sapling))
-        .expect("Sapling activation height must be known.");
+    // If the caller does not know the height at which the transaction was mined, the wallet
+    // may have learned it from scanning.
+    let mined_height = match mined_height {
+        Some(height) => Some(height),
+        None => data.get_tx_height(tx.txid())?,
+    };
 
-    data.store_decrypted_tx(decrypt_transaction(params, height, tx, &ufvks))?;
+    data.store_decrypted_tx(decrypt_transaction(
+        params,
+        mined_height,
+        data.chain_height()?,
+        tx,
+        &ufvks,
+    ))?;
 
     Ok(())
 }
@@ -165,7 +190,7 @@ where
 /// use zcash_primitives::{
 ///     consensus::{self, Network},
 ///     constants::testnet::COIN_TYPE,
-///     transaction::{TxId, components::Amount},
+///     transaction::{TxId, components::amount::NonNegativeAmount},
 ///     zip32::AccountId,
 /// };
 /// use zcash_proofs::prover::LocalTxProver;
@@ -210,7 +235,7 @@ where
 ///     tx_prover,
 ///     &usk,
 ///     &to,
-///     Amount::from_u64(1).unwrap(),
+///     NonNegativeAmount::from_u64(1).unwrap(),
 ///     None,
 ///     OvkPolicy::Sender,
 ///     10,
@@ -384,6 +409,7 @@ where
         input_selector,
         request,
         min_confirmations,
+        PrivacyPolicy::AllowFullyTransparent,
     )?;
 
     create_proposed_transactions(
@@ -397,9 +423,258 @@ where
     )
 }
 
+/// A policy describing what information a proposed transfer is permitted to reveal on chain.
+///
+/// These policies mirror the privacy policies accepted by `zcashd`'s `z_sendmany` RPC method.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PrivacyPolicy {
+    /// Only fully shielded transactions that do not move value between pools are permitted.
+    FullPrivacy,
+    /// Value may move between shielded pools, revealing the amount transferred.
+    AllowRevealedAmounts,
+    /// Payments may be made to transparent recipients, revealing their addresses and the
+    /// amounts they are paid.
+    AllowRevealedRecipients,
+    /// Transparent inputs may be spent, revealing the addresses from which funds are spent.
+    AllowRevealedSenders,
+    /// Both transparent inputs and transparent recipients are permitted.
+    AllowFullyTransparent,
+}
+
+impl PrivacyPolicy {
+    fn allows_revealed_amounts(&self) -> bool {
+        !matches!(self, PrivacyPolicy::FullPrivacy)
+    }
+
+    fn allows_revealed_recipients(&self) -> bool {
+        matches!(
+            self,
+            PrivacyPolicy::AllowRevealedRecipients | PrivacyPolicy::AllowFullyTransparent
+        )
+    }
+
+    fn allows_revealed_senders(&self) -> bool {
+        matches!(
+            self,
+            PrivacyPolicy::AllowRevealedSenders | PrivacyPolicy::AllowFullyTransparent
+        )
+    }
+
+    /// Checks the given proposal against this policy.
+    ///
+    /// Returns a description of the information that the proposal would reveal in violation of
+    /// this policy, if any.
+    pub fn check<FeeRuleT, NoteRef>(
+        &self,
+        proposal: &Proposal<FeeRuleT, NoteRef>,
+    ) -> Result<(), PrivacyViolation> {
+        for (step_index, step) in proposal.steps().iter().enumerate() {
+            if !self.allows_revealed_senders() && !step.transparent_inputs().is_empty() {
+                return Err(PrivacyViolation::RevealedSenders {
+                    step_index,
+                    outpoints: step
+                        .transparent_inputs()
+                        .iter()
+                        .map(|i| i.outpoint().clone())
+                        .collect(),
+                });
+            }
+
+            if !self.allows_revealed_recipients() {
+                let payment_indices = step
+                    .payment_pools()
+                    .iter()
+                    .filter(|(_, pool)| **pool == PoolType::Transparent)
+                    .map(|(idx, _)| *idx)
+                    .collect::<Vec<_>>();
+                if !payment_indices.is_empty() {
+                    return Err(PrivacyViolation::RevealedRecipients {
+                        step_index,
+                        payment_indices,
+                    });
+                }
+            }
+
+            if !self.allows_revealed_amounts() {
+                let mut pools = BTreeSet::new();
+                if !step.transparent_inputs().is_empty() {
+                    pools.insert(PoolType::Transparent);
+                }
+                if let Some(inputs) = step.shielded_inputs() {
+                    pools.extend(
+                        inputs
+                            .notes()
+                            .iter()
+                            .map(|n| PoolType::Shielded(n.note().protocol())),
+                    );
+                }
+                pools.extend(step.payment_pools().values().copied());
+                pools.extend(
+                    step.balance()
+                        .proposed_change()
+                        .iter()
+                        .map(|c| PoolType::Shielded(c.output_pool())),
+                );
+                if pools.len() > 1 {
+                    return Err(PrivacyViolation::RevealedAmounts {
+                        step_index,
+                        pools: pools.into_iter().collect(),
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A description of the information that a proposal would reveal in violation of a
+/// [`PrivacyPolicy`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PrivacyViolation {
+    /// The proposal step at the given index transfers value between the given pools, which
+    /// reveals the amount transferred.
+    RevealedAmounts {
+        step_index: usize,
+        pools: Vec<PoolType>,
+    },
+    /// The proposal step at the given index pays the transparent recipients of the payments at
+    /// the given indices, which reveals their addresses and the amounts paid.
+    RevealedRecipients {
+        step_index: usize,
+        payment_indices: Vec<usize>,
+    },
+    /// The proposal step at the given index spends the given transparent outputs, which reveals
+    /// the addresses from which funds are spent.
+    RevealedSenders {
+        step_index: usize,
+        outpoints: Vec<OutPoint>,
+    },
+}
+
+impl fmt::Display for PrivacyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrivacyViolation::RevealedAmounts { step_index, pools } => write!(
+                f,
+                "Step {} would reveal the amount transferred between the {} pools.",
+                step_index,
+                pools
+                    .iter()
+                    .map(|p| p.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            PrivacyViolation::RevealedRecipients {
+                step_index,
+                payment_indices,
+            } => write!(
+                f,
+                "Step {} would reveal the transparent recipients of payments {:?}.",
+                step_index, payment_indices
+            ),
+            PrivacyViolation::RevealedSenders {
+                step_index,
+                outpoints,
+            } => write!(
+                f,
+                "Step {} would reveal the senders of {} transparent inputs.",
+                step_index,
+                outpoints.len()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PrivacyViolation {}
+
+/// The reason for which a payment to a structurally valid address is rejected, because the
+/// address is known to be unusable.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RecipientRejection {
+    /// The address is for a different network than the one for which the transaction is
+    /// being constructed.
+    NetworkMismatch {
+        expected: NetworkType,
+        actual: NetworkType,
+    },
+    /// The address is a transparent address, or a Unified Address having a transparent
+    /// receiver, whose hash is all zeroes. Such addresses are used as burn addresses, for
+    /// which no spending key is known.
+    BurnAddress(TransparentAddress),
+}
+
+impl fmt::Display for RecipientRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecipientRejection::NetworkMismatch { expected, actual } => write!(
+                f,
+                "The address is for {:?}, but the transaction is for {:?}.",
+                actual, expected
+            ),
+            RecipientRejection::BurnAddress(_) => write!(
+                f,
+                "The address is a burn address, from which funds can never be spent."
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RecipientRejection {}
+
+/// Checks that none of the payments in the given request are to an address that is known to
+/// be unusable, returning the index of the first such payment along with the reason for its
+/// rejection.
+///
+/// Addresses that cannot be converted for reasons other than a network mismatch are left to
+/// be reported by input selection. Payments to the wallet's own ZIP 320 ephemeral addresses
+/// are not detected, as this crate does not yet generate them.
+pub fn check_recipients<P: consensus::Parameters>(
+    params: &P,
+    request: &zip321::TransactionRequest,
+) -> Result<(), (usize, RecipientRejection)> {
+    fn is_burn_address(taddr: &TransparentAddress) -> bool {
+        let hash = match taddr {
+            TransparentAddress::PublicKeyHash(hash) | TransparentAddress::ScriptHash(hash) => hash,
+        };
+        hash == &[0; 20]
+    }
+
+    for (payment_index, payment) in request.payments() {
+        let taddr = match payment
+            .recipient_address()
+            .clone()
+            .convert_if_network::<Address>(params.network_type())
+        {
+            Ok(Address::Transparent(taddr)) => Some(taddr),
+            Ok(Address::Unified(ua)) => ua.transparent().copied(),
+            Ok(_) => None,
+            Err(ConversionError::IncorrectNetwork { expected, actual }) => {
+                return Err((
+                    *payment_index,
+                    RecipientRejection::NetworkMismatch { expected, actual },
+                ));
+            }
+            Err(_) => None,
+        };
+
+        if let Some(taddr) = taddr.filter(is_burn_address) {
+            return Err((*payment_index, RecipientRejection::BurnAddress(taddr)));
+        }
+    }
+
+    Ok(())
+}
+
 /// Select transaction inputs, compute fees, and construct a proposal for a transaction or series
 /// of transactions that can then be authorized and made ready for submission to the network with
 /// [`create_proposed_transactions`].
+///
+/// Returns [`Error::InvalidRecipient`] before any inputs are selected if any payment in the
+/// request is to an address that is known to be unusable; see [`check_recipients`].
+///
+/// Returns [`Error::PrivacyPolicy`] if the resulting proposal would reveal information that is
+/// not permitted by `privacy_policy`.
 #[allow(clippy::too_many_arguments)]
 #[allow(clippy::type_complexity)]
 pub fn propose_transfer<DbT, ParamsT, InputsT, CommitmentTreeErrT>(
@@ -409,6 +684,7 @@ pub fn propose_transfer<DbT, ParamsT, InputsT, CommitmentTreeErrT>(
     input_selector: &InputsT,
     request: zip321::TransactionRequest,
     min_confirmations: NonZeroU32,
+    privacy_policy: PrivacyPolicy,
 ) -> Result<
     Proposal<InputsT::FeeRule, <DbT as InputSource>::NoteRef>,
     Error<
@@ -424,12 +700,19 @@ where
     ParamsT: consensus::Parameters + Clone,
     InputsT: InputSelector<InputSource = DbT>,
 {
+    check_recipients(params, &request).map_err(|(payment_index, reason)| {
+        Error::InvalidRecipient {
+            payment_index,
+            reason,
+        }
+    })?;
+
     let (target_height, anchor_height) = wallet_db
         .get_target_and_anchor_heights(min_confirmations)
         .map_err(|e| Error::from(InputSelectorError::DataSource(e)))?
         .ok_or_else(|| Error::from(InputSelectorError::SyncRequired))?;
 
-    input_selector
+    let proposal = input_selector
         .propose_transaction(
             params,
             wallet_db,
@@ -438,7 +721,268 @@ where
             spend_from_account,
             request,
         )
-        .map_err(Error::from)
+        .map_err(Error::from)?;
+
+    privacy_policy
+        .check(&proposal)
+        .map_err(|violation| Error::PrivacyPolicy {
+            policy: privacy_policy,
+            violation,
+        })?;
+
+    Ok(proposal)
+}
+
+/// Limits on the fees paid by a proposal, used to guard against constructing transactions
+/// that pay more in fees than the user is likely to have intended.
+///
+/// The default guardrails impose no limits on the fee, and warn about change outputs having
+/// a value less than the ZIP 317 marginal fee.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FeeGuardrails {
+    max_fee: Option<NonNegativeAmount>,
+    max_fee_percent: Option<u64>,
+    allow_excessive_fee: bool,
+    change_warning_threshold: NonNegativeAmount,
+}
+
+impl FeeGuardrails {
+    /// Constructs a new set of fee guardrails.
+    ///
+    /// Arguments:
+    /// * `max_fee`: The maximum total fee that the proposal may pay, if any.
+    /// * `max_fee_percent`: The maximum total fee, as a percentage of the total value of the
+    ///   payments made by the proposal, if any. Proposals that make no payments (such as
+    ///   shielding proposals) are not subject to this limit.
+    /// * `allow_excessive_fee`: If `true`, exceeding `max_fee_percent` produces a
+    ///   [`FeeWarning::ExcessiveFee`] instead of a [`FeeGuardrailViolation`]. This can be used
+    ///   after the user has explicitly confirmed that the fee is acceptable.
+    /// * `change_warning_threshold`: Change outputs having a value less than this amount
+    ///   produce a [`FeeWarning::DustChange`].
+    pub fn new(
+        max_fee: Option<NonNegativeAmount>,
+        max_fee_percent: Option<u64>,
+        allow_excessive_fee: bool,
+        change_warning_threshold: NonNegativeAmount,
+    ) -> Self {
+        Self {
+            max_fee,
+            max_fee_percent,
+            allow_excessive_fee,
+            change_warning_threshold,
+        }
+    }
+
+    /// Returns the maximum total fee that a proposal may pay, if any.
+    pub fn max_fee(&self) -> Option<NonNegativeAmount> {
+        self.max_fee
+    }
+
+    /// Returns the maximum total fee as a percentage of the value of a proposal's payments,
+    /// if any.
+    pub fn max_fee_percent(&self) -> Option<u64> {
+        self.max_fee_percent
+    }
+
+    /// Returns whether exceeding [`Self::max_fee_percent`] has been explicitly allowed.
+    pub fn allow_excessive_fee(&self) -> bool {
+        self.allow_excessive_fee
+    }
+
+    /// Returns the value below which change outputs produce a warning.
+    pub fn change_warning_threshold(&self) -> NonNegativeAmount {
+        self.change_warning_threshold
+    }
+
+    /// Checks the given proposal against these guardrails.
+    ///
+    /// Returns the list of warnings that should be presented to the user before the proposal
+    /// is executed, or an error if the proposal violates a limit.
+    pub fn check<FeeRuleT, NoteRef>(
+        &self,
+        proposal: &Proposal<FeeRuleT, NoteRef>,
+    ) -> Result<Vec<FeeWarning>, FeeGuardrailViolation> {
+        let steps = proposal.steps();
+        let fee = steps
+            .iter()
+            .map(|step| step.balance().fee_required())
+            .sum::<Option<NonNegativeAmount>>()
+            .ok_or(FeeGuardrailViolation::BalanceError(BalanceError::Overflow))?;
+        let sent = steps
+            .iter()
+            .map(|step| step.transaction_request().total())
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .sum::<Option<NonNegativeAmount>>()
+            .ok_or(FeeGuardrailViolation::BalanceError(BalanceError::Overflow))?;
+
+        if let Some(max_fee) = self.max_fee {
+            if fee > max_fee {
+                return Err(FeeGuardrailViolation::FeeExceedsMaximum { fee, max_fee });
+            }
+        }
+
+        let mut warnings = vec![];
+        if let Some(max_fee_percent) = self.max_fee_percent {
+            if sent > NonNegativeAmount::ZERO
+                && u128::from(u64::from(fee)) * 100
+                    > u128::from(u64::from(sent)) * u128::from(max_fee_percent)
+            {
+                if self.allow_excessive_fee {
+                    warnings.push(FeeWarning::ExcessiveFee { fee, sent });
+                } else {
+                    return Err(FeeGuardrailViolation::FeeExceedsPercentage {
+                        fee,
+                        sent,
+                        max_fee_percent,
+                    });
+                }
+            }
+        }
+
+        for (step_index, step) in steps.iter().enumerate() {
+            for change in step.balance().proposed_change() {
+                if change.value() < self.change_warning_threshold {
+                    warnings.push(FeeWarning::DustChange {
+                        step_index,
+                        value: change.value(),
+                    });
+                }
+            }
+        }
+
+        Ok(warnings)
+    }
+}
+
+impl Default for FeeGuardrails {
+    fn default() -> Self {
+        FeeGuardrails::new(None, None, false, zip317::MARGINAL_FEE)
+    }
+}
+
+/// A condition detected by [`FeeGuardrails::check`] that does not prevent execution of a
+/// proposal, but that should be presented to the user.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FeeWarning {
+    /// The step at the given index of the proposal produces a change output whose value is
+    /// below the configured warning threshold, and which may cost more to spend than it is
+    /// worth.
+    DustChange {
+        step_index: usize,
+        value: NonNegativeAmount,
+    },
+    /// The proposal pays a fee exceeding the configured percentage of the value sent, which
+    /// has been explicitly allowed.
+    ExcessiveFee {
+        fee: NonNegativeAmount,
+        sent: NonNegativeAmount,
+    },
+}
+
+/// Errors produced when a proposal violates the limits set by [`FeeGuardrails`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FeeGuardrailViolation {
+    /// The total fee paid by the proposal exceeds the configured maximum.
+    FeeExceedsMaximum {
+        fee: NonNegativeAmount,
+        max_fee: NonNegativeAmount,
+    },
+    /// The total fee paid by the proposal exceeds the configured percentage of the value
+    /// sent, and this has not been explicitly allowed.
+    FeeExceedsPercentage {
+        fee: NonNegativeAmount,
+        sent: NonNegativeAmount,
+        max_fee_percent: u64,
+    },
+    /// Zcash amount computation encountered an overflow or underflow.
+    BalanceError(BalanceError),
+}
+
+impl fmt::Display for FeeGuardrailViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FeeGuardrailViolation::FeeExceedsMaximum { fee, max_fee } => write!(
+                f,
+                "The proposed fee of {} zatoshis exceeds the maximum of {} zatoshis.",
+                u64::from(*fee),
+                u64::from(*max_fee)
+            ),
+            FeeGuardrailViolation::FeeExceedsPercentage {
+                fee,
+                sent,
+                max_fee_percent,
+            } => write!(
+                f,
+                "The proposed fee of {} zatoshis exceeds {}% of the {} zatoshis being sent.",
+                u64::from(*fee),
+                max_fee_percent,
+                u64::from(*sent)
+            ),
+            FeeGuardrailViolation::BalanceError(e) => write!(
+                f,
+                "The value lies outside the valid range of Zcash amounts: {}",
+                e
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FeeGuardrailViolation {}
+
+impl From<BalanceError> for FeeGuardrailViolation {
+    fn from(e: BalanceError) -> Self {
+        FeeGuardrailViolation::BalanceError(e)
+    }
+}
+
+/// Constructs a proposal in the same manner as [`propose_transfer`], and checks it against
+/// the provided [`FeeGuardrails`].
+///
+/// Returns the proposal along with any warnings that should be presented to the user before
+/// it is executed using [`create_proposed_transactions`], or
+/// [`Error::FeeGuardrail`] if the proposal violates the guardrails.
+#[allow(clippy::too_many_arguments)]
+#[allow(clippy::type_complexity)]
+pub fn propose_transfer_with_guardrails<DbT, ParamsT, InputsT, CommitmentTreeErrT>(
+    wallet_db: &mut DbT,
+    params: &ParamsT,
+    spend_from_account: <DbT as InputSource>::AccountId,
+    input_selector: &InputsT,
+    request: zip321::TransactionRequest,
+    min_confirmations: NonZeroU32,
+    privacy_policy: PrivacyPolicy,
+    guardrails: &FeeGuardrails,
+) -> Result<
+    (
+        Proposal<InputsT::FeeRule, <DbT as InputSource>::NoteRef>,
+        Vec<FeeWarning>,
+    ),
+    Error<
+        <DbT as WalletRead>::Error,
+        CommitmentTreeErrT,
+        InputsT::Error,
+        <InputsT::FeeRule as FeeRule>::Error,
+    >,
+>
+where
+    DbT: WalletRead + InputSource<Error = <DbT as WalletRead>::Error>,
+    <DbT as InputSource>::NoteRef: Copy + Eq + Ord,
+    ParamsT: consensus::Parameters + Clone,
+    InputsT: InputSelector<InputSource = DbT>,
+{
+    let proposal = propose_transfer(
+        wallet_db,
+        params,
+        spend_from_account,
+        input_selector,
+        request,
+        min_confirmations,
+        privacy_policy,
+    )?;
+
+    let warnings = guardrails.check(&proposal).map_err(Error::FeeGuardrail)?;
+    Ok((proposal, warnings))
 }
 
 /// Proposes making a payment to the specified address from the given account.
@@ -525,6 +1069,7 @@ where
         &input_selector,
         request,
         min_confirmations,
+        PrivacyPolicy::AllowFullyTransparent,
     )
 }
 
@@ -549,6 +1094,35 @@ pub fn propose_shielding<DbT, ParamsT, InputsT, CommitmentTreeErrT>(
         <InputsT::FeeRule as FeeRule>::Error,
     >,
 >
+where
+    ParamsT: consensus::Parameters,
+    DbT: WalletRead + InputSource<Error = <DbT as WalletRead>::Error>,
+    InputsT: ShieldingSelector<InputSource = DbT>,
+{
+    select_shielding_inputs(
+        wallet_db,
+        params,
+        input_selector,
+        shielding_threshold,
+        from_addrs,
+        min_confirmations,
+    )
+    .map_err(Error::from)
+}
+
+#[cfg(feature = "transparent-inputs")]
+#[allow(clippy::type_complexity)]
+fn select_shielding_inputs<DbT, ParamsT, InputsT>(
+    wallet_db: &mut DbT,
+    params: &ParamsT,
+    input_selector: &InputsT,
+    shielding_threshold: NonNegativeAmount,
+    from_addrs: &[TransparentAddress],
+    min_confirmations: u32,
+) -> Result<
+    Proposal<InputsT::FeeRule, Infallible>,
+    InputSelectorError<<DbT as WalletRead>::Error, InputsT::Error>,
+>
 where
     ParamsT: consensus::Parameters,
     DbT: WalletRead + InputSource<Error = <DbT as WalletRead>::Error>,
@@ -556,19 +1130,257 @@ where
 {
     let chain_tip_height = wallet_db
         .chain_height()
-        .map_err(|e| Error::from(InputSelectorError::DataSource(e)))?
-        .ok_or_else(|| Error::from(InputSelectorError::SyncRequired))?;
+        .map_err(InputSelectorError::DataSource)?
+        .ok_or(InputSelectorError::SyncRequired)?;
 
-    input_selector
-        .propose_shielding(
-            params,
-            wallet_db,
-            shielding_threshold,
-            from_addrs,
-            chain_tip_height + 1,
-            min_confirmations,
-        )
-        .map_err(Error::from)
+    input_selector.propose_shielding(
+        params,
+        wallet_db,
+        shielding_threshold,
+        from_addrs,
+        chain_tip_height + 1,
+        min_confirmations,
+    )
+}
+
+/// A hook that is invoked before any transaction of a proposal is constructed by
+/// [`create_proposed_transactions_with_policy`].
+///
+/// Applications can implement this trait to enforce their own spending policies, such as
+/// daily spending limits or requiring out-of-band confirmation of large payments. Returning
+/// an error prevents the proposal from being executed.
+pub trait SpendPolicyHook<AccountId> {
+    /// The error returned when the proposal is vetoed.
+    type Error: std::error::Error + Send + Sync + 'static;
+
+    /// Checks whether the given proposal may be executed by the given account.
+    fn check_proposal<FeeRuleT, NoteRef>(
+        &self,
+        account: AccountId,
+        proposal: &Proposal<FeeRuleT, NoteRef>,
+    ) -> Result<(), Self::Error>;
+}
+
+/// A [`SpendPolicyHook`] that permits every proposal.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoSpendPolicy;
+
+impl<AccountId> SpendPolicyHook<AccountId> for NoSpendPolicy {
+    type Error = Infallible;
+
+    fn check_proposal<FeeRuleT, NoteRef>(
+        &self,
+        _account: AccountId,
+        _proposal: &Proposal<FeeRuleT, NoteRef>,
+    ) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// A holder of the spending keys for an account, which authorizes the spends of the
+/// transactions of a proposal by signing them.
+///
+/// [`create_proposed_transactions`] asks the signer to sign the signature hash of each
+/// transparent input, and to sign the shielded spends of each transaction once all of its
+/// effecting data has been fixed. This allows spending keys to be held in secure storage,
+/// such as a platform keystore or a separate signing process.
+///
+/// Orchard spends are proven using the account's full viewing key. The Sapling transaction
+/// builder derives the proof generation key for a spend from the spending key, and so
+/// Sapling spends are added to the builder by [`Signer::add_sapling_spend`]; the builder
+/// does not retain the spend authorizing key, but a signer that must not release its
+/// Sapling keys at all cannot spend Sapling notes.
+///
+/// [`UnifiedSpendingKey`] implements this trait by deriving each key on demand.
+pub trait Signer {
+    /// The error returned when the signer is unable to authorize a spend.
+    type Error;
+
+    /// Returns the unified full viewing key for the account whose funds are being spent.
+    ///
+    /// This is used to identify the account in the wallet, to prove spends of Orchard
+    /// notes, and to derive the addresses and viewing keys for the change outputs of each
+    /// transaction.
+    fn to_unified_full_viewing_key(&self) -> UnifiedFullViewingKey;
+
+    /// Returns the Sapling outgoing viewing key for the given scope, if the account has a
+    /// Sapling key.
+    ///
+    /// The default implementation derives the key from the account's unified full viewing
+    /// key.
+    fn sapling_ovk(&self, scope: Scope) -> Option<sapling::keys::OutgoingViewingKey> {
+        self.to_unified_full_viewing_key()
+            .sapling()
+            .map(|dfvk| dfvk.to_ovk(scope))
+    }
+
+    /// Returns the Orchard outgoing viewing key for the given scope, if the account has an
+    /// Orchard key.
+    ///
+    /// The default implementation derives the key from the account's unified full viewing
+    /// key.
+    #[cfg(feature = "orchard")]
+    fn orchard_ovk(&self, scope: Scope) -> Option<orchard::keys::OutgoingViewingKey> {
+        self.to_unified_full_viewing_key()
+            .orchard()
+            .map(|fvk| fvk.to_ovk(scope))
+    }
+
+    /// Adds a spend of a Sapling note received by the account in the given scope to the
+    /// transaction under construction, using [`Builder::add_unsigned_sapling_spend`].
+    fn add_sapling_spend<P: consensus::Parameters, FE>(
+        &self,
+        builder: &mut Builder<'_, P, ()>,
+        scope: Scope,
+        note: sapling::Note,
+        merkle_path: sapling::MerklePath,
+    ) -> Result<(), builder::Error<FE, Self::Error>>;
+
+    /// Signs the spends of `bundle` that spend Sapling notes received by the account, over
+    /// the transaction's shielded signature hash `sighash`.
+    fn sign_sapling<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        sighash: &[u8; 32],
+        bundle: SaplingPartiallyAuthorized,
+    ) -> Result<SaplingPartiallyAuthorized, Self::Error>;
+
+    /// Signs the spends of `bundle` that spend Orchard notes received by the account, over
+    /// the transaction's shielded signature hash `sighash`.
+    #[cfg(feature = "orchard")]
+    fn sign_orchard<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        sighash: &[u8; 32],
+        bundle: OrchardPartiallyAuthorized,
+    ) -> Result<OrchardPartiallyAuthorized, Self::Error>;
+
+    /// Signs the signature hash of a transparent input spending an output received at the
+    /// account's address with the given scope and child index.
+    #[cfg(feature = "transparent-inputs")]
+    fn sign_transparent(
+        &self,
+        scope: TransparentKeyScope,
+        address_index: NonHardenedChildIndex,
+        sighash: &SignatureHash,
+    ) -> Result<secp256k1::ecdsa::Signature, Self::Error>;
+}
+
+impl Signer for UnifiedSpendingKey {
+    type Error = Infallible;
+
+    fn to_unified_full_viewing_key(&self) -> UnifiedFullViewingKey {
+        UnifiedSpendingKey::to_unified_full_viewing_key(self)
+    }
+
+    fn add_sapling_spend<P: consensus::Parameters, FE>(
+        &self,
+        builder: &mut Builder<'_, P, ()>,
+        scope: Scope,
+        note: sapling::Note,
+        merkle_path: sapling::MerklePath,
+    ) -> Result<(), builder::Error<FE, Self::Error>> {
+        let extsk = match scope {
+            Scope::External => self.sapling().clone(),
+            Scope::Internal => self.sapling().derive_internal(),
+        };
+        builder.add_unsigned_sapling_spend(&extsk, note, merkle_path)
+    }
+
+    fn sign_sapling<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        _sighash: &[u8; 32],
+        bundle: SaplingPartiallyAuthorized,
+    ) -> Result<SaplingPartiallyAuthorized, Self::Error> {
+        // The external and internal spending keys share the same spend authorizing key.
+        Ok(bundle.sign(rng, &self.sapling().expsk.ask))
+    }
+
+    #[cfg(feature = "orchard")]
+    fn sign_orchard<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        _sighash: &[u8; 32],
+        bundle: OrchardPartiallyAuthorized,
+    ) -> Result<OrchardPartiallyAuthorized, Self::Error> {
+        Ok(bundle.sign(
+            rng,
+            &orchard::keys::SpendAuthorizingKey::from(self.orchard()),
+        ))
+    }
+
+    #[cfg(feature = "transparent-inputs")]
+    fn sign_transparent(
+        &self,
+        scope: TransparentKeyScope,
+        address_index: NonHardenedChildIndex,
+        sighash: &SignatureHash,
+    ) -> Result<secp256k1::ecdsa::Signature, Self::Error> {
+        let sk = self
+            .transparent()
+            .derive_secret_key(scope, address_index)
+            .expect("derivation of a non-hardened child key does not fail in practice");
+        let msg = secp256k1::Message::from_slice(sighash.as_ref()).expect("32 bytes");
+        Ok(secp256k1::Secp256k1::signing_only().sign_ecdsa(&msg, &sk))
+    }
+}
+
+/// Adapts a [`Signer`] to authorize the spends of a single transaction.
+struct SignerAuthorizer<'a, S> {
+    signer: &'a S,
+    /// The derivation of the key for each transparent input of the transaction.
+    #[cfg(feature = "transparent-inputs")]
+    transparent_keys: Vec<(
+        secp256k1::PublicKey,
+        TransparentKeyScope,
+        NonHardenedChildIndex,
+    )>,
+}
+
+impl<'a, S: Signer> SpendAuthorizer for SignerAuthorizer<'a, S> {
+    type Error = S::Error;
+
+    #[cfg(feature = "transparent-inputs")]
+    fn sign_transparent(
+        &self,
+        pubkey: &secp256k1::PublicKey,
+        sighash: &SignatureHash,
+    ) -> Result<secp256k1::ecdsa::Signature, Self::Error> {
+        let (_, scope, address_index) = self
+            .transparent_keys
+            .iter()
+            .find(|(pk, _, _)| pk == pubkey)
+            .expect("every unsigned transparent input was added with a key from this list");
+        self.signer
+            .sign_transparent(*scope, *address_index, sighash)
+    }
+
+    fn sign_sapling<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        sighash: &[u8; 32],
+        bundle: SaplingPartiallyAuthorized,
+    ) -> Result<SaplingPartiallyAuthorized, Self::Error> {
+        self.signer.sign_sapling(rng, sighash, bundle)
+    }
+
+    fn sign_orchard<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        sighash: &[u8; 32],
+        bundle: OrchardPartiallyAuthorized,
+    ) -> Result<OrchardPartiallyAuthorized, Self::Error> {
+        #[cfg(feature = "orchard")]
+        return self.signer.sign_orchard(rng, sighash, bundle);
+
+        // Without the `orchard` feature, no Orchard spends are added to the transaction.
+        #[cfg(not(feature = "orchard"))]
+        {
+            let _ = (rng, sighash);
+            Ok(bundle)
+        }
+    }
 }
 
 /// Construct, prove, and sign a transaction or series of transactions using the inputs supplied by
@@ -582,14 +1394,18 @@ where
 /// step is not supported, because the ultimate positions of those notes in the global note
 /// commitment tree cannot be known until the transaction that produces those notes is mined,
 /// and therefore the required spend proofs for such notes cannot be constructed.
+///
+/// The spends of each transaction are authorized by `signer`, which may be a
+/// [`UnifiedSpendingKey`] or any other [`Signer`] implementation. An error is returned if
+/// the signer's full viewing key does not correspond to an account known to the wallet.
 #[allow(clippy::too_many_arguments)]
 #[allow(clippy::type_complexity)]
-pub fn create_proposed_transactions<DbT, ParamsT, InputsErrT, FeeRuleT, N>(
+pub fn create_proposed_transactions<DbT, ParamsT, InputsErrT, FeeRuleT, N, SignerT>(
     wallet_db: &mut DbT,
     params: &ParamsT,
     spend_prover: &impl SpendProver,
     output_prover: &impl OutputProver,
-    usk: &UnifiedSpendingKey,
+    signer: &SignerT,
     ovk_policy: OvkPolicy,
     proposal: &Proposal<FeeRuleT, N>,
 ) -> Result<
@@ -599,13 +1415,79 @@ pub fn create_proposed_transactions<DbT, ParamsT, InputsErrT, FeeRuleT, N>(
         <DbT as WalletCommitmentTrees>::Error,
         InputsErrT,
         FeeRuleT::Error,
+        SignerT::Error,
     >,
 >
 where
     DbT: WalletWrite + WalletCommitmentTrees,
     ParamsT: consensus::Parameters + Clone,
     FeeRuleT: FeeRule,
+    SignerT: Signer,
 {
+    create_proposed_transactions_with_policy(
+        wallet_db,
+        params,
+        spend_prover,
+        output_prover,
+        signer,
+        ovk_policy,
+        proposal,
+        &NoSpendPolicy,
+    )
+}
+
+/// Construct, prove, and sign a transaction or series of transactions using the inputs supplied by
+/// the given proposal, and persist it to the wallet database, subject to the provided
+/// [`SpendPolicyHook`].
+///
+/// The hook is invoked with the complete proposal before any transaction is constructed; if
+/// it vetoes the proposal, [`Error::SpendPolicy`] is returned and the wallet is not modified.
+/// Otherwise, this behaves in the same manner as [`create_proposed_transactions`].
+#[allow(clippy::too_many_arguments)]
+#[allow(clippy::type_complexity)]
+pub fn create_proposed_transactions_with_policy<
+    DbT,
+    ParamsT,
+    InputsErrT,
+    FeeRuleT,
+    N,
+    HookT,
+    SignerT,
+>(
+    wallet_db: &mut DbT,
+    params: &ParamsT,
+    spend_prover: &impl SpendProver,
+    output_prover: &impl OutputProver,
+    signer: &SignerT,
+    ovk_policy: OvkPolicy,
+    proposal: &Proposal<FeeRuleT, N>,
+    policy: &HookT,
+) -> Result<
+    NonEmpty<TxId>,
+    Error<
+        <DbT as WalletRead>::Error,
+        <DbT as WalletCommitmentTrees>::Error,
+        InputsErrT,
+        FeeRuleT::Error,
+        SignerT::Error,
+        HookT::Error,
+    >,
+>
+where
+    DbT: WalletWrite + WalletCommitmentTrees,
+    ParamsT: consensus::Parameters + Clone,
+    FeeRuleT: FeeRule,
+    HookT: SpendPolicyHook<<DbT as WalletRead>::AccountId>,
+    SignerT: Signer,
+{
+    let account = wallet_db
+        .get_account_for_ufvk(&signer.to_unified_full_viewing_key())
+        .map_err(Error::DataSource)?
+        .ok_or(Error::KeyNotRecognized)?;
+    policy
+        .check_proposal(account.id(), proposal)
+        .map_err(Error::SpendPolicy)?;
+
     let mut step_results = Vec::with_capacity(proposal.steps().len());
     for step in proposal.steps() {
         let step_result = create_proposed_transaction(
@@ -613,7 +1495,7 @@ where
             params,
             spend_prover,
             output_prover,
-            usk,
+            signer,
             ovk_policy.clone(),
             proposal.fee_rule(),
             proposal.min_target_height(),
@@ -632,14 +1514,74 @@ where
     .expect("proposal.steps is NonEmpty"))
 }
 
+/// Construct, prove, and sign a transaction or series of transactions using the inputs supplied by
+/// the given proposal, and persist it to the wallet database, checking the proposal against the
+/// given set of [`NoteReservations`].
+///
+/// If the proposal spends a note or transparent output that is reserved by another prepared
+/// proposal, [`Error::ConflictingProposal`] is returned and the wallet is not modified. Otherwise,
+/// this behaves in the same manner as [`create_proposed_transactions`], and once the transactions
+/// have been created the proposal's own reservation (if any) is released.
+///
+/// [`NoteReservations`]: reservation::NoteReservations
 #[allow(clippy::too_many_arguments)]
 #[allow(clippy::type_complexity)]
-fn create_proposed_transaction<DbT, ParamsT, InputsErrT, FeeRuleT, N>(
+pub fn create_proposed_transactions_with_reservations<
+    DbT,
+    ParamsT,
+    InputsErrT,
+    FeeRuleT,
+    N,
+    SignerT,
+>(
     wallet_db: &mut DbT,
     params: &ParamsT,
     spend_prover: &impl SpendProver,
     output_prover: &impl OutputProver,
-    usk: &UnifiedSpendingKey,
+    signer: &SignerT,
+    ovk_policy: OvkPolicy,
+    proposal: &Proposal<FeeRuleT, N>,
+    reservations: &mut reservation::NoteReservations,
+) -> Result<
+    NonEmpty<TxId>,
+    Error<
+        <DbT as WalletRead>::Error,
+        <DbT as WalletCommitmentTrees>::Error,
+        InputsErrT,
+        FeeRuleT::Error,
+        SignerT::Error,
+    >,
+>
+where
+    DbT: WalletWrite + WalletCommitmentTrees,
+    ParamsT: consensus::Parameters + Clone,
+    FeeRuleT: FeeRule,
+    SignerT: Signer,
+{
+    reservations.check(proposal)?;
+
+    let txids = create_proposed_transactions(
+        wallet_db,
+        params,
+        spend_prover,
+        output_prover,
+        signer,
+        ovk_policy,
+        proposal,
+    )?;
+    reservations.release(proposal);
+
+    Ok(txids)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[allow(clippy::type_complexity)]
+fn create_proposed_transaction<DbT, ParamsT, InputsErrT, FeeRuleT, N, SignerT, PolicyErrT>(
+    wallet_db: &mut DbT,
+    params: &ParamsT,
+    spend_prover: &impl SpendProver,
+    output_prover: &impl OutputProver,
+    signer: &SignerT,
     ovk_policy: OvkPolicy,
     fee_rule: &FeeRuleT,
     min_target_height: BlockHeight,
@@ -652,12 +1594,15 @@ fn create_proposed_transaction<DbT, ParamsT, InputsErrT, FeeRuleT, N>(
         <DbT as WalletCommitmentTrees>::Error,
         InputsErrT,
         FeeRuleT::Error,
+        SignerT::Error,
+        PolicyErrT,
     >,
 >
 where
     DbT: WalletWrite + WalletCommitmentTrees,
     ParamsT: consensus::Parameters + Clone,
     FeeRuleT: FeeRule,
+    SignerT: Signer,
 {
     // TODO: Spending shielded outputs of prior multi-step transaction steps is not yet
     // supported. Maybe support this at some point? Doing so would require a higher-level
@@ -692,89 +1637,87 @@ where
         )?;
     }
 
+    let ufvk = signer.to_unified_full_viewing_key();
     let account = wallet_db
-        .get_account_for_ufvk(&usk.to_unified_full_viewing_key())
+        .get_account_for_ufvk(&ufvk)
         .map_err(Error::DataSource)?
         .ok_or(Error::KeyNotRecognized)?
         .id();
 
-    let (sapling_anchor, sapling_inputs) =
-        if proposal_step.involves(PoolType::Shielded(ShieldedProtocol::Sapling)) {
-            proposal_step.shielded_inputs().map_or_else(
-                || Ok((Some(sapling::Anchor::empty_tree()), vec![])),
-                |inputs| {
-                    wallet_db.with_sapling_tree_mut::<_, _, Error<_, _, _, _>>(|sapling_tree| {
-                        let anchor = sapling_tree
-                            .root_at_checkpoint_id(&inputs.anchor_height())?
-                            .into();
-
-                        let sapling_inputs = inputs
-                            .notes()
-                            .iter()
-                            .filter_map(|selected| match selected.note() {
-                                Note::Sapling(note) => {
-                                    let key = match selected.spending_key_scope() {
-                                        Scope::External => usk.sapling().clone(),
-                                        Scope::Internal => usk.sapling().derive_internal(),
-                                    };
-
-                                    sapling_tree
-                                        .witness_at_checkpoint_id_caching(
-                                            selected.note_commitment_tree_position(),
-                                            &inputs.anchor_height(),
-                                        )
-                                        .map(|merkle_path| Some((key, note, merkle_path)))
-                                        .map_err(Error::from)
-                                        .transpose()
-                                }
-                                #[cfg(feature = "orchard")]
-                                Note::Orchard(_) => None,
-                            })
-                            .collect::<Result<Vec<_>, Error<_, _, _, _>>>()?;
-
-                        Ok((Some(anchor), sapling_inputs))
-                    })
-                },
-            )?
-        } else {
-            (None, vec![])
-        };
+    let (sapling_anchor, sapling_inputs) = if proposal_step
+        .involves(PoolType::Shielded(ShieldedProtocol::Sapling))
+    {
+        proposal_step.shielded_inputs().map_or_else(
+            || Ok((Some(sapling::Anchor::empty_tree()), vec![])),
+            |inputs| {
+                wallet_db.with_sapling_tree_mut::<_, _, Error<_, _, _, _, _, _>>(|sapling_tree| {
+                    let anchor = sapling_tree
+                        .root_at_checkpoint_id(&inputs.anchor_height())?
+                        .into();
+
+                    let sapling_inputs = inputs
+                        .notes()
+                        .iter()
+                        .filter_map(|selected| match selected.note() {
+                            Note::Sapling(note) => sapling_tree
+                                .witness_at_checkpoint_id_caching(
+                                    selected.note_commitment_tree_position(),
+                                    &inputs.anchor_height(),
+                                )
+                                .map(|merkle_path| {
+                                    Some((selected.spending_key_scope(), note, merkle_path))
+                                })
+                                .map_err(Error::from)
+                                .transpose(),
+                            #[cfg(feature = "orchard")]
+                            Note::Orchard(_) => None,
+                        })
+                        .collect::<Result<Vec<_>, Error<_, _, _, _, _, _>>>()?;
+
+                    Ok((Some(anchor), sapling_inputs))
+                })
+            },
+        )?
+    } else {
+        (None, vec![])
+    };
 
     #[cfg(feature = "orchard")]
-    let (orchard_anchor, orchard_inputs) =
-        if proposal_step.involves(PoolType::Shielded(ShieldedProtocol::Orchard)) {
-            proposal_step.shielded_inputs().map_or_else(
-                || Ok((Some(orchard::Anchor::empty_tree()), vec![])),
-                |inputs| {
-                    wallet_db.with_orchard_tree_mut::<_, _, Error<_, _, _, _>>(|orchard_tree| {
-                        let anchor = orchard_tree
-                            .root_at_checkpoint_id(&inputs.anchor_height())?
-                            .into();
-
-                        let orchard_inputs = inputs
-                            .notes()
-                            .iter()
-                            .filter_map(|selected| match selected.note() {
-                                #[cfg(feature = "orchard")]
-                                Note::Orchard(note) => orchard_tree
-                                    .witness_at_checkpoint_id_caching(
-                                        selected.note_commitment_tree_position(),
-                                        &inputs.anchor_height(),
-                                    )
-                                    .map(|merkle_path| Some((note, merkle_path)))
-                                    .map_err(Error::from)
-                                    .transpose(),
-                                Note::Sapling(_) => None,
-                            })
-                            .collect::<Result<Vec<_>, Error<_, _, _, _>>>()?;
-
-                        Ok((Some(anchor), orchard_inputs))
-                    })
-                },
-            )?
-        } else {
-            (None, vec![])
-        };
+    let (orchard_anchor, orchard_inputs) = if proposal_step
+        .involves(PoolType::Shielded(ShieldedProtocol::Orchard))
+    {
+        proposal_step.shielded_inputs().map_or_else(
+            || Ok((Some(orchard::Anchor::empty_tree()), vec![])),
+            |inputs| {
+                wallet_db.with_orchard_tree_mut::<_, _, Error<_, _, _, _, _, _>>(|orchard_tree| {
+                    let anchor = orchard_tree
+                        .root_at_checkpoint_id(&inputs.anchor_height())?
+                        .into();
+
+                    let orchard_inputs = inputs
+                        .notes()
+                        .iter()
+                        .filter_map(|selected| match selected.note() {
+                            #[cfg(feature = "orchard")]
+                            Note::Orchard(note) => orchard_tree
+                                .witness_at_checkpoint_id_caching(
+                                    selected.note_commitment_tree_position(),
+                                    &inputs.anchor_height(),
+                                )
+                                .map(|merkle_path| Some((note, merkle_path)))
+                                .map_err(Error::from)
+                                .transpose(),
+                            Note::Sapling(_) => None,
+                        })
+                        .collect::<Result<Vec<_>, Error<_, _, _, _, _, _>>>()?;
+
+                    Ok((Some(anchor), orchard_inputs))
+                })
+            },
+        )?
+    } else {
+        (None, vec![])
+    };
     #[cfg(not(feature = "orchard"))]
     let orchard_anchor = None;
 
@@ -789,15 +1732,26 @@ where
         },
     );
 
-    for (sapling_key, sapling_note, merkle_path) in sapling_inputs.into_iter() {
-        builder.add_sapling_spend(&sapling_key, sapling_note.clone(), merkle_path)?;
+    for (scope, sapling_note, merkle_path) in sapling_inputs.into_iter() {
+        signer
+            .add_sapling_spend(&mut builder, scope, sapling_note.clone(), merkle_path)
+            .map_err(Error::from_signed_build)?;
     }
 
     #[cfg(feature = "orchard")]
-    for (orchard_note, merkle_path) in orchard_inputs.into_iter() {
-        builder.add_orchard_spend(usk.orchard(), *orchard_note, merkle_path.into())?;
+    if !orchard_inputs.is_empty() {
+        let orchard_fvk = ufvk
+            .orchard()
+            .ok_or_else(|| Error::NoSpendingKey("an Orchard note".to_owned()))?;
+        for (orchard_note, merkle_path) in orchard_inputs.into_iter() {
+            builder.add_unsigned_orchard_spend(orchard_fvk, *orchard_note, merkle_path.into())?;
+        }
     }
 
+    // The keys from which each transparent input's signature is requested from the signer.
+    #[cfg(feature = "transparent-inputs")]
+    let mut transparent_keys = vec![];
+
     #[cfg(feature = "transparent-inputs")]
     let utxos_spent = {
         let known_addrs = wallet_db
@@ -815,6 +1769,8 @@ where
                 <DbT as WalletCommitmentTrees>::Error,
                 InputsErrT,
                 FeeRuleT::Error,
+                SignerT::Error,
+                PolicyErrT,
             >,
         > {
             let address_metadata = known_addrs
@@ -823,13 +1779,25 @@ where
                 .clone()
                 .ok_or_else(|| Error::NoSpendingKey(addr.encode(params)))?;
 
-            let secret_key = usk
+            let pubkey = ufvk
                 .transparent()
-                .derive_secret_key(address_metadata.scope(), address_metadata.address_index())
-                .unwrap();
+                .and_then(|account_pubkey| {
+                    account_pubkey
+                        .derive_address_pubkey(
+                            address_metadata.scope(),
+                            address_metadata.address_index(),
+                        )
+                        .ok()
+                })
+                .ok_or_else(|| Error::NoSpendingKey(addr.encode(params)))?;
 
             utxos_spent.push(outpoint.clone());
-            builder.add_transparent_input(secret_key, outpoint, utxo)?;
+            builder.add_unsigned_transparent_input(pubkey, outpoint, utxo)?;
+            transparent_keys.push((
+                pubkey,
+                address_metadata.scope(),
+                address_metadata.address_index(),
+            ));
 
             Ok(())
         };
@@ -892,11 +1860,11 @@ where
     };
 
     #[cfg(feature = "orchard")]
-    let orchard_fvk: orchard::keys::FullViewingKey = usk.orchard().into();
+    let orchard_fvk = ufvk.orchard();
 
     #[cfg(feature = "orchard")]
     let orchard_external_ovk = match &ovk_policy {
-        OvkPolicy::Sender => Some(orchard_fvk.to_ovk(orchard::keys::Scope::External)),
+        OvkPolicy::Sender => signer.orchard_ovk(Scope::External),
         OvkPolicy::Custom { orchard, .. } => Some(orchard.clone()),
         OvkPolicy::Discard => None,
     };
@@ -905,22 +1873,21 @@ where
     let orchard_internal_ovk = || {
         #[cfg(feature = "transparent-inputs")]
         if proposal_step.is_shielding() {
-            return Some(orchard::keys::OutgoingViewingKey::from(
-                usk.transparent()
-                    .to_account_pubkey()
-                    .internal_ovk()
-                    .as_bytes(),
-            ));
+            if let Some(account_pubkey) = ufvk.transparent() {
+                return Some(orchard::keys::OutgoingViewingKey::from(
+                    account_pubkey.internal_ovk().as_bytes(),
+                ));
+            }
         }
 
-        Some(orchard_fvk.to_ovk(Scope::Internal))
+        signer.orchard_ovk(Scope::Internal)
     };
 
-    let sapling_dfvk = usk.sapling().to_diversifiable_full_viewing_key();
+    let sapling_dfvk = ufvk.sapling();
 
     // Apply the outgoing viewing key policy.
     let sapling_external_ovk = match &ovk_policy {
-        OvkPolicy::Sender => Some(sapling_dfvk.to_ovk(Scope::External)),
+        OvkPolicy::Sender => signer.sapling_ovk(Scope::External),
         OvkPolicy::Custom { sapling, .. } => Some(*sapling),
         OvkPolicy::Discard => None,
     };
@@ -928,15 +1895,14 @@ where
     let sapling_internal_ovk = || {
         #[cfg(feature = "transparent-inputs")]
         if proposal_step.is_shielding() {
-            return Some(sapling::keys::OutgoingViewingKey(
-                usk.transparent()
-                    .to_account_pubkey()
-                    .internal_ovk()
-                    .as_bytes(),
-            ));
+            if let Some(account_pubkey) = ufvk.transparent() {
+                return Some(sapling::keys::OutgoingViewingKey(
+                    account_pubkey.internal_ovk().as_bytes(),
+                ));
+            }
         }
 
-        Some(sapling_dfvk.to_ovk(Scope::Internal))
+        signer.sapling_ovk(Scope::Internal)
     };
 
     #[cfg(feature = "orchard")]
@@ -1053,9 +2019,13 @@ where
             .map_or_else(MemoBytes::empty, |m| m.clone());
         match change_value.output_pool() {
             ShieldedProtocol::Sapling => {
+                let change_address = sapling_dfvk
+                    .ok_or(Error::UnsupportedChangeType(PoolType::SAPLING))?
+                    .change_address()
+                    .1;
                 builder.add_sapling_output(
                     sapling_internal_ovk(),
-                    sapling_dfvk.change_address().1,
+                    change_address,
                     change_value.value(),
                     memo.clone(),
                 )?;
@@ -1077,9 +2047,12 @@ where
 
                 #[cfg(feature = "orchard")]
                 {
+                    let change_address = orchard_fvk
+                        .ok_or(Error::UnsupportedChangeType(PoolType::ORCHARD))?
+                        .address_at(0u32, orchard::keys::Scope::Internal);
                     builder.add_orchard_output(
                         orchard_internal_ovk(),
-                        orchard_fvk.address_at(0u32, orchard::keys::Scope::Internal),
+                        change_address,
                         change_value.value().into(),
                         memo.clone(),
                     )?;
@@ -1098,10 +2071,17 @@ where
     }
 
     // Build the transaction with the specified fee rule
-    let build_result = builder.build(OsRng, spend_prover, output_prover, fee_rule)?;
+    let authorizer = SignerAuthorizer {
+        signer,
+        #[cfg(feature = "transparent-inputs")]
+        transparent_keys,
+    };
+    let build_result = builder
+        .build_with_authorizer(OsRng, spend_prover, output_prover, fee_rule, &authorizer)
+        .map_err(Error::from_signed_build)?;
 
     #[cfg(feature = "orchard")]
-    let orchard_internal_ivk = orchard_fvk.to_ivk(orchard::keys::Scope::Internal);
+    let orchard_internal_ivk = orchard_fvk.map(|fvk| fvk.to_ivk(orchard::keys::Scope::Internal));
     #[cfg(feature = "orchard")]
     let orchard_outputs =
         orchard_output_meta
@@ -1119,9 +2099,10 @@ where
                         build_result
                             .transaction()
                             .orchard_bundle()
-                            .and_then(|bundle| {
+                            .zip(orchard_internal_ivk.as_ref())
+                            .and_then(|(bundle, ivk)| {
                                 bundle
-                                    .decrypt_output_with_key(output_index, &orchard_internal_ivk)
+                                    .decrypt_output_with_key(output_index, ivk)
                                     .map(|(note, _, _)| Note::Orchard(note))
                             })
                     })
@@ -1132,7 +2113,7 @@ where
             });
 
     let sapling_internal_ivk =
-        PreparedIncomingViewingKey::new(&sapling_dfvk.to_ivk(Scope::Internal));
+        sapling_dfvk.map(|dfvk| PreparedIncomingViewingKey::new(&dfvk.to_ivk(Scope::Internal)));
     let sapling_outputs =
         sapling_output_meta
             .into_iter()
@@ -1149,9 +2130,10 @@ where
                         build_result
                             .transaction()
                             .sapling_bundle()
-                            .and_then(|bundle| {
+                            .zip(sapling_internal_ivk.as_ref())
+                            .and_then(|(bundle, ivk)| {
                                 try_sapling_note_decryption(
-                                    &sapling_internal_ivk,
+                                    ivk,
                                     &bundle.shielded_outputs()[output_index],
                                     zip212_enforcement(params, min_target_height),
                                 )
@@ -1224,12 +2206,12 @@ where
 ///   transaction.
 /// * `input_selector`: The [`InputSelector`] to for note selection and change and fee
 ///   determination
-/// * `usk`: The unified spending key that will be used to detect and spend transparent UTXOs,
-///   and that will provide the shielded address to which funds will be sent. Funds will be
-///   shielded to the internal (change) address associated with the most preferred shielded
-///   receiver corresponding to this account, or if no shielded receiver can be used for this
-///   account, this function will return an error. This procedure will return an error if the
-///   USK does not correspond to an account known to the wallet.
+/// * `signer`: The [`Signer`] that will authorize the spends of transparent UTXOs,
+///   and whose full viewing key will provide the shielded address to which funds will be
+///   sent. Funds will be shielded to the internal (change) address associated with the most
+///   preferred shielded receiver corresponding to this account, or if no shielded receiver
+///   can be used for this account, this function will return an error. This procedure will
+///   return an error if the signer does not correspond to an account known to the wallet.
 /// * `from_addrs`: The list of transparent addresses that will be used to filter transaparent
 ///   UTXOs received by the wallet. Only UTXOs received at one of the provided addresses will
 ///   be selected to be shielded.
@@ -1243,14 +2225,14 @@ where
 #[cfg(feature = "transparent-inputs")]
 #[allow(clippy::too_many_arguments)]
 #[allow(clippy::type_complexity)]
-pub fn shield_transparent_funds<DbT, ParamsT, InputsT>(
+pub fn shield_transparent_funds<DbT, ParamsT, InputsT, SignerT>(
     wallet_db: &mut DbT,
     params: &ParamsT,
     spend_prover: &impl SpendProver,
     output_prover: &impl OutputProver,
     input_selector: &InputsT,
     shielding_threshold: NonNegativeAmount,
-    usk: &UnifiedSpendingKey,
+    signer: &SignerT,
     from_addrs: &[TransparentAddress],
     min_confirmations: u32,
 ) -> Result<
@@ -1260,14 +2242,16 @@ pub fn shield_transparent_funds<DbT, ParamsT, InputsT>(
         <DbT as WalletCommitmentTrees>::Error,
         InputsT::Error,
         <InputsT::FeeRule as FeeRule>::Error,
+        SignerT::Error,
     >,
 >
 where
     ParamsT: consensus::Parameters,
     DbT: WalletWrite + WalletCommitmentTrees + InputSource<Error = <DbT as WalletRead>::Error>,
     InputsT: ShieldingSelector<InputSource = DbT>,
+    SignerT: Signer,
 {
-    let proposal = propose_shielding(
+    let proposal = select_shielding_inputs(
         wallet_db,
         params,
         input_selector,
@@ -1281,8 +2265,207 @@ where
         params,
         spend_prover,
         output_prover,
-        usk,
+        signer,
         OvkPolicy::Sender,
         &proposal,
     )
 }
+
+/// The number of blocks before its expiry height within which a transaction will be rejected
+/// from the mempool by `zcashd` as expiring soon.
+const TX_EXPIRING_SOON_THRESHOLD: u32 = 3;
+
+/// The exclusive upper bound on the expiry height of a transaction, as defined by consensus.
+const TX_EXPIRY_HEIGHT_THRESHOLD: u32 = 500_000_000;
+
+/// Reasons for which [`validate_for_broadcast`] may reject a transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BroadcastRejection {
+    /// The transaction commits to a consensus branch ID that is not valid for the block
+    /// following the chain tip.
+    BranchIdMismatch {
+        tx_branch_id: BranchId,
+        expected: BranchId,
+    },
+    /// The transaction's expiry height is below the height at which it could next be mined.
+    Expired {
+        expiry_height: BlockHeight,
+        target_height: BlockHeight,
+    },
+    /// The transaction will expire too soon after the next block to be accepted to the
+    /// mempool.
+    ExpiringSoon {
+        expiry_height: BlockHeight,
+        target_height: BlockHeight,
+    },
+    /// The transaction's expiry height exceeds the maximum permitted by consensus.
+    ExpiryHeightTooLarge(BlockHeight),
+    /// The value of the output spent by a transparent input was not provided, so the fee paid
+    /// by the transaction could not be determined.
+    MissingPrevout(OutPoint),
+    /// Zcash amount computation encountered an overflow or underflow.
+    BalanceError(BalanceError),
+    /// The fee paid by the transaction is less than the ZIP 317 conventional fee.
+    InsufficientFee {
+        paid: NonNegativeAmount,
+        required: NonNegativeAmount,
+    },
+}
+
+impl fmt::Display for BroadcastRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BroadcastRejection::BranchIdMismatch {
+                tx_branch_id,
+                expected,
+            } => write!(
+                f,
+                "Transaction uses consensus branch ID {:?}, but {:?} is required for the next block.",
+                tx_branch_id, expected
+            ),
+            BroadcastRejection::Expired {
+                expiry_height,
+                target_height,
+            } => write!(
+                f,
+                "Transaction expired at height {}, and cannot be mined at height {}.",
+                expiry_height, target_height
+            ),
+            BroadcastRejection::ExpiringSoon {
+                expiry_height,
+                target_height,
+            } => write!(
+                f,
+                "Transaction expires at height {}, too soon after the next block at height {}.",
+                expiry_height, target_height
+            ),
+            BroadcastRejection::ExpiryHeightTooLarge(expiry_height) => write!(
+                f,
+                "Transaction expiry height {} exceeds the consensus limit.",
+                expiry_height
+            ),
+            BroadcastRejection::MissingPrevout(outpoint) => write!(
+                f,
+                "The value of the output spent by transparent input {:?} is unknown.",
+                outpoint
+            ),
+            BroadcastRejection::BalanceError(e) => write!(
+                f,
+                "The value balance of the transaction is outside the valid range: {}",
+                e
+            ),
+            BroadcastRejection::InsufficientFee { paid, required } => write!(
+                f,
+                "Transaction pays a fee of {} zatoshis, but at least {} zatoshis are required.",
+                u64::from(*paid),
+                u64::from(*required)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BroadcastRejection {}
+
+impl From<BalanceError> for BroadcastRejection {
+    fn from(e: BalanceError) -> Self {
+        BroadcastRejection::BalanceError(e)
+    }
+}
+
+/// Checks that a transaction is suitable for broadcast to the network, given the height of
+/// the current chain tip.
+///
+/// This performs the following checks against the height of the next block:
+/// - the transaction's consensus branch ID must match the branch that will be active;
+/// - if the transaction has a nonzero expiry height, the transaction must not have expired,
+///   and must not be so close to expiry that it would be rejected from the mempool;
+/// - the fee paid by the transaction must be at least the ZIP 317 conventional fee.
+///
+/// `get_prevout` is used to look up the value of the output spent by each transparent input
+/// of the transaction, and is not called for fully shielded transactions.
+pub fn validate_for_broadcast<P, F>(
+    tx: &Transaction,
+    params: &P,
+    chain_tip: BlockHeight,
+    mut get_prevout: F,
+) -> Result<(), BroadcastRejection>
+where
+    P: consensus::Parameters,
+    F: FnMut(&OutPoint) -> Option<NonNegativeAmount>,
+{
+    let target_height = chain_tip + 1;
+
+    let expected = BranchId::for_height(params, target_height);
+    if tx.consensus_branch_id() != expected {
+        return Err(BroadcastRejection::BranchIdMismatch {
+            tx_branch_id: tx.consensus_branch_id(),
+            expected,
+        });
+    }
+
+    let expiry_height = tx.expiry_height();
+    if u32::from(expiry_height) >= TX_EXPIRY_HEIGHT_THRESHOLD {
+        return Err(BroadcastRejection::ExpiryHeightTooLarge(expiry_height));
+    }
+    if expiry_height != BlockHeight::from(0) {
+        if expiry_height < target_height {
+            return Err(BroadcastRejection::Expired {
+                expiry_height,
+                target_height,
+            });
+        }
+        if expiry_height < target_height + TX_EXPIRING_SOON_THRESHOLD {
+            return Err(BroadcastRejection::ExpiringSoon {
+                expiry_height,
+                target_height,
+            });
+        }
+    }
+
+    // A transaction that spends less than it outputs has a negative fee, which cannot be
+    // represented as a `NonNegativeAmount`.
+    let paid = NonNegativeAmount::try_from(tx.fee_paid(|outpoint| {
+        get_prevout(outpoint)
+            .map(Amount::from)
+            .ok_or_else(|| BroadcastRejection::MissingPrevout(outpoint.clone()))
+    })?)?;
+    let required = conventional_fee(tx)?;
+    if paid < required {
+        return Err(BroadcastRejection::InsufficientFee { paid, required });
+    }
+
+    Ok(())
+}
+
+/// Computes the ZIP 317 conventional fee for the given transaction.
+fn conventional_fee(tx: &Transaction) -> Result<NonNegativeAmount, BalanceError> {
+    let ceildiv = |num: usize, den: usize| (num + den - 1) / den;
+
+    let (t_in_total_size, t_out_total_size) = tx.transparent_bundle().map_or((0, 0), |b| {
+        (
+            b.vin
+                .iter()
+                .map(|t_in| {
+                    let script_len = t_in.script_sig.0.len();
+                    36 + CompactSize::serialized_size(script_len) + script_len + 4
+                })
+                .sum::<usize>(),
+            b.vout
+                .iter()
+                .map(|t_out| t_out.serialized_size())
+                .sum::<usize>(),
+        )
+    });
+
+    let logical_actions = max(
+        ceildiv(t_in_total_size, zip317::P2PKH_STANDARD_INPUT_SIZE),
+        ceildiv(t_out_total_size, zip317::P2PKH_STANDARD_OUTPUT_SIZE),
+    ) + 2 * tx.sprout_bundle().map_or(0, |b| b.joinsplits.len())
+        + tx.sapling_bundle().map_or(0, |b| {
+            max(b.shielded_spends().len(), b.shielded_outputs().len())
+        })
+        + tx.orchard_bundle().map_or(0, |b| b.actions().len());
+
+    (zip317::MARGINAL_FEE * max(zip317::GRACE_ACTIONS, logical_actions))
+        .ok_or(BalanceError::Overflow)
+}
@@ -0,0 +1,345 @@
+//! Proposal of payouts to many recipients as a sequence of transactions.
+//!
+//! Exchanges and payroll services frequently need to pay more recipients than can be paid in a
+//! single transaction of reasonable size. [`propose_batch_transfer`] divides such a list of
+//! payments into batches, each of which is paid by a separate transaction having no more than
+//! a given number of ZIP 317 logical actions, and returns a [`BatchPlan`] describing the order
+//! in which the batches are to be executed.
+//!
+//! Payments to the same pool are packed together, so that each transaction pays the fee for as
+//! few logical actions as possible. The inputs of the proposed batches are disjoint, so that
+//! their transactions may be created and broadcast together. When the notes that are
+//! spendable at the time of planning are insufficient to fund every batch, the remaining
+//! payments are placed in deferred batches that depend upon the change outputs of the proposed
+//! batches; they should be planned again once the transactions on which they depend have been
+//! mined.
+
+use std::num::{NonZeroU32, NonZeroUsize};
+
+use zcash_primitives::transaction::{components::amount::NonNegativeAmount, fees::FeeRule, TxId};
+use zcash_protocol::consensus::{self, BlockHeight};
+
+use super::input_selection::{
+    GreedyInputSelector, GreedyInputSelectorError, InputSelector, InputSelectorError,
+};
+use crate::{
+    address::Address,
+    data_api::{error::Error, InputSource, SpendableNotes, WalletRead},
+    fees::{logical_action_count, ChangeStrategy, DustOutputPolicy},
+    proposal::Proposal,
+    wallet::{Note, ReceivedNote},
+    zip321::{Payment, TransactionRequest},
+    PoolType, ShieldedProtocol,
+};
+
+#[cfg(feature = "transparent-inputs")]
+use {
+    crate::wallet::WalletTransparentOutput,
+    zcash_primitives::{legacy::TransparentAddress, transaction::components::OutPoint},
+};
+
+/// A single transaction of a [`BatchPlan`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlannedBatch<FeeRuleT, NoteRef> {
+    payment_indices: Vec<usize>,
+    request: TransactionRequest,
+    proposal: Option<Proposal<FeeRuleT, NoteRef>>,
+    depends_on: Vec<usize>,
+}
+
+impl<FeeRuleT, NoteRef> PlannedBatch<FeeRuleT, NoteRef> {
+    /// Returns the indices, within the list of payments provided to
+    /// [`propose_batch_transfer`], of the payments made by this batch.
+    pub fn payment_indices(&self) -> &[usize] {
+        &self.payment_indices
+    }
+
+    /// Returns the transaction request for the payments made by this batch.
+    pub fn transaction_request(&self) -> &TransactionRequest {
+        &self.request
+    }
+
+    /// Returns the proposal for the transaction that makes this batch's payments, or `None` if
+    /// the batch has been deferred until the batches on which it depends have been mined.
+    pub fn proposal(&self) -> Option<&Proposal<FeeRuleT, NoteRef>> {
+        self.proposal.as_ref()
+    }
+
+    /// Returns the indices, within the [`BatchPlan`], of the batches whose change outputs are
+    /// required to fund this batch.
+    ///
+    /// This is empty for every batch that has a proposal.
+    pub fn depends_on(&self) -> &[usize] {
+        &self.depends_on
+    }
+}
+
+/// An ordered execution plan for a set of payments, as returned by [`propose_batch_transfer`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BatchPlan<FeeRuleT, NoteRef> {
+    batches: Vec<PlannedBatch<FeeRuleT, NoteRef>>,
+}
+
+impl<FeeRuleT, NoteRef> BatchPlan<FeeRuleT, NoteRef> {
+    /// Returns the batches of the plan, in the order in which they are to be executed.
+    ///
+    /// Every batch that has a proposal precedes every deferred batch.
+    pub fn batches(&self) -> &[PlannedBatch<FeeRuleT, NoteRef>] {
+        &self.batches
+    }
+
+    /// Returns the batches of the plan that have been deferred.
+    pub fn deferred(&self) -> impl Iterator<Item = &PlannedBatch<FeeRuleT, NoteRef>> {
+        self.batches.iter().filter(|b| b.proposal.is_none())
+    }
+}
+
+/// An [`InputSource`] that never selects the given notes, which have been selected as inputs
+/// to earlier batches.
+struct ExcludingSource<'a, DbT: InputSource> {
+    inner: &'a DbT,
+    excluded: &'a [DbT::NoteRef],
+}
+
+impl<'a, DbT: InputSource> InputSource for ExcludingSource<'a, DbT> {
+    type Error = DbT::Error;
+    type AccountId = DbT::AccountId;
+    type NoteRef = DbT::NoteRef;
+
+    fn get_spendable_note(
+        &self,
+        txid: &TxId,
+        protocol: ShieldedProtocol,
+        index: u32,
+    ) -> Result<Option<ReceivedNote<Self::NoteRef, Note>>, Self::Error> {
+        Ok(self
+            .inner
+            .get_spendable_note(txid, protocol, index)?
+            .filter(|note| !self.excluded.contains(note.internal_note_id())))
+    }
+
+    fn select_spendable_notes(
+        &self,
+        account: Self::AccountId,
+        target_value: NonNegativeAmount,
+        sources: &[ShieldedProtocol],
+        anchor_height: BlockHeight,
+        exclude: &[Self::NoteRef],
+    ) -> Result<SpendableNotes<Self::NoteRef>, Self::Error> {
+        let exclude = exclude
+            .iter()
+            .chain(self.excluded.iter())
+            .copied()
+            .collect::<Vec<_>>();
+        self.inner
+            .select_spendable_notes(account, target_value, sources, anchor_height, &exclude)
+    }
+
+    #[cfg(feature = "transparent-inputs")]
+    fn get_unspent_transparent_output(
+        &self,
+        outpoint: &OutPoint,
+    ) -> Result<Option<WalletTransparentOutput>, Self::Error> {
+        self.inner.get_unspent_transparent_output(outpoint)
+    }
+
+    #[cfg(feature = "transparent-inputs")]
+    fn get_unspent_transparent_outputs(
+        &self,
+        address: &TransparentAddress,
+        max_height: BlockHeight,
+        exclude: &[OutPoint],
+    ) -> Result<Vec<WalletTransparentOutput>, Self::Error> {
+        self.inner
+            .get_unspent_transparent_outputs(address, max_height, exclude)
+    }
+}
+
+/// Returns the pool to which the greedy input selector sends a payment.
+fn payment_pool<ParamsT: consensus::Parameters>(
+    params: &ParamsT,
+    payment: &Payment,
+) -> Result<PoolType, zcash_address::ConversionError<&'static str>> {
+    let address: Address = payment
+        .recipient_address()
+        .clone()
+        .convert_if_network(params.network_type())?;
+    Ok(match address {
+        Address::Transparent(_) => PoolType::Transparent,
+        Address::Sapling(_) => PoolType::Shielded(ShieldedProtocol::Sapling),
+        Address::Unified(ua) => {
+            #[cfg(feature = "orchard")]
+            if ua.orchard().is_some() {
+                return Ok(PoolType::Shielded(ShieldedProtocol::Orchard));
+            }
+
+            if ua.sapling().is_some() {
+                PoolType::Shielded(ShieldedProtocol::Sapling)
+            } else {
+                PoolType::Transparent
+            }
+        }
+    })
+}
+
+/// Proposes paying the given list of payments from the given account in a sequence of
+/// transactions, none of which has more than `max_tx_size` ZIP 317 logical actions.
+///
+/// Payments are grouped by the pool that they will be paid to, and each group is divided into
+/// batches that are as large as the action limit allows. Inputs for each batch are selected
+/// using a [`GreedyInputSelector`] with the given change strategy, excluding the notes
+/// selected for earlier batches. If the spendable notes are insufficient to fund a batch, it
+/// is deferred until the proposed batches have been mined (see [`PlannedBatch::depends_on`]),
+/// at which point its payments should be planned again.
+///
+/// Returns [`Error::ActionLimitExceeded`] if a single payment cannot be made within the
+/// action limit, and [`Error::InsufficientFunds`] if the wallet cannot fund even the first
+/// batch.
+#[allow(clippy::too_many_arguments)]
+#[allow(clippy::type_complexity)]
+pub fn propose_batch_transfer<DbT, ParamsT, ChangeT, CommitmentTreeErrT>(
+    wallet_db: &mut DbT,
+    params: &ParamsT,
+    spend_from_account: <DbT as InputSource>::AccountId,
+    change_strategy: &ChangeT,
+    payments: Vec<Payment>,
+    max_tx_size: NonZeroUsize,
+    min_confirmations: NonZeroU32,
+) -> Result<
+    BatchPlan<ChangeT::FeeRule, <DbT as InputSource>::NoteRef>,
+    Error<
+        <DbT as WalletRead>::Error,
+        CommitmentTreeErrT,
+        GreedyInputSelectorError<ChangeT::Error, <DbT as InputSource>::NoteRef>,
+        <ChangeT::FeeRule as FeeRule>::Error,
+    >,
+>
+where
+    DbT: WalletRead + InputSource<Error = <DbT as WalletRead>::Error>,
+    <DbT as InputSource>::NoteRef: Copy + Eq + Ord,
+    ParamsT: consensus::Parameters + Clone,
+    ChangeT: ChangeStrategy + Clone,
+    ChangeT::FeeRule: Clone,
+{
+    let limit = max_tx_size.get();
+    let (target_height, anchor_height) = wallet_db
+        .get_target_and_anchor_heights(min_confirmations)
+        .map_err(|e| Error::from(InputSelectorError::DataSource(e)))?
+        .ok_or_else(|| Error::from(InputSelectorError::SyncRequired))?;
+
+    // Group the payments by pool, retaining their relative order within each pool.
+    let mut by_pool = payments
+        .iter()
+        .enumerate()
+        .map(|(idx, payment)| Ok((payment_pool(params, payment)?, idx)))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(Error::Address)?;
+    by_pool.sort_by_key(|(pool, _)| match pool {
+        PoolType::Shielded(ShieldedProtocol::Orchard) => 0,
+        PoolType::Shielded(ShieldedProtocol::Sapling) => 1,
+        PoolType::Transparent => 2,
+    });
+
+    // Each batch initially leaves room for a change output within the action limit. A batch
+    // whose proposal nonetheless exceeds the limit (because it requires many inputs) is split
+    // in half and the halves are proposed in its place.
+    let chunk_size = std::cmp::max(limit - 1, 1);
+    let mut pending = by_pool
+        .chunks(chunk_size)
+        .map(|chunk| chunk.iter().map(|(_, idx)| *idx).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    pending.reverse();
+
+    let mut proposed = vec![];
+    let mut deferred = vec![];
+    let mut excluded = vec![];
+    while let Some(payment_indices) = pending.pop() {
+        let request = TransactionRequest::new(
+            payment_indices
+                .iter()
+                .map(|idx| payments[*idx].clone())
+                .collect(),
+        )
+        .expect("The payments were valid as provided.");
+
+        let input_source = ExcludingSource {
+            inner: &*wallet_db,
+            excluded: &excluded,
+        };
+        let input_selector = GreedyInputSelector::<ExcludingSource<'_, DbT>, _>::new(
+            change_strategy.clone(),
+            DustOutputPolicy::default(),
+        );
+        let proposal = match input_selector.propose_transaction(
+            params,
+            &input_source,
+            target_height,
+            anchor_height,
+            spend_from_account,
+            request.clone(),
+        ) {
+            Ok(proposal) => proposal,
+            Err(InputSelectorError::InsufficientFunds { .. }) if !proposed.is_empty() => {
+                deferred.push((payment_indices, request));
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let actions = logical_action_count(&proposal).head;
+        if actions > limit {
+            if payment_indices.len() == 1 {
+                return Err(Error::ActionLimitExceeded {
+                    payment_index: payment_indices[0],
+                    actions,
+                    limit,
+                });
+            }
+            let (first, second) = payment_indices.split_at(payment_indices.len() / 2);
+            pending.push(second.to_vec());
+            pending.push(first.to_vec());
+            continue;
+        }
+
+        excluded.extend(
+            proposal
+                .steps()
+                .iter()
+                .filter_map(|step| step.shielded_inputs())
+                .flat_map(|inputs| inputs.notes().iter().map(|n| *n.internal_note_id())),
+        );
+        proposed.push(PlannedBatch {
+            payment_indices,
+            request,
+            proposal: Some(proposal),
+            depends_on: vec![],
+        });
+    }
+
+    // Deferred batches are funded by the change from the proposed batches.
+    let change_sources = proposed
+        .iter()
+        .enumerate()
+        .filter(|(_, batch)| {
+            batch.proposal.as_ref().map_or(false, |p| {
+                p.steps()
+                    .iter()
+                    .any(|step| !step.balance().proposed_change().is_empty())
+            })
+        })
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+    let mut batches = proposed;
+    batches.extend(
+        deferred
+            .into_iter()
+            .map(|(payment_indices, request)| PlannedBatch {
+                payment_indices,
+                request,
+                proposal: None,
+                depends_on: change_sources.clone(),
+            }),
+    );
+
+    Ok(BatchPlan { batches })
+}
@@ -18,11 +18,12 @@ use zcash_primitives::{
         },
         fees::FeeRule,
     },
+    zip32::Scope,
 };
 
 use crate::{
     address::{Address, UnifiedAddress},
-    data_api::{InputSource, SimpleNoteRetention, SpendableNotes},
+    data_api::{DustFilter, InputSource, SimpleNoteRetention, SpendableNotes},
     fees::{sapling, ChangeError, ChangeStrategy, DustOutputPolicy},
     proposal::{Proposal, ProposalError, ShieldedInputs},
     wallet::WalletTransparentOutput,
@@ -312,19 +313,87 @@ impl orchard_fees::OutputView for OrchardPayment {
 pub struct GreedyInputSelector<DbT, ChangeT> {
     change_strategy: ChangeT,
     dust_output_policy: DustOutputPolicy,
+    dust_filter: DustFilter,
     _ds_type: PhantomData<DbT>,
 }
 
 impl<DbT, ChangeT: ChangeStrategy> GreedyInputSelector<DbT, ChangeT> {
     /// Constructs a new greedy input selector that uses the provided change strategy to determine
     /// change values and fee amounts.
+    ///
+    /// The returned selector considers notes of any value; use [`Self::with_dust_filter`] to
+    /// exclude dust notes from selection.
     pub fn new(change_strategy: ChangeT, dust_output_policy: DustOutputPolicy) -> Self {
         GreedyInputSelector {
             change_strategy,
             dust_output_policy,
+            dust_filter: DustFilter::IncludeDust,
             _ds_type: PhantomData,
         }
     }
+
+    /// Returns a copy of this input selector that will never select notes rejected by the
+    /// given [`DustFilter`].
+    pub fn with_dust_filter(self, dust_filter: DustFilter) -> Self {
+        GreedyInputSelector {
+            dust_filter,
+            ..self
+        }
+    }
+
+    /// Returns the [`DustFilter`] applied by this input selector.
+    pub fn dust_filter(&self) -> DustFilter {
+        self.dust_filter
+    }
+}
+
+/// Removes the notes rejected by `dust_filter` from `notes`, returning the remaining notes along
+/// with the identifiers of the notes that were removed.
+fn remove_dust<NoteRef: Copy>(
+    notes: SpendableNotes<NoteRef>,
+    dust_filter: &DustFilter,
+) -> Result<(SpendableNotes<NoteRef>, Vec<NoteRef>), BalanceError> {
+    let mut dust = vec![];
+    let mut retain = |note_value: NonNegativeAmount, scope: Scope, note_id: &NoteRef| {
+        let is_dust = dust_filter.is_dust(note_value, scope == Scope::Internal);
+        if is_dust {
+            dust.push(*note_id);
+        }
+        !is_dust
+    };
+
+    let mut sapling = vec![];
+    for note in notes.sapling() {
+        if retain(
+            note.note_value()?,
+            note.spending_key_scope(),
+            note.internal_note_id(),
+        ) {
+            sapling.push(note.clone());
+        }
+    }
+
+    #[cfg(feature = "orchard")]
+    let mut orchard = vec![];
+    #[cfg(feature = "orchard")]
+    for note in notes.orchard() {
+        if retain(
+            note.note_value()?,
+            note.spending_key_scope(),
+            note.internal_note_id(),
+        ) {
+            orchard.push(note.clone());
+        }
+    }
+
+    Ok((
+        SpendableNotes::new(
+            sapling,
+            #[cfg(feature = "orchard")]
+            orchard,
+        ),
+        dust,
+    ))
 }
 
 impl<DbT, ChangeT> InputSelector for GreedyInputSelector<DbT, ChangeT>
@@ -515,15 +584,26 @@ where
             #[cfg(feature = "orchard")]
             let selectable_pools = &[ShieldedProtocol::Sapling, ShieldedProtocol::Orchard];
 
-            shielded_inputs = wallet_db
-                .select_spendable_notes(
-                    account,
-                    amount_required,
-                    selectable_pools,
-                    anchor_height,
-                    &exclude,
-                )
-                .map_err(InputSelectorError::DataSource)?;
+            // Dust notes are added to the exclusion set and selection is repeated until only
+            // admissible notes are returned. This terminates because each repetition strictly
+            // grows the exclusion set.
+            shielded_inputs = loop {
+                let selected = wallet_db
+                    .select_spendable_notes(
+                        account,
+                        amount_required,
+                        selectable_pools,
+                        anchor_height,
+                        &exclude,
+                    )
+                    .map_err(InputSelectorError::DataSource)?;
+
+                let (selected, mut dust) = remove_dust(selected, &self.dust_filter)?;
+                if dust.is_empty() {
+                    break selected;
+                }
+                exclude.append(&mut dust);
+            };
 
             let new_available = shielded_inputs.total_value()?;
             if new_available <= prior_available {
@@ -580,6 +660,8 @@ where
             .map_err(InputSelectorError::DataSource)?
             .into_iter()
             .flat_map(|v| v.into_iter())
+            // Coinbase outputs cannot be spent until they have matured.
+            .filter(|utxo| utxo.is_mature(target_height))
             .collect();
 
         let trial_balance = self.change_strategy.compute_balance(
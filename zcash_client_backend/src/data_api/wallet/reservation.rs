@@ -0,0 +1,154 @@
+//! Reservation of the inputs spent by prepared proposals.
+//!
+//! The wallet marks a note as spent only once a transaction spending it has been created and
+//! stored. An application that prepares several proposals before building any of them (for
+//! example, to present them for approval) may therefore obtain proposals that spend the same
+//! notes or transparent outputs, and only one of the resulting transactions could be mined.
+//!
+//! [`NoteReservations`] tracks the inputs spent by proposals that have been prepared but not
+//! yet built. Reserving the inputs of a proposal that spends an input already reserved by
+//! another proposal fails with [`Error::ConflictingProposal`], so that the conflict is
+//! detected before either transaction is built. Building a proposal with
+//! [`create_proposed_transactions_with_reservations`] fails in the same way if the proposal
+//! spends inputs reserved by another proposal, and releases the proposal's own reservation once
+//! it has been built. A proposal that is discarded should be released with
+//! [`NoteReservations::release`].
+//!
+//! [`create_proposed_transactions_with_reservations`]: super::create_proposed_transactions_with_reservations
+
+use std::collections::BTreeSet;
+
+use zcash_primitives::transaction::components::OutPoint;
+
+use crate::{data_api::error::Error, proposal::Proposal, wallet::NoteId};
+
+/// An input spent by a proposal.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum SpentInput {
+    Note(NoteId),
+    Utxo(OutPoint),
+}
+
+/// Returns the shielded notes and transparent outputs spent by the given proposal.
+///
+/// Notes and outputs produced by earlier steps of a multi-step proposal are not yet known to
+/// the wallet, and so are not included.
+fn spent_inputs<FeeRuleT, N>(proposal: &Proposal<FeeRuleT, N>) -> BTreeSet<SpentInput> {
+    let notes = proposal
+        .steps()
+        .iter()
+        .filter_map(|step| step.shielded_inputs())
+        .flat_map(|inputs| inputs.notes().iter())
+        .map(|note| {
+            SpentInput::Note(NoteId::new(
+                *note.txid(),
+                note.note().protocol(),
+                note.output_index(),
+            ))
+        });
+    let utxos = proposal
+        .steps()
+        .iter()
+        .flat_map(|step| step.transparent_inputs().iter())
+        .map(|utxo| SpentInput::Utxo(utxo.outpoint().clone()));
+
+    notes.chain(utxos).collect()
+}
+
+/// The set of inputs reserved by proposals that have been prepared but not yet built.
+#[derive(Debug, Clone, Default)]
+pub struct NoteReservations {
+    /// The inputs reserved by each prepared proposal. These sets are pairwise disjoint.
+    reserved: Vec<BTreeSet<SpentInput>>,
+}
+
+impl NoteReservations {
+    /// Constructs an empty set of reservations.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the inputs in `inputs` that are reserved by a proposal that does not spend
+    /// exactly `inputs`, as an [`Error::ConflictingProposal`].
+    fn check_conflicts<DE, CE, SE, FE, SignerErrT, PolicyErrT>(
+        &self,
+        inputs: &BTreeSet<SpentInput>,
+    ) -> Result<(), Error<DE, CE, SE, FE, SignerErrT, PolicyErrT>> {
+        let mut notes = vec![];
+        let mut outpoints = vec![];
+        for reserved in self.reserved.iter().filter(|reserved| *reserved != inputs) {
+            for input in inputs.intersection(reserved) {
+                match input {
+                    SpentInput::Note(note) => notes.push(*note),
+                    SpentInput::Utxo(outpoint) => outpoints.push(outpoint.clone()),
+                }
+            }
+        }
+
+        if notes.is_empty() && outpoints.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::ConflictingProposal { notes, outpoints })
+        }
+    }
+
+    /// Reserves the shielded notes and transparent outputs spent by the given proposal.
+    ///
+    /// Returns [`Error::ConflictingProposal`] with the inputs that are already reserved if the
+    /// proposal spends any of them; in that case, none of the inputs spent by the proposal are
+    /// reserved.
+    pub fn reserve<FeeRuleT, N, DE, CE, SE, FE>(
+        &mut self,
+        proposal: &Proposal<FeeRuleT, N>,
+    ) -> Result<(), Error<DE, CE, SE, FE>> {
+        let inputs = spent_inputs(proposal);
+        if self.reserved.contains(&inputs) {
+            return Ok(());
+        }
+        self.check_conflicts(&inputs)?;
+
+        if !inputs.is_empty() {
+            self.reserved.push(inputs);
+        }
+        Ok(())
+    }
+
+    /// Checks that the given proposal, which is about to be built, spends no inputs that are
+    /// reserved by another proposal.
+    pub(crate) fn check<FeeRuleT, N, DE, CE, SE, FE, SignerErrT, PolicyErrT>(
+        &self,
+        proposal: &Proposal<FeeRuleT, N>,
+    ) -> Result<(), Error<DE, CE, SE, FE, SignerErrT, PolicyErrT>> {
+        self.check_conflicts(&spent_inputs(proposal))
+    }
+
+    /// Releases the reservation of the inputs spent by the given proposal.
+    ///
+    /// This should be called once the proposal has been built, at which point the wallet
+    /// records its inputs as spent, or once the proposal has been discarded.
+    pub fn release<FeeRuleT, N>(&mut self, proposal: &Proposal<FeeRuleT, N>) {
+        let inputs = spent_inputs(proposal);
+        self.reserved.retain(|reserved| reserved != &inputs);
+    }
+
+    /// Returns whether the given note is reserved by a prepared proposal.
+    pub fn is_reserved(&self, note: &NoteId) -> bool {
+        let input = SpentInput::Note(*note);
+        self.reserved
+            .iter()
+            .any(|reserved| reserved.contains(&input))
+    }
+
+    /// Returns whether the given transparent output is reserved by a prepared proposal.
+    pub fn is_outpoint_reserved(&self, outpoint: &OutPoint) -> bool {
+        let input = SpentInput::Utxo(outpoint.clone());
+        self.reserved
+            .iter()
+            .any(|reserved| reserved.contains(&input))
+    }
+
+    /// Returns whether no inputs are reserved.
+    pub fn is_empty(&self) -> bool {
+        self.reserved.is_empty()
+    }
+}
@@ -0,0 +1,538 @@
+//! Sweeping funds controlled by an imported standalone spending key.
+//!
+//! A paper wallet, or a key exported from another wallet, controls funds that do not belong to
+//! any account in the wallet. [`sweep_external_key`] finds the funds controlled by such a key,
+//! builds a transaction that sends all of them to an account in the wallet, and stores that
+//! transaction in the wallet so that the incoming funds are reported as pending until it is
+//! mined. The key is consumed by the sweep and is never written to the wallet database.
+//!
+//! Sapling notes are found by scanning a caller-specified range of compact blocks with the
+//! key's incoming viewing keys, using an in-memory note commitment tree that is discarded once
+//! the sweep transaction has been built. Compact blocks do not contain transparent outputs, so
+//! the unspent outputs controlled by a transparent key must be obtained by the caller (for
+//! example, from a light wallet server's `GetAddressUtxos` method, for the address returned by
+//! [`ExternalKey::transparent_address`]).
+
+use std::convert::Infallible;
+use std::fmt;
+
+use incrementalmerkletree::{Position, Retention};
+use rand_core::OsRng;
+use sapling::prover::{OutputProver, SpendProver};
+use shardtree::{error::ShardTreeError, store::memory::MemoryShardStore, ShardTree};
+use zcash_keys::encoding::decode_extended_spending_key;
+use zcash_primitives::transaction::{
+    builder::{self, BuildConfig, Builder},
+    components::{
+        amount::{BalanceError, NonNegativeAmount},
+        TxOut,
+    },
+    fees::{zip317, FeeRule},
+    TxId,
+};
+use zcash_protocol::{
+    consensus::{self, BlockHeight},
+    memo::MemoBytes,
+};
+use zip32::Scope;
+
+use crate::{
+    data_api::{
+        chain::{self, BlockSource, ChainState},
+        BlockMetadata, WalletRead, WalletWrite, SAPLING_SHARD_HEIGHT,
+    },
+    scanning::{scan_block, Nullifiers, ScanError, ScanningKeys},
+};
+
+#[cfg(feature = "transparent-inputs")]
+use {
+    crate::wallet::WalletTransparentOutput, zcash_primitives::legacy::TransparentAddress,
+    zcash_protocol::consensus::NetworkType,
+};
+
+use super::decrypt_and_store_transaction;
+
+/// The identifier used for the imported key when scanning.
+const SWEEP_KEY_ID: u32 = 0;
+
+/// A standalone spending key that is not part of any account in the wallet.
+pub enum ExternalKey {
+    /// A Sapling extended spending key.
+    Sapling(sapling::zip32::ExtendedSpendingKey),
+    /// The secret key for a P2PKH transparent address.
+    #[cfg(feature = "transparent-inputs")]
+    Transparent(secp256k1::SecretKey),
+}
+
+impl ExternalKey {
+    /// Decodes a Bech32-encoded Sapling extended spending key or, when the `transparent-inputs`
+    /// feature is enabled, a transparent secret key in Wallet Import Format, for the given
+    /// network.
+    ///
+    /// Only WIF keys that correspond to compressed public keys are supported. Returns `None` if
+    /// the string is not a key of either kind for the network.
+    pub fn decode<P: consensus::Parameters>(params: &P, encoded: &str) -> Option<Self> {
+        if let Ok(extsk) =
+            decode_extended_spending_key(params.hrp_sapling_extended_spending_key(), encoded)
+        {
+            return Some(ExternalKey::Sapling(extsk));
+        }
+
+        #[cfg(feature = "transparent-inputs")]
+        if let Some(sk) = decode_wif(params.network_type(), encoded) {
+            return Some(ExternalKey::Transparent(sk));
+        }
+
+        None
+    }
+
+    /// Returns the P2PKH address controlled by this key, if it is a transparent key.
+    #[cfg(feature = "transparent-inputs")]
+    pub fn transparent_address(&self) -> Option<TransparentAddress> {
+        match self {
+            ExternalKey::Sapling(_) => None,
+            ExternalKey::Transparent(sk) => {
+                let pubkey =
+                    secp256k1::PublicKey::from_secret_key(&secp256k1::Secp256k1::new(), sk);
+                #[allow(deprecated)]
+                Some(zcash_primitives::legacy::keys::pubkey_to_address(&pubkey))
+            }
+        }
+    }
+}
+
+/// Decodes a transparent secret key in Wallet Import Format.
+#[cfg(feature = "transparent-inputs")]
+fn decode_wif(network: NetworkType, encoded: &str) -> Option<secp256k1::SecretKey> {
+    let prefix = match network {
+        NetworkType::Main => 0x80,
+        NetworkType::Test | NetworkType::Regtest => 0xef,
+    };
+    let decoded = bs58::decode(encoded).with_check(None).into_vec().ok()?;
+    // The key is followed by a 0x01 byte when it corresponds to a compressed public key.
+    match &decoded[..] {
+        [p, key @ .., 0x01] if *p == prefix && key.len() == 32 => {
+            secp256k1::SecretKey::from_slice(key).ok()
+        }
+        _ => None,
+    }
+}
+
+/// Errors that can occur when sweeping the funds controlled by an [`ExternalKey`].
+#[derive(Debug)]
+pub enum SweepError<DbError, BlockSourceError> {
+    /// An error occurred retrieving data from, or storing data in, the wallet.
+    DataSource(DbError),
+    /// An error occurred retrieving blocks from the block source.
+    BlockSource(BlockSourceError),
+    /// A block in the scanned range could not be scanned.
+    Scan(ScanError),
+    /// An error occurred in updating the in-memory note commitment tree.
+    CommitmentTree(ShardTreeError<Infallible>),
+    /// The wallet has not yet scanned any blocks, so the height at which the sweep transaction
+    /// will be mined is unknown.
+    ScanRequired,
+    /// The destination account is not known to the wallet, or has no Sapling receiver to which
+    /// the funds can be sent.
+    NoSaplingReceiver,
+    /// The funds controlled by the key do not exceed the fee for the sweep transaction.
+    InsufficientFunds {
+        available: NonNegativeAmount,
+        required: NonNegativeAmount,
+    },
+    /// An error occurred building the sweep transaction.
+    Builder(builder::Error<zip317::FeeError>),
+}
+
+impl<DE: fmt::Display, BE: fmt::Display> fmt::Display for SweepError<DE, BE> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SweepError::DataSource(e) => write!(f, "The wallet produced an error: {}", e),
+            SweepError::BlockSource(e) => write!(f, "The block source produced an error: {}", e),
+            SweepError::Scan(e) => write!(f, "A block could not be scanned: {}", e),
+            SweepError::CommitmentTree(e) => {
+                write!(f, "The note commitment tree could not be updated: {}", e)
+            }
+            SweepError::ScanRequired => write!(
+                f,
+                "The wallet must be synchronized before funds can be swept into it."
+            ),
+            SweepError::NoSaplingReceiver => write!(
+                f,
+                "The destination account has no Sapling receiver to which funds can be swept."
+            ),
+            SweepError::InsufficientFunds {
+                available,
+                required,
+            } => write!(
+                f,
+                "The key controls {} zatoshis, which does not exceed the sweep fee of {} zatoshis.",
+                u64::from(*available),
+                u64::from(*required)
+            ),
+            SweepError::Builder(e) => {
+                write!(f, "The sweep transaction could not be built: {}", e)
+            }
+        }
+    }
+}
+
+impl<DE, BE> std::error::Error for SweepError<DE, BE>
+where
+    DE: fmt::Debug + fmt::Display + std::error::Error + 'static,
+    BE: fmt::Debug + fmt::Display + std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SweepError::DataSource(e) => Some(e),
+            SweepError::BlockSource(e) => Some(e),
+            SweepError::Scan(e) => Some(e),
+            SweepError::CommitmentTree(e) => Some(e),
+            SweepError::Builder(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// A Sapling note controlled by the imported key that remains unspent as of the end of the
+/// scanned range.
+struct FoundNote {
+    nf: sapling::Nullifier,
+    note: sapling::Note,
+    position: Position,
+    scope: Scope,
+}
+
+/// The notes found by [`scan_for_notes`], together with the note commitment tree from which their
+/// witnesses are computed.
+struct ScannedNotes {
+    notes: Vec<FoundNote>,
+    tree: ShardTree<
+        MemoryShardStore<sapling::Node, BlockHeight>,
+        { sapling::NOTE_COMMITMENT_TREE_DEPTH },
+        SAPLING_SHARD_HEIGHT,
+    >,
+    anchor_height: BlockHeight,
+}
+
+/// Scans the blocks following `from_state`, up to `limit` blocks, for Sapling notes received
+/// and not subsequently spent by the given key.
+fn scan_for_notes<ParamsT, BlockSourceT>(
+    params: &ParamsT,
+    block_source: &BlockSourceT,
+    from_state: &ChainState,
+    limit: usize,
+    dfvk: &sapling::zip32::DiversifiableFullViewingKey,
+) -> Result<ScannedNotes, chain::error::Error<ShardTreeError<Infallible>, BlockSourceT::Error>>
+where
+    ParamsT: consensus::Parameters + Send + 'static,
+    BlockSourceT: BlockSource,
+{
+    let scanning_keys = ScanningKeys::from_sapling_dfvk(SWEEP_KEY_ID, dfvk);
+
+    let mut tree = ShardTree::new(MemoryShardStore::empty(), 100);
+    tree.insert_frontier(
+        from_state.final_sapling_tree().clone(),
+        Retention::Checkpoint {
+            id: from_state.block_height(),
+            is_marked: false,
+        },
+    )
+    .map_err(chain::error::Error::Wallet)?;
+
+    let mut prior_block_metadata = BlockMetadata::from_parts(
+        from_state.block_height(),
+        from_state.block_hash(),
+        Some(
+            u32::try_from(from_state.final_sapling_tree().tree_size())
+                .expect("Sapling note commitment tree size is representable as u32"),
+        ),
+        #[cfg(feature = "orchard")]
+        Some(
+            u32::try_from(from_state.final_orchard_tree().tree_size())
+                .expect("Orchard note commitment tree size is representable as u32"),
+        ),
+    );
+
+    let mut notes: Vec<FoundNote> = vec![];
+    block_source.with_blocks::<_, ShardTreeError<Infallible>>(
+        Some(from_state.block_height() + 1),
+        Some(limit),
+        |block| {
+            let nullifiers = Nullifiers::new(
+                notes.iter().map(|found| (SWEEP_KEY_ID, found.nf)).collect(),
+                #[cfg(feature = "orchard")]
+                vec![],
+            );
+            let scanned_block = scan_block(
+                params,
+                block,
+                &scanning_keys,
+                &nullifiers,
+                Some(&prior_block_metadata),
+            )
+            .map_err(chain::error::Error::Scan)?;
+
+            for wtx in scanned_block.transactions() {
+                for spend in wtx.sapling_spends() {
+                    notes.retain(|found| &found.nf != spend.nf());
+                }
+                for output in wtx.sapling_outputs() {
+                    if let (Some(nf), Some(scope)) = (output.nf(), output.recipient_key_scope()) {
+                        notes.push(FoundNote {
+                            nf: *nf,
+                            note: output.note().clone(),
+                            position: output.note_commitment_tree_position(),
+                            scope,
+                        });
+                    }
+                }
+            }
+
+            prior_block_metadata = scanned_block.to_block_metadata();
+            let height = scanned_block.height();
+            let final_tree_size = u64::from(scanned_block.sapling().final_tree_size());
+            let commitments = scanned_block.into_commitments().sapling;
+            if commitments.is_empty() {
+                tree.checkpoint(height)
+                    .map_err(chain::error::Error::Wallet)?;
+            } else {
+                let start_position = Position::from(final_tree_size - commitments.len() as u64);
+                tree.batch_insert(start_position, commitments.into_iter())
+                    .map_err(chain::error::Error::Wallet)?;
+            }
+
+            Ok(())
+        },
+    )?;
+
+    Ok(ScannedNotes {
+        notes,
+        tree,
+        anchor_height: prior_block_metadata.block_height(),
+    })
+}
+
+/// Sweeps all of the funds controlled by the given standalone spending key into an account in
+/// the wallet.
+///
+/// For a Sapling key, up to `limit` compact blocks following `from_state` are scanned for notes
+/// received by the key that are not spent within the scanned range; the range should begin
+/// before the first funds were sent to the key, and end at the wallet's chain tip. For a
+/// transparent key, `transparent_utxos` are the unspent outputs controlled by the key, and
+/// no blocks are scanned; `transparent_utxos` is ignored for Sapling keys.
+///
+/// The sweep transaction sends the value of all of the funds found, less the ZIP 317 fee, to
+/// the Sapling receiver of the current address of `to_account`. It is stored in the wallet,
+/// so that it can be retrieved for broadcast with [`WalletRead::get_transaction`] using the
+/// returned transaction ID, and so that the incoming funds are shown as pending. The key is
+/// dropped when this function returns, and is not stored in the wallet.
+#[allow(clippy::too_many_arguments)]
+#[allow(clippy::type_complexity)]
+pub fn sweep_external_key<DbT, ParamsT, BlockSourceT>(
+    wallet_db: &mut DbT,
+    params: &ParamsT,
+    spend_prover: &impl SpendProver,
+    output_prover: &impl OutputProver,
+    block_source: &BlockSourceT,
+    from_state: &ChainState,
+    limit: usize,
+    #[cfg(feature = "transparent-inputs")] transparent_utxos: &[WalletTransparentOutput],
+    key: ExternalKey,
+    to_account: DbT::AccountId,
+) -> Result<TxId, SweepError<<DbT as WalletRead>::Error, BlockSourceT::Error>>
+where
+    DbT: WalletWrite,
+    ParamsT: consensus::Parameters + Clone + Send + 'static,
+    BlockSourceT: BlockSource,
+{
+    let to = wallet_db
+        .get_current_address(to_account)
+        .map_err(SweepError::DataSource)?
+        .and_then(|ua| ua.sapling().copied())
+        .ok_or(SweepError::NoSaplingReceiver)?;
+    let target_height = wallet_db
+        .chain_height()
+        .map_err(SweepError::DataSource)?
+        .ok_or(SweepError::ScanRequired)?
+        + 1;
+
+    let (sapling_anchor, sapling_inputs) = match &key {
+        ExternalKey::Sapling(extsk) => {
+            let mut scanned = scan_for_notes(
+                params,
+                block_source,
+                from_state,
+                limit,
+                &extsk.to_diversifiable_full_viewing_key(),
+            )
+            .map_err(|e| match e {
+                chain::error::Error::Wallet(e) => SweepError::CommitmentTree(e),
+                chain::error::Error::BlockSource(e) => SweepError::BlockSource(e),
+                chain::error::Error::Scan(e) => SweepError::Scan(e),
+            })?;
+
+            let anchor = scanned
+                .tree
+                .root_at_checkpoint_id(&scanned.anchor_height)
+                .map_err(SweepError::CommitmentTree)?
+                .into();
+            let inputs = scanned
+                .notes
+                .into_iter()
+                .map(|found| {
+                    let key = match found.scope {
+                        Scope::External => extsk.clone(),
+                        Scope::Internal => extsk.derive_internal(),
+                    };
+                    scanned
+                        .tree
+                        .witness_at_checkpoint_id(found.position, &scanned.anchor_height)
+                        .map(|merkle_path| (key, found.note, merkle_path))
+                        .map_err(SweepError::CommitmentTree)
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            (anchor, inputs)
+        }
+        #[cfg(feature = "transparent-inputs")]
+        ExternalKey::Transparent(_) => (sapling::Anchor::empty_tree(), vec![]),
+    };
+
+    #[cfg(feature = "transparent-inputs")]
+    let transparent_inputs = match &key {
+        ExternalKey::Sapling(_) => &[][..],
+        ExternalKey::Transparent(_) => transparent_utxos,
+    };
+    #[cfg(not(feature = "transparent-inputs"))]
+    let transparent_inputs: &[Infallible] = &[];
+
+    // The sweep transaction has a single Sapling output, which is padded as the builder pads
+    // it.
+    let sapling_output_count = sapling::builder::BundleType::DEFAULT
+        .num_outputs(sapling_inputs.len(), 1)
+        .expect("the default bundle type permits a single output");
+    let no_outputs: &[TxOut] = &[];
+    let fee_rule = zip317::FeeRule::standard();
+    let fee = fee_rule
+        .fee_required(
+            params,
+            target_height,
+            transparent_inputs,
+            no_outputs,
+            sapling_inputs.len(),
+            sapling_output_count,
+            0,
+        )
+        .map_err(|e| SweepError::Builder(builder::Error::Fee(builder::FeeError::FeeRule(e))))?;
+
+    let input_values = sapling_inputs
+        .iter()
+        .map(|(_, note, _)| NonNegativeAmount::from_u64(note.value().inner()).ok());
+    #[cfg(feature = "transparent-inputs")]
+    let input_values = input_values.chain(transparent_inputs.iter().map(|utxo| Some(utxo.value())));
+    let available = input_values
+        .try_fold(NonNegativeAmount::ZERO, |total, value| {
+            value.and_then(|v| total + v)
+        })
+        .ok_or(SweepError::Builder(builder::Error::Balance(
+            BalanceError::Overflow,
+        )))?;
+    let value = (available - fee)
+        .filter(|value| value.is_positive())
+        .ok_or(SweepError::InsufficientFunds {
+            available,
+            required: fee,
+        })?;
+
+    let mut builder = Builder::new(
+        params.clone(),
+        target_height,
+        BuildConfig::Standard {
+            sapling_anchor: Some(sapling_anchor),
+            orchard_anchor: None,
+        },
+    );
+    for (extsk, note, merkle_path) in sapling_inputs {
+        builder
+            .add_sapling_spend::<zip317::FeeError>(&extsk, note, merkle_path)
+            .map_err(SweepError::Builder)?;
+    }
+    #[cfg(feature = "transparent-inputs")]
+    if let ExternalKey::Transparent(sk) = &key {
+        for utxo in transparent_inputs {
+            builder
+                .add_transparent_input(*sk, utxo.outpoint().clone(), utxo.txout().clone())
+                .map_err(|e| SweepError::Builder(builder::Error::TransparentBuild(e)))?;
+        }
+    }
+    // The key is discarded once the transaction has been built, so its outgoing viewing key
+    // would be of no use for recovering the output.
+    builder
+        .add_sapling_output::<zip317::FeeError>(None, to, value, MemoBytes::empty())
+        .map_err(SweepError::Builder)?;
+
+    let build_result = builder
+        .build(OsRng, spend_prover, output_prover, &fee_rule)
+        .map_err(SweepError::Builder)?;
+    let tx = build_result.transaction();
+    decrypt_and_store_transaction(params, wallet_db, tx, None).map_err(SweepError::DataSource)?;
+
+    Ok(tx.txid())
+}
+
+#[cfg(all(test, feature = "transparent-inputs"))]
+mod tests {
+    use zcash_protocol::consensus::NetworkType;
+
+    use super::decode_wif;
+
+    // The example key from the Bitcoin wiki's description of Wallet Import Format.
+    const KEY: [u8; 32] = [
+        0x0c, 0x28, 0xfc, 0xa3, 0x86, 0xc7, 0xa2, 0x27, 0x60, 0x0b, 0x2f, 0xe5, 0x0b, 0x7c, 0xae,
+        0x11, 0xec, 0x86, 0xd3, 0xbf, 0x1f, 0xbe, 0x47, 0x1b, 0xe8, 0x98, 0x27, 0xe1, 0x9d, 0x72,
+        0xaa, 0x1d,
+    ];
+
+    const MAINNET_COMPRESSED: &str = "KwdMAjGmerYanjeui5SHS7JkmpZvVipYvB2LJGU1ZxJwYvP98617";
+    const MAINNET_UNCOMPRESSED: &str = "5HueCGU8rMjxEXxiPuD5BDku4MkFqeZyd4dZ1jvhTVqvbTLvyTJ";
+    const TESTNET_COMPRESSED: &str = "cMzLdeGd5vEqxB8B6VFQoRopQ3sLAAvEzDAoQgvX54xwofSWj1fx";
+    const TESTNET_UNCOMPRESSED: &str = "91gGn1HgSap6CbU12F6z3pJri26xzp7Ay1VW6NHCoEayNXwRpu2";
+
+    #[test]
+    fn decodes_compressed_wif() {
+        assert_eq!(
+            decode_wif(NetworkType::Main, MAINNET_COMPRESSED).map(|sk| sk.secret_bytes()),
+            Some(KEY)
+        );
+        assert_eq!(
+            decode_wif(NetworkType::Test, TESTNET_COMPRESSED).map(|sk| sk.secret_bytes()),
+            Some(KEY)
+        );
+        assert_eq!(
+            decode_wif(NetworkType::Regtest, TESTNET_COMPRESSED).map(|sk| sk.secret_bytes()),
+            Some(KEY)
+        );
+    }
+
+    #[test]
+    fn rejects_uncompressed_wif() {
+        // The address of an uncompressed key is derived from a different public key encoding
+        // than the one used when spending, so these keys are not supported.
+        assert!(decode_wif(NetworkType::Main, MAINNET_UNCOMPRESSED).is_none());
+        assert!(decode_wif(NetworkType::Test, TESTNET_UNCOMPRESSED).is_none());
+    }
+
+    #[test]
+    fn rejects_wif_for_other_network() {
+        assert!(decode_wif(NetworkType::Test, MAINNET_COMPRESSED).is_none());
+        assert!(decode_wif(NetworkType::Main, TESTNET_COMPRESSED).is_none());
+    }
+
+    #[test]
+    fn rejects_corrupted_wif() {
+        let mut corrupted = MAINNET_COMPRESSED.to_owned();
+        corrupted.replace_range(10..11, "z");
+        assert!(decode_wif(NetworkType::Main, &corrupted).is_none());
+    }
+}
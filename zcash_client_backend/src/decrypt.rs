@@ -100,12 +100,21 @@ impl<A> DecryptedOutput<orchard::note::Note, A> {
 
 /// Scans a [`Transaction`] for any information that can be decrypted by the set of
 /// [`UnifiedFullViewingKey`]s.
+///
+/// `mined_height` is the height of the block in which the transaction was mined, if it is
+/// known to have been mined. Otherwise, the transaction is decrypted as of the block following
+/// `chain_tip_height` (or as of Sapling activation, if the chain tip is not known).
 pub fn decrypt_transaction<'a, P: consensus::Parameters, AccountId: Copy>(
     params: &P,
-    height: BlockHeight,
+    mined_height: Option<BlockHeight>,
+    chain_tip_height: Option<BlockHeight>,
     tx: &'a Transaction,
     ufvks: &HashMap<AccountId, UnifiedFullViewingKey>,
 ) -> DecryptedTransaction<'a, AccountId> {
+    let height = mined_height
+        .or_else(|| chain_tip_height.map(|h| h + 1))
+        .or_else(|| params.activation_height(consensus::NetworkUpgrade::Sapling))
+        .expect("Sapling activation height must be known.");
     let zip212_enforcement = zip212_enforcement(params, height);
     let sapling_bundle = tx.sapling_bundle();
     let sapling_outputs = sapling_bundle
@@ -215,6 +224,7 @@ pub fn decrypt_transaction<'a, P: consensus::Parameters, AccountId: Copy>(
         .collect();
 
     DecryptedTransaction::new(
+        mined_height,
         tx,
         sapling_outputs,
         #[cfg(feature = "orchard")]
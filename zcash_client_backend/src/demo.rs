@@ -0,0 +1,429 @@
+//! Utilities for demonstrating a wallet without network access.
+//!
+//! [`DemoChain`] fabricates a local chain of compact blocks, in which funds can be paid to
+//! addresses belonging to a wallet, and in which the transactions that the wallet creates can
+//! be mined. It implements [`BlockSource`], so that a wallet can be synchronized with it using
+//! [`scan_cached_blocks`] exactly as it would be with a chain obtained from `lightwalletd`.
+//!
+//! The blocks of a [`DemoChain`] are not valid: they contain no headers or proofs, and the
+//! transactions that pay the wallet exist only in compact form. They are suitable only for
+//! exercising wallet user interfaces, and must never be used with a wallet that holds real
+//! funds.
+//!
+//! A typical demonstration proceeds as follows:
+//!
+//! 1. Construct a [`DemoChain`], and add an account to the wallet using
+//!    [`DemoChain::birthday`] as the account's birthday.
+//! 2. Pay funds to the account with [`DemoChain::push_payment`], and call
+//!    [`DemoChain::scan_to_tip`] to synchronize the wallet.
+//! 3. Spend the funds with [`DemoChain::send`], which mines the resulting transactions in a
+//!    new block, and call [`DemoChain::scan_to_tip`] again to observe the spend.
+
+use std::{
+    cmp::{max, min},
+    collections::BTreeMap,
+    convert::Infallible,
+    num::NonZeroU32,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use nonempty::NonEmpty;
+use rand_core::{OsRng, RngCore};
+use sapling::{
+    note_encryption::{sapling_note_encryption, SaplingDomain},
+    prover::{OutputProver, SpendProver},
+    util::generate_random_rseed,
+    value::NoteValue,
+    zip32::ExtendedSpendingKey,
+    PaymentAddress,
+};
+use subtle::ConditionallySelectable;
+use zcash_note_encryption::Domain;
+use zcash_primitives::{
+    block::BlockHash,
+    consensus::{self, BlockHeight, NetworkUpgrade},
+    memo::MemoBytes,
+    transaction::{
+        components::{amount::NonNegativeAmount, sapling::zip212_enforcement},
+        fees::{zip317::FeeError as Zip317FeeError, StandardFeeRule},
+        Transaction, TxId,
+    },
+};
+
+use crate::{
+    address::Address,
+    data_api::{
+        chain::{error::Error as ChainError, scan_cached_blocks, BlockSource, ChainState},
+        error::Error,
+        scanning::ScanPriority,
+        wallet::{
+            create_proposed_transactions, input_selection::GreedyInputSelectorError,
+            propose_standard_transfer_to_address,
+        },
+        AccountBirthday, InputSource, WalletCommitmentTrees, WalletRead, WalletWrite,
+    },
+    keys::UnifiedSpendingKey,
+    proto::compact_formats::{self as compact, CompactBlock, CompactSaplingOutput, CompactTx},
+    wallet::OvkPolicy,
+    ShieldedProtocol,
+};
+
+/// The number of seconds between the times of consecutive fabricated blocks, matching the
+/// post-Blossom target block spacing.
+const BLOCK_SPACING: u32 = 75;
+
+/// A fabricated chain of compact blocks, along with the state of the note commitment trees as
+/// of the end of each block.
+pub struct DemoChain<P> {
+    params: P,
+    prior_state: ChainState,
+    blocks: BTreeMap<BlockHeight, (CompactBlock, ChainState)>,
+    next_block_time: u32,
+}
+
+impl<P: consensus::Parameters> DemoChain<P> {
+    /// Constructs a chain containing no blocks, that begins after the given chain state.
+    pub fn new(params: P, prior_state: ChainState) -> Self {
+        let next_block_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| u32::try_from(d.as_secs()).unwrap_or(u32::MAX));
+
+        DemoChain {
+            params,
+            prior_state,
+            blocks: BTreeMap::new(),
+            next_block_time,
+        }
+    }
+
+    /// Constructs a chain containing no blocks, whose first block will be the Sapling
+    /// activation block of the given network.
+    ///
+    /// Returns `None` if Sapling is not active on the given network.
+    pub fn from_sapling_activation(params: P) -> Option<Self> {
+        let activation = params.activation_height(NetworkUpgrade::Sapling)?;
+        Some(Self::new(
+            params,
+            ChainState::empty(activation - 1, BlockHash([0; 32])),
+        ))
+    }
+
+    /// Returns the network parameters of this chain.
+    pub fn params(&self) -> &P {
+        &self.params
+    }
+
+    /// Returns a birthday for accounts that are created before any block of this chain.
+    pub fn birthday(&self) -> AccountBirthday {
+        AccountBirthday::from_parts(self.prior_state.clone(), None)
+    }
+
+    /// Returns the state of the chain as of the end of its last block.
+    pub fn tip_state(&self) -> &ChainState {
+        self.blocks
+            .values()
+            .next_back()
+            .map_or(&self.prior_state, |(_, state)| state)
+    }
+
+    /// Returns the state of the chain as of the end of the block at the given height, or
+    /// `None` if the height is not within this chain.
+    pub fn state_at(&self, height: BlockHeight) -> Option<&ChainState> {
+        if height == self.prior_state.block_height() {
+            Some(&self.prior_state)
+        } else {
+            self.blocks.get(&height).map(|(_, state)| state)
+        }
+    }
+
+    /// Appends a block containing a transaction that pays `value` to the given Sapling
+    /// address, and returns the height of the new block.
+    ///
+    /// The transaction also contains an output to a randomly generated address, so that the
+    /// wallet must trial-decrypt outputs that do not belong to it.
+    pub fn push_payment(&mut self, to: &PaymentAddress, value: NonNegativeAmount) -> BlockHeight {
+        let height = self.tip_state().block_height() + 1;
+        let mut rng = OsRng;
+
+        let decoy_to = {
+            let mut seed = [0; 32];
+            rng.fill_bytes(&mut seed);
+            ExtendedSpendingKey::master(&seed).default_address().1
+        };
+
+        let mut txid = vec![0; 32];
+        rng.fill_bytes(&mut txid);
+        let ctx = CompactTx {
+            hash: txid,
+            outputs: vec![
+                self.compact_output(height, to, value),
+                self.compact_output(height, &decoy_to, value),
+            ],
+            ..Default::default()
+        };
+
+        self.push_block(vec![ctx])
+    }
+
+    /// Appends the given number of blocks containing no transactions, and returns the height
+    /// of the last block of the chain.
+    pub fn push_empty_blocks(&mut self, count: u32) -> BlockHeight {
+        for _ in 0..count {
+            self.push_block(vec![]);
+        }
+        self.tip_state().block_height()
+    }
+
+    /// Appends a block containing the given transactions, and returns the height of the new
+    /// block.
+    pub fn mine(&mut self, txs: &[Transaction]) -> BlockHeight {
+        self.push_block(
+            txs.iter()
+                .enumerate()
+                .map(|(i, tx)| CompactTx::from_transaction(i as u64, tx))
+                .collect(),
+        )
+    }
+
+    /// Removes all blocks above the given height from the chain, in order to simulate a
+    /// chain reorganization.
+    pub fn truncate_to_height(&mut self, height: BlockHeight) {
+        self.blocks
+            .retain(|block_height, _| *block_height <= height);
+    }
+
+    /// Notifies the wallet of the tip of this chain, and then scans every block that the
+    /// wallet requires, in the order that the wallet suggests.
+    pub fn scan_to_tip<DbT>(
+        &self,
+        wallet_db: &mut DbT,
+    ) -> Result<(), ChainError<DbT::Error, Infallible>>
+    where
+        P: Clone + Send + 'static,
+        DbT: WalletWrite,
+        <DbT as WalletRead>::AccountId: ConditionallySelectable + Default + Send + 'static,
+    {
+        let first_height = self.prior_state.block_height() + 1;
+        let tip_height = self.tip_state().block_height();
+        wallet_db
+            .update_chain_tip(tip_height)
+            .map_err(ChainError::Wallet)?;
+
+        loop {
+            // Ranges outside this chain cannot be scanned, so they are skipped.
+            let next_range = wallet_db
+                .suggest_scan_ranges()
+                .map_err(ChainError::Wallet)?
+                .into_iter()
+                .filter(|range| range.priority() > ScanPriority::Scanned)
+                .find_map(|range| {
+                    let start = max(range.block_range().start, first_height);
+                    let end = min(range.block_range().end, tip_height + 1);
+                    (start < end).then_some(start..end)
+                });
+
+            match next_range {
+                Some(range) => {
+                    let from_state = self
+                        .state_at(range.start - 1)
+                        .expect("the range lies within the chain");
+                    scan_cached_blocks(
+                        &self.params,
+                        self,
+                        wallet_db,
+                        range.start,
+                        from_state,
+                        (u32::from(range.end) - u32::from(range.start)) as usize,
+                    )?;
+                }
+                None => return Ok(()),
+            }
+        }
+    }
+
+    /// Creates transactions that send `amount` from the account of the given spending key to
+    /// the given address, and mines them in a new block.
+    ///
+    /// The wallet must have been synchronized with this chain using [`Self::scan_to_tip`] so
+    /// that it is able to select notes to spend; it must be synchronized again afterwards in
+    /// order to observe the mined transactions.
+    #[allow(clippy::too_many_arguments)]
+    #[allow(clippy::type_complexity)]
+    pub fn send<DbT>(
+        &mut self,
+        wallet_db: &mut DbT,
+        spend_prover: &impl SpendProver,
+        output_prover: &impl OutputProver,
+        usk: &UnifiedSpendingKey,
+        to: &Address,
+        amount: NonNegativeAmount,
+        memo: Option<MemoBytes>,
+    ) -> Result<
+        NonEmpty<TxId>,
+        Error<
+            <DbT as WalletRead>::Error,
+            <DbT as WalletCommitmentTrees>::Error,
+            GreedyInputSelectorError<Zip317FeeError, DbT::NoteRef>,
+            Zip317FeeError,
+        >,
+    >
+    where
+        P: Clone,
+        DbT: InputSource,
+        DbT: WalletWrite<
+            Error = <DbT as InputSource>::Error,
+            AccountId = <DbT as InputSource>::AccountId,
+        >,
+        DbT: WalletCommitmentTrees,
+        DbT::NoteRef: Copy + Eq + Ord,
+    {
+        let account = wallet_db
+            .get_account_for_ufvk(&usk.to_unified_full_viewing_key())
+            .map_err(Error::DataSource)?
+            .ok_or(Error::KeyNotRecognized)?;
+
+        let proposal = propose_standard_transfer_to_address(
+            wallet_db,
+            &self.params,
+            StandardFeeRule::Zip317,
+            account.id(),
+            NonZeroU32::new(1).unwrap(),
+            to,
+            amount,
+            memo,
+            None,
+            ShieldedProtocol::Sapling,
+        )?;
+
+        let txids = create_proposed_transactions(
+            wallet_db,
+            &self.params,
+            spend_prover,
+            output_prover,
+            usk,
+            OvkPolicy::Sender,
+            &proposal,
+        )?;
+
+        let txs = txids
+            .iter()
+            .map(|txid| {
+                wallet_db
+                    .get_transaction(*txid)
+                    .map_err(Error::DataSource)
+                    .map(|tx| tx.expect("the wallet stores the transactions that it creates"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        self.mine(&txs);
+
+        Ok(txids)
+    }
+
+    /// Constructs a compact output that pays `value` to the given address in a block at the
+    /// given height.
+    fn compact_output(
+        &self,
+        height: BlockHeight,
+        to: &PaymentAddress,
+        value: NonNegativeAmount,
+    ) -> CompactSaplingOutput {
+        let mut rng = OsRng;
+        let rseed = generate_random_rseed(zip212_enforcement(&self.params, height), &mut rng);
+        let note = sapling::Note::from_parts(*to, NoteValue::from_raw(value.into()), rseed);
+        let encryptor =
+            sapling_note_encryption(None, note.clone(), *MemoBytes::empty().as_array(), &mut rng);
+
+        CompactSaplingOutput {
+            cmu: note.cmu().to_bytes().to_vec(),
+            ephemeral_key: SaplingDomain::epk_bytes(encryptor.epk()).0.to_vec(),
+            ciphertext: encryptor.encrypt_note_plaintext().as_ref()[..52].to_vec(),
+        }
+    }
+
+    /// Appends a block containing the given transactions, and returns its height.
+    fn push_block(&mut self, mut vtx: Vec<CompactTx>) -> BlockHeight {
+        let prior_state = self.tip_state().clone();
+        let height = prior_state.block_height() + 1;
+
+        for (i, tx) in vtx.iter_mut().enumerate() {
+            tx.index = i as u64;
+        }
+
+        let final_sapling_tree = vtx.iter().flat_map(|tx| tx.outputs.iter()).fold(
+            prior_state.final_sapling_tree().clone(),
+            |mut acc, output| {
+                acc.append(sapling::Node::from_cmu(
+                    &output.cmu().expect("fabricated outputs are valid"),
+                ));
+                acc
+            },
+        );
+        #[cfg(feature = "orchard")]
+        let final_orchard_tree = vtx.iter().flat_map(|tx| tx.actions.iter()).fold(
+            prior_state.final_orchard_tree().clone(),
+            |mut acc, action| {
+                acc.append(orchard::tree::MerkleHashOrchard::from_cmx(
+                    &action.cmx().expect("mined actions are valid"),
+                ));
+                acc
+            },
+        );
+
+        let mut hash = [0; 32];
+        OsRng.fill_bytes(&mut hash);
+
+        let cb = CompactBlock {
+            height: height.into(),
+            hash: hash.to_vec(),
+            prev_hash: prior_state.block_hash().0.to_vec(),
+            time: self.next_block_time,
+            chain_metadata: Some(compact::ChainMetadata {
+                sapling_commitment_tree_size: u32::try_from(final_sapling_tree.tree_size())
+                    .expect("Sapling note commitment tree size fits in a u32"),
+                #[cfg(feature = "orchard")]
+                orchard_commitment_tree_size: u32::try_from(final_orchard_tree.tree_size())
+                    .expect("Orchard note commitment tree size fits in a u32"),
+                #[cfg(not(feature = "orchard"))]
+                orchard_commitment_tree_size: 0,
+            }),
+            vtx,
+            ..Default::default()
+        };
+        self.next_block_time = self.next_block_time.saturating_add(BLOCK_SPACING);
+
+        let state = ChainState::new(
+            height,
+            BlockHash(hash),
+            final_sapling_tree,
+            #[cfg(feature = "orchard")]
+            final_orchard_tree,
+        );
+        self.blocks.insert(height, (cb, state));
+        height
+    }
+}
+
+impl<P> BlockSource for DemoChain<P> {
+    type Error = Infallible;
+
+    fn with_blocks<F, WalletErrT>(
+        &self,
+        from_height: Option<BlockHeight>,
+        limit: Option<usize>,
+        mut with_block: F,
+    ) -> Result<(), ChainError<WalletErrT, Self::Error>>
+    where
+        F: FnMut(CompactBlock) -> Result<(), ChainError<WalletErrT, Self::Error>>,
+    {
+        for (block, _) in self
+            .blocks
+            .range(from_height.unwrap_or_else(|| BlockHeight::from_u32(0))..)
+            .map(|(_, entry)| entry)
+            .take(limit.unwrap_or(usize::MAX))
+        {
+            with_block(block.clone())?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,389 @@
+//! Payment disclosures for individual shielded outputs.
+//!
+//! A payment disclosure allows the sender of a transaction to prove to a third party
+//! (typically the recipient, such as a merchant) that a particular shielded output of that
+//! transaction paid a specific value and memo to a specific recipient, without revealing
+//! any viewing key. The disclosure contains the outgoing cipher key (`ock`) for a single
+//! output; this key can only be used to decrypt that output, and the note commitment
+//! check performed during decryption binds the recovered note to the transaction.
+
+use std::fmt;
+
+use sapling::note_encryption::SaplingDomain;
+use zcash_note_encryption::{try_output_recovery_with_ock, Domain, OutgoingCipherKey};
+use zcash_primitives::{
+    consensus::{self, BlockHeight},
+    memo::MemoBytes,
+    transaction::{
+        components::{amount::NonNegativeAmount, sapling::zip212_enforcement},
+        Transaction, TxId,
+    },
+    zip32::Scope,
+};
+
+use crate::{address::Receiver, keys::UnifiedFullViewingKey, wallet::Note, ShieldedProtocol};
+
+#[cfg(feature = "orchard")]
+use {orchard::note_encryption::OrchardDomain, zcash_note_encryption::EphemeralKeyBytes};
+
+/// The version byte of the current payment disclosure encoding.
+const DISCLOSURE_VERSION: u8 = 1;
+
+/// The length in bytes of an encoded payment disclosure.
+const DISCLOSURE_LEN: usize = 1 + 32 + 1 + 4 + 32;
+
+/// Errors that can occur in the creation, encoding, or verification of a payment
+/// disclosure.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The transaction provided does not have the transaction ID referenced by the
+    /// disclosure.
+    TxIdMismatch { expected: TxId, actual: TxId },
+    /// The transaction does not contain an output at the given index in the given
+    /// shielded pool.
+    OutputNotFound(ShieldedProtocol, usize),
+    /// The output could not be recovered; either the disclosure was not produced for
+    /// this output, or the viewing key used to create it is not the sender's key.
+    RecoveryFailed,
+    /// The disclosure references a shielded protocol that is not supported by this build.
+    ProtocolNotSupported(ShieldedProtocol),
+    /// The encoded disclosure was not valid hex, or had an incorrect length.
+    InvalidEncoding,
+    /// The encoded disclosure has a version that is not recognized.
+    UnknownVersion(u8),
+    /// The encoded disclosure references an unknown shielded protocol.
+    UnknownProtocol(u8),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::TxIdMismatch { expected, actual } => write!(
+                f,
+                "Disclosure is for transaction {}, but transaction {} was provided.",
+                expected, actual
+            ),
+            Error::OutputNotFound(protocol, index) => write!(
+                f,
+                "Transaction has no {:?} output at index {}.",
+                protocol, index
+            ),
+            Error::RecoveryFailed => write!(
+                f,
+                "The disclosed output could not be recovered using the disclosed key."
+            ),
+            Error::ProtocolNotSupported(protocol) => write!(
+                f,
+                "Disclosures for the {:?} protocol are not supported by this build.",
+                protocol
+            ),
+            Error::InvalidEncoding => write!(f, "Payment disclosure encoding is invalid."),
+            Error::UnknownVersion(v) => write!(f, "Unknown payment disclosure version {}.", v),
+            Error::UnknownProtocol(p) => {
+                write!(f, "Unknown shielded protocol identifier {}.", p)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// A disclosure of the outgoing cipher key for a single shielded output of a transaction.
+#[derive(Clone, PartialEq, Eq)]
+pub struct PaymentDisclosure {
+    txid: TxId,
+    protocol: ShieldedProtocol,
+    output_index: u32,
+    ock: [u8; 32],
+}
+
+impl fmt::Debug for PaymentDisclosure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PaymentDisclosure")
+            .field("txid", &self.txid)
+            .field("protocol", &self.protocol)
+            .field("output_index", &self.output_index)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PaymentDisclosure {
+    /// Returns the ID of the transaction containing the disclosed output.
+    pub fn txid(&self) -> &TxId {
+        &self.txid
+    }
+
+    /// Returns the shielded protocol of the disclosed output.
+    pub fn protocol(&self) -> ShieldedProtocol {
+        self.protocol
+    }
+
+    /// Returns the index of the disclosed output within its bundle. For Orchard, this is
+    /// the index of the action.
+    pub fn output_index(&self) -> u32 {
+        self.output_index
+    }
+
+    /// Encodes this disclosure as a hex string, suitable for sharing with a verifier.
+    pub fn encode(&self) -> String {
+        let mut bytes = Vec::with_capacity(DISCLOSURE_LEN);
+        bytes.push(DISCLOSURE_VERSION);
+        bytes.extend_from_slice(self.txid.as_ref());
+        bytes.push(match self.protocol {
+            ShieldedProtocol::Sapling => 0,
+            ShieldedProtocol::Orchard => 1,
+        });
+        bytes.extend_from_slice(&self.output_index.to_le_bytes());
+        bytes.extend_from_slice(&self.ock);
+        hex::encode(bytes)
+    }
+
+    /// Decodes a disclosure from the hex encoding produced by [`PaymentDisclosure::encode`].
+    pub fn decode(s: &str) -> Result<Self, Error> {
+        let bytes = hex::decode(s).map_err(|_| Error::InvalidEncoding)?;
+        if bytes.len() != DISCLOSURE_LEN {
+            return Err(Error::InvalidEncoding);
+        }
+        if bytes[0] != DISCLOSURE_VERSION {
+            return Err(Error::UnknownVersion(bytes[0]));
+        }
+
+        let txid = TxId::from_bytes(bytes[1..33].try_into().unwrap());
+        let protocol = match bytes[33] {
+            0 => ShieldedProtocol::Sapling,
+            1 => ShieldedProtocol::Orchard,
+            p => return Err(Error::UnknownProtocol(p)),
+        };
+        let output_index = u32::from_le_bytes(bytes[34..38].try_into().unwrap());
+        let ock = bytes[38..70].try_into().unwrap();
+
+        Ok(PaymentDisclosure {
+            txid,
+            protocol,
+            output_index,
+            ock,
+        })
+    }
+}
+
+/// The payment information revealed by a verified [`PaymentDisclosure`].
+pub struct DisclosedPayment {
+    note: Note,
+    memo: MemoBytes,
+}
+
+impl DisclosedPayment {
+    /// Returns the note created by the disclosed output.
+    pub fn note(&self) -> &Note {
+        &self.note
+    }
+
+    /// Returns the value of the disclosed output.
+    pub fn value(&self) -> NonNegativeAmount {
+        self.note.value()
+    }
+
+    /// Returns the protocol-level recipient of the disclosed output.
+    ///
+    /// Use [`Receiver::corresponds`] to check whether this receiver is part of an address.
+    pub fn recipient(&self) -> Receiver {
+        match &self.note {
+            Note::Sapling(n) => Receiver::Sapling(n.recipient()),
+            #[cfg(feature = "orchard")]
+            Note::Orchard(n) => Receiver::Orchard(n.recipient()),
+        }
+    }
+
+    /// Returns the memo of the disclosed output.
+    pub fn memo(&self) -> &MemoBytes {
+        &self.memo
+    }
+}
+
+/// Creates a [`PaymentDisclosure`] for the output at `output_index` of the given shielded
+/// pool in `tx`, using the outgoing viewing keys of the sender's [`UnifiedFullViewingKey`].
+///
+/// `height` is the height at which the transaction was mined, or the target height at which
+/// it was constructed, and is used to determine the note plaintext version. Both the
+/// external and internal outgoing viewing keys are tried; an error is returned if neither
+/// can recover the output.
+pub fn create_payment_disclosure<P: consensus::Parameters>(
+    params: &P,
+    height: BlockHeight,
+    tx: &Transaction,
+    ufvk: &UnifiedFullViewingKey,
+    protocol: ShieldedProtocol,
+    output_index: u32,
+) -> Result<PaymentDisclosure, Error> {
+    let disclosure = |ock: OutgoingCipherKey| PaymentDisclosure {
+        txid: tx.txid(),
+        protocol,
+        output_index,
+        ock: ock.0,
+    };
+
+    [Scope::External, Scope::Internal]
+        .into_iter()
+        .map(|scope| -> Result<Option<PaymentDisclosure>, Error> {
+            let ock = match protocol {
+                ShieldedProtocol::Sapling => {
+                    let output = sapling_output(tx, output_index)?;
+                    ufvk.sapling().map(|dfvk| {
+                        SaplingDomain::derive_ock(
+                            &dfvk.to_ovk(scope),
+                            output.cv(),
+                            &output.cmu().to_bytes(),
+                            output.ephemeral_key(),
+                        )
+                    })
+                }
+                #[cfg(feature = "orchard")]
+                ShieldedProtocol::Orchard => {
+                    let action = orchard_action(tx, output_index)?;
+                    ufvk.orchard().map(|fvk| {
+                        OrchardDomain::derive_ock(
+                            &fvk.to_ovk(scope),
+                            action.cv_net(),
+                            &action.cmx().to_bytes(),
+                            &EphemeralKeyBytes(action.encrypted_note().epk_bytes),
+                        )
+                    })
+                }
+                #[cfg(not(feature = "orchard"))]
+                ShieldedProtocol::Orchard => {
+                    return Err(Error::ProtocolNotSupported(protocol));
+                }
+            };
+
+            match ock.map(disclosure) {
+                Some(d) => match recover(params, height, tx, &d) {
+                    Ok(_) => Ok(Some(d)),
+                    Err(Error::RecoveryFailed) => Ok(None),
+                    Err(e) => Err(e),
+                },
+                None => Ok(None),
+            }
+        })
+        .find_map(|res| res.transpose())
+        .unwrap_or(Err(Error::RecoveryFailed))
+}
+
+/// Verifies a [`PaymentDisclosure`] against the transaction it references, returning the
+/// value, recipient, and memo of the disclosed output.
+///
+/// `height` is the height at which the transaction was mined, and is used to determine the
+/// note plaintext version.
+pub fn verify_payment_disclosure<P: consensus::Parameters>(
+    params: &P,
+    height: BlockHeight,
+    tx: &Transaction,
+    disclosure: &PaymentDisclosure,
+) -> Result<DisclosedPayment, Error> {
+    if tx.txid() != disclosure.txid {
+        return Err(Error::TxIdMismatch {
+            expected: disclosure.txid,
+            actual: tx.txid(),
+        });
+    }
+
+    recover(params, height, tx, disclosure)
+}
+
+fn recover<P: consensus::Parameters>(
+    params: &P,
+    height: BlockHeight,
+    tx: &Transaction,
+    disclosure: &PaymentDisclosure,
+) -> Result<DisclosedPayment, Error> {
+    let ock = OutgoingCipherKey(disclosure.ock);
+    let (note, memo) = match disclosure.protocol {
+        ShieldedProtocol::Sapling => {
+            let output = sapling_output(tx, disclosure.output_index)?;
+            let domain = SaplingDomain::new(zip212_enforcement(params, height));
+            try_output_recovery_with_ock(&domain, &ock, output, output.out_ciphertext())
+                .map(|(note, _, memo)| (Note::Sapling(note), memo))
+        }
+        #[cfg(feature = "orchard")]
+        ShieldedProtocol::Orchard => {
+            let action = orchard_action(tx, disclosure.output_index)?;
+            let domain = OrchardDomain::for_action(action);
+            try_output_recovery_with_ock(
+                &domain,
+                &ock,
+                action,
+                &action.encrypted_note().out_ciphertext,
+            )
+            .map(|(note, _, memo)| (Note::Orchard(note), memo))
+        }
+        #[cfg(not(feature = "orchard"))]
+        ShieldedProtocol::Orchard => {
+            return Err(Error::ProtocolNotSupported(disclosure.protocol));
+        }
+    }
+    .ok_or(Error::RecoveryFailed)?;
+
+    Ok(DisclosedPayment {
+        note,
+        memo: MemoBytes::from_bytes(&memo).expect("correct length"),
+    })
+}
+
+fn sapling_output(
+    tx: &Transaction,
+    output_index: u32,
+) -> Result<&sapling::bundle::OutputDescription<sapling::bundle::GrothProofBytes>, Error> {
+    tx.sapling_bundle()
+        .and_then(|b| b.shielded_outputs().get(output_index as usize))
+        .ok_or(Error::OutputNotFound(
+            ShieldedProtocol::Sapling,
+            output_index as usize,
+        ))
+}
+
+#[cfg(feature = "orchard")]
+fn orchard_action(
+    tx: &Transaction,
+    output_index: u32,
+) -> Result<
+    &orchard::Action<
+        orchard::primitives::redpallas::Signature<orchard::primitives::redpallas::SpendAuth>,
+    >,
+    Error,
+> {
+    tx.orchard_bundle()
+        .and_then(|b| b.actions().get(output_index as usize))
+        .ok_or(Error::OutputNotFound(
+            ShieldedProtocol::Orchard,
+            output_index as usize,
+        ))
+}
+
+#[cfg(test)]
+mod tests {
+    use zcash_primitives::transaction::TxId;
+
+    use super::{Error, PaymentDisclosure};
+    use crate::ShieldedProtocol;
+
+    #[test]
+    fn encoding_roundtrip() {
+        let disclosure = PaymentDisclosure {
+            txid: TxId::from_bytes([7; 32]),
+            protocol: ShieldedProtocol::Orchard,
+            output_index: 3,
+            ock: [42; 32],
+        };
+
+        let encoded = disclosure.encode();
+        assert_eq!(PaymentDisclosure::decode(&encoded), Ok(disclosure));
+
+        assert_eq!(
+            PaymentDisclosure::decode(&encoded[2..]),
+            Err(Error::InvalidEncoding)
+        );
+        assert_eq!(
+            PaymentDisclosure::decode(&format!("02{}", &encoded[2..])),
+            Err(Error::UnknownVersion(2))
+        );
+    }
+}
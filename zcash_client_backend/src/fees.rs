@@ -15,6 +15,7 @@ use zcash_primitives::{
 use crate::ShieldedProtocol;
 
 pub(crate) mod common;
+mod estimate;
 pub mod fixed;
 #[cfg(feature = "orchard")]
 pub mod orchard;
@@ -22,6 +23,8 @@ pub mod sapling;
 pub mod standard;
 pub mod zip317;
 
+pub use estimate::{estimate_tx_size, logical_action_count, MAX_STANDARD_TX_SIZE};
+
 /// A proposed change amount and output pool.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ChangeValue {
@@ -0,0 +1,275 @@
+//! Estimation of the sizes of the transactions described by a proposal.
+//!
+//! The size of a v5 transaction is determined by the number of its inputs and outputs in each
+//! pool: shielded spends, outputs, and actions have fixed encoded sizes, and the Sapling and
+//! Orchard bundles are padded to the minimum numbers of outputs and actions that the
+//! transaction builder produces. Transparent inputs are assumed to spend P2PKH outputs with
+//! signatures of the maximum length, and transparent outputs are assumed to be P2PKH outputs,
+//! so the estimate is an upper bound for transactions having transparent components and is
+//! exact otherwise.
+
+use nonempty::NonEmpty;
+
+use crate::{
+    proposal::{Proposal, Step, StepOutputIndex},
+    PoolType, ShieldedProtocol,
+};
+
+/// The maximum size, in bytes, of a transaction that nodes will relay under their default
+/// policy.
+pub const MAX_STANDARD_TX_SIZE: usize = 100_000;
+
+/// The size of the fields of a v5 transaction that precede its bundles.
+const V5_HEADER_SIZE: usize = 4 + 4 + 4 + 4 + 4;
+
+/// The maximum size of a transparent input spending a P2PKH output: the outpoint, the script
+/// length, a script containing a 73-byte signature and a 33-byte public key, and the sequence
+/// number.
+const P2PKH_INPUT_SIZE: usize = 36 + 1 + (1 + 73 + 1 + 33) + 4;
+
+/// The size of a P2PKH transparent output.
+const P2PKH_OUTPUT_SIZE: usize = 8 + 1 + 25;
+
+/// The size of a Sapling spend: the compact description, its proof, and its spend
+/// authorization signature.
+const SAPLING_SPEND_SIZE: usize = 32 * 3 + 192 + 64;
+
+/// The size of a Sapling output: the compact description, including the note ciphertexts, and
+/// its proof.
+const SAPLING_OUTPUT_SIZE: usize = 32 * 3 + 580 + 80 + 192;
+
+/// The size of an Orchard action, excluding its share of the aggregated proof: the action
+/// description, including the note ciphertexts, and its spend authorization signature.
+const ORCHARD_ACTION_SIZE: usize = 32 * 5 + 580 + 80 + 64;
+
+/// The minimum number of outputs in a non-empty Sapling bundle.
+const MIN_SAPLING_OUTPUTS: usize = 2;
+
+/// The minimum number of actions in a non-empty Orchard bundle.
+const MIN_ORCHARD_ACTIONS: usize = 2;
+
+/// The numbers of inputs and outputs in each pool of the transaction described by a step.
+#[derive(Default)]
+struct StepShape {
+    transparent_inputs: usize,
+    transparent_outputs: usize,
+    sapling_spends: usize,
+    sapling_outputs: usize,
+    orchard_spends: usize,
+    orchard_outputs: usize,
+}
+
+impl StepShape {
+    fn add_input(&mut self, pool: PoolType) {
+        match pool {
+            PoolType::Transparent => self.transparent_inputs += 1,
+            PoolType::Shielded(ShieldedProtocol::Sapling) => self.sapling_spends += 1,
+            PoolType::Shielded(ShieldedProtocol::Orchard) => self.orchard_spends += 1,
+        }
+    }
+
+    fn add_output(&mut self, pool: PoolType) {
+        match pool {
+            PoolType::Transparent => self.transparent_outputs += 1,
+            PoolType::Shielded(ShieldedProtocol::Sapling) => self.sapling_outputs += 1,
+            PoolType::Shielded(ShieldedProtocol::Orchard) => self.orchard_outputs += 1,
+        }
+    }
+
+    /// Returns the number of outputs in the Sapling bundle, including padding.
+    fn sapling_bundle_outputs(&self) -> usize {
+        if self.sapling_spends + self.sapling_outputs > 0 {
+            std::cmp::max(self.sapling_outputs, MIN_SAPLING_OUTPUTS)
+        } else {
+            0
+        }
+    }
+
+    /// Returns the number of actions in the Orchard bundle, including padding.
+    fn orchard_bundle_actions(&self) -> usize {
+        if self.orchard_spends + self.orchard_outputs > 0 {
+            std::cmp::max(
+                std::cmp::max(self.orchard_spends, self.orchard_outputs),
+                MIN_ORCHARD_ACTIONS,
+            )
+        } else {
+            0
+        }
+    }
+
+    fn logical_actions(&self) -> usize {
+        // Under ZIP 317, each P2PKH input and output accounts for a single logical action.
+        std::cmp::max(self.transparent_inputs, self.transparent_outputs)
+            + std::cmp::max(self.sapling_spends, self.sapling_bundle_outputs())
+            + self.orchard_bundle_actions()
+    }
+
+    fn size(&self) -> usize {
+        let transparent = compact_size_len(self.transparent_inputs)
+            + self.transparent_inputs * P2PKH_INPUT_SIZE
+            + compact_size_len(self.transparent_outputs)
+            + self.transparent_outputs * P2PKH_OUTPUT_SIZE;
+
+        let sapling_outputs = self.sapling_bundle_outputs();
+        let sapling = compact_size_len(self.sapling_spends)
+            + self.sapling_spends * SAPLING_SPEND_SIZE
+            + compact_size_len(sapling_outputs)
+            + sapling_outputs * SAPLING_OUTPUT_SIZE
+            + if self.sapling_spends > 0 {
+                // anchorSapling
+                32
+            } else {
+                0
+            }
+            + if self.sapling_spends + sapling_outputs > 0 {
+                // valueBalanceSapling and bindingSigSapling
+                8 + 64
+            } else {
+                0
+            };
+
+        let actions = self.orchard_bundle_actions();
+        let orchard = compact_size_len(actions)
+            + actions * ORCHARD_ACTION_SIZE
+            + if actions > 0 {
+                let proof_size = 2720 + 2272 * actions;
+                // flagsOrchard, valueBalanceOrchard, anchorOrchard, the proof, and
+                // bindingSigOrchard
+                1 + 8 + 32 + compact_size_len(proof_size) + proof_size + 64
+            } else {
+                0
+            };
+
+        V5_HEADER_SIZE + transparent + sapling + orchard
+    }
+}
+
+/// Returns the length of the CompactSize encoding of `n`.
+fn compact_size_len(n: usize) -> usize {
+    match n {
+        0..=0xfc => 1,
+        0xfd..=0xffff => 3,
+        0x10000..=0xffff_ffff => 5,
+        _ => 9,
+    }
+}
+
+fn step_shape<NoteRef>(steps: &NonEmpty<Step<NoteRef>>, step: &Step<NoteRef>) -> StepShape {
+    let mut shape = StepShape {
+        transparent_inputs: step.transparent_inputs().len(),
+        ..Default::default()
+    };
+
+    if let Some(inputs) = step.shielded_inputs() {
+        for note in inputs.notes() {
+            shape.add_input(PoolType::Shielded(note.note().protocol()));
+        }
+    }
+    for input in step.prior_step_inputs() {
+        let prior = &steps[input.step_index()];
+        let pool = match input.output_index() {
+            StepOutputIndex::Payment(i) => prior.payment_pools().get(&i).copied(),
+            StepOutputIndex::Change(i) => prior
+                .balance()
+                .proposed_change()
+                .get(i)
+                .map(|change| PoolType::Shielded(change.output_pool())),
+        };
+        // A proposal that passed validation refers only to outputs that exist.
+        if let Some(pool) = pool {
+            shape.add_input(pool);
+        }
+    }
+
+    for pool in step.payment_pools().values() {
+        shape.add_output(*pool);
+    }
+    for change in step.balance().proposed_change() {
+        shape.add_output(PoolType::Shielded(change.output_pool()));
+    }
+
+    shape
+}
+
+fn map_steps<FeeRuleT, NoteRef>(
+    proposal: &Proposal<FeeRuleT, NoteRef>,
+    f: impl Fn(&StepShape) -> usize,
+) -> NonEmpty<usize> {
+    let steps = proposal.steps();
+    NonEmpty::from_vec(
+        steps
+            .iter()
+            .map(|step| f(&step_shape(steps, step)))
+            .collect(),
+    )
+    .expect("A proposal has at least one step.")
+}
+
+/// Returns the number of ZIP 317 logical actions of the transaction produced by each step of
+/// the given proposal.
+///
+/// Padding of the Sapling and Orchard bundles is included, as it is in the fee computed by
+/// the transaction builder.
+pub fn logical_action_count<FeeRuleT, NoteRef>(
+    proposal: &Proposal<FeeRuleT, NoteRef>,
+) -> NonEmpty<usize> {
+    map_steps(proposal, StepShape::logical_actions)
+}
+
+/// Returns an estimate of the serialized size, in bytes, of the v5 transaction produced by
+/// each step of the given proposal.
+///
+/// The estimate is exact for transactions having no transparent inputs or outputs, and is an
+/// upper bound otherwise. Callers can compare the estimate with [`MAX_STANDARD_TX_SIZE`] to
+/// check that a transaction will be relayed before building it.
+pub fn estimate_tx_size<FeeRuleT, NoteRef>(
+    proposal: &Proposal<FeeRuleT, NoteRef>,
+) -> NonEmpty<usize> {
+    map_steps(proposal, StepShape::size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compact_size_len, StepShape};
+
+    #[test]
+    fn fully_shielded_sizes() {
+        // A Sapling transaction with one spend and two outputs.
+        let sapling = StepShape {
+            sapling_spends: 1,
+            sapling_outputs: 2,
+            ..Default::default()
+        };
+        assert_eq!(sapling.logical_actions(), 2);
+        assert_eq!(sapling.size(), 20 + 2 + 1 + 352 + 1 + 2 * 948 + 32 + 72 + 1);
+
+        // A single Sapling output is padded to two.
+        let padded = StepShape {
+            sapling_spends: 1,
+            sapling_outputs: 1,
+            ..Default::default()
+        };
+        assert_eq!(padded.size(), sapling.size());
+
+        // An Orchard transaction with one spend and one output has two actions.
+        let orchard = StepShape {
+            orchard_spends: 1,
+            orchard_outputs: 1,
+            ..Default::default()
+        };
+        assert_eq!(orchard.logical_actions(), 2);
+        let proof_size = 2720 + 2272 * 2;
+        assert_eq!(
+            orchard.size(),
+            20 + 2 + 2 + 1 + 2 * 884 + 1 + 8 + 32 + 3 + proof_size + 64
+        );
+    }
+
+    #[test]
+    fn compact_size_lengths() {
+        assert_eq!(compact_size_len(0), 1);
+        assert_eq!(compact_size_len(0xfc), 1);
+        assert_eq!(compact_size_len(0xfd), 3);
+        assert_eq!(compact_size_len(0x10000), 5);
+    }
+}
@@ -0,0 +1,265 @@
+//! Detection of hash time-locked contracts that are relevant to the wallet.
+//!
+//! Coins locked by a hash time-locked contract (HTLC), as used for cross-chain atomic swaps,
+//! are sent to the P2SH address of the contract's redeem script. The terms of the contract are
+//! not revealed on chain until the coins are spent, so the wallet cannot recognize such coins
+//! by scanning alone; instead, the parties to a swap agree upon the terms of the contract out
+//! of band, and the wallet registers them with an [`HtlcWatcher`]. The watcher then recognizes
+//! transactions that fund the contract, and transactions that claim the coins (revealing the
+//! preimage of the contract's payment hash, which the counterparty needs in order to complete
+//! the swap) or refund them.
+//!
+//! [`sync::watch_htlcs`] retrieves the transactions involving each watched contract from
+//! lightwalletd and passes them to the watcher.
+//!
+//! [`sync::watch_htlcs`]: crate::sync::watch_htlcs
+
+use std::collections::{HashMap, HashSet};
+
+use zcash_primitives::{
+    consensus::BlockHeight,
+    legacy::{
+        script::{Htlc, HtlcSpend},
+        TransparentAddress,
+    },
+    transaction::{
+        components::{amount::NonNegativeAmount, OutPoint},
+        Transaction, TxId,
+    },
+};
+
+/// The role of the wallet in a hash time-locked contract.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HtlcRole {
+    /// The wallet holds the key that can claim the coins with the preimage.
+    Recipient,
+    /// The wallet holds the key to which the coins can be refunded after the lock time.
+    Refund,
+}
+
+impl HtlcRole {
+    /// Returns the role of the wallet in the given contract, if either of the contract's
+    /// keys corresponds to one of the wallet's transparent `receivers`.
+    ///
+    /// If the wallet holds both keys, it is considered to be the recipient.
+    pub fn for_receivers<'a>(
+        htlc: &Htlc,
+        receivers: impl IntoIterator<Item = &'a TransparentAddress>,
+    ) -> Option<Self> {
+        let mut role = None;
+        for receiver in receivers {
+            match receiver {
+                TransparentAddress::PublicKeyHash(hash) if hash == htlc.recipient() => {
+                    return Some(HtlcRole::Recipient);
+                }
+                TransparentAddress::PublicKeyHash(hash) if hash == htlc.refund() => {
+                    role = Some(HtlcRole::Refund);
+                }
+                _ => (),
+            }
+        }
+        role
+    }
+}
+
+/// A change in the state of a watched contract, detected in a transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HtlcEventKind {
+    /// The output at `outpoint` locked `value` with the contract.
+    Funded {
+        outpoint: OutPoint,
+        value: NonNegativeAmount,
+    },
+    /// The coin at `spent` was claimed by the contract's recipient, revealing `preimage`.
+    Claimed { spent: OutPoint, preimage: [u8; 32] },
+    /// The coin at `spent` was refunded after the contract's lock time.
+    Refunded { spent: OutPoint },
+}
+
+/// An event relating to a watched contract, as detected by [`HtlcWatcher::scan_transaction`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HtlcEvent {
+    txid: TxId,
+    mined_height: Option<BlockHeight>,
+    htlc: Htlc,
+    kind: HtlcEventKind,
+}
+
+impl HtlcEvent {
+    /// Returns the ID of the transaction in which the event was detected.
+    pub fn txid(&self) -> TxId {
+        self.txid
+    }
+
+    /// Returns the height at which the transaction was mined, or `None` if it is unmined.
+    pub fn mined_height(&self) -> Option<BlockHeight> {
+        self.mined_height
+    }
+
+    /// Returns the terms of the contract to which the event relates.
+    pub fn htlc(&self) -> &Htlc {
+        &self.htlc
+    }
+
+    /// Returns the kind of change to the contract.
+    pub fn kind(&self) -> &HtlcEventKind {
+        &self.kind
+    }
+}
+
+/// A set of hash time-locked contracts for which the wallet is watching the chain.
+#[derive(Debug, Clone, Default)]
+pub struct HtlcWatcher {
+    contracts: HashMap<TransparentAddress, Htlc>,
+}
+
+impl HtlcWatcher {
+    /// Constructs a watcher that is not watching any contracts.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts watching the given contract, returning the P2SH address to which coins locked
+    /// by it are sent.
+    pub fn watch(&mut self, htlc: Htlc) -> TransparentAddress {
+        let address = htlc.address();
+        self.contracts.insert(address, htlc);
+        address
+    }
+
+    /// Starts watching the given contract if the wallet has a role in it, as determined by
+    /// [`HtlcRole::for_receivers`] from the wallet's transparent `receivers`.
+    pub fn watch_if_relevant<'a>(
+        &mut self,
+        htlc: Htlc,
+        receivers: impl IntoIterator<Item = &'a TransparentAddress>,
+    ) -> Option<HtlcRole> {
+        let role = HtlcRole::for_receivers(&htlc, receivers)?;
+        self.watch(htlc);
+        Some(role)
+    }
+
+    /// Stops watching the given contract, returning whether it was being watched.
+    pub fn unwatch(&mut self, htlc: &Htlc) -> bool {
+        self.contracts.remove(&htlc.address()).is_some()
+    }
+
+    /// Returns the P2SH addresses of the watched contracts.
+    pub fn addresses(&self) -> impl Iterator<Item = &TransparentAddress> {
+        self.contracts.keys()
+    }
+
+    /// Returns the watched contracts.
+    pub fn contracts(&self) -> impl Iterator<Item = &Htlc> {
+        self.contracts.values()
+    }
+
+    /// Returns the events relating to watched contracts in the given transaction.
+    ///
+    /// Outputs are recognized by their P2SH address, and spends by the redeem script in their
+    /// `scriptSig`; spends are therefore recognized even if the transaction that funded the
+    /// contract has not been seen. Claims are only reported if the revealed preimage matches
+    /// the contract's payment hash.
+    pub fn scan_transaction(
+        &self,
+        tx: &Transaction,
+        mined_height: Option<BlockHeight>,
+    ) -> Vec<HtlcEvent> {
+        let txid = tx.txid();
+        let bundle = match tx.transparent_bundle() {
+            Some(bundle) => bundle,
+            None => return vec![],
+        };
+
+        let event = |htlc: Htlc, kind| HtlcEvent {
+            txid,
+            mined_height,
+            htlc,
+            kind,
+        };
+
+        let spends = bundle.vin.iter().filter_map(|txin| {
+            let (htlc, spend) = txin.script_sig.htlc_spend()?;
+            if !self.contracts.contains_key(&htlc.address()) {
+                return None;
+            }
+            let kind = match spend {
+                HtlcSpend::Claim(preimage) => {
+                    if !htlc.is_preimage(&preimage) {
+                        return None;
+                    }
+                    HtlcEventKind::Claimed {
+                        spent: txin.prevout.clone(),
+                        preimage,
+                    }
+                }
+                HtlcSpend::Refund => HtlcEventKind::Refunded {
+                    spent: txin.prevout.clone(),
+                },
+            };
+            Some(event(htlc, kind))
+        });
+
+        let outputs = bundle.vout.iter().enumerate().filter_map(|(index, txout)| {
+            let htlc = self.contracts.get(&txout.script_pubkey.address()?)?;
+            Some(event(
+                *htlc,
+                HtlcEventKind::Funded {
+                    outpoint: OutPoint::new(*txid.as_ref(), index as u32),
+                    value: txout.value,
+                },
+            ))
+        });
+
+        spends.chain(outputs).collect()
+    }
+
+    /// Returns the events relating to watched contracts in the given transactions, skipping
+    /// any transaction that appears more than once.
+    pub fn scan_transactions<'a>(
+        &self,
+        txs: impl IntoIterator<Item = (&'a Transaction, Option<BlockHeight>)>,
+    ) -> Vec<HtlcEvent> {
+        let mut seen = HashSet::new();
+        txs.into_iter()
+            .filter(|(tx, _)| seen.insert(tx.txid()))
+            .flat_map(|(tx, mined_height)| self.scan_transaction(tx, mined_height))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zcash_primitives::legacy::{script::Htlc, TransparentAddress};
+
+    use super::{HtlcRole, HtlcWatcher};
+
+    #[test]
+    fn roles() {
+        let htlc = Htlc::new([0; 32], [1; 20], [2; 20], 1000);
+        let recipient = TransparentAddress::PublicKeyHash([1; 20]);
+        let refund = TransparentAddress::PublicKeyHash([2; 20]);
+        let other = TransparentAddress::PublicKeyHash([3; 20]);
+
+        assert_eq!(
+            HtlcRole::for_receivers(&htlc, [&refund, &recipient]),
+            Some(HtlcRole::Recipient)
+        );
+        assert_eq!(
+            HtlcRole::for_receivers(&htlc, [&other, &refund]),
+            Some(HtlcRole::Refund)
+        );
+        assert_eq!(HtlcRole::for_receivers(&htlc, [&other]), None);
+
+        let mut watcher = HtlcWatcher::new();
+        assert_eq!(watcher.watch_if_relevant(htlc, [&other]), None);
+        assert_eq!(watcher.addresses().count(), 0);
+        assert_eq!(
+            watcher.watch_if_relevant(htlc, [&refund]),
+            Some(HtlcRole::Refund)
+        );
+        assert_eq!(watcher.addresses().collect::<Vec<_>>(), [&htlc.address()]);
+        assert!(watcher.unwatch(&htlc));
+        assert!(!watcher.unwatch(&htlc));
+    }
+}
@@ -62,10 +62,18 @@
 #![allow(clippy::result_unit_err)]
 
 pub use zcash_keys::address;
+pub mod address_signature;
+pub mod capabilities;
 pub mod data_api;
 mod decrypt;
+#[cfg(feature = "demo")]
+pub mod demo;
+pub use zcash_keys::derivation;
+pub mod disclosure;
 pub use zcash_keys::encoding;
 pub mod fees;
+#[cfg(feature = "transparent-inputs")]
+pub mod htlc;
 pub use zcash_keys::keys;
 pub mod proposal;
 pub mod proto;
@@ -80,6 +88,9 @@ pub mod sync;
 #[cfg(feature = "unstable-serialization")]
 pub mod serialization;
 
+#[cfg(feature = "ur")]
+pub mod ur;
+
 pub use decrypt::{decrypt_transaction, DecryptedOutput, TransferType};
 pub use zcash_protocol::{PoolType, ShieldedProtocol};
 
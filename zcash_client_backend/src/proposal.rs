@@ -47,6 +47,9 @@ pub enum ProposalError {
     /// There was a mismatch between the payments in the proposal's transaction request
     /// and the payment pool selection values.
     PaymentPoolsMismatch,
+    /// The proposal spends a transparent output of a coinbase transaction in a transaction
+    /// that has transparent outputs. Coinbase outputs must be spent to the shielded pools.
+    CoinbaseNotShielded,
 }
 
 impl Display for ProposalError {
@@ -90,6 +93,10 @@ impl Display for ProposalError {
                 f,
                 "The chosen payment pools did not match the payments of the transaction request."
             ),
+            ProposalError::CoinbaseNotShielded => write!(
+                f,
+                "Transparent coinbase outputs may only be spent by transactions with no transparent outputs."
+            ),
         }
     }
 }
@@ -132,6 +139,17 @@ impl<NoteRef> ShieldedInputs<NoteRef> {
 /// Each step of the proposal represents a separate transaction to be created. At present, only
 /// transparent outputs of earlier steps may be spent in later steps; the ability to chain shielded
 /// transaction steps may be added in a future update.
+///
+/// When the `serde` feature flag is enabled, proposals that use a [`StandardFeeRule`] are
+/// serialized as a hex string containing the protobuf encoding of the equivalent
+/// [`proto::proposal::Proposal`]. Decoding a proposal requires looking up the notes that it
+/// spends in the wallet, so a serialized proposal is deserialized as a
+/// [`proto::proposal::Proposal`] and then converted using
+/// [`proto::proposal::Proposal::try_into_standard_proposal`].
+///
+/// [`StandardFeeRule`]: zcash_primitives::transaction::fees::StandardFeeRule
+/// [`proto::proposal::Proposal`]: crate::proto::proposal::Proposal
+/// [`proto::proposal::Proposal::try_into_standard_proposal`]: crate::proto::proposal::Proposal::try_into_standard_proposal
 #[derive(Clone, PartialEq, Eq)]
 pub struct Proposal<FeeRuleT, NoteRef> {
     fee_rule: FeeRuleT,
@@ -383,6 +401,16 @@ impl<NoteRef> Step<NoteRef> {
             }
         }
 
+        // Consensus requires that coinbase outputs be spent only by transactions that have no
+        // transparent outputs; change is always shielded, so only payments need be checked.
+        if transparent_inputs.iter().any(|i| i.is_coinbase())
+            && payment_pools
+                .values()
+                .any(|pool| *pool == PoolType::Transparent)
+        {
+            return Err(ProposalError::CoinbaseNotShielded);
+        }
+
         let transparent_input_total = transparent_inputs
             .iter()
             .map(|out| out.txout().value)
@@ -1,6 +1,6 @@
 //! Generated code for handling light client protobuf structs.
 
-use incrementalmerkletree::frontier::CommitmentTree;
+use incrementalmerkletree::frontier::{CommitmentTree, Frontier};
 use nonempty::NonEmpty;
 use std::{
     array::TryFromSliceError,
@@ -13,14 +13,16 @@ use sapling::{self, note::ExtractedNoteCommitment, Node};
 use zcash_note_encryption::{EphemeralKeyBytes, COMPACT_NOTE_SIZE};
 use zcash_primitives::{
     block::{BlockHash, BlockHeader},
-    consensus::BlockHeight,
+    consensus::{self, BlockHeight, BranchId},
     memo::{self, MemoBytes},
     merkle_tree::read_commitment_tree,
-    transaction::{components::amount::NonNegativeAmount, fees::StandardFeeRule, TxId},
+    transaction::{
+        components::amount::NonNegativeAmount, fees::StandardFeeRule, Transaction, TxId,
+    },
 };
 
 use crate::{
-    data_api::{chain::ChainState, InputSource},
+    data_api::{chain::ChainState, BlockNullifiers, InputSource},
     fees::{ChangeValue, TransactionBalance},
     proposal::{Proposal, ProposalError, ShieldedInputs, Step, StepOutput, StepOutputIndex},
     zip321::{TransactionRequest, Zip321Error},
@@ -28,7 +30,13 @@ use crate::{
 };
 
 #[cfg(feature = "transparent-inputs")]
-use zcash_primitives::transaction::components::OutPoint;
+use {
+    crate::wallet::WalletTransparentOutput,
+    zcash_primitives::{
+        legacy::Script,
+        transaction::components::{OutPoint, TxOut},
+    },
+};
 
 #[cfg(feature = "orchard")]
 use orchard::tree::MerkleHashOrchard;
@@ -48,6 +56,8 @@ pub mod proposal;
 #[allow(clippy::derive_partial_eq_without_eq)]
 pub mod service;
 
+pub mod stream;
+
 impl compact_formats::CompactBlock {
     /// Returns the [`BlockHash`] for this block.
     ///
@@ -105,9 +115,172 @@ impl compact_formats::CompactBlock {
             BlockHeader::read(&self.header[..]).ok()
         }
     }
+
+    /// Returns the number of Sapling outputs in this block.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the number of outputs is not representable within a u32.
+    pub fn sapling_output_count(&self) -> u32 {
+        self.vtx
+            .iter()
+            .map(|tx| tx.outputs.len())
+            .sum::<usize>()
+            .try_into()
+            .expect("Sapling output count cannot exceed a u32")
+    }
+
+    /// Returns the number of Orchard actions in this block.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the number of actions is not representable within a u32.
+    pub fn orchard_action_count(&self) -> u32 {
+        self.vtx
+            .iter()
+            .map(|tx| tx.actions.len())
+            .sum::<usize>()
+            .try_into()
+            .expect("Orchard action count cannot exceed a u32")
+    }
+
+    /// Returns the size of the Sapling note commitment tree as of the end of this block, if it
+    /// was provided by the server.
+    ///
+    /// Servers that predate the addition of [`CompactBlock.chainMetadata`] do not provide this
+    /// information.
+    ///
+    /// [`CompactBlock.chainMetadata`]: #structfield.chain_metadata
+    pub fn sapling_commitment_tree_size(&self) -> Option<u32> {
+        self.chain_metadata
+            .as_ref()
+            .map(|m| m.sapling_commitment_tree_size)
+    }
+
+    /// Returns the size of the Orchard note commitment tree as of the end of this block, if it
+    /// was provided by the server.
+    ///
+    /// Servers that predate the addition of [`CompactBlock.chainMetadata`] do not provide this
+    /// information. For blocks below NU5 activation, the size is zero.
+    ///
+    /// [`CompactBlock.chainMetadata`]: #structfield.chain_metadata
+    pub fn orchard_commitment_tree_size(&self) -> Option<u32> {
+        self.chain_metadata
+            .as_ref()
+            .map(|m| m.orchard_commitment_tree_size)
+    }
+
+    /// Extracts the nullifiers revealed by the transactions of this block.
+    ///
+    /// This requires only the spend and action nullifiers to be present, and so may be used with
+    /// the blocks returned by the `GetBlockRangeNullifiers` method of the light wallet service.
+    /// Transactions that reveal no nullifiers are omitted. Returns an error if any of the
+    /// nullifiers cannot be parsed.
+    pub fn block_nullifiers(&self) -> Result<BlockNullifiers, ()> {
+        let tx_index = |tx: &compact_formats::CompactTx| u16::try_from(tx.index).map_err(|_| ());
+
+        let sapling = self
+            .vtx
+            .iter()
+            .filter(|tx| !tx.spends.is_empty())
+            .map(|tx| {
+                Ok((
+                    tx.txid(),
+                    tx_index(tx)?,
+                    tx.spends
+                        .iter()
+                        .map(|spend| spend.nf())
+                        .collect::<Result<Vec<_>, _>>()?,
+                ))
+            })
+            .collect::<Result<Vec<_>, ()>>()?;
+
+        #[cfg(feature = "orchard")]
+        let orchard = self
+            .vtx
+            .iter()
+            .filter(|tx| !tx.actions.is_empty())
+            .map(|tx| {
+                Ok((
+                    tx.txid(),
+                    tx_index(tx)?,
+                    tx.actions
+                        .iter()
+                        .map(|action| action.nf())
+                        .collect::<Result<Vec<_>, _>>()?,
+                ))
+            })
+            .collect::<Result<Vec<_>, ()>>()?;
+
+        Ok(BlockNullifiers::from_parts(
+            self.height(),
+            sapling,
+            #[cfg(feature = "orchard")]
+            orchard,
+        ))
+    }
 }
 
 impl compact_formats::CompactTx {
+    /// Constructs the compact representation of the shielded and transparent components
+    /// of the given transaction, which appears at position `index` within its block.
+    ///
+    /// The fee is left unset, because computing it requires access to the transaction's
+    /// transparent inputs.
+    pub fn from_transaction(index: u64, tx: &Transaction) -> Self {
+        let (spends, outputs) = tx.sapling_bundle().map_or_else(
+            || (vec![], vec![]),
+            |bundle| {
+                (
+                    bundle.shielded_spends().iter().map(|s| s.into()).collect(),
+                    bundle.shielded_outputs().iter().map(|o| o.into()).collect(),
+                )
+            },
+        );
+
+        #[cfg(feature = "orchard")]
+        let actions = tx.orchard_bundle().map_or_else(Vec::new, |bundle| {
+            bundle.actions().iter().map(|a| a.into()).collect()
+        });
+        #[cfg(not(feature = "orchard"))]
+        let actions = vec![];
+
+        let (vin, vout) = tx.transparent_bundle().map_or_else(
+            || (vec![], vec![]),
+            |bundle| {
+                (
+                    bundle
+                        .vin
+                        .iter()
+                        .map(|txin| compact_formats::CompactTxIn {
+                            prevout_txid: txin.prevout.hash().to_vec(),
+                            prevout_index: txin.prevout.n(),
+                        })
+                        .collect(),
+                    bundle
+                        .vout
+                        .iter()
+                        .map(|txout| compact_formats::TxOut {
+                            value: txout.value.into(),
+                            script_pub_key: txout.script_pubkey.0.clone(),
+                        })
+                        .collect(),
+                )
+            },
+        );
+
+        compact_formats::CompactTx {
+            index,
+            hash: tx.txid().as_ref().to_vec(),
+            fee: 0,
+            spends,
+            outputs,
+            actions,
+            vin,
+            vout,
+        }
+    }
+
     /// Returns the transaction Id
     pub fn txid(&self) -> TxId {
         let mut hash = [0u8; 32];
@@ -116,6 +289,20 @@ impl compact_formats::CompactTx {
     }
 }
 
+#[cfg(feature = "transparent-inputs")]
+impl compact_formats::CompactTxIn {
+    /// Returns the outpoint spent by this input.
+    ///
+    /// A convenience method that parses [`CompactTxIn.prevout_txid`], returning an error if
+    /// it does not have the expected length.
+    ///
+    /// [`CompactTxIn.prevout_txid`]: #structfield.prevout_txid
+    pub fn prevout(&self) -> Result<OutPoint, ()> {
+        let hash: [u8; 32] = self.prevout_txid[..].try_into().map_err(|_| ())?;
+        Ok(OutPoint::new(hash, self.prevout_index))
+    }
+}
+
 impl compact_formats::CompactSaplingOutput {
     /// Returns the note commitment for this output.
     ///
@@ -139,6 +326,16 @@ impl compact_formats::CompactSaplingOutput {
             .map(EphemeralKeyBytes)
             .map_err(|_| ())
     }
+
+    /// Returns the compact note ciphertext for this output.
+    ///
+    /// This borrows [`CompactOutput.ciphertext`] without copying it, and returns an error if
+    /// it does not have the expected length.
+    ///
+    /// [`CompactOutput.ciphertext`]: #structfield.ciphertext
+    pub fn enc_ciphertext(&self) -> Result<&[u8; COMPACT_NOTE_SIZE], ()> {
+        self.ciphertext[..].try_into().map_err(|_| ())
+    }
 }
 
 impl<Proof> From<&sapling::bundle::OutputDescription<Proof>>
@@ -174,7 +371,7 @@ impl TryFrom<&compact_formats::CompactSaplingOutput>
         Ok(sapling::note_encryption::CompactOutputDescription {
             cmu: value.cmu()?,
             ephemeral_key: value.ephemeral_key()?,
-            enc_ciphertext: value.ciphertext[..].try_into().map_err(|_| ())?,
+            enc_ciphertext: *value.enc_ciphertext()?,
         })
     }
 }
@@ -194,7 +391,7 @@ impl TryFrom<&compact_formats::CompactOrchardAction> for orchard::note_encryptio
             value.nf()?,
             value.cmx()?,
             value.ephemeral_key()?,
-            value.ciphertext[..].try_into().map_err(|_| ())?,
+            *value.enc_ciphertext()?,
         ))
     }
 }
@@ -234,6 +431,16 @@ impl compact_formats::CompactOrchardAction {
             .map(EphemeralKeyBytes)
             .map_err(|_| ())
     }
+
+    /// Returns the compact note ciphertext for the output of this action.
+    ///
+    /// This borrows [`CompactOrchardAction.ciphertext`] without copying it, and returns an
+    /// error if it does not have the expected length.
+    ///
+    /// [`CompactOrchardAction.ciphertext`]: #structfield.ciphertext
+    pub fn enc_ciphertext(&self) -> Result<&[u8; COMPACT_NOTE_SIZE], ()> {
+        self.ciphertext[..].try_into().map_err(|_| ())
+    }
 }
 
 impl<A: sapling::bundle::Authorization> From<&sapling::bundle::SpendDescription<A>>
@@ -315,9 +522,7 @@ impl service::TreeState {
         hash_bytes.reverse();
 
         Ok(ChainState::new(
-            self.height
-                .try_into()
-                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid block height"))?,
+            self.block_height()?,
             BlockHash::try_from_slice(&hash_bytes).ok_or_else(|| {
                 io::Error::new(io::ErrorKind::InvalidData, "Invalid block hash length.")
             })?,
@@ -326,6 +531,103 @@ impl service::TreeState {
             self.orchard_tree()?.to_frontier(),
         ))
     }
+
+    /// Returns the height of the block to which this tree state corresponds.
+    pub fn block_height(&self) -> io::Result<BlockHeight> {
+        self.height
+            .try_into()
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid block height"))
+    }
+
+    /// Returns the frontier of the Sapling note commitment tree as of the end of the block to
+    /// which this tree state corresponds.
+    pub fn sapling_frontier(
+        &self,
+    ) -> io::Result<Frontier<Node, { sapling::NOTE_COMMITMENT_TREE_DEPTH }>> {
+        Ok(self.sapling_tree()?.to_frontier())
+    }
+
+    /// Returns the frontier of the Orchard note commitment tree as of the end of the block to
+    /// which this tree state corresponds.
+    #[cfg(feature = "orchard")]
+    pub fn orchard_frontier(
+        &self,
+    ) -> io::Result<Frontier<MerkleHashOrchard, { orchard::NOTE_COMMITMENT_TREE_DEPTH as u8 }>>
+    {
+        Ok(self.orchard_tree()?.to_frontier())
+    }
+}
+
+impl service::RawTransaction {
+    /// Constructs the representation of the given transaction that is returned by
+    /// lightwalletd, recording the height at which it was mined, if any.
+    pub fn from_transaction(
+        tx: &Transaction,
+        mined_height: Option<BlockHeight>,
+    ) -> io::Result<Self> {
+        let mut data = vec![];
+        tx.write(&mut data)?;
+        Ok(service::RawTransaction {
+            data,
+            height: mined_height.map_or(0, |h| u64::from(u32::from(h))),
+        })
+    }
+
+    /// Returns the height at which the transaction was mined, or `None` if it has not been
+    /// mined in the main chain.
+    ///
+    /// lightwalletd reports a height that is not a valid block height (zero, or -1 encoded as
+    /// `u64::MAX`) for transactions that have not been mined.
+    pub fn mined_height(&self) -> Option<BlockHeight> {
+        u32::try_from(self.height)
+            .ok()
+            .filter(|h| *h > 0)
+            .map(BlockHeight::from_u32)
+    }
+
+    /// Parses the transaction, using the consensus branch that is active at the height at
+    /// which it was mined, or at `unmined_height` if it has not been mined.
+    ///
+    /// `unmined_height` should be the height of the block following the chain tip.
+    pub fn to_transaction<P: consensus::Parameters>(
+        &self,
+        params: &P,
+        unmined_height: BlockHeight,
+    ) -> io::Result<Transaction> {
+        let height = self.mined_height().unwrap_or(unmined_height);
+        Transaction::read(&self.data[..], BranchId::for_height(params, height))
+    }
+}
+
+#[cfg(feature = "transparent-inputs")]
+impl service::GetAddressUtxosReply {
+    /// Returns the outpoint of the unspent transparent output.
+    pub fn outpoint(&self) -> io::Result<OutPoint> {
+        let txid: [u8; 32] = self.txid[..]
+            .try_into()
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid txid length"))?;
+        let index = u32::try_from(self.index)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid output index"))?;
+        Ok(OutPoint::new(txid, index))
+    }
+
+    /// Parses the unspent transparent output.
+    ///
+    /// Returns `Ok(None)` if the output's script does not pay to a P2PKH or P2SH address.
+    pub fn to_wallet_transparent_output(&self) -> io::Result<Option<WalletTransparentOutput>> {
+        let value = NonNegativeAmount::from_nonnegative_i64(self.value_zat)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid output value"))?;
+        let height = u32::try_from(self.height)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid block height"))?;
+        Ok(WalletTransparentOutput::from_parts(
+            self.outpoint()?,
+            TxOut {
+                value,
+                script_pubkey: Script(self.script.clone()),
+            },
+            BlockHeight::from_u32(height),
+        ))
+    }
 }
 
 /// Constant for the V1 proposal serialization version.
@@ -482,9 +784,34 @@ impl From<ShieldedProtocol> for proposal::ValuePool {
     }
 }
 
+impl From<ShieldedProtocol> for service::ShieldedProtocol {
+    fn from(value: ShieldedProtocol) -> Self {
+        match value {
+            ShieldedProtocol::Sapling => service::ShieldedProtocol::Sapling,
+            ShieldedProtocol::Orchard => service::ShieldedProtocol::Orchard,
+        }
+    }
+}
+
+impl From<service::ShieldedProtocol> for ShieldedProtocol {
+    fn from(value: service::ShieldedProtocol) -> Self {
+        match value {
+            service::ShieldedProtocol::Sapling => ShieldedProtocol::Sapling,
+            service::ShieldedProtocol::Orchard => ShieldedProtocol::Orchard,
+        }
+    }
+}
+
 impl proposal::Proposal {
     /// Serializes a [`Proposal`] based upon a supported [`StandardFeeRule`] to its protobuf
     /// representation.
+    ///
+    /// The protobuf representation records the fee rule, the ZIP 321 payment request and the
+    /// pools selected for each payment, the inputs to be spent (identified by the outpoint and
+    /// value of each note or UTXO), and the proposed change and fee for each step. It can be
+    /// transferred to another process or device, which may inspect these fields directly in
+    /// order to review the proposal, and which may then reconstruct the [`Proposal`] using
+    /// [`Self::try_into_standard_proposal`] in order to create the transactions.
     pub fn from_standard_proposal<NoteRef>(value: &Proposal<StandardFeeRule, NoteRef>) -> Self {
         use proposal::proposed_input;
         use proposal::{PriorStepChange, PriorStepOutput, ReceivedOutput};
@@ -604,6 +931,11 @@ impl proposal::Proposal {
 
     /// Attempts to parse a [`Proposal`] based upon a supported [`StandardFeeRule`] from its
     /// protobuf representation.
+    ///
+    /// Each input of the proposal is looked up in `wallet_db`, so this must be called with
+    /// access to a wallet that tracks the notes and UTXOs being spent; an input that the
+    /// wallet does not recognize as spendable results in
+    /// [`ProposalDecodingError::InputNotFound`].
     pub fn try_into_standard_proposal<DbT, DbError>(
         &self,
         wallet_db: &DbT,
@@ -798,6 +1130,34 @@ impl proposal::Proposal {
     }
 }
 
+#[cfg(feature = "serde")]
+impl serde::Serialize for proposal::Proposal {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(prost::Message::encode_to_vec(self)))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for proposal::Proposal {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error as _;
+
+        let encoded = String::deserialize(deserializer)?;
+        let bytes = hex::decode(encoded).map_err(D::Error::custom)?;
+        prost::Message::decode(&bytes[..]).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<NoteRef> serde::Serialize for Proposal<StandardFeeRule, NoteRef> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serde::Serialize::serialize(
+            &proposal::Proposal::from_standard_proposal(self),
+            serializer,
+        )
+    }
+}
+
 #[cfg(feature = "lightwalletd-tonic-transport")]
 impl service::compact_tx_streamer_client::CompactTxStreamerClient<tonic::transport::Channel> {
     /// Attempt to create a new client by connecting to a given endpoint.
@@ -810,3 +1170,31 @@ impl service::compact_tx_streamer_client::CompactTxStreamerClient<tonic::transpo
         Ok(Self::new(conn))
     }
 }
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::proposal;
+
+    #[test]
+    fn proposal_serde_round_trip() {
+        let proposal = proposal::Proposal {
+            proto_version: 1,
+            fee_rule: proposal::FeeRule::Zip317.into(),
+            min_target_height: 2_000_000,
+            steps: vec![],
+        };
+        let json = serde_json::to_string(&proposal).unwrap();
+        assert_eq!(
+            json,
+            format!(
+                "\"{}\"",
+                hex::encode(prost::Message::encode_to_vec(&proposal))
+            )
+        );
+        assert_eq!(
+            serde_json::from_str::<proposal::Proposal>(&json).unwrap(),
+            proposal
+        );
+        assert!(serde_json::from_str::<proposal::Proposal>("\"ff\"").is_err());
+    }
+}
@@ -74,6 +74,35 @@ pub struct CompactTx {
     pub outputs: ::prost::alloc::vec::Vec<CompactSaplingOutput>,
     #[prost(message, repeated, tag = "6")]
     pub actions: ::prost::alloc::vec::Vec<CompactOrchardAction>,
+    /// The transparent inputs and outputs of the transaction: present if the server
+    /// provides them. Older servers leave these empty.
+    #[prost(message, repeated, tag = "7")]
+    pub vin: ::prost::alloc::vec::Vec<CompactTxIn>,
+    #[prost(message, repeated, tag = "8")]
+    pub vout: ::prost::alloc::vec::Vec<TxOut>,
+}
+/// A compact representation of a transparent transaction input, identifying the
+/// output that it spends.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CompactTxIn {
+    /// \[32\] The ID of the transaction containing the spent output
+    #[prost(bytes = "vec", tag = "1")]
+    pub prevout_txid: ::prost::alloc::vec::Vec<u8>,
+    /// The index of the spent output within that transaction
+    #[prost(uint32, tag = "2")]
+    pub prevout_index: u32,
+}
+/// A transparent transaction output.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TxOut {
+    /// The value of the output, in zatoshis
+    #[prost(uint64, tag = "1")]
+    pub value: u64,
+    /// The script that locks the output
+    #[prost(bytes = "vec", tag = "2")]
+    pub script_pub_key: ::prost::alloc::vec::Vec<u8>,
 }
 /// A compact representation of a [Sapling Spend](<https://zips.z.cash/protocol/protocol.pdf#spendencodingandconsensus>).
 ///
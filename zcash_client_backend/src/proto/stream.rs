@@ -0,0 +1,342 @@
+//! Incremental decoding of [`CompactBlock`]s from byte streams.
+//!
+//! Compact blocks are frequently obtained as a long sequence of serialized messages, such as
+//! the body of a `GetBlockRange` gRPC response, or a file containing a cached range of blocks.
+//! [`CompactBlockReader`] decodes such a sequence one block at a time, so that the memory it
+//! requires is bounded by the size of the largest block rather than by the length of the
+//! stream.
+
+use std::fmt;
+use std::io::{self, Read, Write};
+
+use prost::Message;
+
+use super::compact_formats::CompactBlock;
+
+/// The default upper bound on the encoded size of a single compact block.
+///
+/// This matches the default maximum message size accepted by `tonic` clients.
+pub const DEFAULT_MAX_BLOCK_SIZE: usize = 4 * 1024 * 1024;
+
+/// The framing used to delimit consecutive messages within a stream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Framing {
+    /// Each message is preceded by its length, encoded as a protobuf varint. This is the
+    /// framing produced by [`Message::encode_length_delimited`].
+    LengthDelimited,
+    /// Each message is preceded by a gRPC frame header: a one-byte compression flag,
+    /// followed by the length of the message as a big-endian `u32`. This is the framing used
+    /// in the body of a gRPC streaming response. Compressed messages are not supported.
+    Grpc,
+}
+
+/// Errors that can occur while reading compact blocks from a stream.
+#[derive(Debug)]
+pub enum Error {
+    /// An error occurred reading from the underlying stream. An
+    /// [`io::ErrorKind::UnexpectedEof`] error indicates that the stream ended partway
+    /// through a message.
+    Io(io::Error),
+    /// The length prefix of a message could not be parsed.
+    InvalidFrame,
+    /// A gRPC frame indicated that its message was compressed.
+    Compressed,
+    /// The length prefix of a message exceeded the maximum permitted block size.
+    BlockTooLarge { size: u64, max: usize },
+    /// A message could not be decoded as a [`CompactBlock`].
+    Decode(prost::DecodeError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "Error reading compact block stream: {}", e),
+            Error::InvalidFrame => write!(f, "Invalid message length prefix"),
+            Error::Compressed => write!(f, "Compressed gRPC messages are not supported"),
+            Error::BlockTooLarge { size, max } => write!(
+                f,
+                "Compact block of {} bytes exceeds the maximum size of {} bytes",
+                size, max
+            ),
+            Error::Decode(e) => write!(f, "Error decoding compact block: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Decode(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<prost::DecodeError> for Error {
+    fn from(e: prost::DecodeError) -> Self {
+        Error::Decode(e)
+    }
+}
+
+/// Reads a sequence of framed [`CompactBlock`]s from an underlying reader.
+///
+/// Blocks are decoded one at a time as they are requested, reusing a single buffer whose size
+/// is bounded by the maximum block size. Iteration ends when the reader is exhausted at a
+/// message boundary, or after the first error is returned.
+pub struct CompactBlockReader<R> {
+    reader: R,
+    framing: Framing,
+    max_block_size: usize,
+    buf: Vec<u8>,
+    done: bool,
+}
+
+impl<R: Read> CompactBlockReader<R> {
+    /// Constructs a reader that decodes blocks framed using `framing` from `reader`.
+    ///
+    /// The reader performs many small reads, so it is advisable to wrap unbuffered readers
+    /// in an [`io::BufReader`].
+    pub fn new(reader: R, framing: Framing) -> Self {
+        CompactBlockReader {
+            reader,
+            framing,
+            max_block_size: DEFAULT_MAX_BLOCK_SIZE,
+            buf: vec![],
+            done: false,
+        }
+    }
+
+    /// Sets the maximum encoded size of a block that this reader will accept.
+    pub fn with_max_block_size(mut self, max_block_size: usize) -> Self {
+        self.max_block_size = max_block_size;
+        self
+    }
+
+    /// Returns the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Reads the next block from the stream, or returns `Ok(None)` if the stream has ended.
+    pub fn next_block(&mut self) -> Result<Option<CompactBlock>, Error> {
+        if self.done {
+            return Ok(None);
+        }
+
+        let res = self.read_block();
+        if !matches!(res, Ok(Some(_))) {
+            self.done = true;
+        }
+        res
+    }
+
+    fn read_block(&mut self) -> Result<Option<CompactBlock>, Error> {
+        let size = match self.read_length()? {
+            Some(size) => size,
+            None => return Ok(None),
+        };
+        let len = usize::try_from(size)
+            .ok()
+            .filter(|len| *len <= self.max_block_size)
+            .ok_or(Error::BlockTooLarge {
+                size,
+                max: self.max_block_size,
+            })?;
+
+        self.buf.clear();
+        self.buf.resize(len, 0);
+        self.reader.read_exact(&mut self.buf)?;
+        Ok(Some(CompactBlock::decode(&self.buf[..])?))
+    }
+
+    /// Reads the length prefix of the next message, returning `None` if the stream ends
+    /// before the first byte of the prefix.
+    fn read_length(&mut self) -> Result<Option<u64>, Error> {
+        let first = match self.read_byte()? {
+            Some(b) => b,
+            None => return Ok(None),
+        };
+
+        match self.framing {
+            Framing::LengthDelimited => {
+                let mut byte = first;
+                let mut value = u64::from(byte & 0x7f);
+                let mut shift = 7;
+                while byte & 0x80 != 0 {
+                    byte = self
+                        .read_byte()?
+                        .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+                    // A `u64` varint has at most 10 bytes, the last of which may only
+                    // contribute a single bit.
+                    if shift > 63 || (shift == 63 && byte > 1) {
+                        return Err(Error::InvalidFrame);
+                    }
+                    value |= u64::from(byte & 0x7f) << shift;
+                    shift += 7;
+                }
+                Ok(Some(value))
+            }
+            Framing::Grpc => {
+                match first {
+                    0 => (),
+                    1 => return Err(Error::Compressed),
+                    _ => return Err(Error::InvalidFrame),
+                }
+                let mut len = [0; 4];
+                self.reader.read_exact(&mut len)?;
+                Ok(Some(u32::from_be_bytes(len).into()))
+            }
+        }
+    }
+
+    fn read_byte(&mut self) -> Result<Option<u8>, Error> {
+        let mut byte = [0; 1];
+        loop {
+            match self.reader.read(&mut byte) {
+                Ok(0) => return Ok(None),
+                Ok(_) => return Ok(Some(byte[0])),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+impl<R: Read> Iterator for CompactBlockReader<R> {
+    type Item = Result<CompactBlock, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_block().transpose()
+    }
+}
+
+/// Writes `block` to `writer`, framed using `framing` so that it can be read back by a
+/// [`CompactBlockReader`].
+pub fn write_compact_block<W: Write>(
+    mut writer: W,
+    block: &CompactBlock,
+    framing: Framing,
+) -> io::Result<()> {
+    let mut data = Vec::with_capacity(block.encoded_len() + 5);
+    match framing {
+        Framing::LengthDelimited => {
+            prost::encoding::encode_varint(block.encoded_len() as u64, &mut data);
+        }
+        Framing::Grpc => {
+            let len = u32::try_from(block.encoded_len())
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "block is too large"))?;
+            data.push(0);
+            data.extend_from_slice(&len.to_be_bytes());
+        }
+    }
+    block
+        .encode(&mut data)
+        .expect("Vec<u8> has sufficient capacity");
+    writer.write_all(&data)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::{write_compact_block, CompactBlockReader, Error, Framing};
+    use crate::proto::compact_formats::{CompactBlock, CompactTx};
+
+    fn blocks() -> Vec<CompactBlock> {
+        (1..=3)
+            .map(|height| CompactBlock {
+                height,
+                hash: vec![height as u8; 32],
+                vtx: (0..height)
+                    .map(|index| CompactTx {
+                        index,
+                        ..Default::default()
+                    })
+                    .collect(),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    fn encode(blocks: &[CompactBlock], framing: Framing) -> Vec<u8> {
+        let mut data = vec![];
+        for block in blocks {
+            write_compact_block(&mut data, block, framing).unwrap();
+        }
+        data
+    }
+
+    #[test]
+    fn round_trip() {
+        let blocks = blocks();
+        for framing in [Framing::LengthDelimited, Framing::Grpc] {
+            let data = encode(&blocks, framing);
+            let decoded = CompactBlockReader::new(&data[..], framing)
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(decoded, blocks);
+        }
+    }
+
+    #[test]
+    fn length_delimited_matches_prost() {
+        let blocks = blocks();
+        let mut data = vec![];
+        for block in &blocks {
+            prost::Message::encode_length_delimited(block, &mut data).unwrap();
+        }
+        assert_eq!(data, encode(&blocks, Framing::LengthDelimited));
+    }
+
+    #[test]
+    fn truncated_stream() {
+        let blocks = blocks();
+        for framing in [Framing::LengthDelimited, Framing::Grpc] {
+            let data = encode(&blocks, framing);
+            let mut reader = CompactBlockReader::new(&data[..data.len() - 1], framing);
+            assert_eq!(reader.next().unwrap().unwrap(), blocks[0]);
+            assert_eq!(reader.next().unwrap().unwrap(), blocks[1]);
+            assert!(matches!(
+                reader.next(),
+                Some(Err(Error::Io(e))) if e.kind() == io::ErrorKind::UnexpectedEof
+            ));
+            assert!(reader.next().is_none());
+        }
+    }
+
+    #[test]
+    fn oversized_block() {
+        let blocks = blocks();
+        let data = encode(&blocks, Framing::Grpc);
+        let mut reader = CompactBlockReader::new(&data[..], Framing::Grpc).with_max_block_size(40);
+        assert_eq!(reader.next().unwrap().unwrap(), blocks[0]);
+        assert!(matches!(
+            reader.next(),
+            Some(Err(Error::BlockTooLarge { max: 40, .. }))
+        ));
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn invalid_frames() {
+        let mut data = encode(&blocks(), Framing::Grpc);
+        data[0] = 1;
+        assert!(matches!(
+            CompactBlockReader::new(&data[..], Framing::Grpc).next(),
+            Some(Err(Error::Compressed))
+        ));
+
+        let data = [0xff; 11];
+        assert!(matches!(
+            CompactBlockReader::new(&data[..], Framing::LengthDelimited).next(),
+            Some(Err(Error::InvalidFrame))
+        ));
+    }
+}
@@ -16,6 +16,7 @@ use tracing::{debug, trace};
 use zcash_keys::keys::UnifiedFullViewingKey;
 use zcash_note_encryption::{batch, BatchDomain, Domain, ShieldedOutput, COMPACT_NOTE_SIZE};
 use zcash_primitives::{
+    block::BlockHash,
     consensus::{self, BlockHeight, NetworkUpgrade},
     transaction::{components::sapling::zip212_enforcement, TxId},
 };
@@ -38,6 +39,9 @@ use orchard::{
 #[cfg(not(feature = "orchard"))]
 use std::marker::PhantomData;
 
+#[cfg(feature = "transparent-inputs")]
+use zcash_primitives::transaction::components::OutPoint;
+
 /// A key that can be used to perform trial decryption and nullifier
 /// computation for a [`CompactSaplingOutput`] or [`CompactOrchardAction`].
 ///
@@ -296,6 +300,38 @@ impl<AccountId: Copy + Eq + Hash + 'static> ScanningKeys<AccountId, (AccountId,
             orchard,
         }
     }
+
+    /// Constructs a [`ScanningKeys`] for the external and internal scopes of a single Sapling
+    /// full viewing key that does not belong to a wallet account, such as one derived from an
+    /// imported spending key.
+    pub(crate) fn from_sapling_dfvk(
+        account_id: AccountId,
+        dfvk: &sapling::zip32::DiversifiableFullViewingKey,
+    ) -> Self {
+        #![allow(clippy::type_complexity)]
+
+        let mut sapling: HashMap<
+            (AccountId, Scope),
+            Box<dyn ScanningKeyOps<SaplingDomain, AccountId, sapling::Nullifier>>,
+        > = HashMap::new();
+        for scope in [Scope::External, Scope::Internal] {
+            sapling.insert(
+                (account_id, scope),
+                Box::new(ScanningKey {
+                    ivk: dfvk.to_ivk(scope),
+                    nk: Some(dfvk.to_nk(scope)),
+                    account_id,
+                    key_scope: Some(scope),
+                }),
+            );
+        }
+
+        Self {
+            sapling,
+            #[cfg(feature = "orchard")]
+            orchard: HashMap::new(),
+        }
+    }
 }
 
 /// The set of nullifiers being tracked by a wallet.
@@ -303,6 +339,8 @@ pub struct Nullifiers<AccountId> {
     sapling: Vec<(AccountId, sapling::Nullifier)>,
     #[cfg(feature = "orchard")]
     orchard: Vec<(AccountId, orchard::note::Nullifier)>,
+    #[cfg(feature = "transparent-inputs")]
+    transparent: Vec<(AccountId, OutPoint)>,
 }
 
 impl<AccountId> Nullifiers<AccountId> {
@@ -312,6 +350,8 @@ impl<AccountId> Nullifiers<AccountId> {
             sapling: vec![],
             #[cfg(feature = "orchard")]
             orchard: vec![],
+            #[cfg(feature = "transparent-inputs")]
+            transparent: vec![],
         }
     }
 
@@ -324,6 +364,8 @@ impl<AccountId> Nullifiers<AccountId> {
             sapling,
             #[cfg(feature = "orchard")]
             orchard,
+            #[cfg(feature = "transparent-inputs")]
+            transparent: vec![],
         }
     }
 
@@ -338,6 +380,12 @@ impl<AccountId> Nullifiers<AccountId> {
         self.orchard.as_ref()
     }
 
+    /// Returns the outpoints of unspent transparent outputs that the wallet is tracking.
+    #[cfg(feature = "transparent-inputs")]
+    pub fn transparent(&self) -> &[(AccountId, OutPoint)] {
+        self.transparent.as_ref()
+    }
+
     /// Discards Sapling nullifiers from the tracked nullifier set, retaining only those that
     /// satisfy the given predicate.
     pub(crate) fn retain_sapling(&mut self, f: impl Fn(&(AccountId, sapling::Nullifier)) -> bool) {
@@ -367,6 +415,19 @@ impl<AccountId> Nullifiers<AccountId> {
     ) {
         self.orchard.extend(nfs);
     }
+
+    #[cfg(feature = "transparent-inputs")]
+    pub(crate) fn retain_transparent(&mut self, f: impl Fn(&(AccountId, OutPoint)) -> bool) {
+        self.transparent.retain(f);
+    }
+
+    #[cfg(feature = "transparent-inputs")]
+    pub(crate) fn extend_transparent(
+        &mut self,
+        outpoints: impl IntoIterator<Item = (AccountId, OutPoint)>,
+    ) {
+        self.transparent.extend(outpoints);
+    }
 }
 
 /// Errors that may occur in chain scanning
@@ -382,7 +443,14 @@ pub enum ScanError {
 
     /// The hash of the parent block given by a proposed new chain tip does not match the hash of
     /// the current chain tip.
-    PrevHashMismatch { at_height: BlockHeight },
+    PrevHashMismatch {
+        /// The height of the proposed new block.
+        at_height: BlockHeight,
+        /// The hash of the wallet's block at `at_height - 1`.
+        expected: BlockHash,
+        /// The parent hash given by the proposed new block.
+        found: BlockHash,
+    },
 
     /// The block height field of the proposed new block is not equal to the height of the previous
     /// block + 1.
@@ -436,15 +504,47 @@ impl ScanError {
         use ScanError::*;
         match self {
             EncodingInvalid { at_height, .. } => *at_height,
-            PrevHashMismatch { at_height } => *at_height,
+            PrevHashMismatch { at_height, .. } => *at_height,
             BlockHeightDiscontinuity { new_height, .. } => *new_height,
             TreeSizeMismatch { at_height, .. } => *at_height,
             TreeSizeUnknown { at_height, .. } => *at_height,
             TreeSizeInvalid { at_height, .. } => *at_height,
         }
     }
+
+    /// Returns the height to which the wallet (and its block cache) should be truncated in order
+    /// to recover from this error, or `None` if this is not a continuity error.
+    ///
+    /// The returned height is at least [`CONTINUITY_REWIND_DISTANCE`] blocks below the earliest
+    /// block known to be inconsistent with the wallet's view of the chain, so that a reorg of
+    /// moderate depth is recovered from with a single rewind. After truncation, the blocks above
+    /// the returned height must be downloaded and scanned again.
+    pub fn suggested_rewind_height(&self) -> Option<BlockHeight> {
+        use ScanError::*;
+        let inconsistent_height = match self {
+            // The wallet's block at `at_height - 1` is not the parent of the new block.
+            PrevHashMismatch { at_height, .. } => at_height.saturating_sub(1),
+            // Either the wallet's block at `prev_height` or the new block is not part of the
+            // main chain.
+            BlockHeightDiscontinuity {
+                prev_height,
+                new_height,
+            } => std::cmp::min(*prev_height, *new_height),
+            // The tree state at the wallet's previous block is inconsistent with the new block.
+            TreeSizeMismatch { at_height, .. } => at_height.saturating_sub(1),
+            EncodingInvalid { .. } | TreeSizeUnknown { .. } | TreeSizeInvalid { .. } => {
+                return None;
+            }
+        };
+
+        Some(inconsistent_height.saturating_sub(CONTINUITY_REWIND_DISTANCE))
+    }
 }
 
+/// The number of blocks below the first inconsistent block to which
+/// [`ScanError::suggested_rewind_height`] suggests rewinding after a continuity error.
+pub const CONTINUITY_REWIND_DISTANCE: u32 = 10;
+
 impl fmt::Display for ScanError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         use ScanError::*;
@@ -454,10 +554,10 @@ impl fmt::Display for ScanError {
                 "{:?} output {} of transaction {} was improperly encoded.",
                 pool_type, index, txid
             ),
-            PrevHashMismatch { at_height } => write!(
+            PrevHashMismatch { at_height, expected, found } => write!(
                 f,
-                "The parent hash of proposed block does not correspond to the block hash at height {}.",
-                at_height
+                "The parent hash of proposed block at height {} does not correspond to the block hash at height {}; expected {}, found {}.",
+                at_height, at_height.saturating_sub(1), expected, found
             ),
             BlockHeightDiscontinuity { prev_height, new_height } => {
                 write!(f, "Block height discontinuity at height {}; previous height was: {}", new_height, prev_height)
@@ -682,6 +782,8 @@ where
                 debug!("Block hash discontinuity at {:?}", block.height());
                 return Some(ScanError::PrevHashMismatch {
                     at_height: block.height(),
+                    expected: prev.block_hash(),
+                    found: block.prev_hash(),
                 });
             }
         }
@@ -699,11 +801,15 @@ where
     let cur_hash = block.hash();
     let zip212_enforcement = zip212_enforcement(params, cur_height);
 
+    let given_sapling_tree_size = block.sapling_commitment_tree_size();
+    #[cfg(feature = "orchard")]
+    let given_orchard_tree_size = block.orchard_commitment_tree_size();
+
     let mut sapling_commitment_tree_size = prior_block_metadata
         .and_then(|m| m.sapling_tree_size())
         .map_or_else(
             || {
-                block.chain_metadata.as_ref().map_or_else(
+                given_sapling_tree_size.map_or_else(
                     || {
                         // If we're below Sapling activation, or Sapling activation is not set, the tree size is zero
                         params
@@ -722,42 +828,29 @@ where
                                 },
                             )
                     },
-                    |m| {
-                        let sapling_output_count: u32 = block
-                            .vtx
-                            .iter()
-                            .map(|tx| tx.outputs.len())
-                            .sum::<usize>()
-                            .try_into()
-                            .expect("Sapling output count cannot exceed a u32");
-
-                        // The default for m.sapling_commitment_tree_size is zero, so we need to check
-                        // that the subtraction will not underflow; if it would do so, we were given
-                        // invalid chain metadata for a block with Sapling outputs.
-                        m.sapling_commitment_tree_size
-                            .checked_sub(sapling_output_count)
-                            .ok_or(ScanError::TreeSizeInvalid {
+                    |size| {
+                        // The default for the Sapling commitment tree size is zero, so we need to
+                        // check that the subtraction will not underflow; if it would do so, we were
+                        // given invalid chain metadata for a block with Sapling outputs.
+                        size.checked_sub(block.sapling_output_count()).ok_or(
+                            ScanError::TreeSizeInvalid {
                                 protocol: ShieldedProtocol::Sapling,
                                 at_height: cur_height,
-                            })
+                            },
+                        )
                     },
                 )
             },
             Ok,
         )?;
-    let sapling_final_tree_size = sapling_commitment_tree_size
-        + block
-            .vtx
-            .iter()
-            .map(|tx| u32::try_from(tx.outputs.len()).unwrap())
-            .sum::<u32>();
+    let sapling_final_tree_size = sapling_commitment_tree_size + block.sapling_output_count();
 
     #[cfg(feature = "orchard")]
     let mut orchard_commitment_tree_size = prior_block_metadata
         .and_then(|m| m.orchard_tree_size())
         .map_or_else(
             || {
-                block.chain_metadata.as_ref().map_or_else(
+                given_orchard_tree_size.map_or_else(
                     || {
                         // If we're below Orchard activation, or Orchard activation is not set, the tree size is zero
                         params.activation_height(NetworkUpgrade::Nu5).map_or_else(
@@ -774,36 +867,23 @@ where
                             },
                         )
                     },
-                    |m| {
-                        let orchard_action_count: u32 = block
-                            .vtx
-                            .iter()
-                            .map(|tx| tx.actions.len())
-                            .sum::<usize>()
-                            .try_into()
-                            .expect("Orchard action count cannot exceed a u32");
-
-                        // The default for m.orchard_commitment_tree_size is zero, so we need to check
-                        // that the subtraction will not underflow; if it would do so, we were given
-                        // invalid chain metadata for a block with Orchard actions.
-                        m.orchard_commitment_tree_size
-                            .checked_sub(orchard_action_count)
-                            .ok_or(ScanError::TreeSizeInvalid {
+                    |size| {
+                        // The default for the Orchard commitment tree size is zero, so we need to
+                        // check that the subtraction will not underflow; if it would do so, we were
+                        // given invalid chain metadata for a block with Orchard actions.
+                        size.checked_sub(block.orchard_action_count()).ok_or(
+                            ScanError::TreeSizeInvalid {
                                 protocol: ShieldedProtocol::Orchard,
                                 at_height: cur_height,
-                            })
+                            },
+                        )
                     },
                 )
             },
             Ok,
         )?;
     #[cfg(feature = "orchard")]
-    let orchard_final_tree_size = orchard_commitment_tree_size
-        + block
-            .vtx
-            .iter()
-            .map(|tx| u32::try_from(tx.actions.len()).unwrap())
-            .sum::<u32>();
+    let orchard_final_tree_size = orchard_commitment_tree_size + block.orchard_action_count();
 
     let mut wtxs: Vec<WalletTx<AccountId>> = vec![];
     let mut sapling_nullifier_map = Vec::with_capacity(block.vtx.len());
@@ -848,11 +928,31 @@ where
             orchard_spends
         };
 
+        // Transparent inputs are matched against the outpoints of the wallet's unspent
+        // transparent outputs. A prevout that cannot be parsed cannot belong to the wallet.
+        #[cfg(feature = "transparent-inputs")]
+        let transparent_spends = tx
+            .vin
+            .iter()
+            .enumerate()
+            .filter_map(|(index, txin)| {
+                let prevout = txin.prevout().ok()?;
+                nullifiers
+                    .transparent
+                    .iter()
+                    .find(|(_, outpoint)| outpoint == &prevout)
+                    .map(|(account, _)| WalletSpend::from_parts(index, prevout, *account))
+            })
+            .collect::<Vec<_>>();
+
         // Collect the set of accounts that were spent from in this transaction
         let spent_from_accounts = sapling_spends.iter().map(|spend| spend.account_id());
         #[cfg(feature = "orchard")]
         let spent_from_accounts =
             spent_from_accounts.chain(orchard_spends.iter().map(|spend| spend.account_id()));
+        #[cfg(feature = "transparent-inputs")]
+        let spent_from_accounts =
+            spent_from_accounts.chain(transparent_spends.iter().map(|spend| spend.account_id()));
         let spent_from_accounts = spent_from_accounts.copied().collect::<HashSet<_>>();
 
         let (sapling_outputs, mut sapling_nc) = find_received(
@@ -925,7 +1025,12 @@ where
         #[cfg(not(feature = "orchard"))]
         let has_orchard = false;
 
-        if has_sapling || has_orchard {
+        #[cfg(feature = "transparent-inputs")]
+        let has_transparent = !transparent_spends.is_empty();
+        #[cfg(not(feature = "transparent-inputs"))]
+        let has_transparent = false;
+
+        if has_sapling || has_orchard || has_transparent {
             wtxs.push(WalletTx::new(
                 txid,
                 tx_index as usize,
@@ -935,6 +1040,8 @@ where
                 orchard_spends,
                 #[cfg(feature = "orchard")]
                 orchard_outputs,
+                #[cfg(feature = "transparent-inputs")]
+                transparent_spends,
             ));
         }
 
@@ -947,22 +1054,24 @@ where
         }
     }
 
-    if let Some(chain_meta) = block.chain_metadata {
-        if chain_meta.sapling_commitment_tree_size != sapling_commitment_tree_size {
+    if let Some(given) = given_sapling_tree_size {
+        if given != sapling_commitment_tree_size {
             return Err(ScanError::TreeSizeMismatch {
                 protocol: ShieldedProtocol::Sapling,
                 at_height: cur_height,
-                given: chain_meta.sapling_commitment_tree_size,
+                given,
                 computed: sapling_commitment_tree_size,
             });
         }
+    }
 
-        #[cfg(feature = "orchard")]
-        if chain_meta.orchard_commitment_tree_size != orchard_commitment_tree_size {
+    #[cfg(feature = "orchard")]
+    if let Some(given) = given_orchard_tree_size {
+        if given != orchard_commitment_tree_size {
             return Err(ScanError::TreeSizeMismatch {
                 protocol: ShieldedProtocol::Orchard,
                 at_height: cur_height,
-                given: chain_meta.orchard_commitment_tree_size,
+                given,
                 computed: orchard_commitment_tree_size,
             });
         }
@@ -1310,9 +1419,12 @@ mod tests {
     use crate::{
         data_api::BlockMetadata,
         scanning::{BatchRunners, ScanningKeys},
+        ShieldedProtocol,
     };
 
-    use super::{scan_block, scan_block_with_runners, testing::fake_compact_block, Nullifiers};
+    use super::{
+        scan_block, scan_block_with_runners, testing::fake_compact_block, Nullifiers, ScanError,
+    };
 
     #[test]
     fn scan_block_with_my_tx() {
@@ -1530,4 +1642,241 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    #[cfg(feature = "transparent-inputs")]
+    fn scan_block_with_my_transparent_spend() {
+        use zcash_primitives::transaction::components::OutPoint;
+
+        use crate::proto::compact_formats::CompactTxIn;
+
+        let network = Network::TestNetwork;
+        let account = AccountId::try_from(12).unwrap();
+        let usk = UnifiedSpendingKey::from_seed(&network, &[0u8; 32], account).expect("Valid USK");
+        let ufvk = usk.to_unified_full_viewing_key();
+        let scanning_keys = ScanningKeys::<AccountId, Infallible>::empty();
+
+        let outpoint = OutPoint::new([3; 32], 1);
+        let mut nullifiers = Nullifiers::empty();
+        nullifiers.extend_transparent([(account, outpoint.clone())]);
+
+        let mut cb = fake_compact_block(
+            1u32.into(),
+            BlockHash([0; 32]),
+            Nullifier([7; 32]),
+            ufvk.sapling().unwrap(),
+            NonNegativeAmount::const_from_u64(5),
+            false,
+            Some((0, 0)),
+        );
+        assert_eq!(cb.vtx.len(), 2);
+
+        // The first transaction spends an unrelated output with the same txid, and the
+        // second spends the wallet's output after an unrelated input.
+        cb.vtx[0].vin.push(CompactTxIn {
+            prevout_txid: vec![3; 32],
+            prevout_index: 0,
+        });
+        cb.vtx[1].vin.push(CompactTxIn {
+            prevout_txid: vec![4; 32],
+            prevout_index: 1,
+        });
+        cb.vtx[1].vin.push(CompactTxIn {
+            prevout_txid: vec![3; 32],
+            prevout_index: 1,
+        });
+
+        let scanned_block = scan_block(&network, cb, &scanning_keys, &nullifiers, None).unwrap();
+        let txs = scanned_block.transactions();
+        assert_eq!(txs.len(), 1);
+
+        let tx = &txs[0];
+        assert_eq!(tx.block_index(), 1);
+        assert_eq!(tx.sapling_spends().len(), 0);
+        assert_eq!(tx.transparent_spends().len(), 1);
+        assert_eq!(tx.transparent_spends()[0].index(), 1);
+        assert_eq!(tx.transparent_spends()[0].nf(), &outpoint);
+        assert_eq!(tx.transparent_spends()[0].account_id(), &account);
+    }
+
+    #[test]
+    fn scan_block_reports_continuity_errors() {
+        let network = Network::TestNetwork;
+        let account = AccountId::ZERO;
+        let usk = UnifiedSpendingKey::from_seed(&network, &[0u8; 32], account).expect("Valid USK");
+        let ufvk = usk.to_unified_full_viewing_key();
+        let sapling_dfvk = ufvk.sapling().expect("Sapling key is present").clone();
+        let scanning_keys = ScanningKeys::from_account_ufvks([(account, ufvk)]);
+
+        let cb = fake_compact_block(
+            20u32.into(),
+            BlockHash([1; 32]),
+            Nullifier([0; 32]),
+            &sapling_dfvk,
+            NonNegativeAmount::const_from_u64(5),
+            false,
+            Some((0, 0)),
+        );
+
+        let prior_block_metadata = |height: u32| {
+            BlockMetadata::from_parts(
+                BlockHeight::from(height),
+                BlockHash([0; 32]),
+                Some(0),
+                #[cfg(feature = "orchard")]
+                Some(0),
+            )
+        };
+
+        // The parent hash of the block does not match the wallet's block at height 19.
+        let err = scan_block(
+            &network,
+            cb.clone(),
+            &scanning_keys,
+            &Nullifiers::empty(),
+            Some(&prior_block_metadata(19)),
+        )
+        .unwrap_err();
+        assert_matches!(
+            err,
+            ScanError::PrevHashMismatch { at_height, expected, found }
+                if at_height == BlockHeight::from(20)
+                    && expected == BlockHash([0; 32])
+                    && found == BlockHash([1; 32])
+        );
+        assert!(err.is_continuity_error());
+        assert_eq!(err.suggested_rewind_height(), Some(BlockHeight::from(9)));
+
+        // The block does not immediately follow the wallet's block at height 14.
+        let err = scan_block(
+            &network,
+            cb,
+            &scanning_keys,
+            &Nullifiers::empty(),
+            Some(&prior_block_metadata(14)),
+        )
+        .unwrap_err();
+        assert_matches!(err, ScanError::BlockHeightDiscontinuity { .. });
+        assert_eq!(err.suggested_rewind_height(), Some(BlockHeight::from(4)));
+    }
+
+    #[test]
+    fn scan_block_reports_tree_size_mismatch() {
+        let network = Network::TestNetwork;
+        let account = AccountId::ZERO;
+        let usk = UnifiedSpendingKey::from_seed(&network, &[0u8; 32], account).expect("Valid USK");
+        let ufvk = usk.to_unified_full_viewing_key();
+        let scanning_keys = ScanningKeys::<AccountId, Infallible>::empty();
+
+        let mut cb = fake_compact_block(
+            1u32.into(),
+            BlockHash([0; 32]),
+            Nullifier([0; 32]),
+            ufvk.sapling().unwrap(),
+            NonNegativeAmount::const_from_u64(5),
+            false,
+            Some((0, 0)),
+        );
+        assert_eq!(cb.sapling_commitment_tree_size(), Some(2));
+
+        // The block's chain metadata is inconsistent with the wallet's view of the tree.
+        cb.chain_metadata
+            .as_mut()
+            .unwrap()
+            .sapling_commitment_tree_size = 3;
+        let err = scan_block(
+            &network,
+            cb,
+            &scanning_keys,
+            &Nullifiers::empty(),
+            Some(&BlockMetadata::from_parts(
+                BlockHeight::from(0),
+                BlockHash([0; 32]),
+                Some(0),
+                #[cfg(feature = "orchard")]
+                Some(0),
+            )),
+        )
+        .unwrap_err();
+        assert_matches!(
+            err,
+            ScanError::TreeSizeMismatch { protocol: ShieldedProtocol::Sapling, at_height, given: 3, computed: 2 }
+                if at_height == BlockHeight::from(1)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "orchard")]
+    fn scan_block_checks_orchard_tree_size() {
+        use crate::proto::compact_formats::CompactOrchardAction;
+
+        let network = Network::TestNetwork;
+        let account = AccountId::ZERO;
+        let usk = UnifiedSpendingKey::from_seed(&network, &[0u8; 32], account).expect("Valid USK");
+        let ufvk = usk.to_unified_full_viewing_key();
+        let scanning_keys = ScanningKeys::<AccountId, Infallible>::empty();
+        let prior_block_metadata =
+            BlockMetadata::from_parts(BlockHeight::from(0), BlockHash([0; 32]), Some(0), Some(5));
+
+        let cb = fake_compact_block(
+            1u32.into(),
+            BlockHash([0; 32]),
+            Nullifier([0; 32]),
+            ufvk.sapling().unwrap(),
+            NonNegativeAmount::const_from_u64(5),
+            false,
+            Some((0, 0)),
+        );
+
+        // Below NU5 activation, chain metadata reports an empty Orchard tree, which is
+        // consistent with a block that contains no Orchard actions.
+        assert_eq!(cb.orchard_commitment_tree_size(), Some(0));
+        let scanned_block = scan_block(
+            &network,
+            cb.clone(),
+            &scanning_keys,
+            &Nullifiers::empty(),
+            None,
+        )
+        .unwrap();
+        assert_eq!(scanned_block.orchard().final_tree_size(), 0);
+
+        // A zero Orchard tree size is not accepted for a block that contains Orchard actions,
+        // whether or not the size of the tree before the block is known.
+        let mut cb = cb;
+        cb.vtx[1].actions.push(CompactOrchardAction {
+            nullifier: vec![0; 32],
+            cmx: vec![0; 32],
+            ephemeral_key: vec![0; 32],
+            ciphertext: vec![0; 52],
+        });
+        assert_eq!(cb.orchard_commitment_tree_size(), Some(0));
+
+        let err = scan_block(
+            &network,
+            cb.clone(),
+            &scanning_keys,
+            &Nullifiers::empty(),
+            Some(&prior_block_metadata),
+        )
+        .unwrap_err();
+        assert_matches!(
+            err,
+            ScanError::TreeSizeMismatch {
+                protocol: ShieldedProtocol::Orchard,
+                given: 0,
+                computed: 6,
+                ..
+            }
+        );
+
+        let err = scan_block(&network, cb, &scanning_keys, &Nullifiers::empty(), None).unwrap_err();
+        assert_matches!(
+            err,
+            ScanError::TreeSizeInvalid {
+                protocol: ShieldedProtocol::Orchard,
+                ..
+            }
+        );
+    }
 }
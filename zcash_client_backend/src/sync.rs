@@ -2,18 +2,27 @@
 //!
 //! This is currently a simple implementation that does not yet implement a few features:
 //!
-//! - Block batches are not downloaded in parallel with scanning.
 //! - Transactions are not enhanced once detected (that is, after an output is detected in
-//!   a transaction, the full transaction is not downloaded and scanned).
+//!   a transaction, the full transaction is not downloaded and scanned). The only data
+//!   that is downloaded outside of the compact blocks is that which the wallet explicitly
+//!   requests via [`WalletRead::transaction_data_requests`], and (with the
+//!   `transparent-inputs` feature) the transactions involving the wallet's transparent
+//!   addresses.
 //! - There is no mechanism for notifying the caller of progress updates.
 //! - There is no mechanism for interrupting the synchronization flow, other than ending
 //!   the process.
 
+use std::convert::Infallible;
 use std::fmt;
+use std::future::Future;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use futures_util::TryStreamExt;
+use futures_util::{pin_mut, stream, StreamExt, TryStreamExt};
 use shardtree::error::ShardTreeError;
 use subtle::ConditionallySelectable;
+use tokio::{sync::mpsc, task::JoinHandle};
 use tonic::{
     body::BoxBody,
     client::GrpcService,
@@ -21,38 +30,136 @@ use tonic::{
 };
 use tracing::{debug, info};
 use zcash_primitives::{
-    consensus::{BlockHeight, Parameters},
+    block::BlockHash,
+    consensus::{BlockHeight, NetworkUpgrade, Parameters},
     merkle_tree::HashSer,
+    transaction::{Transaction, TxId},
 };
 
 use crate::{
     data_api::{
         chain::{
             error::Error as ChainError, scan_cached_blocks, BlockCache, ChainState,
-            CommitmentTreeRoot,
+            ChainTipTracker, CommitmentTreeRoot, SubtreeRootMismatch, TipChange,
         },
         scanning::{ScanPriority, ScanRange},
-        WalletCommitmentTrees, WalletRead, WalletWrite,
+        wallet::decrypt_and_store_transaction,
+        TransactionDataRequest, TransactionStatus, WalletCommitmentTrees, WalletRead, WalletWrite,
+    },
+    proto::{
+        compact_formats::CompactBlock,
+        service::{self, compact_tx_streamer_client::CompactTxStreamerClient, BlockId},
     },
-    proto::service::{self, compact_tx_streamer_client::CompactTxStreamerClient, BlockId},
     scanning::ScanError,
+    ShieldedProtocol,
 };
 
 #[cfg(feature = "orchard")]
 use orchard::tree::MerkleHashOrchard;
 
+#[cfg(feature = "transparent-inputs")]
+use {
+    crate::{
+        encoding::AddressCodec,
+        htlc::{HtlcEvent, HtlcWatcher},
+    },
+    zcash_primitives::legacy::TransparentAddress,
+};
+
 /// Scans the chain until the wallet is up-to-date.
+///
+/// Blocks are downloaded and scanned in batches, the size of which is adjusted according to
+/// the measured scanning throughput as described in [`ScanningConfig`]. Batches are downloaded
+/// by a separate task ahead of the batch being scanned, so that network latency overlaps with
+/// trial decryption. This function must be called within a Tokio runtime, and the download
+/// only proceeds in parallel with scanning on a multi-threaded runtime.
+///
+/// The latest block reported by lightwalletd is recorded in `tip_tracker`, which should be
+/// retained between calls so that chain reorganizations between them are detected, and which
+/// can be used to determine when the wallet's unmined transactions have expired.
 pub async fn run<P, ChT, CaT, DbT>(
     client: &mut CompactTxStreamerClient<ChT>,
     params: &P,
     db_cache: &CaT,
     db_data: &mut DbT,
-    batch_size: u32,
+    config: &ScanningConfig,
+    tip_tracker: &mut ChainTipTracker,
 ) -> Result<(), Error<CaT::Error, <DbT as WalletRead>::Error, <DbT as WalletCommitmentTrees>::Error>>
 where
     P: Parameters + Send + 'static,
-    ChT: GrpcService<BoxBody>,
-    ChT::Error: Into<StdError>,
+    ChT: GrpcService<BoxBody> + Clone + Send + 'static,
+    ChT::Error: Into<StdError> + Send,
+    ChT::Future: Send,
+    ChT::ResponseBody: Body<Data = Bytes> + Send + 'static,
+    <ChT::ResponseBody as Body>::Error: Into<StdError> + Send,
+    CaT: BlockCache,
+    CaT::Error: std::error::Error + Send + Sync + 'static,
+    DbT: WalletWrite + WalletCommitmentTrees,
+    DbT::AccountId: ConditionallySelectable + Default + Send + 'static,
+    <DbT as WalletRead>::Error: std::error::Error + Send + Sync + 'static,
+    <DbT as WalletCommitmentTrees>::Error: std::error::Error + Send + Sync + 'static,
+{
+    sync_wallet(
+        client,
+        params,
+        db_cache,
+        db_data,
+        config,
+        tip_tracker,
+        false,
+    )
+    .await
+}
+
+/// Scans the chain until the wallet is up-to-date, first detecting spends of the wallet's
+/// existing notes in all blocks that have yet to be scanned.
+///
+/// This behaves like [`run`], except that before any blocks are scanned, the nullifiers
+/// revealed in every unscanned range are downloaded from lightwalletd (which is much cheaper
+/// than downloading and trial-decrypting the full compact blocks) and passed to
+/// [`WalletWrite::put_block_nullifiers`]. As a result, the wallet's balance reflects spends
+/// of its notes without waiting for the ranges containing those spends to be scanned.
+pub async fn run_with_nullifier_lookahead<P, ChT, CaT, DbT>(
+    client: &mut CompactTxStreamerClient<ChT>,
+    params: &P,
+    db_cache: &CaT,
+    db_data: &mut DbT,
+    config: &ScanningConfig,
+    tip_tracker: &mut ChainTipTracker,
+) -> Result<(), Error<CaT::Error, <DbT as WalletRead>::Error, <DbT as WalletCommitmentTrees>::Error>>
+where
+    P: Parameters + Send + 'static,
+    ChT: GrpcService<BoxBody> + Clone + Send + 'static,
+    ChT::Error: Into<StdError> + Send,
+    ChT::Future: Send,
+    ChT::ResponseBody: Body<Data = Bytes> + Send + 'static,
+    <ChT::ResponseBody as Body>::Error: Into<StdError> + Send,
+    CaT: BlockCache,
+    CaT::Error: std::error::Error + Send + Sync + 'static,
+    DbT: WalletWrite + WalletCommitmentTrees,
+    DbT::AccountId: ConditionallySelectable + Default + Send + 'static,
+    <DbT as WalletRead>::Error: std::error::Error + Send + Sync + 'static,
+    <DbT as WalletCommitmentTrees>::Error: std::error::Error + Send + Sync + 'static,
+{
+    sync_wallet(client, params, db_cache, db_data, config, tip_tracker, true).await
+}
+
+/// Implements [`run`] and [`run_with_nullifier_lookahead`], downloading the nullifiers in the
+/// unscanned ranges before scanning if `nullifier_lookahead` is set.
+async fn sync_wallet<P, ChT, CaT, DbT>(
+    client: &mut CompactTxStreamerClient<ChT>,
+    params: &P,
+    db_cache: &CaT,
+    db_data: &mut DbT,
+    config: &ScanningConfig,
+    tip_tracker: &mut ChainTipTracker,
+    nullifier_lookahead: bool,
+) -> Result<(), Error<CaT::Error, <DbT as WalletRead>::Error, <DbT as WalletCommitmentTrees>::Error>>
+where
+    P: Parameters + Send + 'static,
+    ChT: GrpcService<BoxBody> + Clone + Send + 'static,
+    ChT::Error: Into<StdError> + Send,
+    ChT::Future: Send,
     ChT::ResponseBody: Body<Data = Bytes> + Send + 'static,
     <ChT::ResponseBody as Body>::Error: Into<StdError> + Send,
     CaT: BlockCache,
@@ -66,22 +173,241 @@ where
     // 2) Pass the commitment tree data to the database.
     update_subtree_roots(client, db_data).await?;
 
-    while running(client, params, db_cache, db_data, batch_size).await? {}
+    #[cfg(feature = "transparent-inputs")]
+    let transparent_history_start = transparent_history_start(db_data)?;
+
+    // Download the nullifiers for every range that has yet to be scanned, and pass them to
+    // the wallet so that it can detect spends of its existing notes.
+    if nullifier_lookahead {
+        update_chain_tip(client, db_data, tip_tracker).await?;
+        let scan_ranges = db_data.suggest_scan_ranges().map_err(Error::Wallet)?;
+        for scan_range in scan_ranges
+            .iter()
+            .filter(|r| r.priority() != ScanPriority::Ignored)
+        {
+            let mut start = scan_range.block_range().start;
+            while start < scan_range.block_range().end {
+                let end = std::cmp::min(
+                    start + config.max_batch_size(),
+                    scan_range.block_range().end,
+                );
+                download_nullifiers(client, db_data, start..end).await?;
+                start = end;
+            }
+        }
+    }
+
+    let batch_sizer = Arc::new(BatchSizer::new(config));
+    while running(client, params, db_cache, db_data, &batch_sizer, tip_tracker).await? {}
+
+    // Transactions that only involve the transparent pool are not included in compact blocks,
+    // so they are retrieved separately for each of the wallet's transparent addresses.
+    #[cfg(feature = "transparent-inputs")]
+    update_transparent_history(client, params, db_data, transparent_history_start).await?;
+
+    // Retrieve any transaction data that the wallet requires to describe the transactions
+    // that it detected while scanning.
+    service_transaction_data_requests(client, params, db_data).await?;
 
     Ok(())
 }
 
+/// Configuration for the sizing and prefetching of the batches of blocks that are downloaded
+/// and scanned by [`run`].
+///
+/// The batch size is self-tuning: after each batch is scanned, the scanning throughput (in
+/// shielded outputs per second) is measured, and the size of the next batch is chosen such
+/// that, given the density of outputs in the most recently downloaded blocks, scanning it
+/// should take approximately [`Self::target_batch_duration`]. Each batch is committed to the
+/// wallet database as a unit, so this controls both how frequently progress is persisted and
+/// the overhead of each commit. The batch size changes by at most a factor of two after each
+/// batch, and is always kept within the configured bounds.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScanningConfig {
+    initial_batch_size: u32,
+    min_batch_size: u32,
+    max_batch_size: u32,
+    target_batch_duration: Duration,
+    fetch_concurrency: usize,
+}
+
+impl Default for ScanningConfig {
+    /// Returns a configuration that targets one-second batches of between 10 and 10,000
+    /// blocks, and downloads up to 4 batches ahead of the scanner.
+    fn default() -> Self {
+        Self {
+            initial_batch_size: 1000,
+            min_batch_size: 10,
+            max_batch_size: 10_000,
+            target_batch_duration: Duration::from_secs(1),
+            fetch_concurrency: 4,
+        }
+    }
+}
+
+impl ScanningConfig {
+    /// Constructs a new scanning configuration.
+    ///
+    /// Arguments:
+    /// * `initial_batch_size`: The number of blocks in the first batch to be scanned.
+    /// * `min_batch_size`, `max_batch_size`: The bounds on the number of blocks in a batch.
+    /// * `target_batch_duration`: The desired time taken to scan each batch.
+    /// * `fetch_concurrency`: The maximum number of batches that may be downloaded
+    ///   concurrently ahead of the batch being scanned.
+    ///
+    /// The batch size bounds and `fetch_concurrency` are raised to one if zero, and
+    /// `initial_batch_size` is clamped to the batch size bounds.
+    pub fn new(
+        initial_batch_size: u32,
+        min_batch_size: u32,
+        max_batch_size: u32,
+        target_batch_duration: Duration,
+        fetch_concurrency: usize,
+    ) -> Self {
+        let min_batch_size = min_batch_size.max(1);
+        let max_batch_size = max_batch_size.max(min_batch_size);
+        Self {
+            initial_batch_size: initial_batch_size.clamp(min_batch_size, max_batch_size),
+            min_batch_size,
+            max_batch_size,
+            target_batch_duration,
+            fetch_concurrency: fetch_concurrency.max(1),
+        }
+    }
+
+    /// Constructs a configuration that scans batches of exactly `batch_size` blocks,
+    /// disabling adaptive batch sizing.
+    pub fn fixed(batch_size: u32, fetch_concurrency: usize) -> Self {
+        Self::new(
+            batch_size,
+            batch_size,
+            batch_size,
+            Duration::from_secs(1),
+            fetch_concurrency,
+        )
+    }
+
+    /// Returns the number of blocks in the first batch to be scanned.
+    pub fn initial_batch_size(&self) -> u32 {
+        self.initial_batch_size
+    }
+
+    /// Returns the minimum number of blocks in a batch.
+    pub fn min_batch_size(&self) -> u32 {
+        self.min_batch_size
+    }
+
+    /// Returns the maximum number of blocks in a batch.
+    pub fn max_batch_size(&self) -> u32 {
+        self.max_batch_size
+    }
+
+    /// Returns the desired time taken to scan each batch.
+    pub fn target_batch_duration(&self) -> Duration {
+        self.target_batch_duration
+    }
+
+    /// Returns the maximum number of batches that may be downloaded concurrently ahead of the
+    /// batch being scanned.
+    pub fn fetch_concurrency(&self) -> usize {
+        self.fetch_concurrency
+    }
+}
+
+/// The state of the adaptive batch sizing described in [`ScanningConfig`].
+///
+/// This is shared between the scanner, which measures the scanning throughput, and the task
+/// that downloads and sizes the batches to be scanned.
+struct BatchSizer {
+    config: ScanningConfig,
+    state: Mutex<BatchSizerState>,
+}
+
+struct BatchSizerState {
+    batch_size: u32,
+    /// Exponentially-weighted moving average of the scanning throughput, in outputs per second.
+    throughput: Option<f64>,
+    /// The number of outputs per block in the most recently downloaded batch.
+    density: Option<f64>,
+}
+
+impl BatchSizer {
+    fn new(config: &ScanningConfig) -> Self {
+        Self {
+            config: config.clone(),
+            state: Mutex::new(BatchSizerState {
+                batch_size: config.initial_batch_size,
+                throughput: None,
+                density: None,
+            }),
+        }
+    }
+
+    fn batch_size(&self) -> u32 {
+        self.state.lock().unwrap().batch_size
+    }
+
+    fn record_fetched(&self, block_count: usize, output_count: u64) {
+        if block_count > 0 {
+            self.state.lock().unwrap().density = Some(output_count as f64 / block_count as f64);
+        }
+    }
+
+    fn record_scanned(&self, output_count: u64, elapsed: Duration) {
+        let mut state = self.state.lock().unwrap();
+        let current = state.batch_size;
+        let target = self.config.target_batch_duration.as_secs_f64();
+        let elapsed = elapsed.as_secs_f64();
+
+        let next = if output_count > 0 && elapsed > 0.0 {
+            let sample = output_count as f64 / elapsed;
+            let throughput = state.throughput.map_or(sample, |t| (t + sample) / 2.0);
+            state.throughput = Some(throughput);
+
+            match state.density {
+                Some(density) if density > 0.0 => (throughput * target / density) as u64,
+                // The upcoming blocks contain no outputs, so they are cheap to scan.
+                _ => u64::from(current) * 2,
+            }
+        } else if elapsed < target {
+            // There was nothing to decrypt, so the batch can only have been fast to scan.
+            u64::from(current) * 2
+        } else {
+            u64::from(current)
+        };
+
+        state.batch_size = next
+            .clamp(u64::from(current / 2), u64::from(current) * 2)
+            .clamp(
+                u64::from(self.config.min_batch_size),
+                u64::from(self.config.max_batch_size),
+            ) as u32;
+    }
+}
+
+/// Returns the number of shielded outputs to be trial-decrypted in the given blocks.
+fn output_count(blocks: &[CompactBlock]) -> u64 {
+    blocks
+        .iter()
+        .map(|block| {
+            u64::from(block.sapling_output_count()) + u64::from(block.orchard_action_count())
+        })
+        .sum()
+}
+
 async fn running<P, ChT, CaT, DbT, TrErr>(
     client: &mut CompactTxStreamerClient<ChT>,
     params: &P,
     db_cache: &CaT,
     db_data: &mut DbT,
-    batch_size: u32,
+    batch_sizer: &Arc<BatchSizer>,
+    tip_tracker: &mut ChainTipTracker,
 ) -> Result<bool, Error<CaT::Error, <DbT as WalletRead>::Error, TrErr>>
 where
     P: Parameters + Send + 'static,
-    ChT: GrpcService<BoxBody>,
-    ChT::Error: Into<StdError>,
+    ChT: GrpcService<BoxBody> + Clone + Send + 'static,
+    ChT::Error: Into<StdError> + Send,
+    ChT::Future: Send,
     ChT::ResponseBody: Body<Data = Bytes> + Send + 'static,
     <ChT::ResponseBody as Body>::Error: Into<StdError> + Send,
     CaT: BlockCache,
@@ -92,7 +418,7 @@ where
 {
     // 3) Download chain tip metadata from lightwalletd
     // 4) Notify the wallet of the updated chain tip.
-    update_chain_tip(client, db_data).await?;
+    update_chain_tip(client, db_data, tip_tracker).await?;
 
     // 5) Get the suggested scan ranges from the wallet database
     let mut scan_ranges = db_data.suggest_scan_ranges().map_err(Error::Wallet)?;
@@ -148,12 +474,16 @@ where
     //    and calling `scan_cached_blocks` on each range.
     let scan_ranges = db_data.suggest_scan_ranges().map_err(Error::Wallet)?;
     debug!("Suggested ranges: {:?}", scan_ranges);
-    for scan_range in scan_ranges.into_iter().flat_map(|r| {
+    let sizer = Arc::clone(batch_sizer);
+    let batches = scan_ranges.into_iter().flat_map(move |r| {
+        let sizer = Arc::clone(&sizer);
         // Limit the number of blocks we download and scan at any one time.
-        (0..).scan(r, |acc, _| {
+        (0..).scan(r, move |acc, _| {
             if acc.is_empty() {
                 None
-            } else if let Some((cur, next)) = acc.split_at(acc.block_range().start + batch_size) {
+            } else if let Some((cur, next)) =
+                acc.split_at(acc.block_range().start + sizer.batch_size())
+            {
                 *acc = next;
                 Some(cur)
             } else {
@@ -163,22 +493,62 @@ where
                 Some(cur)
             }
         })
-    }) {
-        // Download the blocks in `scan_range` into the block source.
-        download_blocks(client, db_cache, &scan_range).await?;
+    });
+
+    // Fetch the blocks and chain state for up to `fetch_concurrency` batches concurrently in
+    // a separate task, so that network latency overlaps with scanning. The fetched batches
+    // are received in order; at most `fetch_concurrency` of them are queued ahead of the
+    // scanner, so fetching is paused while the scanner catches up. Because the batches are
+    // split off lazily, each batch is sized according to the throughput measured so far.
+    let mut fetcher = {
+        let client = client.clone();
+        let batch_sizer = Arc::clone(batch_sizer);
+        Fetcher::spawn(
+            batches,
+            batch_sizer.config.fetch_concurrency(),
+            move |scan_range| {
+                let mut client = client.clone();
+                let batch_sizer = Arc::clone(&batch_sizer);
+                async move {
+                    let blocks = fetch_blocks(&mut client, &scan_range).await?;
+                    batch_sizer.record_fetched(scan_range.len(), output_count(&blocks));
+                    let chain_state =
+                        download_chain_state(&mut client, scan_range.block_range().start - 1)
+                            .await?;
+                    Ok::<_, Error<Infallible, Infallible, Infallible>>((
+                        scan_range,
+                        blocks,
+                        chain_state,
+                    ))
+                }
+            },
+        )
+    };
+
+    while let Some(fetched) = fetcher.next().await {
+        let (scan_range, compact_blocks, chain_state) = fetched.map_err(Error::widen)?;
 
-        let chain_state = download_chain_state(client, scan_range.block_range().start - 1).await?;
+        // Insert the fetched blocks into the block source.
+        let batch_outputs = output_count(&compact_blocks);
+        db_cache
+            .insert(compact_blocks)
+            .await
+            .map_err(Error::Cache)?;
 
-        // Scan the downloaded blocks.
+        // Scan the downloaded blocks, measuring the throughput so that the size of
+        // subsequent batches can be adjusted.
+        let scan_start = Instant::now();
         let scan_ranges_updated =
             scan_blocks(params, db_cache, db_data, &chain_state, &scan_range).await?;
+        batch_sizer.record_scanned(batch_outputs, scan_start.elapsed());
 
         // Delete the now-scanned blocks.
         block_deletions.push(db_cache.delete(scan_range));
 
         if scan_ranges_updated {
             // The suggested scan ranges have been updated (either due to a continuity
-            // error or because a higher priority range has been added).
+            // error or because a higher priority range has been added). Dropping the
+            // fetcher cancels any fetches that are still in progress.
             info!("Waiting for cached blocks to be deleted...");
             for deletion in block_deletions {
                 deletion.await.map_err(Error::Cache)?;
@@ -194,6 +564,55 @@ where
     Ok(false)
 }
 
+/// A task that fetches the data for a sequence of batches ahead of their consumer.
+///
+/// The task is aborted when the fetcher is dropped.
+struct Fetcher<T, E> {
+    results: mpsc::Receiver<Result<T, E>>,
+    task: JoinHandle<()>,
+}
+
+impl<T: Send + 'static, E: Send + 'static> Fetcher<T, E> {
+    /// Spawns a task that calls `fetch` for each of `batches` in order, with up to
+    /// `concurrency` fetches in progress at a time, and queues at most `concurrency` results
+    /// that have yet to be received. The task stops after the first error.
+    fn spawn<I, F, Fut>(batches: I, concurrency: usize, fetch: F) -> Self
+    where
+        I: Iterator + Send + 'static,
+        I::Item: Send,
+        F: FnMut(I::Item) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<T, E>> + Send + 'static,
+    {
+        let (sender, results) = mpsc::channel(concurrency.max(1));
+        let task = tokio::spawn(async move {
+            let fetched = stream::iter(batches)
+                .map(fetch)
+                .buffered(concurrency.max(1));
+            pin_mut!(fetched);
+            while let Some(result) = fetched.next().await {
+                let failed = result.is_err();
+                if sender.send(result).await.is_err() || failed {
+                    break;
+                }
+            }
+        });
+
+        Fetcher { results, task }
+    }
+
+    /// Returns the result for the next batch, or `None` if all batches have been fetched or
+    /// an error has already been returned.
+    async fn next(&mut self) -> Option<Result<T, E>> {
+        self.results.recv().await
+    }
+}
+
+impl<T, E> Drop for Fetcher<T, E> {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
 async fn update_subtree_roots<ChT, DbT, CaErr, DbErr>(
     client: &mut CompactTxStreamerClient<ChT>,
     db_data: &mut DbT,
@@ -207,7 +626,7 @@ where
     <DbT as WalletCommitmentTrees>::Error: std::error::Error + Send + Sync + 'static,
 {
     let mut request = service::GetSubtreeRootsArg::default();
-    request.set_shielded_protocol(service::ShieldedProtocol::Sapling);
+    request.set_shielded_protocol(ShieldedProtocol::Sapling.into());
     // Hack to work around a bug in the initial lightwalletd implementation.
     request.max_entries = 65536;
 
@@ -226,6 +645,16 @@ where
         .await?;
 
     info!("Sapling tree has {} subtrees", sapling_roots.len());
+
+    // Verify the roots against the tree state at the height at which the last of them was
+    // completed. Every complete subtree is beneath one of the ommers of that frontier, so
+    // this checks each of the roots, and that the server has not omitted any of them.
+    if let Some(last) = sapling_roots.last() {
+        download_chain_state(client, last.subtree_end_height())
+            .await?
+            .verify_sapling_subtree_roots(0, &sapling_roots)
+            .map_err(Error::SubtreeRootMismatch)?;
+    }
     db_data
         .put_sapling_subtree_roots(0, &sapling_roots)
         .map_err(Error::WalletTrees)?;
@@ -233,7 +662,7 @@ where
     #[cfg(feature = "orchard")]
     {
         let mut request = service::GetSubtreeRootsArg::default();
-        request.set_shielded_protocol(service::ShieldedProtocol::Orchard);
+        request.set_shielded_protocol(ShieldedProtocol::Orchard.into());
         // Hack to work around a bug in the initial lightwalletd implementation.
         request.max_entries = 65536;
         let orchard_roots: Vec<CommitmentTreeRoot<MerkleHashOrchard>> = client
@@ -251,6 +680,12 @@ where
             .await?;
 
         info!("Orchard tree has {} subtrees", orchard_roots.len());
+        if let Some(last) = orchard_roots.last() {
+            download_chain_state(client, last.subtree_end_height())
+                .await?
+                .verify_orchard_subtree_roots(0, &orchard_roots)
+                .map_err(Error::SubtreeRootMismatch)?;
+        }
         db_data
             .put_orchard_subtree_roots(0, &orchard_roots)
             .map_err(Error::WalletTrees)?;
@@ -262,6 +697,7 @@ where
 async fn update_chain_tip<ChT, DbT, CaErr, TrErr>(
     client: &mut CompactTxStreamerClient<ChT>,
     db_data: &mut DbT,
+    tip_tracker: &mut ChainTipTracker,
 ) -> Result<(), Error<CaErr, <DbT as WalletRead>::Error, TrErr>>
 where
     ChT: GrpcService<BoxBody>,
@@ -271,22 +707,323 @@ where
     DbT: WalletWrite,
     DbT::Error: std::error::Error + Send + Sync + 'static,
 {
-    let tip_height: BlockHeight = client
+    let latest_block = client
         .get_latest_block(service::ChainSpec::default())
         .await?
-        .get_ref()
+        .into_inner();
+    let tip_height: BlockHeight = latest_block
         .height
         .try_into()
         .map_err(|_| Error::MisbehavingServer)?;
+    let tip_hash = BlockHash::try_from_slice(&latest_block.hash).ok_or(Error::MisbehavingServer)?;
 
     info!("Latest block height is {}", tip_height);
-    db_data
-        .update_chain_tip(tip_height)
-        .map_err(Error::Wallet)?;
+    if let TipChange::Reorg { previous } = tip_tracker.observe(tip_height, tip_hash) {
+        info!(
+            "Chain reorganization detected: previous tip height {}, new tip height {}",
+            previous, tip_height
+        );
+    }
+    tip_tracker.update_wallet(db_data).map_err(Error::Wallet)?;
+
+    Ok(())
+}
+
+/// Services the requests returned by [`WalletRead::transaction_data_requests`] using
+/// lightwalletd, and passes the results to the wallet.
+async fn service_transaction_data_requests<P, ChT, DbT, CaErr, TrErr>(
+    client: &mut CompactTxStreamerClient<ChT>,
+    params: &P,
+    db_data: &mut DbT,
+) -> Result<(), Error<CaErr, <DbT as WalletRead>::Error, TrErr>>
+where
+    P: Parameters,
+    ChT: GrpcService<BoxBody>,
+    ChT::Error: Into<StdError>,
+    ChT::ResponseBody: Body<Data = Bytes> + Send + 'static,
+    <ChT::ResponseBody as Body>::Error: Into<StdError> + Send,
+    DbT: WalletWrite,
+    DbT::Error: std::error::Error + Send + Sync + 'static,
+{
+    for request in db_data.transaction_data_requests().map_err(Error::Wallet)? {
+        match request {
+            TransactionDataRequest::GetStatus(txid) => {
+                debug!("Fetching status of transaction {}", txid);
+                let status = match client.get_transaction(tx_filter(txid)).await {
+                    Ok(response) => response
+                        .get_ref()
+                        .mined_height()
+                        .map_or(TransactionStatus::NotInMainChain, TransactionStatus::Mined),
+                    Err(status) if status.code() == tonic::Code::NotFound => {
+                        TransactionStatus::TxidNotRecognized
+                    }
+                    Err(status) => return Err(status.into()),
+                };
+
+                db_data
+                    .set_transaction_status(txid, status)
+                    .map_err(Error::Wallet)?;
+            }
+            TransactionDataRequest::Enhancement(txid) => {
+                debug!("Fetching transaction {}", txid);
+                let raw_tx = client.get_transaction(tx_filter(txid)).await?.into_inner();
+                let height = raw_tx.mined_height();
+                let tx = parse_raw_transaction(params, db_data, raw_tx)?;
+                if tx.txid() != txid {
+                    return Err(Error::MisbehavingServer);
+                }
+
+                decrypt_and_store_transaction(params, db_data, &tx, height)
+                    .map_err(Error::Wallet)?;
+            }
+            #[cfg(feature = "transparent-inputs")]
+            TransactionDataRequest::SpendsFromAddress {
+                address,
+                block_range_start,
+                block_range_end,
+            } => {
+                let end_height = match block_range_end {
+                    Some(end) => end - 1,
+                    None => db_data
+                        .chain_height()
+                        .map_err(Error::Wallet)?
+                        .unwrap_or(block_range_start),
+                };
+                download_address_history(
+                    client,
+                    params,
+                    db_data,
+                    &address,
+                    block_range_start,
+                    end_height,
+                )
+                .await?;
+                db_data
+                    .notify_address_checked(&address, end_height)
+                    .map_err(Error::Wallet)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the height from which the history of the wallet's transparent addresses must be
+/// retrieved: the transactions in blocks up to the wallet's fully-scanned height were retrieved
+/// by a previous sync.
+#[cfg(feature = "transparent-inputs")]
+fn transparent_history_start<DbT, CaErr, TrErr>(
+    db_data: &DbT,
+) -> Result<Option<BlockHeight>, Error<CaErr, <DbT as WalletRead>::Error, TrErr>>
+where
+    DbT: WalletRead,
+{
+    Ok(db_data
+        .block_fully_scanned()
+        .map_err(Error::Wallet)?
+        .map(|meta| meta.block_height()))
+}
+
+/// Retrieves the transactions involving each of the wallet's transparent addresses that were
+/// mined between `start_height` (or the account's birthday, if later) and the chain tip, and
+/// passes them to the wallet.
+#[cfg(feature = "transparent-inputs")]
+async fn update_transparent_history<P, ChT, DbT, CaErr, TrErr>(
+    client: &mut CompactTxStreamerClient<ChT>,
+    params: &P,
+    db_data: &mut DbT,
+    start_height: Option<BlockHeight>,
+) -> Result<(), Error<CaErr, <DbT as WalletRead>::Error, TrErr>>
+where
+    P: Parameters,
+    ChT: GrpcService<BoxBody>,
+    ChT::Error: Into<StdError>,
+    ChT::ResponseBody: Body<Data = Bytes> + Send + 'static,
+    <ChT::ResponseBody as Body>::Error: Into<StdError> + Send,
+    DbT: WalletWrite,
+    DbT::Error: std::error::Error + Send + Sync + 'static,
+{
+    let chain_tip = match db_data.chain_height().map_err(Error::Wallet)? {
+        Some(height) => height,
+        None => return Ok(()),
+    };
+
+    for account_id in db_data.get_account_ids().map_err(Error::Wallet)? {
+        let birthday = db_data
+            .get_account_birthday(account_id)
+            .map_err(Error::Wallet)?;
+        let start = start_height.map_or(birthday, |h| std::cmp::max(h, birthday));
+        if start > chain_tip {
+            continue;
+        }
+
+        let addresses = db_data
+            .get_transparent_receivers(account_id)
+            .map_err(Error::Wallet)?;
+        for address in addresses.keys() {
+            download_address_history(client, params, db_data, address, start, chain_tip).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Retrieves the transactions involving the given transparent address that were mined in the
+/// blocks `start_height..=end_height`, and passes them to the wallet.
+#[cfg(feature = "transparent-inputs")]
+async fn download_address_history<P, ChT, DbT, CaErr, TrErr>(
+    client: &mut CompactTxStreamerClient<ChT>,
+    params: &P,
+    db_data: &mut DbT,
+    address: &TransparentAddress,
+    start_height: BlockHeight,
+    end_height: BlockHeight,
+) -> Result<(), Error<CaErr, <DbT as WalletRead>::Error, TrErr>>
+where
+    P: Parameters,
+    ChT: GrpcService<BoxBody>,
+    ChT::Error: Into<StdError>,
+    ChT::ResponseBody: Body<Data = Bytes> + Send + 'static,
+    <ChT::ResponseBody as Body>::Error: Into<StdError> + Send,
+    DbT: WalletWrite,
+    DbT::Error: std::error::Error + Send + Sync + 'static,
+{
+    let txs =
+        fetch_address_transactions(client, params, db_data, address, start_height, end_height)
+            .await?;
+    for (tx, height) in txs {
+        decrypt_and_store_transaction(params, db_data, &tx, height).map_err(Error::Wallet)?;
+    }
 
     Ok(())
 }
 
+/// Retrieves the transactions involving the given transparent address that were mined in the
+/// blocks `start_height..=end_height`, along with the heights at which they were mined.
+#[cfg(feature = "transparent-inputs")]
+async fn fetch_address_transactions<P, ChT, DbT, CaErr, TrErr>(
+    client: &mut CompactTxStreamerClient<ChT>,
+    params: &P,
+    db_data: &DbT,
+    address: &TransparentAddress,
+    start_height: BlockHeight,
+    end_height: BlockHeight,
+) -> Result<Vec<(Transaction, Option<BlockHeight>)>, Error<CaErr, <DbT as WalletRead>::Error, TrErr>>
+where
+    P: Parameters,
+    ChT: GrpcService<BoxBody>,
+    ChT::Error: Into<StdError>,
+    ChT::ResponseBody: Body<Data = Bytes> + Send + 'static,
+    <ChT::ResponseBody as Body>::Error: Into<StdError> + Send,
+    DbT: WalletRead,
+{
+    let address = address.encode(params);
+    debug!(
+        "Fetching transactions involving {} in blocks {}..={}",
+        address, start_height, end_height
+    );
+
+    let mut start = service::BlockId::default();
+    start.height = start_height.into();
+    let mut end = service::BlockId::default();
+    end.height = end_height.into();
+    let raw_txs = client
+        .get_taddress_txids(service::TransparentAddressBlockFilter {
+            address,
+            range: Some(service::BlockRange {
+                start: Some(start),
+                end: Some(end),
+            }),
+        })
+        .await?
+        .into_inner()
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    raw_txs
+        .into_iter()
+        .map(|raw_tx| {
+            let height = raw_tx.mined_height();
+            parse_raw_transaction(params, db_data, raw_tx).map(|tx| (tx, height))
+        })
+        .collect()
+}
+
+/// Retrieves the transactions involving each of the hash time-locked contracts watched by
+/// `watcher` that were mined between `start_height` and the wallet's chain tip, and returns
+/// the events relating to those contracts that they contain, in the order in which they were
+/// returned by lightwalletd.
+///
+/// Transactions that fund or spend a contract are not otherwise detected by the wallet, as
+/// the contract's P2SH address is not one of the wallet's transparent addresses. This should
+/// be called after [`run`], so that the wallet's chain tip is up to date; the `start_height`
+/// of each call can be the chain tip as of the previous call, as any events from earlier
+/// blocks will already have been returned.
+#[cfg(feature = "transparent-inputs")]
+pub async fn watch_htlcs<P, ChT, DbT, CaErr, TrErr>(
+    client: &mut CompactTxStreamerClient<ChT>,
+    params: &P,
+    db_data: &DbT,
+    watcher: &HtlcWatcher,
+    start_height: BlockHeight,
+) -> Result<Vec<HtlcEvent>, Error<CaErr, <DbT as WalletRead>::Error, TrErr>>
+where
+    P: Parameters,
+    ChT: GrpcService<BoxBody>,
+    ChT::Error: Into<StdError>,
+    ChT::ResponseBody: Body<Data = Bytes> + Send + 'static,
+    <ChT::ResponseBody as Body>::Error: Into<StdError> + Send,
+    DbT: WalletRead,
+{
+    let chain_tip = match db_data.chain_height().map_err(Error::Wallet)? {
+        Some(height) if height >= start_height => height,
+        _ => return Ok(vec![]),
+    };
+
+    let mut txs = vec![];
+    for address in watcher.addresses() {
+        txs.extend(
+            fetch_address_transactions(client, params, db_data, address, start_height, chain_tip)
+                .await?,
+        );
+    }
+
+    Ok(watcher.scan_transactions(txs.iter().map(|(tx, height)| (tx, *height))))
+}
+
+fn tx_filter(txid: TxId) -> service::TxFilter {
+    service::TxFilter {
+        block: None,
+        index: 0,
+        hash: txid.as_ref().to_vec(),
+    }
+}
+
+/// Parses a transaction returned by lightwalletd.
+fn parse_raw_transaction<P, DbT, CaErr, TrErr>(
+    params: &P,
+    db_data: &DbT,
+    raw_tx: service::RawTransaction,
+) -> Result<Transaction, Error<CaErr, <DbT as WalletRead>::Error, TrErr>>
+where
+    P: Parameters,
+    DbT: WalletRead,
+{
+    // Unmined transactions are parsed as of the block following the chain tip.
+    let unmined_height = db_data
+        .chain_height()
+        .map_err(Error::Wallet)?
+        .map_or_else(
+            || params.activation_height(NetworkUpgrade::Sapling),
+            |h| Some(h + 1),
+        )
+        .expect("Sapling activation height must be known.");
+
+    raw_tx
+        .to_transaction(params, unmined_height)
+        .map_err(|_| Error::MisbehavingServer)
+}
+
 async fn download_blocks<ChT, CaT, DbErr, TrErr>(
     client: &mut CompactTxStreamerClient<ChT>,
     db_cache: &CaT,
@@ -299,6 +1036,26 @@ where
     <ChT::ResponseBody as Body>::Error: Into<StdError> + Send,
     CaT: BlockCache,
     CaT::Error: std::error::Error + Send + Sync + 'static,
+{
+    let compact_blocks = fetch_blocks(client, scan_range).await?;
+
+    db_cache
+        .insert(compact_blocks)
+        .await
+        .map_err(Error::Cache)?;
+
+    Ok(())
+}
+
+async fn fetch_blocks<ChT, CaErr, DbErr, TrErr>(
+    client: &mut CompactTxStreamerClient<ChT>,
+    scan_range: &ScanRange,
+) -> Result<Vec<CompactBlock>, Error<CaErr, DbErr, TrErr>>
+where
+    ChT: GrpcService<BoxBody>,
+    ChT::Error: Into<StdError>,
+    ChT::ResponseBody: Body<Data = Bytes> + Send + 'static,
+    <ChT::ResponseBody as Body>::Error: Into<StdError> + Send,
 {
     info!("Fetching {}", scan_range);
     let mut start = service::BlockId::default();
@@ -316,12 +1073,48 @@ where
         .try_collect::<Vec<_>>()
         .await?;
 
-    db_cache
-        .insert(compact_blocks)
-        .await
-        .map_err(Error::Cache)?;
+    Ok(compact_blocks)
+}
 
-    Ok(())
+async fn download_nullifiers<ChT, DbT, CaErr, TrErr>(
+    client: &mut CompactTxStreamerClient<ChT>,
+    db_data: &mut DbT,
+    block_range: Range<BlockHeight>,
+) -> Result<(), Error<CaErr, <DbT as WalletRead>::Error, TrErr>>
+where
+    ChT: GrpcService<BoxBody>,
+    ChT::Error: Into<StdError>,
+    ChT::ResponseBody: Body<Data = Bytes> + Send + 'static,
+    <ChT::ResponseBody as Body>::Error: Into<StdError> + Send,
+    DbT: WalletWrite,
+    DbT::Error: std::error::Error + Send + Sync + 'static,
+{
+    info!(
+        "Fetching nullifiers for blocks {}..{}",
+        block_range.start, block_range.end
+    );
+    let mut start = service::BlockId::default();
+    start.height = block_range.start.into();
+    let mut end = service::BlockId::default();
+    end.height = (block_range.end - 1).into();
+    let range = service::BlockRange {
+        start: Some(start),
+        end: Some(end),
+    };
+    let block_nullifiers = client
+        .get_block_range_nullifiers(range)
+        .await?
+        .into_inner()
+        .try_collect::<Vec<_>>()
+        .await?
+        .iter()
+        .map(|block| block.block_nullifiers())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| Error::MisbehavingServer)?;
+
+    db_data
+        .put_block_nullifiers(block_nullifiers)
+        .map_err(Error::Wallet)
 }
 
 async fn download_chain_state<ChT, CaErr, DbErr, TrErr>(
@@ -378,15 +1171,14 @@ where
 
     match scan_result {
         Err(ChainError::Scan(err)) if err.is_continuity_error() => {
-            // Pick a height to rewind to, which must be at least one block before the
-            // height at which the error occurred, but may be an earlier height determined
-            // based on heuristics such as the platform, available bandwidth, size of
-            // recent CompactBlocks, etc.
-            let rewind_height = err.at_height().saturating_sub(10);
+            // Rewind to a height below the earliest block that is inconsistent with the
+            // wallet's view of the chain.
+            let rewind_height = err
+                .suggested_rewind_height()
+                .expect("continuity errors always suggest a rewind height");
             info!(
-                "Chain reorg detected at {}, rewinding to {}",
-                err.at_height(),
-                rewind_height,
+                "Chain reorg detected ({}), rewinding to {}",
+                err, rewind_height
             );
 
             // Rewind to the chosen height.
@@ -431,6 +1223,9 @@ pub enum Error<CaErr, DbErr, TrErr> {
     MisbehavingServer,
     /// An error while scanning blocks.
     Scan(ScanError),
+    /// A subtree root returned by the lightwalletd server is inconsistent with the note
+    /// commitment tree state at the chain tip.
+    SubtreeRootMismatch(SubtreeRootMismatch),
     /// An error while communicating with the lightwalletd server.
     Server(tonic::Status),
     /// An error while interacting with a wallet database via [`WalletRead`] or
@@ -451,6 +1246,7 @@ where
             Error::Cache(e) => write!(f, "Error while interacting with block cache: {}", e),
             Error::MisbehavingServer => write!(f, "lightwalletd server is misbehaving"),
             Error::Scan(e) => write!(f, "Error while scanning blocks: {}", e),
+            Error::SubtreeRootMismatch(e) => write!(f, "Invalid subtree root from server: {}", e),
             Error::Server(e) => write!(
                 f,
                 "Error while communicating with lightwalletd server: {}",
@@ -474,6 +1270,24 @@ where
 {
 }
 
+impl<CaErr, DbErr, TrErr> Error<CaErr, DbErr, TrErr> {
+    /// Converts an error that does not involve the block cache or the wallet database.
+    fn widen(e: Error<Infallible, Infallible, Infallible>) -> Self {
+        match e {
+            Error::Cache(e) | Error::Wallet(e) => match e {},
+            Error::WalletTrees(e) => match e {
+                ShardTreeError::Query(e) => Error::WalletTrees(ShardTreeError::Query(e)),
+                ShardTreeError::Insert(e) => Error::WalletTrees(ShardTreeError::Insert(e)),
+                ShardTreeError::Storage(e) => match e {},
+            },
+            Error::MisbehavingServer => Error::MisbehavingServer,
+            Error::Scan(e) => Error::Scan(e),
+            Error::SubtreeRootMismatch(e) => Error::SubtreeRootMismatch(e),
+            Error::Server(e) => Error::Server(e),
+        }
+    }
+}
+
 impl<CaErr, DbErr, TrErr> From<ChainError<DbErr, CaErr>> for Error<CaErr, DbErr, TrErr> {
     fn from(e: ChainError<DbErr, CaErr>) -> Self {
         match e {
@@ -489,3 +1303,145 @@ impl<CaErr, DbErr, TrErr> From<tonic::Status> for Error<CaErr, DbErr, TrErr> {
         Error::Server(status)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+    use std::time::{Duration, Instant};
+
+    use super::{BatchSizer, Fetcher, ScanningConfig};
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .build()
+            .unwrap()
+    }
+
+    /// Blocks the current thread until `condition` holds, for up to five seconds.
+    fn wait_until(condition: impl Fn() -> bool) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !condition() {
+            if Instant::now() > deadline {
+                return false;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        true
+    }
+
+    #[test]
+    fn fetcher_runs_ahead_of_blocked_consumer() {
+        runtime().block_on(async {
+            let started = Arc::new(AtomicUsize::new(0));
+            let counter = Arc::clone(&started);
+            let mut fetcher = Fetcher::spawn(0..100u32, 2, move |i| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                async move { Ok::<_, ()>(i) }
+            });
+
+            assert_eq!(fetcher.next().await, Some(Ok(0)));
+
+            // While the consumer is blocked (and so is not polling the fetcher), the
+            // following batches are fetched in the background...
+            assert!(wait_until(|| started.load(Ordering::SeqCst) >= 4));
+            // ...but no further ahead than the queued and in-progress batches allow.
+            std::thread::sleep(Duration::from_millis(50));
+            assert!(started.load(Ordering::SeqCst) <= 6);
+
+            // Results are received in order.
+            for i in 1..100 {
+                assert_eq!(fetcher.next().await, Some(Ok(i)));
+            }
+            assert_eq!(fetcher.next().await, None);
+        });
+    }
+
+    #[test]
+    fn fetcher_stops_after_error() {
+        runtime().block_on(async {
+            let mut fetcher = Fetcher::spawn(0..10u32, 3, |i| async move {
+                if i == 2 {
+                    Err(i)
+                } else {
+                    Ok(i)
+                }
+            });
+
+            assert_eq!(fetcher.next().await, Some(Ok(0)));
+            assert_eq!(fetcher.next().await, Some(Ok(1)));
+            assert_eq!(fetcher.next().await, Some(Err(2)));
+            assert_eq!(fetcher.next().await, None);
+        });
+    }
+
+    #[test]
+    fn dropping_fetcher_stops_fetching() {
+        runtime().block_on(async {
+            let started = Arc::new(AtomicUsize::new(0));
+            let counter = Arc::clone(&started);
+            let mut fetcher = Fetcher::spawn(0u32.., 1, move |i| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                async move { Ok::<_, ()>(i) }
+            });
+
+            assert_eq!(fetcher.next().await, Some(Ok(0)));
+            drop(fetcher);
+
+            std::thread::sleep(Duration::from_millis(10));
+            let fetched = started.load(Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(50));
+            assert_eq!(started.load(Ordering::SeqCst), fetched);
+        });
+    }
+
+    #[test]
+    fn batch_size_tracks_target_duration() {
+        let config = ScanningConfig::new(1000, 10, 10_000, Duration::from_secs(1), 4);
+        let sizer = BatchSizer::new(&config);
+        assert_eq!(sizer.batch_size(), 1000);
+
+        // 1000 blocks of 10 outputs each, scanned in 4 seconds: the next batch should be
+        // smaller, but by no more than a factor of two.
+        sizer.record_fetched(1000, 10_000);
+        sizer.record_scanned(10_000, Duration::from_secs(4));
+        assert_eq!(sizer.batch_size(), 500);
+
+        // 500 blocks scanned in exactly the target duration: 5000 outputs per second at 10
+        // outputs per block averages with the previous throughput of 2500 outputs per second.
+        sizer.record_fetched(500, 5000);
+        sizer.record_scanned(5000, Duration::from_secs(1));
+        assert_eq!(sizer.batch_size(), 375);
+
+        // Blocks without outputs are cheap to scan, so the batch size grows.
+        sizer.record_fetched(375, 0);
+        sizer.record_scanned(0, Duration::from_millis(10));
+        assert_eq!(sizer.batch_size(), 750);
+    }
+
+    #[test]
+    fn batch_size_respects_bounds() {
+        let config = ScanningConfig::new(20, 10, 30, Duration::from_secs(1), 0);
+        assert_eq!(config.fetch_concurrency(), 1);
+        let sizer = BatchSizer::new(&config);
+
+        sizer.record_fetched(20, 20);
+        sizer.record_scanned(20, Duration::from_millis(1));
+        assert_eq!(sizer.batch_size(), 30);
+
+        sizer.record_fetched(30, 30_000);
+        sizer.record_scanned(30_000, Duration::from_secs(100));
+        assert_eq!(sizer.batch_size(), 15);
+        sizer.record_scanned(30_000, Duration::from_secs(100));
+        assert_eq!(sizer.batch_size(), 10);
+
+        let fixed = ScanningConfig::fixed(100, 2);
+        let sizer = BatchSizer::new(&fixed);
+        sizer.record_fetched(100, 0);
+        sizer.record_scanned(0, Duration::from_millis(1));
+        assert_eq!(sizer.batch_size(), 100);
+    }
+}
@@ -0,0 +1,401 @@
+//! Encoding of large payloads as Uniform Resources (URs), for transfer via QR codes.
+//!
+//! Payloads such as viewing keys, wallet backups, and partially created transactions are
+//! frequently too large to be represented by a single QR code. This module implements the
+//! multi-part UR format specified in [BCR-2020-005], which splits a payload into a sequence of
+//! fragments that can be displayed as an animated QR code. Beyond the first pass through the
+//! fragments, each part combines a pseudorandom selection of fragments using a fountain code,
+//! so that a receiver can reconstruct the payload from any sufficiently large subset of the
+//! parts it observes, irrespective of which parts it missed.
+//!
+//! Each part is encoded as a string of the form `ur:<type>/<seq>-<count>/<bytewords>`, using the
+//! minimal [Bytewords] encoding. These strings consist only of characters that can be encoded
+//! using the QR alphanumeric mode once converted to upper case, which [`Decoder`] accepts.
+//!
+//! [BCR-2020-005]: https://github.com/BlockchainCommons/Research/blob/master/papers/bcr-2020-005-ur.md
+//! [Bytewords]: https://github.com/BlockchainCommons/Research/blob/master/papers/bcr-2020-012-bytewords.md
+
+use std::fmt;
+
+mod bytewords;
+mod fountain;
+
+use fountain::{FountainDecoder, FountainEncoder, Part};
+
+/// The minimum length of a fragment, in bytes.
+pub const MIN_FRAGMENT_LEN: usize = 10;
+
+/// Errors that can occur in encoding or decoding Uniform Resources.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The message to be encoded is empty.
+    EmptyMessage,
+    /// The requested maximum fragment length is less than [`MIN_FRAGMENT_LEN`].
+    FragmentTooShort(usize),
+    /// The string does not begin with the `ur:` scheme.
+    InvalidScheme,
+    /// The type of the resource is empty or contains characters other than lowercase ASCII
+    /// letters, digits, and hyphens.
+    InvalidType,
+    /// The sequence component of a multi-part UR is not of the form `<seq>-<count>`, or does
+    /// not match the part that it accompanies.
+    InvalidSequence,
+    /// The body of the UR is not a valid minimal Bytewords encoding.
+    InvalidBytewords,
+    /// The checksum of a part, or of the message reassembled from a sequence of parts, does not
+    /// match the data.
+    InvalidChecksum,
+    /// A part of a multi-part UR could not be parsed.
+    InvalidPart,
+    /// A part is not consistent with the parts previously received by a [`Decoder`], for
+    /// example because it has a different type or belongs to a different message.
+    InconsistentPart,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::EmptyMessage => write!(f, "Cannot encode an empty message"),
+            Error::FragmentTooShort(len) => write!(
+                f,
+                "Maximum fragment length {} is less than the minimum of {}",
+                len, MIN_FRAGMENT_LEN
+            ),
+            Error::InvalidScheme => write!(f, "Not a Uniform Resource"),
+            Error::InvalidType => write!(f, "Invalid Uniform Resource type"),
+            Error::InvalidSequence => write!(f, "Invalid multi-part sequence component"),
+            Error::InvalidBytewords => write!(f, "Invalid Bytewords encoding"),
+            Error::InvalidChecksum => write!(f, "Checksum mismatch"),
+            Error::InvalidPart => write!(f, "Malformed multi-part Uniform Resource"),
+            Error::InconsistentPart => write!(
+                f,
+                "Part does not belong to the same message as previously received parts"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Computes the CRC-32 checksum (as used by ISO 3309 and zlib) of the given data.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for byte in data {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+fn is_valid_type(ur_type: &str) -> bool {
+    !ur_type.is_empty()
+        && ur_type
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
+}
+
+/// Encodes the given message as a single-part UR of the given type.
+///
+/// For interoperability with other UR implementations, the message should be the CBOR encoding
+/// of a value of the given type.
+pub fn encode(ur_type: &str, message: &[u8]) -> Result<String, Error> {
+    if !is_valid_type(ur_type) {
+        return Err(Error::InvalidType);
+    }
+
+    Ok(format!("ur:{}/{}", ur_type, bytewords::encode(message)))
+}
+
+/// Decodes a single-part UR, returning its type and message.
+///
+/// Returns [`Error::InvalidSequence`] if the string is a part of a multi-part UR; use a
+/// [`Decoder`] to decode such parts.
+pub fn decode(ur: &str) -> Result<(String, Vec<u8>), Error> {
+    match parse(ur)? {
+        Parsed::Single { ur_type, message } => Ok((ur_type, message)),
+        Parsed::Multi { .. } => Err(Error::InvalidSequence),
+    }
+}
+
+enum Parsed {
+    Single { ur_type: String, message: Vec<u8> },
+    Multi { ur_type: String, part: Part },
+}
+
+fn parse(ur: &str) -> Result<Parsed, Error> {
+    let ur = ur.trim().to_ascii_lowercase();
+    let path = ur.strip_prefix("ur:").ok_or(Error::InvalidScheme)?;
+    let components: Vec<&str> = path.split('/').collect();
+    let ur_type = components[0];
+    if !is_valid_type(ur_type) {
+        return Err(Error::InvalidType);
+    }
+
+    match components[1..] {
+        [body] => Ok(Parsed::Single {
+            ur_type: ur_type.to_owned(),
+            message: bytewords::decode(body)?,
+        }),
+        [sequence, body] => {
+            let (seq_num, seq_len) = sequence
+                .split_once('-')
+                .and_then(|(seq_num, seq_len)| {
+                    Some((seq_num.parse::<u32>().ok()?, seq_len.parse::<usize>().ok()?))
+                })
+                .ok_or(Error::InvalidSequence)?;
+            let part = Part::from_cbor(&bytewords::decode(body)?)?;
+            if part.seq_num() != seq_num || part.seq_len() != seq_len {
+                return Err(Error::InvalidSequence);
+            }
+
+            Ok(Parsed::Multi {
+                ur_type: ur_type.to_owned(),
+                part,
+            })
+        }
+        _ => Err(Error::InvalidSequence),
+    }
+}
+
+/// An encoder that produces the parts of a multi-part UR.
+///
+/// The first [`Encoder::part_count`] parts each contain a single fragment of the message. The
+/// encoder then continues indefinitely with parts that each combine several fragments, so that
+/// a receiver that has missed some parts can still reconstruct the message.
+pub struct Encoder {
+    ur_type: String,
+    message: Vec<u8>,
+    fountain: FountainEncoder,
+}
+
+impl Encoder {
+    /// Constructs an encoder for a message of the given type, producing parts whose fragments
+    /// are at most `max_fragment_len` bytes long.
+    ///
+    /// For interoperability with other UR implementations, the message should be the CBOR
+    /// encoding of a value of the given type.
+    pub fn new(ur_type: &str, message: &[u8], max_fragment_len: usize) -> Result<Self, Error> {
+        if !is_valid_type(ur_type) {
+            return Err(Error::InvalidType);
+        }
+
+        Ok(Encoder {
+            ur_type: ur_type.to_owned(),
+            message: message.to_vec(),
+            fountain: FountainEncoder::new(message, max_fragment_len)?,
+        })
+    }
+
+    /// Returns the number of fragments into which the message has been split. This is the
+    /// minimum number of parts that a receiver must observe in order to decode the message.
+    pub fn part_count(&self) -> usize {
+        self.fountain.seq_len()
+    }
+
+    /// Returns whether the message fits within a single fragment, in which case every part
+    /// produced by this encoder is the same single-part UR.
+    pub fn is_single_part(&self) -> bool {
+        self.part_count() == 1
+    }
+
+    /// Returns the next part of the UR.
+    pub fn next_part(&mut self) -> String {
+        if self.is_single_part() {
+            format!("ur:{}/{}", self.ur_type, bytewords::encode(&self.message))
+        } else {
+            let part = self.fountain.next_part();
+            format!(
+                "ur:{}/{}-{}/{}",
+                self.ur_type,
+                part.seq_num(),
+                part.seq_len(),
+                bytewords::encode(&part.to_cbor())
+            )
+        }
+    }
+}
+
+/// A decoder that reassembles a message from the parts of a multi-part UR, which may be received
+/// in any order and with omissions.
+#[derive(Default)]
+pub struct Decoder {
+    ur_type: Option<String>,
+    fountain: FountainDecoder,
+    message: Option<Vec<u8>>,
+}
+
+impl Decoder {
+    /// Constructs a decoder that has not yet received any parts.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Processes the given part.
+    ///
+    /// Single-part URs are also accepted, and complete the decoder immediately. Parts received
+    /// after the message has been reassembled are ignored.
+    pub fn receive(&mut self, ur: &str) -> Result<(), Error> {
+        if self.message.is_some() {
+            return Ok(());
+        }
+
+        let (ur_type, message) = match parse(ur)? {
+            Parsed::Single { ur_type, message } => {
+                // A single-part UR cannot be combined with the parts of a multi-part UR.
+                if self.fountain.has_received_parts() {
+                    return Err(Error::InconsistentPart);
+                }
+                (ur_type, Some(message))
+            }
+            Parsed::Multi { ur_type, part } => {
+                if self.ur_type.as_ref().map_or(false, |t| t != &ur_type) {
+                    return Err(Error::InconsistentPart);
+                }
+                self.fountain.receive(part)?;
+                (ur_type, self.fountain.message().map(|m| m.to_vec()))
+            }
+        };
+
+        self.ur_type = Some(ur_type);
+        self.message = message;
+        Ok(())
+    }
+
+    /// Returns whether the message has been reassembled.
+    pub fn is_complete(&self) -> bool {
+        self.message.is_some()
+    }
+
+    /// Returns the type of the UR being decoded, if any part has been received.
+    pub fn ur_type(&self) -> Option<&str> {
+        self.ur_type.as_deref()
+    }
+
+    /// Returns the reassembled message, if decoding is complete.
+    pub fn message(&self) -> Option<&[u8]> {
+        self.message.as_deref()
+    }
+
+    /// Returns an estimate of the proportion of the message that has been received, between
+    /// 0 and 1, suitable for display as a progress indicator.
+    pub fn estimated_progress(&self) -> f64 {
+        if self.is_complete() {
+            1.0
+        } else {
+            self.fountain.estimated_progress()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{crc32, decode, encode, fountain::make_message, Decoder, Encoder, Error};
+
+    /// Returns the CBOR encoding of the given data as a byte string.
+    fn cbor_bytes(data: &[u8]) -> Vec<u8> {
+        assert!(data.len() < 0x10000);
+        let mut result = if data.len() < 24 {
+            vec![0x40 | data.len() as u8]
+        } else if data.len() < 0x100 {
+            vec![0x58, data.len() as u8]
+        } else {
+            vec![0x59, (data.len() >> 8) as u8, data.len() as u8]
+        };
+        result.extend_from_slice(data);
+        result
+    }
+
+    #[test]
+    fn crc32_test_vector() {
+        assert_eq!(crc32(b"Hello, world!"), 0xebe6c6e6);
+    }
+
+    #[test]
+    fn single_part_round_trip() {
+        let message = cbor_bytes(&make_message(50, b"Wolf"));
+        let ur = encode("bytes", &message).unwrap();
+        assert_eq!(decode(&ur), Ok(("bytes".to_owned(), message.clone())));
+        assert_eq!(
+            decode(&ur.to_ascii_uppercase()),
+            Ok(("bytes".to_owned(), message))
+        );
+
+        assert_eq!(encode("Bytes", &[1]), Err(Error::InvalidType));
+        assert_eq!(
+            decode("bytes/aeadaolazmjendeoti"),
+            Err(Error::InvalidScheme)
+        );
+        assert_eq!(
+            decode("ur:bytes/aeadaolazmjendeote"),
+            Err(Error::InvalidChecksum)
+        );
+    }
+
+    #[test]
+    fn multi_part_test_vectors() {
+        // Test vectors from BCR-2020-005.
+        let message = cbor_bytes(&make_message(256, b"Wolf"));
+        let mut encoder = Encoder::new("bytes", &message, 30).unwrap();
+        assert_eq!(encoder.part_count(), 9);
+
+        let parts: Vec<String> = (0..20).map(|_| encoder.next_part()).collect();
+        assert_eq!(
+            parts[0],
+            "ur:bytes/1-9/lpadascfadaxcywenbpljkhdcahkadaemejtswhhylkepmykhhtsytsnoyoyaxaedsuttydmmhhpktpmsrjtdkgslpgh"
+        );
+        assert_eq!(
+            parts[9],
+            "ur:bytes/10-9/lpbkascfadaxcywenbpljkhdcahkadaemejtswhhylkepmykhhtsytsnoyoyaxaedsuttydmmhhpktpmsrjtwdkiplzs"
+        );
+        assert_eq!(
+            parts[19],
+            "ur:bytes/20-9/lpbbascfadaxcywenbpljkhdcayapmrleeleaxpasfrtrdkncffwjyjzgyetdmlewtkpktgllepfrltataztksmhkbot"
+        );
+    }
+
+    #[test]
+    fn multi_part_round_trip_with_missing_parts() {
+        let message = make_message(32767, b"Wolf");
+        let mut encoder = Encoder::new("bytes", &message, 1000).unwrap();
+        let mut decoder = Decoder::new();
+
+        // Skip every third part, so that the message can only be reassembled using the parts
+        // that combine multiple fragments.
+        let mut seq_num = 0;
+        while !decoder.is_complete() {
+            let part = encoder.next_part();
+            seq_num += 1;
+            if seq_num % 3 != 0 {
+                decoder.receive(&part).unwrap();
+            }
+            assert!(seq_num < 1000);
+        }
+
+        assert!(seq_num > encoder.part_count());
+        assert_eq!(decoder.ur_type(), Some("bytes"));
+        assert_eq!(decoder.message(), Some(&message[..]));
+        assert_eq!(decoder.estimated_progress(), 1.0);
+    }
+
+    #[test]
+    fn decoder_rejects_inconsistent_parts() {
+        let mut encoder_a = Encoder::new("bytes", &make_message(100, b"Wolf"), 20).unwrap();
+        let mut encoder_b = Encoder::new("bytes", &make_message(100, b"Fox"), 20).unwrap();
+        let mut encoder_c = Encoder::new("other", &make_message(100, b"Wolf"), 20).unwrap();
+
+        let mut decoder = Decoder::new();
+        decoder.receive(&encoder_a.next_part()).unwrap();
+        assert_eq!(
+            decoder.receive(&encoder_b.next_part()),
+            Err(Error::InconsistentPart)
+        );
+        assert_eq!(
+            decoder.receive(&encoder_c.next_part()),
+            Err(Error::InconsistentPart)
+        );
+        assert!(!decoder.is_complete());
+    }
+}
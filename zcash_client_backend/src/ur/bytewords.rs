@@ -0,0 +1,95 @@
+//! The minimal Bytewords encoding, as used by Uniform Resources.
+//!
+//! Each byte is represented by the first and last letters of the corresponding word, and the
+//! encoded data is followed by its CRC-32 checksum.
+
+use super::{crc32, Error};
+
+const WORDS: [&str; 256] = [
+    "able", "acid", "also", "apex", "aqua", "arch", "atom", "aunt", "away", "axis", "back", "bald",
+    "barn", "belt", "beta", "bias", "blue", "body", "brag", "brew", "bulb", "buzz", "calm", "cash",
+    "cats", "chef", "city", "claw", "code", "cola", "cook", "cost", "crux", "curl", "cusp", "cyan",
+    "dark", "data", "days", "deli", "dice", "diet", "door", "down", "draw", "drop", "drum", "dull",
+    "duty", "each", "easy", "echo", "edge", "epic", "even", "exam", "exit", "eyes", "fact", "fair",
+    "fern", "figs", "film", "fish", "fizz", "flap", "flew", "flux", "foxy", "free", "frog", "fuel",
+    "fund", "gala", "game", "gear", "gems", "gift", "girl", "glow", "good", "gray", "grim", "guru",
+    "gush", "gyro", "half", "hang", "hard", "hawk", "heat", "help", "high", "hill", "holy", "hope",
+    "horn", "huts", "iced", "idea", "idle", "inch", "inky", "into", "iris", "iron", "item", "jade",
+    "jazz", "join", "jolt", "jowl", "judo", "jugs", "jump", "junk", "jury", "keep", "keno", "kept",
+    "keys", "kick", "kiln", "king", "kite", "kiwi", "knob", "lamb", "lava", "lazy", "leaf", "legs",
+    "liar", "limp", "lion", "list", "logo", "loud", "love", "luau", "luck", "lung", "main", "many",
+    "math", "maze", "memo", "menu", "meow", "mild", "mint", "miss", "monk", "nail", "navy", "need",
+    "news", "next", "noon", "note", "numb", "obey", "oboe", "omit", "onyx", "open", "oval", "owls",
+    "paid", "part", "peck", "play", "plus", "poem", "pool", "pose", "puff", "puma", "purr", "quad",
+    "quiz", "race", "ramp", "real", "redo", "rich", "road", "rock", "roof", "ruby", "ruin", "runs",
+    "rust", "safe", "saga", "scar", "sets", "silk", "skew", "slot", "soap", "solo", "song", "stub",
+    "surf", "swan", "taco", "task", "taxi", "tent", "tied", "time", "tiny", "toil", "tomb", "toys",
+    "trip", "tuna", "twin", "ugly", "undo", "unit", "urge", "user", "vast", "very", "veto", "vial",
+    "vibe", "view", "visa", "void", "vows", "wall", "wand", "warm", "wasp", "wave", "waxy", "webs",
+    "what", "when", "whiz", "wolf", "work", "yank", "yawn", "yell", "yoga", "yurt", "zaps", "zero",
+    "zest", "zinc", "zone", "zoom",
+];
+
+/// Encodes the given data, followed by its checksum.
+pub(super) fn encode(data: &[u8]) -> String {
+    data.iter()
+        .chain(crc32(data).to_be_bytes().iter())
+        .flat_map(|b| {
+            let word = WORDS[usize::from(*b)].as_bytes();
+            [char::from(word[0]), char::from(word[3])]
+        })
+        .collect()
+}
+
+/// Decodes the given lowercase string, verifying and removing the trailing checksum.
+pub(super) fn decode(s: &str) -> Result<Vec<u8>, Error> {
+    let s = s.as_bytes();
+    if s.len() % 2 != 0 || s.len() < 10 {
+        return Err(Error::InvalidBytewords);
+    }
+
+    let mut data = s
+        .chunks_exact(2)
+        .map(|pair| {
+            WORDS
+                .iter()
+                .position(|word| {
+                    let word = word.as_bytes();
+                    word[0] == pair[0] && word[3] == pair[1]
+                })
+                .map(|index| index as u8)
+                .ok_or(Error::InvalidBytewords)
+        })
+        .collect::<Result<Vec<u8>, _>>()?;
+
+    let checksum = data.split_off(data.len() - 4);
+    if crc32(&data).to_be_bytes()[..] == checksum[..] {
+        Ok(data)
+    } else {
+        Err(Error::InvalidChecksum)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, encode};
+    use crate::ur::Error;
+
+    #[test]
+    fn test_vector() {
+        // Test vector from BCR-2020-012.
+        let data = [0, 1, 2, 128, 255];
+        assert_eq!(encode(&data), "aeadaolazmjendeoti");
+        assert_eq!(decode("aeadaolazmjendeoti"), Ok(data.to_vec()));
+    }
+
+    #[test]
+    fn invalid_encodings() {
+        // Odd length, too short to contain a checksum, and an unknown word respectively.
+        assert_eq!(decode("aeadaolazmjendeot"), Err(Error::InvalidBytewords));
+        assert_eq!(decode("aeadao"), Err(Error::InvalidBytewords));
+        assert_eq!(decode("aeadaolazmjendeoxx"), Err(Error::InvalidBytewords));
+
+        assert_eq!(decode("aeadaolazmjendeotd"), Err(Error::InvalidChecksum));
+    }
+}
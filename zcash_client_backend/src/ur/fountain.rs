@@ -0,0 +1,521 @@
+//! The fountain code used to split a message into the parts of a multi-part UR.
+//!
+//! The message is divided into `seq_len` equally sized fragments. Part `n` for `n <= seq_len`
+//! contains fragment `n - 1`; every subsequent part contains the XOR of a pseudorandom subset
+//! of the fragments, selected deterministically from the part's sequence number and the
+//! message checksum so that the receiver can determine which fragments it combines.
+
+use std::collections::{BTreeSet, HashMap};
+
+use sha2::{Digest, Sha256};
+
+use super::{crc32, Error, MIN_FRAGMENT_LEN};
+
+/// The Xoshiro256** pseudorandom number generator.
+struct Xoshiro256 {
+    s: [u64; 4],
+}
+
+impl Xoshiro256 {
+    /// Constructs a generator whose state is the SHA-256 digest of the given seed.
+    fn from_seed(seed: &[u8]) -> Self {
+        let digest = Sha256::digest(seed);
+        let mut s = [0u64; 4];
+        for (word, chunk) in s.iter_mut().zip(digest.chunks_exact(8)) {
+            *word = u64::from_be_bytes(chunk.try_into().unwrap());
+        }
+        Xoshiro256 { s }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let result = self.s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = self.s[1] << 17;
+
+        self.s[2] ^= self.s[0];
+        self.s[3] ^= self.s[1];
+        self.s[1] ^= self.s[2];
+        self.s[0] ^= self.s[3];
+        self.s[2] ^= t;
+        self.s[3] = self.s[3].rotate_left(45);
+
+        result
+    }
+
+    /// Returns a value uniformly distributed in `[0, 1)`.
+    fn next_f64(&mut self) -> f64 {
+        self.next_u64() as f64 / (u64::MAX as f64 + 1.0)
+    }
+
+    /// Returns a value uniformly distributed in `[low, high]`.
+    fn next_in_range(&mut self, low: usize, high: usize) -> usize {
+        (self.next_f64() * (high - low + 1) as f64) as usize + low
+    }
+}
+
+/// Samples indices from a discrete distribution, using Vose's alias method.
+struct WeightedSampler {
+    probs: Vec<f64>,
+    aliases: Vec<usize>,
+}
+
+impl WeightedSampler {
+    fn new(weights: &[f64]) -> Self {
+        let n = weights.len();
+        let sum: f64 = weights.iter().sum();
+        let mut scaled: Vec<f64> = weights.iter().map(|w| w * n as f64 / sum).collect();
+
+        let mut small = vec![];
+        let mut large = vec![];
+        for (i, p) in scaled.iter().enumerate().rev() {
+            if *p < 1.0 {
+                small.push(i);
+            } else {
+                large.push(i);
+            }
+        }
+
+        let mut probs = vec![0.0; n];
+        let mut aliases = vec![0; n];
+        while let (Some(a), Some(g)) = (small.last().copied(), large.last().copied()) {
+            small.pop();
+            large.pop();
+            probs[a] = scaled[a];
+            aliases[a] = g;
+            scaled[g] += scaled[a] - 1.0;
+            if scaled[g] < 1.0 {
+                small.push(g);
+            } else {
+                large.push(g);
+            }
+        }
+        for i in large.into_iter().chain(small) {
+            probs[i] = 1.0;
+        }
+
+        WeightedSampler { probs, aliases }
+    }
+
+    fn next(&self, rng: &mut Xoshiro256) -> usize {
+        let r1 = rng.next_f64();
+        let r2 = rng.next_f64();
+        let i = (self.probs.len() as f64 * r1) as usize;
+        if r2 < self.probs[i] {
+            i
+        } else {
+            self.aliases[i]
+        }
+    }
+}
+
+/// Returns the indices of the fragments that are combined in the part with the given sequence
+/// number.
+fn choose_fragments(seq_num: u32, seq_len: usize, checksum: u32) -> BTreeSet<usize> {
+    if seq_num as usize <= seq_len {
+        return [seq_num as usize - 1].into_iter().collect();
+    }
+
+    let mut seed = [0u8; 8];
+    seed[..4].copy_from_slice(&seq_num.to_be_bytes());
+    seed[4..].copy_from_slice(&checksum.to_be_bytes());
+    let mut rng = Xoshiro256::from_seed(&seed);
+
+    // The degree of the part is chosen with probability inversely proportional to its value.
+    let weights: Vec<f64> = (1..=seq_len).map(|i| 1.0 / i as f64).collect();
+    let degree = WeightedSampler::new(&weights).next(&mut rng) + 1;
+
+    let mut remaining: Vec<usize> = (0..seq_len).collect();
+    let mut chosen = BTreeSet::new();
+    while chosen.len() < degree {
+        let index = rng.next_in_range(0, remaining.len() - 1);
+        chosen.insert(remaining.remove(index));
+    }
+    chosen
+}
+
+/// Returns the length of the fragments into which a message of the given length is divided,
+/// using as few fragments as possible while respecting the maximum fragment length.
+fn fragment_len(message_len: usize, max_fragment_len: usize) -> usize {
+    let max_fragment_count = std::cmp::max(message_len / MIN_FRAGMENT_LEN, 1);
+    let div_ceil = |count: usize| (message_len + count - 1) / count;
+    (1..=max_fragment_count)
+        .map(div_ceil)
+        .find(|len| *len <= max_fragment_len)
+        .unwrap_or_else(|| div_ceil(max_fragment_count))
+}
+
+fn xor_into(target: &mut [u8], source: &[u8]) {
+    for (t, s) in target.iter_mut().zip(source) {
+        *t ^= s;
+    }
+}
+
+/// Appends the head of a CBOR data item with the given major type and argument.
+fn write_cbor_head(buf: &mut Vec<u8>, major: u8, value: u64) {
+    let major = major << 5;
+    if value < 24 {
+        buf.push(major | value as u8);
+    } else if value <= u64::from(u8::MAX) {
+        buf.push(major | 24);
+        buf.push(value as u8);
+    } else if value <= u64::from(u16::MAX) {
+        buf.push(major | 25);
+        buf.extend_from_slice(&(value as u16).to_be_bytes());
+    } else if value <= u64::from(u32::MAX) {
+        buf.push(major | 26);
+        buf.extend_from_slice(&(value as u32).to_be_bytes());
+    } else {
+        buf.push(major | 27);
+        buf.extend_from_slice(&value.to_be_bytes());
+    }
+}
+
+/// Reads the head of a CBOR data item with the given major type, returning its argument.
+fn read_cbor_head(data: &mut &[u8], major: u8) -> Result<u64, Error> {
+    let (initial, rest) = data.split_first().ok_or(Error::InvalidPart)?;
+    if initial >> 5 != major {
+        return Err(Error::InvalidPart);
+    }
+
+    let len = match initial & 0x1f {
+        v @ 0..=23 => {
+            *data = rest;
+            return Ok(u64::from(v));
+        }
+        24 => 1,
+        25 => 2,
+        26 => 4,
+        27 => 8,
+        _ => return Err(Error::InvalidPart),
+    };
+    if rest.len() < len {
+        return Err(Error::InvalidPart);
+    }
+
+    let (arg, rest) = rest.split_at(len);
+    *data = rest;
+    Ok(arg.iter().fold(0u64, |acc, b| (acc << 8) | u64::from(*b)))
+}
+
+/// A part of a multi-part UR.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Part {
+    seq_num: u32,
+    seq_len: usize,
+    message_len: usize,
+    checksum: u32,
+    data: Vec<u8>,
+}
+
+impl Part {
+    pub(super) fn seq_num(&self) -> u32 {
+        self.seq_num
+    }
+
+    pub(super) fn seq_len(&self) -> usize {
+        self.seq_len
+    }
+
+    /// Returns the CBOR encoding of this part, which is the array
+    /// `[seq_num, seq_len, message_len, checksum, data]`.
+    pub(super) fn to_cbor(&self) -> Vec<u8> {
+        let mut buf = vec![];
+        write_cbor_head(&mut buf, 4, 5);
+        write_cbor_head(&mut buf, 0, self.seq_num.into());
+        write_cbor_head(&mut buf, 0, self.seq_len as u64);
+        write_cbor_head(&mut buf, 0, self.message_len as u64);
+        write_cbor_head(&mut buf, 0, self.checksum.into());
+        write_cbor_head(&mut buf, 2, self.data.len() as u64);
+        buf.extend_from_slice(&self.data);
+        buf
+    }
+
+    pub(super) fn from_cbor(mut data: &[u8]) -> Result<Self, Error> {
+        let data = &mut data;
+        if read_cbor_head(data, 4)? != 5 {
+            return Err(Error::InvalidPart);
+        }
+
+        let mut read_uint = |max: u64| {
+            read_cbor_head(data, 0).and_then(|v| {
+                if v <= max {
+                    Ok(v)
+                } else {
+                    Err(Error::InvalidPart)
+                }
+            })
+        };
+        let seq_num = read_uint(u32::MAX.into())? as u32;
+        let seq_len = read_uint(u32::MAX.into())? as usize;
+        let message_len = read_uint(u32::MAX.into())? as usize;
+        let checksum = read_uint(u32::MAX.into())? as u32;
+
+        let fragment_len = read_cbor_head(data, 2)?;
+        if fragment_len != data.len() as u64 {
+            return Err(Error::InvalidPart);
+        }
+
+        // A part must describe a non-empty message that its fragments are large enough to
+        // contain.
+        if seq_num == 0
+            || seq_len == 0
+            || message_len == 0
+            || data.is_empty()
+            || data
+                .len()
+                .checked_mul(seq_len)
+                .map_or(true, |l| l < message_len)
+        {
+            return Err(Error::InvalidPart);
+        }
+
+        Ok(Part {
+            seq_num,
+            seq_len,
+            message_len,
+            checksum,
+            data: data.to_vec(),
+        })
+    }
+}
+
+/// Produces an unbounded sequence of parts from a message.
+pub(super) struct FountainEncoder {
+    fragments: Vec<Vec<u8>>,
+    message_len: usize,
+    checksum: u32,
+    seq_num: u32,
+}
+
+impl FountainEncoder {
+    pub(super) fn new(message: &[u8], max_fragment_len: usize) -> Result<Self, Error> {
+        if message.is_empty() {
+            return Err(Error::EmptyMessage);
+        }
+        if max_fragment_len < MIN_FRAGMENT_LEN {
+            return Err(Error::FragmentTooShort(max_fragment_len));
+        }
+
+        let fragment_len = fragment_len(message.len(), max_fragment_len);
+        let fragments = message
+            .chunks(fragment_len)
+            .map(|chunk| {
+                let mut fragment = chunk.to_vec();
+                fragment.resize(fragment_len, 0);
+                fragment
+            })
+            .collect();
+
+        Ok(FountainEncoder {
+            fragments,
+            message_len: message.len(),
+            checksum: crc32(message),
+            seq_num: 0,
+        })
+    }
+
+    pub(super) fn seq_len(&self) -> usize {
+        self.fragments.len()
+    }
+
+    pub(super) fn next_part(&mut self) -> Part {
+        self.seq_num += 1;
+
+        let mut data = vec![0; self.fragments[0].len()];
+        for index in choose_fragments(self.seq_num, self.seq_len(), self.checksum) {
+            xor_into(&mut data, &self.fragments[index]);
+        }
+
+        Part {
+            seq_num: self.seq_num,
+            seq_len: self.seq_len(),
+            message_len: self.message_len,
+            checksum: self.checksum,
+            data,
+        }
+    }
+}
+
+/// Reassembles a message from parts received in any order.
+///
+/// Fragments recovered directly, or by eliminating all but one fragment from a combined part,
+/// are used to reduce the other combined parts that include them; a combined part is also used
+/// to reduce any combined part whose fragments are a superset of its own.
+#[derive(Default)]
+pub(super) struct FountainDecoder {
+    expected: Option<(usize, usize, u32, usize)>,
+    received: BTreeSet<u32>,
+    simple: HashMap<usize, Vec<u8>>,
+    mixed: HashMap<BTreeSet<usize>, Vec<u8>>,
+    message: Option<Vec<u8>>,
+}
+
+impl FountainDecoder {
+    pub(super) fn has_received_parts(&self) -> bool {
+        !self.received.is_empty()
+    }
+
+    pub(super) fn message(&self) -> Option<&[u8]> {
+        self.message.as_deref()
+    }
+
+    pub(super) fn estimated_progress(&self) -> f64 {
+        match (&self.message, self.expected) {
+            (Some(_), _) => 1.0,
+            (None, Some((seq_len, _, _, _))) => {
+                // On average, slightly more parts than fragments are needed to decode.
+                (self.received.len() as f64 / (seq_len as f64 * 1.75)).min(0.99)
+            }
+            (None, None) => 0.0,
+        }
+    }
+
+    pub(super) fn receive(&mut self, part: Part) -> Result<(), Error> {
+        if self.message.is_some() {
+            return Ok(());
+        }
+
+        let params = (
+            part.seq_len,
+            part.message_len,
+            part.checksum,
+            part.data.len(),
+        );
+        match self.expected {
+            Some(expected) if expected != params => return Err(Error::InconsistentPart),
+            _ => self.expected = Some(params),
+        }
+        if !self.received.insert(part.seq_num) {
+            return Ok(());
+        }
+
+        let mut queue = vec![(
+            choose_fragments(part.seq_num, part.seq_len, part.checksum),
+            part.data,
+        )];
+        while let Some((mut indices, mut data)) = queue.pop() {
+            // Eliminate the fragments that are already known.
+            indices.retain(|i| match self.simple.get(i) {
+                Some(fragment) => {
+                    xor_into(&mut data, fragment);
+                    false
+                }
+                None => true,
+            });
+            for (mixed_indices, mixed_data) in &self.mixed {
+                if mixed_indices.len() < indices.len() && mixed_indices.is_subset(&indices) {
+                    xor_into(&mut data, mixed_data);
+                    indices = &indices - mixed_indices;
+                }
+            }
+
+            if indices.is_empty() || self.mixed.contains_key(&indices) {
+                continue;
+            }
+
+            // Reduce the combined parts that include all of this part's fragments.
+            let supersets: Vec<_> = self
+                .mixed
+                .keys()
+                .filter(|mixed_indices| indices.is_subset(mixed_indices))
+                .cloned()
+                .collect();
+            for mixed_indices in supersets {
+                let mut mixed_data = self.mixed.remove(&mixed_indices).unwrap();
+                xor_into(&mut mixed_data, &data);
+                queue.push((&mixed_indices - &indices, mixed_data));
+            }
+
+            if indices.len() == 1 {
+                let index = *indices.iter().next().unwrap();
+                self.simple.insert(index, data);
+            } else {
+                self.mixed.insert(indices, data);
+            }
+        }
+
+        if self.simple.len() == part.seq_len {
+            let mut message: Vec<u8> = (0..part.seq_len)
+                .flat_map(|i| self.simple[&i].iter().copied())
+                .collect();
+            message.truncate(part.message_len);
+            if crc32(&message) != part.checksum {
+                return Err(Error::InvalidChecksum);
+            }
+            self.message = Some(message);
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns a pseudorandom message of the given length, as used by the test vectors of
+/// BCR-2020-005.
+#[cfg(test)]
+pub(super) fn make_message(len: usize, seed: &[u8]) -> Vec<u8> {
+    let mut rng = Xoshiro256::from_seed(seed);
+    (0..len).map(|_| rng.next_in_range(0, 255) as u8).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{choose_fragments, make_message, FountainEncoder, Part, Xoshiro256};
+
+    #[test]
+    fn rng_test_vector() {
+        let mut rng = Xoshiro256::from_seed(b"Wolf");
+        let values: Vec<u64> = (0..10).map(|_| rng.next_u64() % 100).collect();
+        assert_eq!(values, [42, 81, 85, 8, 82, 84, 76, 73, 70, 88]);
+    }
+
+    #[test]
+    fn choose_fragments_test_vector() {
+        let message = make_message(1024, b"Wolf");
+        let checksum = super::crc32(&message);
+        let seq_len = FountainEncoder::new(&message, 100).unwrap().seq_len();
+        assert_eq!(seq_len, 11);
+
+        let chosen: Vec<Vec<usize>> = (1..=14)
+            .map(|seq_num| {
+                choose_fragments(seq_num, seq_len, checksum)
+                    .into_iter()
+                    .collect()
+            })
+            .collect();
+        let expected: Vec<Vec<usize>> = vec![
+            vec![0],
+            vec![1],
+            vec![2],
+            vec![3],
+            vec![4],
+            vec![5],
+            vec![6],
+            vec![7],
+            vec![8],
+            vec![9],
+            vec![10],
+            vec![9],
+            vec![2, 5, 6, 8, 9, 10],
+            vec![8],
+        ];
+        assert_eq!(chosen, expected);
+    }
+
+    #[test]
+    fn part_cbor_round_trip() {
+        let part = Part {
+            seq_num: 12,
+            seq_len: 8,
+            message_len: 100,
+            checksum: 0x12345678,
+            data: make_message(13, b"Wolf"),
+        };
+        assert_eq!(Part::from_cbor(&part.to_cbor()), Ok(part.clone()));
+
+        // Trailing or missing data is rejected.
+        let mut encoded = part.to_cbor();
+        encoded.push(0);
+        assert!(Part::from_cbor(&encoded).is_err());
+        encoded.truncate(encoded.len() - 2);
+        assert!(Part::from_cbor(&encoded).is_err());
+    }
+}
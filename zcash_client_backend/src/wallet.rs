@@ -1,7 +1,11 @@
 //! Structs representing transaction data scanned from the block chain by a wallet or
 //! light client.
 
+use std::io::{self, Read, Write};
+
+use group::ff::PrimeField;
 use incrementalmerkletree::Position;
+use sapling::Rseed;
 use zcash_address::ZcashAddress;
 use zcash_note_encryption::EphemeralKeyBytes;
 use zcash_primitives::{
@@ -19,7 +23,9 @@ use zcash_primitives::{
 };
 use zcash_protocol::value::BalanceError;
 
-use crate::{fees::sapling as sapling_fees, PoolType, ShieldedProtocol};
+use crate::{
+    fees::sapling as sapling_fees, keys::UnifiedFullViewingKey, PoolType, ShieldedProtocol,
+};
 
 #[cfg(feature = "orchard")]
 use crate::fees::orchard as orchard_fees;
@@ -110,7 +116,7 @@ impl<AccountId, N> Recipient<AccountId, Option<N>> {
     }
 }
 
-/// The shielded subset of a [`Transaction`]'s data that is relevant to a particular wallet.
+/// The subset of a [`Transaction`]'s data that is relevant to a particular wallet.
 ///
 /// [`Transaction`]: zcash_primitives::transaction::Transaction
 pub struct WalletTx<AccountId> {
@@ -122,6 +128,8 @@ pub struct WalletTx<AccountId> {
     orchard_spends: Vec<WalletOrchardSpend<AccountId>>,
     #[cfg(feature = "orchard")]
     orchard_outputs: Vec<WalletOrchardOutput<AccountId>>,
+    #[cfg(feature = "transparent-inputs")]
+    transparent_spends: Vec<WalletTransparentSpend<AccountId>>,
 }
 
 impl<AccountId> WalletTx<AccountId> {
@@ -135,6 +143,9 @@ impl<AccountId> WalletTx<AccountId> {
             WalletSpend<orchard::note::Nullifier, AccountId>,
         >,
         #[cfg(feature = "orchard")] orchard_outputs: Vec<WalletOrchardOutput<AccountId>>,
+        #[cfg(feature = "transparent-inputs")] transparent_spends: Vec<
+            WalletTransparentSpend<AccountId>,
+        >,
     ) -> Self {
         Self {
             txid,
@@ -145,6 +156,8 @@ impl<AccountId> WalletTx<AccountId> {
             orchard_spends,
             #[cfg(feature = "orchard")]
             orchard_outputs,
+            #[cfg(feature = "transparent-inputs")]
+            transparent_spends,
         }
     }
 
@@ -185,14 +198,32 @@ impl<AccountId> WalletTx<AccountId> {
     pub fn orchard_outputs(&self) -> &[WalletOrchardOutput<AccountId>] {
         self.orchard_outputs.as_ref()
     }
+
+    /// Returns a record for each transparent output belonging to the wallet that was spent
+    /// in the transaction.
+    ///
+    /// These are only detected if the compact block source provides the transparent inputs
+    /// of each transaction.
+    #[cfg(feature = "transparent-inputs")]
+    pub fn transparent_spends(&self) -> &[WalletTransparentSpend<AccountId>] {
+        self.transparent_spends.as_ref()
+    }
 }
 
+/// The number of blocks that must have been mined after the block containing a coinbase
+/// transaction before its transparent outputs may be spent.
+///
+/// A transaction that spends a coinbase output must be mined at a height of at least the
+/// coinbase transaction's height plus this value.
+pub const COINBASE_MATURITY: u32 = 100;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct WalletTransparentOutput {
     outpoint: OutPoint,
     txout: TxOut,
     height: BlockHeight,
     recipient_address: TransparentAddress,
+    is_coinbase: bool,
 }
 
 impl WalletTransparentOutput {
@@ -208,9 +239,16 @@ impl WalletTransparentOutput {
                 txout,
                 height,
                 recipient_address,
+                is_coinbase: false,
             })
     }
 
+    /// Sets whether this output was created by a coinbase transaction.
+    pub fn with_coinbase(mut self, is_coinbase: bool) -> Self {
+        self.is_coinbase = is_coinbase;
+        self
+    }
+
     pub fn outpoint(&self) -> &OutPoint {
         &self.outpoint
     }
@@ -230,6 +268,22 @@ impl WalletTransparentOutput {
     pub fn value(&self) -> NonNegativeAmount {
         self.txout.value
     }
+
+    /// Returns whether this output was created by a coinbase transaction.
+    ///
+    /// Coinbase outputs may only be spent once they have matured (see [`COINBASE_MATURITY`]),
+    /// and only by transactions that have no transparent outputs.
+    pub fn is_coinbase(&self) -> bool {
+        self.is_coinbase
+    }
+
+    /// Returns whether this output may be spent by a transaction that will be mined at
+    /// `target_height`.
+    ///
+    /// This is always true for outputs that were not created by coinbase transactions.
+    pub fn is_mature(&self, target_height: BlockHeight) -> bool {
+        !self.is_coinbase || self.height + COINBASE_MATURITY <= target_height
+    }
 }
 
 impl transparent_fees::InputView for WalletTransparentOutput {
@@ -241,7 +295,8 @@ impl transparent_fees::InputView for WalletTransparentOutput {
     }
 }
 
-/// A reference to a spent note belonging to the wallet within a transaction.
+/// A reference to a spent note or transparent output belonging to the wallet within a
+/// transaction.
 pub struct WalletSpend<Nf, AccountId> {
     index: usize,
     nf: Nf,
@@ -258,12 +313,13 @@ impl<Nf, AccountId> WalletSpend<Nf, AccountId> {
         }
     }
 
-    /// Returns the index of the Sapling spend or Orchard action within the transaction that
-    /// created this spend.
+    /// Returns the index of the Sapling spend, Orchard action, or transparent input within
+    /// the transaction that created this spend.
     pub fn index(&self) -> usize {
         self.index
     }
-    /// Returns the nullifier of the spent note.
+    /// Returns the nullifier of the spent note, or the outpoint of the spent transparent
+    /// output.
     pub fn nf(&self) -> &Nf {
         &self.nf
     }
@@ -280,6 +336,10 @@ pub type WalletSaplingSpend<AccountId> = WalletSpend<sapling::Nullifier, Account
 #[cfg(feature = "orchard")]
 pub type WalletOrchardSpend<AccountId> = WalletSpend<orchard::note::Nullifier, AccountId>;
 
+/// A type alias for [`WalletSpend`]s of transparent outputs, identified by their outpoints.
+#[cfg(feature = "transparent-inputs")]
+pub type WalletTransparentSpend<AccountId> = WalletSpend<OutPoint, AccountId>;
+
 /// An output that was successfully decrypted in the process of wallet scanning.
 pub struct WalletOutput<Note, Nullifier, AccountId> {
     index: usize,
@@ -395,6 +455,213 @@ impl Note {
             Note::Orchard(_) => ShieldedProtocol::Orchard,
         }
     }
+
+    /// Returns the raw encoding of the address to which this note was sent.
+    pub fn recipient_bytes(&self) -> [u8; 43] {
+        match self {
+            Note::Sapling(n) => n.recipient().to_bytes(),
+            #[cfg(feature = "orchard")]
+            Note::Orchard(n) => n.recipient().to_raw_address_bytes(),
+        }
+    }
+
+    /// Returns the random seed (`rseed`) from which the note's commitment trapdoor is derived.
+    ///
+    /// Returns `None` for Sapling notes created before the activation of [ZIP 212], which
+    /// have a commitment trapdoor (see [`Self::rcm`]) but no random seed.
+    ///
+    /// [ZIP 212]: https://zips.z.cash/zip-0212
+    pub fn rseed(&self) -> Option<[u8; 32]> {
+        match self {
+            Note::Sapling(n) => match n.rseed() {
+                Rseed::BeforeZip212(_) => None,
+                Rseed::AfterZip212(rseed) => Some(*rseed),
+            },
+            #[cfg(feature = "orchard")]
+            Note::Orchard(n) => Some(*n.rseed().as_bytes()),
+        }
+    }
+
+    /// Returns the commitment trapdoor (`rcm`) of a Sapling note.
+    ///
+    /// Returns `None` for Orchard notes, whose commitment trapdoor is derived from
+    /// [`Self::rseed`] and [`Self::rho`] and is not exposed by the `orchard` crate.
+    pub fn rcm(&self) -> Option<[u8; 32]> {
+        match self {
+            Note::Sapling(n) => Some(n.rcm().to_repr()),
+            #[cfg(feature = "orchard")]
+            Note::Orchard(_) => None,
+        }
+    }
+
+    /// Returns the `rho` value of an Orchard note, which is the nullifier of the note that
+    /// was spent by the action that created it. Returns `None` for Sapling notes.
+    pub fn rho(&self) -> Option<[u8; 32]> {
+        match self {
+            Note::Sapling(_) => None,
+            #[cfg(feature = "orchard")]
+            Note::Orchard(n) => Some(n.rho().to_bytes()),
+        }
+    }
+
+    /// Returns the commitment to this note, in the form in which it appears in the outputs of
+    /// a transaction (`cmu` for Sapling, or `cmx` for Orchard).
+    pub fn commitment(&self) -> [u8; 32] {
+        match self {
+            Note::Sapling(n) => n.cmu().to_bytes(),
+            #[cfg(feature = "orchard")]
+            Note::Orchard(n) => {
+                orchard::note::ExtractedNoteCommitment::from(n.commitment()).to_bytes()
+            }
+        }
+    }
+
+    /// Computes the nullifier that will be revealed when this note is spent.
+    ///
+    /// `ufvk` must be the viewing key that received the note, and `scope` the scope of the
+    /// viewing key for which the note was decrypted. Sapling nullifiers also depend upon the
+    /// position of the note in the note commitment tree; `position` is ignored for Orchard
+    /// notes. Returns `None` if `ufvk` has no viewing key for the note's protocol.
+    pub fn nullifier(
+        &self,
+        ufvk: &UnifiedFullViewingKey,
+        scope: Scope,
+        position: Position,
+    ) -> Option<[u8; 32]> {
+        match self {
+            Note::Sapling(n) => ufvk
+                .sapling()
+                .map(|dfvk| n.nf(&dfvk.to_nk(scope), u64::from(position)).0),
+            #[cfg(feature = "orchard")]
+            Note::Orchard(n) => ufvk.orchard().map(|fvk| n.nullifier(fvk).to_bytes()),
+        }
+    }
+
+    /// Writes the note to the given writer.
+    ///
+    /// The encoding consists of a byte identifying the note's protocol (`0` for Sapling or
+    /// `1` for Orchard), followed by the raw encoding of the recipient address, the note's
+    /// value as a little-endian `u64`, and the data from which its commitment trapdoor is
+    /// derived. For Sapling notes, this is a byte that is `0` if the note predates ZIP 212
+    /// (followed by `rcm`) or `1` otherwise (followed by `rseed`); for Orchard notes, it is
+    /// `rho` followed by `rseed`.
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(&[match self.protocol() {
+            ShieldedProtocol::Sapling => NOTE_SAPLING_TAG,
+            ShieldedProtocol::Orchard => NOTE_ORCHARD_TAG,
+        }])?;
+        self.write_parts(writer)
+    }
+
+    /// Reads a note that was written with [`Self::write`].
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        let protocol = match read_array::<_, 1>(&mut reader)? {
+            [NOTE_SAPLING_TAG] => ShieldedProtocol::Sapling,
+            [NOTE_ORCHARD_TAG] => ShieldedProtocol::Orchard,
+            _ => return Err(invalid_data("Invalid shielded protocol.")),
+        };
+        Self::read_parts(protocol, reader)
+    }
+
+    /// Writes the note without a leading protocol identifier.
+    pub(crate) fn write_parts<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(&self.recipient_bytes())?;
+        match self {
+            Note::Sapling(n) => {
+                writer.write_all(&n.value().inner().to_le_bytes())?;
+                match n.rseed() {
+                    Rseed::BeforeZip212(rcm) => {
+                        writer.write_all(&[RSEED_BEFORE_ZIP_212_TAG])?;
+                        writer.write_all(&rcm.to_repr())
+                    }
+                    Rseed::AfterZip212(rseed) => {
+                        writer.write_all(&[RSEED_AFTER_ZIP_212_TAG])?;
+                        writer.write_all(rseed)
+                    }
+                }
+            }
+            #[cfg(feature = "orchard")]
+            Note::Orchard(n) => {
+                writer.write_all(&n.value().inner().to_le_bytes())?;
+                writer.write_all(&n.rho().to_bytes())?;
+                writer.write_all(n.rseed().as_bytes())
+            }
+        }
+    }
+
+    /// Reads a note of the given protocol that was written with [`Self::write_parts`].
+    pub(crate) fn read_parts<R: Read>(
+        protocol: ShieldedProtocol,
+        mut reader: R,
+    ) -> io::Result<Self> {
+        match protocol {
+            ShieldedProtocol::Sapling => {
+                let recipient = sapling::PaymentAddress::from_bytes(&read_array(&mut reader)?)
+                    .ok_or_else(|| invalid_data("Invalid Sapling recipient."))?;
+                let value = u64::from_le_bytes(read_array(&mut reader)?);
+                let rseed = match read_array::<_, 1>(&mut reader)? {
+                    [RSEED_BEFORE_ZIP_212_TAG] => {
+                        let rcm = Option::from(jubjub::Fr::from_repr(read_array(&mut reader)?))
+                            .ok_or_else(|| {
+                                invalid_data("Invalid Sapling note commitment trapdoor.")
+                            })?;
+                        Rseed::BeforeZip212(rcm)
+                    }
+                    [RSEED_AFTER_ZIP_212_TAG] => Rseed::AfterZip212(read_array(&mut reader)?),
+                    _ => return Err(invalid_data("Invalid Sapling random seed type.")),
+                };
+
+                Ok(Note::Sapling(sapling::Note::from_parts(
+                    recipient,
+                    sapling::value::NoteValue::from_raw(value),
+                    rseed,
+                )))
+            }
+            #[cfg(feature = "orchard")]
+            ShieldedProtocol::Orchard => {
+                use orchard::note::{RandomSeed, Rho};
+
+                let recipient = Option::from(orchard::Address::from_raw_address_bytes(
+                    &read_array(&mut reader)?,
+                ))
+                .ok_or_else(|| invalid_data("Invalid Orchard recipient."))?;
+                let value = u64::from_le_bytes(read_array(&mut reader)?);
+                let rho = Option::from(Rho::from_bytes(&read_array(&mut reader)?))
+                    .ok_or_else(|| invalid_data("Invalid rho."))?;
+                let rseed = Option::from(RandomSeed::from_bytes(read_array(&mut reader)?, &rho))
+                    .ok_or_else(|| invalid_data("Invalid Orchard random seed."))?;
+
+                Option::from(orchard::Note::from_parts(
+                    recipient,
+                    orchard::value::NoteValue::from_raw(value),
+                    rho,
+                    rseed,
+                ))
+                .map(Note::Orchard)
+                .ok_or_else(|| invalid_data("Invalid Orchard note."))
+            }
+            #[cfg(not(feature = "orchard"))]
+            ShieldedProtocol::Orchard => Err(invalid_data(
+                "Orchard notes are not supported by this build.",
+            )),
+        }
+    }
+}
+
+const NOTE_SAPLING_TAG: u8 = 0;
+const NOTE_ORCHARD_TAG: u8 = 1;
+
+const RSEED_BEFORE_ZIP_212_TAG: u8 = 0;
+const RSEED_AFTER_ZIP_212_TAG: u8 = 1;
+
+fn read_array<R: Read, const N: usize>(mut reader: R) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
 }
 
 /// Information about a note that is tracked by the wallet that is available for spending,
@@ -603,3 +870,47 @@ impl TransparentAddressMetadata {
         self.address_index
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use incrementalmerkletree::Position;
+    use sapling::{value::NoteValue, Rseed};
+    use zcash_primitives::consensus::MAIN_NETWORK;
+    use zip32::Scope;
+
+    use super::Note;
+    use crate::keys::UnifiedSpendingKey;
+
+    #[test]
+    fn sapling_note_details() {
+        let usk =
+            UnifiedSpendingKey::from_seed(&MAIN_NETWORK, &[0; 32], zip32::AccountId::ZERO).unwrap();
+        let ufvk = usk.to_unified_full_viewing_key();
+        let dfvk = usk.sapling().to_diversifiable_full_viewing_key();
+        let (_, addr) = dfvk.default_address();
+
+        let sapling_note =
+            addr.create_note(NoteValue::from_raw(50000), Rseed::AfterZip212([7; 32]));
+        let note = Note::Sapling(sapling_note.clone());
+
+        assert_eq!(note.recipient_bytes(), addr.to_bytes());
+        assert_eq!(note.rseed(), Some([7; 32]));
+        assert!(note.rcm().is_some());
+        assert_eq!(note.rho(), None);
+        assert_eq!(note.commitment(), sapling_note.cmu().to_bytes());
+
+        let position = Position::from(3);
+        assert_eq!(
+            note.nullifier(&ufvk, Scope::External, position),
+            Some(sapling_note.nf(&dfvk.to_nk(Scope::External), 3).0)
+        );
+
+        let mut encoded = vec![];
+        note.write(&mut encoded).unwrap();
+        assert_eq!(Note::read(&encoded[..]).unwrap(), note);
+
+        // A note with an unknown protocol identifier is rejected.
+        encoded[0] = 2;
+        assert!(Note::read(&encoded[..]).is_err());
+    }
+}
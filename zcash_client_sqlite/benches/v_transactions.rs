@@ -0,0 +1,230 @@
+//! Benchmarks of queries against the `v_transactions` view in a wallet containing 100,000
+//! received Sapling notes.
+//!
+//! The fixture is generated from a fixed seed, so that every run of these benchmarks operates
+//! on the same dataset. Notes are inserted directly into the wallet's tables rather than by
+//! scanning, as only the query performance of the view is measured.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use rand_chacha::ChaChaRng;
+use rand_core::{RngCore, SeedableRng};
+use rusqlite::{named_params, Connection};
+use secrecy::SecretVec;
+use tempfile::NamedTempFile;
+use zcash_client_backend::data_api::{AccountBirthday, WalletWrite};
+use zcash_client_sqlite::{wallet::init::init_wallet_db, WalletDb};
+use zcash_primitives::{
+    block::BlockHash,
+    consensus::{BlockHeight, Network, NetworkUpgrade, Parameters},
+};
+
+#[cfg(unix)]
+use pprof::criterion::{Output, PProfProfiler};
+
+const BLOCK_COUNT: u32 = 1000;
+const TXS_PER_BLOCK: u32 = 50;
+const NOTES_PER_TX: u32 = 2;
+
+fn random_bytes(rng: &mut ChaChaRng, len: usize) -> Vec<u8> {
+    let mut bytes = vec![0; len];
+    rng.fill_bytes(&mut bytes);
+    bytes
+}
+
+/// Creates a wallet with a single account that has received `NOTES_PER_TX` notes in each of
+/// `BLOCK_COUNT * TXS_PER_BLOCK` transactions, each of which also spends the change note
+/// received in the preceding transaction.
+fn wallet_fixture() -> NamedTempFile {
+    let network = Network::TestNetwork;
+    let data_file = NamedTempFile::new().expect("can create a temporary file");
+    let mut db_data =
+        WalletDb::for_path(data_file.path(), network).expect("can open the wallet database");
+    init_wallet_db(&mut db_data, None).expect("can initialize the wallet database");
+    let birthday = AccountBirthday::from_sapling_activation(&network, BlockHash([0; 32]));
+    let (account_id, _) = db_data
+        .create_account(&SecretVec::new(vec![0; 32]), &birthday)
+        .expect("can create an account");
+
+    let start_height = network
+        .activation_height(NetworkUpgrade::Sapling)
+        .expect("Sapling is active on testnet");
+    let mut rng = ChaChaRng::from_seed([7; 32]);
+
+    let mut conn = Connection::open(data_file.path()).expect("can open the wallet database");
+    let tx = conn.transaction().expect("can begin a transaction");
+    {
+        let mut insert_block = tx
+            .prepare(
+                "INSERT INTO blocks (height, hash, time, sapling_tree)
+                VALUES (:height, :hash, :time, x'00')",
+            )
+            .unwrap();
+        let mut insert_tx = tx
+            .prepare(
+                "INSERT INTO transactions (id_tx, txid, block, tx_index, fee)
+                VALUES (:id_tx, :txid, :block, :tx_index, 10000)",
+            )
+            .unwrap();
+        let mut insert_note = tx
+            .prepare(
+                "INSERT INTO sapling_received_notes (
+                    id, tx, output_index, account_id, diversifier, value, rcm, nf, is_change,
+                    commitment_tree_position, recipient_key_scope
+                )
+                VALUES (
+                    :id, :tx, :output_index, :account_id, :diversifier, :value, :rcm, :nf,
+                    :is_change, :id, :is_change
+                )",
+            )
+            .unwrap();
+        let mut insert_spend = tx
+            .prepare(
+                "INSERT INTO sapling_received_note_spends (sapling_received_note_id, transaction_id)
+                VALUES (:note_id, :tx_id)",
+            )
+            .unwrap();
+
+        let mut tx_id = 0u32;
+        for block in 0..BLOCK_COUNT {
+            let height = u32::from(start_height + block);
+            insert_block
+                .execute(named_params![
+                    ":height": height,
+                    ":hash": random_bytes(&mut rng, 32),
+                    ":time": 1_600_000_000 + i64::from(block) * 75,
+                ])
+                .unwrap();
+
+            for tx_index in 0..TXS_PER_BLOCK {
+                tx_id += 1;
+                insert_tx
+                    .execute(named_params![
+                        ":id_tx": tx_id,
+                        ":txid": random_bytes(&mut rng, 32),
+                        ":block": height,
+                        ":tx_index": tx_index,
+                    ])
+                    .unwrap();
+
+                for output_index in 0..NOTES_PER_TX {
+                    let is_change = output_index + 1 == NOTES_PER_TX;
+                    insert_note
+                        .execute(named_params![
+                            ":id": (tx_id - 1) * NOTES_PER_TX + output_index + 1,
+                            ":tx": tx_id,
+                            ":output_index": output_index,
+                            ":account_id": account_id,
+                            ":diversifier": random_bytes(&mut rng, 11),
+                            ":value": 10000 + (rng.next_u32() % 100_000),
+                            ":rcm": random_bytes(&mut rng, 32),
+                            ":nf": random_bytes(&mut rng, 32),
+                            ":is_change": is_change,
+                        ])
+                        .unwrap();
+                }
+
+                if tx_id > 1 {
+                    insert_spend
+                        .execute(named_params![
+                            ":note_id": (tx_id - 1) * NOTES_PER_TX,
+                            ":tx_id": tx_id,
+                        ])
+                        .unwrap();
+                }
+            }
+        }
+    }
+    tx.commit().expect("can commit the fixture");
+
+    data_file
+}
+
+fn bench_v_transactions(c: &mut Criterion) {
+    let data_file = wallet_fixture();
+    let conn = Connection::open(data_file.path()).expect("can open the wallet database");
+    let last_height = u32::from(
+        Network::TestNetwork
+            .activation_height(NetworkUpgrade::Sapling)
+            .expect("Sapling is active on testnet")
+            + (BLOCK_COUNT - 1),
+    );
+    let some_txid: Vec<u8> = conn
+        .query_row(
+            "SELECT txid FROM transactions WHERE id_tx = :id_tx",
+            named_params![":id_tx": BLOCK_COUNT * TXS_PER_BLOCK / 2],
+            |row| row.get(0),
+        )
+        .expect("fixture contains the transaction");
+
+    let mut group = c.benchmark_group("v_transactions-100k-notes");
+    group.sample_size(10);
+
+    group.bench_function("all", |b| {
+        let mut stmt = conn
+            .prepare("SELECT txid, account_balance_delta FROM v_transactions")
+            .unwrap();
+        b.iter(|| {
+            stmt.query_map([], |row| row.get::<_, Vec<u8>>(0))
+                .unwrap()
+                .count()
+        })
+    });
+
+    group.bench_function("latest-page", |b| {
+        let mut stmt = conn
+            .prepare(
+                "SELECT txid, account_balance_delta FROM v_transactions
+                ORDER BY mined_height DESC, tx_index DESC
+                LIMIT 100",
+            )
+            .unwrap();
+        b.iter(|| {
+            stmt.query_map([], |row| row.get::<_, Vec<u8>>(0))
+                .unwrap()
+                .count()
+        })
+    });
+
+    group.bench_function("by-height", |b| {
+        let mut stmt = conn
+            .prepare(
+                "SELECT txid, account_balance_delta FROM v_transactions
+                WHERE mined_height = :height",
+            )
+            .unwrap();
+        b.iter(|| {
+            stmt.query_map(named_params![":height": last_height], |row| {
+                row.get::<_, Vec<u8>>(0)
+            })
+            .unwrap()
+            .count()
+        })
+    });
+
+    group.bench_function("by-txid", |b| {
+        let mut stmt = conn
+            .prepare(
+                "SELECT account_balance_delta FROM v_transactions
+                WHERE txid = :txid",
+            )
+            .unwrap();
+        b.iter(|| {
+            stmt.query_row(named_params![":txid": some_txid], |row| {
+                row.get::<_, i64>(0)
+            })
+            .unwrap()
+        })
+    });
+
+    group.finish();
+}
+
+#[cfg(unix)]
+criterion_group! {
+    name = benches;
+    config = Criterion::default().with_profiler(PProfProfiler::new(100, Output::Flamegraph(None)));
+    targets = bench_v_transactions
+}
+#[cfg(not(unix))]
+criterion_group!(benches, bench_v_transactions);
+criterion_main!(benches);
@@ -1,18 +1,22 @@
 //! Functions for enforcing chain validity and handling chain reorgs.
 
+use std::ops::Range;
+
 use prost::Message;
-use rusqlite::params;
+use rusqlite::{params, types::ValueRef, Connection, OptionalExtension};
 
-use zcash_primitives::consensus::BlockHeight;
+use zcash_primitives::{block::BlockHash, consensus::BlockHeight};
 
-use zcash_client_backend::{data_api::chain::error::Error, proto::compact_formats::CompactBlock};
+use zcash_client_backend::{
+    data_api::chain::{error::Error, BlockOrder},
+    proto::compact_formats::CompactBlock,
+};
 
 use crate::{error::SqliteClientError, BlockDb};
 
 #[cfg(feature = "unstable")]
 use {
-    crate::{BlockHash, FsBlockDb, FsBlockDbError},
-    rusqlite::Connection,
+    crate::{FsBlockDb, FsBlockDbError},
     std::fs::File,
     std::io::Read,
     std::path::{Path, PathBuf},
@@ -30,6 +34,73 @@ pub(crate) fn blockdb_with_blocks<F, DbErrT>(
     block_source: &BlockDb,
     from_height: Option<BlockHeight>,
     limit: Option<usize>,
+    with_row: F,
+) -> Result<(), Error<DbErrT, SqliteClientError>>
+where
+    F: FnMut(CompactBlock) -> Result<(), Error<DbErrT, SqliteClientError>>,
+{
+    blockdb_query_blocks(
+        block_source,
+        from_height.unwrap_or(BlockHeight::from(0))..BlockHeight::from(u32::MAX),
+        BlockOrder::Ascending,
+        limit,
+        from_height,
+        with_row,
+    )
+}
+
+/// Implements a traversal of the blocks of the block cache database within the given range,
+/// in the given order.
+pub(crate) fn blockdb_with_blocks_in<F, DbErrT>(
+    block_source: &BlockDb,
+    range: Range<BlockHeight>,
+    order: BlockOrder,
+    with_row: F,
+) -> Result<(), Error<DbErrT, SqliteClientError>>
+where
+    F: FnMut(CompactBlock) -> Result<(), Error<DbErrT, SqliteClientError>>,
+{
+    match first_block_height(&range, order) {
+        Some(first_height) => blockdb_query_blocks(
+            block_source,
+            range,
+            order,
+            None,
+            Some(first_height),
+            with_row,
+        ),
+        None => Ok(()),
+    }
+}
+
+/// Returns the height of the first block in the range when traversed in the given order.
+fn first_block_height(range: &Range<BlockHeight>, order: BlockOrder) -> Option<BlockHeight> {
+    if range.is_empty() {
+        None
+    } else {
+        Some(match order {
+            BlockOrder::Ascending => range.start,
+            BlockOrder::Descending => range.end - 1,
+        })
+    }
+}
+
+fn order_sql(order: BlockOrder) -> &'static str {
+    match order {
+        BlockOrder::Ascending => "ASC",
+        BlockOrder::Descending => "DESC",
+    }
+}
+
+/// Invokes `with_row` with at most `limit` blocks from the given range of the block cache
+/// database, in the given order. If `from_height` is set, returns an error if the first block
+/// found does not have that height.
+fn blockdb_query_blocks<F, DbErrT>(
+    block_source: &BlockDb,
+    range: Range<BlockHeight>,
+    order: BlockOrder,
+    limit: Option<usize>,
+    from_height: Option<BlockHeight>,
     mut with_row: F,
 ) -> Result<(), Error<DbErrT, SqliteClientError>>
 where
@@ -42,16 +113,18 @@ where
     // Fetch the CompactBlocks we need to scan
     let mut stmt_blocks = block_source
         .0
-        .prepare(
+        .prepare(&format!(
             "SELECT height, data FROM compactblocks
-            WHERE height >= ?
-            ORDER BY height ASC LIMIT ?",
-        )
+            WHERE height >= ? AND height < ?
+            ORDER BY height {} LIMIT ?",
+            order_sql(order)
+        ))
         .map_err(to_chain_error)?;
 
     let mut rows = stmt_blocks
         .query(params![
-            from_height.map_or(0u32, u32::from),
+            u32::from(range.start),
+            u32::from(range.end),
             limit
                 .and_then(|l| u32::try_from(l).ok())
                 .unwrap_or(u32::MAX)
@@ -72,8 +145,17 @@ where
             }
         }
 
-        let data: Vec<u8> = row.get(1).map_err(to_chain_error)?;
-        let block = CompactBlock::decode(&data[..]).map_err(to_chain_error)?;
+        // Decode the block directly from the row's data, rather than first copying it into
+        // an owned buffer.
+        let block = match row.get_ref(1).map_err(to_chain_error)? {
+            ValueRef::Blob(data) => CompactBlock::decode(data).map_err(to_chain_error)?,
+            _ => {
+                return Err(to_chain_error(SqliteClientError::CorruptedData(format!(
+                    "Data for block {} is not a blob",
+                    height
+                ))));
+            }
+        };
         if block.height() != height {
             return Err(to_chain_error(SqliteClientError::CorruptedData(format!(
                 "Block height {} did not match row's height field value {}",
@@ -93,6 +175,105 @@ where
     Ok(())
 }
 
+/// Removes the blocks above the given height from the block cache database.
+pub(crate) fn blockdb_truncate_to_height(
+    conn: &Connection,
+    block_height: BlockHeight,
+) -> Result<(), rusqlite::Error> {
+    conn.execute(
+        "DELETE FROM compactblocks WHERE height > ?",
+        params![u32::from(block_height)],
+    )?;
+    Ok(())
+}
+
+/// Checks the contents of the block cache database against the blocks that have been scanned
+/// into the wallet database.
+pub(crate) fn blockdb_check_consistency(
+    block_source: &BlockDb,
+    wallet_conn: &Connection,
+) -> Result<CacheConsistency, SqliteClientError> {
+    let mut stmt_blocks = block_source
+        .0
+        .prepare("SELECT height, data FROM compactblocks ORDER BY height ASC")?;
+    let blocks = stmt_blocks.query_and_then([], |row| {
+        let height = BlockHeight::from_u32(row.get(0)?);
+        let block = CompactBlock::decode(&row.get::<_, Vec<u8>>(1)?[..])?;
+        Ok::<_, SqliteClientError>((height, block.hash(), Some(block.prev_hash())))
+    })?;
+
+    check_cache_consistency(wallet_conn, blocks)
+}
+
+/// The result of checking the contents of a block cache against the blocks that have been
+/// scanned into a wallet database.
+///
+/// The wallet database records the hash of each block that it has scanned. The block cache is
+/// expected to contain a contiguous sequence of blocks, each of which agrees with the wallet
+/// database wherever the wallet has scanned a block at the same height. Blocks that the wallet
+/// has not scanned, and scanned blocks that are no longer cached, are not inconsistencies.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CacheConsistency {
+    /// The cache is consistent with the wallet database.
+    Consistent,
+    /// The cache contains blocks below and above the given height, but not the block at the
+    /// given height.
+    Gap(BlockHeight),
+    /// The parent of the cached block at the given height is not the preceding cached block.
+    Discontinuity(BlockHeight),
+    /// The cached block at the given height differs from the block that the wallet scanned at
+    /// that height.
+    HashMismatch(BlockHeight),
+}
+
+impl CacheConsistency {
+    /// Returns the height to which the cache should be truncated in order to restore its
+    /// consistency with the wallet database, or `None` if the cache is consistent.
+    ///
+    /// After the cache has been truncated, the removed blocks should be downloaded again.
+    pub fn truncation_height(&self) -> Option<BlockHeight> {
+        match self {
+            CacheConsistency::Consistent => None,
+            CacheConsistency::Gap(height)
+            | CacheConsistency::Discontinuity(height)
+            | CacheConsistency::HashMismatch(height) => Some(height.saturating_sub(1)),
+        }
+    }
+}
+
+/// Checks a sequence of cached blocks, given in ascending order of height as tuples of the
+/// block's height, hash and (if known) parent hash, against the wallet database.
+fn check_cache_consistency<E: From<rusqlite::Error>>(
+    wallet_conn: &Connection,
+    cached_blocks: impl Iterator<Item = Result<(BlockHeight, BlockHash, Option<BlockHash>), E>>,
+) -> Result<CacheConsistency, E> {
+    let mut stmt_wallet_hash = wallet_conn.prepare("SELECT hash FROM blocks WHERE height = ?")?;
+
+    let mut prev_block: Option<(BlockHeight, BlockHash)> = None;
+    for cached_block in cached_blocks {
+        let (height, hash, prev_hash) = cached_block?;
+        if let Some((prev_height, prev_block_hash)) = prev_block {
+            if height != prev_height + 1 {
+                return Ok(CacheConsistency::Gap(prev_height + 1));
+            }
+            if prev_hash.map_or(false, |h| h != prev_block_hash) {
+                return Ok(CacheConsistency::Discontinuity(height));
+            }
+        }
+
+        let wallet_hash = stmt_wallet_hash
+            .query_row(params![u32::from(height)], |row| row.get::<_, Vec<u8>>(0))
+            .optional()?;
+        if wallet_hash.map_or(false, |h| h[..] != hash.0[..]) {
+            return Ok(CacheConsistency::HashMismatch(height));
+        }
+
+        prev_block = Some((height, hash));
+    }
+
+    Ok(CacheConsistency::Consistent)
+}
+
 /// Data structure representing a row in the block metadata database.
 #[cfg(feature = "unstable")]
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -200,14 +381,32 @@ pub(crate) fn blockmetadb_get_max_cached_height(
     })
 }
 
+/// Checks the block metadata database against the blocks that have been scanned into the wallet
+/// database.
+#[cfg(feature = "unstable")]
+pub(crate) fn blockmetadb_check_consistency(
+    conn: &Connection,
+    wallet_conn: &Connection,
+) -> Result<CacheConsistency, rusqlite::Error> {
+    let mut stmt_meta =
+        conn.prepare("SELECT height, blockhash FROM compactblocks_meta ORDER BY height ASC")?;
+    let blocks = stmt_meta.query_map([], |row| {
+        Ok((
+            BlockHeight::from_u32(row.get(0)?),
+            BlockHash::from_slice(&row.get::<_, Vec<u8>>(1)?),
+            None,
+        ))
+    })?;
+
+    check_cache_consistency(wallet_conn, blocks)
+}
+
 /// Returns the metadata for the block with the given height, if it exists in the database.
 #[cfg(feature = "unstable")]
 pub(crate) fn blockmetadb_find_block(
     conn: &Connection,
     height: BlockHeight,
 ) -> Result<Option<BlockMeta>, rusqlite::Error> {
-    use rusqlite::OptionalExtension;
-
     conn.query_row(
         "SELECT blockhash, time, sapling_outputs_count, orchard_actions_count
         FROM compactblocks_meta
@@ -237,6 +436,51 @@ pub(crate) fn fsblockdb_with_blocks<F, DbErrT>(
     cache: &FsBlockDb,
     from_height: Option<BlockHeight>,
     limit: Option<usize>,
+    with_block: F,
+) -> Result<(), Error<DbErrT, FsBlockDbError>>
+where
+    F: FnMut(CompactBlock) -> Result<(), Error<DbErrT, FsBlockDbError>>,
+{
+    fsblockdb_query_blocks(
+        cache,
+        from_height.unwrap_or(BlockHeight::from(0))..BlockHeight::from(u32::MAX),
+        BlockOrder::Ascending,
+        limit,
+        from_height,
+        with_block,
+    )
+}
+
+/// Implements a traversal of the blocks of the filesystem-backed block cache within the given
+/// range, in the given order.
+#[cfg(feature = "unstable")]
+pub(crate) fn fsblockdb_with_blocks_in<F, DbErrT>(
+    cache: &FsBlockDb,
+    range: Range<BlockHeight>,
+    order: BlockOrder,
+    with_block: F,
+) -> Result<(), Error<DbErrT, FsBlockDbError>>
+where
+    F: FnMut(CompactBlock) -> Result<(), Error<DbErrT, FsBlockDbError>>,
+{
+    match first_block_height(&range, order) {
+        Some(first_height) => {
+            fsblockdb_query_blocks(cache, range, order, None, Some(first_height), with_block)
+        }
+        None => Ok(()),
+    }
+}
+
+/// Invokes `with_block` with at most `limit` blocks from the given range of the
+/// filesystem-backed block cache, in the given order. If `from_height` is set, returns an
+/// error if the first block found does not have that height.
+#[cfg(feature = "unstable")]
+fn fsblockdb_query_blocks<F, DbErrT>(
+    cache: &FsBlockDb,
+    range: Range<BlockHeight>,
+    order: BlockOrder,
+    limit: Option<usize>,
+    from_height: Option<BlockHeight>,
     mut with_block: F,
 ) -> Result<(), Error<DbErrT, FsBlockDbError>>
 where
@@ -249,18 +493,20 @@ where
     // Fetch the CompactBlocks we need to scan
     let mut stmt_blocks = cache
         .conn
-        .prepare(
+        .prepare(&format!(
             "SELECT height, blockhash, time, sapling_outputs_count, orchard_actions_count
              FROM compactblocks_meta
-             WHERE height >= ?
-             ORDER BY height ASC LIMIT ?",
-        )
+             WHERE height >= ? AND height < ?
+             ORDER BY height {} LIMIT ?",
+            order_sql(order)
+        ))
         .map_err(to_chain_error)?;
 
     let rows = stmt_blocks
         .query_map(
             params![
-                from_height.map_or(0u32, u32::from),
+                u32::from(range.start),
+                u32::from(range.end),
                 limit
                     .and_then(|l| u32::try_from(l).ok())
                     .unwrap_or(u32::MAX)
@@ -279,6 +525,8 @@ where
 
     // Only look for the `from_height` in the scanned blocks if it is set.
     let mut from_height_found = from_height.is_none();
+    // A single buffer is reused for reading each block file.
+    let mut block_data = vec![];
     for row_result in rows {
         let cbr = row_result.map_err(to_chain_error)?;
         if !from_height_found {
@@ -293,7 +541,7 @@ where
 
         let mut block_file =
             File::open(cbr.block_file_path(&cache.blocks_dir)).map_err(to_chain_error)?;
-        let mut block_data = vec![];
+        block_data.clear();
         block_file
             .read_to_end(&mut block_data)
             .map_err(to_chain_error)?;
@@ -395,6 +643,136 @@ mod tests {
         testing::pool::scan_cached_blocks_finds_change_notes::<OrchardPoolTester>()
     }
 
+    #[test]
+    fn block_cache_consistency() {
+        use prost::Message;
+        use rusqlite::params;
+        use zcash_client_backend::{data_api::WalletRead, proto::compact_formats::CompactBlock};
+        use zcash_primitives::{
+            block::BlockHash, transaction::components::amount::NonNegativeAmount,
+        };
+
+        use super::CacheConsistency;
+        use crate::testing::{AddressType, TestBuilder};
+
+        let mut st = TestBuilder::new()
+            .with_block_cache()
+            .with_account_from_sapling_activation(BlockHash([0; 32]))
+            .build();
+        let dfvk = st.test_account_sapling().unwrap();
+        let value = NonNegativeAmount::const_from_u64(10000);
+
+        let (h1, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+        let (h2, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+        st.scan_cached_blocks(h1, 2);
+        assert_eq!(
+            st.cache().check_consistency(st.wallet()).unwrap(),
+            CacheConsistency::Consistent
+        );
+
+        let insert_block = |st: &crate::testing::TestState<_>, block: CompactBlock| {
+            st.cache()
+                .0
+                .execute(
+                    "INSERT INTO compactblocks (height, data) VALUES (?, ?)",
+                    params![u32::try_from(block.height).unwrap(), block.encode_to_vec()],
+                )
+                .unwrap();
+        };
+
+        // A block that is not adjacent to the cached blocks leaves a gap.
+        insert_block(
+            &st,
+            CompactBlock {
+                height: u64::from(h2 + 2),
+                hash: vec![1; 32],
+                prev_hash: vec![2; 32],
+                ..Default::default()
+            },
+        );
+        let result = st.cache().check_consistency(st.wallet()).unwrap();
+        assert_eq!(result, CacheConsistency::Gap(h2 + 1));
+        assert_eq!(result.truncation_height(), Some(h2));
+        st.cache().truncate_to_height(h2).unwrap();
+        assert_eq!(
+            st.cache().check_consistency(st.wallet()).unwrap(),
+            CacheConsistency::Consistent
+        );
+
+        // A cached block that differs from the block that the wallet scanned is detected.
+        let h1_hash = st.wallet().get_block_hash(h1).unwrap().unwrap();
+        st.cache().truncate_to_height(h1).unwrap();
+        insert_block(
+            &st,
+            CompactBlock {
+                height: u64::from(h2),
+                hash: vec![1; 32],
+                prev_hash: h1_hash.0.to_vec(),
+                ..Default::default()
+            },
+        );
+        let result = st.cache().check_consistency(st.wallet()).unwrap();
+        assert_eq!(result, CacheConsistency::HashMismatch(h2));
+        assert_eq!(result.truncation_height(), Some(h1));
+    }
+
+    #[test]
+    fn block_cache_serves_ranges_in_either_order() {
+        use zcash_client_backend::data_api::chain::{BlockOrder, BlockSource};
+        use zcash_primitives::{
+            block::BlockHash, consensus::BlockHeight,
+            transaction::components::amount::NonNegativeAmount,
+        };
+
+        use crate::{
+            error::SqliteClientError,
+            testing::{AddressType, TestBuilder},
+        };
+
+        let mut st = TestBuilder::new()
+            .with_block_cache()
+            .with_account_from_sapling_activation(BlockHash([0; 32]))
+            .build();
+        let dfvk = st.test_account_sapling().unwrap();
+        let value = NonNegativeAmount::const_from_u64(10000);
+
+        let (h1, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+        for _ in 0..3 {
+            st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+        }
+
+        let heights_in = |range, order| {
+            let mut heights = vec![];
+            st.cache()
+                .with_blocks_in::<_, ()>(range, order, |block| {
+                    heights.push(block.height());
+                    Ok(())
+                })
+                .map(|()| heights)
+        };
+
+        assert_eq!(
+            heights_in(h1 + 1..h1 + 3, BlockOrder::Ascending).unwrap(),
+            vec![h1 + 1, h1 + 2]
+        );
+        assert_eq!(
+            heights_in(h1..h1 + 4, BlockOrder::Descending).unwrap(),
+            vec![h1 + 3, h1 + 2, h1 + 1, h1]
+        );
+        assert!(heights_in(h1..h1, BlockOrder::Descending)
+            .unwrap()
+            .is_empty());
+
+        // The first block to be supplied must be in the cache.
+        let missing: BlockHeight = h1 + 4;
+        assert_matches!(
+            heights_in(h1..missing + 1, BlockOrder::Descending),
+            Err(zcash_client_backend::data_api::chain::error::Error::BlockSource(
+                SqliteClientError::CacheMiss(h)
+            )) if h == missing
+        );
+    }
+
     #[test]
     fn scan_cached_blocks_detects_spends_out_of_order_sapling() {
         testing::pool::scan_cached_blocks_detects_spends_out_of_order::<SaplingPoolTester>()
@@ -5,12 +5,12 @@ use std::fmt;
 
 use shardtree::error::ShardTreeError;
 use zcash_address::ParseError;
-use zcash_client_backend::PoolType;
+use zcash_client_backend::{data_api::scheduled::ScheduledPaymentId, PoolType};
 use zcash_keys::keys::AddressGenerationError;
 use zcash_primitives::zip32;
 use zcash_primitives::{consensus::BlockHeight, transaction::components::amount::BalanceError};
 
-use crate::wallet::commitment_tree;
+use crate::wallet::{commitment_tree, contacts::ContactId};
 use crate::PRUNING_DEPTH;
 
 #[cfg(feature = "transparent-inputs")]
@@ -93,6 +93,11 @@ pub enum SqliteClientError {
     #[cfg(feature = "transparent-inputs")]
     AddressNotRecognized(TransparentAddress),
 
+    /// The script of a transparent output being inserted does not pay to a transparent
+    /// address, so the output cannot be associated with the wallet.
+    #[cfg(feature = "transparent-inputs")]
+    UnsupportedTransparentScript,
+
     /// An error occurred in inserting data into or accessing data from one of the wallet's note
     /// commitment trees.
     CommitmentTree(ShardTreeError<commitment_tree::Error>),
@@ -112,6 +117,16 @@ pub enum SqliteClientError {
 
     /// An error occurred in computing wallet balance
     BalanceError(BalanceError),
+
+    /// The given string could not be parsed as a Zcash address.
+    InvalidAddress(String),
+
+    /// The address being added to the address book shares a receiver with the address of the
+    /// given contact.
+    ContactConflict(ContactId),
+
+    /// The wallet has no scheduled payment with the given identifier.
+    ScheduledPaymentUnknown(ScheduledPaymentId),
 }
 
 impl error::Error for SqliteClientError {
@@ -157,11 +172,16 @@ impl fmt::Display for SqliteClientError {
             SqliteClientError::AccountIdOutOfRange => write!(f, "Wallet account identifiers must be less than 0x7FFFFFFF."),
             #[cfg(feature = "transparent-inputs")]
             SqliteClientError::AddressNotRecognized(_) => write!(f, "The address associated with a received txo is not identifiable as belonging to the wallet."),
+            #[cfg(feature = "transparent-inputs")]
+            SqliteClientError::UnsupportedTransparentScript => write!(f, "The script of the transparent output does not pay to a transparent address."),
             SqliteClientError::CommitmentTree(err) => write!(f, "An error occurred accessing or updating note commitment tree data: {}.", err),
             SqliteClientError::CacheMiss(height) => write!(f, "Requested height {} does not exist in the block cache.", height),
             SqliteClientError::ChainHeightUnknown => write!(f, "Chain height unknown; please call `update_chain_tip`"),
             SqliteClientError::UnsupportedPoolType(t) => write!(f, "Pool type is not currently supported: {}", t),
             SqliteClientError::BalanceError(e) => write!(f, "Balance error: {}", e),
+            SqliteClientError::InvalidAddress(addr) => write!(f, "Not a valid Zcash address: {}", addr),
+            SqliteClientError::ContactConflict(id) => write!(f, "The address shares a receiver with the address of contact {:?}.", id),
+            SqliteClientError::ScheduledPaymentUnknown(id) => write!(f, "No scheduled payment with identifier {} exists in the wallet.", u64::from(*id)),
         }
     }
 }
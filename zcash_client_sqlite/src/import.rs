@@ -0,0 +1,457 @@
+//! Import of wallets exported by other applications.
+//!
+//! Currently, wallets exported by Zecwallet Lite (and its command-line client) are supported.
+//! Zecwallet Lite exports a wallet as two JSON documents:
+//! - the output of its `seed` command, an object of the form
+//!   `{"seed": "<24-word phrase>", "birthday": <height>}`; and
+//! - the output of its `export` command, an array of objects that each have an `address`
+//!   field, along with private and viewing key fields that are not used by this module.
+//!
+//! Zecwallet Lite derives each of its Sapling addresses as the default address of a separate
+//! ZIP 32 account (at `m/32'/<coin_type>'/<index>'`), and all of its transparent addresses
+//! from the first account (at `m/44'/<coin_type>'/0'/0/<index>`). Importing an export creates
+//! one account in the wallet for each Sapling address that was in use, and registers the
+//! transparent addresses that were in use with the first account, so that funds held in any
+//! of them are found when the wallet is scanned.
+
+use std::fmt;
+
+use rusqlite::Connection;
+use secrecy::{ExposeSecret, SecretString, SecretVec};
+use serde::Deserialize;
+
+use zcash_client_backend::{
+    address::Address,
+    data_api::{AccountBirthday, AccountSource},
+    keys::{sapling, UnifiedSpendingKey},
+};
+use zcash_primitives::{
+    consensus::{self, BlockHeight, NetworkConstants},
+    zip339,
+};
+use zip32::fingerprint::SeedFingerprint;
+
+use crate::{error::SqliteClientError, wallet, AccountId, WalletDb};
+
+#[cfg(feature = "transparent-inputs")]
+use {
+    zcash_client_backend::keys::UnifiedAddressRequest,
+    zcash_primitives::legacy::{
+        keys::{IncomingViewingKey, NonHardenedChildIndex},
+        TransparentAddress,
+    },
+};
+
+/// The number of ZIP 32 accounts beyond the number of exported Sapling addresses that are
+/// searched for a match with each exported Sapling address.
+const SAPLING_ACCOUNT_SEARCH_GAP: u32 = 20;
+
+/// The number of transparent address indices that are searched for a match with each
+/// exported transparent address.
+#[cfg(feature = "transparent-inputs")]
+const TRANSPARENT_ADDRESS_SEARCH_LIMIT: u32 = 1000;
+
+/// Errors that can occur while importing a wallet.
+#[derive(Debug)]
+pub enum Error {
+    /// An exported document could not be parsed.
+    Json(serde_json::Error),
+    /// The exported seed phrase is not a valid English [ZIP 339] mnemonic.
+    ///
+    /// [ZIP 339]: https://zips.z.cash/zip-0339
+    InvalidSeedPhrase(zip339::Error),
+    /// The wallet already contains accounts derived from the exported seed.
+    SeedAlreadyImported,
+    /// An error occurred while adding the imported accounts to the wallet.
+    Wallet(SqliteClientError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Json(e) => write!(f, "Could not parse wallet export: {}", e),
+            Error::InvalidSeedPhrase(e) => write!(f, "Invalid seed phrase: {}", e),
+            Error::SeedAlreadyImported => write!(
+                f,
+                "The wallet already contains accounts derived from this seed"
+            ),
+            Error::Wallet(e) => write!(f, "Could not import accounts: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Json(e) => Some(e),
+            Error::Wallet(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<SqliteClientError> for Error {
+    fn from(e: SqliteClientError) -> Self {
+        Error::Wallet(e)
+    }
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(e: rusqlite::Error) -> Self {
+        Error::Wallet(SqliteClientError::DbError(e))
+    }
+}
+
+#[derive(Deserialize)]
+struct SeedDocument {
+    seed: String,
+    birthday: u32,
+}
+
+#[derive(Deserialize)]
+struct ExportedKey {
+    address: String,
+}
+
+/// A wallet exported by Zecwallet Lite.
+pub struct ZecwalletExport {
+    phrase: SecretString,
+    birthday: BlockHeight,
+    addresses: Vec<String>,
+}
+
+impl ZecwalletExport {
+    /// Parses the output of Zecwallet Lite's `seed` command and, optionally, of its `export`
+    /// command.
+    ///
+    /// If the output of the `export` command is not provided, only the first account of the
+    /// wallet will be imported, and transparent addresses other than the first will not be
+    /// registered with the wallet.
+    pub fn from_json(seed_json: &str, export_json: Option<&str>) -> Result<Self, Error> {
+        let seed: SeedDocument = serde_json::from_str(seed_json).map_err(Error::Json)?;
+        let addresses = export_json
+            .map(serde_json::from_str::<Vec<ExportedKey>>)
+            .transpose()
+            .map_err(Error::Json)?
+            .unwrap_or_default()
+            .into_iter()
+            .map(|key| key.address)
+            .collect();
+
+        Ok(ZecwalletExport {
+            phrase: SecretString::new(seed.seed),
+            birthday: BlockHeight::from(seed.birthday),
+            addresses,
+        })
+    }
+
+    /// Returns the birthday height recorded by Zecwallet Lite.
+    ///
+    /// The [`AccountBirthday`] passed to [`WalletDb::import_zecwallet`] should be constructed
+    /// from the tree state at this height.
+    pub fn birthday(&self) -> BlockHeight {
+        self.birthday
+    }
+
+    /// Returns the addresses that were listed in the export.
+    pub fn addresses(&self) -> &[String] {
+        &self.addresses
+    }
+}
+
+/// The result of importing a wallet.
+#[derive(Debug)]
+pub struct ImportedWallet {
+    accounts: Vec<AccountId>,
+    #[cfg(feature = "transparent-inputs")]
+    transparent_addresses: Vec<TransparentAddress>,
+    unmatched_addresses: Vec<String>,
+}
+
+impl ImportedWallet {
+    /// Returns the accounts that were created, in order of their ZIP 32 account index.
+    pub fn accounts(&self) -> &[AccountId] {
+        &self.accounts
+    }
+
+    /// Returns the exported transparent addresses, all of which are now tracked by the first
+    /// account.
+    #[cfg(feature = "transparent-inputs")]
+    pub fn transparent_addresses(&self) -> &[TransparentAddress] {
+        &self.transparent_addresses
+    }
+
+    /// Returns the exported addresses that are not derived from the exported seed, or that
+    /// are of a kind that this wallet does not support. This includes keys that were
+    /// imported into Zecwallet Lite individually; funds held by such addresses are not
+    /// tracked by the imported accounts.
+    pub fn unmatched_addresses(&self) -> &[String] {
+        &self.unmatched_addresses
+    }
+}
+
+impl<P: consensus::Parameters + Clone> WalletDb<Connection, P> {
+    /// Imports a wallet exported by Zecwallet Lite.
+    ///
+    /// Accounts are created for the exported seed, starting from ZIP 32 account 0, up to
+    /// and including the highest account that was used by Zecwallet Lite, and the exported
+    /// transparent addresses are registered with the first account. All of the accounts
+    /// share the given birthday.
+    ///
+    /// Returns [`Error::SeedAlreadyImported`] if the wallet already contains an account
+    /// derived from the exported seed.
+    pub fn import_zecwallet(
+        &mut self,
+        export: &ZecwalletExport,
+        birthday: &AccountBirthday,
+    ) -> Result<ImportedWallet, Error> {
+        let mnemonic = zip339::Mnemonic::<zip339::English>::from_phrase(
+            export.phrase.expose_secret().as_str(),
+        )
+        .map_err(Error::InvalidSeedPhrase)?;
+        let seed = SecretVec::new(mnemonic.to_seed("").to_vec());
+        let seed_fingerprint = SeedFingerprint::from_seed(seed.expose_secret())
+            .expect("ZIP 339 seeds are 64 bytes long");
+
+        // Zecwallet Lite uses the default address of each account as a Sapling address.
+        let search_limit = u32::try_from(export.addresses.len())
+            .unwrap_or(u32::MAX)
+            .saturating_add(SAPLING_ACCOUNT_SEARCH_GAP);
+        let sapling_addresses = (0..search_limit)
+            .map_while(|i| zip32::AccountId::try_from(i).ok())
+            .map(|account| {
+                sapling::spending_key(seed.expose_secret(), self.params.coin_type(), account)
+                    .to_diversifiable_full_viewing_key()
+                    .default_address()
+                    .1
+            })
+            .collect::<Vec<_>>();
+
+        #[cfg(feature = "transparent-inputs")]
+        let transparent_ivk = UnifiedSpendingKey::from_seed(
+            &self.params,
+            seed.expose_secret(),
+            zip32::AccountId::ZERO,
+        )
+        .map_err(|_| SqliteClientError::KeyDerivationError(zip32::AccountId::ZERO))?
+        .transparent()
+        .to_account_pubkey()
+        .derive_external_ivk()
+        .map_err(SqliteClientError::HdwalletError)?;
+
+        let mut account_count = 1;
+        #[cfg(feature = "transparent-inputs")]
+        let mut transparent_addresses = vec![];
+        let mut unmatched_addresses = vec![];
+        for encoded in &export.addresses {
+            match Address::decode(&self.params, encoded) {
+                Some(Address::Sapling(addr)) => {
+                    match sapling_addresses.iter().position(|a| a == &addr) {
+                        Some(i) => account_count = account_count.max(i + 1),
+                        None => unmatched_addresses.push(encoded.clone()),
+                    }
+                }
+                #[cfg(feature = "transparent-inputs")]
+                Some(Address::Transparent(addr)) => {
+                    let found = (0..TRANSPARENT_ADDRESS_SEARCH_LIMIT)
+                        .filter_map(NonHardenedChildIndex::from_index)
+                        .find(|i| transparent_ivk.derive_address(*i).ok() == Some(addr));
+                    match found {
+                        Some(i) => transparent_addresses.push((addr, i)),
+                        None => unmatched_addresses.push(encoded.clone()),
+                    }
+                }
+                _ => unmatched_addresses.push(encoded.clone()),
+            }
+        }
+
+        let accounts = self.transactionally(|wdb| {
+            if wallet::max_zip32_account_index(wdb.conn.0, &seed_fingerprint)?.is_some() {
+                return Err(Error::SeedAlreadyImported);
+            }
+
+            let mut accounts = vec![];
+            for i in 0..account_count {
+                let account_index = u32::try_from(i)
+                    .ok()
+                    .and_then(|i| zip32::AccountId::try_from(i).ok())
+                    .ok_or(SqliteClientError::AccountIdOutOfRange)?;
+                let usk =
+                    UnifiedSpendingKey::from_seed(&wdb.params, seed.expose_secret(), account_index)
+                        .map_err(|_| SqliteClientError::KeyDerivationError(account_index))?;
+                accounts.push(wallet::add_account(
+                    wdb.conn.0,
+                    &wdb.params,
+                    AccountSource::Derived {
+                        seed_fingerprint,
+                        account_index,
+                    },
+                    wallet::ViewingKey::Full(Box::new(usk.to_unified_full_viewing_key())),
+                    birthday,
+                )?);
+            }
+
+            #[cfg(feature = "transparent-inputs")]
+            {
+                // Transparent receivers are tracked through the unified addresses of the
+                // account, so we generate a unified address at each exported index. Orchard
+                // receivers exist at every diversifier index.
+                let account = accounts[0];
+                let ufvk = UnifiedSpendingKey::from_seed(
+                    &wdb.params,
+                    seed.expose_secret(),
+                    zip32::AccountId::ZERO,
+                )
+                .map_err(|_| SqliteClientError::KeyDerivationError(zip32::AccountId::ZERO))?
+                .to_unified_full_viewing_key();
+                let request = UnifiedAddressRequest::unsafe_new(true, false, true);
+                let known = wallet::get_transparent_receivers(wdb.conn.0, &wdb.params, account)?;
+                for (addr, index) in &transparent_addresses {
+                    if !known.contains_key(addr) {
+                        let ua = ufvk
+                            .address_at(index.index(), request)
+                            .map_err(SqliteClientError::AddressGeneration)?;
+                        wallet::insert_address(
+                            wdb.conn.0,
+                            &wdb.params,
+                            account,
+                            index.index().into(),
+                            &ua,
+                        )?;
+                    }
+                }
+            }
+
+            Ok(accounts)
+        })?;
+
+        Ok(ImportedWallet {
+            accounts,
+            #[cfg(feature = "transparent-inputs")]
+            transparent_addresses: transparent_addresses
+                .into_iter()
+                .map(|(addr, _)| addr)
+                .collect(),
+            unmatched_addresses,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::NamedTempFile;
+
+    use zcash_client_backend::{
+        address::Address,
+        data_api::{AccountBirthday, WalletRead},
+        keys::sapling,
+    };
+    use zcash_primitives::{
+        block::BlockHash,
+        consensus::{Network, NetworkConstants},
+        zip339,
+    };
+
+    use super::{Error, ZecwalletExport};
+    use crate::{wallet::init::init_wallet_db, WalletDb};
+
+    const PHRASE: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    fn sapling_address(seed: &[u8], account: u32) -> String {
+        let network = Network::TestNetwork;
+        let addr = sapling::spending_key(seed, network.coin_type(), account.try_into().unwrap())
+            .to_diversifiable_full_viewing_key()
+            .default_address()
+            .1;
+        Address::Sapling(addr).encode(&network)
+    }
+
+    #[test]
+    fn import_zecwallet_export() {
+        let network = Network::TestNetwork;
+        let data_file = NamedTempFile::new().unwrap();
+        let mut db_data = WalletDb::for_path(data_file.path(), network).unwrap();
+        init_wallet_db(&mut db_data, None).unwrap();
+
+        let seed = zip339::Mnemonic::<zip339::English>::from_phrase(PHRASE)
+            .unwrap()
+            .to_seed("");
+        let mut addresses = vec![
+            sapling_address(&seed, 0),
+            sapling_address(&seed, 1),
+            // A key imported into Zecwallet Lite from elsewhere.
+            sapling_address(&[7; 32], 0),
+        ];
+
+        #[cfg(feature = "transparent-inputs")]
+        let taddr = {
+            use zcash_client_backend::keys::UnifiedSpendingKey;
+            use zcash_primitives::legacy::keys::{IncomingViewingKey, NonHardenedChildIndex};
+
+            let taddr = UnifiedSpendingKey::from_seed(&network, &seed, zip32::AccountId::ZERO)
+                .unwrap()
+                .transparent()
+                .to_account_pubkey()
+                .derive_external_ivk()
+                .unwrap()
+                .derive_address(NonHardenedChildIndex::from_index(5).unwrap())
+                .unwrap();
+            addresses.push(Address::Transparent(taddr).encode(&network));
+            taddr
+        };
+
+        let seed_json = format!(r#"{{"seed": "{}", "birthday": 419200}}"#, PHRASE);
+        let export_json = format!(
+            "[{}]",
+            addresses
+                .iter()
+                .map(|a| format!(r#"{{"address": "{}", "private_key": "unused"}}"#, a))
+                .collect::<Vec<_>>()
+                .join(",")
+        );
+        let export = ZecwalletExport::from_json(&seed_json, Some(&export_json)).unwrap();
+        assert_eq!(u32::from(export.birthday()), 419200);
+
+        let birthday = AccountBirthday::from_sapling_activation(&network, BlockHash([0; 32]));
+        let imported = db_data.import_zecwallet(&export, &birthday).unwrap();
+        assert_eq!(imported.accounts().len(), 2);
+        assert_eq!(imported.unmatched_addresses(), &addresses[2..3]);
+        assert_eq!(db_data.get_account_ids().unwrap().len(), 2);
+
+        #[cfg(feature = "transparent-inputs")]
+        {
+            assert_eq!(imported.transparent_addresses(), &[taddr]);
+            assert!(db_data
+                .get_transparent_receivers(imported.accounts()[0])
+                .unwrap()
+                .contains_key(&taddr));
+        }
+
+        // The same seed cannot be imported twice.
+        assert!(matches!(
+            db_data.import_zecwallet(&export, &birthday),
+            Err(Error::SeedAlreadyImported)
+        ));
+    }
+
+    #[test]
+    fn invalid_export() {
+        assert!(matches!(
+            ZecwalletExport::from_json(r#"{"birthday": 1}"#, None),
+            Err(Error::Json(_))
+        ));
+
+        let export =
+            ZecwalletExport::from_json(r#"{"seed": "abandon abandon", "birthday": 1}"#, None)
+                .unwrap();
+        let data_file = NamedTempFile::new().unwrap();
+        let network = Network::TestNetwork;
+        let mut db_data = WalletDb::for_path(data_file.path(), network).unwrap();
+        init_wallet_db(&mut db_data, None).unwrap();
+        let birthday = AccountBirthday::from_sapling_activation(&network, BlockHash([0; 32]));
+        assert!(matches!(
+            db_data.import_zecwallet(&export, &birthday),
+            Err(Error::InvalidSeedPhrase(_))
+        ));
+    }
+}
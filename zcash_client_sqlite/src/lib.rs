@@ -11,13 +11,23 @@
 //!
 //! - A cache database, used to inform the light client about new [`CompactBlock`]s. It is
 //!   read-only within all light client APIs *except* for [`init_cache_database`] which
-//!   can be used to initialize the database.
+//!   can be used to initialize the database, and [`BlockDb::truncate_to_height`] which
+//!   removes cached blocks.
 //!
 //! - A data database, where the light client's state is stored. It is read-write within
 //!   the light client APIs, and **assumed to be read-only outside these APIs**. Callers
 //!   **MUST NOT** write to the database without using these APIs. Callers **MAY** read
 //!   the database directly in order to extract information for display to users.
 //!
+//! The two databases are updated independently: the cache is filled by downloading blocks
+//! from a light wallet server, and the data database records the blocks that have been
+//! scanned from the cache. The cache holds no state that cannot be downloaded again, and may
+//! be discarded at any time. Because the data database does not track the contents of the
+//! cache, the cache can diverge from the scanned state of the wallet, for example if it is
+//! only partially written or if the wallet is rewound after a chain reorganization. Such
+//! divergence can be detected with [`BlockDb::check_consistency`] and repaired by truncating
+//! the cache to the height that it reports.
+//!
 //! ## Feature flags
 #![doc = document_features::document_features!()]
 //!
@@ -38,12 +48,15 @@ use maybe_rayon::{
     slice::ParallelSliceMut,
 };
 use nonempty::NonEmpty;
-use rusqlite::{self, Connection};
+use rusqlite::{
+    self,
+    types::{FromSql, FromSqlResult, ToSqlOutput, ValueRef},
+    Connection, OpenFlags, ToSql, TransactionBehavior,
+};
 use secrecy::{ExposeSecret, SecretVec};
 use shardtree::{error::ShardTreeError, ShardTree};
 use std::{
-    borrow::Borrow, collections::HashMap, convert::AsRef, fmt, num::NonZeroU32, ops::Range,
-    path::Path,
+    borrow::Borrow, collections::HashMap, convert::AsRef, num::NonZeroU32, ops::Range, path::Path,
 };
 use subtle::ConditionallySelectable;
 use tracing::{debug, trace, warn};
@@ -52,20 +65,25 @@ use zcash_client_backend::{
     address::UnifiedAddress,
     data_api::{
         self,
-        chain::{BlockSource, ChainState, CommitmentTreeRoot},
+        audit::AuditBundle,
+        chain::{BlockOrder, BlockSource, ChainState, CommitmentTreeRoot},
         scanning::{ScanPriority, ScanRange},
-        Account, AccountBirthday, AccountSource, BlockMetadata, DecryptedTransaction, InputSource,
-        NullifierQuery, ScannedBlock, SeedRelevance, SentTransaction, SpendableNotes,
-        WalletCommitmentTrees, WalletRead, WalletSummary, WalletWrite, SAPLING_SHARD_HEIGHT,
+        scheduled::{PaymentInterval, ScheduledPayment, ScheduledPaymentId},
+        Account, AccountBirthday, AccountSource, AddressUsage, BlockMetadata, BlockNullifiers,
+        DecryptedTransaction, DustFilter, HistoricalBalance, InputSource, NullifierQuery,
+        PeriodStatistics, ScannedBlock, SeedRelevance, SentTransaction, SpendableNotes,
+        StatisticsGranularity, TransactionDataRequest, TransactionStatus, WalletCommitmentTrees,
+        WalletRead, WalletSummary, WalletWrite, SAPLING_SHARD_HEIGHT,
     },
     keys::{
-        AddressGenerationError, UnifiedAddressRequest, UnifiedFullViewingKey, UnifiedSpendingKey,
+        AddressGenerationError, UnifiedAddressRequest, UnifiedFullViewingKey,
+        UnifiedIncomingViewingKey, UnifiedSpendingKey,
     },
     proto::compact_formats::CompactBlock,
     wallet::{Note, NoteId, ReceivedNote, Recipient, WalletTransparentOutput},
     DecryptedOutput, PoolType, ShieldedProtocol, TransferType,
 };
-use zcash_keys::address::Receiver;
+use zcash_keys::address::{Address, Receiver};
 use zcash_primitives::{
     block::BlockHash,
     consensus::{self, BlockHeight},
@@ -88,21 +106,27 @@ use {
 #[cfg(feature = "transparent-inputs")]
 use {
     zcash_client_backend::wallet::TransparentAddressMetadata,
-    zcash_primitives::{legacy::TransparentAddress, transaction::components::OutPoint},
+    zcash_primitives::{
+        legacy::TransparentAddress,
+        transaction::components::{OutPoint, TxOut},
+    },
 };
 
 #[cfg(feature = "unstable")]
 use {
-    crate::chain::{fsblockdb_with_blocks, BlockMeta},
+    crate::chain::{fsblockdb_with_blocks, fsblockdb_with_blocks_in, BlockMeta},
     std::path::PathBuf,
     std::{fs, io},
 };
 
 pub mod chain;
 pub mod error;
+#[cfg(feature = "import")]
+pub mod import;
 pub mod wallet;
 use wallet::{
     commitment_tree::{self, put_shard_roots},
+    journal::WalletEventKind,
     SubtreeScanProgress,
 };
 
@@ -114,6 +138,9 @@ mod testing;
 /// this delta from the chain tip to be pruned.
 pub(crate) const PRUNING_DEPTH: u32 = 100;
 
+/// The version of the encoding produced by [`WalletDb::export_tree_state`].
+const TREE_STATE_VERSION: u8 = 1;
+
 /// The number of blocks to verify ahead when the chain tip is updated.
 pub(crate) const VERIFY_LOOKAHEAD: u32 = 10;
 
@@ -136,9 +163,31 @@ pub(crate) const DEFAULT_UA_REQUEST: UnifiedAddressRequest =
     UnifiedAddressRequest::unsafe_new(UA_ORCHARD, true, UA_TRANSPARENT);
 
 /// The ID type for accounts.
+///
+/// When the `serde` feature flag is enabled, an `AccountId` is serialized as an unsigned
+/// integer.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
 pub struct AccountId(u32);
 
+impl ToSql for AccountId {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        self.0.to_sql()
+    }
+}
+
+impl FromSql for AccountId {
+    /// Account identifiers are stored as integers; values outside the range of [`u32`] are
+    /// rejected with [`rusqlite::types::FromSqlError::OutOfRange`].
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        u32::column_result(value).map(AccountId)
+    }
+}
+
 impl ConditionallySelectable for AccountId {
     fn conditional_select(a: &Self, b: &Self, choice: subtle::Choice) -> Self {
         AccountId(ConditionallySelectable::conditional_select(
@@ -147,20 +196,13 @@ impl ConditionallySelectable for AccountId {
     }
 }
 
-/// An opaque type for received note identifiers.
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
-pub struct ReceivedNoteId(pub(crate) ShieldedProtocol, pub(crate) i64);
-
-impl fmt::Display for ReceivedNoteId {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            ReceivedNoteId(protocol, id) => write!(f, "Received {:?} Note: {}", protocol, id),
-        }
-    }
-}
-
 /// A newtype wrapper for sqlite primary key values for the utxos
 /// table.
+///
+/// This is an internal row identifier that is only meaningful within a single wallet
+/// database; transparent outputs should be identified externally by their [`OutPoint`].
+///
+/// [`OutPoint`]: zcash_primitives::transaction::components::OutPoint
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct UtxoId(pub i64);
 
@@ -188,6 +230,69 @@ impl<P: consensus::Parameters + Clone> WalletDb<Connection, P> {
         })
     }
 
+    /// Opens a read-only connection to the wallet database stored at the specified path.
+    ///
+    /// The database must already have been initialized with
+    /// [`init_wallet_db`](wallet::init::init_wallet_db). A read-only handle can be used
+    /// concurrently with a handle that is writing to the same file, for example to run export
+    /// or analytics jobs while the wallet is being synchronized. If the writing handle has
+    /// enabled write-ahead logging with [`WalletDb::enable_write_ahead_log`], reads are not
+    /// blocked by its writes; use [`WalletDb::with_snapshot`] to perform several reads against
+    /// a single consistent state of the wallet.
+    ///
+    /// Any attempt to modify the wallet through the returned handle fails.
+    pub fn for_path_read_only<F: AsRef<Path>>(path: F, params: P) -> Result<Self, rusqlite::Error> {
+        Connection::open_with_flags(
+            path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY
+                | OpenFlags::SQLITE_OPEN_URI
+                | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )
+        .and_then(move |conn| {
+            rusqlite::vtab::array::load_module(&conn)?;
+            Ok(WalletDb { conn, params })
+        })
+    }
+
+    /// Switches the wallet database to write-ahead logging.
+    ///
+    /// In this mode, the wallet can be read through other handles (such as those opened with
+    /// [`WalletDb::for_path_read_only`]) while it is being written, and each read transaction
+    /// observes a snapshot of the wallet as of the time that it began. The setting is stored
+    /// in the database file, and so only needs to be enabled once.
+    ///
+    /// Returns whether write-ahead logging is in effect; SQLite does not support it for
+    /// in-memory databases.
+    pub fn enable_write_ahead_log(&self) -> Result<bool, rusqlite::Error> {
+        let mode = self
+            .conn
+            .pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))?;
+        Ok(mode.eq_ignore_ascii_case("wal"))
+    }
+
+    /// Calls the given function with a view of the wallet that does not change for the
+    /// duration of the call.
+    ///
+    /// All reads made through the view observe the state of the wallet as of the first of
+    /// them, even if another handle writes to the wallet in the meantime. This requires
+    /// write-ahead logging to have been enabled with [`WalletDb::enable_write_ahead_log`];
+    /// otherwise, writes by other handles are blocked until the call returns.
+    pub fn with_snapshot<F, A, E: From<rusqlite::Error>>(&mut self, f: F) -> Result<A, E>
+    where
+        F: FnOnce(&WalletDb<SqlTransaction<'_>, P>) -> Result<A, E>,
+    {
+        let tx = self
+            .conn
+            .transaction_with_behavior(TransactionBehavior::Deferred)?;
+        let wdb = WalletDb {
+            conn: SqlTransaction(&tx),
+            params: self.params.clone(),
+        };
+        let result = f(&wdb)?;
+        tx.rollback()?;
+        Ok(result)
+    }
+
     pub fn transactionally<F, A, E: From<rusqlite::Error>>(&mut self, f: F) -> Result<A, E>
     where
         F: FnOnce(&mut WalletDb<SqlTransaction<'_>, P>) -> Result<A, E>,
@@ -201,11 +306,500 @@ impl<P: consensus::Parameters + Clone> WalletDb<Connection, P> {
         tx.commit()?;
         Ok(result)
     }
+
+    /// Imports an account from its unified full viewing key.
+    ///
+    /// The imported account can track its balance and transaction history, but cannot
+    /// spend. The key may be restricted to a subset of pools, for example one exported
+    /// with [`UnifiedFullViewingKey::export_viewing_key`]; only funds in those pools will
+    /// be detected.
+    pub fn import_account_ufvk(
+        &mut self,
+        ufvk: &UnifiedFullViewingKey,
+        birthday: &AccountBirthday,
+    ) -> Result<AccountId, SqliteClientError> {
+        self.transactionally(|wdb| {
+            wallet::add_account(
+                wdb.conn.0,
+                &wdb.params,
+                AccountSource::Imported,
+                wallet::ViewingKey::Full(Box::new(ufvk.clone())),
+                birthday,
+            )
+        })
+    }
+
+    /// Imports an account from its unified incoming viewing key.
+    ///
+    /// The imported account can detect funds received at its external addresses, but
+    /// cannot detect when those funds are spent, and so cannot maintain an accurate
+    /// balance. This is intended for giving auditors access to an account's incoming
+    /// payments.
+    pub fn import_account_uivk(
+        &mut self,
+        uivk: &UnifiedIncomingViewingKey,
+        birthday: &AccountBirthday,
+    ) -> Result<AccountId, SqliteClientError> {
+        self.transactionally(|wdb| {
+            wallet::add_account(
+                wdb.conn.0,
+                &wdb.params,
+                AccountSource::Imported,
+                wallet::ViewingKey::Incoming(Box::new(uivk.clone())),
+                birthday,
+            )
+        })
+    }
+
+    /// Rescans the chain from the given height, while retaining data that cannot be
+    /// recovered from the chain.
+    ///
+    /// The note commitment trees and the spentness of the wallet's notes are rolled back to
+    /// the state as of the block preceding `from_height`, and all blocks from that height to
+    /// the current chain tip are queued for scanning with [`ScanPriority::Historic`]
+    /// priority. The wallet's view of the chain tip is not changed. Accounts, addresses, and
+    /// transaction data (including memos and sent notes) are retained; transactions mined in
+    /// the rescanned range are treated as unmined until their blocks are scanned again.
+    ///
+    /// Spends by transactions that the wallet created are retained, so that the notes they
+    /// spend do not become spendable again. The full data of the other spending transactions
+    /// in the range is requested again via [`TransactionDataRequest::Enhancement`], because
+    /// their transparent spends are not visible in compact blocks.
+    ///
+    /// Returns [`SqliteClientError::RequestedRewindInvalid`] if the note commitment trees
+    /// cannot be rolled back to the required height.
+    pub fn rescan_from(&mut self, from_height: BlockHeight) -> Result<(), SqliteClientError> {
+        self.transactionally(|wdb| wallet::rescan_from(wdb.conn.0, &wdb.params, from_height))
+    }
+
+    /// Returns the views that are retained in the wallet database only for compatibility,
+    /// along with any views or triggers created by the application that depend upon them.
+    ///
+    /// Applications that query the wallet database directly should check that they do not
+    /// use any of the returned views, which will be removed in a future release of this
+    /// crate. See [`wallet::init::schema::deprecated_view_usage`] for details.
+    pub fn deprecated_view_usage(
+        &self,
+    ) -> Result<Vec<wallet::init::schema::DeprecatedViewUsage>, SqliteClientError> {
+        Ok(wallet::init::schema::deprecated_view_usage(&self.conn)?)
+    }
+
+    /// Checks that the account balances cached by [`WalletRead::get_wallet_summary`] are
+    /// consistent with the balances computed directly from the wallet's notes.
+    ///
+    /// If an inconsistency is found, the cache is cleared and
+    /// [`SqliteClientError::CorruptedData`] is returned.
+    pub fn check_account_balances_cache(&mut self) -> Result<(), SqliteClientError> {
+        let tx = self.conn.transaction()?;
+        let result = wallet::check_account_balances_cache(&tx);
+        if result.is_err() {
+            tx.execute("DELETE FROM account_balances", [])?;
+        }
+        tx.commit()?;
+        result
+    }
+
+    /// Checks the witnesses of the wallet's unspent notes that were mined in the given range
+    /// of heights, and repairs the note commitment tree data for any that are invalid.
+    ///
+    /// A witness is invalid if it cannot be computed from the stored tree data, or if it is
+    /// inconsistent with the tree's root at the latest checkpoint or with the subtree roots
+    /// obtained from the server. Instead of requiring the whole chain to be rescanned, the
+    /// data for each affected shard of the tree is discarded, and the blocks covered by that
+    /// shard are queued for scanning with [`ScanPriority::FoundNote`] priority. The affected
+    /// notes become spendable again once those blocks have been scanned.
+    ///
+    /// Returns the identifiers of the notes whose witnesses were found to be invalid.
+    ///
+    /// [`ScanPriority::FoundNote`]: zcash_client_backend::data_api::scanning::ScanPriority::FoundNote
+    pub fn repair_witnesses(
+        &mut self,
+        range: Range<BlockHeight>,
+    ) -> Result<Vec<NoteId>, SqliteClientError> {
+        self.transactionally(|wdb| {
+            #[allow(unused_mut)]
+            let mut invalid = wallet::repair::repair_witnesses::<
+                sapling::Node,
+                { sapling::NOTE_COMMITMENT_TREE_DEPTH },
+                SAPLING_SHARD_HEIGHT,
+            >(
+                wdb.conn.0,
+                ShieldedProtocol::Sapling,
+                SAPLING_TABLES_PREFIX,
+                &range,
+            )?;
+
+            #[cfg(feature = "orchard")]
+            invalid.extend(wallet::repair::repair_witnesses::<
+                orchard::tree::MerkleHashOrchard,
+                { ORCHARD_SHARD_HEIGHT * 2 },
+                ORCHARD_SHARD_HEIGHT,
+            >(
+                wdb.conn.0,
+                ShieldedProtocol::Orchard,
+                ORCHARD_TABLES_PREFIX,
+                &range,
+            )?);
+
+            Ok(invalid)
+        })
+    }
+
+    /// Prunes note commitment tree data that the wallet no longer requires, returning the
+    /// changes made to each tree.
+    ///
+    /// The stable height of each tree is `stability_depth` blocks below its latest checkpoint;
+    /// a `stability_depth` of less than 100 blocks (the maximum depth of a chain rewind) is
+    /// treated as 100. Checkpoints below the stable height are removed, and the data for each
+    /// fully-scanned shard that was completed below the stable height, and in which all of
+    /// the wallet's notes were spent below the stable height, is replaced by the shard's root.
+    /// Witnesses for the wallet's unspent notes are unaffected.
+    ///
+    /// This can be called periodically (for example, after each sync) to keep the size of the
+    /// wallet database bounded.
+    pub fn prune_note_commitment_trees(
+        &mut self,
+        stability_depth: u32,
+    ) -> Result<Vec<wallet::pruning::TreePruning>, SqliteClientError> {
+        self.transactionally(|wdb| {
+            #[allow(unused_mut)]
+            let mut results = vec![wallet::pruning::prune_tree::<
+                sapling::Node,
+                SAPLING_SHARD_HEIGHT,
+            >(
+                wdb.conn.0,
+                ShieldedProtocol::Sapling,
+                SAPLING_TABLES_PREFIX,
+                stability_depth,
+            )?];
+
+            #[cfg(feature = "orchard")]
+            results.push(wallet::pruning::prune_tree::<
+                orchard::tree::MerkleHashOrchard,
+                ORCHARD_SHARD_HEIGHT,
+            >(
+                wdb.conn.0,
+                ShieldedProtocol::Orchard,
+                ORCHARD_TABLES_PREFIX,
+                stability_depth,
+            )?);
+
+            Ok(results)
+        })
+    }
+
+    /// Returns size metrics for each of the wallet's note commitment trees.
+    pub fn note_commitment_tree_metrics(
+        &self,
+    ) -> Result<Vec<wallet::pruning::TreeMetrics>, SqliteClientError> {
+        #[allow(unused_mut)]
+        let mut metrics = vec![wallet::pruning::tree_metrics::<sapling::Node>(
+            &self.conn,
+            ShieldedProtocol::Sapling,
+            SAPLING_TABLES_PREFIX,
+        )?];
+
+        #[cfg(feature = "orchard")]
+        metrics.push(wallet::pruning::tree_metrics::<
+            orchard::tree::MerkleHashOrchard,
+        >(
+            &self.conn,
+            ShieldedProtocol::Orchard,
+            ORCHARD_TABLES_PREFIX,
+        )?);
+
+        Ok(metrics)
+    }
+
+    /// Exports the state of the wallet's note commitment trees as of the given height.
+    ///
+    /// The returned blob contains the raw shard, cap, and checkpoint data of each tree,
+    /// truncated to the checkpoint at `height`, without being deserialized. It is intended to
+    /// allow bugs in witness construction to be reproduced by importing the state into
+    /// another wallet database with [`WalletDb::import_tree_state`]; its format is not
+    /// stable across versions of this crate.
+    ///
+    /// The wallet is not modified. Returns an error if the trees do not have a checkpoint
+    /// at `height`.
+    pub fn export_tree_state(&mut self, height: BlockHeight) -> Result<Vec<u8>, SqliteClientError> {
+        // The trees are truncated within a transaction that is never committed.
+        let tx = self.conn.transaction()?;
+
+        let mut data = vec![TREE_STATE_VERSION];
+        data.extend_from_slice(&u32::from(height).to_le_bytes());
+
+        commitment_tree::truncate_to_checkpoint::<
+            sapling::Node,
+            { sapling::NOTE_COMMITMENT_TREE_DEPTH },
+            SAPLING_SHARD_HEIGHT,
+        >(&tx, SAPLING_TABLES_PREFIX, height)?;
+        commitment_tree::export_tree_state(&tx, SAPLING_TABLES_PREFIX, &mut data)
+            .map_err(|e| SqliteClientError::CommitmentTree(ShardTreeError::Storage(e)))?;
+
+        #[cfg(feature = "orchard")]
+        {
+            commitment_tree::truncate_to_checkpoint::<
+                orchard::tree::MerkleHashOrchard,
+                { ORCHARD_SHARD_HEIGHT * 2 },
+                ORCHARD_SHARD_HEIGHT,
+            >(&tx, ORCHARD_TABLES_PREFIX, height)?;
+            data.push(1);
+            commitment_tree::export_tree_state(&tx, ORCHARD_TABLES_PREFIX, &mut data)
+                .map_err(|e| SqliteClientError::CommitmentTree(ShardTreeError::Storage(e)))?;
+        }
+        #[cfg(not(feature = "orchard"))]
+        data.push(0);
+
+        tx.rollback()?;
+        Ok(data)
+    }
+
+    /// Replaces the wallet's note commitment trees with the state exported by
+    /// [`WalletDb::export_tree_state`], returning the height at which it was exported.
+    ///
+    /// Only the note commitment trees are modified; this is intended for use with an
+    /// otherwise empty wallet database in a test environment, and the imported trees are
+    /// not checked for consistency with the rest of the wallet's data.
+    pub fn import_tree_state(&mut self, data: &[u8]) -> Result<BlockHeight, SqliteClientError> {
+        let (version, mut reader) = data
+            .split_first()
+            .ok_or_else(|| SqliteClientError::CorruptedData("Tree state is empty.".to_owned()))?;
+        if *version != TREE_STATE_VERSION {
+            return Err(SqliteClientError::CorruptedData(format!(
+                "Unsupported tree state version {}",
+                version
+            )));
+        }
+        if reader.len() < 4 {
+            return Err(SqliteClientError::CorruptedData(
+                "Tree state is truncated.".to_owned(),
+            ));
+        }
+        let (height, rest) = reader.split_at(4);
+        let height = BlockHeight::from(u32::from_le_bytes(height.try_into().unwrap()));
+        reader = rest;
+
+        self.transactionally(|wdb| {
+            commitment_tree::import_tree_state(wdb.conn.0, SAPLING_TABLES_PREFIX, &mut reader)
+                .map_err(|e| SqliteClientError::CommitmentTree(ShardTreeError::Storage(e)))?;
+
+            let (has_orchard, rest) = reader.split_first().ok_or_else(|| {
+                SqliteClientError::CorruptedData("Tree state is truncated.".to_owned())
+            })?;
+            reader = rest;
+            match *has_orchard {
+                0 => (),
+                #[cfg(feature = "orchard")]
+                1 => commitment_tree::import_tree_state(
+                    wdb.conn.0,
+                    ORCHARD_TABLES_PREFIX,
+                    &mut reader,
+                )
+                .map_err(|e| SqliteClientError::CommitmentTree(ShardTreeError::Storage(e)))?,
+                #[cfg(not(feature = "orchard"))]
+                1 => return Err(SqliteClientError::CorruptedData(
+                    "Tree state contains an Orchard tree, but the orchard feature is not enabled."
+                        .to_owned(),
+                )),
+                _ => {
+                    return Err(SqliteClientError::CorruptedData(
+                        "Invalid Orchard tree flag in tree state.".to_owned(),
+                    ))
+                }
+            }
+
+            if !reader.is_empty() {
+                return Err(SqliteClientError::CorruptedData(
+                    "Tree state is followed by trailing data.".to_owned(),
+                ));
+            }
+
+            Ok(height)
+        })
+    }
+
+    /// Enables or disables the wallet's event journal.
+    ///
+    /// While the journal is enabled, significant changes to the wallet's state are recorded
+    /// in the wallet database; see [`wallet::journal`] for details. The journal is disabled
+    /// by default.
+    pub fn set_event_journal_enabled(&mut self, enabled: bool) -> Result<(), SqliteClientError> {
+        self.transactionally(|wdb| wallet::journal::set_enabled(wdb.conn.0, enabled))
+    }
+
+    /// Returns whether the wallet's event journal is enabled.
+    pub fn is_event_journal_enabled(&self) -> Result<bool, SqliteClientError> {
+        wallet::journal::is_enabled(&self.conn)
+    }
+
+    /// Returns the events in the wallet's event journal that were recorded at or after
+    /// `since`, or all events if `since` is `None`, in the order in which they were recorded.
+    ///
+    /// Each event's [`Display`] implementation formats it as a single line of text, suitable
+    /// for attaching to a support request.
+    ///
+    /// [`Display`]: std::fmt::Display
+    pub fn export_event_journal(
+        &self,
+        since: Option<time::OffsetDateTime>,
+    ) -> Result<Vec<wallet::journal::WalletEvent>, SqliteClientError> {
+        wallet::journal::get_events(&self.conn, since)
+    }
+
+    /// Adds a contact with the given name and address to the wallet's address book, or renames
+    /// the contact if the address is already in the address book.
+    ///
+    /// Returns an error if the address cannot be parsed, or if it shares a receiver with the
+    /// address of a different contact. See [`wallet::contacts`] for details.
+    pub fn put_contact(
+        &mut self,
+        name: &str,
+        address: &str,
+    ) -> Result<wallet::contacts::ContactId, SqliteClientError> {
+        self.transactionally(|wdb| {
+            wallet::contacts::put_contact(wdb.conn.0, &wdb.params, name, address)
+        })
+    }
+
+    /// Returns the contacts in the wallet's address book, in order of name.
+    pub fn list_contacts(&self) -> Result<Vec<wallet::contacts::Contact>, SqliteClientError> {
+        wallet::contacts::list_contacts(&self.conn)
+    }
+
+    /// Returns the contact whose address is, or shares a receiver with, the given address.
+    ///
+    /// Returns an error if the address cannot be parsed.
+    pub fn find_contact_for_address(
+        &self,
+        address: &str,
+    ) -> Result<Option<wallet::contacts::Contact>, SqliteClientError> {
+        wallet::contacts::find_contact_for_address(&self.conn, &self.params, address)
+    }
+
+    /// Schedules a payment from the given account that is first due at `first_due`, and recurs
+    /// at the given interval thereafter.
+    ///
+    /// Due payments are returned by [`WalletRead::payments_due`]. Due times are computed in UTC,
+    /// and are stored to the nearest second.
+    pub fn schedule_payment(
+        &mut self,
+        account: AccountId,
+        payment: &zcash_client_backend::zip321::Payment,
+        interval: PaymentInterval,
+        first_due: time::OffsetDateTime,
+    ) -> Result<ScheduledPaymentId, SqliteClientError> {
+        self.transactionally(|wdb| {
+            wallet::scheduled::insert_scheduled_payment(
+                wdb.conn.0, account, payment, interval, first_due,
+            )
+        })
+    }
+
+    /// Records that the next payment of the given schedule has been made, and returns the time
+    /// at which the following payment is due.
+    ///
+    /// This should be called once the transaction created for the payment has been stored in
+    /// the wallet, so that the payment is not returned by [`WalletRead::payments_due`] again
+    /// until its next due time.
+    pub fn record_scheduled_payment(
+        &mut self,
+        id: ScheduledPaymentId,
+    ) -> Result<time::OffsetDateTime, SqliteClientError> {
+        self.transactionally(|wdb| wallet::scheduled::record_scheduled_payment(wdb.conn.0, id))
+    }
+
+    /// Removes the given scheduled payment, so that no further payments fall due.
+    pub fn cancel_scheduled_payment(
+        &mut self,
+        id: ScheduledPaymentId,
+    ) -> Result<(), SqliteClientError> {
+        self.transactionally(|wdb| wallet::scheduled::cancel_scheduled_payment(wdb.conn.0, id))
+    }
+
+    /// Returns the value of the named wallet setting, or `None` if it has not been set.
+    ///
+    /// The value is converted using the [`FromSql`] implementation of `T`, and an error is
+    /// returned if it was stored as a value of an incompatible type. See [`wallet::settings`]
+    /// for details.
+    ///
+    pub fn get_setting<T: FromSql>(&self, name: &str) -> Result<Option<T>, SqliteClientError> {
+        wallet::settings::get_setting(&self.conn, name)
+    }
+
+    /// Sets the value of the named wallet setting, replacing any existing value.
+    pub fn put_setting<T: ToSql>(&mut self, name: &str, value: T) -> Result<(), SqliteClientError> {
+        self.transactionally(|wdb| wallet::settings::put_setting(wdb.conn.0, name, value))
+    }
+
+    /// Removes the named wallet setting, if it has been set.
+    pub fn remove_setting(&mut self, name: &str) -> Result<(), SqliteClientError> {
+        self.transactionally(|wdb| wallet::settings::remove_setting(wdb.conn.0, name))
+    }
+
+    /// Records that the user has verified their backup of the given account's keys, as of the
+    /// current chain tip, and returns the height of the chain tip.
+    ///
+    /// Value received by the account in later blocks is reported by [`Self::backup_status`]
+    /// as not backed up. Returns an error if the chain tip is not yet known.
+    pub fn record_backup_verified(
+        &mut self,
+        account: AccountId,
+    ) -> Result<BlockHeight, SqliteClientError> {
+        self.transactionally(|wdb| wallet::backup::record_backup_verified(wdb.conn.0, account))
+    }
+
+    /// Returns the height at which the backup of the given account was last verified, and the
+    /// value that the account has received from other parties since then. See
+    /// [`wallet::backup`] for details.
+    pub fn backup_status(
+        &self,
+        account: AccountId,
+    ) -> Result<wallet::backup::BackupStatus, SqliteClientError> {
+        wallet::backup::get_backup_status(&self.conn, account)
+    }
+
+    /// Includes or excludes the given account from trial decryption when scanning blocks.
+    ///
+    /// Excluding accounts that are no longer in active use reduces the cost of scanning for
+    /// wallets with many accounts. While an account is excluded, notes received by it
+    /// (including change from its own transactions) are not detected, and its balance may
+    /// become stale. When the account is included again, the blocks that may have been scanned
+    /// while it was excluded are queued to be scanned again, so that those notes are found.
+    ///
+    /// All accounts are included by default.
+    pub fn set_account_scanning_enabled(
+        &mut self,
+        account: AccountId,
+        enabled: bool,
+    ) -> Result<(), SqliteClientError> {
+        self.transactionally(|wdb| {
+            wallet::set_account_scanning_enabled(wdb.conn.0, &wdb.params, account, enabled)
+        })
+    }
+
+    /// Returns whether blocks are scanned for notes belonging to the given account.
+    pub fn is_account_scanning_enabled(
+        &self,
+        account: AccountId,
+    ) -> Result<bool, SqliteClientError> {
+        wallet::is_account_scanning_enabled(&self.conn, account)
+    }
+
+    /// Returns the double-entry ledger entries for the transaction with the given ID.
+    ///
+    /// The entries for a transaction always sum to zero; see [`wallet::ledger`] for the
+    /// conventions that they follow.
+    pub fn get_ledger_entries(
+        &self,
+        txid: TxId,
+    ) -> Result<Vec<wallet::ledger::LedgerEntry>, SqliteClientError> {
+        wallet::ledger::get_ledger_entries(&self.conn, txid)
+    }
 }
 
 impl<C: Borrow<rusqlite::Connection>, P: consensus::Parameters> InputSource for WalletDb<C, P> {
     type Error = SqliteClientError;
-    type NoteRef = ReceivedNoteId;
+    type NoteRef = NoteId;
     type AccountId = AccountId;
 
     fn get_spendable_note(
@@ -409,6 +1003,17 @@ impl<C: Borrow<rusqlite::Connection>, P: consensus::Parameters> WalletRead for W
             .map(|res| res.map(|(addr, _)| addr))
     }
 
+    fn address_usage(&self, account: AccountId) -> Result<Vec<AddressUsage>, Self::Error> {
+        wallet::address_usage(self.conn.borrow(), &self.params, account)
+    }
+
+    fn find_account_for_address(
+        &self,
+        address: &Address,
+    ) -> Result<Option<(AccountId, Scope)>, Self::Error> {
+        wallet::find_account_for_address(self.conn.borrow(), &self.params, address)
+    }
+
     fn get_account_birthday(&self, account: AccountId) -> Result<BlockHeight, Self::Error> {
         wallet::account_birthday(self.conn.borrow(), account).map_err(SqliteClientError::from)
     }
@@ -421,14 +1026,64 @@ impl<C: Borrow<rusqlite::Connection>, P: consensus::Parameters> WalletRead for W
         &self,
         min_confirmations: u32,
     ) -> Result<Option<WalletSummary<Self::AccountId>>, Self::Error> {
-        // This will return a runtime error if we call `get_wallet_summary` from two
-        // threads at the same time, as transactions cannot nest.
-        wallet::get_wallet_summary(
-            &self.conn.borrow().unchecked_transaction()?,
-            &self.params,
-            min_confirmations,
-            &SubtreeScanProgress,
-        )
+        self.get_wallet_summary_with_dust_filter(min_confirmations, DustFilter::IncludeDust)
+    }
+
+    fn get_wallet_summary_with_dust_filter(
+        &self,
+        min_confirmations: u32,
+        dust_filter: DustFilter,
+    ) -> Result<Option<WalletSummary<Self::AccountId>>, Self::Error> {
+        let conn = self.conn.borrow();
+        if conn.is_autocommit() {
+            // This will return a runtime error if we call `get_wallet_summary` from two
+            // threads at the same time, as transactions cannot nest.
+            let tx = conn.unchecked_transaction()?;
+            let summary = wallet::get_wallet_summary(
+                &tx,
+                &self.params,
+                min_confirmations,
+                &dust_filter,
+                &SubtreeScanProgress,
+            )?;
+            // Commit so that any balances computed by this call are retained in the cache.
+            // This transaction makes no changes if the database is read-only.
+            tx.commit()?;
+            Ok(summary)
+        } else {
+            // We are already within a transaction, such as that of a snapshot, which keeps
+            // the wallet state consistent for the duration of the call.
+            wallet::get_wallet_summary(
+                conn,
+                &self.params,
+                min_confirmations,
+                &dust_filter,
+                &SubtreeScanProgress,
+            )
+        }
+    }
+
+    fn get_balance_at_height(
+        &self,
+        account: Self::AccountId,
+        height: BlockHeight,
+    ) -> Result<Option<HistoricalBalance>, Self::Error> {
+        wallet::get_balance_at_height(self.conn.borrow(), &self.params, account, height)
+    }
+
+    fn get_account_statistics(
+        &self,
+        account: Self::AccountId,
+        granularity: StatisticsGranularity,
+    ) -> Result<Vec<PeriodStatistics>, Self::Error> {
+        wallet::get_account_statistics(self.conn.borrow(), account, granularity)
+    }
+
+    fn payments_due(
+        &self,
+        now: time::OffsetDateTime,
+    ) -> Result<Vec<ScheduledPayment<Self::AccountId>>, Self::Error> {
+        wallet::scheduled::payments_due(self.conn.borrow(), now)
     }
 
     fn chain_height(&self) -> Result<Option<BlockHeight>, Self::Error> {
@@ -484,6 +1139,13 @@ impl<C: Borrow<rusqlite::Connection>, P: consensus::Parameters> WalletRead for W
         wallet::get_unified_full_viewing_keys(self.conn.borrow(), &self.params)
     }
 
+    fn get_unified_full_viewing_keys_for_scanning(
+        &self,
+        range: &Range<BlockHeight>,
+    ) -> Result<HashMap<AccountId, UnifiedFullViewingKey>, Self::Error> {
+        wallet::get_unified_full_viewing_keys_for_scanning(self.conn.borrow(), &self.params, range)
+    }
+
     fn get_memo(&self, note_id: NoteId) -> Result<Option<Memo>, Self::Error> {
         let sent_memo = wallet::get_sent_memo(self.conn.borrow(), note_id)?;
         if sent_memo.is_some() {
@@ -498,6 +1160,18 @@ impl<C: Borrow<rusqlite::Connection>, P: consensus::Parameters> WalletRead for W
             .map(|res| res.map(|(_, tx)| tx))
     }
 
+    fn transaction_data_requests(&self) -> Result<Vec<TransactionDataRequest>, Self::Error> {
+        wallet::transaction_data_requests(self.conn.borrow(), &self.params)
+    }
+
+    fn export_account_audit_bundle(
+        &self,
+        account: AccountId,
+        height_range: Range<BlockHeight>,
+    ) -> Result<AuditBundle, Self::Error> {
+        wallet::export_account_audit_bundle(self.conn.borrow(), &self.params, account, height_range)
+    }
+
     fn get_sapling_nullifiers(
         &self,
         query: NullifierQuery,
@@ -529,6 +1203,11 @@ impl<C: Borrow<rusqlite::Connection>, P: consensus::Parameters> WalletRead for W
     ) -> Result<HashMap<TransparentAddress, NonNegativeAmount>, Self::Error> {
         wallet::get_transparent_balances(self.conn.borrow(), &self.params, account, max_height)
     }
+
+    #[cfg(feature = "transparent-inputs")]
+    fn get_unspent_transparent_outpoints(&self) -> Result<Vec<(AccountId, OutPoint)>, Self::Error> {
+        wallet::get_unspent_transparent_outpoints(self.conn.borrow())
+    }
 }
 
 impl<P: consensus::Parameters> WalletWrite for WalletDb<rusqlite::Connection, P> {
@@ -614,6 +1293,10 @@ impl<P: consensus::Parameters> WalletWrite for WalletDb<rusqlite::Connection, P>
         Ok(())
     }
 
+    fn put_block_nullifiers(&mut self, blocks: Vec<BlockNullifiers>) -> Result<(), Self::Error> {
+        self.transactionally(|wdb| wallet::put_block_nullifiers(wdb.conn.0, &blocks))
+    }
+
     #[tracing::instrument(skip_all, fields(height = blocks.first().map(|b| u32::from(b.height())), count = blocks.len()))]
     #[allow(clippy::type_complexity)]
     fn put_blocks(
@@ -654,6 +1337,12 @@ impl<P: consensus::Parameters> WalletWrite for WalletDb<rusqlite::Connection, P>
                     return Err(SqliteClientError::NonSequentialBlocks);
                 }
 
+                // A block that is already stored with the same hash is being scanned again,
+                // for example after an interruption before the wallet recorded its progress.
+                // Its data is re-inserted idempotently, but its notes are not reported again.
+                let rescanned =
+                    wallet::get_block_hash(wdb.conn.0, block.height())? == Some(block.block_hash());
+
                 // Insert the block into the database.
                 wallet::put_block(
                     wdb.conn.0,
@@ -671,6 +1360,35 @@ impl<P: consensus::Parameters> WalletWrite for WalletDb<rusqlite::Connection, P>
                 for tx in block.transactions() {
                     let tx_row = wallet::put_tx_meta(wdb.conn.0, tx, block.height())?;
 
+                    if !rescanned {
+                        let detail = format!(
+                            "sapling_received={} sapling_spent={}",
+                            tx.sapling_outputs().len(),
+                            tx.sapling_spends().len(),
+                        );
+                        #[cfg(feature = "orchard")]
+                        let detail = format!(
+                            "{} orchard_received={} orchard_spent={}",
+                            detail,
+                            tx.orchard_outputs().len(),
+                            tx.orchard_spends().len(),
+                        );
+                        #[cfg(feature = "transparent-inputs")]
+                        let detail = format!(
+                            "{} transparent_spent={}",
+                            detail,
+                            tx.transparent_spends().len(),
+                        );
+                        wallet::journal::record_event(
+                            wdb.conn.0,
+                            WalletEventKind::NotesDetected,
+                            Some(block.height()),
+                            None,
+                            Some(&tx.txid()),
+                            Some(&detail),
+                        )?;
+                    }
+
                     // Mark notes as spent and remove them from the scanning cache
                     for spend in tx.sapling_spends() {
                         wallet::sapling::mark_sapling_note_spent(wdb.conn.0, tx_row, spend.nf())?;
@@ -679,6 +1397,18 @@ impl<P: consensus::Parameters> WalletWrite for WalletDb<rusqlite::Connection, P>
                     for spend in tx.orchard_spends() {
                         wallet::orchard::mark_orchard_note_spent(wdb.conn.0, tx_row, spend.nf())?;
                     }
+                    #[cfg(feature = "transparent-inputs")]
+                    for spend in tx.transparent_spends() {
+                        wallet::mark_transparent_utxo_spent(wdb.conn.0, tx_row, spend.nf())?;
+                    }
+                    let spends_notes = !tx.sapling_spends().is_empty();
+                    #[cfg(feature = "orchard")]
+                    let spends_notes = spends_notes || !tx.orchard_spends().is_empty();
+                    #[cfg(feature = "transparent-inputs")]
+                    let spends_notes = spends_notes || !tx.transparent_spends().is_empty();
+                    if spends_notes {
+                        wallet::mark_external_spend(wdb.conn.0, tx_row)?;
+                    }
 
                     for output in tx.sapling_outputs() {
                         // Check whether this note was spent in a later block range that
@@ -696,6 +1426,16 @@ impl<P: consensus::Parameters> WalletWrite for WalletDb<rusqlite::Connection, P>
                             .flatten();
 
                         wallet::sapling::put_received_note(wdb.conn.0, output, tx_row, spent_in)?;
+                        if let Some(spent_in) = spent_in {
+                            wallet::mark_external_spend(wdb.conn.0, spent_in)?;
+                        }
+                        wallet::mark_scanned_receiver_used(
+                            wdb.conn.0,
+                            &wdb.params,
+                            *output.account_id(),
+                            &Receiver::Sapling(output.note().recipient()),
+                            block.height(),
+                        )?;
                     }
                     #[cfg(feature = "orchard")]
                     for output in tx.orchard_outputs() {
@@ -714,6 +1454,16 @@ impl<P: consensus::Parameters> WalletWrite for WalletDb<rusqlite::Connection, P>
                             .flatten();
 
                         wallet::orchard::put_received_note(wdb.conn.0, output, tx_row, spent_in)?;
+                        if let Some(spent_in) = spent_in {
+                            wallet::mark_external_spend(wdb.conn.0, spent_in)?;
+                        }
+                        wallet::mark_scanned_receiver_used(
+                            wdb.conn.0,
+                            &wdb.params,
+                            *output.account_id(),
+                            &Receiver::Orchard(output.note().recipient()),
+                            block.height(),
+                        )?;
                     }
                 }
 
@@ -1025,6 +1775,15 @@ impl<P: consensus::Parameters> WalletWrite for WalletDb<rusqlite::Connection, P>
                     },
                     &note_positions,
                 )?;
+
+                wallet::journal::record_event(
+                    wdb.conn.0,
+                    WalletEventKind::BlocksScanned,
+                    Some(start_positions.height),
+                    Some(last_scanned_height + 1),
+                    None,
+                    None,
+                )?;
             }
 
             Ok(())
@@ -1044,12 +1803,79 @@ impl<P: consensus::Parameters> WalletWrite for WalletDb<rusqlite::Connection, P>
         );
     }
 
+    #[cfg(feature = "transparent-inputs")]
+    fn put_utxo(
+        &mut self,
+        outpoint: OutPoint,
+        txout: TxOut,
+        height: BlockHeight,
+    ) -> Result<Self::UtxoRef, Self::Error> {
+        let output = WalletTransparentOutput::from_parts(outpoint, txout, height)
+            .ok_or(SqliteClientError::UnsupportedTransparentScript)?;
+        wallet::put_received_transparent_utxo(&self.conn, &self.params, &output)
+    }
+
+    #[cfg(feature = "transparent-inputs")]
+    fn notify_address_checked(
+        &mut self,
+        address: &TransparentAddress,
+        as_of_height: BlockHeight,
+    ) -> Result<(), Self::Error> {
+        wallet::notify_address_checked(&self.conn, &self.params, address, as_of_height)
+    }
+
     fn store_decrypted_tx(
         &mut self,
         d_tx: DecryptedTransaction<AccountId>,
     ) -> Result<(), Self::Error> {
         self.transactionally(|wdb| {
-            let tx_ref = wallet::put_tx_data(wdb.conn.0, d_tx.tx(), None, None)?;
+            let fee = wallet::compute_fee(wdb.conn.0, &wdb.params, d_tx.tx())?;
+            let tx_ref = wallet::put_tx_data(wdb.conn.0, d_tx.tx(), fee, None)?;
+            if let Some(height) = d_tx.mined_height() {
+                wallet::set_tx_mined_height(wdb.conn.0, d_tx.tx().txid(), height)?;
+            }
+
+            // Record any transparent outputs received at the wallet's addresses. The height at
+            // which an output was received is required, so outputs of unmined transactions are
+            // recorded once the transaction has been mined.
+            #[cfg(feature = "transparent-inputs")]
+            if let Some(height) = d_tx.mined_height() {
+                let is_coinbase = d_tx
+                    .tx()
+                    .transparent_bundle()
+                    .map_or(false, |b| b.is_coinbase());
+                for (output_index, txout) in d_tx
+                    .tx()
+                    .transparent_bundle()
+                    .iter()
+                    .flat_map(|b| b.vout.iter())
+                    .enumerate()
+                {
+                    if let Some(address) = txout.recipient_address() {
+                        if let Some((account_id, _)) = wallet::find_account_for_address(
+                            wdb.conn.0,
+                            &wdb.params,
+                            &Address::Transparent(address),
+                        )? {
+                            let outpoint = OutPoint::new(
+                                *d_tx.tx().txid().as_ref(),
+                                u32::try_from(output_index).unwrap(),
+                            );
+                            let output =
+                                WalletTransparentOutput::from_parts(outpoint, txout.clone(), height)
+                                    .expect("The output has a recipient address.")
+                                    .with_coinbase(is_coinbase);
+                            wallet::put_legacy_transparent_utxo(
+                                wdb.conn.0,
+                                &wdb.params,
+                                &output,
+                                account_id,
+                            )?;
+                        }
+                    }
+                }
+            }
+
             let funding_accounts = wallet::get_funding_accounts(wdb.conn.0, d_tx.tx())?;
             let funding_account = funding_accounts.iter().next().copied();
             if funding_accounts.len() > 1 {
@@ -1108,6 +1934,15 @@ impl<P: consensus::Parameters> WalletWrite for WalletDb<rusqlite::Connection, P>
                     }
                     TransferType::Incoming => {
                         wallet::sapling::put_received_note(wdb.conn.0, output, tx_ref, None)?;
+                        if let Some(height) = d_tx.mined_height() {
+                            wallet::mark_address_used(
+                                wdb.conn.0,
+                                &wdb.params,
+                                *output.account(),
+                                &Receiver::Sapling(output.note().recipient()),
+                                height,
+                            )?;
+                        }
 
                         if let Some(account_id) = funding_account {
                             let recipient = Recipient::InternalAccount {
@@ -1189,6 +2024,15 @@ impl<P: consensus::Parameters> WalletWrite for WalletDb<rusqlite::Connection, P>
                     }
                     TransferType::Incoming => {
                         wallet::orchard::put_received_note(wdb.conn.0, output, tx_ref, None)?;
+                        if let Some(height) = d_tx.mined_height() {
+                            wallet::mark_address_used(
+                                wdb.conn.0,
+                                &wdb.params,
+                                *output.account(),
+                                &Receiver::Orchard(output.note().recipient()),
+                                height,
+                            )?;
+                        }
 
                         if let Some(account_id) = funding_account {
                             // Even if the recipient address is external, record the send as internal.
@@ -1293,6 +2137,8 @@ impl<P: consensus::Parameters> WalletWrite for WalletDb<rusqlite::Connection, P>
                 }
             }
 
+            wallet::update_tx_retrieval_queue(wdb.conn.0, &wdb.params, d_tx.tx(), tx_ref, fee)?;
+
             Ok(())
         })
     }
@@ -1306,6 +2152,19 @@ impl<P: consensus::Parameters> WalletWrite for WalletDb<rusqlite::Connection, P>
                 Some(sent_tx.created()),
             )?;
 
+            wallet::journal::record_event(
+                wdb.conn.0,
+                WalletEventKind::TransactionCreated,
+                None,
+                None,
+                Some(&sent_tx.tx().txid()),
+                Some(&format!(
+                    "fee={} outputs={}",
+                    u64::from(sent_tx.fee_amount()),
+                    sent_tx.outputs().len()
+                )),
+            )?;
+
             // Mark notes as spent.
             //
             // This locks the notes so they aren't selected again by a subsequent call to
@@ -1342,8 +2201,19 @@ impl<P: consensus::Parameters> WalletWrite for WalletDb<rusqlite::Connection, P>
                 wallet::mark_transparent_utxo_spent(wdb.conn.0, tx_ref, utxo_outpoint)?;
             }
 
+            // Every output, including change, is recorded in `sent_notes` at construction time,
+            // so that the transaction's inputs are fully accounted for by its outputs and fee
+            // even if the transaction is never decrypted from the chain.
             for output in sent_tx.outputs() {
-                wallet::insert_sent_output(wdb.conn.0, tx_ref, *sent_tx.account_id(), output)?;
+                wallet::put_sent_output(
+                    wdb.conn.0,
+                    *sent_tx.account_id(),
+                    tx_ref,
+                    output.output_index(),
+                    output.recipient(),
+                    output.value(),
+                    output.memo(),
+                )?;
 
                 match output.recipient() {
                     Recipient::InternalAccount {
@@ -1391,6 +2261,10 @@ impl<P: consensus::Parameters> WalletWrite for WalletDb<rusqlite::Connection, P>
                 }
             }
 
+            // Request the status of the transaction, so that the wallet learns whether it has
+            // been mined even if the block that contains it is not scanned promptly.
+            wallet::queue_tx_status_request(wdb.conn.0, sent_tx.tx().txid(), tx_ref)?;
+
             Ok(())
         })
     }
@@ -1400,6 +2274,18 @@ impl<P: consensus::Parameters> WalletWrite for WalletDb<rusqlite::Connection, P>
             wallet::truncate_to_height(wdb.conn.0, &wdb.params, block_height)
         })
     }
+
+    fn truncate_scanned_data(&mut self, range: Range<BlockHeight>) -> Result<(), Self::Error> {
+        self.transactionally(|wdb| wallet::truncate_scanned_data(wdb.conn.0, &wdb.params, range))
+    }
+
+    fn set_transaction_status(
+        &mut self,
+        txid: TxId,
+        status: TransactionStatus,
+    ) -> Result<(), Self::Error> {
+        self.transactionally(|wdb| wallet::set_transaction_status(wdb.conn.0, txid, status))
+    }
 }
 
 impl<P: consensus::Parameters> WalletCommitmentTrees for WalletDb<rusqlite::Connection, P> {
@@ -1602,6 +2488,32 @@ impl BlockDb {
     pub fn for_path<P: AsRef<Path>>(path: P) -> Result<Self, rusqlite::Error> {
         Connection::open(path).map(BlockDb)
     }
+
+    /// Checks that the cached blocks are contiguous and consistent with the blocks that have
+    /// been scanned into the given wallet database.
+    ///
+    /// Every cached block is read and decoded, so this may take some time for a large cache. If
+    /// the cache is inconsistent, it should be truncated to
+    /// [`CacheConsistency::truncation_height`] using [`BlockDb::truncate_to_height`], and the
+    /// removed blocks downloaded again.
+    ///
+    /// [`CacheConsistency::truncation_height`]: chain::CacheConsistency::truncation_height
+    pub fn check_consistency<C: Borrow<rusqlite::Connection>, P>(
+        &self,
+        wallet_db: &WalletDb<C, P>,
+    ) -> Result<chain::CacheConsistency, SqliteClientError> {
+        chain::blockdb_check_consistency(self, wallet_db.conn.borrow())
+    }
+
+    /// Removes the cached blocks above the given height.
+    ///
+    /// This is the counterpart of [`WalletWrite::truncate_to_height`] for the block cache, and
+    /// does not modify the wallet database.
+    ///
+    /// [`WalletWrite::truncate_to_height`]: zcash_client_backend::data_api::WalletWrite::truncate_to_height
+    pub fn truncate_to_height(&self, block_height: BlockHeight) -> Result<(), SqliteClientError> {
+        Ok(chain::blockdb_truncate_to_height(&self.0, block_height)?)
+    }
 }
 
 impl BlockSource for BlockDb {
@@ -1618,6 +2530,18 @@ impl BlockSource for BlockDb {
     {
         chain::blockdb_with_blocks(self, from_height, limit, with_row)
     }
+
+    fn with_blocks_in<F, DbErrT>(
+        &self,
+        range: Range<BlockHeight>,
+        order: BlockOrder,
+        with_row: F,
+    ) -> Result<(), data_api::chain::error::Error<DbErrT, Self::Error>>
+    where
+        F: FnMut(CompactBlock) -> Result<(), data_api::chain::error::Error<DbErrT, Self::Error>>,
+    {
+        chain::blockdb_with_blocks_in(self, range, order, with_row)
+    }
 }
 
 /// A block source that reads block data from disk and block metadata from a SQLite database.
@@ -1631,7 +2555,7 @@ impl BlockSource for BlockDb {
 ///
 /// where `<block_height>` is the decimal value of the height at which the block was mined, and
 /// `<block_hash>` is the hexadecimal representation of the block hash, as produced by the
-/// [`fmt::Display`] implementation for [`zcash_primitives::block::BlockHash`].
+/// [`std::fmt::Display`] implementation for [`zcash_primitives::block::BlockHash`].
 ///
 /// This block source is intended to be used with the following data flow:
 /// * When the cache is being filled:
@@ -1781,6 +2705,25 @@ impl FsBlockDb {
             block_height,
         )?)
     }
+
+    /// Checks that the block metadata is contiguous and consistent with the blocks that have
+    /// been scanned into the given wallet database.
+    ///
+    /// Only the block hashes recorded in the metadata database are checked; the block files
+    /// themselves are not read. If the metadata is inconsistent, it should be truncated to
+    /// [`CacheConsistency::truncation_height`] using [`FsBlockDb::truncate_to_height`], and the
+    /// removed blocks downloaded again.
+    ///
+    /// [`CacheConsistency::truncation_height`]: chain::CacheConsistency::truncation_height
+    pub fn check_consistency<C: Borrow<rusqlite::Connection>, P>(
+        &self,
+        wallet_db: &WalletDb<C, P>,
+    ) -> Result<chain::CacheConsistency, FsBlockDbError> {
+        Ok(chain::blockmetadb_check_consistency(
+            &self.conn,
+            wallet_db.conn.borrow(),
+        )?)
+    }
 }
 
 #[cfg(feature = "unstable")]
@@ -1798,6 +2741,18 @@ impl BlockSource for FsBlockDb {
     {
         fsblockdb_with_blocks(self, from_height, limit, with_row)
     }
+
+    fn with_blocks_in<F, DbErrT>(
+        &self,
+        range: Range<BlockHeight>,
+        order: BlockOrder,
+        with_row: F,
+    ) -> Result<(), data_api::chain::error::Error<DbErrT, Self::Error>>
+    where
+        F: FnMut(CompactBlock) -> Result<(), data_api::chain::error::Error<DbErrT, Self::Error>>,
+    {
+        fsblockdb_with_blocks_in(self, range, order, with_row)
+    }
 }
 
 #[cfg(feature = "unstable")]
@@ -1859,10 +2814,16 @@ extern crate assert_matches;
 #[cfg(test)]
 mod tests {
     use secrecy::SecretVec;
-    use zcash_client_backend::data_api::{WalletRead, WalletWrite};
-    use zcash_primitives::block::BlockHash;
+    use tempfile::NamedTempFile;
+    use zcash_client_backend::data_api::{
+        testing::conformance, AccountBirthday, WalletRead, WalletWrite,
+    };
+    use zcash_primitives::{block::BlockHash, consensus::Network};
 
-    use crate::{testing::TestBuilder, AccountId, DEFAULT_UA_REQUEST};
+    use crate::{
+        error::SqliteClientError, testing::TestBuilder, wallet::init::init_wallet_db, AccountId,
+        WalletDb, DEFAULT_UA_REQUEST,
+    };
 
     #[cfg(feature = "unstable")]
     use {
@@ -1926,6 +2887,168 @@ mod tests {
         assert_eq!(addr2, addr2_cur);
     }
 
+    /// Runs a test from the data API conformance suite against a newly initialized wallet.
+    fn run_conformance_test(test: impl FnOnce(&mut WalletDb<rusqlite::Connection, Network>)) {
+        let data_file = NamedTempFile::new().unwrap();
+        let mut db_data = WalletDb::for_path(data_file.path(), Network::TestNetwork).unwrap();
+        init_wallet_db(&mut db_data, None).unwrap();
+        test(&mut db_data);
+    }
+
+    #[test]
+    fn conformance_receive_and_spend() {
+        run_conformance_test(conformance::receive_and_spend);
+    }
+
+    #[test]
+    fn conformance_reorg() {
+        run_conformance_test(conformance::reorg);
+    }
+
+    #[cfg(feature = "demo")]
+    #[test]
+    fn demo_chain_receive_and_send() {
+        use zcash_client_backend::{address::Address, demo::DemoChain};
+        use zcash_primitives::transaction::components::amount::NonNegativeAmount;
+
+        use crate::wallet::sapling::tests::test_prover;
+
+        let data_file = NamedTempFile::new().unwrap();
+        let mut db_data = WalletDb::for_path(data_file.path(), Network::TestNetwork).unwrap();
+        init_wallet_db(&mut db_data, None).unwrap();
+
+        let mut chain = DemoChain::from_sapling_activation(Network::TestNetwork).unwrap();
+        let (account, usk) = db_data
+            .create_account(&SecretVec::new(vec![0; 32]), &chain.birthday())
+            .unwrap();
+        let total_balance = |db_data: &WalletDb<rusqlite::Connection, Network>| {
+            db_data
+                .get_wallet_summary(1)
+                .unwrap()
+                .unwrap()
+                .account_balances()
+                .get(&account)
+                .unwrap()
+                .total()
+        };
+
+        let to = *db_data
+            .get_current_address(account)
+            .unwrap()
+            .unwrap()
+            .sapling()
+            .unwrap();
+        chain.push_payment(&to, NonNegativeAmount::const_from_u64(100000));
+        chain.scan_to_tip(&mut db_data).unwrap();
+        assert_eq!(
+            total_balance(&db_data),
+            NonNegativeAmount::const_from_u64(100000)
+        );
+
+        // The payment spends one note and creates a payment and a change output, which are two
+        // logical actions for a ZIP 317 fee of 10000 zatoshis.
+        let prover = test_prover();
+        let recipient = Address::Sapling(
+            ::sapling::zip32::ExtendedSpendingKey::master(&[1; 32])
+                .default_address()
+                .1,
+        );
+        let txids = chain
+            .send(
+                &mut db_data,
+                &prover,
+                &prover,
+                &usk,
+                &recipient,
+                NonNegativeAmount::const_from_u64(10000),
+                None,
+            )
+            .unwrap();
+        assert_eq!(txids.len(), 1);
+        chain.scan_to_tip(&mut db_data).unwrap();
+        assert_eq!(
+            total_balance(&db_data),
+            NonNegativeAmount::const_from_u64(80000)
+        );
+    }
+
+    #[test]
+    fn read_only_snapshot_is_isolated_from_writes() {
+        let data_file = NamedTempFile::new().unwrap();
+        let mut db_data = WalletDb::for_path(data_file.path(), Network::TestNetwork).unwrap();
+        init_wallet_db(&mut db_data, None).unwrap();
+        assert!(db_data.enable_write_ahead_log().unwrap());
+
+        let birthday =
+            AccountBirthday::from_sapling_activation(&Network::TestNetwork, BlockHash([0; 32]));
+        let (first, _) = db_data
+            .create_account(&SecretVec::new(vec![0; 32]), &birthday)
+            .unwrap();
+
+        let mut db_read =
+            WalletDb::for_path_read_only(data_file.path(), Network::TestNetwork).unwrap();
+        db_read
+            .with_snapshot::<_, _, SqliteClientError>(|snapshot| {
+                assert_eq!(snapshot.get_account_ids()?, vec![first]);
+
+                // An account created while the snapshot is open is not visible through it.
+                db_data
+                    .create_account(&SecretVec::new(vec![1; 32]), &birthday)
+                    .unwrap();
+                assert_eq!(snapshot.get_account_ids()?, vec![first]);
+                Ok(())
+            })
+            .unwrap();
+
+        // Once the snapshot has ended, the new account is visible.
+        assert_eq!(db_read.get_account_ids().unwrap().len(), 2);
+
+        // The read-only handle cannot be used to modify the wallet.
+        assert!(db_read
+            .create_account(&SecretVec::new(vec![2; 32]), &birthday)
+            .is_err());
+    }
+
+    #[test]
+    fn read_only_wallet_summary() {
+        let data_file = NamedTempFile::new().unwrap();
+        let mut db_data = WalletDb::for_path(data_file.path(), Network::TestNetwork).unwrap();
+        init_wallet_db(&mut db_data, None).unwrap();
+        assert!(db_data.enable_write_ahead_log().unwrap());
+
+        let birthday =
+            AccountBirthday::from_sapling_activation(&Network::TestNetwork, BlockHash([0; 32]));
+        db_data
+            .create_account(&SecretVec::new(vec![0; 32]), &birthday)
+            .unwrap();
+        db_data.update_chain_tip(birthday.height() + 1000).unwrap();
+
+        let cached_rows = |db: &WalletDb<rusqlite::Connection, Network>| -> u32 {
+            db.conn
+                .query_row("SELECT COUNT(*) FROM account_balances", [], |row| {
+                    row.get(0)
+                })
+                .unwrap()
+        };
+
+        // The summary is computed through a read-only handle, but is not cached.
+        let mut db_read =
+            WalletDb::for_path_read_only(data_file.path(), Network::TestNetwork).unwrap();
+        let summary = db_read.get_wallet_summary(1).unwrap().unwrap();
+        assert_eq!(summary.chain_tip_height(), birthday.height() + 1000);
+        assert_eq!(cached_rows(&db_data), 0);
+
+        // The summary can also be computed within a snapshot.
+        let snapshot_summary = db_read
+            .with_snapshot::<_, _, SqliteClientError>(|snapshot| snapshot.get_wallet_summary(1))
+            .unwrap();
+        assert_eq!(snapshot_summary, Some(summary.clone()));
+
+        // A writable handle caches the balances that it computes.
+        assert_eq!(db_data.get_wallet_summary(1).unwrap(), Some(summary));
+        assert_eq!(cached_rows(&db_data), 1);
+    }
+
     #[cfg(feature = "transparent-inputs")]
     #[test]
     fn transparent_receivers() {
@@ -33,12 +33,17 @@ use zcash_client_backend::{
         self,
         chain::{scan_cached_blocks, BlockSource, CommitmentTreeRoot, ScanSummary},
         wallet::{
-            create_proposed_transactions, create_spend_to_address,
+            create_proposed_transactions, create_proposed_transactions_with_policy,
+            create_proposed_transactions_with_reservations, create_spend_to_address,
             input_selection::{GreedyInputSelector, GreedyInputSelectorError, InputSelector},
-            propose_standard_transfer_to_address, propose_transfer, spend,
+            propose_standard_transfer_to_address, propose_transfer,
+            reservation::NoteReservations,
+            spend,
+            sweep::{sweep_external_key, ExternalKey, SweepError},
+            PrivacyPolicy, Signer, SpendPolicyHook,
         },
-        AccountBalance, AccountBirthday, WalletCommitmentTrees, WalletRead, WalletSummary,
-        WalletWrite,
+        AccountBalance, AccountBirthday, DustFilter, WalletCommitmentTrees, WalletRead,
+        WalletSummary, WalletWrite,
     },
     keys::UnifiedSpendingKey,
     proposal::Proposal,
@@ -72,11 +77,8 @@ use zcash_protocol::value::{ZatBalance, Zatoshis};
 use crate::{
     chain::init::init_cache_database,
     error::SqliteClientError,
-    wallet::{
-        commitment_tree, get_wallet_summary, init::init_wallet_db, sapling::tests::test_prover,
-        SubtreeScanProgress,
-    },
-    AccountId, ReceivedNoteId, WalletDb,
+    wallet::{commitment_tree, init::init_wallet_db, sapling::tests::test_prover},
+    AccountId, NoteId, WalletDb,
 };
 
 use super::BlockDb;
@@ -92,6 +94,7 @@ use {
     zcash_client_backend::data_api::wallet::{
         input_selection::ShieldingSelector, propose_shielding, shield_transparent_funds,
     },
+    zcash_client_backend::wallet::WalletTransparentOutput,
     zcash_primitives::legacy::TransparentAddress,
 };
 
@@ -101,7 +104,11 @@ use crate::{
     FsBlockDb,
 };
 
+pub(crate) mod fixture;
 pub(crate) mod pool;
+pub(crate) mod snapshot;
+
+pub(crate) use fixture::{FixtureRecipient, WalletFixture};
 
 pub(crate) struct InitialChainState {
     pub(crate) chain_state: ChainState,
@@ -429,7 +436,6 @@ where
     <Cache::BlockSource as BlockSource>::Error: fmt::Debug,
 {
     /// Exposes an immutable reference to the test's [`BlockSource`].
-    #[cfg(feature = "unstable")]
     pub(crate) fn cache(&self) -> &Cache::BlockSource {
         self.cache.block_source()
     }
@@ -860,7 +866,7 @@ impl<Cache> TestState<Cache> {
         data_api::error::Error<
             SqliteClientError,
             commitment_tree::Error,
-            GreedyInputSelectorError<Zip317FeeError, ReceivedNoteId>,
+            GreedyInputSelectorError<Zip317FeeError, NoteId>,
             Zip317FeeError,
         >,
     > {
@@ -928,7 +934,37 @@ impl<Cache> TestState<Cache> {
         request: zip321::TransactionRequest,
         min_confirmations: NonZeroU32,
     ) -> Result<
-        Proposal<InputsT::FeeRule, ReceivedNoteId>,
+        Proposal<InputsT::FeeRule, NoteId>,
+        data_api::error::Error<
+            SqliteClientError,
+            Infallible,
+            InputsT::Error,
+            <InputsT::FeeRule as FeeRule>::Error,
+        >,
+    >
+    where
+        InputsT: InputSelector<InputSource = WalletDb<Connection, LocalNetwork>>,
+    {
+        self.propose_transfer_with_privacy_policy(
+            spend_from_account,
+            input_selector,
+            request,
+            min_confirmations,
+            PrivacyPolicy::AllowFullyTransparent,
+        )
+    }
+
+    /// Invokes [`propose_transfer`] with the given arguments.
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn propose_transfer_with_privacy_policy<InputsT>(
+        &mut self,
+        spend_from_account: AccountId,
+        input_selector: &InputsT,
+        request: zip321::TransactionRequest,
+        min_confirmations: NonZeroU32,
+        privacy_policy: PrivacyPolicy,
+    ) -> Result<
+        Proposal<InputsT::FeeRule, NoteId>,
         data_api::error::Error<
             SqliteClientError,
             Infallible,
@@ -947,6 +983,7 @@ impl<Cache> TestState<Cache> {
             input_selector,
             request,
             min_confirmations,
+            privacy_policy,
         )
     }
 
@@ -964,11 +1001,11 @@ impl<Cache> TestState<Cache> {
         change_memo: Option<MemoBytes>,
         fallback_change_pool: ShieldedProtocol,
     ) -> Result<
-        Proposal<StandardFeeRule, ReceivedNoteId>,
+        Proposal<StandardFeeRule, NoteId>,
         data_api::error::Error<
             SqliteClientError,
             CommitmentTreeErrT,
-            GreedyInputSelectorError<Zip317FeeError, ReceivedNoteId>,
+            GreedyInputSelectorError<Zip317FeeError, NoteId>,
             Zip317FeeError,
         >,
     > {
@@ -1031,7 +1068,7 @@ impl<Cache> TestState<Cache> {
         &mut self,
         usk: &UnifiedSpendingKey,
         ovk_policy: OvkPolicy,
-        proposal: &Proposal<FeeRuleT, ReceivedNoteId>,
+        proposal: &Proposal<FeeRuleT, NoteId>,
     ) -> Result<
         NonEmpty<TxId>,
         data_api::error::Error<
@@ -1057,6 +1094,111 @@ impl<Cache> TestState<Cache> {
         )
     }
 
+    /// Invokes [`create_proposed_transactions`], authorizing spends with the given signer.
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn create_proposed_transactions_with_signer<InputsErrT, FeeRuleT, SignerT>(
+        &mut self,
+        signer: &SignerT,
+        ovk_policy: OvkPolicy,
+        proposal: &Proposal<FeeRuleT, NoteId>,
+    ) -> Result<
+        NonEmpty<TxId>,
+        data_api::error::Error<
+            SqliteClientError,
+            commitment_tree::Error,
+            InputsErrT,
+            FeeRuleT::Error,
+            SignerT::Error,
+        >,
+    >
+    where
+        FeeRuleT: FeeRule,
+        SignerT: Signer,
+    {
+        let params = self.network();
+        let prover = test_prover();
+        create_proposed_transactions(
+            &mut self.db_data,
+            &params,
+            &prover,
+            &prover,
+            signer,
+            ovk_policy,
+            proposal,
+        )
+    }
+
+    /// Invokes [`create_proposed_transactions_with_policy`] with the given arguments.
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn create_proposed_transactions_with_policy<InputsErrT, FeeRuleT, HookT>(
+        &mut self,
+        usk: &UnifiedSpendingKey,
+        ovk_policy: OvkPolicy,
+        proposal: &Proposal<FeeRuleT, NoteId>,
+        policy: &HookT,
+    ) -> Result<
+        NonEmpty<TxId>,
+        data_api::error::Error<
+            SqliteClientError,
+            commitment_tree::Error,
+            InputsErrT,
+            FeeRuleT::Error,
+            Infallible,
+            HookT::Error,
+        >,
+    >
+    where
+        FeeRuleT: FeeRule,
+        HookT: SpendPolicyHook<AccountId>,
+    {
+        let params = self.network();
+        let prover = test_prover();
+        create_proposed_transactions_with_policy(
+            &mut self.db_data,
+            &params,
+            &prover,
+            &prover,
+            usk,
+            ovk_policy,
+            proposal,
+            policy,
+        )
+    }
+
+    /// Invokes [`create_proposed_transactions_with_reservations`] with the given arguments.
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn create_proposed_transactions_with_reservations<InputsErrT, FeeRuleT>(
+        &mut self,
+        usk: &UnifiedSpendingKey,
+        ovk_policy: OvkPolicy,
+        proposal: &Proposal<FeeRuleT, NoteId>,
+        reservations: &mut NoteReservations,
+    ) -> Result<
+        NonEmpty<TxId>,
+        data_api::error::Error<
+            SqliteClientError,
+            commitment_tree::Error,
+            InputsErrT,
+            FeeRuleT::Error,
+        >,
+    >
+    where
+        FeeRuleT: FeeRule,
+    {
+        let params = self.network();
+        let prover = test_prover();
+        create_proposed_transactions_with_reservations(
+            &mut self.db_data,
+            &params,
+            &prover,
+            &prover,
+            usk,
+            ovk_policy,
+            proposal,
+            reservations,
+        )
+    }
+
     /// Invokes [`shield_transparent_funds`] with the given arguments.
     #[cfg(feature = "transparent-inputs")]
     #[allow(clippy::type_complexity)]
@@ -1094,6 +1236,35 @@ impl<Cache> TestState<Cache> {
         )
     }
 
+    /// Invokes [`sweep_external_key`] with the given arguments, scanning the cached blocks
+    /// following `from_state`.
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn sweep_external_key(
+        &mut self,
+        from_state: &ChainState,
+        limit: usize,
+        #[cfg(feature = "transparent-inputs")] transparent_utxos: &[WalletTransparentOutput],
+        key: ExternalKey,
+        to_account: AccountId,
+    ) -> Result<TxId, SweepError<SqliteClientError, <Cache::BlockSource as BlockSource>::Error>>
+    {
+        let params = self.network();
+        let prover = test_prover();
+        sweep_external_key(
+            &mut self.db_data,
+            &params,
+            &prover,
+            &prover,
+            self.cache.block_source(),
+            from_state,
+            limit,
+            #[cfg(feature = "transparent-inputs")]
+            transparent_utxos,
+            key,
+            to_account,
+        )
+    }
+
     fn with_account_balance<T, F: FnOnce(&AccountBalance) -> T>(
         &self,
         account: AccountId,
@@ -1144,13 +1315,17 @@ impl<Cache> TestState<Cache> {
         &self,
         min_confirmations: u32,
     ) -> Option<WalletSummary<AccountId>> {
-        get_wallet_summary(
-            &self.wallet().conn.unchecked_transaction().unwrap(),
-            &self.wallet().params,
-            min_confirmations,
-            &SubtreeScanProgress,
-        )
-        .unwrap()
+        self.get_wallet_summary_with_dust_filter(min_confirmations, DustFilter::IncludeDust)
+    }
+
+    pub(crate) fn get_wallet_summary_with_dust_filter(
+        &self,
+        min_confirmations: u32,
+        dust_filter: DustFilter,
+    ) -> Option<WalletSummary<AccountId>> {
+        self.wallet()
+            .get_wallet_summary_with_dust_filter(min_confirmations, dust_filter)
+            .unwrap()
     }
 
     /// Returns a vector of transaction summaries
@@ -1166,7 +1341,7 @@ impl<Cache> TestState<Cache> {
         let results = stmt
             .query_and_then::<TransactionSummary<AccountId>, SqliteClientError, _, _>([], |row| {
                 Ok(TransactionSummary {
-                    account_id: AccountId(row.get("account_id")?),
+                    account_id: row.get("account_id")?,
                     txid: TxId::from_bytes(row.get("txid")?),
                     expiry_height: row
                         .get::<_, Option<u32>>("expiry_height")?
@@ -1184,6 +1359,18 @@ impl<Cache> TestState<Cache> {
                     received_note_count: row.get("received_note_count")?,
                     memo_count: row.get("memo_count")?,
                     expired_unmined: row.get("expired_unmined")?,
+                    privacy_level: match row.get::<_, String>("privacy_level")?.as_str() {
+                        "fully_shielded" => PrivacyLevel::FullyShielded,
+                        "shielding" => PrivacyLevel::Shielding,
+                        "deshielding" => PrivacyLevel::Deshielding,
+                        "transparent" => PrivacyLevel::Transparent,
+                        other => {
+                            return Err(SqliteClientError::CorruptedData(format!(
+                                "Unrecognized privacy level: {}",
+                                other
+                            )))
+                        }
+                    },
                 })
             })?
             .collect::<Result<Vec<_>, _>>()?;
@@ -1232,6 +1419,16 @@ pub(crate) struct TransactionSummary<AccountId> {
     received_note_count: usize,
     memo_count: usize,
     expired_unmined: bool,
+    privacy_level: PrivacyLevel,
+}
+
+/// The classification of a transaction in the `privacy_level` column of `v_transactions`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum PrivacyLevel {
+    FullyShielded,
+    Shielding,
+    Deshielding,
+    Transparent,
 }
 
 #[allow(dead_code)]
@@ -1279,6 +1476,10 @@ impl<AccountId> TransactionSummary<AccountId> {
     pub(crate) fn memo_count(&self) -> usize {
         self.memo_count
     }
+
+    pub(crate) fn privacy_level(&self) -> PrivacyLevel {
+        self.privacy_level
+    }
 }
 
 /// Trait used by tests that require a full viewing key.
@@ -1894,7 +2095,7 @@ pub(crate) fn input_selector(
 // the same proposal value.
 fn check_proposal_serialization_roundtrip(
     db_data: &WalletDb<rusqlite::Connection, LocalNetwork>,
-    proposal: &Proposal<StandardFeeRule, ReceivedNoteId>,
+    proposal: &Proposal<StandardFeeRule, NoteId>,
 ) {
     let proposal_proto = proposal::Proposal::from_standard_proposal(proposal);
     let deserialized_proposal = proposal_proto.try_into_standard_proposal(db_data);
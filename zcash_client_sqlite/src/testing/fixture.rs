@@ -0,0 +1,331 @@
+//! Programmatic construction of populated wallet databases in the legacy schema, for use in
+//! migration tests.
+//!
+//! Before the `received_notes_nullable_nf` migration, accounts were identified by their ZIP 32
+//! account index, Sapling notes were stored in the `received_notes` table, and the wallet did
+//! not always record `sent_notes` rows for the change outputs of its own transactions. A
+//! [`WalletFixture`] migrates a new wallet database to a target state within that range of the
+//! migration graph, and then inserts the records described by the builder, so that each test
+//! does not need to spell out the legacy schema in SQL.
+
+use rusqlite::{named_params, Connection};
+use tempfile::NamedTempFile;
+use uuid::Uuid;
+
+use zcash_client_backend::keys::UnifiedSpendingKey;
+use zcash_primitives::{consensus::Network, zip32};
+
+use crate::{wallet::init::init_wallet_db_internal, WalletDb};
+
+/// The recipient of a note recorded in the legacy `sent_notes` table.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum FixtureRecipient {
+    /// An address that does not belong to the wallet.
+    External(&'static str),
+    /// The account with the given index.
+    Internal(u32),
+}
+
+#[derive(Clone, Debug)]
+enum FixtureRecord {
+    Account {
+        account: u32,
+        seed: [u8; 32],
+    },
+    Tx {
+        id_tx: i64,
+        height: u32,
+    },
+    ReceivedNote {
+        tx: i64,
+        output_index: u32,
+        account: u32,
+        value: u64,
+        nf: &'static str,
+        is_change: bool,
+    },
+    Spend {
+        tx: i64,
+        spending_tx: i64,
+    },
+    SentNote {
+        tx: i64,
+        output_pool: u32,
+        output_index: u32,
+        from_account: u32,
+        to: FixtureRecipient,
+        value: u64,
+        memo: Option<Vec<u8>>,
+    },
+    Utxo {
+        tx: i64,
+        height: u32,
+        account: u32,
+        value: u64,
+    },
+}
+
+/// A builder for a wallet database in the legacy schema, populated with accounts, transactions,
+/// and the notes they received and sent.
+///
+/// Transactions are identified by their `id_tx`; the txid of each transaction is the string
+/// `tx<id_tx>`. Records are inserted in the order in which they were added to the builder.
+pub(crate) struct WalletFixture {
+    target_migrations: Vec<Uuid>,
+    next_account: u32,
+    records: Vec<FixtureRecord>,
+}
+
+impl WalletFixture {
+    /// Constructs a fixture for a wallet that has been migrated to the given target migrations.
+    ///
+    /// The target migrations must not include `received_notes_nullable_nf` or any of its
+    /// descendants.
+    pub(crate) fn new(target_migrations: &[Uuid]) -> Self {
+        WalletFixture {
+            target_migrations: target_migrations.to_vec(),
+            next_account: 0,
+            records: vec![],
+        }
+    }
+
+    /// Adds an account whose UFVK is derived from the given seed, at the next account index.
+    pub(crate) fn with_account(mut self, seed: [u8; 32]) -> Self {
+        self.records.push(FixtureRecord::Account {
+            account: self.next_account,
+            seed,
+        });
+        self.next_account += 1;
+        self
+    }
+
+    /// Adds a transaction that was mined in a block at `height`, with an `id_tx` equal to the
+    /// height.
+    pub(crate) fn with_mined_tx(mut self, height: u32) -> Self {
+        self.records.push(FixtureRecord::Tx {
+            id_tx: height.into(),
+            height,
+        });
+        self
+    }
+
+    /// Adds a Sapling note received by `account` in transaction `tx`.
+    pub(crate) fn with_received_note(
+        mut self,
+        tx: i64,
+        output_index: u32,
+        account: u32,
+        value: u64,
+        nf: &'static str,
+    ) -> Self {
+        self.records.push(FixtureRecord::ReceivedNote {
+            tx,
+            output_index,
+            account,
+            value,
+            nf,
+            is_change: false,
+        });
+        self
+    }
+
+    /// Adds a Sapling change note received by `account` in transaction `tx`.
+    ///
+    /// As in wallets created by older versions of the crate, no corresponding `sent_notes` row
+    /// is recorded; use [`Self::with_sent_note`] to add one.
+    pub(crate) fn with_received_change(
+        mut self,
+        tx: i64,
+        output_index: u32,
+        account: u32,
+        value: u64,
+        nf: &'static str,
+    ) -> Self {
+        self.records.push(FixtureRecord::ReceivedNote {
+            tx,
+            output_index,
+            account,
+            value,
+            nf,
+            is_change: true,
+        });
+        self
+    }
+
+    /// Marks every note received in transaction `tx` as spent in transaction `spending_tx`.
+    pub(crate) fn with_notes_spent(mut self, tx: i64, spending_tx: i64) -> Self {
+        self.records.push(FixtureRecord::Spend { tx, spending_tx });
+        self
+    }
+
+    /// Adds a Sapling output of transaction `tx` sent by `from_account`.
+    pub(crate) fn with_sent_note(
+        mut self,
+        tx: i64,
+        output_index: u32,
+        from_account: u32,
+        to: FixtureRecipient,
+        value: u64,
+        memo: Option<&[u8]>,
+    ) -> Self {
+        self.records.push(FixtureRecord::SentNote {
+            tx,
+            output_pool: 2,
+            output_index,
+            from_account,
+            to,
+            value,
+            memo: memo.map(|m| m.to_vec()),
+        });
+        self
+    }
+
+    /// Adds a transparent output of the transaction mined at `height`, received by `account`
+    /// at the address `taddr_tx<height>`.
+    pub(crate) fn with_utxo(mut self, height: u32, account: u32, value: u64) -> Self {
+        self.records.push(FixtureRecord::Utxo {
+            tx: height.into(),
+            height,
+            account,
+            value,
+        });
+        self
+    }
+
+    /// Creates the wallet database, migrates it to the target migrations, and inserts the
+    /// fixture's records.
+    ///
+    /// The returned file must be kept alive for as long as the database is in use.
+    pub(crate) fn build(self) -> (NamedTempFile, WalletDb<Connection, Network>) {
+        let data_file = NamedTempFile::new().unwrap();
+        let mut db_data = WalletDb::for_path(data_file.path(), Network::TestNetwork).unwrap();
+        init_wallet_db_internal(&mut db_data, None, &self.target_migrations, false).unwrap();
+
+        for record in self.records {
+            insert_record(&db_data, record);
+        }
+
+        (data_file, db_data)
+    }
+}
+
+fn insert_record(db_data: &WalletDb<Connection, Network>, record: FixtureRecord) {
+    let conn = &db_data.conn;
+    match record {
+        FixtureRecord::Account { account, seed } => {
+            let usk = UnifiedSpendingKey::from_seed(
+                &db_data.params,
+                &seed[..],
+                zip32::AccountId::try_from(account).unwrap(),
+            )
+            .unwrap();
+            let ufvk = usk.to_unified_full_viewing_key();
+            conn.execute(
+                "INSERT INTO accounts (account, ufvk) VALUES (:account, :ufvk)",
+                named_params![
+                    ":account": account,
+                    ":ufvk": ufvk.encode(&db_data.params),
+                ],
+            )
+            .unwrap();
+        }
+        FixtureRecord::Tx { id_tx, height } => {
+            conn.execute(
+                "INSERT OR IGNORE INTO blocks (height, hash, time, sapling_tree)
+                VALUES (:height, :height, :height, x'00')",
+                named_params![":height": height],
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO transactions (block, id_tx, txid) VALUES (:height, :id_tx, :txid)",
+                named_params![
+                    ":height": height,
+                    ":id_tx": id_tx,
+                    ":txid": format!("tx{}", id_tx),
+                ],
+            )
+            .unwrap();
+        }
+        FixtureRecord::ReceivedNote {
+            tx,
+            output_index,
+            account,
+            value,
+            nf,
+            is_change,
+        } => {
+            conn.execute(
+                "INSERT INTO received_notes
+                    (tx, output_index, account, diversifier, value, rcm, nf, is_change)
+                VALUES (:tx, :output_index, :account, '', :value, '', :nf, :is_change)",
+                named_params![
+                    ":tx": tx,
+                    ":output_index": output_index,
+                    ":account": account,
+                    ":value": value,
+                    ":nf": nf,
+                    ":is_change": is_change,
+                ],
+            )
+            .unwrap();
+        }
+        FixtureRecord::Spend { tx, spending_tx } => {
+            conn.execute(
+                "UPDATE received_notes SET spent = :spending_tx WHERE tx = :tx",
+                named_params![":spending_tx": spending_tx, ":tx": tx],
+            )
+            .unwrap();
+        }
+        FixtureRecord::SentNote {
+            tx,
+            output_pool,
+            output_index,
+            from_account,
+            to,
+            value,
+            memo,
+        } => {
+            let (to_account, to_address) = match to {
+                FixtureRecipient::External(address) => (None, Some(address)),
+                FixtureRecipient::Internal(account) => (Some(account), None),
+            };
+            conn.execute(
+                "INSERT INTO sent_notes
+                    (tx, output_pool, output_index, from_account, to_account, to_address, value, memo)
+                VALUES
+                    (:tx, :output_pool, :output_index, :from_account, :to_account, :to_address, :value, :memo)",
+                named_params![
+                    ":tx": tx,
+                    ":output_pool": output_pool,
+                    ":output_index": output_index,
+                    ":from_account": from_account,
+                    ":to_account": to_account,
+                    ":to_address": to_address,
+                    ":value": value,
+                    ":memo": memo,
+                ],
+            )
+            .unwrap();
+        }
+        FixtureRecord::Utxo {
+            tx,
+            height,
+            account,
+            value,
+        } => {
+            conn.execute(
+                "INSERT INTO utxos
+                    (received_by_account, address, prevout_txid, prevout_idx, script, value_zat, height)
+                VALUES (:account, :address, :txid, 0, '', :value, :height)",
+                named_params![
+                    ":account": account,
+                    ":address": format!("taddr_tx{}", tx),
+                    ":txid": format!("tx{}", tx),
+                    ":value": value,
+                    ":height": height,
+                ],
+            )
+            .unwrap();
+        }
+    }
+}
@@ -4,25 +4,28 @@
 
 use std::{
     convert::Infallible,
-    num::{NonZeroU32, NonZeroU8},
+    fmt,
+    num::{NonZeroU32, NonZeroU8, NonZeroUsize},
+    str::FromStr,
 };
 
 use incrementalmerkletree::{frontier::Frontier, Level};
-use rand_core::RngCore;
-use rusqlite::params;
+use rand_core::{CryptoRng, RngCore};
+use rusqlite::{named_params, params};
 use secrecy::Secret;
 use shardtree::error::ShardTreeError;
 use zcash_primitives::{
     block::BlockHash,
-    consensus::{BranchId, NetworkUpgrade, Parameters},
+    consensus::{BranchId, MainNetwork, NetworkType, NetworkUpgrade, Parameters},
     legacy::TransparentAddress,
     memo::{Memo, MemoBytes},
     transaction::{
-        components::amount::NonNegativeAmount,
+        builder::{self, Builder, SaplingPartiallyAuthorized},
+        components::amount::{Amount, NonNegativeAmount},
         fees::{
             fixed::FeeRule as FixedFeeRule, zip317::FeeError as Zip317FeeError, StandardFeeRule,
         },
-        Transaction,
+        Transaction, TxId,
     },
     zip32::Scope,
 };
@@ -31,44 +34,72 @@ use zcash_client_backend::{
     address::Address,
     data_api::{
         self,
+        audit::AuditBundle,
         chain::{self, ChainState, CommitmentTreeRoot, ScanSummary},
         error::Error,
+        scanning::ScanPriority,
+        scheduled::PaymentInterval,
         wallet::{
+            batch::propose_batch_transfer,
             decrypt_and_store_transaction,
             input_selection::{GreedyInputSelector, GreedyInputSelectorError},
+            reservation::NoteReservations,
+            sweep::{ExternalKey, SweepError},
+            validate_for_broadcast, BroadcastRejection, FeeGuardrailViolation, FeeGuardrails,
+            FeeWarning, PrivacyPolicy, PrivacyViolation, RecipientRejection, Signer,
+            SpendPolicyHook,
         },
-        AccountBirthday, DecryptedTransaction, Ratio, WalletRead, WalletSummary, WalletWrite,
+        AccountBirthday, BlockNullifiers, DecryptedTransaction, DustFilter, PeriodStatistics,
+        Ratio, StatisticsGranularity, TransactionDataRequest, TransactionStatus, WalletRead,
+        WalletSummary, WalletWrite,
     },
     decrypt_transaction,
-    fees::{fixed, standard, DustOutputPolicy},
-    keys::UnifiedSpendingKey,
+    disclosure::{self, PaymentDisclosure},
+    fees::{self, fixed, standard, DustOutputPolicy},
+    keys::{UnifiedFullViewingKey, UnifiedSpendingKey},
     scanning::ScanError,
     wallet::{Note, OvkPolicy, ReceivedNote},
     zip321::{self, Payment, TransactionRequest},
-    ShieldedProtocol,
+    PoolType, ShieldedProtocol,
 };
 use zcash_protocol::consensus::BlockHeight;
 
 use super::TestFvk;
 use crate::{
     error::SqliteClientError,
-    testing::{input_selector, AddressType, BlockCache, InitialChainState, TestBuilder, TestState},
-    wallet::{block_max_scanned, commitment_tree, parse_scope, truncate_to_height},
-    AccountId, NoteId, ReceivedNoteId,
+    testing::{
+        input_selector,
+        snapshot::{assert_views_snapshot, TIME_DEPENDENT_COLUMNS},
+        AddressType, BlockCache, InitialChainState, PrivacyLevel, TestBuilder, TestState,
+    },
+    wallet::{
+        block_max_scanned, commitment_tree, journal::WalletEventKind, ledger::LedgerEntryType,
+        parse_scope, truncate_to_height,
+    },
+    AccountId, NoteId,
 };
 
+#[cfg(feature = "orchard")]
+use zcash_primitives::transaction::builder::OrchardPartiallyAuthorized;
+
 #[cfg(feature = "transparent-inputs")]
 use {
     zcash_client_backend::{
-        fees::TransactionBalance, proposal::Step, wallet::WalletTransparentOutput, PoolType,
+        fees::TransactionBalance, proposal::Step, wallet::WalletTransparentOutput,
+    },
+    zcash_primitives::{
+        legacy::keys::{NonHardenedChildIndex, TransparentKeyScope},
+        transaction::{
+            components::{OutPoint, TxOut},
+            sighash::SignatureHash,
+        },
     },
-    zcash_primitives::transaction::components::{OutPoint, TxOut},
 };
 
 pub(crate) type OutputRecoveryError = Error<
     SqliteClientError,
     commitment_tree::Error,
-    GreedyInputSelectorError<Zip317FeeError, ReceivedNoteId>,
+    GreedyInputSelectorError<Zip317FeeError, NoteId>,
     Zip317FeeError,
 >;
 
@@ -120,8 +151,8 @@ pub(crate) trait ShieldedPoolTester {
         account: AccountId,
         target_value: NonNegativeAmount,
         anchor_height: BlockHeight,
-        exclude: &[ReceivedNoteId],
-    ) -> Result<Vec<ReceivedNote<ReceivedNoteId, Self::Note>>, SqliteClientError>;
+        exclude: &[NoteId],
+    ) -> Result<Vec<ReceivedNote<NoteId, Self::Note>>, SqliteClientError>;
 
     fn decrypted_pool_outputs_count(d_tx: &DecryptedTransaction<'_, AccountId>) -> usize;
 
@@ -138,6 +169,14 @@ pub(crate) trait ShieldedPoolTester {
     ) -> Result<Option<(Note, Address, MemoBytes)>, OutputRecoveryError>;
 
     fn received_note_count(summary: &ScanSummary) -> usize;
+
+    /// Returns the nullifiers of a block at the given height, in which the transaction with
+    /// the given txid spends all of the wallet's unspent notes in this pool.
+    fn spend_all_nullifiers<Cache>(
+        st: &TestState<Cache>,
+        height: BlockHeight,
+        txid: TxId,
+    ) -> BlockNullifiers;
 }
 
 pub(crate) fn send_single_step_proposed_transfer<T: ShieldedPoolTester>() {
@@ -217,7 +256,7 @@ pub(crate) fn send_single_step_proposed_transfer<T: ShieldedPoolTester>() {
     )]
     .into_iter()
     .collect();
-    let d_tx = decrypt_transaction(&st.network(), h + 1, &tx, &ufvks);
+    let d_tx = decrypt_transaction(&st.network(), None, Some(h), &tx, &ufvks);
     assert_eq!(T::decrypted_pool_outputs_count(&d_tx), 2);
 
     let mut found_tx_change_memo = false;
@@ -291,11 +330,204 @@ pub(crate) fn send_single_step_proposed_transfer<T: ShieldedPoolTester>() {
     assert_eq!(tx_history.len(), 2);
 
     assert_matches!(
-        decrypt_and_store_transaction(&st.network(), st.wallet_mut(), &tx),
+        decrypt_and_store_transaction(&st.network(), st.wallet_mut(), &tx, None),
         Ok(_)
     );
 }
 
+/// Funds the test account with a single note of 60000 zatoshis, and proposes a payment of 10000
+/// zatoshis from it to an external recipient under the ZIP 317 fee rule.
+///
+/// Returns the height at which the note was received, the recipient, and the proposal.
+fn propose_single_transfer<T: ShieldedPoolTester>(
+    st: &mut TestState<BlockCache>,
+) -> (
+    BlockHeight,
+    Address,
+    zcash_client_backend::proposal::Proposal<StandardFeeRule, NoteId>,
+) {
+    let account = st.test_account().cloned().unwrap();
+    let dfvk = T::test_account_fvk(st);
+
+    let value = NonNegativeAmount::const_from_u64(60000);
+    let (h, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+    st.scan_cached_blocks(h, 1);
+
+    let to = T::sk_default_address(&T::sk(&[0xf5; 32]));
+    let proposal = st
+        .propose_standard_transfer::<Infallible>(
+            account.account_id(),
+            StandardFeeRule::Zip317,
+            NonZeroU32::new(1).unwrap(),
+            &to,
+            NonNegativeAmount::const_from_u64(10000),
+            None,
+            None,
+            T::SHIELDED_PROTOCOL,
+        )
+        .unwrap();
+
+    (h, to, proposal)
+}
+
+pub(crate) fn payment_disclosure<T: ShieldedPoolTester>() {
+    let mut st = TestBuilder::new()
+        .with_block_cache()
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+    let (h, to, proposal) = propose_single_transfer::<T>(&mut st);
+    let txid = st
+        .create_proposed_transactions::<Infallible, _>(account.usk(), OvkPolicy::Sender, &proposal)
+        .unwrap()[0];
+    let tx = st.wallet().get_transaction(txid).unwrap().unwrap();
+
+    // The payment to the recipient can be disclosed without a viewing key. The transaction's
+    // outputs are shuffled, so find the one that pays the recipient.
+    let ufvk = account.usk().to_unified_full_viewing_key();
+    let disclosure = (0..2)
+        .filter_map(|i| {
+            disclosure::create_payment_disclosure(
+                &st.network(),
+                h + 1,
+                &tx,
+                &ufvk,
+                T::SHIELDED_PROTOCOL,
+                i,
+            )
+            .ok()
+        })
+        .map(|d| PaymentDisclosure::decode(&d.encode()).unwrap())
+        .find(|d| {
+            let payment =
+                disclosure::verify_payment_disclosure(&st.network(), h + 1, &tx, d).unwrap();
+            payment
+                .recipient()
+                .corresponds(&to.to_zcash_address(&st.network()))
+        })
+        .expect("The payment to the recipient can be disclosed.");
+    let payment =
+        disclosure::verify_payment_disclosure(&st.network(), h + 1, &tx, &disclosure).unwrap();
+    assert_eq!(payment.value(), NonNegativeAmount::const_from_u64(10000));
+    assert_eq!(payment.memo(), &MemoBytes::empty());
+}
+
+pub(crate) fn fee_guardrails_check_proposal<T: ShieldedPoolTester>() {
+    let mut st = TestBuilder::new()
+        .with_block_cache()
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let (_, _, proposal) = propose_single_transfer::<T>(&mut st);
+
+    // The proposal pays a fee equal to the value sent, which the fee guardrails can be
+    // configured to refuse.
+    let fee = proposal.steps().head.balance().fee_required();
+    let below_fee = (fee - NonNegativeAmount::const_from_u64(1)).unwrap();
+    assert_matches!(
+        FeeGuardrails::new(Some(below_fee), None, false, NonNegativeAmount::ZERO).check(&proposal),
+        Err(FeeGuardrailViolation::FeeExceedsMaximum { .. })
+    );
+    assert_matches!(
+        FeeGuardrails::new(None, Some(50), false, NonNegativeAmount::ZERO).check(&proposal),
+        Err(FeeGuardrailViolation::FeeExceedsPercentage { .. })
+    );
+    assert_matches!(
+        FeeGuardrails::new(None, Some(50), true, NonNegativeAmount::ZERO).check(&proposal),
+        Ok(warnings) if warnings == [FeeWarning::ExcessiveFee {
+            fee,
+            sent: NonNegativeAmount::const_from_u64(10000),
+        }]
+    );
+    assert_matches!(FeeGuardrails::default().check(&proposal), Ok(warnings) if warnings.is_empty());
+}
+
+pub(crate) fn spend_policy_vetoes_proposal<T: ShieldedPoolTester>() {
+    let mut st = TestBuilder::new()
+        .with_block_cache()
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+    let (_, _, proposal) = propose_single_transfer::<T>(&mut st);
+
+    // A spending policy that limits the total value sent can veto the proposal before any
+    // transaction is created.
+    #[derive(Debug)]
+    struct LimitExceeded;
+    impl fmt::Display for LimitExceeded {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "spending limit exceeded")
+        }
+    }
+    impl std::error::Error for LimitExceeded {}
+
+    struct SpendingLimit(NonNegativeAmount);
+    impl SpendPolicyHook<AccountId> for SpendingLimit {
+        type Error = LimitExceeded;
+
+        fn check_proposal<FeeRuleT, NoteRef>(
+            &self,
+            _account: AccountId,
+            proposal: &zcash_client_backend::proposal::Proposal<FeeRuleT, NoteRef>,
+        ) -> Result<(), Self::Error> {
+            let mut sent = NonNegativeAmount::ZERO;
+            for step in proposal.steps() {
+                let total = step
+                    .transaction_request()
+                    .total()
+                    .map_err(|_| LimitExceeded)?;
+                sent = (sent + total).ok_or(LimitExceeded)?;
+            }
+            if sent > self.0 {
+                Err(LimitExceeded)
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    assert_matches!(
+        st.create_proposed_transactions_with_policy::<Infallible, _, _>(
+            account.usk(),
+            OvkPolicy::Sender,
+            &proposal,
+            &SpendingLimit(NonNegativeAmount::const_from_u64(9999)),
+        ),
+        Err(Error::SpendPolicy(LimitExceeded))
+    );
+}
+
+pub(crate) fn validate_for_broadcast_checks_expiry<T: ShieldedPoolTester>() {
+    let mut st = TestBuilder::new()
+        .with_block_cache()
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+    let (h, _, proposal) = propose_single_transfer::<T>(&mut st);
+    let txid = st
+        .create_proposed_transactions::<Infallible, _>(account.usk(), OvkPolicy::Sender, &proposal)
+        .unwrap()[0];
+    let tx = st.wallet().get_transaction(txid).unwrap().unwrap();
+
+    // The transaction is valid for broadcast at the current chain tip, but not once it is
+    // about to expire or has expired.
+    assert_matches!(
+        validate_for_broadcast(&tx, &st.network(), h, |_| None),
+        Ok(())
+    );
+    assert_matches!(
+        validate_for_broadcast(&tx, &st.network(), tx.expiry_height() - 2, |_| None),
+        Err(BroadcastRejection::ExpiringSoon { .. })
+    );
+    assert_matches!(
+        validate_for_broadcast(&tx, &st.network(), tx.expiry_height(), |_| None),
+        Err(BroadcastRejection::Expired { .. })
+    );
+}
+
 #[cfg(feature = "transparent-inputs")]
 pub(crate) fn send_multi_step_proposed_transfer<T: ShieldedPoolTester>() {
     use nonempty::NonEmpty;
@@ -473,6 +705,157 @@ pub(crate) fn create_to_address_fails_on_incorrect_usk<T: ShieldedPoolTester>()
     );
 }
 
+/// A [`Signer`] that authorizes spends using the keys of an account, unless it has been
+/// configured to decline.
+struct TestSigner<'a> {
+    usk: &'a UnifiedSpendingKey,
+    decline: bool,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Declined;
+
+impl<'a> TestSigner<'a> {
+    fn check(&self) -> Result<(), Declined> {
+        if self.decline {
+            Err(Declined)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl<'a> Signer for TestSigner<'a> {
+    type Error = Declined;
+
+    fn to_unified_full_viewing_key(&self) -> UnifiedFullViewingKey {
+        self.usk.to_unified_full_viewing_key()
+    }
+
+    fn add_sapling_spend<P: Parameters, FE>(
+        &self,
+        builder: &mut Builder<'_, P, ()>,
+        scope: Scope,
+        note: sapling::Note,
+        merkle_path: sapling::MerklePath,
+    ) -> Result<(), builder::Error<FE, Self::Error>> {
+        let extsk = match scope {
+            Scope::External => self.usk.sapling().clone(),
+            Scope::Internal => self.usk.sapling().derive_internal(),
+        };
+        builder.add_unsigned_sapling_spend(&extsk, note, merkle_path)
+    }
+
+    fn sign_sapling<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        sighash: &[u8; 32],
+        bundle: SaplingPartiallyAuthorized,
+    ) -> Result<SaplingPartiallyAuthorized, Self::Error> {
+        self.check()?;
+        Signer::sign_sapling(self.usk, rng, sighash, bundle).map_err(|e| match e {})
+    }
+
+    #[cfg(feature = "orchard")]
+    fn sign_orchard<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        sighash: &[u8; 32],
+        bundle: OrchardPartiallyAuthorized,
+    ) -> Result<OrchardPartiallyAuthorized, Self::Error> {
+        self.check()?;
+        Signer::sign_orchard(self.usk, rng, sighash, bundle).map_err(|e| match e {})
+    }
+
+    #[cfg(feature = "transparent-inputs")]
+    fn sign_transparent(
+        &self,
+        scope: TransparentKeyScope,
+        address_index: NonHardenedChildIndex,
+        sighash: &SignatureHash,
+    ) -> Result<secp256k1::ecdsa::Signature, Self::Error> {
+        self.check()?;
+        Signer::sign_transparent(self.usk, scope, address_index, sighash).map_err(|e| match e {})
+    }
+}
+
+pub(crate) fn spends_are_authorized_by_signer<T: ShieldedPoolTester>() {
+    let mut st = TestBuilder::new()
+        .with_block_cache()
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+    let dfvk = T::test_account_fvk(&st);
+
+    let value = NonNegativeAmount::const_from_u64(60000);
+    let (h, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+    st.scan_cached_blocks(h, 1);
+
+    let to = T::sk_default_address(&T::sk(&[0xf5; 32]));
+    let request = zip321::TransactionRequest::new(vec![Payment::without_memo(
+        to.to_zcash_address(&st.network()),
+        NonNegativeAmount::const_from_u64(10000),
+    )])
+    .unwrap();
+    let change_strategy = standard::SingleOutputChangeStrategy::new(
+        StandardFeeRule::Zip317,
+        None,
+        T::SHIELDED_PROTOCOL,
+    );
+    let input_selector = &GreedyInputSelector::new(change_strategy, DustOutputPolicy::default());
+    let proposal = st
+        .propose_transfer(
+            account.account_id(),
+            input_selector,
+            request,
+            NonZeroU32::new(1).unwrap(),
+        )
+        .unwrap();
+
+    // A signer that declines to sign prevents the transaction from being created, and its
+    // error is returned to the caller.
+    let declining = TestSigner {
+        usk: account.usk(),
+        decline: true,
+    };
+    assert_matches!(
+        st.create_proposed_transactions_with_signer::<Infallible, _, _>(
+            &declining,
+            OvkPolicy::Sender,
+            &proposal,
+        ),
+        Err(Error::Signer(Declined))
+    );
+    assert_eq!(st.get_spendable_balance(account.account_id(), 1), value);
+
+    // The spends of the transaction are authorized by the signer without it providing any
+    // spending key to the wallet.
+    let signer = TestSigner {
+        usk: account.usk(),
+        decline: false,
+    };
+    let txids = st
+        .create_proposed_transactions_with_signer::<Infallible, _, _>(
+            &signer,
+            OvkPolicy::Sender,
+            &proposal,
+        )
+        .unwrap();
+    assert_eq!(txids.len(), 1);
+
+    let tx = st
+        .wallet()
+        .get_transaction(txids.head)
+        .unwrap()
+        .expect("Created transaction was stored.");
+    let spends = match T::SHIELDED_PROTOCOL {
+        ShieldedProtocol::Sapling => tx.sapling_bundle().map(|b| b.shielded_spends().len()),
+        ShieldedProtocol::Orchard => tx.orchard_bundle().map(|b| b.actions().len()),
+    };
+    assert_matches!(spends, Some(n) if n > 0);
+}
+
 #[allow(deprecated)]
 pub(crate) fn proposal_fails_with_no_blocks<T: ShieldedPoolTester>() {
     let mut st = TestBuilder::new()
@@ -653,6 +1036,122 @@ pub(crate) fn spend_fails_on_unverified_notes<T: ShieldedPoolTester>() {
     );
 }
 
+pub(crate) fn audit_bundle_export<T: ShieldedPoolTester>() {
+    let mut st = TestBuilder::new()
+        .with_block_cache()
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+    let account_id = account.account_id();
+    let (_, _, proposal) = propose_single_transfer::<T>(&mut st);
+    let txid = st
+        .create_proposed_transactions::<Infallible, _>(account.usk(), OvkPolicy::Sender, &proposal)
+        .unwrap()[0];
+    let (h, _) = st.generate_next_block_including(txid);
+    st.scan_cached_blocks(h, 1);
+
+    // The mined transaction, including both the payment and the change output, can be
+    // disclosed to an auditor.
+    let bundle = st
+        .wallet()
+        .export_account_audit_bundle(account_id, h..h + 1)
+        .unwrap();
+    assert_eq!(bundle.transactions().len(), 1);
+    assert_eq!(bundle.transactions()[0].txid(), txid);
+    assert_eq!(bundle.transactions()[0].outputs().len(), 2);
+
+    // Transactions outside the requested range are not disclosed.
+    assert!(st
+        .wallet()
+        .export_account_audit_bundle(account_id, (h + 1)..(h + 2))
+        .unwrap()
+        .transactions()
+        .is_empty());
+
+    // The serialized bundle can be verified against the chain.
+    let mut encoded = vec![];
+    bundle.write(&mut encoded).unwrap();
+    let bundle = AuditBundle::read(&encoded[..]).unwrap();
+    assert_matches!(
+        bundle.verify(|txid| st
+            .wallet()
+            .get_transaction(txid)
+            .map(|tx| tx.map(|tx| (h, tx)))),
+        Ok(())
+    );
+}
+
+pub(crate) fn change_pending_scanning_is_reported_as_change<T: ShieldedPoolTester>() {
+    let mut st = TestBuilder::new()
+        .with_block_cache()
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+    let account_id = account.account_id();
+    let dfvk = T::test_account_fvk(&st);
+
+    // Add funds to the wallet in a single note.
+    let value = NonNegativeAmount::const_from_u64(60000);
+    let (h1, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+    st.scan_cached_blocks(h1, 1);
+
+    // Send part of the funds to an external recipient, which leaves a change note.
+    let to = T::sk_default_address(&T::sk(&[0xf5; 32]));
+    let send_value = NonNegativeAmount::const_from_u64(20000);
+    let proposal = st
+        .propose_standard_transfer::<Infallible>(
+            account_id,
+            StandardFeeRule::Zip317,
+            NonZeroU32::new(1).unwrap(),
+            &to,
+            send_value,
+            None,
+            None,
+            T::SHIELDED_PROTOCOL,
+        )
+        .unwrap();
+    let fee = proposal.steps().head.balance().fee_required();
+    let change = ((value - send_value).unwrap() - fee).unwrap();
+    let txid = st
+        .create_proposed_transactions::<Infallible, _>(account.usk(), OvkPolicy::Sender, &proposal)
+        .unwrap()[0];
+
+    // Mine the transaction, followed by enough blocks for its change to be confirmed.
+    let (h2, _) = st.generate_next_block_including(txid);
+    let not_our_key = T::sk_to_fvk(&T::sk(&[0xf6; 32]));
+    let mut h_end = h2;
+    for _ in 0..10 {
+        let (h, _, _) = st.generate_next_block(
+            &not_our_key,
+            AddressType::DefaultExternal,
+            NonNegativeAmount::const_from_u64(10000),
+        );
+        h_end = h;
+    }
+    st.wallet_mut().update_chain_tip(h_end).unwrap();
+
+    // Scan only the block containing the transaction. The change has enough confirmations,
+    // but witnesses cannot be constructed for it until the rest of its shard is scanned, so
+    // it is reported as pending change rather than as value pending spendability.
+    st.scan_cached_blocks(h2, 1);
+    assert_eq!(
+        st.get_spendable_balance(account_id, 1),
+        NonNegativeAmount::ZERO
+    );
+    assert_eq!(st.get_pending_change(account_id, 1), change);
+    assert_eq!(st.get_pending_shielded_balance(account_id, 1), change);
+
+    // Once the remaining blocks have been scanned, the change is spendable.
+    st.scan_cached_blocks(h2 + 1, 10);
+    assert_eq!(st.get_spendable_balance(account_id, 1), change);
+    assert_eq!(
+        st.get_pending_change(account_id, 1),
+        NonNegativeAmount::ZERO
+    );
+}
+
 pub(crate) fn spend_fails_on_locked_notes<T: ShieldedPoolTester>() {
     let mut st = TestBuilder::new()
         .with_block_cache()
@@ -826,7 +1325,7 @@ pub(crate) fn ovk_policy_prevents_recovery_from_chain<T: ShieldedPoolTester>() {
         Error<
             SqliteClientError,
             commitment_tree::Error,
-            GreedyInputSelectorError<Zip317FeeError, ReceivedNoteId>,
+            GreedyInputSelectorError<Zip317FeeError, NoteId>,
             Zip317FeeError,
         >,
     > {
@@ -1174,36 +1673,1771 @@ pub(crate) fn zip317_spend<T: ShieldedPoolTester>() {
     );
 }
 
-#[cfg(feature = "transparent-inputs")]
-pub(crate) fn shield_transparent<T: ShieldedPoolTester>() {
+pub(crate) fn dust_notes_ignored_by_filter<T: ShieldedPoolTester>() {
     let mut st = TestBuilder::new()
         .with_block_cache()
         .with_account_from_sapling_activation(BlockHash([0; 32]))
         .build();
 
     let account = st.test_account().cloned().unwrap();
+    let account_id = account.account_id();
     let dfvk = T::test_account_fvk(&st);
 
-    let uaddr = st
-        .wallet()
-        .get_current_address(account.account_id())
-        .unwrap()
-        .unwrap();
-    let taddr = uaddr.transparent().unwrap();
-
-    // Ensure that the wallet has at least one block
-    let (h, _, _) = st.generate_next_block(
+    // Add funds to the wallet
+    let (h1, _, _) = st.generate_next_block(
         &dfvk,
-        AddressType::Internal,
+        AddressType::DefaultExternal,
         NonNegativeAmount::const_from_u64(50000),
     );
-    st.scan_cached_blocks(h, 1);
 
-    let utxo = WalletTransparentOutput::from_parts(
-        OutPoint::new([1u8; 32], 1),
-        TxOut {
-            value: NonNegativeAmount::const_from_u64(10000),
-            script_pubkey: taddr.script(),
+    // Add 10 dust notes to the wallet
+    for _ in 1..=10 {
+        st.generate_next_block(
+            &dfvk,
+            AddressType::DefaultExternal,
+            NonNegativeAmount::const_from_u64(1000),
+        );
+    }
+
+    st.scan_cached_blocks(h1, 11);
+
+    let total = NonNegativeAmount::const_from_u64(60000);
+    let non_dust = NonNegativeAmount::const_from_u64(50000);
+    let dust_filter = DustFilter::MinNoteValue(NonNegativeAmount::const_from_u64(5000));
+    assert_eq!(st.get_spendable_balance(account_id, 1), total);
+
+    // With the dust filter applied, the dust notes are omitted from the wallet summary.
+    let summary = st
+        .get_wallet_summary_with_dust_filter(1, dust_filter)
+        .unwrap();
+    let balance = summary.account_balances().get(&account_id).unwrap();
+    assert_eq!(balance.total(), non_dust);
+    assert_eq!(balance.spendable_value(), non_dust);
+
+    // The "include dust" filter reports the same balance as the unfiltered summary.
+    let summary = st
+        .get_wallet_summary_with_dust_filter(1, DustFilter::IncludeDust)
+        .unwrap();
+    assert_eq!(summary, st.get_wallet_summary(1).unwrap());
+
+    let input_selector = input_selector(StandardFeeRule::Zip317, None, T::SHIELDED_PROTOCOL)
+        .with_dust_filter(dust_filter);
+
+    // A request that could only be satisfied by spending dust fails.
+    let req = TransactionRequest::new(vec![Payment::without_memo(
+        T::fvk_default_address(&dfvk).to_zcash_address(&st.network()),
+        NonNegativeAmount::const_from_u64(45000),
+    )])
+    .unwrap();
+    assert_matches!(
+        st.propose_transfer(account_id, &input_selector, req, NonZeroU32::new(1).unwrap()),
+        Err(Error::InsufficientFunds { available, .. }) if available == non_dust
+    );
+
+    // A request that can be satisfied from non-dust notes spends only the non-dust note.
+    let req = TransactionRequest::new(vec![Payment::without_memo(
+        T::fvk_default_address(&dfvk).to_zcash_address(&st.network()),
+        NonNegativeAmount::const_from_u64(30000),
+    )])
+    .unwrap();
+    let proposal = st
+        .propose_transfer(
+            account_id,
+            &input_selector,
+            req,
+            NonZeroU32::new(1).unwrap(),
+        )
+        .unwrap();
+    let notes = proposal.steps().head.shielded_inputs().unwrap().notes();
+    assert_eq!(notes.len(), 1);
+    assert_eq!(notes.head.note().value(), non_dust);
+}
+
+pub(crate) fn spends_detected_by_nullifier_lookahead<T: ShieldedPoolTester>() {
+    let mut st = TestBuilder::new()
+        .with_block_cache()
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+    let account_id = account.account_id();
+    let dfvk = T::test_account_fvk(&st);
+
+    // Add funds to the wallet
+    let value = NonNegativeAmount::const_from_u64(50000);
+    let (h, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+    st.scan_cached_blocks(h, 1);
+    assert_eq!(st.get_total_balance(account_id), value);
+
+    // Provide the wallet with the nullifiers of a block far beyond its scanned height, in
+    // which its note is spent.
+    let spend_height = h + 100;
+    let spend_txid = TxId::from_bytes([7; 32]);
+    let block = T::spend_all_nullifiers(&st, spend_height, spend_txid);
+    st.wallet_mut().put_block_nullifiers(vec![block]).unwrap();
+
+    // The spend is reflected in the wallet's balance without scanning the block.
+    assert_eq!(st.get_total_balance(account_id), NonNegativeAmount::ZERO);
+    // The spending transaction's mined height is not known until its block is scanned.
+    assert_eq!(st.wallet().get_tx_height(spend_txid).unwrap(), None);
+
+    // Providing the same nullifiers again is a no-op.
+    let block = T::spend_all_nullifiers(&st, spend_height, spend_txid);
+    st.wallet_mut().put_block_nullifiers(vec![block]).unwrap();
+    assert_eq!(st.get_total_balance(account_id), NonNegativeAmount::ZERO);
+}
+
+pub(crate) fn account_balances_cache_invalidated<T: ShieldedPoolTester>() {
+    let mut st = TestBuilder::new()
+        .with_block_cache()
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+    let account_id = account.account_id();
+    let dfvk = T::test_account_fvk(&st);
+
+    let cached_rows = |st: &TestState<BlockCache>| -> u32 {
+        st.wallet()
+            .conn
+            .query_row("SELECT COUNT(*) FROM account_balances", [], |row| {
+                row.get(0)
+            })
+            .unwrap()
+    };
+
+    // Add funds to the wallet
+    let value = NonNegativeAmount::const_from_u64(50000);
+    let (h, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+    st.scan_cached_blocks(h, 1);
+
+    // Computing the wallet summary populates the cache, and the cached summary is identical.
+    assert_eq!(cached_rows(&st), 0);
+    let summary = st.get_wallet_summary(1).unwrap();
+    assert_eq!(cached_rows(&st), 1);
+    assert_eq!(st.get_wallet_summary(1).unwrap(), summary);
+    st.wallet_mut().check_account_balances_cache().unwrap();
+
+    // Each combination of confirmation depth and dust filter is cached separately.
+    st.get_wallet_summary_with_dust_filter(
+        1,
+        DustFilter::MinNoteValue(NonNegativeAmount::const_from_u64(5000)),
+    );
+    st.get_wallet_summary(10);
+    assert_eq!(cached_rows(&st), 3);
+    st.wallet_mut().check_account_balances_cache().unwrap();
+
+    // Receiving a note invalidates the cache.
+    let (h, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+    st.scan_cached_blocks(h, 1);
+    assert_eq!(cached_rows(&st), 0);
+    assert_eq!(
+        st.get_wallet_summary(1)
+            .unwrap()
+            .account_balances()
+            .get(&account_id)
+            .unwrap()
+            .total(),
+        (value + value).unwrap()
+    );
+    st.wallet_mut().check_account_balances_cache().unwrap();
+
+    // Spending a note invalidates the cache.
+    let block = T::spend_all_nullifiers(&st, h + 1, TxId::from_bytes([7; 32]));
+    st.wallet_mut().put_block_nullifiers(vec![block]).unwrap();
+    assert_eq!(cached_rows(&st), 0);
+    assert_eq!(st.get_total_balance(account_id), NonNegativeAmount::ZERO);
+    st.wallet_mut().check_account_balances_cache().unwrap();
+
+    // A cache that disagrees with the wallet's notes is detected and cleared.
+    st.wallet()
+        .conn
+        .execute("UPDATE account_balances SET unshielded_value = 1", [])
+        .unwrap();
+    assert_matches!(
+        st.wallet_mut().check_account_balances_cache(),
+        Err(SqliteClientError::CorruptedData(_))
+    );
+    assert_eq!(cached_rows(&st), 0);
+}
+
+pub(crate) fn account_balances_cache_retained<T: ShieldedPoolTester>() {
+    let mut st = TestBuilder::new()
+        .with_block_cache()
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let dfvk = T::test_account_fvk(&st);
+
+    let cached_rows = |st: &TestState<BlockCache>| -> u32 {
+        st.wallet()
+            .conn
+            .query_row("SELECT COUNT(*) FROM account_balances", [], |row| {
+                row.get(0)
+            })
+            .unwrap()
+    };
+
+    let value = NonNegativeAmount::const_from_u64(50000);
+    let (h, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+    st.scan_cached_blocks(h, 1);
+    let summary = st.get_wallet_summary(1).unwrap();
+    assert_eq!(cached_rows(&st), 1);
+
+    // Writes that cannot change a balance, such as storing the raw data of a transaction
+    // or updating the data of a note commitment tree shard, leave the cache intact.
+    st.wallet()
+        .conn
+        .execute_batch(&format!(
+            "UPDATE transactions SET raw = X'00', fee = 1000;
+            UPDATE {}_tree_shards SET root_hash = root_hash, shard_data = shard_data;",
+            T::TABLES_PREFIX
+        ))
+        .unwrap();
+    assert_eq!(cached_rows(&st), 1);
+    assert_eq!(st.get_wallet_summary(1).unwrap(), summary);
+
+    // Advancing the chain tip invalidates the cache.
+    st.wallet_mut().update_chain_tip(h + 10).unwrap();
+    assert_eq!(cached_rows(&st), 0);
+}
+
+pub(crate) fn truncate_scanned_data_requeues_range<T: ShieldedPoolTester>() {
+    let mut st = TestBuilder::new()
+        .with_block_cache()
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+    let account_id = account.account_id();
+    let dfvk = T::test_account_fvk(&st);
+
+    // Add funds to the wallet in two blocks
+    let value = NonNegativeAmount::const_from_u64(50000);
+    let (h1, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+    let (h2, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+    st.scan_cached_blocks(h1, 2);
+    let total = (value + value).unwrap();
+    assert_eq!(st.get_spendable_balance(account_id, 1), total);
+
+    // Attach a memo to the note received in the first block, as though it had been obtained
+    // from the full transaction.
+    let memo = MemoBytes::from(Memo::from_str("requested again").unwrap());
+    let memo_txid = st
+        .wallet()
+        .conn
+        .query_row(
+            "SELECT txid FROM transactions WHERE block = :height",
+            named_params![":height": u32::from(h1)],
+            |row| row.get::<_, [u8; 32]>(0).map(TxId::from_bytes),
+        )
+        .unwrap();
+    st.wallet()
+        .conn
+        .execute(
+            &format!(
+                "UPDATE {}_received_notes SET memo = :memo
+                 WHERE tx IN (SELECT id_tx FROM transactions WHERE block = :height)",
+                T::TABLES_PREFIX,
+            ),
+            named_params![":memo": memo.as_slice(), ":height": u32::from(h1)],
+        )
+        .unwrap();
+
+    st.wallet_mut().truncate_scanned_data(h1..(h2 + 1)).unwrap();
+
+    // Both notes are removed, and the transaction whose note carried a memo is requested
+    // again so that the memo can be restored.
+    assert_eq!(st.get_total_balance(account_id), NonNegativeAmount::ZERO);
+    assert_eq!(
+        st.wallet().transaction_data_requests().unwrap(),
+        vec![TransactionDataRequest::Enhancement(memo_txid)]
+    );
+
+    // The range is queued to be scanned again.
+    assert!(st
+        .wallet()
+        .suggest_scan_ranges()
+        .unwrap()
+        .iter()
+        .any(|range| range.priority() == ScanPriority::Historic
+            && range.block_range().start <= h1
+            && range.block_range().end > h2));
+
+    // Rescanning the range restores the notes.
+    st.scan_cached_blocks(h1, 2);
+    assert_eq!(st.get_spendable_balance(account_id, 1), total);
+}
+
+fn received_note_spend_count<T: ShieldedPoolTester>(st: &TestState<BlockCache>) -> u32 {
+    st.wallet()
+        .conn
+        .query_row(
+            &format!(
+                "SELECT COUNT(*) FROM {}_received_note_spends",
+                T::TABLES_PREFIX
+            ),
+            [],
+            |row| row.get(0),
+        )
+        .unwrap()
+}
+
+pub(crate) fn rescan_from_rolls_back_spends<T: ShieldedPoolTester>() {
+    let mut st = TestBuilder::new()
+        .with_block_cache()
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+    let account_id = account.account_id();
+    let dfvk = T::test_account_fvk(&st);
+
+    // Receive a note, and then spend it.
+    let value = NonNegativeAmount::const_from_u64(50000);
+    let (received_height, _, nf) =
+        st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+    let not_our_key = T::sk_to_fvk(&T::sk(&[0xf5; 32]));
+    let to = T::fvk_default_address(&not_our_key);
+    let value2 = NonNegativeAmount::const_from_u64(20000);
+    let (spent_height, _) = st.generate_next_block_spending(&dfvk, (nf, value), to, value2);
+    st.scan_cached_blocks(received_height, 2);
+    let change = (value - value2).unwrap();
+    assert_eq!(st.get_total_balance(account_id), change);
+    assert_eq!(received_note_spend_count::<T>(&st), 1);
+
+    let spending_txid = st
+        .wallet()
+        .conn
+        .query_row(
+            "SELECT txid FROM transactions WHERE block = :height",
+            named_params![":height": u32::from(spent_height)],
+            |row| row.get::<_, [u8; 32]>(0).map(TxId::from_bytes),
+        )
+        .unwrap();
+
+    st.wallet_mut().rescan_from(spent_height).unwrap();
+
+    // The spend has been rolled back, but the chain tip is unchanged and the account is
+    // retained. The full data of the spending transaction is requested again.
+    assert_eq!(received_note_spend_count::<T>(&st), 0);
+    assert!(st
+        .wallet()
+        .transaction_data_requests()
+        .unwrap()
+        .contains(&TransactionDataRequest::Enhancement(spending_txid)));
+    assert_eq!(st.wallet().chain_height().unwrap(), Some(spent_height));
+    assert_eq!(st.wallet().get_account_ids().unwrap(), vec![account_id]);
+    assert!(st
+        .wallet()
+        .suggest_scan_ranges()
+        .unwrap()
+        .iter()
+        .any(|range| range.priority() == ScanPriority::Historic
+            && range.block_range().start == spent_height));
+
+    // Rescanning the block rediscovers the spend.
+    st.scan_cached_blocks(spent_height, 1);
+    assert_eq!(received_note_spend_count::<T>(&st), 1);
+    assert_eq!(st.get_total_balance(account_id), change);
+}
+
+pub(crate) fn rescan_from_retains_wallet_spends<T: ShieldedPoolTester>() {
+    let mut st = TestBuilder::new()
+        .with_block_cache()
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+    let (_, _, proposal) = propose_single_transfer::<T>(&mut st);
+    let txid = st
+        .create_proposed_transactions::<Infallible, _>(account.usk(), OvkPolicy::Sender, &proposal)
+        .unwrap()[0];
+    let (h, _) = st.generate_next_block_including(txid);
+    st.scan_cached_blocks(h, 1);
+    assert_eq!(received_note_spend_count::<T>(&st), 1);
+
+    st.wallet_mut().rescan_from(h).unwrap();
+
+    // The spend by the wallet's own transaction is retained, and its data is not requested.
+    assert_eq!(received_note_spend_count::<T>(&st), 1);
+    assert!(!st
+        .wallet()
+        .transaction_data_requests()
+        .unwrap()
+        .contains(&TransactionDataRequest::Enhancement(txid)));
+}
+
+pub(crate) fn export_import_tree_state<T: ShieldedPoolTester>() {
+    let mut st = TestBuilder::new()
+        .with_block_cache()
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let dfvk = T::test_account_fvk(&st);
+    let max_checkpoint_id = |conn: &rusqlite::Connection| -> Option<u32> {
+        conn.query_row(
+            &format!(
+                "SELECT MAX(checkpoint_id) FROM {}_tree_checkpoints",
+                T::TABLES_PREFIX
+            ),
+            [],
+            |row| row.get(0),
+        )
+        .unwrap()
+    };
+
+    let value = NonNegativeAmount::const_from_u64(50000);
+    let (h1, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+    let (h2, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+    st.scan_cached_blocks(h1, 2);
+
+    // Exporting the state as of the first block does not modify the wallet.
+    let exported = st.wallet_mut().export_tree_state(h1).unwrap();
+    assert_eq!(max_checkpoint_id(&st.wallet().conn), Some(u32::from(h2)));
+    assert_ne!(st.wallet_mut().export_tree_state(h2).unwrap(), exported);
+
+    // There is no checkpoint beyond the chain tip.
+    assert_matches!(
+        st.wallet_mut().export_tree_state(h2 + 1),
+        Err(SqliteClientError::CommitmentTree(ShardTreeError::Query(_)))
+    );
+
+    // Importing the state into another wallet reproduces the trees as of the first block.
+    let mut other = TestBuilder::new().build();
+    assert_eq!(other.wallet_mut().import_tree_state(&exported).unwrap(), h1);
+    assert_eq!(max_checkpoint_id(&other.wallet().conn), Some(u32::from(h1)));
+    assert_eq!(other.wallet_mut().export_tree_state(h1).unwrap(), exported);
+
+    // Malformed state is rejected.
+    assert_matches!(
+        other
+            .wallet_mut()
+            .import_tree_state(&exported[..exported.len() - 1]),
+        Err(_)
+    );
+}
+
+pub(crate) fn repair_witnesses_rescans_damaged_shard<T: ShieldedPoolTester>() {
+    let mut st = TestBuilder::new()
+        .with_block_cache()
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+    let account_id = account.account_id();
+    let dfvk = T::test_account_fvk(&st);
+
+    let value = NonNegativeAmount::const_from_u64(50000);
+    let (h1, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+    let (h2, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+    st.scan_cached_blocks(h1, 2);
+    assert_eq!(
+        st.get_spendable_balance(account_id, 1),
+        (value + value).unwrap()
+    );
+
+    // The witnesses of intact notes are valid.
+    assert_eq!(
+        st.wallet_mut().repair_witnesses(h1..h2 + 1).unwrap(),
+        vec![]
+    );
+
+    // Discard the tree data for the shard containing the notes.
+    st.wallet()
+        .conn
+        .execute(&format!("DELETE FROM {}_tree_shards", T::TABLES_PREFIX), [])
+        .unwrap();
+
+    // Both notes are detected, and the shard's blocks are queued for rescanning.
+    let invalid = st.wallet_mut().repair_witnesses(h1..h2 + 1).unwrap();
+    assert_eq!(invalid.len(), 2);
+    assert!(invalid
+        .iter()
+        .all(|note_id| note_id.protocol() == T::SHIELDED_PROTOCOL));
+    assert!(st
+        .wallet()
+        .suggest_scan_ranges()
+        .unwrap()
+        .iter()
+        .any(|range| range.priority() == ScanPriority::FoundNote
+            && range.block_range().start <= h1
+            && range.block_range().end > h2));
+
+    // Rescanning the shard's blocks restores the witnesses.
+    st.scan_cached_blocks(h1, 2);
+    assert_eq!(
+        st.wallet_mut().repair_witnesses(h1..h2 + 1).unwrap(),
+        vec![]
+    );
+    assert_eq!(
+        st.get_spendable_balance(account_id, 1),
+        (value + value).unwrap()
+    );
+}
+
+pub(crate) fn prune_note_commitment_trees<T: ShieldedPoolTester>() {
+    let mut st = TestBuilder::new()
+        .with_block_cache()
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+    let account_id = account.account_id();
+    let dfvk = T::test_account_fvk(&st);
+    let metrics = |st: &TestState<BlockCache>| {
+        st.wallet()
+            .note_commitment_tree_metrics()
+            .unwrap()
+            .into_iter()
+            .find(|m| m.protocol() == T::SHIELDED_PROTOCOL)
+            .unwrap()
+    };
+    let min_checkpoint_id = |conn: &rusqlite::Connection| -> Option<u32> {
+        conn.query_row(
+            &format!(
+                "SELECT MIN(checkpoint_id) FROM {}_tree_checkpoints",
+                T::TABLES_PREFIX
+            ),
+            [],
+            |row| row.get(0),
+        )
+        .unwrap()
+    };
+
+    // Pruning an empty wallet has no effect.
+    assert!(st
+        .wallet_mut()
+        .prune_note_commitment_trees(0)
+        .unwrap()
+        .iter()
+        .all(|p| p.checkpoints_removed() == 0 && p.shards_pruned() == 0));
+
+    let value = NonNegativeAmount::const_from_u64(50000);
+    let (h1, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+    for _ in 0..120 {
+        st.generate_empty_block();
+    }
+    st.scan_cached_blocks(h1, 121);
+    let tip = h1 + 120;
+
+    let before = metrics(&st);
+    assert_eq!(before.shard_count(), 1);
+    assert_eq!(before.pruned_shard_count(), 0);
+    assert!(before.checkpoint_count() > 0);
+
+    // Checkpoints below the stability depth are removed, but the shard containing the unspent
+    // note is retained.
+    let pruning = st.wallet_mut().prune_note_commitment_trees(110).unwrap();
+    let pruning = pruning
+        .iter()
+        .find(|p| p.protocol() == T::SHIELDED_PROTOCOL)
+        .unwrap();
+    assert_eq!(pruning.shards_pruned(), 0);
+    assert_eq!(pruning.bytes_freed(), 0);
+    assert!(min_checkpoint_id(&st.wallet().conn).unwrap() >= u32::from(tip) - 110);
+
+    let after = metrics(&st);
+    assert_eq!(
+        after.checkpoint_count(),
+        before.checkpoint_count() - pruning.checkpoints_removed()
+    );
+    assert_eq!(after.shard_data_bytes(), before.shard_data_bytes());
+
+    // The note remains spendable.
+    assert_eq!(st.get_spendable_balance(account_id, 1), value);
+}
+
+pub(crate) fn view_snapshots<T: ShieldedPoolTester>() {
+    let mut st = TestBuilder::new()
+        .with_block_cache()
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+    let dfvk = T::test_account_fvk(&st);
+
+    // Receive two notes, and then send part of their value to an external recipient with a
+    // memo, returning the remainder to the wallet as change.
+    let (h, _, _) = st.generate_next_block(
+        &dfvk,
+        AddressType::DefaultExternal,
+        NonNegativeAmount::const_from_u64(60000),
+    );
+    st.generate_next_block(
+        &dfvk,
+        AddressType::DefaultExternal,
+        NonNegativeAmount::const_from_u64(40000),
+    );
+    st.scan_cached_blocks(h, 2);
+
+    let to_extsk = T::sk(&[0xf5; 32]);
+    let to: Address = T::sk_default_address(&to_extsk);
+    let request = zip321::TransactionRequest::new(vec![Payment::new(
+        to.to_zcash_address(&st.network()),
+        NonNegativeAmount::const_from_u64(70000),
+        Some("Snapshot memo".parse::<Memo>().unwrap().into()),
+        None,
+        None,
+        vec![],
+    )
+    .unwrap()])
+    .unwrap();
+
+    let change_strategy = standard::SingleOutputChangeStrategy::new(
+        StandardFeeRule::Zip317,
+        None,
+        T::SHIELDED_PROTOCOL,
+    );
+    let input_selector = &GreedyInputSelector::new(change_strategy, DustOutputPolicy::default());
+    let proposal = st
+        .propose_transfer(
+            account.account_id(),
+            input_selector,
+            request,
+            NonZeroU32::new(1).unwrap(),
+        )
+        .unwrap();
+    let txids = st
+        .create_proposed_transactions::<Infallible, _>(account.usk(), OvkPolicy::Sender, &proposal)
+        .unwrap();
+
+    // Snapshot the views both before and after the transaction is mined.
+    assert_views_snapshot(
+        &st.wallet().conn,
+        &format!("{}_send_unmined", T::TABLES_PREFIX),
+        TIME_DEPENDENT_COLUMNS,
+    );
+
+    let (h, _) = st.generate_next_block_including(txids.head);
+    st.scan_cached_blocks(h, 1);
+    assert_views_snapshot(
+        &st.wallet().conn,
+        &format!("{}_send_mined", T::TABLES_PREFIX),
+        TIME_DEPENDENT_COLUMNS,
+    );
+}
+
+pub(crate) fn balance_at_height<T: ShieldedPoolTester>() {
+    let mut st = TestBuilder::new()
+        .with_block_cache()
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+    let account_id = account.account_id();
+    let dfvk = T::test_account_fvk(&st);
+
+    let (h1, _, _) = st.generate_next_block(
+        &dfvk,
+        AddressType::DefaultExternal,
+        NonNegativeAmount::const_from_u64(60000),
+    );
+    let (h2, _, _) = st.generate_next_block(
+        &dfvk,
+        AddressType::DefaultExternal,
+        NonNegativeAmount::const_from_u64(40000),
+    );
+    st.scan_cached_blocks(h1, 2);
+
+    let to_extsk = T::sk(&[0xf5; 32]);
+    let to: Address = T::sk_default_address(&to_extsk);
+    let request = zip321::TransactionRequest::new(vec![Payment::without_memo(
+        to.to_zcash_address(&st.network()),
+        NonNegativeAmount::const_from_u64(70000),
+    )])
+    .unwrap();
+    let change_strategy = standard::SingleOutputChangeStrategy::new(
+        StandardFeeRule::Zip317,
+        None,
+        T::SHIELDED_PROTOCOL,
+    );
+    let input_selector = &GreedyInputSelector::new(change_strategy, DustOutputPolicy::default());
+    let proposal = st
+        .propose_transfer(
+            account_id,
+            input_selector,
+            request,
+            NonZeroU32::new(1).unwrap(),
+        )
+        .unwrap();
+    let fee = proposal.steps().head.balance().fee_required();
+    let txids = st
+        .create_proposed_transactions::<Infallible, _>(account.usk(), OvkPolicy::Sender, &proposal)
+        .unwrap();
+
+    let balance_at = |st: &TestState<BlockCache>, height| {
+        st.wallet()
+            .get_balance_at_height(account_id, height)
+            .unwrap()
+            .map(|balance| balance.total())
+    };
+
+    // The unmined transaction does not affect the balance as of any height.
+    assert_eq!(
+        balance_at(&st, h1),
+        Some(NonNegativeAmount::const_from_u64(60000))
+    );
+    assert_eq!(
+        balance_at(&st, h2),
+        Some(NonNegativeAmount::const_from_u64(100000))
+    );
+
+    // The balance beyond the scanned chain cannot be determined.
+    assert_eq!(balance_at(&st, h2 + 1), None);
+
+    let (h3, _) = st.generate_next_block_including(txids.head);
+    st.scan_cached_blocks(h3, 1);
+
+    // Once the transaction is mined, it reduces the balance only from the height at which it
+    // was mined.
+    assert_eq!(
+        balance_at(&st, h2),
+        Some(NonNegativeAmount::const_from_u64(100000))
+    );
+    let change = (NonNegativeAmount::const_from_u64(30000) - fee).unwrap();
+    let balance = st
+        .wallet()
+        .get_balance_at_height(account_id, h3)
+        .unwrap()
+        .unwrap();
+    assert_eq!(balance.height(), h3);
+    assert_eq!(balance.total(), change);
+    assert_eq!(balance.unshielded(), NonNegativeAmount::ZERO);
+    assert_eq!(st.get_total_balance(account_id), change);
+}
+
+pub(crate) fn account_statistics<T: ShieldedPoolTester>() {
+    let mut st = TestBuilder::new()
+        .with_block_cache()
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+    let account_id = account.account_id();
+    let dfvk = T::test_account_fvk(&st);
+
+    assert_eq!(
+        st.wallet()
+            .get_account_statistics(account_id, StatisticsGranularity::Day)
+            .unwrap(),
+        vec![]
+    );
+
+    let (h, _, _) = st.generate_next_block(
+        &dfvk,
+        AddressType::DefaultExternal,
+        NonNegativeAmount::const_from_u64(60000),
+    );
+    st.generate_next_block(
+        &dfvk,
+        AddressType::DefaultExternal,
+        NonNegativeAmount::const_from_u64(40000),
+    );
+    st.scan_cached_blocks(h, 2);
+
+    let to_extsk = T::sk(&[0xf5; 32]);
+    let to: Address = T::sk_default_address(&to_extsk);
+    let request = zip321::TransactionRequest::new(vec![Payment::without_memo(
+        to.to_zcash_address(&st.network()),
+        NonNegativeAmount::const_from_u64(70000),
+    )])
+    .unwrap();
+    let change_strategy = standard::SingleOutputChangeStrategy::new(
+        StandardFeeRule::Zip317,
+        None,
+        T::SHIELDED_PROTOCOL,
+    );
+    let input_selector = &GreedyInputSelector::new(change_strategy, DustOutputPolicy::default());
+    let proposal = st
+        .propose_transfer(
+            account_id,
+            input_selector,
+            request,
+            NonZeroU32::new(1).unwrap(),
+        )
+        .unwrap();
+    let fee = proposal.steps().head.balance().fee_required();
+    let txids = st
+        .create_proposed_transactions::<Infallible, _>(account.usk(), OvkPolicy::Sender, &proposal)
+        .unwrap();
+
+    // The blocks generated by the test framework all have a timestamp of zero. The unmined
+    // transaction is not included in the statistics.
+    let epoch = time::OffsetDateTime::UNIX_EPOCH.date();
+    let received = PeriodStatistics::from_parts(
+        epoch,
+        NonNegativeAmount::const_from_u64(100000),
+        2,
+        NonNegativeAmount::ZERO,
+        0,
+        NonNegativeAmount::ZERO,
+    );
+    assert_eq!(
+        st.wallet()
+            .get_account_statistics(account_id, StatisticsGranularity::Day)
+            .unwrap(),
+        vec![received]
+    );
+
+    let (h, _) = st.generate_next_block_including(txids.head);
+    st.scan_cached_blocks(h, 1);
+
+    let expected = PeriodStatistics::from_parts(
+        epoch,
+        NonNegativeAmount::const_from_u64(100000),
+        2,
+        NonNegativeAmount::const_from_u64(70000),
+        1,
+        fee,
+    );
+    for granularity in [StatisticsGranularity::Day, StatisticsGranularity::Month] {
+        assert_eq!(
+            st.wallet()
+                .get_account_statistics(account_id, granularity)
+                .unwrap(),
+            vec![expected]
+        );
+    }
+}
+
+pub(crate) fn scheduled_payments<T: ShieldedPoolTester>() {
+    let mut st = TestBuilder::new()
+        .with_block_cache()
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+    let account_id = account.account_id();
+    let dfvk = T::test_account_fvk(&st);
+
+    let (h, _, _) = st.generate_next_block(
+        &dfvk,
+        AddressType::DefaultExternal,
+        NonNegativeAmount::const_from_u64(60000),
+    );
+    st.scan_cached_blocks(h, 1);
+
+    let to_extsk = T::sk(&[0xf5; 32]);
+    let to: Address = T::sk_default_address(&to_extsk);
+    let payment = Payment::new(
+        to.to_zcash_address(&st.network()),
+        NonNegativeAmount::const_from_u64(10000),
+        Some(MemoBytes::from(Memo::from_str("rent").unwrap())),
+        Some("Rent".to_owned()),
+        None,
+        vec![],
+    )
+    .unwrap();
+
+    let first_due = time::OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+    let interval = PaymentInterval::Months(NonZeroU32::new(1).unwrap());
+    let id = st
+        .wallet_mut()
+        .schedule_payment(account_id, &payment, interval, first_due)
+        .unwrap();
+
+    // The payment is not due before its first due time.
+    let before = first_due - time::Duration::SECOND;
+    assert_eq!(st.wallet().payments_due(before).unwrap(), vec![]);
+
+    let due = st.wallet().payments_due(first_due).unwrap();
+    assert_eq!(due.len(), 1);
+    assert_eq!(due[0].id(), id);
+    assert_eq!(due[0].account_id(), account_id);
+    assert_eq!(due[0].payment(), &payment);
+    assert_eq!(due[0].payments_made(), 0);
+
+    let input_selector = input_selector(StandardFeeRule::Zip317, None, T::SHIELDED_PROTOCOL);
+    let proposal = st
+        .propose_transfer(
+            due[0].account_id(),
+            &input_selector,
+            due[0].to_transaction_request().unwrap(),
+            NonZeroU32::new(1).unwrap(),
+        )
+        .unwrap();
+    st.create_proposed_transactions::<Infallible, _>(account.usk(), OvkPolicy::Sender, &proposal)
+        .unwrap();
+
+    // Once the payment is recorded, it is not due again until a month later.
+    let next_due = st.wallet_mut().record_scheduled_payment(id).unwrap();
+    assert_eq!(next_due, interval.nth_after(first_due, 1).unwrap());
+    assert_eq!(
+        st.wallet()
+            .payments_due(next_due - time::Duration::SECOND)
+            .unwrap(),
+        vec![]
+    );
+    let due = st.wallet().payments_due(next_due).unwrap();
+    assert_eq!(due.len(), 1);
+    assert_eq!(due[0].payments_made(), 1);
+    assert_eq!(due[0].next_due(), Some(next_due));
+
+    st.wallet_mut().cancel_scheduled_payment(id).unwrap();
+    assert_eq!(st.wallet().payments_due(next_due).unwrap(), vec![]);
+    assert_matches!(
+        st.wallet_mut().cancel_scheduled_payment(id),
+        Err(SqliteClientError::ScheduledPaymentUnknown(unknown)) if unknown == id
+    );
+}
+
+pub(crate) fn batch_transfer<T: ShieldedPoolTester>() {
+    let mut st = TestBuilder::new()
+        .with_block_cache()
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+    let account_id = account.account_id();
+    let dfvk = T::test_account_fvk(&st);
+
+    // Fund the account with two notes, each of which can fund a single batch.
+    let (h, _, _) = st.generate_next_block(
+        &dfvk,
+        AddressType::DefaultExternal,
+        NonNegativeAmount::const_from_u64(50000),
+    );
+    st.generate_next_block(
+        &dfvk,
+        AddressType::DefaultExternal,
+        NonNegativeAmount::const_from_u64(50000),
+    );
+    st.scan_cached_blocks(h, 2);
+
+    let to_extsk = T::sk(&[0xf5; 32]);
+    let to: Address = T::sk_default_address(&to_extsk);
+    let payments = (0..5)
+        .map(|_| {
+            Payment::without_memo(
+                to.to_zcash_address(&st.network()),
+                NonNegativeAmount::const_from_u64(10000),
+            )
+        })
+        .collect::<Vec<_>>();
+    let change_strategy = standard::SingleOutputChangeStrategy::new(
+        StandardFeeRule::Zip317,
+        None,
+        T::SHIELDED_PROTOCOL,
+    );
+    let network = st.network();
+
+    // A transaction paying a single recipient has at least two logical actions.
+    assert_matches!(
+        propose_batch_transfer::<_, _, _, Infallible>(
+            st.wallet_mut(),
+            &network,
+            account_id,
+            &change_strategy,
+            payments.clone(),
+            NonZeroUsize::new(1).unwrap(),
+            NonZeroU32::new(1).unwrap(),
+        ),
+        Err(Error::ActionLimitExceeded {
+            payment_index: 0,
+            actions: 2,
+            limit: 1,
+        })
+    );
+
+    // With a limit of three logical actions, each batch pays two recipients and its change.
+    let plan = propose_batch_transfer::<_, _, _, Infallible>(
+        st.wallet_mut(),
+        &network,
+        account_id,
+        &change_strategy,
+        payments,
+        NonZeroUsize::new(3).unwrap(),
+        NonZeroU32::new(1).unwrap(),
+    )
+    .unwrap();
+
+    let batches = plan.batches();
+    assert_eq!(batches.len(), 3);
+    assert_eq!(batches[0].payment_indices(), &[0, 1]);
+    assert_eq!(batches[1].payment_indices(), &[2, 3]);
+    assert_eq!(batches[2].payment_indices(), &[4]);
+
+    // The first two batches spend different notes; the last must wait for their change.
+    let inputs = batches[..2]
+        .iter()
+        .map(|batch| {
+            let step = batch.proposal().unwrap().steps().first();
+            assert!(batch.depends_on().is_empty());
+            assert_eq!(step.transaction_request().payments().len(), 2);
+            let notes = step.shielded_inputs().unwrap().notes();
+            assert_eq!(notes.len(), 1);
+            *notes.first().internal_note_id()
+        })
+        .collect::<Vec<_>>();
+    assert_ne!(inputs[0], inputs[1]);
+    assert!(batches[2].proposal().is_none());
+    assert_eq!(batches[2].depends_on(), &[0, 1]);
+    assert_eq!(plan.deferred().count(), 1);
+
+    // The proposed batches can be executed independently of one another.
+    for batch in &batches[..2] {
+        st.create_proposed_transactions::<Infallible, _>(
+            account.usk(),
+            OvkPolicy::Sender,
+            batch.proposal().unwrap(),
+        )
+        .unwrap();
+    }
+}
+
+pub(crate) fn transaction_size_estimate<T: ShieldedPoolTester>() {
+    let mut st = TestBuilder::new()
+        .with_block_cache()
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+    let dfvk = T::test_account_fvk(&st);
+
+    let (h, _, _) = st.generate_next_block(
+        &dfvk,
+        AddressType::DefaultExternal,
+        NonNegativeAmount::const_from_u64(60000),
+    );
+    st.generate_next_block(
+        &dfvk,
+        AddressType::DefaultExternal,
+        NonNegativeAmount::const_from_u64(60000),
+    );
+    st.scan_cached_blocks(h, 2);
+
+    let to_extsk = T::sk(&[0xf5; 32]);
+    let to: Address = T::sk_default_address(&to_extsk);
+    let request = zip321::TransactionRequest::new(vec![
+        Payment::without_memo(
+            to.to_zcash_address(&st.network()),
+            NonNegativeAmount::const_from_u64(50000),
+        ),
+        Payment::without_memo(
+            to.to_zcash_address(&st.network()),
+            NonNegativeAmount::const_from_u64(30000),
+        ),
+    ])
+    .unwrap();
+
+    let input_selector = input_selector(StandardFeeRule::Zip317, None, T::SHIELDED_PROTOCOL);
+    let proposal = st
+        .propose_transfer(
+            account.account_id(),
+            &input_selector,
+            request,
+            NonZeroU32::new(1).unwrap(),
+        )
+        .unwrap();
+
+    // Two spends, and two payments plus change.
+    assert_eq!(fees::logical_action_count(&proposal).head, 3);
+    let estimate = fees::estimate_tx_size(&proposal).head;
+    assert!(estimate < fees::MAX_STANDARD_TX_SIZE);
+
+    let txids = st
+        .create_proposed_transactions::<Infallible, _>(account.usk(), OvkPolicy::Sender, &proposal)
+        .unwrap();
+    let tx = st.wallet().get_transaction(txids.head).unwrap().unwrap();
+    let mut tx_bytes = vec![];
+    tx.write(&mut tx_bytes).unwrap();
+
+    // The transaction is fully shielded, so the estimate is exact.
+    assert_eq!(estimate, tx_bytes.len());
+}
+
+pub(crate) fn conflicting_proposals<T: ShieldedPoolTester>() {
+    let mut st = TestBuilder::new()
+        .with_block_cache()
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+    let dfvk = T::test_account_fvk(&st);
+
+    // The account has a single note, so any two proposals must spend it.
+    let (h, _, _) = st.generate_next_block(
+        &dfvk,
+        AddressType::DefaultExternal,
+        NonNegativeAmount::const_from_u64(60000),
+    );
+    st.scan_cached_blocks(h, 1);
+
+    let to_extsk = T::sk(&[0xf5; 32]);
+    let to: Address = T::sk_default_address(&to_extsk);
+    let input_selector = input_selector(StandardFeeRule::Zip317, None, T::SHIELDED_PROTOCOL);
+    let mut propose = |value| {
+        let request = zip321::TransactionRequest::new(vec![Payment::without_memo(
+            to.to_zcash_address(&st.network()),
+            NonNegativeAmount::const_from_u64(value),
+        )])
+        .unwrap();
+        st.propose_transfer(
+            account.account_id(),
+            &input_selector,
+            request,
+            NonZeroU32::new(1).unwrap(),
+        )
+        .unwrap()
+    };
+    let first = propose(10000);
+    let second = propose(20000);
+
+    let spent = first
+        .steps()
+        .first()
+        .shielded_inputs()
+        .unwrap()
+        .notes()
+        .first();
+    let spent_id = zcash_client_backend::wallet::NoteId::new(
+        *spent.txid(),
+        T::SHIELDED_PROTOCOL,
+        spent.output_index(),
+    );
+
+    let mut reservations = NoteReservations::new();
+    let reserved: Result<(), Error<Infallible, Infallible, Infallible, Infallible>> =
+        reservations.reserve(&first);
+    assert_matches!(reserved, Ok(()));
+    assert!(reservations.is_reserved(&spent_id));
+
+    // The second proposal spends the same note, so the conflict is detected before building.
+    let conflict: Result<(), Error<Infallible, Infallible, Infallible, Infallible>> =
+        reservations.reserve(&second);
+    assert_matches!(
+        conflict,
+        Err(Error::ConflictingProposal { notes, outpoints })
+            if notes == vec![spent_id] && outpoints.is_empty()
+    );
+
+    // Building the second proposal is also rejected while the first one holds the reservation,
+    // and the wallet is not modified.
+    assert_matches!(
+        st.create_proposed_transactions_with_reservations::<Infallible, _>(
+            account.usk(),
+            OvkPolicy::Sender,
+            &second,
+            &mut reservations,
+        ),
+        Err(Error::ConflictingProposal { notes, .. }) if notes == vec![spent_id]
+    );
+    assert!(reservations.is_reserved(&spent_id));
+
+    // Building the first proposal records the note as spent and releases its reservation.
+    st.create_proposed_transactions_with_reservations::<Infallible, _>(
+        account.usk(),
+        OvkPolicy::Sender,
+        &first,
+        &mut reservations,
+    )
+    .unwrap();
+    assert!(reservations.is_empty());
+}
+
+pub(crate) fn external_spends_are_marked<T: ShieldedPoolTester>() {
+    let mut st = TestBuilder::new()
+        .with_block_cache()
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+    let account_id = account.account_id();
+    let dfvk = T::test_account_fvk(&st);
+    st.wallet_mut().set_event_journal_enabled(true).unwrap();
+
+    // Receive a note, and spend it in a transaction that the wallet did not create, as another
+    // device using the same seed would.
+    let value = NonNegativeAmount::const_from_u64(50000);
+    let (received_height, _, nf) =
+        st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+    let not_our_key = T::sk_to_fvk(&T::sk(&[0xf5; 32]));
+    let to = T::fvk_default_address(&not_our_key);
+    let (external_height, _) = st.generate_next_block_spending(
+        &dfvk,
+        (nf, value),
+        to.clone(),
+        NonNegativeAmount::const_from_u64(20000),
+    );
+    st.scan_cached_blocks(received_height, 2);
+
+    // A transaction created by the wallet is not marked when it is mined.
+    let proposal = st
+        .propose_standard_transfer::<Infallible>(
+            account_id,
+            StandardFeeRule::Zip317,
+            NonZeroU32::new(1).unwrap(),
+            &to,
+            NonNegativeAmount::const_from_u64(10000),
+            None,
+            None,
+            T::SHIELDED_PROTOCOL,
+        )
+        .unwrap();
+    let txid = st
+        .create_proposed_transactions::<Infallible, _>(account.usk(), OvkPolicy::Sender, &proposal)
+        .unwrap()[0];
+    let (created_height, _) = st.generate_next_block_including(txid);
+    st.scan_cached_blocks(created_height, 1);
+
+    let marked = st
+        .wallet()
+        .conn
+        .prepare("SELECT mined_height, spent_externally FROM v_transactions ORDER BY mined_height")
+        .unwrap()
+        .query_map([], |row| {
+            Ok((BlockHeight::from_u32(row.get(0)?), row.get(1)?))
+        })
+        .unwrap()
+        .collect::<Result<Vec<(BlockHeight, bool)>, _>>()
+        .unwrap();
+    assert_eq!(
+        marked,
+        vec![
+            (received_height, false),
+            (external_height, true),
+            (created_height, false),
+        ]
+    );
+
+    // The detection is recorded in the event journal.
+    let events = st
+        .wallet()
+        .export_event_journal(None)
+        .unwrap()
+        .into_iter()
+        .filter(|e| e.kind() == WalletEventKind::ExternalSpendDetected)
+        .collect::<Vec<_>>();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].start_height(), Some(external_height));
+    assert!(events[0].txid().is_some());
+}
+
+pub(crate) fn rescanning_scanned_blocks_is_noop<T: ShieldedPoolTester>() {
+    let mut st = TestBuilder::new()
+        .with_block_cache()
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+    let account_id = account.account_id();
+    let dfvk = T::test_account_fvk(&st);
+    st.wallet_mut().set_event_journal_enabled(true).unwrap();
+
+    // Receive two notes, one of which is partly spent in between.
+    let value = NonNegativeAmount::const_from_u64(60000);
+    let (h, _, nf) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+    let not_our_key = T::sk_to_fvk(&T::sk(&[0xf5; 32]));
+    let to = T::fvk_default_address(&not_our_key);
+    st.generate_next_block_spending(
+        &dfvk,
+        (nf, value),
+        to.clone(),
+        NonNegativeAmount::const_from_u64(20000),
+    );
+    st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+
+    let wallet_state = |st: &TestState<BlockCache>| {
+        let counts = st
+            .wallet()
+            .conn
+            .query_row(
+                &format!(
+                    "SELECT (SELECT COUNT(*) FROM transactions),
+                            (SELECT COUNT(*) FROM v_received_notes),
+                            (SELECT COUNT(*) FROM v_received_note_spends),
+                            (SELECT COUNT(*) FROM nullifier_map),
+                            (SELECT COUNT(*) FROM {}_tree_checkpoints),
+                            (SELECT COUNT(*) FROM wallet_events WHERE kind = :kind)",
+                    T::TABLES_PREFIX
+                ),
+                named_params![":kind": WalletEventKind::NotesDetected.code()],
+                |row| {
+                    Ok([
+                        row.get::<_, u64>(0)?,
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                        row.get(4)?,
+                        row.get(5)?,
+                    ])
+                },
+            )
+            .unwrap();
+        (
+            counts,
+            st.get_total_balance(account_id),
+            st.get_spendable_balance(account_id, 1),
+        )
+    };
+
+    let first = st.scan_cached_blocks(h, 3);
+    let scanned = wallet_state(&st);
+    assert_eq!(scanned.1, NonNegativeAmount::const_from_u64(40000 + 60000));
+
+    // Scanning the same range again, as would happen if the wallet were interrupted after
+    // committing the scan results but before recording its progress, changes nothing.
+    let second = st.scan_cached_blocks(h, 3);
+    assert_eq!(second.scanned_range(), first.scanned_range());
+    assert_eq!(wallet_state(&st), scanned);
+
+    // The note commitment tree is intact, so the rescanned notes can be spent.
+    let proposal = st
+        .propose_standard_transfer::<Infallible>(
+            account_id,
+            StandardFeeRule::Zip317,
+            NonZeroU32::new(1).unwrap(),
+            &to,
+            NonNegativeAmount::const_from_u64(80000),
+            None,
+            None,
+            T::SHIELDED_PROTOCOL,
+        )
+        .unwrap();
+    assert_matches!(
+        st.create_proposed_transactions::<Infallible, _>(
+            account.usk(),
+            OvkPolicy::Sender,
+            &proposal
+        ),
+        Ok(_)
+    );
+}
+
+/// Checks that the value of the notes spent by the given transaction is equal to the value of
+/// its outputs recorded in `sent_notes` plus its fee, and that each change output of the
+/// transaction is recorded in `sent_notes` as having been sent from and to the account that
+/// received it.
+///
+/// Returns the number of change outputs of the transaction.
+fn check_sent_notes_balance(st: &TestState<BlockCache>, txid: TxId) -> u32 {
+    let conn = &st.wallet().conn;
+
+    let spent: i64 = conn
+        .query_row(
+            "SELECT SUM(rn.value)
+            FROM v_received_note_spends rns
+            JOIN v_received_notes rn
+                ON rn.pool = rns.pool
+                AND rn.id_within_pool_table = rns.received_note_id
+            WHERE rns.spending_txid = :txid",
+            named_params![":txid": txid.as_ref()],
+            |row| row.get(0),
+        )
+        .unwrap();
+    let (sent, fee): (i64, i64) = conn
+        .query_row(
+            "SELECT SUM(sent_notes.value), transactions.fee
+            FROM sent_notes
+            JOIN transactions ON transactions.id_tx = sent_notes.tx
+            WHERE transactions.txid = :txid
+            GROUP BY transactions.id_tx",
+            named_params![":txid": txid.as_ref()],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!(spent, sent + fee);
+
+    let (change_count, misrecorded_count): (u32, u32) = conn
+        .query_row(
+            "SELECT COUNT(*),
+                    COALESCE(SUM(
+                        sent_notes.id IS NULL
+                        OR sent_notes.from_account_id IS NOT rn.account_id
+                        OR sent_notes.to_account_id IS NOT rn.account_id
+                    ), 0)
+            FROM v_received_notes rn
+            LEFT JOIN sent_notes
+                ON (sent_notes.tx, sent_notes.output_pool, sent_notes.output_index) =
+                   (rn.tx, rn.pool, rn.output_index)
+            WHERE rn.txid = :txid
+            AND rn.is_change",
+            named_params![":txid": txid.as_ref()],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!(misrecorded_count, 0);
+
+    change_count
+}
+
+pub(crate) fn sent_notes_record_change<T: ShieldedPoolTester>() {
+    let mut st = TestBuilder::new()
+        .with_block_cache()
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+    let dfvk = T::test_account_fvk(&st);
+
+    let value = NonNegativeAmount::const_from_u64(60000);
+    let (h, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+    st.scan_cached_blocks(h, 1);
+
+    let to = T::sk_default_address(&T::sk(&[0xf5; 32]));
+    let proposal = st
+        .propose_standard_transfer::<Infallible>(
+            account.account_id(),
+            StandardFeeRule::Zip317,
+            NonZeroU32::new(1).unwrap(),
+            &to,
+            NonNegativeAmount::const_from_u64(20000),
+            None,
+            None,
+            T::SHIELDED_PROTOCOL,
+        )
+        .unwrap();
+    let txid = st
+        .create_proposed_transactions::<Infallible, _>(account.usk(), OvkPolicy::Sender, &proposal)
+        .unwrap()[0];
+
+    // The change output is recorded when the transaction is constructed.
+    assert_eq!(check_sent_notes_balance(&st, txid), 1);
+
+    // Rediscovering the transaction's outputs once it has been mined does not alter the
+    // records.
+    let (h, _) = st.generate_next_block_including(txid);
+    st.scan_cached_blocks(h, 1);
+    let tx = st.wallet().get_transaction(txid).unwrap().unwrap();
+    decrypt_and_store_transaction(&st.network(), st.wallet_mut(), &tx, None).unwrap();
+    assert_eq!(check_sent_notes_balance(&st, txid), 1);
+
+    // The change from a payment to the other shielded pool is sent to the preferred pool,
+    // which need not be the pool of the spent note, and is recorded in the same way.
+    #[cfg(feature = "orchard")]
+    {
+        use crate::wallet::{orchard::tests::OrchardPoolTester, sapling::tests::SaplingPoolTester};
+
+        let to = match T::SHIELDED_PROTOCOL {
+            ShieldedProtocol::Sapling => {
+                OrchardPoolTester::sk_default_address(&OrchardPoolTester::sk(&[0xf5; 32]))
+            }
+            ShieldedProtocol::Orchard => {
+                SaplingPoolTester::sk_default_address(&SaplingPoolTester::sk(&[0xf5; 32]))
+            }
+        };
+        let request = TransactionRequest::new(vec![Payment::without_memo(
+            to.to_zcash_address(&st.network()),
+            NonNegativeAmount::const_from_u64(10000),
+        )])
+        .unwrap();
+        let proposal = st
+            .propose_transfer(
+                account.account_id(),
+                &input_selector(StandardFeeRule::Zip317, None, ShieldedProtocol::Orchard),
+                request,
+                NonZeroU32::new(1).unwrap(),
+            )
+            .unwrap();
+        let txid = st
+            .create_proposed_transactions::<Infallible, _>(
+                account.usk(),
+                OvkPolicy::Sender,
+                &proposal,
+            )
+            .unwrap()[0];
+        assert_eq!(check_sent_notes_balance(&st, txid), 1);
+
+        let (h, _) = st.generate_next_block_including(txid);
+        st.scan_cached_blocks(h, 1);
+        assert_eq!(check_sent_notes_balance(&st, txid), 1);
+    }
+}
+
+pub(crate) fn ledger_entries_balance<T: ShieldedPoolTester>() {
+    let mut st = TestBuilder::new()
+        .with_block_cache()
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+    let dfvk = T::test_account_fvk(&st);
+
+    let value = NonNegativeAmount::const_from_u64(60000);
+    let (h, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+    st.scan_cached_blocks(h, 1);
+
+    let to = T::sk_default_address(&T::sk(&[0xf5; 32]));
+    let send_value = NonNegativeAmount::const_from_u64(20000);
+    let proposal = st
+        .propose_standard_transfer::<Infallible>(
+            account.account_id(),
+            StandardFeeRule::Zip317,
+            NonZeroU32::new(1).unwrap(),
+            &to,
+            send_value,
+            None,
+            None,
+            T::SHIELDED_PROTOCOL,
+        )
+        .unwrap();
+    let fee = proposal.steps().head.balance().fee_required();
+    let txid = st
+        .create_proposed_transactions::<Infallible, _>(account.usk(), OvkPolicy::Sender, &proposal)
+        .unwrap()[0];
+    let (h, _) = st.generate_next_block_including(txid);
+    st.scan_cached_blocks(h, 1);
+
+    // The entries for every transaction sum to zero.
+    let unbalanced: u32 = st
+        .wallet()
+        .conn
+        .query_row(
+            "SELECT COUNT(*) FROM (
+                SELECT txid FROM v_ledger GROUP BY txid HAVING SUM(value) != 0
+            )",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(unbalanced, 0);
+
+    // The transaction created by the wallet is fully accounted for by its spends, outputs, and
+    // fee.
+    let entries = st.wallet().get_ledger_entries(txid).unwrap();
+    let total_of = |entry_type| {
+        entries
+            .iter()
+            .filter(|e| e.entry_type() == entry_type)
+            .map(|e| e.value())
+            .sum::<Option<Amount>>()
+            .unwrap()
+    };
+    assert_eq!(
+        entries.iter().map(|e| e.value()).sum::<Option<Amount>>(),
+        Some(Amount::zero())
+    );
+    assert_eq!(total_of(LedgerEntryType::Spend), -Amount::from(value));
+    assert_eq!(total_of(LedgerEntryType::Send), Amount::from(send_value));
+    assert_eq!(total_of(LedgerEntryType::Fee), Amount::from(fee));
+    assert!(entries
+        .iter()
+        .all(|e| e.entry_type() != LedgerEntryType::External));
+    assert!(entries.iter().all(|e| e.mined_height() == Some(h)));
+    assert!(entries
+        .iter()
+        .filter(|e| matches!(
+            e.entry_type(),
+            LedgerEntryType::Receive | LedgerEntryType::Spend
+        ))
+        .all(|e| e.account_id() == Some(account.account_id())
+            && e.pool() == Some(PoolType::Shielded(T::SHIELDED_PROTOCOL))));
+
+    // The value received from outside the wallet is balanced by an external entry for the
+    // received output.
+    let external: Vec<(i64, Option<i64>)> = st
+        .wallet()
+        .conn
+        .prepare("SELECT value, output_pool FROM v_ledger WHERE entry_type = 'external'")
+        .unwrap()
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(external.len(), 1);
+    assert_eq!(external[0].0, -i64::from(Amount::from(value)));
+    assert!(external[0].1.is_some());
+}
+
+pub(crate) fn decrypted_tx_fee_is_stored<T: ShieldedPoolTester>() {
+    let mut st = TestBuilder::new()
+        .with_block_cache()
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+    let dfvk = T::test_account_fvk(&st);
+
+    let value = NonNegativeAmount::const_from_u64(60000);
+    let (h, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+    st.scan_cached_blocks(h, 1);
+
+    let to = T::sk_default_address(&T::sk(&[0xf5; 32]));
+    let proposal = st
+        .propose_standard_transfer::<Infallible>(
+            account.account_id(),
+            StandardFeeRule::Zip317,
+            NonZeroU32::new(1).unwrap(),
+            &to,
+            NonNegativeAmount::const_from_u64(20000),
+            None,
+            None,
+            T::SHIELDED_PROTOCOL,
+        )
+        .unwrap();
+    let fee = proposal.steps().head.balance().fee_required();
+    let txid = st
+        .create_proposed_transactions::<Infallible, _>(account.usk(), OvkPolicy::Sender, &proposal)
+        .unwrap()[0];
+
+    let get_fee = |st: &TestState<BlockCache>| -> Option<u64> {
+        st.wallet()
+            .conn
+            .query_row(
+                "SELECT fee FROM transactions WHERE txid = :txid",
+                named_params![":txid": txid.as_ref()],
+                |row| row.get(0),
+            )
+            .unwrap()
+    };
+    assert_eq!(get_fee(&st), Some(u64::from(fee)));
+
+    // Forget the fee, as though the transaction had not been created by this wallet.
+    st.wallet()
+        .conn
+        .execute(
+            "UPDATE transactions SET fee = NULL WHERE txid = :txid",
+            named_params![":txid": txid.as_ref()],
+        )
+        .unwrap();
+    assert_eq!(get_fee(&st), None);
+
+    // The fee of a fully-shielded transaction is computed from its value balances when the
+    // transaction is decrypted and stored.
+    let tx = st.wallet().get_transaction(txid).unwrap().unwrap();
+    decrypt_and_store_transaction(&st.network(), st.wallet_mut(), &tx, None).unwrap();
+    assert_eq!(get_fee(&st), Some(u64::from(fee)));
+}
+
+pub(crate) fn transaction_status_requests<T: ShieldedPoolTester>() {
+    let mut st = TestBuilder::new()
+        .with_block_cache()
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+    let dfvk = T::test_account_fvk(&st);
+
+    let value = NonNegativeAmount::const_from_u64(60000);
+    let (h, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+    st.scan_cached_blocks(h, 1);
+
+    let to = T::sk_default_address(&T::sk(&[0xf5; 32]));
+    let proposal = st
+        .propose_standard_transfer::<Infallible>(
+            account.account_id(),
+            StandardFeeRule::Zip317,
+            NonZeroU32::new(1).unwrap(),
+            &to,
+            NonNegativeAmount::const_from_u64(20000),
+            None,
+            None,
+            T::SHIELDED_PROTOCOL,
+        )
+        .unwrap();
+    let txid = st
+        .create_proposed_transactions::<Infallible, _>(account.usk(), OvkPolicy::Sender, &proposal)
+        .unwrap()[0];
+
+    // The status of a transaction created by the wallet is requested until it is mined.
+    let status_requested = |st: &TestState<BlockCache>| {
+        st.wallet()
+            .transaction_data_requests()
+            .unwrap()
+            .contains(&TransactionDataRequest::GetStatus(txid))
+    };
+    assert!(status_requested(&st));
+    st.wallet_mut()
+        .set_transaction_status(txid, TransactionStatus::NotInMainChain)
+        .unwrap();
+    assert!(status_requested(&st));
+    assert_eq!(st.wallet().get_tx_height(txid).unwrap(), None);
+
+    let (h, _) = st.generate_next_block_including(txid);
+    st.scan_cached_blocks(h, 1);
+    st.wallet_mut()
+        .set_transaction_status(txid, TransactionStatus::Mined(h))
+        .unwrap();
+    assert!(!status_requested(&st));
+    assert_eq!(st.wallet().get_tx_height(txid).unwrap(), Some(h));
+
+    // A transaction that is reported as not having been mined in the main chain is treated as
+    // unmined, until its mined height is reported again.
+    st.wallet_mut()
+        .set_transaction_status(txid, TransactionStatus::NotInMainChain)
+        .unwrap();
+    assert_eq!(st.wallet().get_tx_height(txid).unwrap(), None);
+    st.wallet_mut()
+        .set_transaction_status(txid, TransactionStatus::Mined(h))
+        .unwrap();
+    assert_eq!(st.wallet().get_tx_height(txid).unwrap(), Some(h));
+
+    // The mined height of a transaction is not recorded if the block at that height has not
+    // been scanned.
+    st.wallet_mut()
+        .set_transaction_status(txid, TransactionStatus::Mined(h + 1))
+        .unwrap();
+    assert_eq!(st.wallet().get_tx_height(txid).unwrap(), Some(h));
+}
+
+pub(crate) fn lookahead_spends_removed_by_truncation<T: ShieldedPoolTester>() {
+    let mut st = TestBuilder::new()
+        .with_block_cache()
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+    let account_id = account.account_id();
+    let dfvk = T::test_account_fvk(&st);
+
+    // Add funds to the wallet
+    let value = NonNegativeAmount::const_from_u64(50000);
+    let (h, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+    st.scan_cached_blocks(h, 1);
+    assert_eq!(st.get_spendable_balance(account_id, 1), value);
+
+    // Provide the wallet with the nullifiers of an unscanned block in which its note is spent.
+    let spend_height = h + 100;
+    let spend_txid = TxId::from_bytes([7; 32]);
+    let block = T::spend_all_nullifiers(&st, spend_height, spend_txid);
+    st.wallet_mut().put_block_nullifiers(vec![block]).unwrap();
+    assert_eq!(st.get_total_balance(account_id), NonNegativeAmount::ZERO);
+
+    // Roll back the chain to below the block in which the spend was observed. The spend was
+    // never confirmed by scanning, so the note becomes spendable again.
+    st.wallet_mut()
+        .truncate_to_height(spend_height - 1)
+        .unwrap();
+    assert_eq!(st.get_total_balance(account_id), value);
+    assert_eq!(st.get_spendable_balance(account_id, 1), value);
+    assert!(st.wallet().get_transaction(spend_txid).unwrap().is_none());
+
+    // The note is selected for spending again.
+    let notes = T::select_spendable_notes(&st, account_id, value, h, &[]).unwrap();
+    assert_eq!(notes.len(), 1);
+}
+
+#[cfg(feature = "transparent-inputs")]
+pub(crate) fn shield_transparent<T: ShieldedPoolTester>() {
+    let mut st = TestBuilder::new()
+        .with_block_cache()
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+    let dfvk = T::test_account_fvk(&st);
+
+    let uaddr = st
+        .wallet()
+        .get_current_address(account.account_id())
+        .unwrap()
+        .unwrap();
+    let taddr = uaddr.transparent().unwrap();
+
+    // Ensure that the wallet has at least one block
+    let (h, _, _) = st.generate_next_block(
+        &dfvk,
+        AddressType::Internal,
+        NonNegativeAmount::const_from_u64(50000),
+    );
+    st.scan_cached_blocks(h, 1);
+
+    let utxo = WalletTransparentOutput::from_parts(
+        OutPoint::new([1u8; 32], 1),
+        TxOut {
+            value: NonNegativeAmount::const_from_u64(10000),
+            script_pubkey: taddr.script(),
         },
         h,
     )
@@ -1234,6 +3468,151 @@ pub(crate) fn shield_transparent<T: ShieldedPoolTester>() {
     );
 }
 
+pub(crate) fn sweep_sapling_external_key() {
+    let mut st = TestBuilder::new()
+        .with_block_cache()
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+    let from_state = ChainState::empty(st.sapling_activation_height() - 1, BlockHash([0; 32]));
+
+    let extsk = sapling::zip32::ExtendedSpendingKey::master(&[0xf6; 32]);
+    let dfvk = extsk.to_diversifiable_full_viewing_key();
+    let elsewhere = sapling::zip32::ExtendedSpendingKey::master(&[0xf7; 32])
+        .default_address()
+        .1;
+
+    // The key receives a note at its external address, and a second note that it then spends,
+    // receiving the change from that spend at its internal address.
+    st.generate_next_block(
+        &dfvk,
+        AddressType::DefaultExternal,
+        NonNegativeAmount::const_from_u64(50000),
+    );
+    let (_, _, nf) = st.generate_next_block(
+        &dfvk,
+        AddressType::DefaultExternal,
+        NonNegativeAmount::const_from_u64(20000),
+    );
+    st.generate_next_block_spending(
+        &dfvk,
+        (nf, NonNegativeAmount::const_from_u64(20000)),
+        Address::Sapling(elsewhere),
+        NonNegativeAmount::const_from_u64(15000),
+    );
+    st.scan_cached_blocks(account.birthday().height(), 3);
+
+    // The spent note is not swept; the two unspent notes are swept with a ZIP 317 fee of
+    // 10000 zatoshis for their two logical actions.
+    let txid = st
+        .sweep_external_key(
+            &from_state,
+            3,
+            #[cfg(feature = "transparent-inputs")]
+            &[],
+            ExternalKey::Sapling(extsk),
+            account.account_id(),
+        )
+        .unwrap();
+
+    let (h, _) = st.generate_next_block_including(txid);
+    st.scan_cached_blocks(h, 1);
+    assert_eq!(
+        st.get_total_balance(account.account_id()),
+        NonNegativeAmount::const_from_u64(45000)
+    );
+}
+
+pub(crate) fn sweep_requires_funds_exceeding_fee() {
+    let mut st = TestBuilder::new()
+        .with_block_cache()
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+    let from_state = ChainState::empty(st.sapling_activation_height() - 1, BlockHash([0; 32]));
+
+    let extsk = sapling::zip32::ExtendedSpendingKey::master(&[0xf6; 32]);
+    let dfvk = extsk.to_diversifiable_full_viewing_key();
+
+    st.generate_next_block(
+        &dfvk,
+        AddressType::DefaultExternal,
+        NonNegativeAmount::const_from_u64(5000),
+    );
+    st.scan_cached_blocks(account.birthday().height(), 1);
+
+    assert_matches!(
+        st.sweep_external_key(
+            &from_state,
+            1,
+            #[cfg(feature = "transparent-inputs")]
+            &[],
+            ExternalKey::Sapling(extsk),
+            account.account_id(),
+        ),
+        Err(SweepError::InsufficientFunds { available, required })
+            if available == NonNegativeAmount::const_from_u64(5000)
+                && required == NonNegativeAmount::const_from_u64(10000)
+    );
+}
+
+#[cfg(feature = "transparent-inputs")]
+pub(crate) fn sweep_transparent_external_key() {
+    let mut st = TestBuilder::new()
+        .with_block_cache()
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+
+    // The wallet must have scanned a block for the sweep transaction's target height to be
+    // known.
+    let (h, _) = st.generate_empty_block();
+    st.scan_cached_blocks(h, 1);
+
+    let key = ExternalKey::Transparent(secp256k1::SecretKey::from_slice(&[0xf6; 32]).unwrap());
+    let taddr = key.transparent_address().unwrap();
+    let utxos = [
+        NonNegativeAmount::const_from_u64(60000),
+        NonNegativeAmount::const_from_u64(40000),
+    ]
+    .into_iter()
+    .enumerate()
+    .map(|(i, value)| {
+        WalletTransparentOutput::from_parts(
+            OutPoint::new([1u8; 32], i as u32),
+            TxOut {
+                value,
+                script_pubkey: taddr.script(),
+            },
+            h,
+        )
+        .unwrap()
+    })
+    .collect::<Vec<_>>();
+
+    // Two transparent inputs and two padded Sapling outputs are four logical actions, for a
+    // ZIP 317 fee of 20000 zatoshis.
+    let txid = st
+        .sweep_external_key(
+            &ChainState::empty(h, BlockHash([0; 32])),
+            0,
+            &utxos,
+            key,
+            account.account_id(),
+        )
+        .unwrap();
+
+    let (h, _) = st.generate_next_block_including(txid);
+    st.scan_cached_blocks(h, 1);
+    assert_eq!(
+        st.get_total_balance(account.account_id()),
+        NonNegativeAmount::const_from_u64(80000)
+    );
+}
+
 // FIXME: This requires fixes to the test framework.
 #[allow(dead_code)]
 pub(crate) fn birthday_in_anchor_shard<T: ShieldedPoolTester>() {
@@ -1418,6 +3797,201 @@ pub(crate) fn checkpoint_gaps<T: ShieldedPoolTester>() {
     );
 }
 
+pub(crate) fn unusable_recipients_rejected<T: ShieldedPoolTester>() {
+    let mut st = TestBuilder::new()
+        .with_block_cache()
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+    let dfvk = T::test_account_fvk(&st);
+    let value = NonNegativeAmount::const_from_u64(10000);
+
+    // A transparent address whose hash is all zeroes is a burn address.
+    let burn_to = Address::Transparent(TransparentAddress::PublicKeyHash([0; 20]));
+    assert_matches!(
+        st.propose_standard_transfer::<Infallible>(
+            account.account_id(),
+            StandardFeeRule::Zip317,
+            NonZeroU32::new(1).unwrap(),
+            &burn_to,
+            value,
+            None,
+            None,
+            T::SHIELDED_PROTOCOL,
+        ),
+        Err(Error::InvalidRecipient {
+            payment_index: 0,
+            reason: RecipientRejection::BurnAddress(_),
+        })
+    );
+
+    // The wallet's own address, encoded for mainnet, is rejected by the regtest wallet.
+    let mainnet_to = T::fvk_default_address(&dfvk).to_zcash_address(&MainNetwork);
+    let request =
+        zip321::TransactionRequest::new(vec![Payment::without_memo(mainnet_to, value)]).unwrap();
+    assert_matches!(
+        st.propose_transfer(
+            account.account_id(),
+            &input_selector(StandardFeeRule::Zip317, None, T::SHIELDED_PROTOCOL),
+            request,
+            NonZeroU32::new(1).unwrap(),
+        ),
+        Err(Error::InvalidRecipient {
+            payment_index: 0,
+            reason: RecipientRejection::NetworkMismatch {
+                expected: NetworkType::Regtest,
+                actual: NetworkType::Main,
+            },
+        })
+    );
+}
+
+pub(crate) fn privacy_policy_single_pool<T: ShieldedPoolTester>() {
+    let mut st = TestBuilder::new()
+        .with_block_cache()
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+    let dfvk = T::test_account_fvk(&st);
+
+    let (h, _, _) = st.generate_next_block(
+        &dfvk,
+        AddressType::DefaultExternal,
+        NonNegativeAmount::const_from_u64(60000),
+    );
+    st.scan_cached_blocks(h, 1);
+
+    let input_selector = input_selector(StandardFeeRule::Zip317, None, T::SHIELDED_PROTOCOL);
+    let value = NonNegativeAmount::const_from_u64(10000);
+
+    // A transfer within the pool from which it is funded is fully private.
+    let to = T::sk_default_address(&T::sk(&[0xf5; 32]));
+    let request = TransactionRequest::new(vec![Payment::without_memo(
+        to.to_zcash_address(&st.network()),
+        value,
+    )])
+    .unwrap();
+    let proposal = st
+        .propose_transfer_with_privacy_policy(
+            account.account_id(),
+            &input_selector,
+            request,
+            NonZeroU32::new(1).unwrap(),
+            PrivacyPolicy::FullPrivacy,
+        )
+        .unwrap();
+    assert_eq!(PrivacyPolicy::FullPrivacy.check(&proposal), Ok(()));
+
+    // Paying a transparent recipient requires a policy that permits revealing recipients.
+    let t_to = Address::Transparent(TransparentAddress::PublicKeyHash([7; 20]));
+    let request = TransactionRequest::new(vec![Payment::without_memo(
+        t_to.to_zcash_address(&st.network()),
+        value,
+    )])
+    .unwrap();
+    for policy in [
+        PrivacyPolicy::FullPrivacy,
+        PrivacyPolicy::AllowRevealedAmounts,
+        PrivacyPolicy::AllowRevealedSenders,
+    ] {
+        assert_matches!(
+            st.propose_transfer_with_privacy_policy(
+                account.account_id(),
+                &input_selector,
+                request.clone(),
+                NonZeroU32::new(1).unwrap(),
+                policy,
+            ),
+            Err(Error::PrivacyPolicy {
+                violation: PrivacyViolation::RevealedRecipients { step_index: 0, payment_indices },
+                ..
+            }) if payment_indices == [0]
+        );
+    }
+    assert_matches!(
+        st.propose_transfer_with_privacy_policy(
+            account.account_id(),
+            &input_selector,
+            request,
+            NonZeroU32::new(1).unwrap(),
+            PrivacyPolicy::AllowRevealedRecipients,
+        ),
+        Ok(_)
+    );
+}
+
+pub(crate) fn tx_history_privacy_level<T: ShieldedPoolTester>() {
+    let mut st = TestBuilder::new()
+        .with_block_cache()
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+    let dfvk = T::test_account_fvk(&st);
+
+    let (h, _, _) = st.generate_next_block(
+        &dfvk,
+        AddressType::DefaultExternal,
+        NonNegativeAmount::const_from_u64(60000),
+    );
+    st.scan_cached_blocks(h, 1);
+
+    let send_to = |st: &mut TestState<BlockCache>, to: &Address| {
+        let proposal = st
+            .propose_standard_transfer::<Infallible>(
+                account.account_id(),
+                StandardFeeRule::Zip317,
+                NonZeroU32::new(1).unwrap(),
+                to,
+                NonNegativeAmount::const_from_u64(10000),
+                None,
+                None,
+                T::SHIELDED_PROTOCOL,
+            )
+            .unwrap();
+        let txid = st
+            .create_proposed_transactions::<Infallible, _>(
+                account.usk(),
+                OvkPolicy::Sender,
+                &proposal,
+            )
+            .unwrap()[0];
+        let (h, _) = st.generate_next_block_including(txid);
+        st.scan_cached_blocks(h, 1);
+        txid
+    };
+
+    // A payment to a shielded recipient in the same pool is fully shielded, while a payment
+    // to a transparent recipient moves funds out of the shielded pools.
+    let shielded_txid = send_to(&mut st, &T::sk_default_address(&T::sk(&[0xf5; 32])));
+    let deshielding_txid = send_to(
+        &mut st,
+        &Address::Transparent(TransparentAddress::PublicKeyHash([7; 20])),
+    );
+
+    let tx_history = st.get_tx_history().unwrap();
+    assert_eq!(tx_history.len(), 3);
+    let privacy_level = |txid| {
+        tx_history
+            .iter()
+            .find(|tx| tx.txid() == txid)
+            .map(|tx| tx.privacy_level())
+    };
+    assert_eq!(
+        privacy_level(shielded_txid),
+        Some(PrivacyLevel::FullyShielded)
+    );
+    assert_eq!(
+        privacy_level(deshielding_txid),
+        Some(PrivacyLevel::Deshielding)
+    );
+    assert!(tx_history.iter().all(
+        |tx| tx.txid() == deshielding_txid || tx.privacy_level() == PrivacyLevel::FullyShielded
+    ));
+}
+
 #[cfg(feature = "orchard")]
 pub(crate) fn pool_crossing_required<P0: ShieldedPoolTester, P1: ShieldedPoolTester>() {
     let mut st = TestBuilder::new()
@@ -1504,6 +4078,60 @@ pub(crate) fn pool_crossing_required<P0: ShieldedPoolTester, P1: ShieldedPoolTes
     );
 }
 
+#[cfg(feature = "orchard")]
+pub(crate) fn privacy_policy_pool_crossing<P0: ShieldedPoolTester, P1: ShieldedPoolTester>() {
+    let mut st = TestBuilder::new()
+        .with_block_cache()
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+    let p0_fvk = P0::test_account_fvk(&st);
+    let p1_to = P1::fvk_default_address(&P1::test_account_fvk(&st));
+
+    let (h, _, _) = st.generate_next_block(
+        &p0_fvk,
+        AddressType::DefaultExternal,
+        NonNegativeAmount::const_from_u64(350000),
+    );
+    st.scan_cached_blocks(h, 1);
+
+    let p0_to_p1 = TransactionRequest::new(vec![Payment::without_memo(
+        p1_to.to_zcash_address(&st.network()),
+        NonNegativeAmount::const_from_u64(200000),
+    )])
+    .unwrap();
+    let input_selector = input_selector(StandardFeeRule::Zip317, None, P1::SHIELDED_PROTOCOL);
+
+    // Crossing pools reveals the amount transferred, which full privacy does not permit.
+    assert_matches!(
+        st.propose_transfer_with_privacy_policy(
+            account.account_id(),
+            &input_selector,
+            p0_to_p1.clone(),
+            NonZeroU32::new(1).unwrap(),
+            PrivacyPolicy::FullPrivacy,
+        ),
+        Err(Error::PrivacyPolicy {
+            policy: PrivacyPolicy::FullPrivacy,
+            violation: PrivacyViolation::RevealedAmounts { step_index: 0, .. },
+        })
+    );
+    let proposal = st
+        .propose_transfer_with_privacy_policy(
+            account.account_id(),
+            &input_selector,
+            p0_to_p1,
+            NonZeroU32::new(1).unwrap(),
+            PrivacyPolicy::AllowRevealedAmounts,
+        )
+        .unwrap();
+    assert_matches!(
+        PrivacyPolicy::FullPrivacy.check(&proposal),
+        Err(PrivacyViolation::RevealedAmounts { step_index: 0, .. })
+    );
+}
+
 #[cfg(feature = "orchard")]
 pub(crate) fn fully_funded_fully_private<P0: ShieldedPoolTester, P1: ShieldedPoolTester>() {
     let mut st = TestBuilder::new()
@@ -1974,8 +4602,8 @@ pub(crate) fn invalid_chain_cache_disconnected<T: ShieldedPoolTester>() {
             disconnect_height,
             2
         ),
-        Err(chain::error::Error::Scan(ScanError::PrevHashMismatch { at_height }))
-            if at_height == disconnect_height
+        Err(chain::error::Error::Scan(ScanError::PrevHashMismatch { at_height, found, .. }))
+            if at_height == disconnect_height && found == BlockHash([1; 32])
     );
 }
 
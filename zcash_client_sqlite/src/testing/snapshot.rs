@@ -0,0 +1,143 @@
+//! Golden-file snapshots of the contents of the wallet database's views.
+//!
+//! The views are the interface through which applications read the wallet database, so a
+//! migration that changes their semantics affects every downstream user. [`assert_views_snapshot`]
+//! renders the rows of every view in a wallet built by a test, and compares the result with a
+//! snapshot file checked in under `src/testing/snapshots`, so that any such change shows up in
+//! review as a change to the snapshot.
+//!
+//! Values that are not deterministic across test runs are normalized: BLOBs (which include
+//! txids, nullifiers and commitments derived from the randomness used to build transactions)
+//! are rendered by their length only, and the columns passed as `masked_columns` are rendered
+//! only as present or absent.
+//!
+//! Set the `UPDATE_VIEW_SNAPSHOTS` environment variable when running the tests to write the
+//! snapshot files for new tests, or to rewrite them after an intentional change. Otherwise, a
+//! missing snapshot file causes the test that expects it to fail; snapshot files are never
+//! written into the source tree by an ordinary test run.
+
+use std::fmt::Write;
+use std::fs;
+use std::path::PathBuf;
+
+use rusqlite::{types::ValueRef, Connection};
+
+/// Columns whose values depend upon the time at which a test is run.
+pub(crate) const TIME_DEPENDENT_COLUMNS: &[&str] = &["created"];
+
+fn render_value(value: ValueRef<'_>, masked: bool) -> String {
+    match value {
+        ValueRef::Null => "NULL".to_string(),
+        _ if masked => "<masked>".to_string(),
+        ValueRef::Integer(i) => i.to_string(),
+        ValueRef::Real(f) => f.to_string(),
+        ValueRef::Text(t) => format!("{:?}", String::from_utf8_lossy(t)),
+        ValueRef::Blob(b) => format!("<{} bytes>", b.len()),
+    }
+}
+
+/// Renders the rows of every view in the database.
+///
+/// Views are rendered in order of name, and the rows of each view are sorted by their rendered
+/// form, so that the result does not depend upon the order in which SQLite returns them.
+pub(crate) fn render_views(conn: &Connection, masked_columns: &[&str]) -> String {
+    let view_names = conn
+        .prepare("SELECT name FROM sqlite_master WHERE type = 'view' ORDER BY name")
+        .unwrap()
+        .query_map([], |row| row.get::<_, String>(0))
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    let mut rendered = String::new();
+    for view_name in view_names {
+        let mut stmt = conn
+            .prepare(&format!("SELECT * FROM \"{}\"", view_name))
+            .unwrap();
+        let columns = stmt
+            .column_names()
+            .into_iter()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        let mut rows = stmt
+            .query_map([], |row| {
+                Ok(columns
+                    .iter()
+                    .enumerate()
+                    .map(|(i, name)| {
+                        Ok(render_value(
+                            row.get_ref(i)?,
+                            masked_columns.contains(&name.as_str()),
+                        ))
+                    })
+                    .collect::<Result<Vec<_>, rusqlite::Error>>()?
+                    .join(" | "))
+            })
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        rows.sort();
+
+        writeln!(rendered, "== {} ({} rows)", view_name, rows.len()).unwrap();
+        writeln!(rendered, "{}", columns.join(" | ")).unwrap();
+        for row in rows {
+            writeln!(rendered, "{}", row).unwrap();
+        }
+        writeln!(rendered).unwrap();
+    }
+
+    rendered
+}
+
+/// Checks that the rendered views of the database match the snapshot with the given name.
+pub(crate) fn assert_views_snapshot(conn: &Connection, name: &str, masked_columns: &[&str]) {
+    let actual = render_views(conn, masked_columns);
+    let path: PathBuf = [
+        env!("CARGO_MANIFEST_DIR"),
+        "src",
+        "testing",
+        "snapshots",
+        &format!("{}.snap", name),
+    ]
+    .iter()
+    .collect();
+
+    if std::env::var_os("UPDATE_VIEW_SNAPSHOTS").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, &actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path).unwrap_or_else(|_| {
+        panic!(
+            "The view snapshot {} is missing. Run the test with UPDATE_VIEW_SNAPSHOTS=1 to \
+             create it, and commit the result.",
+            path.display()
+        )
+    });
+
+    if let Some((line, (e, a))) = expected
+        .lines()
+        .zip(actual.lines())
+        .enumerate()
+        .find(|(_, (e, a))| e != a)
+    {
+        panic!(
+            "The contents of the views differ from the snapshot {} at line {}:\n\
+             expected: {}\n  actual: {}\n\
+             If the change is intended, rerun the test with UPDATE_VIEW_SNAPSHOTS=1 and \
+             review the changes to the snapshot.",
+            path.display(),
+            line + 1,
+            e,
+            a
+        );
+    }
+    assert_eq!(
+        expected.lines().count(),
+        actual.lines().count(),
+        "The number of lines in the rendered views differs from the snapshot {}. If the \
+         change is intended, rerun the test with UPDATE_VIEW_SNAPSHOTS=1 and review the \
+         changes to the snapshot.",
+        path.display()
+    );
+}
@@ -9,7 +9,10 @@
 //!
 //! # Views
 //!
-//! The wallet database exposes the following views as part of its public API:
+//! The wallet database exposes the following views as part of its public API. Transactions are
+//! identified in these views by their `txid`; the integer row identifiers used internally by the
+//! wallet database (such as `id_tx`) are not stable across wallet databases and are not part of
+//! the public API.
 //!
 //! ## `v_transactions`
 //!
@@ -26,6 +29,13 @@
 //!   transaction, this fee amount will be repeated for each such row. Therefore, if more than one
 //!   of the wallet's accounts is involved with the transaction, this fee should be considered only
 //!   once in determining the total value sent from the wallet as a whole.
+//! - `privacy_level`: a classification of the transaction according to the value pools involved in
+//!   the associated account's inputs and outputs. This is one of `fully_shielded` (no transparent
+//!   inputs or outputs), `transparent` (no shielded inputs or outputs), `deshielding` (shielded
+//!   inputs and transparent outputs), or `shielding` (any other combination that moves value
+//!   between the transparent and shielded pools). For transactions received from outside the
+//!   wallet, only the outputs received by the account are known, so the classification reflects
+//!   those outputs alone.
 //!
 //! ### Seed Phrase with Single Account
 //!
@@ -39,7 +49,7 @@
 //! In the case that the seed phrase for in this wallet has been used to create multiple accounts,
 //! this view may contain multiple rows per transaction, one for each account involved. In this
 //! case, the total amount sent to addresses outside the wallet can usually be calculated by
-//! grouping rows by `id_tx` and then using `SUM(account_balance_delta) + MAX(fee_paid)`.
+//! grouping rows by `txid` and then using `SUM(account_balance_delta) + MAX(fee_paid)`.
 //!
 //! ### Imported Seed Phrases
 //!
@@ -53,6 +63,7 @@
 //! This view exposes the history of transaction outputs received by and sent from the wallet,
 //! keyed by transaction ID, pool type, and output index. The contents of this view are useful for
 //! producing a detailed report of the effects of a transaction. Each row of this view contains:
+//! - `output_pool` the [code](#pool-codes) of the value pool containing the output.
 //! - `from_account_id` for sent outputs, the account from which the value was sent.
 //! - `to_account_id` in the case that the output was received by an account in the wallet, the
 //!   identifier for the account receiving the funds.
@@ -63,8 +74,14 @@
 //! - `is_change` a boolean flag indicating whether this is a change output belonging to the
 //!   wallet.
 //! - `memo` the shielded memo associated with the output, if any.
+//!
+//! # Pool codes
+//!
+//! Columns that identify a value pool, such as the `output_pool` column of `v_tx_outputs` and
+//! the `pool` column of `v_received_notes`, contain the stable numeric codes returned by
+//! [`PoolType::code`]: `0` for the transparent pool, `2` for Sapling, and `3` for Orchard.
 
-use incrementalmerkletree::Retention;
+use incrementalmerkletree::{Level, Position, Retention};
 use rusqlite::{self, named_params, OptionalExtension};
 use secrecy::{ExposeSecret, SecretVec};
 use shardtree::{error::ShardTreeError, store::ShardStore, ShardTree};
@@ -74,16 +91,20 @@ use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
 use std::io::{self, Cursor};
 use std::num::NonZeroU32;
-use std::ops::RangeInclusive;
-use tracing::debug;
+use std::ops::{Range, RangeInclusive};
+use tracing::{debug, warn};
 
 use zcash_address::ZcashAddress;
 use zcash_client_backend::{
     data_api::{
+        audit::AuditBundle,
         scanning::{ScanPriority, ScanRange},
-        AccountBalance, AccountBirthday, AccountSource, BlockMetadata, Ratio,
-        SentTransactionOutput, WalletSummary, SAPLING_SHARD_HEIGHT,
+        AccountBalance, AccountBirthday, AccountSource, AddressUsage, BlockMetadata,
+        BlockNullifiers, DustFilter, HistoricalBalance, NullifierQuery, PeriodStatistics, Ratio,
+        StatisticsGranularity, TransactionDataRequest, TransactionStatus, WalletSummary,
+        SAPLING_SHARD_HEIGHT,
     },
+    decrypt_transaction,
     encoding::AddressCodec,
     keys::UnifiedFullViewingKey,
     wallet::{Note, NoteId, Recipient, WalletTx},
@@ -102,7 +123,7 @@ use zcash_primitives::{
     memo::{Memo, MemoBytes},
     merkle_tree::read_commitment_tree,
     transaction::{
-        components::{amount::NonNegativeAmount, Amount},
+        components::{amount::NonNegativeAmount, Amount, OutPoint},
         Transaction, TransactionData, TxId,
     },
 };
@@ -115,7 +136,13 @@ use crate::{
     SAPLING_TABLES_PREFIX,
 };
 
-use self::scanning::{parse_priority_code, priority_code, replace_queue_entries};
+use self::{
+    journal::WalletEventKind,
+    scanning::{
+        backfill_accounts, clear_account_backfill, parse_priority_code, priority_code,
+        queue_account_backfill, replace_queue_entries,
+    },
+};
 
 #[cfg(feature = "orchard")]
 use {crate::ORCHARD_TABLES_PREFIX, zcash_client_backend::data_api::ORCHARD_SHARD_HEIGHT};
@@ -126,23 +153,33 @@ use {
     rusqlite::Row,
     std::collections::BTreeSet,
     zcash_address::unified::{Encoding, Ivk, Uivk},
-    zcash_client_backend::wallet::{TransparentAddressMetadata, WalletTransparentOutput},
+    zcash_client_backend::wallet::{
+        TransparentAddressMetadata, WalletTransparentOutput, COINBASE_MATURITY,
+    },
     zcash_primitives::{
         legacy::{
             keys::{IncomingViewingKey, NonHardenedChildIndex},
             Script, TransparentAddress,
         },
-        transaction::components::{OutPoint, TxOut},
+        transaction::components::TxOut,
     },
 };
 
+pub mod backup;
 pub mod commitment_tree;
 pub(crate) mod common;
+pub mod contacts;
 pub mod init;
+pub mod journal;
+pub mod ledger;
 #[cfg(feature = "orchard")]
 pub(crate) mod orchard;
+pub mod pruning;
+pub(crate) mod repair;
 pub(crate) mod sapling;
 pub(crate) mod scanning;
+pub(crate) mod scheduled;
+pub mod settings;
 #[cfg(feature = "transparent-inputs")]
 pub(crate) mod transparent;
 
@@ -298,14 +335,11 @@ pub(crate) fn seed_matches_derived_account<P: consensus::Parameters>(
 }
 
 pub(crate) fn pool_code(pool_type: PoolType) -> i64 {
-    // These constants are *incidentally* shared with the typecodes
-    // for unified addresses, but this is exclusively an internal
-    // implementation detail.
-    match pool_type {
-        PoolType::Transparent => 0i64,
-        PoolType::Shielded(ShieldedProtocol::Sapling) => 2i64,
-        PoolType::Shielded(ShieldedProtocol::Orchard) => 3i64,
-    }
+    i64::from(pool_type.code())
+}
+
+pub(crate) fn parse_pool_code(code: i64) -> Option<PoolType> {
+    u32::try_from(code).ok().and_then(PoolType::from_code)
 }
 
 pub(crate) fn scope_code(scope: Scope) -> i64 {
@@ -418,7 +452,7 @@ pub(crate) fn add_account<P: consensus::Parameters>(
             ":birthday_orchard_tree_size": birthday_orchard_tree_size,
             ":recover_until_height": birthday.recover_until().map(u32::from)
         ],
-        |row| Ok(AccountId(row.get(0)?)),
+        |row| row.get(0),
     )?;
 
     let account = Account {
@@ -504,24 +538,21 @@ pub(crate) fn add_account<P: consensus::Parameters>(
     };
 
     // Rewrite the scan ranges from the birthday height up to the chain tip so that we'll ensure we
-    // re-scan to find any notes that might belong to the newly added account.
+    // re-scan to find any notes that might belong to the newly added account. Blocks that have
+    // already been scanned for the wallet's other accounts are re-scanned for this account only.
     if let Some(t) = scan_queue_extrema(conn)?.map(|range| *range.end()) {
-        let rescan_range = birthday.height()..(t + 1);
-
-        replace_queue_entries::<SqliteClientError>(
-            conn,
-            &rescan_range,
-            Some(ScanRange::from_parts(
-                rescan_range.clone(),
-                ScanPriority::Historic,
-            ))
-            .into_iter(),
-            true, // force rescan
-        )?;
+        queue_account_backfill(conn, account_id, &(birthday.height()..(t + 1)))?;
     }
 
-    // Always derive the default Unified Address for the account.
-    let (address, d_idx) = account.default_address(DEFAULT_UA_REQUEST)?;
+    // Always derive the default Unified Address for the account. Imported keys may lack
+    // some of the components used by default, so only request receivers the key can derive.
+    let ua_request = account
+        .viewing_key
+        .uivk()
+        .to_address_request()
+        .and_then(|request| request.intersect(&DEFAULT_UA_REQUEST))
+        .ok_or(AddressGenerationError::ShieldedReceiverRequired)?;
+    let (address, d_idx) = account.default_address(ua_request)?;
     insert_address(conn, params, account_id, d_idx, &address)?;
 
     Ok(account_id)
@@ -539,7 +570,7 @@ pub(crate) fn get_current_address<P: consensus::Parameters>(
             FROM addresses WHERE account_id = :account_id
             ORDER BY diversifier_index_be DESC
             LIMIT 1",
-            named_params![":account_id": account_id.0],
+            named_params![":account_id": account_id],
             |row| Ok((row.get(0)?, row.get(1)?)),
         )
         .optional()?;
@@ -595,7 +626,7 @@ pub(crate) fn insert_address<P: consensus::Parameters>(
     let mut di_be = *diversifier_index.as_bytes();
     di_be.reverse();
     stmt.execute(named_params![
-        ":account": account.0,
+        ":account": account,
         ":diversifier_index_be": &di_be[..],
         ":address": &address.encode(params),
         ":cached_transparent_receiver_address": &address.transparent().map(|r| r.encode(params)),
@@ -604,6 +635,232 @@ pub(crate) fn insert_address<P: consensus::Parameters>(
     Ok(())
 }
 
+/// Returns usage information for each of the addresses generated for the given account, in
+/// order of increasing diversifier index.
+pub(crate) fn address_usage<P: consensus::Parameters>(
+    conn: &rusqlite::Connection,
+    params: &P,
+    account_id: AccountId,
+) -> Result<Vec<AddressUsage>, SqliteClientError> {
+    let mut stmt = conn.prepare(
+        "SELECT address, diversifier_index_be, first_used_height, last_used_height
+        FROM addresses WHERE account_id = :account_id
+        ORDER BY diversifier_index_be ASC",
+    )?;
+
+    let mut rows = stmt.query(named_params![":account_id": account_id])?;
+    let mut result = vec![];
+    while let Some(row) = rows.next()? {
+        let addr_str: String = row.get(0)?;
+        let di_vec: Vec<u8> = row.get(1)?;
+        let mut di_be: [u8; 11] = di_vec.try_into().map_err(|_| {
+            SqliteClientError::CorruptedData("Diversifier index is not an 11-byte value".to_owned())
+        })?;
+        di_be.reverse();
+
+        let ua = match Address::decode(params, &addr_str) {
+            Some(Address::Unified(ua)) => ua,
+            Some(_) => {
+                return Err(SqliteClientError::CorruptedData(format!(
+                    "Addresses table contains {} which is not a unified address",
+                    addr_str,
+                )))
+            }
+            None => {
+                return Err(SqliteClientError::CorruptedData(
+                    "Not a valid Zcash recipient address".to_owned(),
+                ))
+            }
+        };
+
+        result.push(AddressUsage::from_parts(
+            ua,
+            DiversifierIndex::from(di_be),
+            row.get::<_, Option<u32>>(2)?.map(BlockHeight::from),
+            row.get::<_, Option<u32>>(3)?.map(BlockHeight::from),
+        ));
+    }
+
+    Ok(result)
+}
+
+/// Records that the wallet address at which an output detected by scanning was received was
+/// paid by a transaction mined at the given height.
+///
+/// The account and key scope from which the receiver was derived are resolved with
+/// [`find_account_for_receiver`], so that the use is attributed to the account that owns the
+/// address even if the output was detected with the viewing key of `scanning_account`. Uses of
+/// internal (change) addresses are not recorded.
+pub(crate) fn mark_scanned_receiver_used<P: consensus::Parameters>(
+    conn: &rusqlite::Connection,
+    params: &P,
+    scanning_account: AccountId,
+    receiver: &Receiver,
+    height: BlockHeight,
+) -> Result<(), SqliteClientError> {
+    if let Some((account_id, scope)) = find_account_for_receiver(conn, params, receiver)? {
+        if account_id != scanning_account {
+            warn!(
+                "An output detected by account {:?} was received at an address of account {:?}",
+                scanning_account, account_id
+            );
+        }
+        if scope == Scope::External {
+            mark_address_used(conn, params, account_id, receiver, height)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Records that the wallet address corresponding to the given receiver was paid by a transaction
+/// mined at the given height.
+///
+/// This has no effect if the receiver does not belong to an address in the `addresses` table,
+/// such as when it was derived using the account's internal key scope.
+pub(crate) fn mark_address_used<P: consensus::Parameters>(
+    conn: &rusqlite::Connection,
+    params: &P,
+    account_id: AccountId,
+    receiver: &Receiver,
+    height: BlockHeight,
+) -> Result<(), SqliteClientError> {
+    if let Some(address) = select_receiving_address(params, conn, account_id, receiver)? {
+        record_address_use(conn, "address", &address.encode(), height)?;
+    }
+
+    Ok(())
+}
+
+/// Widens the range of heights at which the address whose `address_column` has the value
+/// `address` has been used so that it includes the given height.
+fn record_address_use(
+    conn: &rusqlite::Connection,
+    address_column: &'static str,
+    address: &str,
+    height: BlockHeight,
+) -> Result<(), SqliteClientError> {
+    let mut stmt = conn.prepare_cached(&format!(
+        "UPDATE addresses
+        SET first_used_height = MIN(IFNULL(first_used_height, :height), :height),
+            last_used_height = MAX(IFNULL(last_used_height, :height), :height)
+        WHERE {address_column} = :address"
+    ))?;
+    stmt.execute(named_params![
+        ":address": address,
+        ":height": u32::from(height),
+    ])?;
+
+    Ok(())
+}
+
+/// Discards the uses of the wallet's addresses that were recorded above the given height.
+///
+/// The last height at which each affected address was used at or below the truncation height is
+/// recomputed from the outputs that the wallet received at the address.
+fn truncate_address_usage<P: consensus::Parameters>(
+    conn: &rusqlite::Connection,
+    params: &P,
+    block_height: BlockHeight,
+) -> Result<(), SqliteClientError> {
+    conn.execute(
+        "UPDATE addresses
+        SET first_used_height = NULL, last_used_height = NULL
+        WHERE first_used_height > :block_height",
+        named_params![":block_height": u32::from(block_height)],
+    )?;
+
+    let max_height = |sql: &str, account_id: AccountId, receiver: &dyn rusqlite::ToSql| {
+        conn.query_row(
+            sql,
+            named_params![
+                ":account_id": account_id,
+                ":receiver": receiver,
+                ":block_height": u32::from(block_height),
+            ],
+            |row| row.get::<_, Option<u32>>(0),
+        )
+    };
+
+    let mut stmt = conn.prepare(
+        "SELECT account_id, address, first_used_height
+        FROM addresses
+        WHERE last_used_height > :block_height",
+    )?;
+    let mut rows = stmt.query(named_params![":block_height": u32::from(block_height)])?;
+    while let Some(row) = rows.next()? {
+        let account_id: AccountId = row.get(0)?;
+        let addr_str: String = row.get(1)?;
+        let first_used_height: u32 = row.get(2)?;
+        let ua = match Address::decode(params, &addr_str) {
+            Some(Address::Unified(ua)) => ua,
+            _ => {
+                return Err(SqliteClientError::CorruptedData(format!(
+                    "Addresses table contains {} which is not a unified address",
+                    addr_str,
+                )))
+            }
+        };
+
+        let mut last_used_height = first_used_height;
+        if let Some(addr) = ua.sapling() {
+            let height = max_height(
+                "SELECT MAX(t.block)
+                FROM sapling_received_notes n
+                JOIN transactions t ON t.id_tx = n.tx
+                WHERE n.account_id = :account_id
+                AND n.diversifier = :receiver
+                AND NOT n.is_change
+                AND t.block <= :block_height",
+                account_id,
+                &&addr.diversifier().0[..],
+            )?;
+            last_used_height = last_used_height.max(height.unwrap_or(0));
+        }
+        #[cfg(feature = "orchard")]
+        if let Some(addr) = ua.orchard() {
+            let height = max_height(
+                "SELECT MAX(t.block)
+                FROM orchard_received_notes n
+                JOIN transactions t ON t.id_tx = n.tx
+                WHERE n.account_id = :account_id
+                AND n.diversifier = :receiver
+                AND NOT n.is_change
+                AND t.block <= :block_height",
+                account_id,
+                &&addr.diversifier().as_array()[..],
+            )?;
+            last_used_height = last_used_height.max(height.unwrap_or(0));
+        }
+        if let Some(taddr) = ua.transparent() {
+            let height = max_height(
+                "SELECT MAX(height)
+                FROM utxos
+                WHERE received_by_account_id = :account_id
+                AND address = :receiver
+                AND height <= :block_height",
+                account_id,
+                &Address::Transparent(*taddr).encode(params),
+            )?;
+            last_used_height = last_used_height.max(height.unwrap_or(0));
+        }
+
+        conn.execute(
+            "UPDATE addresses
+            SET last_used_height = :last_used_height
+            WHERE account_id = :account_id
+            AND address = :address",
+            named_params![
+                ":last_used_height": last_used_height,
+                ":account_id": account_id,
+                ":address": addr_str,
+            ],
+        )?;
+    }
+
+    Ok(())
+}
+
 #[cfg(feature = "transparent-inputs")]
 pub(crate) fn get_transparent_receivers<P: consensus::Parameters>(
     conn: &rusqlite::Connection,
@@ -616,7 +873,7 @@ pub(crate) fn get_transparent_receivers<P: consensus::Parameters>(
     let mut ua_query = conn.prepare(
         "SELECT address, diversifier_index_be FROM addresses WHERE account_id = :account",
     )?;
-    let mut rows = ua_query.query(named_params![":account": account.0])?;
+    let mut rows = ua_query.query(named_params![":account": account])?;
 
     while let Some(row) = rows.next()? {
         let ua_str: String = row.get(0)?;
@@ -690,7 +947,7 @@ pub(crate) fn get_legacy_transparent_address<P: consensus::Parameters>(
     let uivk_str: Option<String> = conn
         .query_row(
             "SELECT uivk FROM accounts WHERE id = :account",
-            [account_id.0],
+            [account_id],
             |row| row.get(0),
         )
         .optional()?;
@@ -720,21 +977,54 @@ pub(crate) fn get_legacy_transparent_address<P: consensus::Parameters>(
 pub(crate) fn get_unified_full_viewing_keys<P: consensus::Parameters>(
     conn: &rusqlite::Connection,
     params: &P,
+) -> Result<HashMap<AccountId, UnifiedFullViewingKey>, SqliteClientError> {
+    get_ufvks(conn, params, false)
+}
+
+/// Returns the [`UnifiedFullViewingKey`]s of the accounts that are enabled for scanning and
+/// for which the blocks in the given range have not yet been scanned.
+///
+/// If every block in the range has already been scanned for some of the wallet's accounts (for
+/// example, because the range is being scanned for an account that was added with an earlier
+/// birthday), only the keys of the remaining accounts are returned.
+pub(crate) fn get_unified_full_viewing_keys_for_scanning<P: consensus::Parameters>(
+    conn: &rusqlite::Connection,
+    params: &P,
+    range: &Range<BlockHeight>,
+) -> Result<HashMap<AccountId, UnifiedFullViewingKey>, SqliteClientError> {
+    let ufvks = get_ufvks(conn, params, true)?;
+    Ok(match backfill_accounts(conn, range)? {
+        Some(accounts) => ufvks
+            .into_iter()
+            .filter(|(account_id, _)| accounts.contains(account_id))
+            .collect(),
+        None => ufvks,
+    })
+}
+
+fn get_ufvks<P: consensus::Parameters>(
+    conn: &rusqlite::Connection,
+    params: &P,
+    scanning_only: bool,
 ) -> Result<HashMap<AccountId, UnifiedFullViewingKey>, SqliteClientError> {
     // Fetch the UnifiedFullViewingKeys we are tracking
-    let mut stmt_fetch_accounts = conn.prepare("SELECT id, ufvk FROM accounts")?;
-
-    let rows = stmt_fetch_accounts.query_map([], |row| {
-        let acct: u32 = row.get(0)?;
-        let ufvk_str: Option<String> = row.get(1)?;
-        if let Some(ufvk_str) = ufvk_str {
-            let ufvk = UnifiedFullViewingKey::decode(params, &ufvk_str)
-                .map_err(SqliteClientError::CorruptedData);
-            Ok(Some((AccountId(acct), ufvk)))
-        } else {
-            Ok(None)
-        }
-    })?;
+    let mut stmt_fetch_accounts = conn.prepare(
+        "SELECT id, ufvk FROM accounts
+        WHERE NOT :scanning_only OR enabled_for_scanning = 1",
+    )?;
+
+    let rows =
+        stmt_fetch_accounts.query_map(named_params![":scanning_only": scanning_only], |row| {
+            let acct: AccountId = row.get(0)?;
+            let ufvk_str: Option<String> = row.get(1)?;
+            if let Some(ufvk_str) = ufvk_str {
+                let ufvk = UnifiedFullViewingKey::decode(params, &ufvk_str)
+                    .map_err(SqliteClientError::CorruptedData);
+                Ok(Some((acct, ufvk)))
+            } else {
+                Ok(None)
+            }
+        })?;
 
     let mut res: HashMap<AccountId, UnifiedFullViewingKey> = HashMap::new();
     for row in rows {
@@ -807,6 +1097,133 @@ pub(crate) fn get_account_for_ufvk<P: consensus::Parameters>(
     }
 }
 
+/// Returns the account and key scope from which the given address was derived, if the
+/// address belongs to any account in the wallet.
+///
+/// Shielded receivers are recognized for both the external and internal scopes of accounts
+/// having a full viewing key, and for the external scope of accounts having only an incoming
+/// viewing key. Transparent receivers are recognized if they are cached in the `addresses`
+/// table or are the legacy transparent address of an account. For a unified address, the
+/// first of its receivers (in order of preference) that belongs to the wallet determines the
+/// result.
+pub(crate) fn find_account_for_address<P: consensus::Parameters>(
+    conn: &rusqlite::Connection,
+    params: &P,
+    address: &Address,
+) -> Result<Option<(AccountId, Scope)>, SqliteClientError> {
+    match address {
+        Address::Sapling(addr) => {
+            find_account_for_receiver(conn, params, &Receiver::Sapling(*addr))
+        }
+        Address::Transparent(taddr) => {
+            find_account_for_receiver(conn, params, &Receiver::Transparent(*taddr))
+        }
+        Address::Unified(ua) => {
+            #[cfg(feature = "orchard")]
+            if let Some(addr) = ua.orchard() {
+                if let Some(res) =
+                    find_account_for_receiver(conn, params, &Receiver::Orchard(*addr))?
+                {
+                    return Ok(Some(res));
+                }
+            }
+
+            if let Some(addr) = ua.sapling() {
+                if let Some(res) =
+                    find_account_for_receiver(conn, params, &Receiver::Sapling(*addr))?
+                {
+                    return Ok(Some(res));
+                }
+            }
+
+            match ua.transparent() {
+                Some(taddr) => {
+                    find_account_for_receiver(conn, params, &Receiver::Transparent(*taddr))
+                }
+                None => Ok(None),
+            }
+        }
+    }
+}
+
+/// Returns the account and key scope from which the given receiver was derived, if the
+/// receiver belongs to any account in the wallet.
+///
+/// See [`find_account_for_address`] for the receivers that are recognized.
+pub(crate) fn find_account_for_receiver<P: consensus::Parameters>(
+    conn: &rusqlite::Connection,
+    params: &P,
+    receiver: &Receiver,
+) -> Result<Option<(AccountId, Scope)>, SqliteClientError> {
+    match receiver {
+        #[cfg(feature = "orchard")]
+        Receiver::Orchard(addr) => find_account_for_orchard_receiver(conn, params, addr),
+        Receiver::Sapling(addr) => find_account_for_sapling_receiver(conn, params, addr),
+        Receiver::Transparent(_taddr) => {
+            #[cfg(feature = "transparent-inputs")]
+            return Ok(find_account_for_transparent_address(conn, params, _taddr)?
+                .map(|account_id| (account_id, Scope::External)));
+
+            #[cfg(not(feature = "transparent-inputs"))]
+            return Ok(None);
+        }
+    }
+}
+
+fn find_account_for_sapling_receiver<P: consensus::Parameters>(
+    conn: &rusqlite::Connection,
+    params: &P,
+    addr: &::sapling::PaymentAddress,
+) -> Result<Option<(AccountId, Scope)>, SqliteClientError> {
+    for account_id in get_account_ids(conn)? {
+        let scope = match get_account(conn, params, account_id)?.map(|a| a.viewing_key) {
+            Some(ViewingKey::Full(ufvk)) => ufvk
+                .sapling()
+                .and_then(|dfvk| dfvk.decrypt_diversifier(addr))
+                .map(|(_, scope)| scope),
+            Some(ViewingKey::Incoming(uivk)) => uivk
+                .sapling()
+                .as_ref()
+                .and_then(|ivk| ivk.decrypt_diversifier(addr))
+                .map(|_| Scope::External),
+            None => None,
+        };
+
+        if let Some(scope) = scope {
+            return Ok(Some((account_id, scope)));
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(feature = "orchard")]
+fn find_account_for_orchard_receiver<P: consensus::Parameters>(
+    conn: &rusqlite::Connection,
+    params: &P,
+    addr: &::orchard::Address,
+) -> Result<Option<(AccountId, Scope)>, SqliteClientError> {
+    for account_id in get_account_ids(conn)? {
+        let scope = match get_account(conn, params, account_id)?.map(|a| a.viewing_key) {
+            Some(ViewingKey::Full(ufvk)) => {
+                ufvk.orchard().and_then(|fvk| fvk.scope_for_address(addr))
+            }
+            Some(ViewingKey::Incoming(uivk)) => uivk
+                .orchard()
+                .as_ref()
+                .and_then(|ivk| ivk.diversifier_index(addr))
+                .map(|_| Scope::External),
+            None => None,
+        };
+
+        if let Some(scope) = scope {
+            return Ok(Some((account_id, scope)));
+        }
+    }
+
+    Ok(None)
+}
+
 /// Returns the account id corresponding to a given [`SeedFingerprint`]
 /// and [`zip32::AccountId`], if any.
 pub(crate) fn get_derived_account<P: consensus::Parameters>(
@@ -1072,14 +1489,20 @@ impl ScanProgress for SubtreeScanProgress {
 ///
 /// `min_confirmations` can be 0, but that case is currently treated identically to
 /// `min_confirmations == 1` for shielded notes. This behaviour may change in the future.
-#[tracing::instrument(skip(tx, params, progress))]
+///
+/// Shielded notes rejected by `dust_filter` are omitted from the returned balances.
+///
+/// This should be called within a transaction, so that the summary reflects a single state of
+/// the wallet.
+#[tracing::instrument(skip(conn, params, progress))]
 pub(crate) fn get_wallet_summary<P: consensus::Parameters>(
-    tx: &rusqlite::Transaction,
+    conn: &rusqlite::Connection,
     params: &P,
     min_confirmations: u32,
+    dust_filter: &DustFilter,
     progress: &impl ScanProgress,
 ) -> Result<Option<WalletSummary<AccountId>>, SqliteClientError> {
-    let chain_tip_height = match scan_queue_extrema(tx)? {
+    let chain_tip_height = match scan_queue_extrema(conn)? {
         Some(range) => *range.end(),
         None => {
             return Ok(None);
@@ -1087,14 +1510,14 @@ pub(crate) fn get_wallet_summary<P: consensus::Parameters>(
     };
 
     let birthday_height =
-        wallet_birthday(tx)?.expect("If a scan range exists, we know the wallet birthday.");
+        wallet_birthday(conn)?.expect("If a scan range exists, we know the wallet birthday.");
 
     let fully_scanned_height =
-        block_fully_scanned(tx, params)?.map_or(birthday_height - 1, |m| m.block_height());
+        block_fully_scanned(conn, params)?.map_or(birthday_height - 1, |m| m.block_height());
     let summary_height = (chain_tip_height + 1).saturating_sub(std::cmp::max(min_confirmations, 1));
 
     let sapling_scan_progress = progress.sapling_scan_progress(
-        tx,
+        conn,
         birthday_height,
         fully_scanned_height,
         chain_tip_height,
@@ -1102,7 +1525,7 @@ pub(crate) fn get_wallet_summary<P: consensus::Parameters>(
 
     #[cfg(feature = "orchard")]
     let orchard_scan_progress = progress.orchard_scan_progress(
-        tx,
+        conn,
         birthday_height,
         fully_scanned_height,
         chain_tip_height,
@@ -1122,17 +1545,98 @@ pub(crate) fn get_wallet_summary<P: consensus::Parameters>(
         .or(sapling_scan_progress)
         .or(orchard_scan_progress);
 
-    let mut stmt_accounts = tx.prepare_cached("SELECT id FROM accounts")?;
+    let account_balances = match get_cached_account_balances(conn, min_confirmations, dust_filter)?
+    {
+        Some(balances) => balances,
+        None => {
+            let balances = compute_account_balances(
+                conn,
+                chain_tip_height,
+                summary_height,
+                min_confirmations,
+                dust_filter,
+            )?;
+            // A read-only handle computes the balances without caching them.
+            if !conn.is_readonly(rusqlite::DatabaseName::Main)? {
+                put_cached_account_balances(conn, min_confirmations, dust_filter, &balances)?;
+            }
+            balances
+        }
+    };
+
+    // The approach used here for Sapling and Orchard subtree indexing was a quick hack
+    // that has not yet been replaced. TODO: Make less hacky.
+    // https://github.com/zcash/librustzcash/issues/1249
+    let next_sapling_subtree_index = {
+        // The last shard will be incomplete, and we want the next range to overlap with
+        // the last complete shard, so return the index of the second-to-last shard root.
+        commitment_tree::get_shard_roots(
+            conn,
+            SAPLING_TABLES_PREFIX,
+            Level::from(SAPLING_SHARD_HEIGHT),
+        )
+        .map_err(ShardTreeError::Storage)?
+        .iter()
+        .rev()
+        .nth(1)
+        .map(|addr| addr.index())
+        .unwrap_or(0)
+    };
+
+    #[cfg(feature = "orchard")]
+    let next_orchard_subtree_index = {
+        // The last shard will be incomplete, and we want the next range to overlap with
+        // the last complete shard, so return the index of the second-to-last shard root.
+        commitment_tree::get_shard_roots(
+            conn,
+            ORCHARD_TABLES_PREFIX,
+            Level::from(ORCHARD_SHARD_HEIGHT),
+        )
+        .map_err(ShardTreeError::Storage)?
+        .iter()
+        .rev()
+        .nth(1)
+        .map(|addr| addr.index())
+        .unwrap_or(0)
+    };
+
+    let summary = WalletSummary::new(
+        account_balances,
+        chain_tip_height,
+        fully_scanned_height,
+        scan_progress,
+        next_sapling_subtree_index,
+        #[cfg(feature = "orchard")]
+        next_orchard_subtree_index,
+    );
+
+    Ok(Some(summary))
+}
+
+/// Computes the balances of all accounts in the wallet from their received notes and outputs.
+///
+/// This requires time linear in the number of notes in the wallet; see
+/// [`get_cached_account_balances`].
+#[cfg_attr(not(feature = "transparent-inputs"), allow(unused_variables))]
+fn compute_account_balances(
+    conn: &rusqlite::Connection,
+    chain_tip_height: BlockHeight,
+    summary_height: BlockHeight,
+    min_confirmations: u32,
+    dust_filter: &DustFilter,
+) -> Result<HashMap<AccountId, AccountBalance>, SqliteClientError> {
+    let mut stmt_accounts = conn.prepare_cached("SELECT id FROM accounts")?;
     let mut account_balances = stmt_accounts
         .query([])?
         .and_then(|row| {
-            Ok::<_, SqliteClientError>((AccountId(row.get::<_, u32>(0)?), AccountBalance::ZERO))
+            Ok::<_, SqliteClientError>((row.get::<_, AccountId>(0)?, AccountBalance::ZERO))
         })
         .collect::<Result<HashMap<AccountId, AccountBalance>, _>>()?;
 
     fn count_notes<F>(
-        tx: &rusqlite::Transaction,
+        conn: &rusqlite::Connection,
         summary_height: BlockHeight,
+        dust_filter: &DustFilter,
         account_balances: &mut HashMap<AccountId, AccountBalance>,
         table_prefix: &'static str,
         with_pool_balance: F,
@@ -1169,8 +1673,8 @@ pub(crate) fn get_wallet_summary<P: consensus::Parameters>(
             .map_err(|e| e.into())
         }
 
-        let any_spendable = is_any_spendable(tx, summary_height, table_prefix)?;
-        let mut stmt_select_notes = tx.prepare_cached(&format!(
+        let any_spendable = is_any_spendable(conn, summary_height, table_prefix)?;
+        let mut stmt_select_notes = conn.prepare_cached(&format!(
             "SELECT n.account_id, n.value, n.is_change, scan_state.max_priority, t.block
              FROM {table_prefix}_received_notes n
              JOIN transactions t ON t.id_tx = n.tx
@@ -1196,7 +1700,7 @@ pub(crate) fn get_wallet_summary<P: consensus::Parameters>(
         let mut rows =
             stmt_select_notes.query(named_params![":summary_height": u32::from(summary_height)])?;
         while let Some(row) = rows.next()? {
-            let account = AccountId(row.get::<_, u32>(0)?);
+            let account: AccountId = row.get(0)?;
 
             let value_raw = row.get::<_, i64>(1)?;
             let value = NonNegativeAmount::from_nonnegative_i64(value_raw).map_err(|_| {
@@ -1207,6 +1711,9 @@ pub(crate) fn get_wallet_summary<P: consensus::Parameters>(
             })?;
 
             let is_change = row.get::<_, bool>(2)?;
+            if dust_filter.is_dust(value, is_change) {
+                continue;
+            }
 
             // If `max_priority` is null, this means that the note is not positioned; the note
             // will not be spendable, so we assign the scan priority to `ChainTip` as a priority
@@ -1230,14 +1737,14 @@ pub(crate) fn get_wallet_summary<P: consensus::Parameters>(
                 && received_height.iter().any(|h| h <= &summary_height)
                 && max_priority <= ScanPriority::Scanned;
 
-            let is_pending_change =
-                is_change && received_height.iter().all(|h| h > &summary_height);
-
+            // Change is reported separately from value received from other parties, whether
+            // it is awaiting confirmations or scanning, because it is the output of one of the
+            // wallet's own transactions.
             let (spendable_value, change_pending_confirmation, value_pending_spendability) = {
                 let zero = NonNegativeAmount::ZERO;
                 if is_spendable {
                     (value, zero, zero)
-                } else if is_pending_change {
+                } else if is_change {
                     (zero, value, zero)
                 } else {
                     (zero, zero, value)
@@ -1260,8 +1767,9 @@ pub(crate) fn get_wallet_summary<P: consensus::Parameters>(
     {
         let orchard_trace = tracing::info_span!("orchard_balances").entered();
         count_notes(
-            tx,
+            conn,
             summary_height,
+            dust_filter,
             &mut account_balances,
             ORCHARD_TABLES_PREFIX,
             |balances, spendable_value, change_pending_confirmation, value_pending_spendability| {
@@ -1278,8 +1786,9 @@ pub(crate) fn get_wallet_summary<P: consensus::Parameters>(
 
     let sapling_trace = tracing::info_span!("sapling_balances").entered();
     count_notes(
-        tx,
+        conn,
         summary_height,
+        dust_filter,
         &mut account_balances,
         SAPLING_TABLES_PREFIX,
         |balances, spendable_value, change_pending_confirmation, value_pending_spendability| {
@@ -1299,8 +1808,16 @@ pub(crate) fn get_wallet_summary<P: consensus::Parameters>(
         let zero_conf_height = (chain_tip_height + 1).saturating_sub(min_confirmations);
         let stable_height = chain_tip_height.saturating_sub(PRUNING_DEPTH);
 
-        let mut stmt_transparent_balances = tx.prepare(
-            "SELECT u.received_by_account_id, SUM(u.value_zat)
+        let coinbase_max_height = coinbase_maturity_height(chain_tip_height);
+
+        // Coinbase outputs that have not yet matured are reported separately, because they
+        // cannot yet be shielded.
+        let mut stmt_transparent_balances = conn.prepare(
+            "SELECT u.received_by_account_id,
+                SUM(CASE WHEN u.is_coinbase = 0 OR u.height <= :coinbase_max_height
+                    THEN u.value_zat ELSE 0 END),
+                SUM(CASE WHEN u.is_coinbase = 0 OR u.height <= :coinbase_max_height
+                    THEN 0 ELSE u.value_zat END)
              FROM utxos u
              WHERE u.height <= :max_height
              -- and the received txo is unspent
@@ -1317,76 +1834,200 @@ pub(crate) fn get_wallet_summary<P: consensus::Parameters>(
         )?;
         let mut rows = stmt_transparent_balances.query(named_params![
             ":max_height": u32::from(zero_conf_height),
-            ":stable_height": u32::from(stable_height)
+            ":stable_height": u32::from(stable_height),
+            ":coinbase_max_height": u32::from(coinbase_max_height),
         ])?;
 
+        let value =
+            |row: &rusqlite::Row, idx: usize| -> Result<NonNegativeAmount, SqliteClientError> {
+                let raw_value = row.get(idx)?;
+                NonNegativeAmount::from_nonnegative_i64(raw_value).map_err(|_| {
+                    SqliteClientError::CorruptedData(format!("Negative UTXO value {:?}", raw_value))
+                })
+            };
+
         while let Some(row) = rows.next()? {
-            let account = AccountId(row.get(0)?);
-            let raw_value = row.get(1)?;
-            let value = NonNegativeAmount::from_nonnegative_i64(raw_value).map_err(|_| {
-                SqliteClientError::CorruptedData(format!("Negative UTXO value {:?}", raw_value))
-            })?;
-
+            let account: AccountId = row.get(0)?;
             if let Some(balances) = account_balances.get_mut(&account) {
-                balances.add_unshielded_value(value)?;
+                balances.add_unshielded_value(value(row, 1)?)?;
+                balances.add_unshielded_pending_maturity_value(value(row, 2)?)?;
             }
         }
         drop(transparent_trace);
     }
 
-    // The approach used here for Sapling and Orchard subtree indexing was a quick hack
-    // that has not yet been replaced. TODO: Make less hacky.
-    // https://github.com/zcash/librustzcash/issues/1249
-    let next_sapling_subtree_index = {
-        let shard_store =
-            SqliteShardStore::<_, ::sapling::Node, SAPLING_SHARD_HEIGHT>::from_connection(
-                tx,
-                SAPLING_TABLES_PREFIX,
-            )?;
+    Ok(account_balances)
+}
 
-        // The last shard will be incomplete, and we want the next range to overlap with
-        // the last complete shard, so return the index of the second-to-last shard root.
-        shard_store
-            .get_shard_roots()
-            .map_err(ShardTreeError::Storage)?
-            .iter()
-            .rev()
-            .nth(1)
-            .map(|addr| addr.index())
-            .unwrap_or(0)
+/// Returns the cached balances of all accounts in the wallet for the given confirmation depth
+/// and dust filter, if they are present in the `account_balances` table.
+///
+/// The cache is cleared by triggers whenever a write may change the balances, such as when a
+/// note is received or spent, a transaction is mined, the chain tip advances, or blocks are
+/// scanned, so any cached balances are consistent with the current wallet state.
+fn get_cached_account_balances(
+    conn: &rusqlite::Connection,
+    min_confirmations: u32,
+    dust_filter: &DustFilter,
+) -> Result<Option<HashMap<AccountId, AccountBalance>>, SqliteClientError> {
+    let mut stmt_balances = conn.prepare_cached(
+        "SELECT account_id,
+                sapling_spendable_value,
+                sapling_change_pending_confirmation,
+                sapling_value_pending_spendability,
+                orchard_spendable_value,
+                orchard_change_pending_confirmation,
+                orchard_value_pending_spendability,
+                unshielded_value,
+                unshielded_pending_maturity_value
+         FROM account_balances
+         WHERE min_confirmations = :min_confirmations
+         AND min_note_value = :min_note_value",
+    )?;
+
+    let value = |row: &rusqlite::Row, idx: usize| -> Result<NonNegativeAmount, SqliteClientError> {
+        let raw = row.get::<_, i64>(idx)?;
+        NonNegativeAmount::from_nonnegative_i64(raw).map_err(|_| {
+            SqliteClientError::CorruptedData(format!("Negative cached balance value: {}", raw))
+        })
     };
 
-    #[cfg(feature = "orchard")]
-    let next_orchard_subtree_index = {
-        let shard_store = SqliteShardStore::<
-            _,
-            ::orchard::tree::MerkleHashOrchard,
-            ORCHARD_SHARD_HEIGHT,
-        >::from_connection(tx, ORCHARD_TABLES_PREFIX)?;
+    let balances = stmt_balances
+        .query_and_then(
+            named_params![
+                ":min_confirmations": min_confirmations,
+                ":min_note_value": u64::from(dust_filter.min_note_value()),
+            ],
+            |row| {
+                let mut balance = AccountBalance::ZERO;
+                balance.with_sapling_balance_mut::<_, SqliteClientError>(|bal| {
+                    bal.add_spendable_value(value(row, 1)?)?;
+                    bal.add_pending_change_value(value(row, 2)?)?;
+                    bal.add_pending_spendable_value(value(row, 3)?)?;
+                    Ok(())
+                })?;
+                balance.with_orchard_balance_mut::<_, SqliteClientError>(|bal| {
+                    bal.add_spendable_value(value(row, 4)?)?;
+                    bal.add_pending_change_value(value(row, 5)?)?;
+                    bal.add_pending_spendable_value(value(row, 6)?)?;
+                    Ok(())
+                })?;
+                balance.add_unshielded_value(value(row, 7)?)?;
+                balance.add_unshielded_pending_maturity_value(value(row, 8)?)?;
+                Ok((row.get::<_, AccountId>(0)?, balance))
+            },
+        )?
+        .collect::<Result<HashMap<_, _>, SqliteClientError>>()?;
 
-        // The last shard will be incomplete, and we want the next range to overlap with
-        // the last complete shard, so return the index of the second-to-last shard root.
-        shard_store
-            .get_shard_roots()
-            .map_err(ShardTreeError::Storage)?
-            .iter()
-            .rev()
-            .nth(1)
-            .map(|addr| addr.index())
-            .unwrap_or(0)
+    // Cached balances are written for every account at once, and are cleared whenever an
+    // account is added, so an empty result means that nothing is cached.
+    Ok(Some(balances).filter(|b| !b.is_empty()))
+}
+
+/// Stores the given account balances in the `account_balances` cache.
+fn put_cached_account_balances(
+    conn: &rusqlite::Connection,
+    min_confirmations: u32,
+    dust_filter: &DustFilter,
+    balances: &HashMap<AccountId, AccountBalance>,
+) -> Result<(), SqliteClientError> {
+    let mut stmt_insert = conn.prepare_cached(
+        "INSERT INTO account_balances (
+            account_id, min_confirmations, min_note_value,
+            sapling_spendable_value,
+            sapling_change_pending_confirmation,
+            sapling_value_pending_spendability,
+            orchard_spendable_value,
+            orchard_change_pending_confirmation,
+            orchard_value_pending_spendability,
+            unshielded_value,
+            unshielded_pending_maturity_value
+        )
+        VALUES (
+            :account_id, :min_confirmations, :min_note_value,
+            :sapling_spendable_value,
+            :sapling_change_pending_confirmation,
+            :sapling_value_pending_spendability,
+            :orchard_spendable_value,
+            :orchard_change_pending_confirmation,
+            :orchard_value_pending_spendability,
+            :unshielded_value,
+            :unshielded_pending_maturity_value
+        )
+        ON CONFLICT (account_id, min_confirmations, min_note_value) DO NOTHING",
+    )?;
+
+    for (account_id, balance) in balances {
+        stmt_insert.execute(named_params![
+            ":account_id": account_id,
+            ":min_confirmations": min_confirmations,
+            ":min_note_value": u64::from(dust_filter.min_note_value()),
+            ":sapling_spendable_value": u64::from(balance.sapling_balance().spendable_value()),
+            ":sapling_change_pending_confirmation": u64::from(balance.sapling_balance().change_pending_confirmation()),
+            ":sapling_value_pending_spendability": u64::from(balance.sapling_balance().value_pending_spendability()),
+            ":orchard_spendable_value": u64::from(balance.orchard_balance().spendable_value()),
+            ":orchard_change_pending_confirmation": u64::from(balance.orchard_balance().change_pending_confirmation()),
+            ":orchard_value_pending_spendability": u64::from(balance.orchard_balance().value_pending_spendability()),
+            ":unshielded_value": u64::from(balance.unshielded()),
+            ":unshielded_pending_maturity_value": u64::from(balance.unshielded_pending_maturity()),
+        ])?;
+    }
+
+    Ok(())
+}
+
+/// Checks that the balances in the `account_balances` cache are equal to those computed
+/// directly from the wallet's notes, returning an error describing the first inconsistency
+/// found.
+pub(crate) fn check_account_balances_cache(
+    tx: &rusqlite::Transaction,
+) -> Result<(), SqliteClientError> {
+    let chain_tip_height = match scan_queue_extrema(tx)? {
+        Some(range) => *range.end(),
+        None => return Ok(()),
     };
 
-    let summary = WalletSummary::new(
-        account_balances,
-        chain_tip_height,
-        fully_scanned_height,
-        scan_progress,
-        next_sapling_subtree_index,
-        #[cfg(feature = "orchard")]
-        next_orchard_subtree_index,
-    );
+    let mut stmt_keys = tx.prepare_cached(
+        "SELECT DISTINCT min_confirmations, min_note_value FROM account_balances",
+    )?;
+    let keys = stmt_keys
+        .query_and_then([], |row| {
+            let min_confirmations = row.get::<_, u32>(0)?;
+            let min_note_value = row.get::<_, i64>(1)?;
+            let dust_filter = NonNegativeAmount::from_nonnegative_i64(min_note_value)
+                .map(DustFilter::MinNoteValue)
+                .map_err(|_| {
+                    SqliteClientError::CorruptedData(format!(
+                        "Negative cached minimum note value: {}",
+                        min_note_value
+                    ))
+                })?;
+            Ok::<_, SqliteClientError>((min_confirmations, dust_filter))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
 
-    Ok(Some(summary))
+    for (min_confirmations, dust_filter) in keys {
+        let summary_height =
+            (chain_tip_height + 1).saturating_sub(std::cmp::max(min_confirmations, 1));
+        let computed = compute_account_balances(
+            tx,
+            chain_tip_height,
+            summary_height,
+            min_confirmations,
+            &dust_filter,
+        )?;
+        let cached =
+            get_cached_account_balances(tx, min_confirmations, &dust_filter)?.unwrap_or_default();
+        if cached != computed {
+            return Err(SqliteClientError::CorruptedData(format!(
+                "Cached account balances for min_confirmations = {} and {:?} are inconsistent \
+                 with the wallet's notes: cached {:?}, computed {:?}",
+                min_confirmations, dust_filter, cached, computed
+            )));
+        }
+    }
+
+    Ok(())
 }
 
 /// Returns the memo for a received note, if the note is known to the wallet.
@@ -1505,6 +2146,71 @@ pub(crate) fn get_transaction<P: Parameters>(
     .transpose()
 }
 
+/// Returns an [`AuditBundle`] containing the outputs decrypted by the given account's full
+/// viewing key in each transaction involving that account that was mined within the given
+/// range of heights.
+///
+/// Transactions for which the wallet has not retrieved the full transaction data are omitted,
+/// as their outputs cannot be disclosed.
+pub(crate) fn export_account_audit_bundle<P: consensus::Parameters>(
+    conn: &rusqlite::Connection,
+    params: &P,
+    account_id: AccountId,
+    height_range: Range<BlockHeight>,
+) -> Result<AuditBundle, SqliteClientError> {
+    let account =
+        get_account(conn, params, account_id)?.ok_or(SqliteClientError::AccountUnknown)?;
+    let ufvk = account.viewing_key.ufvk().cloned().ok_or_else(|| {
+        SqliteClientError::BadAccountData(
+            "Audit bundles can only be exported for accounts with a full viewing key.".to_owned(),
+        )
+    })?;
+
+    let mut stmt = conn.prepare_cached(
+        "SELECT v_transactions.txid, v_transactions.mined_height
+         FROM v_transactions
+         JOIN transactions ON transactions.txid = v_transactions.txid
+         WHERE v_transactions.account_id = :account_id
+         AND v_transactions.mined_height >= :start_height
+         AND v_transactions.mined_height < :end_height
+         AND transactions.raw IS NOT NULL
+         ORDER BY v_transactions.mined_height, v_transactions.tx_index",
+    )?;
+
+    let transactions = stmt
+        .query_and_then(
+            named_params![
+                ":account_id": account_id,
+                ":start_height": u32::from(height_range.start),
+                ":end_height": u32::from(height_range.end),
+            ],
+            |row| -> Result<_, SqliteClientError> {
+                let txid = TxId::from_bytes(row.get("txid")?);
+                let mined_height = BlockHeight::from(row.get::<_, u32>("mined_height")?);
+                let (_, tx) = get_transaction(conn, params, txid)?.ok_or_else(|| {
+                    SqliteClientError::CorruptedData(format!(
+                        "Transaction data not found for txid {}",
+                        txid
+                    ))
+                })?;
+                Ok((mined_height, tx))
+            },
+        )?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let ufvks = HashMap::from([(account_id, ufvk)]);
+    Ok(AuditBundle::from_decrypted_transactions(
+        account_id,
+        height_range,
+        transactions.iter().map(|(mined_height, tx)| {
+            (
+                *mined_height,
+                decrypt_transaction(params, Some(*mined_height), None, tx, &ufvks),
+            )
+        }),
+    ))
+}
+
 pub(crate) fn get_funding_accounts(
     conn: &rusqlite::Connection,
     tx: &Transaction,
@@ -1596,7 +2302,7 @@ pub(crate) fn account_birthday(
         "SELECT birthday_height
          FROM accounts
          WHERE id = :account_id",
-        named_params![":account_id": account.0],
+        named_params![":account_id": account],
         |row| row.get::<_, u32>(0).map(BlockHeight::from),
     )
     .optional()
@@ -1604,6 +2310,239 @@ pub(crate) fn account_birthday(
     .and_then(|opt| opt.ok_or(SqliteClientError::AccountUnknown))
 }
 
+/// Returns the balance of the given account as of the end of the block at `height`, or `None`
+/// if the wallet has not been fully scanned up to that height.
+///
+/// The balance is the value of the outputs received by the account in transactions mined at or
+/// below `height` that were not spent in a transaction mined at or below `height`.
+pub(crate) fn get_balance_at_height<P: consensus::Parameters>(
+    conn: &rusqlite::Connection,
+    params: &P,
+    account: AccountId,
+    height: BlockHeight,
+) -> Result<Option<HistoricalBalance>, SqliteClientError> {
+    // Check that the account exists.
+    account_birthday(conn, account)?;
+
+    match block_fully_scanned(conn, params)? {
+        Some(meta) if meta.block_height() >= height => (),
+        _ => return Ok(None),
+    }
+
+    let value_at_height = |sql: &str| -> Result<NonNegativeAmount, SqliteClientError> {
+        let value = conn.query_row(
+            sql,
+            named_params![":account_id": account, ":height": u32::from(height),],
+            |row| row.get::<_, i64>(0),
+        )?;
+        NonNegativeAmount::from_nonnegative_i64(value).map_err(|_| {
+            SqliteClientError::CorruptedData(format!("Negative received value {}", value))
+        })
+    };
+
+    let shielded_value = |table_prefix: &str| {
+        value_at_height(&format!(
+            "SELECT COALESCE(SUM(rn.value), 0)
+            FROM {table_prefix}_received_notes rn
+            JOIN transactions t ON t.id_tx = rn.tx
+            WHERE rn.account_id = :account_id
+            AND t.block <= :height
+            AND NOT EXISTS (
+                SELECT 1
+                FROM {table_prefix}_received_note_spends sp
+                JOIN transactions stx ON stx.id_tx = sp.transaction_id
+                WHERE sp.{table_prefix}_received_note_id = rn.id
+                AND stx.block <= :height
+            )"
+        ))
+    };
+
+    let sapling = shielded_value(SAPLING_TABLES_PREFIX)?;
+    #[cfg(feature = "orchard")]
+    let orchard = shielded_value(ORCHARD_TABLES_PREFIX)?;
+    #[cfg(not(feature = "orchard"))]
+    let orchard = NonNegativeAmount::ZERO;
+
+    let unshielded = value_at_height(
+        "SELECT COALESCE(SUM(u.value_zat), 0)
+        FROM utxos u
+        WHERE u.received_by_account_id = :account_id
+        AND u.height <= :height
+        AND NOT EXISTS (
+            SELECT 1
+            FROM transparent_received_output_spends sp
+            JOIN transactions stx ON stx.id_tx = sp.transaction_id
+            WHERE sp.transparent_received_output_id = u.id
+            AND stx.block <= :height
+        )",
+    )?;
+
+    Ok(Some(HistoricalBalance::from_parts(
+        height, sapling, orchard, unshielded,
+    )?))
+}
+
+/// Returns aggregate statistics for the mined transactions of the given account, bucketed by
+/// the UTC day or month in which their blocks were mined.
+pub(crate) fn get_account_statistics(
+    conn: &rusqlite::Connection,
+    account: AccountId,
+    granularity: StatisticsGranularity,
+) -> Result<Vec<PeriodStatistics>, SqliteClientError> {
+    // Check that the account exists.
+    account_birthday(conn, account)?;
+
+    let period_modifier = match granularity {
+        StatisticsGranularity::Day => "start of day",
+        StatisticsGranularity::Month => "start of month",
+    };
+
+    // Each row of `v_transactions` describes the net effect of a transaction on the balance of
+    // a single account, so the account's rows can be aggregated directly.
+    let mut stmt = conn.prepare_cached(
+        "SELECT CAST(strftime('%s', block_time, 'unixepoch', :period_modifier) AS INTEGER)
+                    AS period_start,
+                SUM(MAX(account_balance_delta, 0)),
+                SUM(account_balance_delta > 0),
+                SUM(
+                    CASE WHEN account_balance_delta < 0
+                    THEN MAX(-account_balance_delta - IFNULL(fee_paid, 0), 0)
+                    ELSE 0 END
+                ),
+                SUM(account_balance_delta < 0),
+                SUM(
+                    CASE WHEN account_balance_delta < 0
+                    THEN MIN(IFNULL(fee_paid, 0), -account_balance_delta)
+                    ELSE 0 END
+                )
+         FROM v_transactions
+         WHERE account_id = :account_id
+         AND mined_height IS NOT NULL
+         AND block_time IS NOT NULL
+         GROUP BY period_start
+         ORDER BY period_start",
+    )?;
+
+    let value = |row: &rusqlite::Row, idx: usize| -> Result<NonNegativeAmount, SqliteClientError> {
+        let raw_value = row.get(idx)?;
+        NonNegativeAmount::from_nonnegative_i64(raw_value).map_err(|_| {
+            SqliteClientError::CorruptedData(format!("Negative aggregate value {:?}", raw_value))
+        })
+    };
+
+    let rows = stmt.query_and_then(
+        named_params![":account_id": account, ":period_modifier": period_modifier,],
+        |row| {
+            let period_start = time::OffsetDateTime::from_unix_timestamp(row.get(0)?)
+                .map_err(|e| {
+                    SqliteClientError::CorruptedData(format!("Invalid block time: {}", e))
+                })?
+                .date();
+            Ok(PeriodStatistics::from_parts(
+                period_start,
+                value(row, 1)?,
+                row.get(2)?,
+                value(row, 3)?,
+                row.get(4)?,
+                value(row, 5)?,
+            ))
+        },
+    )?;
+
+    rows.collect()
+}
+
+/// Returns whether blocks are scanned for notes belonging to the given account.
+pub(crate) fn is_account_scanning_enabled(
+    conn: &rusqlite::Connection,
+    account: AccountId,
+) -> Result<bool, SqliteClientError> {
+    conn.query_row(
+        "SELECT enabled_for_scanning
+         FROM accounts
+         WHERE id = :account_id",
+        named_params![":account_id": account],
+        |row| row.get::<_, bool>(0),
+    )
+    .optional()
+    .map_err(SqliteClientError::from)
+    .and_then(|opt| opt.ok_or(SqliteClientError::AccountUnknown))
+}
+
+/// Includes or excludes the given account from the set of accounts for which blocks are
+/// scanned.
+///
+/// When an account is excluded, the height of the first block that may subsequently be scanned
+/// without detecting the account's notes is recorded. When the account is included again, all
+/// blocks from that height (or the account's birthday, if later) up to the chain tip are
+/// re-queued for scanning, so that the notes that were missed while the account was excluded
+/// are found. Blocks that have already been scanned for the wallet's other accounts are
+/// re-scanned for this account only.
+pub(crate) fn set_account_scanning_enabled<P: consensus::Parameters>(
+    conn: &rusqlite::Transaction,
+    params: &P,
+    account: AccountId,
+    enabled: bool,
+) -> Result<(), SqliteClientError> {
+    if is_account_scanning_enabled(conn, account)? == enabled {
+        return Ok(());
+    }
+
+    if enabled {
+        let disabled_height = conn
+            .query_row(
+                "SELECT scanning_disabled_height FROM accounts WHERE id = :account_id",
+                named_params![":account_id": account],
+                |row| row.get::<_, Option<u32>>(0),
+            )?
+            .map(BlockHeight::from);
+
+        conn.execute(
+            "UPDATE accounts
+             SET enabled_for_scanning = 1, scanning_disabled_height = NULL
+             WHERE id = :account_id",
+            named_params![":account_id": account],
+        )?;
+
+        let birthday_height = account_birthday(conn, account)?;
+        let from_height =
+            disabled_height.map_or(birthday_height, |h| std::cmp::max(h, birthday_height));
+        if let Some(chain_tip_height) = scan_queue_extrema(conn)?.map(|range| *range.end()) {
+            if from_height <= chain_tip_height {
+                let rescan_range = from_height..(chain_tip_height + 1);
+                queue_account_backfill(conn, account, &rescan_range)?;
+
+                journal::record_event(
+                    conn,
+                    WalletEventKind::Rescan,
+                    Some(rescan_range.start),
+                    Some(rescan_range.end),
+                    None,
+                    Some(&format!("account {} enabled for scanning", account.0)),
+                )?;
+            }
+        }
+    } else {
+        // Blocks above the fully-scanned height may be scanned after this point, and so may not
+        // have been scanned for this account.
+        let disabled_height = block_fully_scanned(conn, params)?
+            .map(|m| m.block_height() + 1)
+            .unwrap_or_else(|| BlockHeight::from(0));
+
+        conn.execute(
+            "UPDATE accounts
+             SET enabled_for_scanning = 0, scanning_disabled_height = :disabled_height
+             WHERE id = :account_id",
+            named_params![
+                ":account_id": account,
+                ":disabled_height": u32::from(disabled_height),
+            ],
+        )?;
+    }
+
+    Ok(())
+}
+
 /// Returns the minimum and maximum heights for blocks stored in the wallet database.
 pub(crate) fn block_height_extrema(
     conn: &rusqlite::Connection,
@@ -1630,7 +2569,7 @@ pub(crate) fn get_account<P: Parameters>(
         "#,
     )?;
 
-    let mut result = sql.query(named_params![":account_id": account_id.0])?;
+    let mut result = sql.query(named_params![":account_id": account_id])?;
     let row = result.next()?;
     match row {
         Some(row) => {
@@ -1981,7 +2920,8 @@ pub(crate) fn get_min_unspent_height(
 /// Truncates the database to the given height.
 ///
 /// If the requested height is greater than or equal to the height of the last scanned
-/// block, this function does nothing.
+/// block, only the data obtained from the nullifiers of unscanned blocks above the requested
+/// height (see [`put_block_nullifiers`]) is removed.
 ///
 /// This should only be executed inside a transactional context.
 pub(crate) fn truncate_to_height<P: consensus::Parameters>(
@@ -2023,6 +2963,59 @@ pub(crate) fn truncate_to_height<P: consensus::Parameters>(
         named_params![":new_end_height": u32::from(block_height + 1)],
     )?;
 
+    // Remove the transactions that were recorded from the nullifiers of unscanned blocks above
+    // the truncation height, along with the spends of the wallet's notes that they record.
+    // These blocks may be above the last scanned height.
+    for spends_table in [
+        "sapling_received_note_spends",
+        "orchard_received_note_spends",
+    ] {
+        conn.execute(
+            &format!(
+                "DELETE FROM {spends_table}
+                 WHERE transaction_id IN (
+                    SELECT id_tx FROM transactions
+                    WHERE block IS NULL
+                    AND lookahead_height > :block_height
+                 )"
+            ),
+            named_params![":block_height": u32::from(block_height)],
+        )?;
+    }
+    conn.execute(
+        "DELETE FROM transactions
+        WHERE block IS NULL
+        AND lookahead_height > :block_height",
+        named_params![":block_height": u32::from(block_height)],
+    )?;
+
+    // Delete from the nullifier map any entries with a locator referencing a block height
+    // greater than the truncation height.
+    conn.execute(
+        "DELETE FROM tx_locator_map
+        WHERE block_height > :block_height",
+        named_params![":block_height": u32::from(block_height)],
+    )?;
+
+    // The history of the wallet's transparent addresses must be checked again above the
+    // truncation height.
+    conn.execute(
+        "UPDATE transparent_address_checks
+        SET checked_height = :block_height
+        WHERE checked_height > :block_height",
+        named_params![":block_height": u32::from(block_height)],
+    )?;
+
+    // Uses of the wallet's addresses above the truncation height will be detected again.
+    truncate_address_usage(conn, params, block_height)?;
+
+    // Blocks above the truncation height will be scanned again for all accounts.
+    clear_account_backfill(
+        conn,
+        &((block_height + 1)..BlockHeight::from(u32::MAX)),
+        false,
+    )?;
+
     // If we're removing scanned blocks, we need to truncate the note commitment tree, un-mine
     // transactions, and remove received transparent outputs and affected block records from the
     // database.
@@ -2067,47 +3060,303 @@ pub(crate) fn truncate_to_height<P: consensus::Parameters>(
             [u32::from(block_height)],
         )?;
 
-        // Delete from the nullifier map any entries with a locator referencing a block
-        // height greater than the truncation height.
-        conn.execute(
-            "DELETE FROM tx_locator_map
-            WHERE block_height > :block_height",
-            named_params![":block_height": u32::from(block_height)],
+        journal::record_event(
+            conn,
+            WalletEventKind::Rewind,
+            Some(block_height + 1),
+            Some(last_scanned_height + 1),
+            None,
+            None,
         )?;
     }
 
     Ok(())
 }
 
-#[cfg(feature = "transparent-inputs")]
-fn to_unspent_transparent_output(row: &Row) -> Result<WalletTransparentOutput, SqliteClientError> {
-    let txid: Vec<u8> = row.get("prevout_txid")?;
-    let mut txid_bytes = [0u8; 32];
-    txid_bytes.copy_from_slice(&txid);
+/// Rolls back the wallet's scanned state to the block preceding `from_height`, and re-queues
+/// all blocks from that height to the current chain tip for scanning.
+///
+/// In contrast to [`truncate_to_height`], the wallet's view of the chain tip is not changed.
+/// Records of spends by transactions mined at or above `from_height` that were not created by
+/// the wallet are removed, so that they are rediscovered when the blocks are rescanned, and the
+/// full data of those transactions is requested again so that their transparent spends, which
+/// are not visible in compact blocks, are also restored. Spends by transactions that the wallet
+/// created are retained, so that the notes they spend do not become spendable again. Accounts,
+/// addresses, transaction data (including raw transactions and memos) and sent notes are
+/// retained.
+pub(crate) fn rescan_from<P: consensus::Parameters>(
+    conn: &rusqlite::Transaction,
+    params: &P,
+    from_height: BlockHeight,
+) -> Result<(), SqliteClientError> {
+    let chain_tip_height = match scan_queue_extrema(conn)? {
+        Some(range) => *range.end(),
+        None => return Ok(()),
+    };
+    // Blocks below the wallet's birthday have never been scanned.
+    let from_height = match wallet_birthday(conn)? {
+        Some(birthday_height) => std::cmp::max(from_height, birthday_height),
+        None => return Ok(()),
+    };
+    if from_height > chain_tip_height {
+        return Ok(());
+    }
 
-    let index: u32 = row.get("prevout_idx")?;
-    let script_pubkey = Script(row.get("script")?);
-    let raw_value: i64 = row.get("value_zat")?;
-    let value = NonNegativeAmount::from_nonnegative_i64(raw_value).map_err(|_| {
-        SqliteClientError::CorruptedData(format!("Invalid UTXO value: {}", raw_value))
-    })?;
-    let height: u32 = row.get("height")?;
+    // Roll back spentness. This must be done before the spending transactions are un-mined,
+    // after which they can no longer be identified by height.
+    let spends_tables = [
+        "sapling_received_note_spends",
+        "orchard_received_note_spends",
+        "transparent_received_output_spends",
+    ];
+    for spends_table in spends_tables {
+        conn.execute(
+            &format!(
+                "INSERT INTO tx_retrieval_queue (txid, query_type, dependent_transaction_id)
+                 SELECT txid, :query_type, id_tx FROM transactions
+                 WHERE block >= :from_height
+                 AND created IS NULL
+                 AND id_tx IN (SELECT transaction_id FROM {spends_table})
+                 ON CONFLICT (txid, query_type, dependent_transaction_id) DO NOTHING"
+            ),
+            named_params![
+                ":query_type": TxQueryType::Enhancement.code(),
+                ":from_height": u32::from(from_height),
+            ],
+        )?;
+    }
+    for spends_table in spends_tables {
+        conn.execute(
+            &format!(
+                "DELETE FROM {spends_table}
+                 WHERE transaction_id IN (
+                    SELECT id_tx FROM transactions
+                    WHERE block >= :from_height
+                    AND created IS NULL
+                 )"
+            ),
+            named_params![":from_height": u32::from(from_height)],
+        )?;
+    }
 
-    let outpoint = OutPoint::new(txid_bytes, index);
-    WalletTransparentOutput::from_parts(
-        outpoint,
-        TxOut {
-            value,
-            script_pubkey,
-        },
-        BlockHeight::from(height),
-    )
-    .ok_or_else(|| {
-        SqliteClientError::CorruptedData(
-            "Txout script_pubkey value did not correspond to a P2PKH or P2SH address".to_string(),
-        )
-    })
-}
+    // Roll back the note commitment trees, un-mine transactions and remove block records.
+    truncate_to_height(conn, params, from_height - 1)?;
+
+    // Restore the scan queue up to the previous chain tip.
+    let rescan_range = from_height..(chain_tip_height + 1);
+    replace_queue_entries::<SqliteClientError>(
+        conn,
+        &rescan_range,
+        Some(ScanRange::from_parts(
+            rescan_range.clone(),
+            ScanPriority::Historic,
+        ))
+        .into_iter(),
+        true, // force rescan
+    )?;
+
+    journal::record_event(
+        conn,
+        WalletEventKind::Rescan,
+        Some(rescan_range.start),
+        Some(rescan_range.end),
+        None,
+        None,
+    )?;
+
+    Ok(())
+}
+
+/// Removes the data obtained by trial-decrypting the blocks in the given range, and re-queues
+/// the range for scanning.
+///
+/// Unspent received notes in the range are deleted, so that they do not contribute to the
+/// wallet's balance until they have been rediscovered. Memos are not visible in compact blocks,
+/// so the full data of each transaction whose deleted notes carried memos is requested again.
+/// Received notes that have been spent are retained, so that the spends of the wallet's
+/// transactions remain recorded; their note commitment tree positions are cleared. Transactions,
+/// sent notes and blocks are not modified.
+pub(crate) fn truncate_scanned_data<P: consensus::Parameters>(
+    conn: &rusqlite::Transaction,
+    params: &P,
+    range: Range<BlockHeight>,
+) -> Result<(), SqliteClientError> {
+    if range.is_empty() {
+        return Ok(());
+    }
+
+    fn received_note_positions(
+        conn: &rusqlite::Connection,
+        table_prefix: &'static str,
+        range: &Range<BlockHeight>,
+    ) -> Result<Vec<Position>, SqliteClientError> {
+        let mut stmt = conn.prepare_cached(&format!(
+            "SELECT rn.commitment_tree_position
+             FROM {table_prefix}_received_notes rn
+             JOIN transactions t ON t.id_tx = rn.tx
+             WHERE t.block >= :start AND t.block < :end
+             AND rn.commitment_tree_position IS NOT NULL"
+        ))?;
+        let positions = stmt
+            .query_and_then(
+                named_params![
+                    ":start": u32::from(range.start),
+                    ":end": u32::from(range.end),
+                ],
+                |row| row.get::<_, u64>(0).map(Position::from),
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(positions)
+    }
+
+    fn truncate_received_notes(
+        conn: &rusqlite::Connection,
+        table_prefix: &'static str,
+        range: &Range<BlockHeight>,
+    ) -> Result<(), SqliteClientError> {
+        conn.execute(
+            &format!(
+                "INSERT INTO tx_retrieval_queue (txid, query_type, dependent_transaction_id)
+                 SELECT t.txid, :query_type, t.id_tx FROM transactions t
+                 WHERE t.block >= :start AND t.block < :end
+                 AND EXISTS (
+                    SELECT 1 FROM {table_prefix}_received_notes rn
+                    WHERE rn.tx = t.id_tx
+                    AND rn.memo IS NOT NULL
+                    AND rn.id NOT IN (
+                        SELECT {table_prefix}_received_note_id
+                        FROM {table_prefix}_received_note_spends
+                    )
+                 )
+                 ON CONFLICT (txid, query_type, dependent_transaction_id) DO NOTHING"
+            ),
+            named_params![
+                ":query_type": TxQueryType::Enhancement.code(),
+                ":start": u32::from(range.start),
+                ":end": u32::from(range.end),
+            ],
+        )?;
+        conn.execute(
+            &format!(
+                "DELETE FROM {table_prefix}_received_notes
+                 WHERE tx IN (
+                    SELECT id_tx FROM transactions
+                    WHERE block >= :start AND block < :end
+                 )
+                 AND id NOT IN (
+                    SELECT {table_prefix}_received_note_id
+                    FROM {table_prefix}_received_note_spends
+                 )"
+            ),
+            named_params![
+                ":start": u32::from(range.start),
+                ":end": u32::from(range.end),
+            ],
+        )?;
+        conn.execute(
+            &format!(
+                "UPDATE {table_prefix}_received_notes
+                 SET commitment_tree_position = NULL
+                 WHERE tx IN (
+                    SELECT id_tx FROM transactions
+                    WHERE block >= :start AND block < :end
+                 )"
+            ),
+            named_params![
+                ":start": u32::from(range.start),
+                ":end": u32::from(range.end),
+            ],
+        )?;
+        Ok(())
+    }
+
+    // Stop retaining witnesses for the notes in the range; the leaves for notes that are
+    // rediscovered will be marked again when the range is rescanned.
+    let sapling_positions = received_note_positions(conn, SAPLING_TABLES_PREFIX, &range)?;
+    #[cfg(feature = "orchard")]
+    let orchard_positions = received_note_positions(conn, ORCHARD_TABLES_PREFIX, &range)?;
+
+    let mut wdb = WalletDb {
+        conn: SqlTransaction(conn),
+        params: params.clone(),
+    };
+    wdb.with_sapling_tree_mut(|tree| {
+        for position in &sapling_positions {
+            tree.remove_mark(*position, None)?;
+        }
+        Ok::<_, SqliteClientError>(())
+    })?;
+    #[cfg(feature = "orchard")]
+    wdb.with_orchard_tree_mut(|tree| {
+        for position in &orchard_positions {
+            tree.remove_mark(*position, None)?;
+        }
+        Ok::<_, SqliteClientError>(())
+    })?;
+
+    truncate_received_notes(conn, SAPLING_TABLES_PREFIX, &range)?;
+    #[cfg(feature = "orchard")]
+    truncate_received_notes(conn, ORCHARD_TABLES_PREFIX, &range)?;
+    clear_account_backfill(conn, &range, false)?;
+
+    // Re-queue the range, including any parts of it that have already been scanned.
+    replace_queue_entries::<SqliteClientError>(
+        conn,
+        &range,
+        Some(ScanRange::from_parts(range.clone(), ScanPriority::Historic)).into_iter(),
+        true, // force rescan
+    )?;
+
+    journal::record_event(
+        conn,
+        WalletEventKind::Rescan,
+        Some(range.start),
+        Some(range.end),
+        None,
+        Some("discarded trial-decrypted notes"),
+    )?;
+
+    Ok(())
+}
+
+#[cfg(feature = "transparent-inputs")]
+fn to_unspent_transparent_output(row: &Row) -> Result<WalletTransparentOutput, SqliteClientError> {
+    let txid: Vec<u8> = row.get("prevout_txid")?;
+    let mut txid_bytes = [0u8; 32];
+    txid_bytes.copy_from_slice(&txid);
+
+    let index: u32 = row.get("prevout_idx")?;
+    let script_pubkey = Script(row.get("script")?);
+    let raw_value: i64 = row.get("value_zat")?;
+    let value = NonNegativeAmount::from_nonnegative_i64(raw_value).map_err(|_| {
+        SqliteClientError::CorruptedData(format!("Invalid UTXO value: {}", raw_value))
+    })?;
+    let height: u32 = row.get("height")?;
+    let is_coinbase: bool = row.get("is_coinbase")?;
+
+    let outpoint = OutPoint::new(txid_bytes, index);
+    WalletTransparentOutput::from_parts(
+        outpoint,
+        TxOut {
+            value,
+            script_pubkey,
+        },
+        BlockHeight::from(height),
+    )
+    .map(|output| output.with_coinbase(is_coinbase))
+    .ok_or_else(|| {
+        SqliteClientError::CorruptedData(
+            "Txout script_pubkey value did not correspond to a P2PKH or P2SH address".to_string(),
+        )
+    })
+}
+
+/// Returns the greatest height at which a coinbase output may have been mined for it to be
+/// spendable by a transaction mined in the block following `chain_tip_height`.
+#[cfg(feature = "transparent-inputs")]
+fn coinbase_maturity_height(chain_tip_height: BlockHeight) -> BlockHeight {
+    (chain_tip_height + 1).saturating_sub(COINBASE_MATURITY)
+}
 
 #[cfg(feature = "transparent-inputs")]
 pub(crate) fn get_unspent_transparent_output(
@@ -2115,7 +3364,7 @@ pub(crate) fn get_unspent_transparent_output(
     outpoint: &OutPoint,
 ) -> Result<Option<WalletTransparentOutput>, SqliteClientError> {
     let mut stmt_select_utxo = conn.prepare_cached(
-        "SELECT u.prevout_txid, u.prevout_idx, u.script, u.value_zat, u.height
+        "SELECT u.prevout_txid, u.prevout_idx, u.script, u.value_zat, u.height, u.is_coinbase
          FROM utxos u
          WHERE u.prevout_txid = :txid
          AND u.prevout_idx = :output_index
@@ -2160,7 +3409,7 @@ pub(crate) fn get_unspent_transparent_outputs<P: consensus::Parameters>(
 
     let mut stmt_utxos = conn.prepare(
         "SELECT u.prevout_txid, u.prevout_idx, u.script,
-                u.value_zat, u.height
+                u.value_zat, u.height, u.is_coinbase
          FROM utxos u
          WHERE u.address = :address
          AND u.height <= :max_height
@@ -2199,6 +3448,9 @@ pub(crate) fn get_unspent_transparent_outputs<P: consensus::Parameters>(
 /// Returns the unspent balance for each transparent address associated with the specified account,
 /// such that the block that included the transaction was mined at a height less than or equal to
 /// the provided `max_height`.
+///
+/// Coinbase outputs that could not be spent in the block following the chain tip, because they
+/// have not yet matured, are excluded.
 #[cfg(feature = "transparent-inputs")]
 pub(crate) fn get_transparent_balances<P: consensus::Parameters>(
     conn: &rusqlite::Connection,
@@ -2210,12 +3462,14 @@ pub(crate) fn get_transparent_balances<P: consensus::Parameters>(
     let stable_height = chain_tip_height
         .unwrap_or(max_height)
         .saturating_sub(PRUNING_DEPTH);
+    let coinbase_max_height = coinbase_maturity_height(chain_tip_height.unwrap_or(max_height));
 
     let mut stmt_blocks = conn.prepare(
         "SELECT u.address, SUM(u.value_zat)
          FROM utxos u
          WHERE u.received_by_account_id = :account_id
          AND u.height <= :max_height
+         AND (u.is_coinbase = 0 OR u.height <= :coinbase_max_height)
          AND u.id NOT IN (
             SELECT txo_spends.transparent_received_output_id
             FROM transparent_received_output_spends txo_spends
@@ -2230,9 +3484,10 @@ pub(crate) fn get_transparent_balances<P: consensus::Parameters>(
 
     let mut res = HashMap::new();
     let mut rows = stmt_blocks.query(named_params![
-        ":account_id": account.0,
+        ":account_id": account,
         ":max_height": u32::from(max_height),
         ":stable_height": u32::from(stable_height),
+        ":coinbase_max_height": u32::from(coinbase_max_height),
     ])?;
     while let Some(row) = rows.next()? {
         let taddr_str: String = row.get(0)?;
@@ -2245,6 +3500,36 @@ pub(crate) fn get_transparent_balances<P: consensus::Parameters>(
     Ok(res)
 }
 
+/// Returns the outpoints of the wallet's transparent outputs that have not been spent by a
+/// mined transaction, along with the accounts that received them.
+#[cfg(feature = "transparent-inputs")]
+pub(crate) fn get_unspent_transparent_outpoints(
+    conn: &rusqlite::Connection,
+) -> Result<Vec<(AccountId, OutPoint)>, SqliteClientError> {
+    let mut stmt = conn.prepare(
+        "SELECT u.received_by_account_id, u.prevout_txid, u.prevout_idx
+         FROM utxos u
+         WHERE u.id NOT IN (
+            SELECT txo_spends.transparent_received_output_id
+            FROM transparent_received_output_spends txo_spends
+            JOIN transactions tx ON tx.id_tx = txo_spends.transaction_id
+            WHERE tx.block IS NOT NULL -- the spending tx is mined
+         )",
+    )?;
+
+    let mut rows = stmt.query([])?;
+    let mut result = Vec::new();
+    while let Some(row) = rows.next()? {
+        let account_id: AccountId = row.get(0)?;
+        let txid: Vec<u8> = row.get(1)?;
+        let mut txid_bytes = [0u8; 32];
+        txid_bytes.copy_from_slice(&txid);
+        let index: u32 = row.get(2)?;
+        result.push((account_id, OutPoint::new(txid_bytes, index)));
+    }
+    Ok(result)
+}
+
 /// Returns a vector with the IDs of all accounts known to this wallet.
 pub(crate) fn get_account_ids(
     conn: &rusqlite::Connection,
@@ -2253,7 +3538,7 @@ pub(crate) fn get_account_ids(
     let mut rows = stmt.query([])?;
     let mut result = Vec::new();
     while let Some(row) = rows.next()? {
-        let id = AccountId(row.get(0)?);
+        let id: AccountId = row.get(0)?;
         result.push(id);
     }
     Ok(result)
@@ -2354,7 +3639,8 @@ pub(crate) fn put_tx_meta(
         VALUES (:txid, :block, :tx_index)
         ON CONFLICT (txid) DO UPDATE
         SET block = :block,
-            tx_index = :tx_index
+            tx_index = :tx_index,
+            lookahead_height = NULL
         RETURNING id_tx",
     )?;
 
@@ -2370,6 +3656,48 @@ pub(crate) fn put_tx_meta(
         .map_err(SqliteClientError::from)
 }
 
+/// Marks the given transaction, which spends notes belonging to the wallet, as having been spent
+/// externally if the wallet did not create it, and records the detection in the event journal.
+///
+/// Transactions created by the wallet are stored with a creation time before they are mined, so
+/// a spending transaction without one was created elsewhere, such as by another device using the
+/// same seed.
+pub(crate) fn mark_external_spend(
+    conn: &rusqlite::Connection,
+    tx_ref: i64,
+) -> Result<(), SqliteClientError> {
+    let marked = conn
+        .query_row(
+            "UPDATE transactions
+            SET spent_externally = 1
+            WHERE id_tx = :tx_ref
+            AND created IS NULL
+            AND spent_externally = 0
+            RETURNING txid, block",
+            named_params![":tx_ref": tx_ref],
+            |row| {
+                Ok((
+                    TxId::from_bytes(row.get::<_, [u8; 32]>(0)?),
+                    row.get::<_, Option<u32>>(1)?,
+                ))
+            },
+        )
+        .optional()?;
+
+    if let Some((txid, block)) = marked {
+        journal::record_event(
+            conn,
+            WalletEventKind::ExternalSpendDetected,
+            block.map(BlockHeight::from),
+            None,
+            Some(&txid),
+            None,
+        )?;
+    }
+
+    Ok(())
+}
+
 /// Returns the most likely wallet address that corresponds to the protocol-level receiver of a
 /// note or UTXO.
 pub(crate) fn select_receiving_address<P: consensus::Parameters>(
@@ -2398,7 +3726,7 @@ pub(crate) fn select_receiving_address<P: consensus::Parameters>(
             let mut stmt =
                 conn.prepare_cached("SELECT address FROM addresses WHERE account_id = :account")?;
 
-            let mut result = stmt.query(named_params! { ":account": account.0 })?;
+            let mut result = stmt.query(named_params! { ":account": account })?;
             while let Some(row) = result.next()? {
                 let addr_str = row.get::<_, String>(0)?;
                 let decoded = addr_str.parse::<ZcashAddress>()?;
@@ -2446,174 +3774,607 @@ pub(crate) fn put_tx_data(
         .map_err(SqliteClientError::from)
 }
 
-/// Marks the given UTXO as having been spent.
-#[cfg(feature = "transparent-inputs")]
-pub(crate) fn mark_transparent_utxo_spent(
+/// Returns the value of the transparent output at the given outpoint, if it is known to the
+/// wallet.
+///
+/// The value is known if the output was received by the wallet, or if the full data of the
+/// transaction that created it has been stored in the wallet.
+pub(crate) fn get_transparent_output_value<P: consensus::Parameters>(
     conn: &rusqlite::Connection,
-    tx_ref: i64,
+    params: &P,
     outpoint: &OutPoint,
-) -> Result<(), SqliteClientError> {
-    let mut stmt_mark_transparent_utxo_spent = conn.prepare_cached(
-        "INSERT INTO transparent_received_output_spends (transparent_received_output_id, transaction_id)
-         SELECT txo.id, :spent_in_tx
-         FROM utxos txo
-         WHERE txo.prevout_txid = :prevout_txid
-         AND txo.prevout_idx = :prevout_idx
-         ON CONFLICT (transparent_received_output_id, transaction_id) DO NOTHING",
-    )?;
+) -> Result<Option<NonNegativeAmount>, SqliteClientError> {
+    let received_value = conn
+        .query_row(
+            "SELECT value_zat FROM utxos
+            WHERE prevout_txid = :prevout_txid
+            AND prevout_idx = :prevout_idx",
+            named_params![
+                ":prevout_txid": &outpoint.hash()[..],
+                ":prevout_idx": outpoint.n(),
+            ],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()?;
+    if let Some(raw_value) = received_value {
+        return NonNegativeAmount::from_nonnegative_i64(raw_value)
+            .map(Some)
+            .map_err(|_| {
+                SqliteClientError::CorruptedData(format!("Negative UTXO value {:?}", raw_value))
+            });
+    }
 
-    let sql_args = named_params![
-        ":spent_in_tx": &tx_ref,
-        ":prevout_txid": &outpoint.hash().to_vec(),
-        ":prevout_idx": &outpoint.n(),
-    ];
+    let prev_txid = TxId::from_bytes(*outpoint.hash());
+    let has_raw = conn
+        .query_row(
+            "SELECT 1 FROM transactions WHERE txid = :txid AND raw IS NOT NULL",
+            named_params![":txid": prev_txid.as_ref()],
+            |_| Ok(()),
+        )
+        .optional()?
+        .is_some();
+    if !has_raw {
+        return Ok(None);
+    }
 
-    stmt_mark_transparent_utxo_spent.execute(sql_args)?;
-    Ok(())
+    let prev_tx = get_transaction(conn, params, prev_txid)?.map(|(_, tx)| tx);
+    Ok(prev_tx.and_then(|tx| {
+        tx.transparent_bundle()
+            .and_then(|b| b.vout.get(usize::try_from(outpoint.n()).ok()?))
+            .map(|txout| txout.value)
+    }))
 }
 
-/// Adds the given received UTXO to the datastore.
-#[cfg(feature = "transparent-inputs")]
-pub(crate) fn put_received_transparent_utxo<P: consensus::Parameters>(
+/// Computes the fee paid by the given transaction, if the values of all of the transparent
+/// outputs that it spends are known to the wallet.
+///
+/// Returns `None` for coinbase transactions, which do not pay a fee.
+pub(crate) fn compute_fee<P: consensus::Parameters>(
     conn: &rusqlite::Connection,
     params: &P,
-    output: &WalletTransparentOutput,
-) -> Result<UtxoId, SqliteClientError> {
-    let address_str = output.recipient_address().encode(params);
-    let account_id = conn
-        .query_row(
-            "SELECT account_id FROM addresses WHERE cached_transparent_receiver_address = :address",
-            named_params![":address": &address_str],
-            |row| Ok(AccountId(row.get(0)?)),
-        )
-        .optional()?;
+    tx: &Transaction,
+) -> Result<Option<NonNegativeAmount>, SqliteClientError> {
+    if tx.transparent_bundle().map_or(false, |b| b.is_coinbase()) {
+        return Ok(None);
+    }
 
-    if let Some(account) = account_id {
-        Ok(put_legacy_transparent_utxo(conn, params, output, account)?)
-    } else {
-        // If the UTXO is received at the legacy transparent address (at BIP 44 address
-        // index 0 within its particular account, which we specifically ensure is returned
-        // from `get_transparent_receivers`), there may be no entry in the addresses table
-        // that can be used to tie the address to a particular account. In this case, we
-        // look up the legacy address for each account in the wallet, and check whether it
-        // matches the address for the received UTXO; if so, insert/update it directly.
-        get_account_ids(conn)?
-            .into_iter()
-            .find_map(
-                |account| match get_legacy_transparent_address(params, conn, account) {
-                    Ok(Some((legacy_taddr, _))) if &legacy_taddr == output.recipient_address() => {
-                        Some(
-                            put_legacy_transparent_utxo(conn, params, output, account)
-                                .map_err(SqliteClientError::from),
-                        )
-                    }
-                    Ok(_) => None,
-                    Err(e) => Some(Err(e)),
+    let mut all_prevouts_known = true;
+    let fee = tx.fee_paid(|outpoint| {
+        get_transparent_output_value(conn, params, outpoint).map(|value| {
+            value.map_or_else(
+                || {
+                    all_prevouts_known = false;
+                    Amount::zero()
                 },
+                Amount::from,
             )
-            // The UTXO was not for any of the legacy transparent addresses.
-            .unwrap_or_else(|| {
-                Err(SqliteClientError::AddressNotRecognized(
-                    *output.recipient_address(),
-                ))
-            })
+        })
+    })?;
+
+    if all_prevouts_known {
+        NonNegativeAmount::try_from(fee).map(Some).map_err(|_| {
+            SqliteClientError::CorruptedData(format!(
+                "Transaction {} has a negative fee",
+                tx.txid()
+            ))
+        })
+    } else {
+        Ok(None)
     }
 }
 
-#[cfg(feature = "transparent-inputs")]
-pub(crate) fn put_legacy_transparent_utxo<P: consensus::Parameters>(
-    conn: &rusqlite::Connection,
-    params: &P,
-    output: &WalletTransparentOutput,
-    received_by_account: AccountId,
-) -> Result<UtxoId, rusqlite::Error> {
-    #[cfg(feature = "transparent-inputs")]
-    let mut stmt_upsert_legacy_transparent_utxo = conn.prepare_cached(
-        "INSERT INTO utxos (
-            prevout_txid, prevout_idx,
-            received_by_account_id, address, script,
-            value_zat, height)
-        VALUES
-            (:prevout_txid, :prevout_idx,
-            :received_by_account_id, :address, :script,
-            :value_zat, :height)
-        ON CONFLICT (prevout_txid, prevout_idx) DO UPDATE
-        SET received_by_account_id = :received_by_account_id,
-            height = :height,
-            address = :address,
-            script = :script,
-            value_zat = :value_zat
-        RETURNING id",
-    )?;
-
-    let sql_args = named_params![
-        ":prevout_txid": &output.outpoint().hash().to_vec(),
-        ":prevout_idx": &output.outpoint().n(),
-        ":received_by_account_id": received_by_account.0,
-        ":address": &output.recipient_address().encode(params),
-        ":script": &output.txout().script_pubkey.0,
-        ":value_zat": &i64::from(Amount::from(output.txout().value)),
-        ":height": &u32::from(output.height()),
-    ];
+/// Returns whether the given transaction created or spent any of the wallet's notes or
+/// transparent outputs, or sent funds from the wallet.
+fn is_wallet_transaction(conn: &rusqlite::Connection, txid: TxId) -> Result<bool, rusqlite::Error> {
+    conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM v_received_notes WHERE txid = :txid)
+            OR EXISTS (SELECT 1 FROM v_received_note_spends WHERE spending_txid = :txid)
+            OR EXISTS (SELECT 1 FROM utxos WHERE prevout_txid = :txid)
+            OR EXISTS (
+                SELECT 1 FROM transactions
+                JOIN transparent_received_output_spends tros
+                    ON tros.transaction_id = transactions.id_tx
+                WHERE transactions.txid = :txid
+            )
+            OR EXISTS (
+                SELECT 1 FROM transactions
+                JOIN sent_notes ON sent_notes.tx = transactions.id_tx
+                WHERE transactions.txid = :txid
+            )",
+        named_params![":txid": txid.as_ref()],
+        |row| row.get(0),
+    )
+}
 
-    stmt_upsert_legacy_transparent_utxo.query_row(sql_args, |row| row.get::<_, i64>(0).map(UtxoId))
+/// The kinds of request recorded in the `tx_retrieval_queue` table.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum TxQueryType {
+    Status,
+    Enhancement,
 }
 
-// A utility function for creation of parameters for use in `insert_sent_output`
-// and `put_sent_output`
-fn recipient_params(
-    to: &Recipient<AccountId, Note>,
-) -> (Option<String>, Option<AccountId>, PoolType) {
-    match to {
-        Recipient::External(addr, pool) => (Some(addr.encode()), None, *pool),
-        Recipient::InternalAccount {
-            receiving_account,
-            external_address,
-            note,
-        } => (
-            external_address.as_ref().map(|a| a.encode()),
-            Some(*receiving_account),
-            PoolType::Shielded(note.protocol()),
-        ),
+impl TxQueryType {
+    pub(crate) fn code(&self) -> i64 {
+        match self {
+            TxQueryType::Status => 0,
+            TxQueryType::Enhancement => 1,
+        }
+    }
+
+    pub(crate) fn from_code(code: i64) -> Option<Self> {
+        match code {
+            0 => Some(TxQueryType::Status),
+            1 => Some(TxQueryType::Enhancement),
+            _ => None,
+        }
     }
 }
 
-/// Records information about a transaction output that your wallet created.
-pub(crate) fn insert_sent_output(
+/// Updates the queue of transactions to be retrieved, once the full data of the given
+/// transaction has been stored.
+///
+/// Any requests for the transaction's data are removed, and the fees of the transactions that
+/// required it are recomputed. Then, if the transaction belongs to the wallet but its fee
+/// could not be computed, the transactions that created the transparent outputs that it
+/// spends (and whose values are not known to the wallet) are queued for retrieval.
+/// Transactions that are already queued, or whose data has already been stored, are not
+/// queued again.
+pub(crate) fn update_tx_retrieval_queue<P: consensus::Parameters>(
     conn: &rusqlite::Connection,
+    params: &P,
+    tx: &Transaction,
     tx_ref: i64,
-    from_account: AccountId,
-    output: &SentTransactionOutput<AccountId>,
+    fee: Option<NonNegativeAmount>,
 ) -> Result<(), SqliteClientError> {
-    let mut stmt_insert_sent_output = conn.prepare_cached(
-        "INSERT INTO sent_notes (
-            tx, output_pool, output_index, from_account_id,
-            to_address, to_account_id, value, memo)
-        VALUES (
-            :tx, :output_pool, :output_index, :from_account_id,
-            :to_address, :to_account_id, :value, :memo)",
+    let txid = tx.txid();
+
+    let dependents = conn
+        .prepare_cached(
+            "DELETE FROM tx_retrieval_queue
+            WHERE txid = :txid
+            AND query_type = :query_type
+            RETURNING dependent_transaction_id",
+        )?
+        .query_and_then(
+            named_params![
+                ":txid": txid.as_ref(),
+                ":query_type": TxQueryType::Enhancement.code(),
+            ],
+            |row| row.get::<_, i64>(0),
+        )?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for dependent_tx_ref in dependents {
+        let dependent_txid = conn.query_row(
+            "SELECT txid FROM transactions WHERE id_tx = :id_tx",
+            named_params![":id_tx": dependent_tx_ref],
+            |row| row.get::<_, [u8; 32]>(0).map(TxId::from_bytes),
+        )?;
+        if let Some((_, dependent_tx)) = get_transaction(conn, params, dependent_txid)? {
+            if let Some(fee) = compute_fee(conn, params, &dependent_tx)? {
+                conn.execute(
+                    "UPDATE transactions SET fee = :fee WHERE id_tx = :id_tx",
+                    named_params![":fee": u64::from(fee), ":id_tx": dependent_tx_ref],
+                )?;
+            }
+        }
+    }
+
+    if fee.is_some() || !is_wallet_transaction(conn, txid)? {
+        return Ok(());
+    }
+
+    let mut stmt_queue = conn.prepare_cached(
+        "INSERT INTO tx_retrieval_queue (txid, query_type, dependent_transaction_id)
+        SELECT :txid, :query_type, :dependent_transaction_id
+        WHERE NOT EXISTS (
+            SELECT 1 FROM transactions WHERE txid = :txid AND raw IS NOT NULL
+        )
+        ON CONFLICT (txid, query_type, dependent_transaction_id) DO NOTHING",
+    )?;
+    for txin in tx.transparent_bundle().iter().flat_map(|b| b.vin.iter()) {
+        if get_transparent_output_value(conn, params, &txin.prevout)?.is_none() {
+            stmt_queue.execute(named_params![
+                ":txid": &txin.prevout.hash()[..],
+                ":query_type": TxQueryType::Enhancement.code(),
+                ":dependent_transaction_id": tx_ref,
+            ])?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Queues a request for the status of the given transaction, which was created by the wallet.
+pub(crate) fn queue_tx_status_request(
+    conn: &rusqlite::Connection,
+    txid: TxId,
+    tx_ref: i64,
+) -> Result<(), SqliteClientError> {
+    conn.execute(
+        "INSERT INTO tx_retrieval_queue (txid, query_type, dependent_transaction_id)
+        VALUES (:txid, :query_type, :dependent_transaction_id)
+        ON CONFLICT (txid, query_type, dependent_transaction_id) DO NOTHING",
+        named_params![
+            ":txid": txid.as_ref(),
+            ":query_type": TxQueryType::Status.code(),
+            ":dependent_transaction_id": tx_ref,
+        ],
+    )?;
+
+    Ok(())
+}
+
+/// Records the height at which the given transaction was mined, if the block at that height
+/// has been scanned.
+///
+/// Otherwise, the mined height of the transaction will be recorded when that block is scanned.
+pub(crate) fn set_tx_mined_height(
+    conn: &rusqlite::Connection,
+    txid: TxId,
+    height: BlockHeight,
+) -> Result<(), SqliteClientError> {
+    conn.execute(
+        "UPDATE transactions SET block = :height
+        WHERE txid = :txid
+        AND EXISTS (SELECT 1 FROM blocks WHERE height = :height)",
+        named_params![":txid": txid.as_ref(), ":height": u32::from(height)],
+    )?;
+
+    Ok(())
+}
+
+/// Records the status of a transaction, as reported by the network.
+///
+/// The mined height of a transaction can only be recorded once the block at that height has
+/// been scanned; until then, the transaction is treated as unmined, and its mined height will
+/// be recorded when the block is scanned. The status of a transaction that has not been mined
+/// in the main chain continues to be requested until the transaction expires.
+pub(crate) fn set_transaction_status(
+    conn: &rusqlite::Transaction,
+    txid: TxId,
+    status: TransactionStatus,
+) -> Result<(), SqliteClientError> {
+    match status {
+        TransactionStatus::TxidNotRecognized | TransactionStatus::NotInMainChain => {
+            conn.execute(
+                "UPDATE transactions SET block = NULL, tx_index = NULL WHERE txid = :txid",
+                named_params![":txid": txid.as_ref()],
+            )?;
+        }
+        TransactionStatus::Mined(height) => {
+            set_tx_mined_height(conn, txid, height)?;
+        }
+    }
+
+    // A transaction that has not been mined may yet be, until it expires.
+    let still_pending = match status {
+        TransactionStatus::TxidNotRecognized | TransactionStatus::NotInMainChain => {
+            let chain_tip = scan_queue_extrema(conn)?.map(|range| *range.end());
+            let expiry_height = conn
+                .query_row(
+                    "SELECT expiry_height FROM transactions WHERE txid = :txid",
+                    named_params![":txid": txid.as_ref()],
+                    |row| row.get::<_, Option<u32>>(0),
+                )
+                .optional()?
+                .flatten()
+                .map(BlockHeight::from);
+
+            // An expiry height of zero means that the transaction does not expire.
+            match (expiry_height, chain_tip) {
+                (Some(expiry), Some(tip)) => expiry == BlockHeight::from(0) || expiry > tip,
+                _ => true,
+            }
+        }
+        TransactionStatus::Mined(_) => false,
+    };
+
+    if !still_pending {
+        conn.execute(
+            "DELETE FROM tx_retrieval_queue
+            WHERE txid = :txid
+            AND query_type = :query_type",
+            named_params![
+                ":txid": txid.as_ref(),
+                ":query_type": TxQueryType::Status.code(),
+            ],
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Returns the outstanding requests for transaction data.
+///
+/// In addition to the requests recorded in the `tx_retrieval_queue` table, the transactions
+/// involving each transparent address at which the wallet holds unspent outputs are requested,
+/// so that spends of those outputs are detected. The request for each address starts at the
+/// height of the earliest such output, or after the height to which the address's history has
+/// already been checked (see [`notify_address_checked`]) if that is greater, and is omitted if
+/// the address has been checked up to the chain tip.
+pub(crate) fn transaction_data_requests<P: consensus::Parameters>(
+    conn: &rusqlite::Connection,
+    _params: &P,
+) -> Result<Vec<TransactionDataRequest>, SqliteClientError> {
+    let mut stmt = conn
+        .prepare_cached("SELECT DISTINCT txid, query_type FROM tx_retrieval_queue ORDER BY txid")?;
+    #[allow(unused_mut)]
+    let mut requests = stmt
+        .query_and_then([], |row| {
+            let txid = TxId::from_bytes(row.get::<_, [u8; 32]>(0)?);
+            let query_type = row.get::<_, i64>(1)?;
+            match TxQueryType::from_code(query_type) {
+                Some(TxQueryType::Status) => Ok(TransactionDataRequest::GetStatus(txid)),
+                Some(TxQueryType::Enhancement) => Ok(TransactionDataRequest::Enhancement(txid)),
+                None => Err(SqliteClientError::CorruptedData(format!(
+                    "Unrecognized transaction query type {}",
+                    query_type
+                ))),
+            }
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    #[cfg(feature = "transparent-inputs")]
+    {
+        let chain_tip_height = scan_queue_extrema(conn)?.map(|range| *range.end());
+
+        let mut stmt = conn.prepare_cached(
+            "SELECT u.address, MIN(u.height), c.checked_height
+            FROM utxos u
+            LEFT OUTER JOIN transparent_address_checks c ON c.address = u.address
+            WHERE u.id NOT IN (
+                SELECT transparent_received_output_id
+                FROM transparent_received_output_spends
+            )
+            GROUP BY u.address
+            ORDER BY u.address",
+        )?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let address = TransparentAddress::decode(_params, &row.get::<_, String>(0)?)?;
+            let earliest_utxo_height = BlockHeight::from(row.get::<_, u32>(1)?);
+            let checked_height = row.get::<_, Option<u32>>(2)?.map(BlockHeight::from);
+
+            // The address has already been checked up to the chain tip.
+            if let (Some(checked), Some(tip)) = (checked_height, chain_tip_height) {
+                if checked >= tip {
+                    continue;
+                }
+            }
+
+            requests.push(TransactionDataRequest::SpendsFromAddress {
+                address,
+                block_range_start: checked_height.map_or(earliest_utxo_height, |checked| {
+                    std::cmp::max(earliest_utxo_height, checked + 1)
+                }),
+                block_range_end: None,
+            });
+        }
+    }
+
+    Ok(requests)
+}
+
+/// Records that the transactions involving the given transparent address have been retrieved
+/// for all blocks up to and including `as_of_height`.
+#[cfg(feature = "transparent-inputs")]
+pub(crate) fn notify_address_checked<P: consensus::Parameters>(
+    conn: &rusqlite::Connection,
+    params: &P,
+    address: &TransparentAddress,
+    as_of_height: BlockHeight,
+) -> Result<(), SqliteClientError> {
+    conn.execute(
+        "INSERT INTO transparent_address_checks (address, checked_height)
+        VALUES (:address, :checked_height)
+        ON CONFLICT (address) DO UPDATE
+        SET checked_height = MAX(checked_height, excluded.checked_height)",
+        named_params![
+            ":address": address.encode(params),
+            ":checked_height": u32::from(as_of_height),
+        ],
+    )?;
+
+    Ok(())
+}
+
+/// Marks the given UTXO as having been spent.
+#[cfg(feature = "transparent-inputs")]
+pub(crate) fn mark_transparent_utxo_spent(
+    conn: &rusqlite::Connection,
+    tx_ref: i64,
+    outpoint: &OutPoint,
+) -> Result<(), SqliteClientError> {
+    let mut stmt_mark_transparent_utxo_spent = conn.prepare_cached(
+        "INSERT INTO transparent_received_output_spends (transparent_received_output_id, transaction_id)
+         SELECT txo.id, :spent_in_tx
+         FROM utxos txo
+         WHERE txo.prevout_txid = :prevout_txid
+         AND txo.prevout_idx = :prevout_idx
+         ON CONFLICT (transparent_received_output_id, transaction_id) DO NOTHING",
     )?;
 
-    let (to_address, to_account_id, pool_type) = recipient_params(output.recipient());
     let sql_args = named_params![
-        ":tx": &tx_ref,
-        ":output_pool": &pool_code(pool_type),
-        ":output_index": &i64::try_from(output.output_index()).unwrap(),
-        ":from_account_id": from_account.0,
-        ":to_address": &to_address,
-        ":to_account_id": to_account_id.map(|a| a.0),
-        ":value": &i64::from(Amount::from(output.value())),
-        ":memo": memo_repr(output.memo())
+        ":spent_in_tx": &tx_ref,
+        ":prevout_txid": &outpoint.hash().to_vec(),
+        ":prevout_idx": &outpoint.n(),
     ];
 
-    stmt_insert_sent_output.execute(sql_args)?;
-
+    stmt_mark_transparent_utxo_spent.execute(sql_args)?;
     Ok(())
 }
 
+/// Returns the account that controls the given transparent address, if it belongs to the
+/// wallet.
+#[cfg(feature = "transparent-inputs")]
+pub(crate) fn find_account_for_transparent_address<P: consensus::Parameters>(
+    conn: &rusqlite::Connection,
+    params: &P,
+    address: &TransparentAddress,
+) -> Result<Option<AccountId>, SqliteClientError> {
+    let account_id = conn
+        .query_row(
+            "SELECT account_id FROM addresses WHERE cached_transparent_receiver_address = :address",
+            named_params![":address": &address.encode(params)],
+            |row| row.get(0),
+        )
+        .optional()?;
+
+    if account_id.is_some() {
+        Ok(account_id)
+    } else {
+        // If the address is the legacy transparent address (at BIP 44 address index 0
+        // within its particular account, which we specifically ensure is returned from
+        // `get_transparent_receivers`), there may be no entry in the addresses table that
+        // can be used to tie the address to a particular account. In this case, we look up
+        // the legacy address for each account in the wallet, and check whether it matches
+        // the given address.
+        for account in get_account_ids(conn)? {
+            if let Some((legacy_taddr, _)) = get_legacy_transparent_address(params, conn, account)?
+            {
+                if &legacy_taddr == address {
+                    return Ok(Some(account));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// Adds the given received UTXO to the datastore.
+#[cfg(feature = "transparent-inputs")]
+pub(crate) fn put_received_transparent_utxo<P: consensus::Parameters>(
+    conn: &rusqlite::Connection,
+    params: &P,
+    output: &WalletTransparentOutput,
+) -> Result<UtxoId, SqliteClientError> {
+    // Sources such as the light wallet server's `GetAddressUtxos` method do not report whether
+    // an output was created by a coinbase transaction, so check whether the wallet knows the
+    // transaction to be one.
+    let output = if output.is_coinbase() {
+        output.clone()
+    } else {
+        let txid = TxId::from_bytes(*output.outpoint().hash());
+        output
+            .clone()
+            .with_coinbase(is_coinbase_transaction(conn, params, txid)?)
+    };
+
+    match find_account_for_transparent_address(conn, params, output.recipient_address())? {
+        Some(account) => Ok(put_legacy_transparent_utxo(conn, params, &output, account)?),
+        None => Err(SqliteClientError::AddressNotRecognized(
+            *output.recipient_address(),
+        )),
+    }
+}
+
+/// Returns whether the wallet knows the transaction with the given ID to be a coinbase
+/// transaction, either because it is the first transaction of its block or from its raw data.
+#[cfg(feature = "transparent-inputs")]
+fn is_coinbase_transaction<P: consensus::Parameters>(
+    conn: &rusqlite::Connection,
+    params: &P,
+    txid: TxId,
+) -> Result<bool, SqliteClientError> {
+    let tx_info = conn
+        .query_row(
+            "SELECT tx_index, raw IS NOT NULL FROM transactions WHERE txid = :txid",
+            named_params![":txid": txid.as_ref()],
+            |row| Ok((row.get::<_, Option<u32>>(0)?, row.get::<_, bool>(1)?)),
+        )
+        .optional()?;
+
+    match tx_info {
+        Some((Some(0), _)) => Ok(true),
+        Some((_, true)) => Ok(
+            get_transaction(conn, params, txid)?.map_or(false, |(_, tx)| {
+                tx.transparent_bundle().map_or(false, |b| b.is_coinbase())
+            }),
+        ),
+        _ => Ok(false),
+    }
+}
+
+#[cfg(feature = "transparent-inputs")]
+pub(crate) fn put_legacy_transparent_utxo<P: consensus::Parameters>(
+    conn: &rusqlite::Connection,
+    params: &P,
+    output: &WalletTransparentOutput,
+    received_by_account: AccountId,
+) -> Result<UtxoId, rusqlite::Error> {
+    #[cfg(feature = "transparent-inputs")]
+    let mut stmt_upsert_legacy_transparent_utxo = conn.prepare_cached(
+        "INSERT INTO utxos (
+            prevout_txid, prevout_idx,
+            received_by_account_id, address, script,
+            value_zat, height, is_coinbase)
+        VALUES
+            (:prevout_txid, :prevout_idx,
+            :received_by_account_id, :address, :script,
+            :value_zat, :height, :is_coinbase)
+        ON CONFLICT (prevout_txid, prevout_idx) DO UPDATE
+        SET received_by_account_id = :received_by_account_id,
+            height = :height,
+            address = :address,
+            script = :script,
+            value_zat = :value_zat,
+            -- A source that does not know whether the output is a coinbase output must not
+            -- clear a previously recorded flag.
+            is_coinbase = MAX(is_coinbase, :is_coinbase)
+        RETURNING id",
+    )?;
+
+    let sql_args = named_params![
+        ":prevout_txid": &output.outpoint().hash().to_vec(),
+        ":prevout_idx": &output.outpoint().n(),
+        ":received_by_account_id": received_by_account,
+        ":address": &output.recipient_address().encode(params),
+        ":script": &output.txout().script_pubkey.0,
+        ":value_zat": &i64::from(Amount::from(output.txout().value)),
+        ":height": &u32::from(output.height()),
+        ":is_coinbase": output.is_coinbase(),
+    ];
+
+    let utxo_id = stmt_upsert_legacy_transparent_utxo
+        .query_row(sql_args, |row| row.get::<_, i64>(0).map(UtxoId))?;
+
+    record_address_use(
+        conn,
+        "cached_transparent_receiver_address",
+        &output.recipient_address().encode(params),
+        output.height(),
+    )?;
+
+    Ok(utxo_id)
+}
+
+// A utility function for creation of parameters for use in `put_sent_output`
+fn recipient_params(
+    to: &Recipient<AccountId, Note>,
+) -> (Option<String>, Option<AccountId>, PoolType) {
+    match to {
+        Recipient::External(addr, pool) => (Some(addr.encode()), None, *pool),
+        Recipient::InternalAccount {
+            receiving_account,
+            external_address,
+            note,
+        } => (
+            external_address.as_ref().map(|a| a.encode()),
+            Some(*receiving_account),
+            PoolType::Shielded(note.protocol()),
+        ),
+    }
+}
+
 /// Records information about a transaction output that your wallet created, from the constituent
 /// properties of that output.
 ///
+/// This is used both when the wallet constructs a transaction and when it later decrypts the
+/// transaction's outputs, in either order; an existing record for the output is updated rather
+/// than duplicated. Change outputs are recorded with a `to_account_id` equal to `from_account`.
+///
 /// - If `recipient` is a Unified address, `output_index` is an index into the outputs of the
 ///   transaction within the bundle associated with the recipient's output pool.
 /// - If `recipient` is a Sapling address, `output_index` is an index into the Sapling outputs of
@@ -2652,9 +4413,9 @@ pub(crate) fn put_sent_output(
         ":tx": &tx_ref,
         ":output_pool": &pool_code(pool_type),
         ":output_index": &i64::try_from(output_index).unwrap(),
-        ":from_account_id": from_account.0,
+        ":from_account_id": from_account,
         ":to_address": &to_address,
-        ":to_account_id": &to_account_id.map(|a| a.0),
+        ":to_account_id": to_account_id,
         ":value": &i64::from(Amount::from(value)),
         ":memo": memo_repr(memo)
     ];
@@ -2791,6 +4552,18 @@ pub(crate) fn query_nullifier_map<N: AsRef<[u8]>, S>(
         None => return Ok(None),
     };
 
+    // The locator may have been obtained via `put_block_nullifiers` for a block that has
+    // not yet been scanned, in which case we cannot yet record the transaction's mined
+    // height.
+    let block_scanned = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM blocks WHERE height = :height)",
+        named_params![":height": u32::from(height)],
+        |row| row.get::<_, bool>(0),
+    )?;
+    if !block_scanned {
+        return put_tx_locator(conn, &txid, index, height).map(Some);
+    }
+
     // Find or create a corresponding row in the `transactions` table. Usually a row will
     // have been created during the same scan that the locator was added to the nullifier
     // map, but it would not happen if the transaction in question spent the note with no
@@ -2806,23 +4579,127 @@ pub(crate) fn query_nullifier_map<N: AsRef<[u8]>, S>(
             vec![],
             #[cfg(feature = "orchard")]
             vec![],
+            #[cfg(feature = "transparent-inputs")]
+            vec![],
         ),
         height,
     )
     .map(Some)
 }
 
-/// Deletes from the nullifier map any entries with a locator referencing a block height
-/// lower than the pruning height.
-pub(crate) fn prune_nullifier_map(
-    conn: &rusqlite::Transaction<'_>,
-    block_height: BlockHeight,
-) -> Result<(), SqliteClientError> {
-    let mut stmt_delete_locators = conn.prepare_cached(
-        "DELETE FROM tx_locator_map
-        WHERE block_height < :block_height",
-    )?;
-
+/// Finds or creates the row of the `transactions` table for the given transaction, which was
+/// observed at `observed_height` in a block that has not yet been scanned, without modifying
+/// its mined height, and returns its identifier.
+///
+/// A row that is created here, or that otherwise exists only because of such an observation,
+/// is flagged with the observed height so that [`truncate_to_height`] can remove it, along with
+/// the spends that it records, if the block is rolled back before it is scanned.
+fn put_tx_locator(
+    conn: &rusqlite::Connection,
+    txid: &TxId,
+    tx_index: u16,
+    observed_height: BlockHeight,
+) -> Result<i64, SqliteClientError> {
+    let mut stmt_upsert_tx_locator = conn.prepare_cached(
+        "INSERT INTO transactions (txid, tx_index, lookahead_height)
+        VALUES (:txid, :tx_index, :observed_height)
+        ON CONFLICT (txid) DO UPDATE
+        SET tx_index = :tx_index,
+            lookahead_height = CASE
+                WHEN block IS NULL AND expiry_height IS NULL AND created IS NULL AND raw IS NULL
+                THEN :observed_height
+                ELSE lookahead_height
+            END
+        RETURNING id_tx",
+    )?;
+
+    stmt_upsert_tx_locator
+        .query_row(
+            named_params![
+                ":txid": txid.as_ref(),
+                ":tx_index": tx_index,
+                ":observed_height": u32::from(observed_height),
+            ],
+            |row| row.get::<_, i64>(0),
+        )
+        .map_err(SqliteClientError::from)
+}
+
+/// Records the nullifiers revealed in the given blocks in the nullifier map, and marks as
+/// spent any of the wallet's unspent notes whose nullifiers are among them.
+///
+/// The blocks need not have been scanned. A transaction in which a spend is detected is
+/// recorded without its mined height; this is filled in when the block containing it is
+/// scanned. If the block is instead rolled back by [`truncate_to_height`], the transaction and
+/// the spends that it records are removed.
+pub(crate) fn put_block_nullifiers(
+    conn: &rusqlite::Transaction<'_>,
+    blocks: &[BlockNullifiers],
+) -> Result<(), SqliteClientError> {
+    let sapling_unspent = sapling::get_sapling_nullifiers(conn, NullifierQuery::Unspent)?
+        .into_iter()
+        .map(|(_, nf)| nf.0)
+        .collect::<HashSet<_>>();
+    #[cfg(feature = "orchard")]
+    let orchard_unspent = orchard::get_orchard_nullifiers(conn, NullifierQuery::Unspent)?
+        .into_iter()
+        .map(|(_, nf)| nf.to_bytes())
+        .collect::<HashSet<_>>();
+
+    for block in blocks {
+        insert_nullifier_map(
+            conn,
+            block.block_height(),
+            ShieldedProtocol::Sapling,
+            block.sapling(),
+        )?;
+        for (txid, tx_index, nfs) in block.sapling() {
+            for nf in nfs.iter().filter(|nf| sapling_unspent.contains(&nf.0)) {
+                let tx_ref = put_tx_locator(conn, txid, *tx_index, block.block_height())?;
+                sapling::mark_sapling_note_spent(conn, tx_ref, nf)?;
+            }
+        }
+
+        #[cfg(feature = "orchard")]
+        {
+            insert_nullifier_map(
+                conn,
+                block.block_height(),
+                ShieldedProtocol::Orchard,
+                &block
+                    .orchard()
+                    .iter()
+                    .map(|(txid, idx, nfs)| {
+                        (*txid, *idx, nfs.iter().map(|nf| nf.to_bytes()).collect())
+                    })
+                    .collect::<Vec<_>>(),
+            )?;
+            for (txid, tx_index, nfs) in block.orchard() {
+                for nf in nfs
+                    .iter()
+                    .filter(|nf| orchard_unspent.contains(&nf.to_bytes()))
+                {
+                    let tx_ref = put_tx_locator(conn, txid, *tx_index, block.block_height())?;
+                    orchard::mark_orchard_note_spent(conn, tx_ref, nf)?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Deletes from the nullifier map any entries with a locator referencing a block height
+/// lower than the pruning height.
+pub(crate) fn prune_nullifier_map(
+    conn: &rusqlite::Transaction<'_>,
+    block_height: BlockHeight,
+) -> Result<(), SqliteClientError> {
+    let mut stmt_delete_locators = conn.prepare_cached(
+        "DELETE FROM tx_locator_map
+        WHERE block_height < :block_height",
+    )?;
+
     stmt_delete_locators.execute(named_params![":block_height": u32::from(block_height)])?;
 
     Ok(())
@@ -2855,7 +4732,9 @@ mod tests {
         },
         zcash_primitives::{
             consensus::BlockHeight,
+            legacy::TransparentAddress,
             transaction::{
+                builder::BuildResult,
                 components::{OutPoint, TxOut},
                 fees::fixed::FeeRule as FixedFeeRule,
             },
@@ -2966,32 +4845,506 @@ mod tests {
             st.wallet().get_unspent_transparent_output(utxo2.outpoint()),
             Ok(Some(ret)) if (ret.outpoint(), ret.txout(), ret.height()) == (utxo2.outpoint(), utxo2.txout(), height_2)
         );
-
-        // If we include `height_2` then the output is returned.
-        assert_matches!(
+
+        // If we include `height_2` then the output is returned.
+        assert_matches!(
+            st.wallet()
+                .get_unspent_transparent_outputs(taddr, height_2, &[])
+                .as_deref(),
+            Ok(&[ref ret]) if (ret.outpoint(), ret.txout(), ret.height()) == (utxo.outpoint(), utxo.txout(), height_2)
+        );
+
+        assert_matches!(
+            st.wallet().get_transparent_balances(account_id, height_2),
+            Ok(h) if h.get(taddr) == Some(&value)
+        );
+
+        // Artificially delete the address from the addresses table so that
+        // we can ensure the update fails if the join doesn't work.
+        st.wallet()
+            .conn
+            .execute(
+                "DELETE FROM addresses WHERE cached_transparent_receiver_address = ?",
+                [Some(taddr.encode(&st.wallet().params))],
+            )
+            .unwrap();
+
+        let res2 = st.wallet_mut().put_received_transparent_utxo(&utxo2);
+        assert_matches!(res2, Err(_));
+    }
+
+    #[test]
+    #[cfg(feature = "transparent-inputs")]
+    fn put_received_transparent_utxo_detects_coinbase() {
+        let mut st = TestBuilder::new()
+            .with_account_from_sapling_activation(BlockHash([0; 32]))
+            .build();
+
+        let account_id = st.test_account().unwrap().account_id();
+        let uaddr = st
+            .wallet()
+            .get_current_address(account_id)
+            .unwrap()
+            .unwrap();
+        let taddr = uaddr.transparent().unwrap();
+        let height = BlockHeight::from_u32(12345);
+        let txout = TxOut {
+            value: NonNegativeAmount::const_from_u64(100000),
+            script_pubkey: taddr.script(),
+        };
+
+        // The wallet knows the first transaction of a block, which is its coinbase transaction.
+        st.wallet()
+            .conn
+            .execute(
+                "INSERT INTO transactions (txid, tx_index) VALUES (?, 0)",
+                [&[2u8; 32][..]],
+            )
+            .unwrap();
+
+        // An output whose source does not report the coinbase flag is recorded as a coinbase
+        // output if it was created by a known coinbase transaction.
+        for (txid, expected) in [([1u8; 32], false), ([2u8; 32], true)] {
+            let utxo =
+                WalletTransparentOutput::from_parts(OutPoint::new(txid, 0), txout.clone(), height)
+                    .unwrap();
+            assert!(!utxo.is_coinbase());
+            st.wallet_mut()
+                .put_received_transparent_utxo(&utxo)
+                .unwrap();
+            assert_matches!(
+                st.wallet().get_unspent_transparent_output(utxo.outpoint()),
+                Ok(Some(ret)) if ret.is_coinbase() == expected
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "transparent-inputs")]
+    fn put_utxo() {
+        use crate::error::SqliteClientError;
+        use zcash_primitives::legacy::{Script, TransparentAddress};
+
+        let mut st = TestBuilder::new()
+            .with_account_from_sapling_activation(BlockHash([0; 32]))
+            .build();
+
+        let account_id = st.test_account().unwrap().account_id();
+        let uaddr = st
+            .wallet()
+            .get_current_address(account_id)
+            .unwrap()
+            .unwrap();
+        let taddr = *uaddr.transparent().unwrap();
+        let height = BlockHeight::from_u32(12345);
+        let value = NonNegativeAmount::const_from_u64(100000);
+
+        // An output paying to one of the wallet's addresses contributes to its balance.
+        let res = st.wallet_mut().put_utxo(
+            OutPoint::new([1u8; 32], 1),
+            TxOut {
+                value,
+                script_pubkey: taddr.script(),
+            },
+            height,
+        );
+        assert_matches!(res, Ok(_));
+        assert_matches!(
+            st.wallet().get_transparent_balances(account_id, height),
+            Ok(h) if h.get(&taddr) == Some(&value)
+        );
+
+        // An output paying to an address that does not belong to the wallet is rejected.
+        let other_taddr = TransparentAddress::PublicKeyHash([7; 20]);
+        assert_matches!(
+            st.wallet_mut().put_utxo(
+                OutPoint::new([2u8; 32], 0),
+                TxOut {
+                    value,
+                    script_pubkey: other_taddr.script(),
+                },
+                height,
+            ),
+            Err(SqliteClientError::AddressNotRecognized(addr)) if addr == other_taddr
+        );
+
+        // An output whose script does not pay to an address is rejected.
+        assert_matches!(
+            st.wallet_mut().put_utxo(
+                OutPoint::new([3u8; 32], 0),
+                TxOut {
+                    value,
+                    script_pubkey: Script(vec![0x6a]),
+                },
+                height,
+            ),
+            Err(SqliteClientError::UnsupportedTransparentScript)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "transparent-inputs")]
+    fn address_history_is_requested_after_checked_height() {
+        use zcash_client_backend::data_api::TransactionDataRequest;
+        use zcash_primitives::consensus::{NetworkUpgrade, Parameters};
+
+        let mut st = TestBuilder::new()
+            .with_account_from_sapling_activation(BlockHash([0; 32]))
+            .build();
+
+        let account_id = st.test_account().unwrap().account_id();
+        let uaddr = st
+            .wallet()
+            .get_current_address(account_id)
+            .unwrap()
+            .unwrap();
+        let taddr = *uaddr.transparent().unwrap();
+        let height = st
+            .network()
+            .activation_height(NetworkUpgrade::Sapling)
+            .unwrap()
+            + 1;
+
+        st.wallet_mut()
+            .put_utxo(
+                OutPoint::new([1u8; 32], 0),
+                TxOut {
+                    value: NonNegativeAmount::const_from_u64(100000),
+                    script_pubkey: taddr.script(),
+                },
+                height,
+            )
+            .unwrap();
+        st.wallet_mut().update_chain_tip(height + 10).unwrap();
+
+        let spends_request = |st: &TestState<_>| {
+            st.wallet()
+                .transaction_data_requests()
+                .unwrap()
+                .into_iter()
+                .find_map(|req| match req {
+                    TransactionDataRequest::SpendsFromAddress {
+                        address,
+                        block_range_start,
+                        block_range_end,
+                    } if address == taddr => {
+                        assert_eq!(block_range_end, None);
+                        Some(block_range_start)
+                    }
+                    _ => None,
+                })
+        };
+
+        // Until the address has been checked, its history is requested from the height of
+        // its earliest unspent output.
+        assert_eq!(spends_request(&st), Some(height));
+
+        // Once part of the history has been checked, only the remainder is requested.
+        st.wallet_mut()
+            .notify_address_checked(&taddr, height + 5)
+            .unwrap();
+        assert_eq!(spends_request(&st), Some(height + 6));
+
+        // A notification for an earlier height does not move the checked height back.
+        st.wallet_mut()
+            .notify_address_checked(&taddr, height + 2)
+            .unwrap();
+        assert_eq!(spends_request(&st), Some(height + 6));
+
+        // Nothing is requested once the address has been checked up to the chain tip.
+        st.wallet_mut()
+            .notify_address_checked(&taddr, height + 10)
+            .unwrap();
+        assert_eq!(spends_request(&st), None);
+
+        // Blocks above the truncation height must be checked again.
+        st.wallet_mut().truncate_to_height(height + 3).unwrap();
+        st.wallet_mut().update_chain_tip(height + 10).unwrap();
+        assert_eq!(spends_request(&st), Some(height + 4));
+    }
+
+    #[test]
+    fn address_usage_is_tracked_during_scanning() {
+        use crate::DEFAULT_UA_REQUEST;
+        use zcash_client_backend::data_api::WalletWrite;
+
+        let mut st = TestBuilder::new()
+            .with_block_cache()
+            .with_account_from_sapling_activation(BlockHash([0; 32]))
+            .build();
+
+        let account = st.test_account().cloned().unwrap();
+        let dfvk = account.usk().sapling().to_diversifiable_full_viewing_key();
+        let not_our_key = ExtendedSpendingKey::master(&[]).to_diversifiable_full_viewing_key();
+        let value = NonNegativeAmount::const_from_u64(10000);
+
+        // Generate a second address, which is never used.
+        st.wallet_mut()
+            .get_next_available_address(account.account_id(), DEFAULT_UA_REQUEST)
+            .unwrap()
+            .unwrap();
+
+        let (h1, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+        st.generate_next_block(&not_our_key, AddressType::DefaultExternal, value);
+        let (h3, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+        st.scan_cached_blocks(h1, 3);
+
+        let usage = st.wallet().address_usage(account.account_id()).unwrap();
+        assert_eq!(usage.len(), 2);
+        assert_eq!(usage[0].first_used_height(), Some(h1));
+        assert_eq!(usage[0].last_used_height(), Some(h3));
+        assert!(!usage[1].is_used());
+    }
+
+    #[test]
+    fn address_usage_is_truncated() {
+        use zcash_client_backend::data_api::WalletWrite;
+
+        let mut st = TestBuilder::new()
+            .with_block_cache()
+            .with_account_from_sapling_activation(BlockHash([0; 32]))
+            .build();
+
+        let account = st.test_account().cloned().unwrap();
+        let dfvk = account.usk().sapling().to_diversifiable_full_viewing_key();
+        let not_our_key = ExtendedSpendingKey::master(&[]).to_diversifiable_full_viewing_key();
+        let value = NonNegativeAmount::const_from_u64(10000);
+
+        let (h1, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+        let (h2, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+        let (h3, _, _) = st.generate_next_block(&not_our_key, AddressType::DefaultExternal, value);
+        let (h4, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+        st.scan_cached_blocks(h1, 4);
+
+        let used_heights = |st: &TestState<BlockCache>| {
+            let usage = st.wallet().address_usage(account.account_id()).unwrap();
+            (usage[0].first_used_height(), usage[0].last_used_height())
+        };
+        assert_eq!(used_heights(&st), (Some(h1), Some(h4)));
+
+        // The last use at or below the truncation height is recovered from the received notes.
+        st.wallet_mut().truncate_to_height(h3).unwrap();
+        assert_eq!(used_heights(&st), (Some(h1), Some(h2)));
+
+        // An address that was first used above the truncation height is no longer used.
+        st.wallet_mut().truncate_to_height(h1 - 1).unwrap();
+        assert_eq!(used_heights(&st), (None, None));
+    }
+
+    #[test]
+    fn backup_status_reports_value_received_since_backup() {
+        use zcash_client_backend::data_api::WalletWrite;
+
+        use crate::error::SqliteClientError;
+
+        let mut st = TestBuilder::new()
+            .with_block_cache()
+            .with_account_from_sapling_activation(BlockHash([0; 32]))
+            .build();
+
+        let account = st.test_account().cloned().unwrap();
+        let account_id = account.account_id();
+        let dfvk = account.usk().sapling().to_diversifiable_full_viewing_key();
+        let value = NonNegativeAmount::const_from_u64(10000);
+
+        let (h1, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+        st.scan_cached_blocks(h1, 1);
+
+        let status = st.wallet().backup_status(account_id).unwrap();
+        assert_eq!(status.verified_height(), None);
+        assert_eq!(status.unbacked_up_value(), value);
+
+        st.wallet_mut().update_chain_tip(h1).unwrap();
+        assert_eq!(
+            st.wallet_mut().record_backup_verified(account_id).unwrap(),
+            h1
+        );
+        let status = st.wallet().backup_status(account_id).unwrap();
+        assert_eq!(status.verified_height(), Some(h1));
+        assert_eq!(status.unbacked_up_value(), NonNegativeAmount::ZERO);
+
+        let (h2, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+        st.scan_cached_blocks(h2, 1);
+        assert_eq!(
+            st.wallet()
+                .backup_status(account_id)
+                .unwrap()
+                .unbacked_up_value(),
+            value
+        );
+
+        assert_matches!(
+            st.wallet().backup_status(AccountId(account_id.0 + 1)),
+            Err(SqliteClientError::AccountUnknown)
+        );
+    }
+
+    #[test]
+    fn event_journal_records_sync_events() {
+        use crate::wallet::journal::WalletEventKind;
+
+        let mut st = TestBuilder::new()
+            .with_block_cache()
+            .with_account_from_sapling_activation(BlockHash([0; 32]))
+            .build();
+
+        let account = st.test_account().cloned().unwrap();
+        let dfvk = account.usk().sapling().to_diversifiable_full_viewing_key();
+        let not_our_key = ExtendedSpendingKey::master(&[]).to_diversifiable_full_viewing_key();
+        let value = NonNegativeAmount::const_from_u64(10000);
+
+        // Nothing is recorded until the journal is enabled.
+        let (h1, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+        st.scan_cached_blocks(h1, 1);
+        assert!(!st.wallet().is_event_journal_enabled().unwrap());
+        assert!(st.wallet().export_event_journal(None).unwrap().is_empty());
+
+        st.wallet_mut().set_event_journal_enabled(true).unwrap();
+        assert!(st.wallet().is_event_journal_enabled().unwrap());
+
+        let (h2, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+        st.generate_next_block(&not_our_key, AddressType::DefaultExternal, value);
+        st.scan_cached_blocks(h2, 2);
+        st.wallet_mut().rescan_from(h2).unwrap();
+
+        let events = st.wallet().export_event_journal(None).unwrap();
+        assert_eq!(
+            events.iter().map(|e| e.kind()).collect::<Vec<_>>(),
+            vec![
+                WalletEventKind::JournalEnabled,
+                WalletEventKind::NotesDetected,
+                WalletEventKind::BlocksScanned,
+                WalletEventKind::Rewind,
+                WalletEventKind::Rescan,
+            ]
+        );
+        assert_eq!(events[1].start_height(), Some(h2));
+        assert!(events[1].txid().is_some());
+        assert_eq!(
+            (events[2].start_height(), events[2].end_height()),
+            (Some(h2), Some(h2 + 2))
+        );
+        assert_eq!(events[3].start_height(), Some(h2));
+        assert_eq!(events[4].start_height(), Some(h2));
+        assert!(events.windows(2).all(|w| w[0].id() < w[1].id()));
+
+        // Disabling the journal retains the existing events.
+        st.wallet_mut().set_event_journal_enabled(false).unwrap();
+        st.scan_cached_blocks(h2, 2);
+        let events = st.wallet().export_event_journal(None).unwrap();
+        assert_eq!(events.len(), 6);
+        assert_eq!(events[5].kind(), WalletEventKind::JournalDisabled);
+    }
+
+    #[test]
+    fn account_scanning_can_be_disabled() {
+        let mut st = TestBuilder::new()
+            .with_block_cache()
+            .with_account_from_sapling_activation(BlockHash([0; 32]))
+            .build();
+
+        let account = st.test_account().cloned().unwrap();
+        let account_id = account.account_id();
+        let dfvk = account.usk().sapling().to_diversifiable_full_viewing_key();
+        let value = NonNegativeAmount::const_from_u64(10000);
+
+        let (h1, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+        st.scan_cached_blocks(h1, 1);
+        assert_eq!(st.get_total_balance(account_id), value);
+
+        // Notes received while the account is disabled are not detected.
+        st.wallet_mut()
+            .set_account_scanning_enabled(account_id, false)
+            .unwrap();
+        assert!(!st.wallet().is_account_scanning_enabled(account_id).unwrap());
+        assert!(st
+            .wallet()
+            .get_unified_full_viewing_keys_for_scanning(&(h1..(h1 + 2)))
+            .unwrap()
+            .is_empty());
+
+        let (h2, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+        st.scan_cached_blocks(h2, 1);
+        assert_eq!(st.get_total_balance(account_id), value);
+
+        // Re-enabling the account queues the blocks scanned since it was disabled.
+        st.wallet_mut()
+            .set_account_scanning_enabled(account_id, true)
+            .unwrap();
+        assert!(st.wallet().is_account_scanning_enabled(account_id).unwrap());
+        assert!(st
+            .wallet()
+            .suggest_scan_ranges()
+            .unwrap()
+            .iter()
+            .any(|range| range.block_range().contains(&h2)));
+
+        st.scan_cached_blocks(h2, 1);
+        assert_eq!(st.get_total_balance(account_id), (value + value).unwrap());
+    }
+
+    #[test]
+    fn added_account_is_scanned_independently() {
+        use zcash_client_backend::data_api::WalletWrite;
+        use zcash_keys::keys::UnifiedSpendingKey;
+
+        let mut st = TestBuilder::new()
+            .with_block_cache()
+            .with_account_from_sapling_activation(BlockHash([0; 32]))
+            .build();
+
+        let account = st.test_account().cloned().unwrap();
+        let account_a = account.account_id();
+        let dfvk_a = account.usk().sapling().to_diversifiable_full_viewing_key();
+        let seed = SecretVec::new(st.test_seed().unwrap().expose_secret().clone());
+        let usk_b = UnifiedSpendingKey::from_seed(
+            &st.network(),
+            seed.expose_secret(),
+            zip32::AccountId::try_from(1).unwrap(),
+        )
+        .unwrap();
+        let dfvk_b = usk_b.sapling().to_diversifiable_full_viewing_key();
+        let value = NonNegativeAmount::const_from_u64(10000);
+
+        let (h1, _, _) = st.generate_next_block(&dfvk_a, AddressType::DefaultExternal, value);
+        let (h2, _, _) = st.generate_next_block(&dfvk_b, AddressType::DefaultExternal, value);
+        st.scan_cached_blocks(h1, 2);
+        assert_eq!(st.get_total_balance(account_a), value);
+
+        // Adding an account with an earlier birthday requires the scanned blocks to be
+        // scanned again, but only for the new account.
+        let (account_b, _) = st
+            .wallet_mut()
+            .create_account(&seed, account.birthday())
+            .unwrap();
+        let scanned = h1..(h2 + 1);
+        assert_eq!(
+            st.wallet()
+                .get_unified_full_viewing_keys_for_scanning(&scanned)
+                .unwrap()
+                .into_keys()
+                .collect::<Vec<_>>(),
+            vec![account_b]
+        );
+        // Blocks that have not yet been scanned are scanned for all accounts.
+        assert_eq!(
             st.wallet()
-                .get_unspent_transparent_outputs(taddr, height_2, &[])
-                .as_deref(),
-            Ok(&[ref ret]) if (ret.outpoint(), ret.txout(), ret.height()) == (utxo.outpoint(), utxo.txout(), height_2)
+                .get_unified_full_viewing_keys_for_scanning(&(h1..(h2 + 2)))
+                .unwrap()
+                .len(),
+            2
         );
 
-        assert_matches!(
-            st.wallet().get_transparent_balances(account_id, height_2),
-            Ok(h) if h.get(taddr) == Some(&value)
+        st.scan_cached_blocks(h1, 2);
+        assert_eq!(st.get_total_balance(account_a), value);
+        assert_eq!(st.get_total_balance(account_b), value);
+        assert_eq!(
+            st.wallet()
+                .get_unified_full_viewing_keys_for_scanning(&scanned)
+                .unwrap()
+                .len(),
+            2
         );
-
-        // Artificially delete the address from the addresses table so that
-        // we can ensure the update fails if the join doesn't work.
-        st.wallet()
-            .conn
-            .execute(
-                "DELETE FROM addresses WHERE cached_transparent_receiver_address = ?",
-                [Some(taddr.encode(&st.wallet().params))],
-            )
-            .unwrap();
-
-        let res2 = st.wallet_mut().put_received_transparent_utxo(&utxo2);
-        assert_matches!(res2, Err(_));
     }
 
     #[test]
@@ -3030,6 +5383,127 @@ mod tests {
         }
     }
 
+    #[test]
+    fn find_account_for_address() {
+        use crate::testing::TestBuilder;
+        use zcash_client_backend::data_api::WalletWrite;
+        use zcash_keys::address::Address;
+        use zip32::Scope;
+
+        let mut st = TestBuilder::new()
+            .with_account_from_sapling_activation(BlockHash([0; 32]))
+            .build();
+
+        let seed = SecretVec::new(st.test_seed().unwrap().expose_secret().clone());
+        let birthday = st.test_account().unwrap().birthday().clone();
+        let account_1 = st.test_account().unwrap().account_id();
+        let (account_2, usk_2) = st.wallet_mut().create_account(&seed, &birthday).unwrap();
+
+        let ua_1 = st.wallet().get_current_address(account_1).unwrap().unwrap();
+        let ua_2 = st.wallet().get_current_address(account_2).unwrap().unwrap();
+
+        // Unified addresses, and their individual receivers, resolve to their accounts.
+        assert_eq!(
+            st.wallet()
+                .find_account_for_address(&Address::Unified(ua_1.clone()))
+                .unwrap(),
+            Some((account_1, Scope::External))
+        );
+        assert_eq!(
+            st.wallet()
+                .find_account_for_address(&Address::Sapling(*ua_2.sapling().unwrap()))
+                .unwrap(),
+            Some((account_2, Scope::External))
+        );
+        #[cfg(feature = "transparent-inputs")]
+        assert_eq!(
+            st.wallet()
+                .find_account_for_address(&Address::Transparent(*ua_1.transparent().unwrap()))
+                .unwrap(),
+            Some((account_1, Scope::External))
+        );
+
+        // Internal addresses are recognized.
+        let (_, change_addr) = usk_2
+            .sapling()
+            .to_diversifiable_full_viewing_key()
+            .change_address();
+        assert_eq!(
+            st.wallet()
+                .find_account_for_address(&Address::Sapling(change_addr))
+                .unwrap(),
+            Some((account_2, Scope::Internal))
+        );
+
+        // Addresses from other wallets are not.
+        let (_, foreign_addr) = ExtendedSpendingKey::master(&[])
+            .to_diversifiable_full_viewing_key()
+            .default_address();
+        assert_eq!(
+            st.wallet()
+                .find_account_for_address(&Address::Sapling(foreign_addr))
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn import_restricted_viewing_keys() {
+        use zcash_client_backend::data_api::Account;
+        use zcash_keys::keys::{
+            UnifiedFullViewingKey, UnifiedIncomingViewingKey, ViewingCapability, ViewingKeyRequest,
+        };
+
+        use super::get_current_address;
+
+        let sapling_only = ViewingKeyRequest::new(false, true, false).unwrap();
+        for capability in [
+            ViewingCapability::Full(sapling_only),
+            ViewingCapability::Incoming(sapling_only),
+        ] {
+            let mut st = TestBuilder::new()
+                .with_account_from_sapling_activation(BlockHash([0; 32]))
+                .build();
+            let account = st.test_account().cloned().unwrap();
+            let encoded = account
+                .usk()
+                .to_unified_full_viewing_key()
+                .export_viewing_key(&st.network(), capability)
+                .unwrap();
+
+            let account_id = match capability {
+                ViewingCapability::Full(_) => {
+                    let ufvk = UnifiedFullViewingKey::decode(&st.network(), &encoded).unwrap();
+                    st.wallet_mut()
+                        .import_account_ufvk(&ufvk, account.birthday())
+                        .unwrap()
+                }
+                _ => {
+                    let uivk = UnifiedIncomingViewingKey::decode(&st.network(), &encoded).unwrap();
+                    st.wallet_mut()
+                        .import_account_uivk(&uivk, account.birthday())
+                        .unwrap()
+                }
+            };
+
+            let imported = st.wallet().get_account(account_id).unwrap().unwrap();
+            assert_eq!(imported.source(), AccountSource::Imported);
+            assert_eq!(
+                imported.ufvk().is_some(),
+                matches!(capability, ViewingCapability::Full(_))
+            );
+
+            // The default address only contains the receivers the key can derive.
+            let (ua, _) = get_current_address(&st.wallet().conn, &st.network(), account_id)
+                .unwrap()
+                .unwrap();
+            assert!(ua.sapling().is_some());
+            assert!(ua.transparent().is_none());
+            #[cfg(feature = "orchard")]
+            assert!(ua.orchard().is_none());
+        }
+    }
+
     #[test]
     #[cfg(feature = "transparent-inputs")]
     fn transparent_balance_across_shielding() {
@@ -3184,6 +5658,93 @@ mod tests {
         check_balance(&st, 2, value);
     }
 
+    #[test]
+    #[cfg(feature = "transparent-inputs")]
+    fn coinbase_outputs_mature_before_shielding() {
+        use zcash_client_backend::{wallet::COINBASE_MATURITY, ShieldedProtocol};
+
+        let mut st = TestBuilder::new()
+            .with_block_cache()
+            .with_account_from_sapling_activation(BlockHash([0; 32]))
+            .build();
+
+        let account = st.test_account().cloned().unwrap();
+        let uaddr = st
+            .wallet()
+            .get_current_address(account.account_id())
+            .unwrap()
+            .unwrap();
+        let taddr = *uaddr.transparent().unwrap();
+
+        let not_our_key = ExtendedSpendingKey::master(&[]).to_diversifiable_full_viewing_key();
+        let not_our_value = NonNegativeAmount::const_from_u64(10000);
+        let (start_height, _, _) =
+            st.generate_next_block(&not_our_key, AddressType::DefaultExternal, not_our_value);
+        for _ in 1..10 {
+            st.generate_next_block(&not_our_key, AddressType::DefaultExternal, not_our_value);
+        }
+        st.scan_cached_blocks(start_height, 10);
+        let chain_tip = st.wallet().chain_height().unwrap().unwrap();
+
+        // A coinbase output that can first be spent in the block after the one following the
+        // chain tip.
+        let value = NonNegativeAmount::const_from_u64(100000);
+        let utxo = WalletTransparentOutput::from_parts(
+            OutPoint::new([1u8; 32], 0),
+            TxOut {
+                value,
+                script_pubkey: taddr.script(),
+            },
+            chain_tip + 2 - COINBASE_MATURITY,
+        )
+        .unwrap()
+        .with_coinbase(true);
+        st.wallet_mut()
+            .put_received_transparent_utxo(&utxo)
+            .unwrap();
+
+        let check_balance = |st: &TestState<_>, unshielded, pending_maturity| {
+            let summary = st.wallet().get_wallet_summary(1).unwrap().unwrap();
+            let balance = summary
+                .account_balances()
+                .get(&account.account_id())
+                .unwrap();
+            assert_eq!(balance.unshielded(), unshielded);
+            assert_eq!(balance.unshielded_pending_maturity(), pending_maturity);
+            assert_eq!(balance.total(), value);
+        };
+
+        let input_selector = GreedyInputSelector::new(
+            fixed::SingleOutputChangeStrategy::new(
+                FixedFeeRule::non_standard(NonNegativeAmount::ZERO),
+                None,
+                ShieldedProtocol::Sapling,
+            ),
+            DustOutputPolicy::default(),
+        );
+
+        // The immature output is reported separately, and cannot be shielded.
+        check_balance(&st, NonNegativeAmount::ZERO, value);
+        assert!(st
+            .wallet()
+            .get_transparent_balances(account.account_id(), chain_tip)
+            .unwrap()
+            .is_empty());
+        assert_matches!(
+            st.shield_transparent_funds(&input_selector, value, account.usk(), &[taddr], 1),
+            Err(_)
+        );
+
+        // Once another block has been mined, the output has matured.
+        st.generate_next_block(&not_our_key, AddressType::DefaultExternal, not_our_value);
+        st.scan_cached_blocks(chain_tip + 1, 1);
+        check_balance(&st, value, NonNegativeAmount::ZERO);
+        assert_matches!(
+            st.shield_transparent_funds(&input_selector, value, account.usk(), &[taddr], 1),
+            Ok(_)
+        );
+    }
+
     #[test]
     fn block_fully_scanned() {
         let mut st = TestBuilder::new()
@@ -3254,4 +5815,325 @@ mod tests {
             Ok(birthday) if birthday == st.sapling_activation_height()
         )
     }
+
+    /// Returns the key for, and the default transparent address of, a third party's account.
+    #[cfg(feature = "transparent-inputs")]
+    fn third_party_transparent_key(
+        st: &TestState<BlockCache>,
+    ) -> (secp256k1::SecretKey, TransparentAddress) {
+        use zcash_keys::keys::UnifiedSpendingKey;
+        use zcash_primitives::legacy::keys::{IncomingViewingKey, NonHardenedChildIndex};
+
+        let third_party =
+            UnifiedSpendingKey::from_seed(&st.network(), &[0xf5; 32], zip32::AccountId::ZERO)
+                .unwrap();
+        let sk = third_party
+            .transparent()
+            .derive_external_secret_key(NonHardenedChildIndex::ZERO)
+            .unwrap();
+        let address = third_party
+            .transparent()
+            .to_account_pubkey()
+            .derive_external_ivk()
+            .unwrap()
+            .default_address()
+            .0;
+        (sk, address)
+    }
+
+    /// Builds a transaction at the Sapling activation height in which the third party of
+    /// [`third_party_transparent_key`] spends outputs of the given values held at its default
+    /// transparent address to the given recipients, paying the given fee.
+    #[cfg(feature = "transparent-inputs")]
+    fn build_third_party_transaction(
+        st: &TestState<BlockCache>,
+        inputs: &[(OutPoint, NonNegativeAmount)],
+        transparent_output: Option<(TransparentAddress, NonNegativeAmount)>,
+        sapling_output: Option<(sapling::PaymentAddress, NonNegativeAmount)>,
+        fee: NonNegativeAmount,
+    ) -> BuildResult {
+        use std::convert::Infallible;
+
+        use rand_core::OsRng;
+        use zcash_primitives::{
+            consensus::{NetworkUpgrade, Parameters},
+            memo::MemoBytes,
+            transaction::builder::{BuildConfig, Builder},
+        };
+        use zcash_proofs::prover::LocalTxProver;
+
+        let (sk, address) = third_party_transparent_key(st);
+        let height = st
+            .network()
+            .activation_height(NetworkUpgrade::Sapling)
+            .unwrap();
+        let mut builder = Builder::new(
+            st.network(),
+            height,
+            BuildConfig::Standard {
+                sapling_anchor: Some(sapling::Anchor::empty_tree()),
+                orchard_anchor: None,
+            },
+        );
+        for (outpoint, value) in inputs {
+            let coin = TxOut {
+                value: *value,
+                script_pubkey: address.script(),
+            };
+            builder
+                .add_transparent_input(sk, outpoint.clone(), coin)
+                .unwrap();
+        }
+        if let Some((to, value)) = transparent_output {
+            builder.add_transparent_output(&to, value).unwrap();
+        }
+        if let Some((to, value)) = sapling_output {
+            builder
+                .add_sapling_output::<Infallible>(None, to, value, MemoBytes::empty())
+                .unwrap();
+        }
+        let prover = LocalTxProver::bundled();
+        builder
+            .build(OsRng, &prover, &prover, &FixedFeeRule::non_standard(fee))
+            .unwrap()
+    }
+
+    #[test]
+    #[cfg(feature = "transparent-inputs")]
+    fn transparent_prevouts_are_requested() {
+        use zcash_client_backend::data_api::{
+            wallet::decrypt_and_store_transaction, TransactionDataRequest,
+        };
+
+        let mut st = TestBuilder::new()
+            .with_block_cache()
+            .with_account_from_sapling_activation(BlockHash([0; 32]))
+            .build();
+        let account = st.test_account().cloned().unwrap();
+        let (_, to) = account
+            .usk()
+            .to_unified_full_viewing_key()
+            .sapling()
+            .unwrap()
+            .default_address();
+
+        // A third party holds a transparent output that it spends to the wallet.
+        let (_, third_party_address) = third_party_transparent_key(&st);
+        let value = NonNegativeAmount::const_from_u64(20000);
+        let prev_res = build_third_party_transaction(
+            &st,
+            &[(OutPoint::new([1; 32], 0), value)],
+            Some((third_party_address, value)),
+            None,
+            NonNegativeAmount::ZERO,
+        );
+        let prev_tx = prev_res.transaction();
+        let res = build_third_party_transaction(
+            &st,
+            &[(OutPoint::new(*prev_tx.txid().as_ref(), 0), value)],
+            None,
+            Some((to, NonNegativeAmount::const_from_u64(15000))),
+            NonNegativeAmount::const_from_u64(5000),
+        );
+        let tx = res.transaction();
+
+        let get_fee = |st: &TestState<BlockCache>| -> Option<u64> {
+            st.wallet()
+                .conn
+                .query_row(
+                    "SELECT fee FROM transactions WHERE txid = ?",
+                    [tx.txid().as_ref()],
+                    |row| row.get(0),
+                )
+                .unwrap()
+        };
+
+        // The fee of the received transaction cannot be computed until the transaction that
+        // created its transparent input has been retrieved. Storing the received transaction
+        // again does not request that transaction a second time.
+        decrypt_and_store_transaction(&st.network(), st.wallet_mut(), tx, None).unwrap();
+        decrypt_and_store_transaction(&st.network(), st.wallet_mut(), tx, None).unwrap();
+        assert_eq!(get_fee(&st), None);
+        assert_eq!(
+            st.wallet().transaction_data_requests().unwrap(),
+            vec![TransactionDataRequest::Enhancement(prev_tx.txid())]
+        );
+
+        // Once the requested transaction has been stored, the request is satisfied and the fee
+        // is computed.
+        decrypt_and_store_transaction(&st.network(), st.wallet_mut(), prev_tx, None).unwrap();
+        assert_eq!(st.wallet().transaction_data_requests().unwrap(), vec![]);
+        assert_eq!(get_fee(&st), Some(5000));
+    }
+
+    #[test]
+    #[cfg(feature = "transparent-inputs")]
+    fn transparent_receipts_are_stored_with_mined_height() {
+        use zcash_client_backend::data_api::{
+            wallet::decrypt_and_store_transaction, TransactionDataRequest,
+        };
+        use zcash_primitives::{
+            consensus::{NetworkUpgrade, Parameters},
+            legacy::keys::IncomingViewingKey,
+        };
+
+        let mut st = TestBuilder::new()
+            .with_block_cache()
+            .with_account_from_sapling_activation(BlockHash([0; 32]))
+            .build();
+        let account = st.test_account().cloned().unwrap();
+        let taddr = account
+            .usk()
+            .transparent()
+            .to_account_pubkey()
+            .derive_external_ivk()
+            .unwrap()
+            .default_address()
+            .0;
+
+        // A third party spends a transparent output to the wallet's transparent address, in a
+        // transaction that has no shielded components.
+        let res = build_third_party_transaction(
+            &st,
+            &[(
+                OutPoint::new([1; 32], 0),
+                NonNegativeAmount::const_from_u64(20000),
+            )],
+            Some((taddr, NonNegativeAmount::const_from_u64(15000))),
+            None,
+            NonNegativeAmount::const_from_u64(5000),
+        );
+        let tx = res.transaction();
+
+        let height = st
+            .network()
+            .activation_height(NetworkUpgrade::Sapling)
+            .unwrap();
+
+        let balance = |st: &TestState<BlockCache>| {
+            st.wallet()
+                .get_transparent_balances(account.account_id(), height + 10)
+                .unwrap()
+                .get(&taddr)
+                .copied()
+        };
+
+        // The output of an unmined transaction is not recorded, because the height at which it
+        // was received is not known.
+        decrypt_and_store_transaction(&st.network(), st.wallet_mut(), tx, None).unwrap();
+        assert_eq!(balance(&st), None);
+
+        // Once the transaction is known to have been mined, the output is recorded, and spends
+        // from the address are requested from that height.
+        decrypt_and_store_transaction(&st.network(), st.wallet_mut(), tx, Some(height + 1))
+            .unwrap();
+        assert_eq!(balance(&st), Some(NonNegativeAmount::const_from_u64(15000)));
+        assert!(st.wallet().transaction_data_requests().unwrap().contains(
+            &TransactionDataRequest::SpendsFromAddress {
+                address: taddr,
+                block_range_start: height + 1,
+                block_range_end: None,
+            }
+        ));
+    }
+
+    #[test]
+    fn address_book_matches_receivers() {
+        use zcash_keys::{
+            address::Address,
+            keys::{UnifiedAddressRequest, UnifiedSpendingKey},
+        };
+        use zcash_primitives::zip32;
+
+        use crate::error::SqliteClientError;
+
+        let mut st = TestBuilder::new()
+            .with_account_from_sapling_activation(BlockHash([0; 32]))
+            .build();
+
+        let usk =
+            UnifiedSpendingKey::from_seed(&st.network(), &[1; 32], zip32::AccountId::ZERO).unwrap();
+        let (ua, _) = usk
+            .to_unified_full_viewing_key()
+            .default_address(UnifiedAddressRequest::all().unwrap())
+            .unwrap();
+        let ua_str = ua.encode(&st.network());
+        let sapling_str = Address::Sapling(*ua.sapling().unwrap()).encode(&st.network());
+
+        assert!(st.wallet().list_contacts().unwrap().is_empty());
+        let id = st.wallet_mut().put_contact("Alice", &ua_str).unwrap();
+
+        // The contact is found by its Unified Address, and by the Sapling receiver within it.
+        for address in [&ua_str, &sapling_str] {
+            let contact = st
+                .wallet()
+                .find_contact_for_address(address)
+                .unwrap()
+                .unwrap();
+            assert_eq!(contact.id(), id);
+            assert_eq!(contact.name(), "Alice");
+            assert_eq!(contact.address(), ua_str);
+        }
+
+        // Adding the same address again renames the contact.
+        assert_eq!(st.wallet_mut().put_contact("Alicia", &ua_str).unwrap(), id);
+        let contacts = st.wallet().list_contacts().unwrap();
+        assert_eq!(contacts.len(), 1);
+        assert_eq!(contacts[0].name(), "Alicia");
+
+        // A receiver of the contact's address cannot be added as a different contact.
+        assert_matches!(
+            st.wallet_mut().put_contact("Mallory", &sapling_str),
+            Err(SqliteClientError::ContactConflict(conflict)) if conflict == id
+        );
+        assert_matches!(
+            st.wallet_mut().put_contact("Bob", "not an address"),
+            Err(SqliteClientError::InvalidAddress(_))
+        );
+
+        // An address that is unrelated to any contact is not attributed to one.
+        let (other_ua, _) =
+            UnifiedSpendingKey::from_seed(&st.network(), &[2; 32], zip32::AccountId::ZERO)
+                .unwrap()
+                .to_unified_full_viewing_key()
+                .default_address(UnifiedAddressRequest::all().unwrap())
+                .unwrap();
+        assert_eq!(
+            st.wallet()
+                .find_contact_for_address(&other_ua.encode(&st.network()))
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn wallet_settings_round_trip() {
+        let mut st = TestBuilder::new().build();
+
+        assert_eq!(st.wallet().get_setting::<String>("server").unwrap(), None);
+
+        st.wallet_mut()
+            .put_setting("server", "https://zec.rocks:443")
+            .unwrap();
+        st.wallet_mut().put_setting("batch_size", 1000u32).unwrap();
+        assert_eq!(
+            st.wallet().get_setting::<String>("server").unwrap(),
+            Some("https://zec.rocks:443".to_owned())
+        );
+        assert_eq!(
+            st.wallet().get_setting::<u32>("batch_size").unwrap(),
+            Some(1000)
+        );
+
+        // A setting is replaced by a later value, which may be of a different type.
+        st.wallet_mut().put_setting("batch_size", "auto").unwrap();
+        assert_eq!(
+            st.wallet().get_setting::<String>("batch_size").unwrap(),
+            Some("auto".to_owned())
+        );
+        assert!(st.wallet().get_setting::<u32>("batch_size").is_err());
+
+        st.wallet_mut().remove_setting("server").unwrap();
+        assert_eq!(st.wallet().get_setting::<String>("server").unwrap(), None);
+    }
 }
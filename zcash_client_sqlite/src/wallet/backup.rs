@@ -0,0 +1,107 @@
+//! Tracking of when the user last verified their backup of an account's keys.
+//!
+//! The chain tip height at which the backup of each account was last verified is recorded in the
+//! `backup_verified_height` column of the `accounts` table. Value that the account has received
+//! from other parties in blocks above that height is reported as not backed up, so that an
+//! application can prompt the user to verify their backup again. Change, and value received in
+//! transactions that have not been mined, are not counted.
+
+use rusqlite::{named_params, Connection, OptionalExtension};
+use zcash_primitives::{
+    consensus::BlockHeight, transaction::components::amount::NonNegativeAmount,
+};
+
+use crate::{error::SqliteClientError, AccountId};
+
+use super::scan_queue_extrema;
+
+/// The backup state of an account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackupStatus {
+    verified_height: Option<BlockHeight>,
+    unbacked_up_value: NonNegativeAmount,
+}
+
+impl BackupStatus {
+    /// Returns the chain tip height as of which the backup of the account was last verified,
+    /// or `None` if it has never been verified.
+    pub fn verified_height(&self) -> Option<BlockHeight> {
+        self.verified_height
+    }
+
+    /// Returns the total value received by the account from other parties in blocks above
+    /// [`Self::verified_height`], or in any block if the backup has never been verified.
+    pub fn unbacked_up_value(&self) -> NonNegativeAmount {
+        self.unbacked_up_value
+    }
+}
+
+/// Records that the backup of the given account has been verified as of the current chain tip,
+/// and returns the height of the chain tip.
+pub(crate) fn record_backup_verified(
+    conn: &Connection,
+    account: AccountId,
+) -> Result<BlockHeight, SqliteClientError> {
+    let chain_tip_height = scan_queue_extrema(conn)?
+        .map(|range| *range.end())
+        .ok_or(SqliteClientError::ChainHeightUnknown)?;
+
+    let updated = conn.execute(
+        "UPDATE accounts
+         SET backup_verified_height = :height
+         WHERE id = :account_id",
+        named_params![
+            ":account_id": account,
+            ":height": u32::from(chain_tip_height),
+        ],
+    )?;
+    if updated == 0 {
+        return Err(SqliteClientError::AccountUnknown);
+    }
+
+    Ok(chain_tip_height)
+}
+
+/// Returns the backup state of the given account.
+pub(crate) fn get_backup_status(
+    conn: &Connection,
+    account: AccountId,
+) -> Result<BackupStatus, SqliteClientError> {
+    let verified_height = conn
+        .query_row(
+            "SELECT backup_verified_height FROM accounts WHERE id = :account_id",
+            named_params![":account_id": account],
+            |row| row.get::<_, Option<u32>>(0),
+        )
+        .optional()?
+        .ok_or(SqliteClientError::AccountUnknown)?
+        .map(BlockHeight::from);
+
+    let unbacked_up_value = conn.query_row(
+        "SELECT COALESCE(SUM(value), 0) FROM (
+            SELECT rn.value AS value
+            FROM v_received_notes rn
+            JOIN transactions t ON t.id_tx = rn.tx
+            WHERE rn.account_id = :account_id
+            AND NOT rn.is_change
+            AND t.block > :verified_height
+            UNION ALL
+            SELECT u.value_zat AS value
+            FROM utxos u
+            WHERE u.received_by_account_id = :account_id
+            AND u.height > :verified_height
+        )",
+        named_params![
+            ":account_id": account,
+            ":verified_height": verified_height.map_or(-1, |h| i64::from(u32::from(h))),
+        ],
+        |row| row.get::<_, i64>(0),
+    )?;
+
+    Ok(BackupStatus {
+        verified_height,
+        unbacked_up_value: NonNegativeAmount::from_nonnegative_i64(unbacked_up_value).map_err(
+            |_| SqliteClientError::CorruptedData("Unbacked-up value is out of range".to_owned()),
+        )?,
+    })
+}
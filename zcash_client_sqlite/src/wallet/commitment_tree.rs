@@ -1,3 +1,4 @@
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use rusqlite::{self, named_params, OptionalExtension};
 use std::{
     collections::BTreeSet,
@@ -12,14 +13,17 @@ use zcash_client_backend::data_api::chain::CommitmentTreeRoot;
 
 use incrementalmerkletree::{Address, Hashable, Level, Position, Retention};
 use shardtree::{
-    error::ShardTreeError,
+    error::{QueryError, ShardTreeError},
     store::{Checkpoint, ShardStore, TreeState},
-    LocatedPrunableTree, LocatedTree, PrunableTree, RetentionFlags,
+    LocatedPrunableTree, LocatedTree, PrunableTree, RetentionFlags, ShardTree,
 };
 
 use zcash_primitives::{consensus::BlockHeight, merkle_tree::HashSer};
 
 use zcash_client_backend::serialization::shardtree::{read_shard, write_shard};
+use zcash_encoding::{Optional, Vector};
+
+use crate::PRUNING_DEPTH;
 
 /// Errors that can appear in SQLite-back [`ShardStore`] implementation operations.
 #[derive(Debug)]
@@ -1075,6 +1079,260 @@ pub(crate) fn put_shard_roots<
     Ok(())
 }
 
+/// Truncates the note commitment tree with the given table prefix to its state as of the
+/// checkpoint with the given identifier, retaining that checkpoint.
+///
+/// Returns [`QueryError::CheckpointPruned`] if no such checkpoint exists.
+pub(crate) fn truncate_to_checkpoint<
+    H: Hashable + HashSer + Clone + Eq,
+    const DEPTH: u8,
+    const SHARD_HEIGHT: u8,
+>(
+    conn: &rusqlite::Transaction<'_>,
+    table_prefix: &'static str,
+    checkpoint_id: BlockHeight,
+) -> Result<(), ShardTreeError<Error>> {
+    let checkpoint = get_checkpoint(conn, table_prefix, checkpoint_id)
+        .map_err(ShardTreeError::Storage)?
+        .ok_or(ShardTreeError::Query(QueryError::CheckpointPruned))?;
+
+    let mut tree: ShardTree<SqliteShardStore<_, H, SHARD_HEIGHT>, DEPTH, SHARD_HEIGHT> =
+        ShardTree::new(
+            SqliteShardStore::from_connection(conn, table_prefix)
+                .map_err(|e| ShardTreeError::Storage(Error::Query(e)))?,
+            PRUNING_DEPTH.try_into().unwrap(),
+        );
+    // This removes the checkpoint itself along with all later tree data, so we restore it
+    // (including the marks removed at that checkpoint) afterwards.
+    tree.truncate_removing_checkpoint(&checkpoint_id)?;
+    add_checkpoint(conn, table_prefix, checkpoint_id, checkpoint).map_err(ShardTreeError::Storage)
+}
+
+/// A row of the `*_tree_shards` table, as written by [`export_tree_state`].
+struct ShardRow {
+    shard_index: u64,
+    subtree_end_height: Option<u32>,
+    root_hash: Option<Vec<u8>>,
+    shard_data: Option<Vec<u8>>,
+    contains_marked: Option<bool>,
+}
+
+/// A row of the `*_tree_checkpoints` table, along with the marks removed at that checkpoint.
+struct CheckpointRow {
+    checkpoint_id: u32,
+    position: Option<u64>,
+    marks_removed: Vec<u64>,
+}
+
+fn write_bytes<W: io::Write>(mut writer: W, bytes: &[u8]) -> io::Result<()> {
+    Vector::write(&mut writer, bytes, |w, b| w.write_u8(*b))
+}
+
+fn read_bytes<R: io::Read>(mut reader: R) -> io::Result<Vec<u8>> {
+    Vector::read(&mut reader, |r| r.read_u8())
+}
+
+/// Writes the raw contents of the tables that store the note commitment tree with the given
+/// table prefix, in the format read by [`import_tree_state`].
+///
+/// Shard and cap data are written exactly as stored, without being deserialized, so that a
+/// corrupted tree can be reproduced elsewhere.
+pub(crate) fn export_tree_state<W: io::Write>(
+    conn: &rusqlite::Connection,
+    table_prefix: &'static str,
+    mut writer: W,
+) -> Result<(), Error> {
+    let mut shard_stmt = conn
+        .prepare(&format!(
+            "SELECT shard_index, subtree_end_height, root_hash, shard_data, contains_marked
+            FROM {}_tree_shards
+            ORDER BY shard_index",
+            table_prefix
+        ))
+        .map_err(Error::Query)?;
+    let shards = shard_stmt
+        .query_map([], |row| {
+            Ok(ShardRow {
+                shard_index: row.get(0)?,
+                subtree_end_height: row.get(1)?,
+                root_hash: row.get(2)?,
+                shard_data: row.get(3)?,
+                contains_marked: row.get(4)?,
+            })
+        })
+        .map_err(Error::Query)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(Error::Query)?;
+
+    let cap_data = conn
+        .query_row(
+            &format!("SELECT cap_data FROM {}_tree_cap", table_prefix),
+            [],
+            |row| row.get::<_, Vec<u8>>(0),
+        )
+        .optional()
+        .map_err(Error::Query)?;
+
+    let mut checkpoint_stmt = conn
+        .prepare(&format!(
+            "SELECT checkpoint_id, position
+            FROM {}_tree_checkpoints
+            ORDER BY checkpoint_id",
+            table_prefix
+        ))
+        .map_err(Error::Query)?;
+    let checkpoints = checkpoint_stmt
+        .query_map([], |row| Ok((row.get::<_, u32>(0)?, row.get(1)?)))
+        .map_err(Error::Query)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(Error::Query)?
+        .into_iter()
+        .map(|(checkpoint_id, position)| {
+            Ok(CheckpointRow {
+                checkpoint_id,
+                position,
+                marks_removed: get_marks_removed(conn, table_prefix, checkpoint_id.into())?
+                    .into_iter()
+                    .map(u64::from)
+                    .collect(),
+            })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    Vector::write(&mut writer, &shards, |w, shard| {
+        w.write_u64::<LittleEndian>(shard.shard_index)?;
+        Optional::write(&mut *w, shard.subtree_end_height, |w, h| {
+            w.write_u32::<LittleEndian>(h)
+        })?;
+        Optional::write(&mut *w, shard.root_hash.as_deref(), write_bytes)?;
+        Optional::write(&mut *w, shard.shard_data.as_deref(), write_bytes)?;
+        Optional::write(&mut *w, shard.contains_marked, |w, m| w.write_u8(m.into()))
+    })
+    .map_err(Error::Serialization)?;
+    Optional::write(&mut writer, cap_data.as_deref(), write_bytes).map_err(Error::Serialization)?;
+    Vector::write(&mut writer, &checkpoints, |w, checkpoint| {
+        w.write_u32::<LittleEndian>(checkpoint.checkpoint_id)?;
+        Optional::write(&mut *w, checkpoint.position, |w, p| {
+            w.write_u64::<LittleEndian>(p)
+        })?;
+        Vector::write(w, &checkpoint.marks_removed, |w, p| {
+            w.write_u64::<LittleEndian>(*p)
+        })
+    })
+    .map_err(Error::Serialization)
+}
+
+/// Replaces the contents of the tables that store the note commitment tree with the given
+/// table prefix with the state read from `reader`, as written by [`export_tree_state`].
+pub(crate) fn import_tree_state<R: io::Read>(
+    conn: &rusqlite::Transaction<'_>,
+    table_prefix: &'static str,
+    mut reader: R,
+) -> Result<(), Error> {
+    let shards = Vector::read(&mut reader, |r| {
+        Ok(ShardRow {
+            shard_index: r.read_u64::<LittleEndian>()?,
+            subtree_end_height: Optional::read(&mut *r, |r| r.read_u32::<LittleEndian>())?,
+            root_hash: Optional::read(&mut *r, read_bytes)?,
+            shard_data: Optional::read(&mut *r, read_bytes)?,
+            contains_marked: Optional::read(&mut *r, |r| {
+                r.read_u8().and_then(|m| match m {
+                    0 => Ok(false),
+                    1 => Ok(true),
+                    _ => Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "invalid contains_marked flag",
+                    )),
+                })
+            })?,
+        })
+    })
+    .map_err(Error::Serialization)?;
+    let cap_data = Optional::read(&mut reader, read_bytes).map_err(Error::Serialization)?;
+    let checkpoints = Vector::read(&mut reader, |r| {
+        Ok(CheckpointRow {
+            checkpoint_id: r.read_u32::<LittleEndian>()?,
+            position: Optional::read(&mut *r, |r| r.read_u64::<LittleEndian>())?,
+            marks_removed: Vector::read(r, |r| r.read_u64::<LittleEndian>())?,
+        })
+    })
+    .map_err(Error::Serialization)?;
+
+    conn.execute_batch(&format!(
+        "DELETE FROM {0}_tree_checkpoint_marks_removed;
+        DELETE FROM {0}_tree_checkpoints;
+        DELETE FROM {0}_tree_cap;
+        DELETE FROM {0}_tree_shards;",
+        table_prefix
+    ))
+    .map_err(Error::Query)?;
+
+    let mut shard_stmt = conn
+        .prepare(&format!(
+            "INSERT INTO {}_tree_shards
+                (shard_index, subtree_end_height, root_hash, shard_data, contains_marked)
+            VALUES
+                (:shard_index, :subtree_end_height, :root_hash, :shard_data, :contains_marked)",
+            table_prefix
+        ))
+        .map_err(Error::Query)?;
+    for shard in shards {
+        shard_stmt
+            .execute(named_params![
+                ":shard_index": shard.shard_index,
+                ":subtree_end_height": shard.subtree_end_height,
+                ":root_hash": shard.root_hash,
+                ":shard_data": shard.shard_data,
+                ":contains_marked": shard.contains_marked,
+            ])
+            .map_err(Error::Query)?;
+    }
+
+    if let Some(cap_data) = cap_data {
+        conn.execute(
+            &format!(
+                "INSERT INTO {}_tree_cap (cap_id, cap_data) VALUES (0, :cap_data)",
+                table_prefix
+            ),
+            named_params![":cap_data": cap_data],
+        )
+        .map_err(Error::Query)?;
+    }
+
+    let mut checkpoint_stmt = conn
+        .prepare(&format!(
+            "INSERT INTO {}_tree_checkpoints (checkpoint_id, position)
+            VALUES (:checkpoint_id, :position)",
+            table_prefix
+        ))
+        .map_err(Error::Query)?;
+    let mut mark_removed_stmt = conn
+        .prepare(&format!(
+            "INSERT INTO {}_tree_checkpoint_marks_removed (checkpoint_id, mark_removed_position)
+            VALUES (:checkpoint_id, :position)",
+            table_prefix
+        ))
+        .map_err(Error::Query)?;
+    for checkpoint in checkpoints {
+        checkpoint_stmt
+            .execute(named_params![
+                ":checkpoint_id": checkpoint.checkpoint_id,
+                ":position": checkpoint.position,
+            ])
+            .map_err(Error::Query)?;
+        for position in checkpoint.marks_removed {
+            mark_removed_stmt
+                .execute(named_params![
+                    ":checkpoint_id": checkpoint.checkpoint_id,
+                    ":position": position,
+                ])
+                .map_err(Error::Query)?;
+        }
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use tempfile::NamedTempFile;
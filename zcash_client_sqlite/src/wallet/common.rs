@@ -1,14 +1,18 @@
 //! Functions common to Sapling and Orchard support in the wallet.
 
-use rusqlite::{named_params, types::Value, Connection, Row};
-use std::rc::Rc;
+use incrementalmerkletree::Position;
+use rusqlite::{named_params, types::Value, Connection, OptionalExtension, Row};
+use std::{ops::Range, rc::Rc};
 
-use zcash_client_backend::{wallet::ReceivedNote, ShieldedProtocol};
+use zcash_client_backend::{
+    wallet::{NoteId, ReceivedNote},
+    ShieldedProtocol,
+};
 use zcash_primitives::transaction::{components::amount::NonNegativeAmount, TxId};
 use zcash_protocol::consensus::{self, BlockHeight};
 
 use super::wallet_birthday;
-use crate::{error::SqliteClientError, AccountId, ReceivedNoteId, SAPLING_TABLES_PREFIX};
+use crate::{error::SqliteClientError, AccountId, SAPLING_TABLES_PREFIX};
 
 #[cfg(feature = "orchard")]
 use crate::ORCHARD_TABLES_PREFIX;
@@ -58,9 +62,9 @@ pub(crate) fn get_spendable_note<P: consensus::Parameters, F, Note>(
     index: u32,
     protocol: ShieldedProtocol,
     to_spendable_note: F,
-) -> Result<Option<ReceivedNote<ReceivedNoteId, Note>>, SqliteClientError>
+) -> Result<Option<ReceivedNote<NoteId, Note>>, SqliteClientError>
 where
-    F: Fn(&P, &Row) -> Result<Option<ReceivedNote<ReceivedNoteId, Note>>, SqliteClientError>,
+    F: Fn(&P, &Row) -> Result<Option<ReceivedNote<NoteId, Note>>, SqliteClientError>,
 {
     let (table_prefix, index_col, note_reconstruction_cols) = per_protocol_names(protocol);
     let result = conn.query_row_and_then(
@@ -109,12 +113,12 @@ pub(crate) fn select_spendable_notes<P: consensus::Parameters, F, Note>(
     account: AccountId,
     target_value: NonNegativeAmount,
     anchor_height: BlockHeight,
-    exclude: &[ReceivedNoteId],
+    exclude: &[NoteId],
     protocol: ShieldedProtocol,
     to_spendable_note: F,
-) -> Result<Vec<ReceivedNote<ReceivedNoteId, Note>>, SqliteClientError>
+) -> Result<Vec<ReceivedNote<NoteId, Note>>, SqliteClientError>
 where
-    F: Fn(&P, &Row) -> Result<Option<ReceivedNote<ReceivedNoteId, Note>>, SqliteClientError>,
+    F: Fn(&P, &Row) -> Result<Option<ReceivedNote<NoteId, Note>>, SqliteClientError>,
 {
     let birthday_height = match wallet_birthday(conn)? {
         Some(birthday) => birthday,
@@ -196,21 +200,33 @@ where
         )
     )?;
 
-    let excluded: Vec<Value> = exclude
-        .iter()
-        .filter_map(|ReceivedNoteId(p, n)| {
-            if *p == protocol {
-                Some(Value::from(*n))
-            } else {
-                None
-            }
-        })
-        .collect();
+    // Notes are excluded by their row IDs, which are looked up from their stable identifiers.
+    let mut stmt_note_row_id = conn.prepare_cached(&format!(
+        "SELECT rn.id
+         FROM {table_prefix}_received_notes rn
+         INNER JOIN transactions ON transactions.id_tx = rn.tx
+         WHERE txid = :txid AND {index_col} = :output_index"
+    ))?;
+    let mut excluded: Vec<Value> = vec![];
+    for note_id in exclude.iter().filter(|n| n.protocol() == protocol) {
+        if let Some(row_id) = stmt_note_row_id
+            .query_row(
+                named_params![
+                    ":txid": note_id.txid().as_ref(),
+                    ":output_index": note_id.output_index(),
+                ],
+                |row| row.get::<_, i64>(0),
+            )
+            .optional()?
+        {
+            excluded.push(Value::from(row_id));
+        }
+    }
     let excluded_ptr = Rc::new(excluded);
 
     let notes = stmt_select_notes.query_and_then(
         named_params![
-            ":account": account.0,
+            ":account": account,
             ":anchor_height": &u32::from(anchor_height),
             ":target_value": &u64::from(target_value),
             ":exclude": &excluded_ptr,
@@ -223,3 +239,62 @@ where
         .filter_map(|r| r.transpose())
         .collect::<Result<_, _>>()
 }
+
+/// Returns the note commitment tree positions of the wallet's unspent notes that were mined in
+/// the given range of heights, up to and including `anchor_height`.
+///
+/// Notes in shards of the note commitment tree that have not been fully scanned are excluded,
+/// because witnesses cannot yet be computed for them.
+pub(crate) fn get_unspent_note_positions(
+    conn: &Connection,
+    protocol: ShieldedProtocol,
+    range: &Range<BlockHeight>,
+    anchor_height: BlockHeight,
+) -> Result<Vec<(NoteId, Position)>, SqliteClientError> {
+    let birthday_height = match wallet_birthday(conn)? {
+        Some(birthday) => birthday,
+        None => return Ok(vec![]),
+    };
+
+    let (table_prefix, index_col, _) = per_protocol_names(protocol);
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT txid, {index_col}, commitment_tree_position
+         FROM {table_prefix}_received_notes rn
+         INNER JOIN transactions ON transactions.id_tx = rn.tx
+         WHERE transactions.block >= :start_height
+         AND transactions.block < :end_height
+         AND transactions.block <= :anchor_height
+         AND commitment_tree_position IS NOT NULL
+         AND rn.id NOT IN (
+           SELECT {table_prefix}_received_note_id
+           FROM {table_prefix}_received_note_spends
+           JOIN transactions stx ON stx.id_tx = transaction_id
+           WHERE stx.block IS NOT NULL -- the spending tx is mined
+         )
+         AND NOT EXISTS (
+            SELECT 1 FROM v_{table_prefix}_shard_unscanned_ranges unscanned
+            WHERE rn.commitment_tree_position >= unscanned.start_position
+            AND rn.commitment_tree_position < unscanned.end_position_exclusive
+            AND unscanned.block_range_start <= :anchor_height
+            AND unscanned.block_range_end > :wallet_birthday
+         )
+         ORDER BY commitment_tree_position"
+    ))?;
+
+    let rows = stmt.query_and_then(
+        named_params![
+            ":start_height": u32::from(range.start),
+            ":end_height": u32::from(range.end),
+            ":anchor_height": u32::from(anchor_height),
+            ":wallet_birthday": u32::from(birthday_height),
+        ],
+        |row| {
+            let txid = TxId::from_bytes(row.get(0)?);
+            let output_index = row.get(1)?;
+            let position = Position::from(row.get::<_, u64>(2)?);
+            Ok::<_, SqliteClientError>((NoteId::new(txid, protocol, output_index), position))
+        },
+    )?;
+
+    rows.collect()
+}
@@ -0,0 +1,166 @@
+//! An address book of labeled external addresses.
+//!
+//! Each contact associates a name with an address outside the wallet. When the address is a
+//! Unified Address, each of its receivers is also recorded, so that an address that shares a
+//! receiver with the contact's address (for example, the Sapling address extracted from the
+//! Unified Address by a wallet that does not support Unified Addresses) is attributed to the same
+//! contact. The `v_tx_outputs_contacts` view annotates each row of `v_tx_outputs` with the name
+//! of the contact to which the output was sent, if any.
+
+use rusqlite::{named_params, Connection, OptionalExtension};
+use zcash_keys::address::{Address, UnifiedAddress};
+use zcash_primitives::consensus;
+
+use crate::error::SqliteClientError;
+
+/// The identifier of a contact in the wallet's address book.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ContactId(i64);
+
+/// A labeled external address in the wallet's address book.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Contact {
+    id: ContactId,
+    name: String,
+    address: String,
+}
+
+impl Contact {
+    /// Returns the identifier of the contact.
+    pub fn id(&self) -> ContactId {
+        self.id
+    }
+
+    /// Returns the name of the contact.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the encoded address of the contact, as it was provided to
+    /// [`WalletDb::put_contact`].
+    ///
+    /// [`WalletDb::put_contact`]: crate::WalletDb::put_contact
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+}
+
+/// Returns the encodings under which outputs sent to the given address may be recorded: the
+/// address itself, and the standalone encoding of each of its receivers.
+fn receiver_encodings<P: consensus::Parameters>(
+    params: &P,
+    address: &str,
+) -> Result<Vec<String>, SqliteClientError> {
+    let decoded = Address::decode(params, address)
+        .ok_or_else(|| SqliteClientError::InvalidAddress(address.to_owned()))?;
+
+    let mut encodings = vec![decoded.encode(params)];
+    if let Address::Unified(ua) = decoded {
+        if let Some(addr) = ua.transparent() {
+            encodings.push(Address::Transparent(*addr).encode(params));
+        }
+        if let Some(addr) = ua.sapling() {
+            encodings.push(Address::Sapling(*addr).encode(params));
+        }
+        if let Some(addr) = ua.orchard() {
+            if let Some(orchard_ua) = UnifiedAddress::from_receivers(Some(*addr), None, None) {
+                encodings.push(orchard_ua.encode(params));
+            }
+        }
+    }
+    encodings.dedup();
+
+    Ok(encodings)
+}
+
+fn to_contact(row: &rusqlite::Row) -> Result<Contact, rusqlite::Error> {
+    Ok(Contact {
+        id: ContactId(row.get("id")?),
+        name: row.get("name")?,
+        address: row.get("address")?,
+    })
+}
+
+/// Returns the contact to which any receiver of the given address belongs.
+fn contact_for_receivers(
+    conn: &Connection,
+    receivers: &[String],
+) -> Result<Option<Contact>, SqliteClientError> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT contacts.id, contacts.name, contacts.address
+         FROM contact_receivers
+         JOIN contacts ON contacts.id = contact_receivers.contact_id
+         WHERE contact_receivers.address = :address",
+    )?;
+    for receiver in receivers {
+        if let Some(contact) = stmt
+            .query_row(named_params![":address": receiver], to_contact)
+            .optional()?
+        {
+            return Ok(Some(contact));
+        }
+    }
+    Ok(None)
+}
+
+/// Adds a contact with the given name and address to the address book, or renames the contact
+/// if the address is already in the address book.
+///
+/// Returns an error if the address shares a receiver with the address of a different contact.
+pub(crate) fn put_contact<P: consensus::Parameters>(
+    conn: &rusqlite::Transaction,
+    params: &P,
+    name: &str,
+    address: &str,
+) -> Result<ContactId, SqliteClientError> {
+    let receivers = receiver_encodings(params, address)?;
+
+    if let Some(existing) = contact_for_receivers(conn, &receivers)? {
+        if existing.address == receivers[0] {
+            conn.execute(
+                "UPDATE contacts SET name = :name WHERE id = :id",
+                named_params![":name": name, ":id": existing.id.0],
+            )?;
+            return Ok(existing.id);
+        } else {
+            return Err(SqliteClientError::ContactConflict(existing.id));
+        }
+    }
+
+    let id = ContactId(conn.query_row(
+        "INSERT INTO contacts (name, address) VALUES (:name, :address) RETURNING id",
+        named_params![":name": name, ":address": receivers[0]],
+        |row| row.get(0),
+    )?);
+    let mut stmt = conn.prepare_cached(
+        "INSERT INTO contact_receivers (contact_id, address) VALUES (:contact_id, :address)",
+    )?;
+    for receiver in &receivers {
+        stmt.execute(named_params![":contact_id": id.0, ":address": receiver])?;
+    }
+
+    Ok(id)
+}
+
+/// Returns all of the contacts in the address book, in order of name.
+pub(crate) fn list_contacts(conn: &Connection) -> Result<Vec<Contact>, SqliteClientError> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT id, name, address
+         FROM contacts
+         ORDER BY name, id",
+    )?;
+    let contacts = stmt
+        .query_map([], to_contact)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(contacts)
+}
+
+/// Returns the contact whose address is, or shares a receiver with, the given address.
+pub(crate) fn find_contact_for_address<P: consensus::Parameters>(
+    conn: &Connection,
+    params: &P,
+    address: &str,
+) -> Result<Option<Contact>, SqliteClientError> {
+    let receivers = receiver_encodings(params, address)?;
+    contact_for_receivers(conn, &receivers)
+}
@@ -1,12 +1,14 @@
 //! Functions for initializing the various databases.
 
+use std::collections::HashSet;
 use std::fmt;
 use std::rc::Rc;
 
-use schemer::{Migrator, MigratorError};
+use schemer::{Migration, Migrator, MigratorError};
 use schemer_rusqlite::RusqliteAdapter;
 use secrecy::SecretVec;
 use shardtree::error::ShardTreeError;
+use tracing::warn;
 use uuid::Uuid;
 
 use zcash_client_backend::{
@@ -18,7 +20,12 @@ use zcash_primitives::{consensus, transaction::components::amount::BalanceError}
 use super::commitment_tree;
 use crate::{error::SqliteClientError, WalletDb};
 
+mod batched;
+mod integrity;
 mod migrations;
+pub mod schema;
+
+pub use self::batched::MigrationProgress;
 
 #[derive(Debug)]
 pub enum WalletMigrationError {
@@ -53,6 +60,15 @@ pub enum WalletMigrationError {
 
     /// Reverting the specified migration is not supported.
     CannotRevert(Uuid),
+
+    /// The specified migration has been applied to the database, but is not known to this
+    /// version of the crate. The database may have been created by a newer version of the
+    /// crate, or by a fork with a different migration history.
+    UnknownMigration(Uuid),
+
+    /// The definition of the specified object in the database has been modified or removed
+    /// by something other than this crate's migrations.
+    SchemaModified(String),
 }
 
 impl From<rusqlite::Error> for WalletMigrationError {
@@ -106,6 +122,20 @@ impl fmt::Display for WalletMigrationError {
             WalletMigrationError::CannotRevert(uuid) => {
                 write!(f, "Reverting migration {} is not supported", uuid)
             }
+            WalletMigrationError::UnknownMigration(uuid) => {
+                write!(
+                    f,
+                    "Migration {} has been applied to the database, but is not known to this version of the wallet",
+                    uuid
+                )
+            }
+            WalletMigrationError::SchemaModified(object) => {
+                write!(
+                    f,
+                    "The definition of {} in the wallet database has been modified outside of the wallet's migrations",
+                    object
+                )
+            }
         }
     }
 }
@@ -151,6 +181,9 @@ fn sqlite_client_error_to_wallet_migration_error(e: SqliteClientError) -> Wallet
             format!("Wallet DB contains unsupported pool type {}", pool),
         ),
         SqliteClientError::BalanceError(e) => WalletMigrationError::BalanceError(e),
+        SqliteClientError::InvalidAddress(addr) => {
+            WalletMigrationError::CorruptedData(format!("Invalid address: {}", addr))
+        }
         SqliteClientError::TableNotEmpty => unreachable!("wallet already initialized"),
         SqliteClientError::BlockConflict(_)
         | SqliteClientError::NonSequentialBlocks
@@ -158,7 +191,9 @@ fn sqlite_client_error_to_wallet_migration_error(e: SqliteClientError) -> Wallet
         | SqliteClientError::KeyDerivationError(_)
         | SqliteClientError::AccountIdDiscontinuity
         | SqliteClientError::AccountIdOutOfRange
-        | SqliteClientError::CacheMiss(_) => {
+        | SqliteClientError::CacheMiss(_)
+        | SqliteClientError::ContactConflict(_)
+        | SqliteClientError::ScheduledPaymentUnknown(_) => {
             unreachable!("we only call WalletRead methods; mutations can't occur")
         }
         #[cfg(feature = "transparent-inputs")]
@@ -257,10 +292,58 @@ pub fn init_wallet_db<P: consensus::Parameters + 'static>(
     wdb: &mut WalletDb<rusqlite::Connection, P>,
     seed: Option<SecretVec<u8>>,
 ) -> Result<(), MigratorError<WalletMigrationError>> {
-    init_wallet_db_internal(wdb, seed, &[], true)
+    init_wallet_db_with_progress(wdb, seed, |_| ())
 }
 
-fn init_wallet_db_internal<P: consensus::Parameters + 'static>(
+/// Sets up the internal structure of the data database, reporting the progress of
+/// long-running data migrations.
+///
+/// This behaves in the same way as [`init_wallet_db`]. Once the database structure has been
+/// updated, data migrations that rewrite large tables are applied in batches, and `progress`
+/// is called with the state of each such migration after every batch, so that applications
+/// can show that the upgrade is proceeding. The progress of these migrations is saved after
+/// each batch; if the application is terminated during a migration, the migration resumes
+/// where it left off the next time this function is called.
+///
+/// A warning is logged for each deprecated view that other views or triggers in the database
+/// depend upon; see [`WalletDb::deprecated_view_usage`].
+pub fn init_wallet_db_with_progress<P, F>(
+    wdb: &mut WalletDb<rusqlite::Connection, P>,
+    seed: Option<SecretVec<u8>>,
+    mut progress: F,
+) -> Result<(), MigratorError<WalletMigrationError>>
+where
+    P: consensus::Parameters + 'static,
+    F: FnMut(&MigrationProgress),
+{
+    init_wallet_db_internal(wdb, seed, &[], true)?;
+
+    batched::apply_batched_migrations(
+        &mut wdb.conn,
+        &migrations::all_batched_migrations(&wdb.params),
+        batched::BATCH_SIZE,
+        &mut progress,
+    )
+    .map_err(MigratorError::Adapter)?;
+
+    for usage in schema::deprecated_view_usage(&wdb.conn)
+        .map_err(|e| MigratorError::Adapter(WalletMigrationError::from(e)))?
+    {
+        if !usage.dependents().is_empty() {
+            warn!(
+                "{} depend on the deprecated view {}, which will be removed in version {}; use {} instead.",
+                usage.dependents().join(", "),
+                usage.view(),
+                usage.removal_version(),
+                usage.replacement(),
+            );
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) fn init_wallet_db_internal<P: consensus::Parameters + 'static>(
     wdb: &mut WalletDb<rusqlite::Connection, P>,
     seed: Option<SecretVec<u8>>,
     target_migrations: &[Uuid],
@@ -276,12 +359,24 @@ fn init_wallet_db_internal<P: consensus::Parameters + 'static>(
              PRAGMA legacy_alter_table = TRUE;",
         )
         .map_err(|e| MigratorError::Adapter(WalletMigrationError::from(e)))?;
+
+    // Check that the database has not been modified since it was last migrated, before any
+    // further changes are made to it.
+    let known_migrations = migrations::all_migrations(&wdb.params, seed.clone())
+        .iter()
+        .map(|m| m.id())
+        .collect::<HashSet<_>>();
+    integrity::verify(&wdb.conn, &known_migrations).map_err(MigratorError::Adapter)?;
+
     let adapter = RusqliteAdapter::new(&mut wdb.conn, Some("schemer_migrations".to_string()));
     adapter.init().expect("Migrations table setup succeeds.");
 
     let mut migrator = Migrator::new(adapter);
     migrator
-        .register_multiple(migrations::all_migrations(&wdb.params, seed.clone()))
+        .register_multiple(integrity::track(migrations::all_migrations(
+            &wdb.params,
+            seed.clone(),
+        )))
         .expect("Wallet migration registration should have been successful.");
     if target_migrations.is_empty() {
         migrator.up(None)?;
@@ -365,6 +460,29 @@ mod tests {
         let re = Regex::new(r"\s+").unwrap();
 
         let expected_tables = vec![
+            "CREATE TABLE account_backfill_ranges (
+                account_id INTEGER NOT NULL,
+                block_range_start INTEGER NOT NULL,
+                block_range_end INTEGER NOT NULL,
+                FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE,
+                CONSTRAINT range_bounds_order CHECK (
+                    block_range_start < block_range_end
+                )
+            )",
+            "CREATE TABLE account_balances (
+                account_id INTEGER NOT NULL,
+                min_confirmations INTEGER NOT NULL,
+                min_note_value INTEGER NOT NULL,
+                sapling_spendable_value INTEGER NOT NULL,
+                sapling_change_pending_confirmation INTEGER NOT NULL,
+                sapling_value_pending_spendability INTEGER NOT NULL,
+                orchard_spendable_value INTEGER NOT NULL,
+                orchard_change_pending_confirmation INTEGER NOT NULL,
+                orchard_value_pending_spendability INTEGER NOT NULL,
+                unshielded_value INTEGER NOT NULL,
+                PRIMARY KEY (account_id, min_confirmations, min_note_value),
+                FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+            , unshielded_pending_maturity_value INTEGER NOT NULL DEFAULT 0)",
             r#"CREATE TABLE "accounts" (
                 id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
                 account_kind INTEGER NOT NULL DEFAULT 0,
@@ -393,7 +511,7 @@ mod tests {
                     AND hd_account_index IS NULL
                   )
                 )
-            )"#,
+            , enabled_for_scanning INTEGER NOT NULL DEFAULT 1, scanning_disabled_height INTEGER, backup_verified_height INTEGER)"#,
             r#"CREATE TABLE "addresses" (
                 account_id INTEGER NOT NULL,
                 diversifier_index_be BLOB NOT NULL,
@@ -401,7 +519,12 @@ mod tests {
                 cached_transparent_receiver_address TEXT,
                 FOREIGN KEY (account_id) REFERENCES accounts(id),
                 CONSTRAINT diversification UNIQUE (account_id, diversifier_index_be)
-            )"#,
+            , first_used_height INTEGER, last_used_height INTEGER)"#,
+            "CREATE TABLE batched_migrations (
+                migration_id TEXT NOT NULL PRIMARY KEY,
+                last_row_id INTEGER,
+                rows_processed INTEGER NOT NULL DEFAULT 0
+            )",
             "CREATE TABLE blocks (
                 height INTEGER PRIMARY KEY,
                 hash BLOB NOT NULL,
@@ -411,6 +534,21 @@ mod tests {
                 orchard_commitment_tree_size INTEGER,
                 sapling_output_count INTEGER,
                 orchard_action_count INTEGER)",
+            "CREATE TABLE contact_receivers (
+                contact_id INTEGER NOT NULL,
+                address TEXT NOT NULL UNIQUE,
+                FOREIGN KEY (contact_id) REFERENCES contacts(id) ON DELETE CASCADE
+            )",
+            "CREATE TABLE contacts (
+                id INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                address TEXT NOT NULL UNIQUE
+            )",
+            "CREATE TABLE deprecated_views (
+                view_name TEXT NOT NULL PRIMARY KEY,
+                replacement TEXT NOT NULL,
+                removal_version TEXT NOT NULL
+            )",
             "CREATE TABLE nullifier_map (
                 spend_pool INTEGER NOT NULL,
                 nf BLOB NOT NULL,
@@ -540,6 +678,24 @@ mod tests {
                     block_range_start < block_range_end
                 )
             )",
+            "CREATE TABLE scheduled_payments (
+                id INTEGER PRIMARY KEY,
+                account_id INTEGER NOT NULL,
+                payment_uri TEXT NOT NULL,
+                interval_unit INTEGER NOT NULL,
+                interval_count INTEGER NOT NULL,
+                first_due_at INTEGER NOT NULL,
+                payments_made INTEGER NOT NULL DEFAULT 0,
+                next_due_at INTEGER NOT NULL,
+                FOREIGN KEY (account_id) REFERENCES accounts(id),
+                CONSTRAINT interval_valid CHECK (interval_unit IN (0, 1) AND interval_count > 0)
+            )",
+            "CREATE TABLE schema_integrity (
+                name TEXT NOT NULL PRIMARY KEY,
+                type TEXT NOT NULL,
+                sql_hash BLOB NOT NULL,
+                migration_id BLOB NOT NULL
+            )",
             "CREATE TABLE schemer_migrations (
                 id blob PRIMARY KEY
             )",
@@ -573,6 +729,10 @@ mod tests {
                 raw BLOB,
                 fee INTEGER,
                 FOREIGN KEY (block) REFERENCES blocks(height)
+            , spent_externally INTEGER NOT NULL DEFAULT 0, lookahead_height INTEGER)",
+            "CREATE TABLE transparent_address_checks (
+                address TEXT NOT NULL PRIMARY KEY,
+                checked_height INTEGER NOT NULL
             )",
             "CREATE TABLE transparent_received_output_spends (
                 transparent_received_output_id INTEGER NOT NULL,
@@ -591,6 +751,15 @@ mod tests {
                 txid BLOB NOT NULL UNIQUE,
                 PRIMARY KEY (block_height, tx_index)
             )",
+            "CREATE TABLE tx_retrieval_queue (
+                txid BLOB NOT NULL,
+                query_type INTEGER NOT NULL,
+                dependent_transaction_id INTEGER NOT NULL,
+                FOREIGN KEY (dependent_transaction_id)
+                    REFERENCES transactions(id_tx)
+                    ON DELETE CASCADE,
+                UNIQUE (txid, query_type, dependent_transaction_id)
+            )",
             r#"CREATE TABLE "utxos" (
                 id INTEGER PRIMARY KEY,
                 received_by_account_id INTEGER NOT NULL,
@@ -602,7 +771,20 @@ mod tests {
                 height INTEGER NOT NULL,
                 FOREIGN KEY (received_by_account_id) REFERENCES accounts(id),
                 CONSTRAINT tx_outpoint UNIQUE (prevout_txid, prevout_idx)
-            )"#,
+            , is_coinbase INTEGER NOT NULL DEFAULT 0)"#,
+            "CREATE TABLE wallet_events (
+                id INTEGER PRIMARY KEY,
+                recorded_at INTEGER NOT NULL,
+                kind TEXT NOT NULL,
+                start_height INTEGER,
+                end_height INTEGER,
+                txid BLOB,
+                detail TEXT
+            )",
+            "CREATE TABLE wallet_settings (
+                name TEXT NOT NULL PRIMARY KEY,
+                value NOT NULL
+            )",
         ];
 
         let mut tables_query = st
@@ -622,12 +804,15 @@ mod tests {
         }
 
         let expected_indices = vec![
+            r#"CREATE INDEX account_backfill_ranges_start
+                ON account_backfill_ranges (block_range_start)"#,
             r#"CREATE UNIQUE INDEX accounts_ufvk ON "accounts" (ufvk)"#,
             r#"CREATE UNIQUE INDEX accounts_uivk ON "accounts" (uivk)"#,
             r#"CREATE UNIQUE INDEX hd_account ON "accounts" (hd_seed_fingerprint, hd_account_index)"#,
             r#"CREATE INDEX "addresses_accounts" ON "addresses" (
                 "account_id" ASC
             )"#,
+            r#"CREATE INDEX contact_receivers_contact ON contact_receivers (contact_id)"#,
             r#"CREATE INDEX nf_map_locator_idx ON nullifier_map(block_height, tx_index)"#,
             r#"CREATE INDEX orchard_received_notes_account ON orchard_received_notes (
                 account_id ASC
@@ -641,10 +826,12 @@ mod tests {
             r#"CREATE INDEX "sapling_received_notes_tx" ON "sapling_received_notes" (
                 "tx" ASC
             )"#,
+            r#"CREATE INDEX scheduled_payments_next_due ON scheduled_payments (next_due_at)"#,
             r#"CREATE INDEX sent_notes_from_account ON "sent_notes" (from_account_id)"#,
             r#"CREATE INDEX sent_notes_to_account ON "sent_notes" (to_account_id)"#,
             r#"CREATE INDEX sent_notes_tx ON "sent_notes" (tx)"#,
             r#"CREATE INDEX utxos_received_by_account ON "utxos" (received_by_account_id)"#,
+            r#"CREATE INDEX wallet_events_kind ON wallet_events (kind)"#,
         ];
         let mut indices_query = st
             .wallet()
@@ -663,6 +850,102 @@ mod tests {
         }
 
         let expected_views = vec![
+            // v_ledger
+            "CREATE VIEW v_ledger AS
+                WITH entries AS (
+                    SELECT v_received_notes.txid,
+                           transactions.block           AS mined_height,
+                           v_received_notes.account_id,
+                           'receive'                    AS entry_type,
+                           v_received_notes.pool        AS output_pool,
+                           v_received_notes.output_index,
+                           v_received_notes.value
+                    FROM v_received_notes
+                    JOIN transactions
+                        ON transactions.id_tx = v_received_notes.tx
+                    UNION ALL
+                    SELECT utxos.prevout_txid           AS txid,
+                           utxos.height                 AS mined_height,
+                           utxos.received_by_account_id AS account_id,
+                           'receive'                    AS entry_type,
+                           0                            AS output_pool,
+                           utxos.prevout_idx            AS output_index,
+                           utxos.value_zat              AS value
+                    FROM utxos
+                    UNION ALL
+                    SELECT v_received_note_spends.spending_txid AS txid,
+                           transactions.block           AS mined_height,
+                           v_received_notes.account_id,
+                           'spend'                      AS entry_type,
+                           v_received_notes.pool        AS output_pool,
+                           NULL                         AS output_index,
+                           -v_received_notes.value      AS value
+                    FROM v_received_note_spends
+                    JOIN v_received_notes
+                        ON v_received_notes.pool = v_received_note_spends.pool
+                        AND v_received_notes.id_within_pool_table = v_received_note_spends.received_note_id
+                    JOIN transactions
+                        ON transactions.id_tx = v_received_note_spends.transaction_id
+                    UNION ALL
+                    SELECT transactions.txid,
+                           transactions.block           AS mined_height,
+                           utxos.received_by_account_id AS account_id,
+                           'spend'                      AS entry_type,
+                           0                            AS output_pool,
+                           NULL                         AS output_index,
+                           -utxos.value_zat             AS value
+                    FROM transparent_received_output_spends
+                    JOIN utxos
+                        ON utxos.id = transparent_received_output_spends.transparent_received_output_id
+                    JOIN transactions
+                        ON transactions.id_tx = transparent_received_output_spends.transaction_id
+                    UNION ALL
+                    SELECT transactions.txid,
+                           transactions.block           AS mined_height,
+                           NULL                         AS account_id,
+                           'send'                       AS entry_type,
+                           sent_notes.output_pool,
+                           sent_notes.output_index,
+                           sent_notes.value
+                    FROM sent_notes
+                    JOIN transactions
+                        ON transactions.id_tx = sent_notes.tx
+                    LEFT JOIN v_received_notes
+                        ON v_received_notes.sent_note_id = sent_notes.id
+                    LEFT JOIN utxos
+                        ON sent_notes.output_pool = 0
+                        AND utxos.prevout_txid = transactions.txid
+                        AND utxos.prevout_idx = sent_notes.output_index
+                    WHERE v_received_notes.sent_note_id IS NULL
+                    AND utxos.id IS NULL
+                    UNION ALL
+                    SELECT transactions.txid,
+                           transactions.block           AS mined_height,
+                           NULL                         AS account_id,
+                           'fee'                        AS entry_type,
+                           NULL                         AS output_pool,
+                           NULL                         AS output_index,
+                           transactions.fee             AS value
+                    FROM transactions
+                    WHERE transactions.fee IS NOT NULL
+                )
+                SELECT txid, mined_height, account_id, entry_type, output_pool, output_index, value
+                FROM entries
+                UNION ALL
+                SELECT txid,
+                       mined_height,
+                       NULL                             AS account_id,
+                       'external'                       AS entry_type,
+                       output_pool,
+                       output_index,
+                       -value                           AS value
+                FROM entries
+                WHERE entry_type IN ('receive', 'send', 'fee')
+                AND NOT EXISTS (
+                    SELECT 1 FROM entries spends
+                    WHERE spends.txid = entries.txid
+                    AND spends.entry_type = 'spend'
+                )".to_owned(),
             // v_orchard_shard_scan_ranges
             format!(
                 "CREATE VIEW v_orchard_shard_scan_ranges AS
@@ -732,19 +1015,24 @@ mod tests {
             SELECT
                 2 AS pool,
                 sapling_received_note_id AS received_note_id,
-                transaction_id
+                transaction_id,
+                transactions.txid AS spending_txid
             FROM sapling_received_note_spends
+            JOIN transactions ON transactions.id_tx = transaction_id
             UNION
             SELECT
                 3 AS pool,
                 orchard_received_note_id AS received_note_id,
-                transaction_id
-            FROM orchard_received_note_spends".to_owned(),
+                transaction_id,
+                transactions.txid AS spending_txid
+            FROM orchard_received_note_spends
+            JOIN transactions ON transactions.id_tx = transaction_id".to_owned(),
             // v_received_notes
             "CREATE VIEW v_received_notes AS
                 SELECT
                     sapling_received_notes.id AS id_within_pool_table,
                     sapling_received_notes.tx,
+                    transactions.txid,
                     2 AS pool,
                     sapling_received_notes.output_index AS output_index,
                     account_id,
@@ -753,6 +1041,8 @@ mod tests {
                     sapling_received_notes.memo,
                     sent_notes.id AS sent_note_id
                 FROM sapling_received_notes
+                JOIN transactions
+                    ON transactions.id_tx = sapling_received_notes.tx
                 LEFT JOIN sent_notes
                 ON (sent_notes.tx, sent_notes.output_pool, sent_notes.output_index) =
                    (sapling_received_notes.tx, 2, sapling_received_notes.output_index)
@@ -760,6 +1050,7 @@ mod tests {
                 SELECT
                     orchard_received_notes.id AS id_within_pool_table,
                     orchard_received_notes.tx,
+                    transactions.txid,
                     3 AS pool,
                     orchard_received_notes.action_index AS output_index,
                     account_id,
@@ -768,6 +1059,8 @@ mod tests {
                     orchard_received_notes.memo,
                     sent_notes.id AS sent_note_id
                 FROM orchard_received_notes
+                JOIN transactions
+                    ON transactions.id_tx = orchard_received_notes.tx
                 LEFT JOIN sent_notes
                 ON (sent_notes.tx, sent_notes.output_pool, sent_notes.output_index) =
                    (orchard_received_notes.tx, 3, orchard_received_notes.action_index)".to_owned(),
@@ -858,7 +1151,8 @@ mod tests {
                              WHEN (v_received_notes.memo IS NULL OR v_received_notes.memo = X'F6')
                                THEN 0
                              ELSE 1
-                           END AS memo_present
+                           END AS memo_present,
+                           0                               AS is_spend
                     FROM v_received_notes
                     JOIN transactions
                          ON transactions.id_tx = v_received_notes.tx
@@ -872,7 +1166,8 @@ mod tests {
                            utxos.value_zat              AS value,
                            0                            AS is_change,
                            1                            AS received_count,
-                           0                            AS memo_present
+                           0                            AS memo_present,
+                           0                            AS is_spend
                     FROM utxos
                     UNION
                     -- Shielded notes spent in this transaction
@@ -884,7 +1179,8 @@ mod tests {
                            -v_received_notes.value      AS value,
                            0                            AS is_change,
                            0                            AS received_count,
-                           0                            AS memo_present
+                           0                            AS memo_present,
+                           1                            AS is_spend
                     FROM v_received_notes
                     JOIN v_received_note_spends rns
                          ON rns.pool = v_received_notes.pool
@@ -901,7 +1197,8 @@ mod tests {
                            -utxos.value_zat             AS value,
                            0                            AS is_change,
                            0                            AS received_count,
-                           0                            AS memo_present
+                           0                            AS memo_present,
+                           1                            AS is_spend
                     FROM utxos
                     JOIN transparent_received_output_spends tros
                          ON tros.transparent_received_output_id = utxos.id
@@ -920,7 +1217,9 @@ mod tests {
                                  THEN 0
                                ELSE 1
                              END
-                           ) AS memo_count
+                           ) AS memo_count,
+                           MAX(sent_notes.output_pool = 0) AS sent_transparent,
+                           MAX(sent_notes.output_pool != 0) AS sent_shielded
                     FROM sent_notes
                     JOIN transactions
                          ON transactions.id_tx = sent_notes.tx
@@ -948,7 +1247,23 @@ mod tests {
                        (
                             blocks.height IS NULL
                             AND transactions.expiry_height BETWEEN 1 AND blocks_max_height.max_height
-                       ) AS expired_unmined
+                       ) AS expired_unmined,
+                       CASE
+                            WHEN MAX(notes.pool = 0) = 0
+                                 AND MAX(COALESCE(sent_note_counts.sent_transparent, 0)) = 0
+                              THEN 'fully_shielded'
+                            WHEN MAX(notes.pool != 0) = 0
+                                 AND MAX(COALESCE(sent_note_counts.sent_shielded, 0)) = 0
+                              THEN 'transparent'
+                            WHEN MAX(notes.is_spend AND notes.pool != 0) = 1
+                                 AND (
+                                    MAX(NOT notes.is_spend AND notes.pool = 0) = 1
+                                    OR MAX(COALESCE(sent_note_counts.sent_transparent, 0)) = 1
+                                 )
+                              THEN 'deshielding'
+                            ELSE 'shielding'
+                       END AS privacy_level,
+                       COALESCE(transactions.spent_externally, 0) AS spent_externally
                 FROM notes
                 LEFT JOIN transactions
                      ON notes.txid = transactions.txid
@@ -1001,6 +1316,64 @@ mod tests {
                 LEFT JOIN v_received_notes
                     ON sent_notes.id = v_received_notes.sent_note_id
                 WHERE COALESCE(v_received_notes.is_change, 0) = 0".to_owned(),
+            // v_tx_outputs_contacts
+            "CREATE VIEW v_tx_outputs_contacts AS
+            SELECT v_tx_outputs.*,
+                   contacts.id   AS contact_id,
+                   contacts.name AS contact_name
+            FROM v_tx_outputs
+            LEFT JOIN contact_receivers
+                ON contact_receivers.address = v_tx_outputs.to_address
+            LEFT JOIN contacts
+                ON contacts.id = contact_receivers.contact_id".to_owned(),
+            // v_tx_received
+            "CREATE VIEW v_tx_received AS
+                SELECT transactions.id_tx                          AS id_tx,
+                       transactions.block                          AS mined_height,
+                       transactions.tx_index                       AS tx_index,
+                       transactions.txid                           AS txid,
+                       transactions.expiry_height                  AS expiry_height,
+                       transactions.raw                            AS raw,
+                       MAX(v_received_notes.account_id)            AS received_by_account,
+                       SUM(v_received_notes.value)                 AS received_total,
+                       COUNT(v_received_notes.id_within_pool_table) AS received_note_count,
+                       SUM(
+                           CASE
+                               WHEN v_received_notes.memo IS NULL THEN 0
+                               ELSE 1
+                           END
+                       ) AS memo_count,
+                       blocks.time                                 AS block_time
+                FROM   transactions
+                       JOIN v_received_notes
+                              ON transactions.id_tx = v_received_notes.tx
+                       LEFT JOIN blocks
+                              ON transactions.block = blocks.height
+                GROUP BY v_received_notes.tx, v_received_notes.account_id".to_owned(),
+            // v_tx_sent
+            "CREATE VIEW v_tx_sent AS
+                SELECT transactions.id_tx              AS id_tx,
+                       transactions.block              AS mined_height,
+                       transactions.tx_index           AS tx_index,
+                       transactions.txid               AS txid,
+                       transactions.expiry_height      AS expiry_height,
+                       transactions.raw                AS raw,
+                       MAX(sent_notes.from_account_id) AS sent_from_account,
+                       SUM(sent_notes.value)           AS sent_total,
+                       COUNT(sent_notes.id)            AS sent_note_count,
+                       SUM(
+                           CASE
+                               WHEN sent_notes.memo IS NULL THEN 0
+                               ELSE 1
+                           END
+                       ) AS memo_count,
+                       blocks.time                     AS block_time
+                FROM   transactions
+                       JOIN sent_notes
+                              ON transactions.id_tx = sent_notes.tx
+                       LEFT JOIN blocks
+                              ON transactions.block = blocks.height
+                GROUP BY sent_notes.tx, sent_notes.from_account_id".to_owned(),
         ];
 
         let mut views_query = st
@@ -0,0 +1,299 @@
+//! Data migrations that are applied in batches of rows.
+//!
+//! Each schema migration is applied within a single transaction. A migration that rewrites
+//! every row of a large table can take minutes on a big wallet, during which the application
+//! appears to hang, and all of its work is lost if the application is killed before it
+//! completes. Such work can instead be performed by a [`BatchedMigration`]: the schema migration
+//! schedules it by inserting its ID into the `batched_migrations` table, and once all schema
+//! migrations have been applied it is run a batch of rows at a time. Each batch is committed
+//! together with the ID of the last row processed, so that an interrupted migration resumes
+//! from where it left off the next time the wallet database is initialized.
+//!
+//! Schema migrations must not depend upon the results of batched migrations, because the latter
+//! only run after all schema migrations have been applied.
+
+use rusqlite::{named_params, OptionalExtension};
+use uuid::Uuid;
+
+use super::WalletMigrationError;
+
+/// The number of rows processed in each transaction by [`apply_batched_migrations`].
+pub(super) const BATCH_SIZE: u32 = 1000;
+
+/// The progress of a batched data migration.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MigrationProgress {
+    id: Uuid,
+    description: &'static str,
+    rows_processed: u64,
+    rows_total: u64,
+}
+
+impl MigrationProgress {
+    /// Returns the unique identifier of the migration.
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    /// Returns a description of the migration.
+    pub fn description(&self) -> &'static str {
+        self.description
+    }
+
+    /// Returns the number of rows that have been processed so far, including those processed
+    /// before the migration was interrupted.
+    pub fn rows_processed(&self) -> u64 {
+        self.rows_processed
+    }
+
+    /// Returns the total number of rows to be processed by the migration.
+    pub fn rows_total(&self) -> u64 {
+        self.rows_total
+    }
+}
+
+/// A data migration that processes the rows of a table in batches, in increasing order of
+/// row ID.
+pub(super) trait BatchedMigration {
+    /// Returns the unique identifier of the migration, as recorded in the
+    /// `batched_migrations` table when the migration is scheduled.
+    fn id(&self) -> Uuid;
+
+    /// Returns a description of the migration, suitable for display to the user.
+    fn description(&self) -> &'static str;
+
+    /// Returns the number of rows with IDs greater than `after` that remain to be processed.
+    fn rows_remaining(
+        &self,
+        conn: &rusqlite::Connection,
+        after: Option<i64>,
+    ) -> Result<u64, WalletMigrationError>;
+
+    /// Processes up to `limit` rows with IDs greater than `after`.
+    ///
+    /// Returns the ID of the last row processed and the number of rows processed, or `None`
+    /// if there were no rows left to process.
+    fn process_batch(
+        &self,
+        transaction: &rusqlite::Transaction<'_>,
+        after: Option<i64>,
+        limit: u32,
+    ) -> Result<Option<(i64, u64)>, WalletMigrationError>;
+}
+
+/// Applies each of the given migrations that has been scheduled and has not yet completed,
+/// reporting progress after each batch.
+pub(super) fn apply_batched_migrations(
+    conn: &mut rusqlite::Connection,
+    migrations: &[Box<dyn BatchedMigration>],
+    batch_size: u32,
+    progress: &mut dyn FnMut(&MigrationProgress),
+) -> Result<(), WalletMigrationError> {
+    for migration in migrations {
+        let migration_id = migration.id().to_string();
+        let (mut after, mut rows_processed) = match conn
+            .query_row(
+                "SELECT last_row_id, rows_processed
+                FROM batched_migrations
+                WHERE migration_id = :migration_id",
+                named_params![":migration_id": migration_id],
+                |row| Ok((row.get::<_, Option<i64>>(0)?, row.get::<_, u64>(1)?)),
+            )
+            .optional()?
+        {
+            Some(state) => state,
+            None => continue,
+        };
+
+        // Rows added while the migration is running may take the count above its initial
+        // estimate.
+        let rows_total = rows_processed + migration.rows_remaining(conn, after)?;
+        let report = |rows_processed: u64| MigrationProgress {
+            id: migration.id(),
+            description: migration.description(),
+            rows_processed,
+            rows_total: std::cmp::max(rows_total, rows_processed),
+        };
+        progress(&report(rows_processed));
+
+        loop {
+            let transaction = conn.transaction()?;
+            match migration.process_batch(&transaction, after, batch_size)? {
+                Some((last_row_id, count)) => {
+                    after = Some(last_row_id);
+                    rows_processed += count;
+                    transaction.execute(
+                        "UPDATE batched_migrations
+                        SET last_row_id = :last_row_id, rows_processed = :rows_processed
+                        WHERE migration_id = :migration_id",
+                        named_params![
+                            ":last_row_id": last_row_id,
+                            ":rows_processed": rows_processed,
+                            ":migration_id": migration_id,
+                        ],
+                    )?;
+                    transaction.commit()?;
+                    progress(&report(rows_processed));
+                }
+                None => {
+                    transaction.execute(
+                        "DELETE FROM batched_migrations WHERE migration_id = :migration_id",
+                        named_params![":migration_id": migration_id],
+                    )?;
+                    transaction.commit()?;
+                    break;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use rusqlite::named_params;
+    use uuid::Uuid;
+
+    use super::{apply_batched_migrations, BatchedMigration, MigrationProgress};
+    use crate::{testing::TestBuilder, wallet::init::WalletMigrationError};
+
+    const TEST_MIGRATION_ID: Uuid = Uuid::from_u128(0x0b5e7c3a_21f4_4d8e_9a6c_5f1e2d3c4b5a);
+
+    /// Doubles the values in a test table, failing once it has processed `fail_after` rows.
+    struct Doubler {
+        fail_after: Cell<Option<u64>>,
+    }
+
+    impl BatchedMigration for Doubler {
+        fn id(&self) -> Uuid {
+            TEST_MIGRATION_ID
+        }
+
+        fn description(&self) -> &'static str {
+            "Doubles the values in the test table."
+        }
+
+        fn rows_remaining(
+            &self,
+            conn: &rusqlite::Connection,
+            after: Option<i64>,
+        ) -> Result<u64, WalletMigrationError> {
+            Ok(conn.query_row(
+                "SELECT COUNT(*) FROM test_values WHERE id > :after",
+                named_params![":after": after.unwrap_or(i64::MIN)],
+                |row| row.get(0),
+            )?)
+        }
+
+        fn process_batch(
+            &self,
+            transaction: &rusqlite::Transaction<'_>,
+            after: Option<i64>,
+            limit: u32,
+        ) -> Result<Option<(i64, u64)>, WalletMigrationError> {
+            let ids = transaction
+                .prepare("SELECT id FROM test_values WHERE id > :after ORDER BY id LIMIT :limit")?
+                .query_map(
+                    named_params![":after": after.unwrap_or(i64::MIN), ":limit": limit],
+                    |row| row.get::<_, i64>(0),
+                )?
+                .collect::<Result<Vec<_>, _>>()?;
+
+            for id in &ids {
+                transaction.execute(
+                    "UPDATE test_values SET value = value * 2 WHERE id = :id",
+                    named_params![":id": id],
+                )?;
+                if let Some(remaining) = self.fail_after.get() {
+                    if remaining == 0 {
+                        return Err(WalletMigrationError::CorruptedData("interrupted".into()));
+                    }
+                    self.fail_after.set(Some(remaining - 1));
+                }
+            }
+
+            Ok(ids.last().map(|id| (*id, ids.len() as u64)))
+        }
+    }
+
+    #[test]
+    fn batched_migration_resumes() {
+        let mut st = TestBuilder::new().build();
+        let conn = &mut st.wallet_mut().conn;
+        conn.execute_batch(
+            "CREATE TABLE test_values (id INTEGER PRIMARY KEY, value INTEGER NOT NULL);
+            WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 25)
+            INSERT INTO test_values (id, value) SELECT i, i FROM n;",
+        )
+        .unwrap();
+
+        let values = |conn: &rusqlite::Connection| -> Vec<i64> {
+            conn.prepare("SELECT value FROM test_values ORDER BY id")
+                .unwrap()
+                .query_map([], |row| row.get(0))
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap()
+        };
+
+        // Migrations that have not been scheduled are not applied.
+        let migrations: Vec<Box<dyn BatchedMigration>> = vec![Box::new(Doubler {
+            fail_after: Cell::new(Some(12)),
+        })];
+        let mut reports = vec![];
+        apply_batched_migrations(conn, &migrations, 10, &mut |p| reports.push(p.clone())).unwrap();
+        assert!(reports.is_empty());
+        assert_eq!(values(conn), (1..=25).collect::<Vec<_>>());
+
+        // The migration fails partway through its second batch; the first batch is retained.
+        conn.execute(
+            "INSERT INTO batched_migrations (migration_id) VALUES (:migration_id)",
+            named_params![":migration_id": TEST_MIGRATION_ID.to_string()],
+        )
+        .unwrap();
+        assert!(
+            apply_batched_migrations(conn, &migrations, 10, &mut |p| reports.push(p.clone()))
+                .is_err()
+        );
+        assert_eq!(
+            values(conn),
+            (1..=25)
+                .map(|i| if i <= 10 { i * 2 } else { i })
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            reports
+                .iter()
+                .map(|p| p.rows_processed())
+                .collect::<Vec<_>>(),
+            vec![0, 10]
+        );
+
+        // Running the migration again resumes after the first batch.
+        let migrations: Vec<Box<dyn BatchedMigration>> = vec![Box::new(Doubler {
+            fail_after: Cell::new(None),
+        })];
+        let mut reports: Vec<MigrationProgress> = vec![];
+        apply_batched_migrations(conn, &migrations, 10, &mut |p| reports.push(p.clone())).unwrap();
+        assert_eq!(values(conn), (1..=25).map(|i| i * 2).collect::<Vec<_>>());
+        assert_eq!(
+            reports
+                .iter()
+                .map(|p| p.rows_processed())
+                .collect::<Vec<_>>(),
+            vec![10, 20, 25]
+        );
+        assert!(reports.iter().all(|p| p.rows_total() == 25));
+
+        // The completed migration is no longer scheduled.
+        let scheduled: i64 = conn
+            .query_row("SELECT COUNT(*) FROM batched_migrations", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(scheduled, 0);
+    }
+}
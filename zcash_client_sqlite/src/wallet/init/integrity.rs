@@ -0,0 +1,302 @@
+//! Detection of wallet databases that have been modified outside of this crate's migrations.
+//!
+//! As each migration is applied, the tables, views, indexes and triggers that it creates or
+//! redefines are recorded in the `schema_integrity` table, together with a hash of their SQL
+//! definitions and the identifier of the migration; objects that it drops are removed from the
+//! record. The next time the database is opened, and before any further migrations are
+//! applied, the recorded definitions are checked against the database. This detects databases
+//! whose schema has been altered out-of-band, as well as databases to which migrations that
+//! are not known to this version of the crate have been applied (for example, by a newer or
+//! forked version of the crate), before migrating such a database can cause further damage.
+//!
+//! Objects that are not created by this crate's migrations, such as views added by the
+//! application, are not recorded, and may be created, modified or dropped freely.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use rusqlite::{named_params, Connection};
+use schemer_rusqlite::RusqliteMigration;
+use uuid::Uuid;
+
+use super::{schema::applied_migration_ids, WalletMigrationError};
+
+const SCHEMA_HASH_PERSONALIZATION: &[u8; 16] = b"ZcashWalletSchma";
+
+/// Returns whether the database has a `schema_integrity` table.
+fn has_integrity_table(conn: &Connection) -> Result<bool, rusqlite::Error> {
+    conn.query_row(
+        "SELECT EXISTS (
+            SELECT 1 FROM sqlite_schema WHERE type = 'table' AND name = 'schema_integrity'
+        )",
+        [],
+        |row| row.get(0),
+    )
+}
+
+/// Returns the hash of the definition of each object in the database schema, keyed by the type
+/// and name of the object.
+fn schema_hashes(conn: &Connection) -> Result<HashMap<(String, String), Vec<u8>>, rusqlite::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT type, name, sql
+        FROM sqlite_schema
+        WHERE type IN ('table', 'view', 'index', 'trigger')
+        AND name NOT LIKE 'sqlite_%'
+        AND sql IS NOT NULL",
+    )?;
+    let rows = stmt
+        .query_map([], |row| {
+            let sql: String = row.get(2)?;
+            // SQLite stores the definitions of objects as they were written, so whitespace
+            // is normalized in order for formatting differences not to matter.
+            let normalized = sql.split_whitespace().collect::<Vec<_>>().join(" ");
+            let hash = blake2b_simd::Params::new()
+                .hash_length(32)
+                .personal(SCHEMA_HASH_PERSONALIZATION)
+                .hash(normalized.as_bytes());
+            Ok(((row.get(0)?, row.get(1)?), hash.as_bytes().to_vec()))
+        })?
+        .collect::<Result<_, _>>()?;
+    Ok(rows)
+}
+
+/// Checks that every migration applied to the database is one of `known_migrations`, and that
+/// none of the objects recorded as having been created by a migration has since been modified
+/// or removed.
+pub(super) fn verify(
+    conn: &Connection,
+    known_migrations: &HashSet<Uuid>,
+) -> Result<(), WalletMigrationError> {
+    if let Some(id) = applied_migration_ids(conn)?
+        .into_iter()
+        .find(|id| !known_migrations.contains(id))
+    {
+        return Err(WalletMigrationError::UnknownMigration(id));
+    }
+
+    if !has_integrity_table(conn)? {
+        return Ok(());
+    }
+
+    let current = schema_hashes(conn)?;
+    let mut stmt =
+        conn.prepare("SELECT type, name, sql_hash FROM schema_integrity ORDER BY name")?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let object_type: String = row.get(0)?;
+        let name: String = row.get(1)?;
+        let hash: Vec<u8> = row.get(2)?;
+        if current.get(&(object_type.clone(), name.clone())) != Some(&hash) {
+            return Err(WalletMigrationError::SchemaModified(format!(
+                "{} {}",
+                object_type, name
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// A change to the schema made by a migration.
+enum SchemaChange {
+    /// The migration created or redefined the object.
+    Defined {
+        migration_id: Uuid,
+        object_type: String,
+        name: String,
+        hash: Vec<u8>,
+    },
+    /// The migration dropped the object.
+    Dropped { object_type: String, name: String },
+}
+
+/// A migration whose changes to the schema are recorded in the `schema_integrity` table.
+///
+/// Changes made by migrations that are applied before the `schema_integrity` table has been
+/// created are held in memory, and are recorded by the first migration that is applied once
+/// the table exists.
+struct TrackedMigration {
+    inner: Box<dyn RusqliteMigration<Error = WalletMigrationError>>,
+    pending: Rc<RefCell<Vec<SchemaChange>>>,
+}
+
+impl schemer::Migration for TrackedMigration {
+    fn id(&self) -> Uuid {
+        self.inner.id()
+    }
+
+    fn dependencies(&self) -> HashSet<Uuid> {
+        self.inner.dependencies()
+    }
+
+    fn description(&self) -> &'static str {
+        self.inner.description()
+    }
+}
+
+impl RusqliteMigration for TrackedMigration {
+    type Error = WalletMigrationError;
+
+    fn up(&self, transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        let before = schema_hashes(transaction)?;
+        self.inner.up(transaction)?;
+        let after = schema_hashes(transaction)?;
+
+        let mut pending = self.pending.borrow_mut();
+        for ((object_type, name), hash) in &after {
+            if before.get(&(object_type.clone(), name.clone())) != Some(hash) {
+                pending.push(SchemaChange::Defined {
+                    migration_id: self.inner.id(),
+                    object_type: object_type.clone(),
+                    name: name.clone(),
+                    hash: hash.clone(),
+                });
+            }
+        }
+        for (object_type, name) in before.into_keys() {
+            if !after.contains_key(&(object_type.clone(), name.clone())) {
+                pending.push(SchemaChange::Dropped { object_type, name });
+            }
+        }
+
+        if has_integrity_table(transaction)? {
+            for change in pending.drain(..) {
+                match change {
+                    SchemaChange::Defined {
+                        migration_id,
+                        object_type,
+                        name,
+                        hash,
+                    } => transaction.execute(
+                        "INSERT INTO schema_integrity (name, type, sql_hash, migration_id)
+                        VALUES (:name, :type, :sql_hash, :migration_id)
+                        ON CONFLICT (name) DO UPDATE
+                        SET type = :type, sql_hash = :sql_hash, migration_id = :migration_id",
+                        named_params![
+                            ":name": name,
+                            ":type": object_type,
+                            ":sql_hash": hash,
+                            ":migration_id": migration_id.as_bytes().to_vec(),
+                        ],
+                    )?,
+                    SchemaChange::Dropped { object_type, name } => transaction.execute(
+                        "DELETE FROM schema_integrity WHERE name = :name AND type = :type",
+                        named_params![":name": name, ":type": object_type],
+                    )?,
+                };
+            }
+        }
+
+        Ok(())
+    }
+
+    fn down(&self, transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        self.inner.down(transaction)
+    }
+}
+
+/// Wraps the given migrations so that the objects that each of them creates, redefines or
+/// drops are recorded in the `schema_integrity` table as it is applied.
+pub(super) fn track(
+    migrations: Vec<Box<dyn RusqliteMigration<Error = WalletMigrationError>>>,
+) -> Vec<Box<dyn RusqliteMigration<Error = WalletMigrationError>>> {
+    let pending = Rc::new(RefCell::new(vec![]));
+    migrations
+        .into_iter()
+        .map(|inner| {
+            Box::new(TrackedMigration {
+                inner,
+                pending: pending.clone(),
+            }) as Box<dyn RusqliteMigration<Error = WalletMigrationError>>
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+    use schemer::MigratorError;
+    use uuid::Uuid;
+
+    use crate::{
+        testing::TestBuilder,
+        wallet::init::{init_wallet_db, WalletMigrationError},
+    };
+
+    #[test]
+    fn detects_out_of_band_changes() {
+        let mut st = TestBuilder::new().build();
+
+        // Reopening an unmodified database succeeds, as does reopening one to which the
+        // application has added objects of its own.
+        init_wallet_db(st.wallet_mut(), None).unwrap();
+        st.wallet()
+            .conn
+            .execute_batch("CREATE VIEW app_txids AS SELECT txid FROM transactions;")
+            .unwrap();
+        init_wallet_db(st.wallet_mut(), None).unwrap();
+
+        // Redefining a view created by the migrations is detected.
+        st.wallet()
+            .conn
+            .execute_batch(
+                "DROP VIEW v_tx_outputs;
+                CREATE VIEW v_tx_outputs AS SELECT txid FROM transactions;",
+            )
+            .unwrap();
+        assert_matches!(
+            init_wallet_db(st.wallet_mut(), None),
+            Err(MigratorError::Adapter(WalletMigrationError::SchemaModified(name)))
+                if name == "view v_tx_outputs"
+        );
+    }
+
+    #[test]
+    fn ignores_application_objects() {
+        let mut st = TestBuilder::new().build();
+
+        // Objects created by the application are not recorded, so the application may drop
+        // or redefine them between openings.
+        st.wallet()
+            .conn
+            .execute_batch("CREATE VIEW app_txids AS SELECT txid FROM transactions;")
+            .unwrap();
+        init_wallet_db(st.wallet_mut(), None).unwrap();
+        st.wallet()
+            .conn
+            .execute_batch("DROP VIEW app_txids;")
+            .unwrap();
+        init_wallet_db(st.wallet_mut(), None).unwrap();
+
+        let recorded: bool = st
+            .wallet()
+            .conn
+            .query_row(
+                "SELECT EXISTS (SELECT 1 FROM schema_integrity WHERE name = 'app_txids')",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(!recorded);
+    }
+
+    #[test]
+    fn detects_unknown_migrations() {
+        let mut st = TestBuilder::new().build();
+
+        let unknown = Uuid::from_u128(0x4f1c2b3a_5d6e_4f70_8a9b_0c1d2e3f4a5b);
+        st.wallet()
+            .conn
+            .execute(
+                "INSERT INTO schemer_migrations (id) VALUES (?)",
+                [unknown.as_bytes().to_vec()],
+            )
+            .unwrap();
+        assert_matches!(
+            init_wallet_db(st.wallet_mut(), None),
+            Err(MigratorError::Adapter(WalletMigrationError::UnknownMigration(id)))
+                if id == unknown
+        );
+    }
+}
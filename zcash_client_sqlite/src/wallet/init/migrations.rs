@@ -1,26 +1,48 @@
+mod account_backfill_ranges;
+mod account_balances_cache;
+mod account_id_bounds;
+mod account_scanning_flag;
 mod add_account_birthdays;
 mod add_transaction_views;
 mod add_utxo_account;
+mod address_usage_heights;
 mod addresses_table;
+mod backup_tracking;
+mod batched_migrations_table;
+mod contacts;
+mod deprecated_view_shims;
 mod ensure_orchard_ua_receiver;
+mod external_spends;
 mod full_account_ids;
 mod initial_setup;
+mod lookahead_spends;
 mod nullifier_map;
 mod orchard_received_notes;
 mod orchard_shardtree;
+mod pending_change_balances;
 mod received_notes_nullable_nf;
 mod receiving_key_scopes;
 mod sapling_memo_consistency;
+mod scheduled_payments;
+mod schema_integrity;
 mod sent_notes_to_internal;
 mod shardtree_support;
+mod transparent_address_checks;
+mod tx_retrieval_queue;
 mod ufvk_support;
+mod utxos_coinbase;
 mod utxos_table;
+mod v_ledger;
+mod v_received_notes_txid;
 mod v_sapling_shard_unscanned_ranges;
 mod v_transactions_net;
 mod v_transactions_note_uniqueness;
+mod v_transactions_privacy_level;
 mod v_transactions_shielding_balance;
 mod v_transactions_transparent_history;
 mod v_tx_outputs_use_legacy_false;
+mod wallet_events;
+mod wallet_settings;
 mod wallet_summaries;
 
 use std::rc::Rc;
@@ -29,7 +51,7 @@ use schemer_rusqlite::RusqliteMigration;
 use secrecy::SecretVec;
 use zcash_protocol::consensus;
 
-use super::WalletMigrationError;
+use super::{batched::BatchedMigration, WalletMigrationError};
 
 pub(super) fn all_migrations<P: consensus::Parameters + 'static>(
     params: &P,
@@ -65,6 +87,50 @@ pub(super) fn all_migrations<P: consensus::Parameters + 'static>(
     //                                             orchard_received_notes
     //                                                       |
     //                                           ensure_orchard_ua_receiver
+    //                                                       |
+    //                                           v_transactions_privacy_level
+    //                                                       |
+    //                                             account_balances_cache
+    //                                                       |
+    //                                               account_id_bounds
+    //                                                       |
+    //                                              v_received_notes_txid
+    //                                                       |
+    //                                                    v_ledger
+    //                                                       |
+    //                                               tx_retrieval_queue
+    //                                                       |
+    //                                                utxos_coinbase
+    //                                                       |
+    //                                             address_usage_heights
+    //                                                       |
+    //                                            batched_migrations_table
+    //                                                       |
+    //                                             deprecated_view_shims
+    //                                                       |
+    //                                                 wallet_events
+    //                                                       |
+    //                                                schema_integrity
+    //                                                       |
+    //                                             account_scanning_flag
+    //                                                       |
+    //                                            account_backfill_ranges
+    //                                                       |
+    //                                                    contacts
+    //                                                       |
+    //                                              scheduled_payments
+    //                                                       |
+    //                                                external_spends
+    //                                                       |
+    //                                                wallet_settings
+    //                                                       |
+    //                                                backup_tracking
+    //                                                       |
+    //                                            pending_change_balances
+    //                                                       |
+    //                                                lookahead_spends
+    //                                                       |
+    //                                           transparent_address_checks
     vec![
         Box::new(initial_setup::Migration {}),
         Box::new(utxos_table::Migration {}),
@@ -114,5 +180,39 @@ pub(super) fn all_migrations<P: consensus::Parameters + 'static>(
         Box::new(ensure_orchard_ua_receiver::Migration {
             params: params.clone(),
         }),
+        Box::new(v_transactions_privacy_level::Migration),
+        Box::new(account_balances_cache::Migration),
+        Box::new(account_id_bounds::Migration),
+        Box::new(v_received_notes_txid::Migration),
+        Box::new(v_ledger::Migration),
+        Box::new(tx_retrieval_queue::Migration),
+        Box::new(utxos_coinbase::Migration {
+            params: params.clone(),
+        }),
+        Box::new(address_usage_heights::Migration {
+            params: params.clone(),
+        }),
+        Box::new(batched_migrations_table::Migration),
+        Box::new(deprecated_view_shims::Migration),
+        Box::new(wallet_events::Migration),
+        Box::new(schema_integrity::Migration),
+        Box::new(account_scanning_flag::Migration),
+        Box::new(account_backfill_ranges::Migration),
+        Box::new(contacts::Migration),
+        Box::new(scheduled_payments::Migration),
+        Box::new(external_spends::Migration),
+        Box::new(wallet_settings::Migration),
+        Box::new(backup_tracking::Migration),
+        Box::new(pending_change_balances::Migration),
+        Box::new(lookahead_spends::Migration),
+        Box::new(transparent_address_checks::Migration),
     ]
 }
+
+/// Returns the data migrations that may be scheduled by the schema migrations returned by
+/// [`all_migrations`], in the order in which they are to be applied.
+pub(super) fn all_batched_migrations<P: consensus::Parameters + 'static>(
+    _params: &P,
+) -> Vec<Box<dyn BatchedMigration>> {
+    vec![]
+}
@@ -0,0 +1,56 @@
+//! This migration adds the `account_backfill_ranges` table, which records the ranges of blocks
+//! that have been scanned for some of the wallet's accounts but not yet for others.
+
+use std::collections::HashSet;
+
+use schemer_rusqlite::RusqliteMigration;
+use uuid::Uuid;
+
+use super::account_scanning_flag;
+use crate::wallet::init::WalletMigrationError;
+
+pub(super) const MIGRATION_ID: Uuid = Uuid::from_u128(0xc3d7e915_2b6a_4f48_a1e0_8d94b5c27f13);
+
+pub(super) struct Migration;
+
+impl schemer::Migration for Migration {
+    fn id(&self) -> Uuid {
+        MIGRATION_ID
+    }
+
+    fn dependencies(&self) -> HashSet<Uuid> {
+        [account_scanning_flag::MIGRATION_ID].into_iter().collect()
+    }
+
+    fn description(&self) -> &'static str {
+        "Tracks the blocks that remain to be scanned for individual accounts."
+    }
+}
+
+impl RusqliteMigration for Migration {
+    type Error = WalletMigrationError;
+
+    fn up(&self, transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        // Existing wallets have scanned every block for all of their accounts, so the table
+        // starts out empty.
+        transaction.execute_batch(
+            "CREATE TABLE account_backfill_ranges (
+                account_id INTEGER NOT NULL,
+                block_range_start INTEGER NOT NULL,
+                block_range_end INTEGER NOT NULL,
+                FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE,
+                CONSTRAINT range_bounds_order CHECK (
+                    block_range_start < block_range_end
+                )
+            );
+            CREATE INDEX account_backfill_ranges_start
+                ON account_backfill_ranges (block_range_start);",
+        )?;
+        Ok(())
+    }
+
+    fn down(&self, transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        transaction.execute_batch("DROP TABLE account_backfill_ranges;")?;
+        Ok(())
+    }
+}
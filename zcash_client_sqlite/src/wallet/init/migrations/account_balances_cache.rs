@@ -0,0 +1,161 @@
+//! This migration adds the `account_balances` table, which caches the per-account balances
+//! computed by `get_wallet_summary`, along with triggers that invalidate the cache whenever
+//! the data from which the balances are computed is modified in a way that can change them.
+
+use std::collections::HashSet;
+
+use schemer_rusqlite::RusqliteMigration;
+use uuid::Uuid;
+use zcash_client_backend::data_api::scanning::ScanPriority;
+
+use super::v_transactions_privacy_level;
+use crate::wallet::{init::WalletMigrationError, scanning::priority_code};
+
+pub(super) const MIGRATION_ID: Uuid = Uuid::from_u128(0x3cab6b05_ea64_4a1c_8b99_eb109f88b743);
+
+/// The tables for which every change can affect the balances reported by `get_wallet_summary`.
+const BALANCE_SOURCE_TABLES: &[&str] = &[
+    "sapling_received_notes",
+    "sapling_received_note_spends",
+    "orchard_received_notes",
+    "orchard_received_note_spends",
+    "utxos",
+    "transparent_received_output_spends",
+];
+
+pub(super) struct Migration;
+
+impl schemer::Migration for Migration {
+    fn id(&self) -> Uuid {
+        MIGRATION_ID
+    }
+
+    fn dependencies(&self) -> HashSet<Uuid> {
+        [v_transactions_privacy_level::MIGRATION_ID]
+            .into_iter()
+            .collect()
+    }
+
+    fn description(&self) -> &'static str {
+        "Adds a cache of account balances that is invalidated by changes to the wallet's notes."
+    }
+}
+
+impl RusqliteMigration for Migration {
+    type Error = WalletMigrationError;
+
+    fn up(&self, transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        transaction.execute_batch(
+            "CREATE TABLE account_balances (
+                account_id INTEGER NOT NULL,
+                min_confirmations INTEGER NOT NULL,
+                min_note_value INTEGER NOT NULL,
+                sapling_spendable_value INTEGER NOT NULL,
+                sapling_change_pending_confirmation INTEGER NOT NULL,
+                sapling_value_pending_spendability INTEGER NOT NULL,
+                orchard_spendable_value INTEGER NOT NULL,
+                orchard_change_pending_confirmation INTEGER NOT NULL,
+                orchard_value_pending_spendability INTEGER NOT NULL,
+                unshielded_value INTEGER NOT NULL,
+                PRIMARY KEY (account_id, min_confirmations, min_note_value),
+                FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+            );",
+        )?;
+
+        // Any change to the data from which balances are computed invalidates every cached
+        // balance. The triggers run within the transaction that makes the change, so the cache
+        // can never be observed in a state that is inconsistent with the wallet's notes.
+        for table in BALANCE_SOURCE_TABLES {
+            for op in ["INSERT", "UPDATE", "DELETE"] {
+                transaction.execute_batch(&format!(
+                    "CREATE TRIGGER {table}_{}_invalidate_balances
+                    AFTER {op} ON {table}
+                    BEGIN
+                        DELETE FROM account_balances;
+                    END;",
+                    op.to_lowercase(),
+                ))?;
+            }
+        }
+
+        // Only some changes to the remaining tables can affect balances:
+        // - Accounts contribute to balances only through their notes and outputs, but a new
+        //   account must be given a (zero) balance.
+        // - A transaction affects the balance only once it has received or spent a note, and
+        //   then only through its mined height and its expiry height.
+        // - Shard metadata affects whether a note can be spent only through the end height of
+        //   each shard; the shard data itself is updated for every scanned block.
+        // - The scan queue affects balances only when a range of unscanned blocks is added,
+        //   such as when the chain tip advances, or removed, such as when it is scanned.
+        let scanned = priority_code(&ScanPriority::Scanned);
+        transaction.execute_batch(&format!(
+            "CREATE TRIGGER accounts_insert_invalidate_balances
+            AFTER INSERT ON accounts
+            BEGIN
+                DELETE FROM account_balances;
+            END;
+            CREATE TRIGGER accounts_delete_invalidate_balances
+            AFTER DELETE ON accounts
+            BEGIN
+                DELETE FROM account_balances;
+            END;
+
+            CREATE TRIGGER transactions_update_invalidate_balances
+            AFTER UPDATE OF block, expiry_height ON transactions
+            WHEN OLD.block IS NOT NEW.block OR OLD.expiry_height IS NOT NEW.expiry_height
+            BEGIN
+                DELETE FROM account_balances;
+            END;
+            CREATE TRIGGER transactions_delete_invalidate_balances
+            AFTER DELETE ON transactions
+            BEGIN
+                DELETE FROM account_balances;
+            END;
+
+            CREATE TRIGGER scan_queue_insert_invalidate_balances
+            AFTER INSERT ON scan_queue
+            WHEN NEW.priority > {scanned}
+            BEGIN
+                DELETE FROM account_balances;
+            END;
+            CREATE TRIGGER scan_queue_update_invalidate_balances
+            AFTER UPDATE ON scan_queue
+            BEGIN
+                DELETE FROM account_balances;
+            END;
+            CREATE TRIGGER scan_queue_delete_invalidate_balances
+            AFTER DELETE ON scan_queue
+            WHEN OLD.priority > {scanned}
+            BEGIN
+                DELETE FROM account_balances;
+            END;"
+        ))?;
+
+        for pool in ["sapling", "orchard"] {
+            transaction.execute_batch(&format!(
+                "CREATE TRIGGER {pool}_tree_shards_insert_invalidate_balances
+                AFTER INSERT ON {pool}_tree_shards
+                BEGIN
+                    DELETE FROM account_balances;
+                END;
+                CREATE TRIGGER {pool}_tree_shards_update_invalidate_balances
+                AFTER UPDATE OF subtree_end_height ON {pool}_tree_shards
+                WHEN OLD.subtree_end_height IS NOT NEW.subtree_end_height
+                BEGIN
+                    DELETE FROM account_balances;
+                END;
+                CREATE TRIGGER {pool}_tree_shards_delete_invalidate_balances
+                AFTER DELETE ON {pool}_tree_shards
+                BEGIN
+                    DELETE FROM account_balances;
+                END;"
+            ))?;
+        }
+
+        Ok(())
+    }
+
+    fn down(&self, _transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        Err(WalletMigrationError::CannotRevert(MIGRATION_ID))
+    }
+}
@@ -0,0 +1,125 @@
+//! This migration checks that the identifiers of existing accounts are within the ranges
+//! supported by the wallet, and adds triggers that prevent out-of-range identifiers from being
+//! written to the `accounts` table.
+//!
+//! Account row identifiers are exposed as [`AccountId`], which wraps a `u32`, and ZIP 32
+//! account indices must be less than 2^31.
+//!
+//! [`AccountId`]: crate::AccountId
+
+use std::collections::HashSet;
+
+use schemer_rusqlite::RusqliteMigration;
+use uuid::Uuid;
+
+use super::account_balances_cache;
+use crate::wallet::init::WalletMigrationError;
+
+pub(super) const MIGRATION_ID: Uuid = Uuid::from_u128(0x6e1a3f2d_84c7_4b0e_9d55_2f7c3a91e0b4);
+
+/// Returns the condition under which an `accounts` row has an out-of-range identifier, with
+/// the row's columns qualified by the given prefix.
+fn out_of_range(row: &str) -> String {
+    format!(
+        "{row}id < 0 OR {row}id > 4294967295
+        OR {row}hd_account_index < 0 OR {row}hd_account_index > 2147483647"
+    )
+}
+
+pub(super) struct Migration;
+
+impl schemer::Migration for Migration {
+    fn id(&self) -> Uuid {
+        MIGRATION_ID
+    }
+
+    fn dependencies(&self) -> HashSet<Uuid> {
+        [account_balances_cache::MIGRATION_ID].into_iter().collect()
+    }
+
+    fn description(&self) -> &'static str {
+        "Enforces the bounds of account identifiers and ZIP 32 account indices."
+    }
+}
+
+impl RusqliteMigration for Migration {
+    type Error = WalletMigrationError;
+
+    fn up(&self, transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        let invalid_accounts: u32 = transaction.query_row(
+            &format!("SELECT COUNT(*) FROM accounts WHERE {}", out_of_range("")),
+            [],
+            |row| row.get(0),
+        )?;
+        if invalid_accounts > 0 {
+            return Err(WalletMigrationError::CorruptedData(format!(
+                "{} accounts have identifiers that are out of range.",
+                invalid_accounts
+            )));
+        }
+
+        // The row ID of an inserted account is only known after the insertion, so these
+        // triggers must run after the row is written; raising an error aborts the statement.
+        for op in ["INSERT", "UPDATE"] {
+            transaction.execute_batch(&format!(
+                "CREATE TRIGGER accounts_{}_check_id_bounds
+                AFTER {op} ON accounts
+                WHEN {}
+                BEGIN
+                    SELECT RAISE(ABORT, 'account identifier out of range');
+                END;",
+                op.to_lowercase(),
+                out_of_range("NEW."),
+            ))?;
+        }
+
+        Ok(())
+    }
+
+    fn down(&self, _transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        Err(WalletMigrationError::CannotRevert(MIGRATION_ID))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::named_params;
+    use secrecy::SecretVec;
+    use tempfile::NamedTempFile;
+
+    use zcash_client_backend::data_api::{AccountBirthday, WalletRead, WalletWrite};
+    use zcash_primitives::{block::BlockHash, consensus::Network};
+
+    use crate::{wallet::init::init_wallet_db, WalletDb};
+
+    #[test]
+    fn out_of_range_account_index_rejected() {
+        let data_file = NamedTempFile::new().unwrap();
+        let mut db_data = WalletDb::for_path(data_file.path(), Network::TestNetwork).unwrap();
+        let seed = SecretVec::new(vec![0x10; 32]);
+        init_wallet_db(&mut db_data, Some(seed.clone())).unwrap();
+
+        let birthday =
+            AccountBirthday::from_sapling_activation(&db_data.params, BlockHash([0; 32]));
+        let (account_id, _) = db_data.create_account(&seed, &birthday).unwrap();
+        assert_eq!(db_data.get_account_ids().unwrap(), vec![account_id]);
+
+        // Writing a ZIP 32 account index that is out of range fails.
+        assert!(db_data
+            .conn
+            .execute(
+                "UPDATE accounts SET hd_account_index = :index WHERE id = :account_id",
+                named_params![":index": 1u32 << 31, ":account_id": account_id],
+            )
+            .is_err());
+
+        // Writing one that is in range succeeds.
+        db_data
+            .conn
+            .execute(
+                "UPDATE accounts SET hd_account_index = :index WHERE id = :account_id",
+                named_params![":index": (1u32 << 31) - 1, ":account_id": account_id],
+            )
+            .unwrap();
+    }
+}
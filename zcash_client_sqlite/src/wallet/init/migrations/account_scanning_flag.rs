@@ -0,0 +1,46 @@
+//! This migration adds the `enabled_for_scanning` and `scanning_disabled_height` columns to the
+//! `accounts` table, which allow accounts to be excluded from trial decryption when scanning.
+
+use std::collections::HashSet;
+
+use schemer_rusqlite::RusqliteMigration;
+use uuid::Uuid;
+
+use super::schema_integrity;
+use crate::wallet::init::WalletMigrationError;
+
+pub(super) const MIGRATION_ID: Uuid = Uuid::from_u128(0x4f2a9c71_d6e8_4b03_8c5a_e17b3d90f2a6);
+
+pub(super) struct Migration;
+
+impl schemer::Migration for Migration {
+    fn id(&self) -> Uuid {
+        MIGRATION_ID
+    }
+
+    fn dependencies(&self) -> HashSet<Uuid> {
+        [schema_integrity::MIGRATION_ID].into_iter().collect()
+    }
+
+    fn description(&self) -> &'static str {
+        "Allows accounts to be excluded from scanning."
+    }
+}
+
+impl RusqliteMigration for Migration {
+    type Error = WalletMigrationError;
+
+    fn up(&self, transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        // All existing accounts remain enabled. `scanning_disabled_height` is the height of the
+        // first block that may not have been scanned for an account while it was disabled.
+        transaction.execute_batch(
+            "ALTER TABLE accounts ADD COLUMN enabled_for_scanning INTEGER NOT NULL DEFAULT 1;
+            ALTER TABLE accounts ADD COLUMN scanning_disabled_height INTEGER;",
+        )?;
+        Ok(())
+    }
+
+    fn down(&self, _transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        Err(WalletMigrationError::CannotRevert(MIGRATION_ID))
+    }
+}
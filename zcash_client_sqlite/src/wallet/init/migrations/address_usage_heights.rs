@@ -0,0 +1,154 @@
+//! This migration adds `first_used_height` and `last_used_height` columns to the `addresses`
+//! table, which record the range of heights at which the wallet has observed mined transactions
+//! paying to each address.
+
+use std::collections::HashSet;
+
+use rusqlite::{named_params, OptionalExtension};
+use schemer_rusqlite::RusqliteMigration;
+use uuid::Uuid;
+use zcash_keys::address::Address;
+use zcash_primitives::consensus;
+use zip32::Scope;
+
+use super::utxos_coinbase;
+use crate::wallet::{init::WalletMigrationError, scope_code};
+
+pub(super) const MIGRATION_ID: Uuid = Uuid::from_u128(0x8a7cdaad_c9df_4c93_b1ba_d1fbd475a434);
+
+pub(super) struct Migration<P> {
+    pub(super) params: P,
+}
+
+impl<P> schemer::Migration for Migration<P> {
+    fn id(&self) -> Uuid {
+        MIGRATION_ID
+    }
+
+    fn dependencies(&self) -> HashSet<Uuid> {
+        [utxos_coinbase::MIGRATION_ID].into_iter().collect()
+    }
+
+    fn description(&self) -> &'static str {
+        "Records the heights at which each of the wallet's addresses was first and last used."
+    }
+}
+
+impl<P: consensus::Parameters> RusqliteMigration for Migration<P> {
+    type Error = WalletMigrationError;
+
+    fn up(&self, transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        transaction.execute_batch(
+            "ALTER TABLE addresses ADD COLUMN first_used_height INTEGER;
+            ALTER TABLE addresses ADD COLUMN last_used_height INTEGER;",
+        )?;
+
+        // Transparent receivers are cached in the `addresses` table, so their usage can be
+        // determined directly.
+        transaction.execute_batch(
+            "UPDATE addresses
+            SET first_used_height = (
+                    SELECT MIN(utxos.height) FROM utxos
+                    WHERE utxos.address = addresses.cached_transparent_receiver_address
+                ),
+                last_used_height = (
+                    SELECT MAX(utxos.height) FROM utxos
+                    WHERE utxos.address = addresses.cached_transparent_receiver_address
+                )
+            WHERE cached_transparent_receiver_address IS NOT NULL;",
+        )?;
+
+        // Shielded receivers are identified by their diversifier. Only notes received via the
+        // account's external key scope can correspond to an entry in the `addresses` table.
+        let mut stmt_addresses =
+            transaction.prepare("SELECT rowid, account_id, address FROM addresses")?;
+        let mut stmt_sapling_heights = transaction.prepare(
+            "SELECT MIN(t.block), MAX(t.block)
+            FROM sapling_received_notes rn
+            JOIN transactions t ON t.id_tx = rn.tx
+            WHERE rn.account_id = :account_id
+            AND rn.diversifier = :diversifier
+            AND rn.recipient_key_scope = :external_scope
+            AND t.block IS NOT NULL",
+        )?;
+        #[cfg(feature = "orchard")]
+        let mut stmt_orchard_heights = transaction.prepare(
+            "SELECT MIN(t.block), MAX(t.block)
+            FROM orchard_received_notes rn
+            JOIN transactions t ON t.id_tx = rn.tx
+            WHERE rn.account_id = :account_id
+            AND rn.diversifier = :diversifier
+            AND rn.recipient_key_scope = :external_scope
+            AND t.block IS NOT NULL",
+        )?;
+        let mut stmt_update = transaction.prepare(
+            "UPDATE addresses
+            SET first_used_height = MIN(IFNULL(first_used_height, :first), :first),
+                last_used_height = MAX(IFNULL(last_used_height, :last), :last)
+            WHERE rowid = :rowid",
+        )?;
+
+        let mut rows = stmt_addresses.query([])?;
+        while let Some(row) = rows.next()? {
+            let rowid: i64 = row.get(0)?;
+            let account_id: u32 = row.get(1)?;
+            let addr_str: String = row.get(2)?;
+            let ua = match Address::decode(&self.params, &addr_str) {
+                Some(Address::Unified(ua)) => ua,
+                _ => {
+                    return Err(WalletMigrationError::CorruptedData(format!(
+                        "Addresses table contains {} which is not a unified address",
+                        addr_str
+                    )));
+                }
+            };
+
+            let mut ranges: Vec<(Option<u32>, Option<u32>)> = vec![];
+            if let Some(sapling) = ua.sapling() {
+                ranges.extend(
+                    stmt_sapling_heights
+                        .query_row(
+                            named_params![
+                                ":account_id": account_id,
+                                ":diversifier": &sapling.diversifier().0[..],
+                                ":external_scope": scope_code(Scope::External),
+                            ],
+                            |row| Ok((row.get(0)?, row.get(1)?)),
+                        )
+                        .optional()?,
+                );
+            }
+            #[cfg(feature = "orchard")]
+            if let Some(orchard) = ua.orchard() {
+                ranges.extend(
+                    stmt_orchard_heights
+                        .query_row(
+                            named_params![
+                                ":account_id": account_id,
+                                ":diversifier": &orchard.diversifier().as_array()[..],
+                                ":external_scope": scope_code(Scope::External),
+                            ],
+                            |row| Ok((row.get(0)?, row.get(1)?)),
+                        )
+                        .optional()?,
+                );
+            }
+
+            let first = ranges.iter().filter_map(|(first, _)| *first).min();
+            let last = ranges.iter().filter_map(|(_, last)| *last).max();
+            if let (Some(first), Some(last)) = (first, last) {
+                stmt_update.execute(named_params![
+                    ":rowid": rowid,
+                    ":first": first,
+                    ":last": last,
+                ])?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn down(&self, _transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        Err(WalletMigrationError::CannotRevert(MIGRATION_ID))
+    }
+}
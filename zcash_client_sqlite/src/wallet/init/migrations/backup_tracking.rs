@@ -0,0 +1,43 @@
+//! This migration adds the `backup_verified_height` column to the `accounts` table, which records
+//! the chain tip height as of which the user last verified their backup of the account's keys.
+
+use std::collections::HashSet;
+
+use schemer_rusqlite::RusqliteMigration;
+use uuid::Uuid;
+
+use super::wallet_settings;
+use crate::wallet::init::WalletMigrationError;
+
+pub(super) const MIGRATION_ID: Uuid = Uuid::from_u128(0x9b1f04d6_73ce_4a2b_8e51_d2c86a03f4e9);
+
+pub(super) struct Migration;
+
+impl schemer::Migration for Migration {
+    fn id(&self) -> Uuid {
+        MIGRATION_ID
+    }
+
+    fn dependencies(&self) -> HashSet<Uuid> {
+        [wallet_settings::MIGRATION_ID].into_iter().collect()
+    }
+
+    fn description(&self) -> &'static str {
+        "Records the height at which the backup of each account was last verified."
+    }
+}
+
+impl RusqliteMigration for Migration {
+    type Error = WalletMigrationError;
+
+    fn up(&self, transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        // The backups of existing accounts are treated as never having been verified.
+        transaction
+            .execute_batch("ALTER TABLE accounts ADD COLUMN backup_verified_height INTEGER;")?;
+        Ok(())
+    }
+
+    fn down(&self, _transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        Err(WalletMigrationError::CannotRevert(MIGRATION_ID))
+    }
+}
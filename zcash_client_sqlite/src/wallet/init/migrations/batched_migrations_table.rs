@@ -0,0 +1,51 @@
+//! This migration adds the `batched_migrations` table, which records the progress of data
+//! migrations that are applied a batch of rows at a time after the schema has been migrated.
+
+use std::collections::HashSet;
+
+use schemer_rusqlite::RusqliteMigration;
+use uuid::Uuid;
+
+use super::address_usage_heights;
+use crate::wallet::init::WalletMigrationError;
+
+pub(super) const MIGRATION_ID: Uuid = Uuid::from_u128(0xc617e291_7dbd_45e7_82d9_616354af68d4);
+
+pub(super) struct Migration;
+
+impl schemer::Migration for Migration {
+    fn id(&self) -> Uuid {
+        MIGRATION_ID
+    }
+
+    fn dependencies(&self) -> HashSet<Uuid> {
+        [address_usage_heights::MIGRATION_ID].into_iter().collect()
+    }
+
+    fn description(&self) -> &'static str {
+        "Adds a table recording the progress of batched data migrations."
+    }
+}
+
+impl RusqliteMigration for Migration {
+    type Error = WalletMigrationError;
+
+    fn up(&self, transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        // A row is inserted by the schema migration that schedules a batched migration, and
+        // is removed once the batched migration has processed all of its rows.
+        transaction.execute_batch(
+            "CREATE TABLE batched_migrations (
+                migration_id TEXT NOT NULL PRIMARY KEY,
+                last_row_id INTEGER,
+                rows_processed INTEGER NOT NULL DEFAULT 0
+            );",
+        )?;
+
+        Ok(())
+    }
+
+    fn down(&self, transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        transaction.execute_batch("DROP TABLE batched_migrations;")?;
+        Ok(())
+    }
+}
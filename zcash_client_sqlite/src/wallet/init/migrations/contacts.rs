@@ -0,0 +1,74 @@
+//! This migration adds the `contacts` and `contact_receivers` tables, which form an address
+//! book of labeled external addresses, and the `v_tx_outputs_contacts` view, which annotates
+//! the rows of `v_tx_outputs` with the name of the contact to which each output was sent.
+
+use std::collections::HashSet;
+
+use schemer_rusqlite::RusqliteMigration;
+use uuid::Uuid;
+
+use super::account_backfill_ranges;
+use crate::wallet::init::WalletMigrationError;
+
+pub(super) const MIGRATION_ID: Uuid = Uuid::from_u128(0x5b1e8f47_93c2_4d6a_b8f1_2e7a90c4d316);
+
+pub(super) struct Migration;
+
+impl schemer::Migration for Migration {
+    fn id(&self) -> Uuid {
+        MIGRATION_ID
+    }
+
+    fn dependencies(&self) -> HashSet<Uuid> {
+        [account_backfill_ranges::MIGRATION_ID]
+            .into_iter()
+            .collect()
+    }
+
+    fn description(&self) -> &'static str {
+        "Adds an address book of external contacts."
+    }
+}
+
+impl RusqliteMigration for Migration {
+    type Error = WalletMigrationError;
+
+    fn up(&self, transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        // Each contact's address is recorded in `contact_receivers` along with the standalone
+        // encoding of each of its receivers, so that outputs sent to any receiver of a
+        // contact's Unified Address can be attributed to the contact.
+        transaction.execute_batch(
+            "CREATE TABLE contacts (
+                id INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                address TEXT NOT NULL UNIQUE
+            );
+            CREATE TABLE contact_receivers (
+                contact_id INTEGER NOT NULL,
+                address TEXT NOT NULL UNIQUE,
+                FOREIGN KEY (contact_id) REFERENCES contacts(id) ON DELETE CASCADE
+            );
+            CREATE INDEX contact_receivers_contact ON contact_receivers (contact_id);
+
+            CREATE VIEW v_tx_outputs_contacts AS
+            SELECT v_tx_outputs.*,
+                   contacts.id   AS contact_id,
+                   contacts.name AS contact_name
+            FROM v_tx_outputs
+            LEFT JOIN contact_receivers
+                ON contact_receivers.address = v_tx_outputs.to_address
+            LEFT JOIN contacts
+                ON contacts.id = contact_receivers.contact_id;",
+        )?;
+        Ok(())
+    }
+
+    fn down(&self, transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        transaction.execute_batch(
+            "DROP VIEW v_tx_outputs_contacts;
+            DROP TABLE contact_receivers;
+            DROP TABLE contacts;",
+        )?;
+        Ok(())
+    }
+}
@@ -0,0 +1,117 @@
+//! This migration restores the `v_tx_received` and `v_tx_sent` views, which were dropped by
+//! [`v_transactions_net`], as compatibility wrappers over the current schema, and adds the
+//! `deprecated_views` table that records the views that are retained only for compatibility.
+//!
+//! [`v_transactions_net`]: super::v_transactions_net
+
+use std::collections::HashSet;
+
+use schemer_rusqlite::RusqliteMigration;
+use uuid::Uuid;
+
+use super::batched_migrations_table;
+use crate::wallet::init::WalletMigrationError;
+
+pub(super) const MIGRATION_ID: Uuid = Uuid::from_u128(0x5d1c6a8e_3f0b_4c27_a9e4_7b2d81f06c39);
+
+pub(super) struct Migration;
+
+impl schemer::Migration for Migration {
+    fn id(&self) -> Uuid {
+        MIGRATION_ID
+    }
+
+    fn dependencies(&self) -> HashSet<Uuid> {
+        [batched_migrations_table::MIGRATION_ID]
+            .into_iter()
+            .collect()
+    }
+
+    fn description(&self) -> &'static str {
+        "Restores the v_tx_received and v_tx_sent views as deprecated compatibility wrappers."
+    }
+}
+
+impl RusqliteMigration for Migration {
+    type Error = WalletMigrationError;
+
+    fn up(&self, transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        // Each deprecated view is retained until the crate release given by `removal_version`,
+        // at which point a migration drops both the view and its row in this table.
+        transaction.execute_batch(
+            "CREATE TABLE deprecated_views (
+                view_name TEXT NOT NULL PRIMARY KEY,
+                replacement TEXT NOT NULL,
+                removal_version TEXT NOT NULL
+            );
+            INSERT INTO deprecated_views (view_name, replacement, removal_version)
+            VALUES
+                ('v_tx_received', 'v_tx_outputs', '0.13.0'),
+                ('v_tx_sent', 'v_tx_outputs', '0.13.0');",
+        )?;
+
+        // These views have the columns of the views that were dropped by `v_transactions_net`.
+        // They now include Orchard notes as well as Sapling notes, and the account columns
+        // contain internal account IDs rather than ZIP 32 account indices.
+        transaction.execute_batch(
+            "CREATE VIEW v_tx_received AS
+            SELECT transactions.id_tx                          AS id_tx,
+                   transactions.block                          AS mined_height,
+                   transactions.tx_index                       AS tx_index,
+                   transactions.txid                           AS txid,
+                   transactions.expiry_height                  AS expiry_height,
+                   transactions.raw                            AS raw,
+                   MAX(v_received_notes.account_id)            AS received_by_account,
+                   SUM(v_received_notes.value)                 AS received_total,
+                   COUNT(v_received_notes.id_within_pool_table) AS received_note_count,
+                   SUM(
+                       CASE
+                           WHEN v_received_notes.memo IS NULL THEN 0
+                           ELSE 1
+                       END
+                   ) AS memo_count,
+                   blocks.time                                 AS block_time
+            FROM   transactions
+                   JOIN v_received_notes
+                          ON transactions.id_tx = v_received_notes.tx
+                   LEFT JOIN blocks
+                          ON transactions.block = blocks.height
+            GROUP BY v_received_notes.tx, v_received_notes.account_id;
+
+            CREATE VIEW v_tx_sent AS
+            SELECT transactions.id_tx              AS id_tx,
+                   transactions.block              AS mined_height,
+                   transactions.tx_index           AS tx_index,
+                   transactions.txid               AS txid,
+                   transactions.expiry_height      AS expiry_height,
+                   transactions.raw                AS raw,
+                   MAX(sent_notes.from_account_id) AS sent_from_account,
+                   SUM(sent_notes.value)           AS sent_total,
+                   COUNT(sent_notes.id)            AS sent_note_count,
+                   SUM(
+                       CASE
+                           WHEN sent_notes.memo IS NULL THEN 0
+                           ELSE 1
+                       END
+                   ) AS memo_count,
+                   blocks.time                     AS block_time
+            FROM   transactions
+                   JOIN sent_notes
+                          ON transactions.id_tx = sent_notes.tx
+                   LEFT JOIN blocks
+                          ON transactions.block = blocks.height
+            GROUP BY sent_notes.tx, sent_notes.from_account_id;",
+        )?;
+
+        Ok(())
+    }
+
+    fn down(&self, transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        transaction.execute_batch(
+            "DROP VIEW v_tx_sent;
+            DROP VIEW v_tx_received;
+            DROP TABLE deprecated_views;",
+        )?;
+        Ok(())
+    }
+}
@@ -0,0 +1,194 @@
+//! This migration adds the `spent_externally` column to the `transactions` table, which marks
+//! transactions that spent the wallet's notes but were not created by the wallet (for example,
+//! transactions created by another device using the same seed), and exposes it as a column of
+//! the `v_transactions` view.
+
+use std::collections::HashSet;
+
+use schemer_rusqlite::RusqliteMigration;
+use uuid::Uuid;
+
+use super::scheduled_payments;
+use crate::wallet::init::WalletMigrationError;
+
+pub(super) const MIGRATION_ID: Uuid = Uuid::from_u128(0x7a5e2c94_0b1d_4f63_9d28_e46c1f83ab57);
+
+pub(super) struct Migration;
+
+impl schemer::Migration for Migration {
+    fn id(&self) -> Uuid {
+        MIGRATION_ID
+    }
+
+    fn dependencies(&self) -> HashSet<Uuid> {
+        [scheduled_payments::MIGRATION_ID].into_iter().collect()
+    }
+
+    fn description(&self) -> &'static str {
+        "Marks transactions that spent the wallet's notes but were not created by the wallet."
+    }
+}
+
+impl RusqliteMigration for Migration {
+    type Error = WalletMigrationError;
+
+    fn up(&self, transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        // Existing transactions are not marked; only spends detected after this migration are
+        // checked against the transactions that the wallet created.
+        transaction.execute_batch(
+            "ALTER TABLE transactions ADD COLUMN spent_externally INTEGER NOT NULL DEFAULT 0;
+
+            DROP VIEW v_transactions;
+            CREATE VIEW v_transactions AS
+                WITH
+                notes AS (
+                    -- Shielded notes received in this transaction
+                    SELECT v_received_notes.account_id     AS account_id,
+                           transactions.block              AS block,
+                           transactions.txid               AS txid,
+                           v_received_notes.pool           AS pool,
+                           id_within_pool_table,
+                           v_received_notes.value          AS value,
+                           CASE
+                                WHEN v_received_notes.is_change THEN 1
+                                ELSE 0
+                           END AS is_change,
+                           CASE
+                                WHEN v_received_notes.is_change THEN 0
+                                ELSE 1
+                           END AS received_count,
+                           CASE
+                             WHEN (v_received_notes.memo IS NULL OR v_received_notes.memo = X'F6')
+                               THEN 0
+                             ELSE 1
+                           END AS memo_present,
+                           0                               AS is_spend
+                    FROM v_received_notes
+                    JOIN transactions
+                         ON transactions.id_tx = v_received_notes.tx
+                    UNION
+                    -- Transparent TXOs received in this transaction
+                    SELECT utxos.received_by_account_id AS account_id,
+                           utxos.height                 AS block,
+                           utxos.prevout_txid           AS txid,
+                           0                            AS pool,
+                           utxos.id                     AS id_within_pool_table,
+                           utxos.value_zat              AS value,
+                           0                            AS is_change,
+                           1                            AS received_count,
+                           0                            AS memo_present,
+                           0                            AS is_spend
+                    FROM utxos
+                    UNION
+                    -- Shielded notes spent in this transaction
+                    SELECT v_received_notes.account_id  AS account_id,
+                           transactions.block           AS block,
+                           transactions.txid            AS txid,
+                           v_received_notes.pool        AS pool,
+                           id_within_pool_table,
+                           -v_received_notes.value      AS value,
+                           0                            AS is_change,
+                           0                            AS received_count,
+                           0                            AS memo_present,
+                           1                            AS is_spend
+                    FROM v_received_notes
+                    JOIN v_received_note_spends rns
+                         ON rns.pool = v_received_notes.pool
+                         AND rns.received_note_id = v_received_notes.id_within_pool_table
+                    JOIN transactions
+                         ON transactions.id_tx = rns.transaction_id
+                    UNION
+                    -- Transparent TXOs spent in this transaction
+                    SELECT utxos.received_by_account_id AS account_id,
+                           transactions.block           AS block,
+                           transactions.txid            AS txid,
+                           0                            AS pool,
+                           utxos.id                     AS id_within_pool_table,
+                           -utxos.value_zat             AS value,
+                           0                            AS is_change,
+                           0                            AS received_count,
+                           0                            AS memo_present,
+                           1                            AS is_spend
+                    FROM utxos
+                    JOIN transparent_received_output_spends tros
+                         ON tros.transparent_received_output_id = utxos.id
+                    JOIN transactions
+                         ON transactions.id_tx = tros.transaction_id
+                ),
+                -- Obtain a count of the notes that the wallet created in each transaction,
+                -- not counting change notes.
+                sent_note_counts AS (
+                    SELECT sent_notes.from_account_id AS account_id,
+                           transactions.txid       AS txid,
+                           COUNT(DISTINCT sent_notes.id) as sent_notes,
+                           SUM(
+                             CASE
+                               WHEN (sent_notes.memo IS NULL OR sent_notes.memo = X'F6' OR v_received_notes.tx IS NOT NULL)
+                                 THEN 0
+                               ELSE 1
+                             END
+                           ) AS memo_count,
+                           MAX(sent_notes.output_pool = 0) AS sent_transparent,
+                           MAX(sent_notes.output_pool != 0) AS sent_shielded
+                    FROM sent_notes
+                    JOIN transactions
+                         ON transactions.id_tx = sent_notes.tx
+                    LEFT JOIN v_received_notes
+                         ON sent_notes.id = v_received_notes.sent_note_id
+                    WHERE COALESCE(v_received_notes.is_change, 0) = 0
+                    GROUP BY account_id, txid
+                ),
+                blocks_max_height AS (
+                    SELECT MAX(blocks.height) as max_height FROM blocks
+                )
+                SELECT notes.account_id                  AS account_id,
+                       notes.block                       AS mined_height,
+                       notes.txid                        AS txid,
+                       transactions.tx_index             AS tx_index,
+                       transactions.expiry_height        AS expiry_height,
+                       transactions.raw                  AS raw,
+                       SUM(notes.value)                  AS account_balance_delta,
+                       transactions.fee                  AS fee_paid,
+                       SUM(notes.is_change) > 0          AS has_change,
+                       MAX(COALESCE(sent_note_counts.sent_notes, 0))  AS sent_note_count,
+                       SUM(notes.received_count)         AS received_note_count,
+                       SUM(notes.memo_present) + MAX(COALESCE(sent_note_counts.memo_count, 0)) AS memo_count,
+                       blocks.time                       AS block_time,
+                       (
+                            blocks.height IS NULL
+                            AND transactions.expiry_height BETWEEN 1 AND blocks_max_height.max_height
+                       ) AS expired_unmined,
+                       CASE
+                            WHEN MAX(notes.pool = 0) = 0
+                                 AND MAX(COALESCE(sent_note_counts.sent_transparent, 0)) = 0
+                              THEN 'fully_shielded'
+                            WHEN MAX(notes.pool != 0) = 0
+                                 AND MAX(COALESCE(sent_note_counts.sent_shielded, 0)) = 0
+                              THEN 'transparent'
+                            WHEN MAX(notes.is_spend AND notes.pool != 0) = 1
+                                 AND (
+                                    MAX(NOT notes.is_spend AND notes.pool = 0) = 1
+                                    OR MAX(COALESCE(sent_note_counts.sent_transparent, 0)) = 1
+                                 )
+                              THEN 'deshielding'
+                            ELSE 'shielding'
+                       END AS privacy_level,
+                       COALESCE(transactions.spent_externally, 0) AS spent_externally
+                FROM notes
+                LEFT JOIN transactions
+                     ON notes.txid = transactions.txid
+                JOIN blocks_max_height
+                LEFT JOIN blocks ON blocks.height = notes.block
+                LEFT JOIN sent_note_counts
+                     ON sent_note_counts.account_id = notes.account_id
+                     AND sent_note_counts.txid = notes.txid
+                GROUP BY notes.account_id, notes.txid;",
+        )?;
+
+        Ok(())
+    }
+
+    fn down(&self, _transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        Err(WalletMigrationError::CannotRevert(MIGRATION_ID))
+    }
+}
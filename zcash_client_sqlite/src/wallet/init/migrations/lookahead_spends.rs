@@ -0,0 +1,59 @@
+//! This migration adds the `lookahead_height` column to the `transactions` table, which flags
+//! the transactions that were recorded from the nullifiers of blocks that had not yet been
+//! scanned, so that they can be removed if those blocks are rolled back.
+
+use std::collections::HashSet;
+
+use schemer_rusqlite::RusqliteMigration;
+use uuid::Uuid;
+
+use super::pending_change_balances;
+use crate::wallet::init::WalletMigrationError;
+
+pub(super) const MIGRATION_ID: Uuid = Uuid::from_u128(0xd3a85f27_61c4_4e0b_9f7d_2b8e46c1a593);
+
+pub(super) struct Migration;
+
+impl schemer::Migration for Migration {
+    fn id(&self) -> Uuid {
+        MIGRATION_ID
+    }
+
+    fn dependencies(&self) -> HashSet<Uuid> {
+        [pending_change_balances::MIGRATION_ID]
+            .into_iter()
+            .collect()
+    }
+
+    fn description(&self) -> &'static str {
+        "Flags transactions that were detected from the nullifiers of unscanned blocks."
+    }
+}
+
+impl RusqliteMigration for Migration {
+    type Error = WalletMigrationError;
+
+    fn up(&self, transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        // Transactions that were recorded only because they spend the wallet's notes in a block
+        // that has not yet been scanned have no mined height, expiry height, creation time, or
+        // raw data, and are located by the nullifier map.
+        transaction.execute_batch(
+            "ALTER TABLE transactions ADD COLUMN lookahead_height INTEGER;
+            UPDATE transactions
+            SET lookahead_height = (
+                SELECT tx_locator_map.block_height
+                FROM tx_locator_map
+                WHERE tx_locator_map.txid = transactions.txid
+            )
+            WHERE block IS NULL
+            AND expiry_height IS NULL
+            AND created IS NULL
+            AND raw IS NULL;",
+        )?;
+        Ok(())
+    }
+
+    fn down(&self, _transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        Err(WalletMigrationError::CannotRevert(MIGRATION_ID))
+    }
+}
@@ -0,0 +1,43 @@
+//! This migration clears the `account_balances` cache, because the classification of pending
+//! shielded value has changed: all change that is not yet spendable is now reported as pending
+//! change, and only value received from other parties is reported as pending spendability.
+
+use std::collections::HashSet;
+
+use schemer_rusqlite::RusqliteMigration;
+use uuid::Uuid;
+
+use super::backup_tracking;
+use crate::wallet::init::WalletMigrationError;
+
+pub(super) const MIGRATION_ID: Uuid = Uuid::from_u128(0x4c7a2e91_0d5b_4f63_a8c2_7e19b3d5f046);
+
+pub(super) struct Migration;
+
+impl schemer::Migration for Migration {
+    fn id(&self) -> Uuid {
+        MIGRATION_ID
+    }
+
+    fn dependencies(&self) -> HashSet<Uuid> {
+        [backup_tracking::MIGRATION_ID].into_iter().collect()
+    }
+
+    fn description(&self) -> &'static str {
+        "Separates pending change from pending external receipts in cached account balances."
+    }
+}
+
+impl RusqliteMigration for Migration {
+    type Error = WalletMigrationError;
+
+    fn up(&self, transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        // The cache is repopulated the next time that a wallet summary is requested.
+        transaction.execute_batch("DELETE FROM account_balances;")?;
+        Ok(())
+    }
+
+    fn down(&self, _transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        Err(WalletMigrationError::CannotRevert(MIGRATION_ID))
+    }
+}
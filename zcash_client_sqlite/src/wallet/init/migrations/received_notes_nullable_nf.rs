@@ -223,50 +223,21 @@ impl RusqliteMigration for Migration {
 
 #[cfg(test)]
 mod tests {
-    use rusqlite::{self, params};
-    use tempfile::NamedTempFile;
-
-    use zcash_client_backend::keys::UnifiedSpendingKey;
-    use zcash_primitives::{consensus::Network, zip32::AccountId};
-
     use crate::{
+        testing::WalletFixture,
         wallet::init::{init_wallet_db_internal, migrations::v_transactions_net},
-        WalletDb,
     };
 
     #[test]
     fn received_notes_nullable_migration() {
-        let data_file = NamedTempFile::new().unwrap();
-        let mut db_data = WalletDb::for_path(data_file.path(), Network::TestNetwork).unwrap();
-        init_wallet_db_internal(
-            &mut db_data,
-            None,
-            &[v_transactions_net::MIGRATION_ID],
-            false,
-        )
-        .unwrap();
-
-        // Create an account in the wallet
-        let usk0 = UnifiedSpendingKey::from_seed(&db_data.params, &[0u8; 32][..], AccountId::ZERO)
-            .unwrap();
-        let ufvk0 = usk0.to_unified_full_viewing_key();
-        db_data
-            .conn
-            .execute(
-                "INSERT INTO accounts (account, ufvk) VALUES (0, ?)",
-                params![ufvk0.encode(&db_data.params)],
-            )
-            .unwrap();
-
-        // Tx 0 contains two received notes of 2 and 5 zatoshis that are controlled by account 0.
-        db_data.conn.execute_batch(
-            "INSERT INTO blocks (height, hash, time, sapling_tree) VALUES (0, 0, 0, x'00');
-            INSERT INTO transactions (block, id_tx, txid) VALUES (0, 0, 'tx0');
-
-            INSERT INTO received_notes (tx, output_index, account, diversifier, value, rcm, nf, is_change)
-            VALUES (0, 0, 0, '', 2, '', 'nf_a', false);
-            INSERT INTO received_notes (tx, output_index, account, diversifier, value, rcm, nf, is_change)
-            VALUES (0, 3, 0, '', 5, '', 'nf_b', false);").unwrap();
+        // Create an account in the wallet. Tx 0 contains two received notes of 2 and 5 zatoshis
+        // that are controlled by account 0.
+        let (_data_file, mut db_data) = WalletFixture::new(&[v_transactions_net::MIGRATION_ID])
+            .with_account([0u8; 32])
+            .with_mined_tx(0)
+            .with_received_note(0, 0, 0, 2, "nf_a")
+            .with_received_note(0, 3, 0, 5, "nf_b")
+            .build();
 
         // Apply the current migration
         init_wallet_db_internal(&mut db_data, None, &[super::MIGRATION_ID], false).unwrap();
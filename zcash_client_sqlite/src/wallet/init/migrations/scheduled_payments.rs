@@ -0,0 +1,58 @@
+//! This migration adds the `scheduled_payments` table, which records ZIP 321 payments that
+//! recur at a fixed interval.
+
+use std::collections::HashSet;
+
+use schemer_rusqlite::RusqliteMigration;
+use uuid::Uuid;
+
+use super::contacts;
+use crate::wallet::init::WalletMigrationError;
+
+pub(super) const MIGRATION_ID: Uuid = Uuid::from_u128(0xc3d76a12_5f08_4b9e_a4e7_81f03b62d95c);
+
+pub(super) struct Migration;
+
+impl schemer::Migration for Migration {
+    fn id(&self) -> Uuid {
+        MIGRATION_ID
+    }
+
+    fn dependencies(&self) -> HashSet<Uuid> {
+        [contacts::MIGRATION_ID].into_iter().collect()
+    }
+
+    fn description(&self) -> &'static str {
+        "Adds a table of recurring scheduled payments."
+    }
+}
+
+impl RusqliteMigration for Migration {
+    type Error = WalletMigrationError;
+
+    fn up(&self, transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        // Each payment is stored as a single-payment ZIP 321 URI. `next_due_at` is derived from
+        // the other columns, and is stored only so that due payments can be found by index.
+        transaction.execute_batch(
+            "CREATE TABLE scheduled_payments (
+                id INTEGER PRIMARY KEY,
+                account_id INTEGER NOT NULL,
+                payment_uri TEXT NOT NULL,
+                interval_unit INTEGER NOT NULL,
+                interval_count INTEGER NOT NULL,
+                first_due_at INTEGER NOT NULL,
+                payments_made INTEGER NOT NULL DEFAULT 0,
+                next_due_at INTEGER NOT NULL,
+                FOREIGN KEY (account_id) REFERENCES accounts(id),
+                CONSTRAINT interval_valid CHECK (interval_unit IN (0, 1) AND interval_count > 0)
+            );
+            CREATE INDEX scheduled_payments_next_due ON scheduled_payments (next_due_at);",
+        )?;
+        Ok(())
+    }
+
+    fn down(&self, transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        transaction.execute_batch("DROP TABLE scheduled_payments;")?;
+        Ok(())
+    }
+}
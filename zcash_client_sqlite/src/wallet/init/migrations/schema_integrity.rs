@@ -0,0 +1,52 @@
+//! This migration adds the `schema_integrity` table, which records hashes of the definitions of
+//! the objects created by each migration so that out-of-band modifications can be detected.
+
+use std::collections::HashSet;
+
+use schemer_rusqlite::RusqliteMigration;
+use uuid::Uuid;
+
+use super::wallet_events;
+use crate::wallet::init::WalletMigrationError;
+
+pub(super) const MIGRATION_ID: Uuid = Uuid::from_u128(0x1b8e4d62_a7f3_4c95_9e0d_3c52f8a6b174);
+
+pub(super) struct Migration;
+
+impl schemer::Migration for Migration {
+    fn id(&self) -> Uuid {
+        MIGRATION_ID
+    }
+
+    fn dependencies(&self) -> HashSet<Uuid> {
+        [wallet_events::MIGRATION_ID].into_iter().collect()
+    }
+
+    fn description(&self) -> &'static str {
+        "Adds a table recording hashes of the objects created by each migration."
+    }
+}
+
+impl RusqliteMigration for Migration {
+    type Error = WalletMigrationError;
+
+    fn up(&self, transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        // The table is populated as migrations are applied, starting with the objects created
+        // by the migrations that preceded this one.
+        transaction.execute_batch(
+            "CREATE TABLE schema_integrity (
+                name TEXT NOT NULL PRIMARY KEY,
+                type TEXT NOT NULL,
+                sql_hash BLOB NOT NULL,
+                migration_id BLOB NOT NULL
+            );",
+        )?;
+
+        Ok(())
+    }
+
+    fn down(&self, transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        transaction.execute_batch("DROP TABLE schema_integrity;")?;
+        Ok(())
+    }
+}
@@ -0,0 +1,48 @@
+//! This migration adds the `transparent_address_checks` table, which records the height up to
+//! which the history of each of the wallet's transparent addresses has been retrieved from the
+//! network, so that it is not requested again.
+
+use std::collections::HashSet;
+
+use schemer_rusqlite::RusqliteMigration;
+use uuid::Uuid;
+
+use super::lookahead_spends;
+use crate::wallet::init::WalletMigrationError;
+
+pub(super) const MIGRATION_ID: Uuid = Uuid::from_u128(0xc9a2bc90_195b_4e23_867e_db35e86f7c0e);
+
+pub(super) struct Migration;
+
+impl schemer::Migration for Migration {
+    fn id(&self) -> Uuid {
+        MIGRATION_ID
+    }
+
+    fn dependencies(&self) -> HashSet<Uuid> {
+        [lookahead_spends::MIGRATION_ID].into_iter().collect()
+    }
+
+    fn description(&self) -> &'static str {
+        "Records the height to which the history of each transparent address has been checked."
+    }
+}
+
+impl RusqliteMigration for Migration {
+    type Error = WalletMigrationError;
+
+    fn up(&self, transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        transaction.execute_batch(
+            "CREATE TABLE transparent_address_checks (
+                address TEXT NOT NULL PRIMARY KEY,
+                checked_height INTEGER NOT NULL
+            );",
+        )?;
+
+        Ok(())
+    }
+
+    fn down(&self, _transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        Err(WalletMigrationError::CannotRevert(MIGRATION_ID))
+    }
+}
@@ -0,0 +1,56 @@
+//! This migration adds the `tx_retrieval_queue` table, which records the transaction data that
+//! the wallet needs to retrieve from the network in order to fully describe its own
+//! transactions.
+
+use std::collections::HashSet;
+
+use schemer_rusqlite::RusqliteMigration;
+use uuid::Uuid;
+
+use super::v_ledger;
+use crate::wallet::init::WalletMigrationError;
+
+pub(super) const MIGRATION_ID: Uuid = Uuid::from_u128(0xd5c939df_78fd_4519_b9f1_faf52383ffd3);
+
+pub(super) struct Migration;
+
+impl schemer::Migration for Migration {
+    fn id(&self) -> Uuid {
+        MIGRATION_ID
+    }
+
+    fn dependencies(&self) -> HashSet<Uuid> {
+        [v_ledger::MIGRATION_ID].into_iter().collect()
+    }
+
+    fn description(&self) -> &'static str {
+        "Adds a queue of transactions to be retrieved from outside the wallet."
+    }
+}
+
+impl RusqliteMigration for Migration {
+    type Error = WalletMigrationError;
+
+    fn up(&self, transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        // `query_type` distinguishes requests for a transaction's status from requests for its
+        // full data. The same data may be required by several of the wallet's transactions; it
+        // is retrieved once, after which all of the corresponding entries are removed.
+        transaction.execute_batch(
+            "CREATE TABLE tx_retrieval_queue (
+                txid BLOB NOT NULL,
+                query_type INTEGER NOT NULL,
+                dependent_transaction_id INTEGER NOT NULL,
+                FOREIGN KEY (dependent_transaction_id)
+                    REFERENCES transactions(id_tx)
+                    ON DELETE CASCADE,
+                UNIQUE (txid, query_type, dependent_transaction_id)
+            );",
+        )?;
+
+        Ok(())
+    }
+
+    fn down(&self, _transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        Err(WalletMigrationError::CannotRevert(MIGRATION_ID))
+    }
+}
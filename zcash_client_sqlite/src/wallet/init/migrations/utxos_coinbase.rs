@@ -0,0 +1,86 @@
+//! This migration adds an `is_coinbase` column to the `utxos` table, so that transparent
+//! outputs of coinbase transactions can be excluded from shielding until they have matured,
+//! and records the value of such outputs separately in the `account_balances` cache.
+
+use std::collections::HashSet;
+
+use rusqlite::named_params;
+use schemer_rusqlite::RusqliteMigration;
+use uuid::Uuid;
+use zcash_primitives::{
+    consensus::{self, BlockHeight, BranchId},
+    transaction::Transaction,
+};
+
+use super::tx_retrieval_queue;
+use crate::wallet::init::WalletMigrationError;
+
+pub(super) const MIGRATION_ID: Uuid = Uuid::from_u128(0xb98f7bd8_c3d0_412f_a0e3_8cdc574f69ba);
+
+pub(super) struct Migration<P> {
+    pub(super) params: P,
+}
+
+impl<P> schemer::Migration for Migration<P> {
+    fn id(&self) -> Uuid {
+        MIGRATION_ID
+    }
+
+    fn dependencies(&self) -> HashSet<Uuid> {
+        [tx_retrieval_queue::MIGRATION_ID].into_iter().collect()
+    }
+
+    fn description(&self) -> &'static str {
+        "Records whether each transparent output was created by a coinbase transaction."
+    }
+}
+
+impl<P: consensus::Parameters> RusqliteMigration for Migration<P> {
+    type Error = WalletMigrationError;
+
+    fn up(&self, transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        // Cached balances do not distinguish immature coinbase outputs, so they are discarded.
+        transaction.execute_batch(
+            "ALTER TABLE utxos ADD COLUMN is_coinbase INTEGER NOT NULL DEFAULT 0;
+            DELETE FROM account_balances;
+            ALTER TABLE account_balances
+                ADD COLUMN unshielded_pending_maturity_value INTEGER NOT NULL DEFAULT 0;",
+        )?;
+
+        // Outputs whose creating transaction has been stored can be classified immediately.
+        // Any other output is treated as not being a coinbase output, because the wallet has
+        // no way to tell.
+        let mut stmt_utxo_txs = transaction.prepare(
+            "SELECT utxos.id, utxos.height, transactions.raw
+            FROM utxos
+            JOIN transactions ON transactions.txid = utxos.prevout_txid
+            WHERE transactions.raw IS NOT NULL",
+        )?;
+        let mut stmt_set_coinbase =
+            transaction.prepare("UPDATE utxos SET is_coinbase = 1 WHERE id = :id")?;
+
+        let mut rows = stmt_utxo_txs.query([])?;
+        while let Some(row) = rows.next()? {
+            let id: i64 = row.get(0)?;
+            let height = BlockHeight::from(row.get::<_, u32>(1)?);
+            let raw: Vec<u8> = row.get(2)?;
+            let tx = Transaction::read(&raw[..], BranchId::for_height(&self.params, height))
+                .map_err(|e| {
+                    WalletMigrationError::CorruptedData(format!(
+                        "Unable to parse raw transaction: {}",
+                        e
+                    ))
+                })?;
+
+            if tx.transparent_bundle().map_or(false, |b| b.is_coinbase()) {
+                stmt_set_coinbase.execute(named_params![":id": id])?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn down(&self, _transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        Err(WalletMigrationError::CannotRevert(MIGRATION_ID))
+    }
+}
@@ -0,0 +1,145 @@
+//! This migration adds the `v_ledger` view, which presents the effect of each transaction on the
+//! wallet as a set of double-entry ledger rows that sum to zero.
+
+use std::collections::HashSet;
+
+use schemer_rusqlite::RusqliteMigration;
+use uuid::Uuid;
+
+use super::v_received_notes_txid;
+use crate::wallet::init::WalletMigrationError;
+
+pub(super) const MIGRATION_ID: Uuid = Uuid::from_u128(0xe56787df_b661_4c7b_9c51_b8995d59384e);
+
+pub(super) struct Migration;
+
+impl schemer::Migration for Migration {
+    fn id(&self) -> Uuid {
+        MIGRATION_ID
+    }
+
+    fn dependencies(&self) -> HashSet<Uuid> {
+        [v_received_notes_txid::MIGRATION_ID].into_iter().collect()
+    }
+
+    fn description(&self) -> &'static str {
+        "Adds a view that presents each transaction as balanced debit and credit entries."
+    }
+}
+
+impl RusqliteMigration for Migration {
+    type Error = WalletMigrationError;
+
+    fn up(&self, transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        // Debits are positive and credits negative. Receiving a note debits the receiving
+        // account, spending a note credits the account that owned it, and payments to
+        // recipients outside the wallet and fees are debits that are not attributed to any
+        // account. A transaction that spends nothing from the wallet was funded from outside
+        // it, so each of its receive, send, and fee entries is matched by an `external`
+        // credit for the same output. The entries of a transaction therefore sum to zero
+        // only if the wallet's records of its inputs, outputs, and fee are consistent.
+        transaction.execute_batch(
+            "CREATE VIEW v_ledger AS
+            WITH entries AS (
+                SELECT v_received_notes.txid,
+                       transactions.block           AS mined_height,
+                       v_received_notes.account_id,
+                       'receive'                    AS entry_type,
+                       v_received_notes.pool        AS output_pool,
+                       v_received_notes.output_index,
+                       v_received_notes.value
+                FROM v_received_notes
+                JOIN transactions
+                    ON transactions.id_tx = v_received_notes.tx
+                UNION ALL
+                SELECT utxos.prevout_txid           AS txid,
+                       utxos.height                 AS mined_height,
+                       utxos.received_by_account_id AS account_id,
+                       'receive'                    AS entry_type,
+                       0                            AS output_pool,
+                       utxos.prevout_idx            AS output_index,
+                       utxos.value_zat              AS value
+                FROM utxos
+                UNION ALL
+                SELECT v_received_note_spends.spending_txid AS txid,
+                       transactions.block           AS mined_height,
+                       v_received_notes.account_id,
+                       'spend'                      AS entry_type,
+                       v_received_notes.pool        AS output_pool,
+                       NULL                         AS output_index,
+                       -v_received_notes.value      AS value
+                FROM v_received_note_spends
+                JOIN v_received_notes
+                    ON v_received_notes.pool = v_received_note_spends.pool
+                    AND v_received_notes.id_within_pool_table = v_received_note_spends.received_note_id
+                JOIN transactions
+                    ON transactions.id_tx = v_received_note_spends.transaction_id
+                UNION ALL
+                SELECT transactions.txid,
+                       transactions.block           AS mined_height,
+                       utxos.received_by_account_id AS account_id,
+                       'spend'                      AS entry_type,
+                       0                            AS output_pool,
+                       NULL                         AS output_index,
+                       -utxos.value_zat             AS value
+                FROM transparent_received_output_spends
+                JOIN utxos
+                    ON utxos.id = transparent_received_output_spends.transparent_received_output_id
+                JOIN transactions
+                    ON transactions.id_tx = transparent_received_output_spends.transaction_id
+                UNION ALL
+                SELECT transactions.txid,
+                       transactions.block           AS mined_height,
+                       NULL                         AS account_id,
+                       'send'                       AS entry_type,
+                       sent_notes.output_pool,
+                       sent_notes.output_index,
+                       sent_notes.value
+                FROM sent_notes
+                JOIN transactions
+                    ON transactions.id_tx = sent_notes.tx
+                LEFT JOIN v_received_notes
+                    ON v_received_notes.sent_note_id = sent_notes.id
+                LEFT JOIN utxos
+                    ON sent_notes.output_pool = 0
+                    AND utxos.prevout_txid = transactions.txid
+                    AND utxos.prevout_idx = sent_notes.output_index
+                WHERE v_received_notes.sent_note_id IS NULL
+                AND utxos.id IS NULL
+                UNION ALL
+                SELECT transactions.txid,
+                       transactions.block           AS mined_height,
+                       NULL                         AS account_id,
+                       'fee'                        AS entry_type,
+                       NULL                         AS output_pool,
+                       NULL                         AS output_index,
+                       transactions.fee             AS value
+                FROM transactions
+                WHERE transactions.fee IS NOT NULL
+            )
+            SELECT txid, mined_height, account_id, entry_type, output_pool, output_index, value
+            FROM entries
+            UNION ALL
+            SELECT txid,
+                   mined_height,
+                   NULL                             AS account_id,
+                   'external'                       AS entry_type,
+                   output_pool,
+                   output_index,
+                   -value                           AS value
+            FROM entries
+            WHERE entry_type IN ('receive', 'send', 'fee')
+            AND NOT EXISTS (
+                SELECT 1 FROM entries spends
+                WHERE spends.txid = entries.txid
+                AND spends.entry_type = 'spend'
+            );",
+        )?;
+
+        Ok(())
+    }
+
+    fn down(&self, _transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        Err(WalletMigrationError::CannotRevert(MIGRATION_ID))
+    }
+}
@@ -0,0 +1,105 @@
+//! This migration adds `txid` columns to the `v_received_notes` and `v_received_note_spends`
+//! views, so that the transactions to which notes belong can be identified without reference
+//! to the internal row identifiers of the `transactions` table.
+
+use std::collections::HashSet;
+
+use schemer_rusqlite::RusqliteMigration;
+use uuid::Uuid;
+use zcash_client_backend::{PoolType, ShieldedProtocol};
+
+use super::account_id_bounds;
+use crate::wallet::{init::WalletMigrationError, pool_code};
+
+pub(super) const MIGRATION_ID: Uuid = Uuid::from_u128(0x686f9d25_535c_42d3_a7d9_a5f838fbecf3);
+
+pub(super) struct Migration;
+
+impl schemer::Migration for Migration {
+    fn id(&self) -> Uuid {
+        MIGRATION_ID
+    }
+
+    fn dependencies(&self) -> HashSet<Uuid> {
+        [account_id_bounds::MIGRATION_ID].into_iter().collect()
+    }
+
+    fn description(&self) -> &'static str {
+        "Adds txid columns to the v_received_notes and v_received_note_spends views."
+    }
+}
+
+impl RusqliteMigration for Migration {
+    type Error = WalletMigrationError;
+
+    fn up(&self, transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        let sapling_pool_code = pool_code(PoolType::Shielded(ShieldedProtocol::Sapling));
+        let orchard_pool_code = pool_code(PoolType::Shielded(ShieldedProtocol::Orchard));
+
+        // The existing columns are retained, as `v_transactions` and `v_tx_outputs` are defined
+        // in terms of them.
+        transaction.execute_batch(&format!(
+            "DROP VIEW v_received_notes;
+            CREATE VIEW v_received_notes AS
+                SELECT
+                    sapling_received_notes.id AS id_within_pool_table,
+                    sapling_received_notes.tx,
+                    transactions.txid,
+                    {sapling_pool_code} AS pool,
+                    sapling_received_notes.output_index AS output_index,
+                    account_id,
+                    sapling_received_notes.value,
+                    is_change,
+                    sapling_received_notes.memo,
+                    sent_notes.id AS sent_note_id
+                FROM sapling_received_notes
+                JOIN transactions
+                    ON transactions.id_tx = sapling_received_notes.tx
+                LEFT JOIN sent_notes
+                ON (sent_notes.tx, sent_notes.output_pool, sent_notes.output_index) =
+                   (sapling_received_notes.tx, {sapling_pool_code}, sapling_received_notes.output_index)
+            UNION
+                SELECT
+                    orchard_received_notes.id AS id_within_pool_table,
+                    orchard_received_notes.tx,
+                    transactions.txid,
+                    {orchard_pool_code} AS pool,
+                    orchard_received_notes.action_index AS output_index,
+                    account_id,
+                    orchard_received_notes.value,
+                    is_change,
+                    orchard_received_notes.memo,
+                    sent_notes.id AS sent_note_id
+                FROM orchard_received_notes
+                JOIN transactions
+                    ON transactions.id_tx = orchard_received_notes.tx
+                LEFT JOIN sent_notes
+                ON (sent_notes.tx, sent_notes.output_pool, sent_notes.output_index) =
+                   (orchard_received_notes.tx, {orchard_pool_code}, orchard_received_notes.action_index);
+
+            DROP VIEW v_received_note_spends;
+            CREATE VIEW v_received_note_spends AS
+            SELECT
+                {sapling_pool_code} AS pool,
+                sapling_received_note_id AS received_note_id,
+                transaction_id,
+                transactions.txid AS spending_txid
+            FROM sapling_received_note_spends
+            JOIN transactions ON transactions.id_tx = transaction_id
+            UNION
+            SELECT
+                {orchard_pool_code} AS pool,
+                orchard_received_note_id AS received_note_id,
+                transaction_id,
+                transactions.txid AS spending_txid
+            FROM orchard_received_note_spends
+            JOIN transactions ON transactions.id_tx = transaction_id;"
+        ))?;
+
+        Ok(())
+    }
+
+    fn down(&self, _transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        Err(WalletMigrationError::CannotRevert(MIGRATION_ID))
+    }
+}
@@ -202,107 +202,45 @@ impl RusqliteMigration for Migration {
 
 #[cfg(test)]
 mod tests {
-    use rusqlite::{self, params};
-    use tempfile::NamedTempFile;
-
-    use zcash_client_backend::keys::UnifiedSpendingKey;
-    use zcash_primitives::{consensus::Network, zip32::AccountId};
-
     use crate::{
+        testing::{FixtureRecipient, WalletFixture},
         wallet::init::{init_wallet_db_internal, migrations::add_transaction_views},
-        WalletDb,
     };
 
     #[test]
     fn v_transactions_net() {
-        let data_file = NamedTempFile::new().unwrap();
-        let mut db_data = WalletDb::for_path(data_file.path(), Network::TestNetwork).unwrap();
-        init_wallet_db_internal(
-            &mut db_data,
-            None,
-            &[add_transaction_views::MIGRATION_ID],
-            false,
-        )
-        .unwrap();
-
-        // Create two accounts in the wallet.
-        let usk0 = UnifiedSpendingKey::from_seed(&db_data.params, &[0u8; 32][..], AccountId::ZERO)
-            .unwrap();
-        let ufvk0 = usk0.to_unified_full_viewing_key();
-        db_data
-            .conn
-            .execute(
-                "INSERT INTO accounts (account, ufvk) VALUES (0, ?)",
-                params![ufvk0.encode(&db_data.params)],
-            )
-            .unwrap();
-
-        let usk1 = UnifiedSpendingKey::from_seed(
-            &db_data.params,
-            &[1u8; 32][..],
-            AccountId::try_from(1).unwrap(),
-        )
-        .unwrap();
-        let ufvk1 = usk1.to_unified_full_viewing_key();
-        db_data
-            .conn
-            .execute(
-                "INSERT INTO accounts (account, ufvk) VALUES (1, ?)",
-                params![ufvk1.encode(&db_data.params)],
-            )
-            .unwrap();
-
-        // - Tx 0 contains two received notes of 2 and 5 zatoshis that are controlled by account 0.
-        db_data.conn.execute_batch(
-            "INSERT INTO blocks (height, hash, time, sapling_tree) VALUES (0, 0, 0, x'00');
-            INSERT INTO transactions (block, id_tx, txid) VALUES (0, 0, 'tx0');
-
-            INSERT INTO received_notes (tx, output_index, account, diversifier, value, rcm, nf, is_change)
-            VALUES (0, 0, 0, '', 2, '', 'nf_a', false);
-            INSERT INTO received_notes (tx, output_index, account, diversifier, value, rcm, nf, is_change)
-            VALUES (0, 3, 0, '', 5, '', 'nf_b', false);").unwrap();
-
-        // - Tx 1 creates two notes of 2 and 3 zatoshis for an external address, and a change note
-        //   of 2 zatoshis. This is representative of a historic transaction where no `sent_notes`
-        //   entry was created for the change value.
-        db_data.conn.execute_batch(
-            "INSERT INTO blocks (height, hash, time, sapling_tree) VALUES (1, 1, 1, x'00');
-            INSERT INTO transactions (block, id_tx, txid) VALUES (1, 1, 'tx1');
-            UPDATE received_notes SET spent = 1 WHERE tx = 0;
-            INSERT INTO sent_notes (tx, output_pool, output_index, from_account, to_account, to_address, value)
-            VALUES (1, 2, 0, 0, NULL, 'addra', 2);
-            INSERT INTO sent_notes (tx, output_pool, output_index, from_account, to_account, to_address, value, memo)
-            VALUES (1, 2, 1, 0, NULL, 'addrb', 3, X'61');
-            INSERT INTO received_notes (tx, output_index, account, diversifier, value, rcm, nf, is_change)
-            VALUES (1,    2, 0, '', 2, '', 'nf_c', true);").unwrap();
-
-        // - Tx 2 sends the half of the wallet value from account 0 to account 1 and returns the
-        //   other half to the sending account as change. Also there's a random transparent utxo,
-        //   received, who knows where it came from but it's for account 0.
-        db_data.conn.execute_batch(
-            "INSERT INTO blocks (height, hash, time, sapling_tree) VALUES (2, 2, 2, x'00');
-            INSERT INTO transactions (block, id_tx, txid) VALUES (2, 2, 'tx2');
-            UPDATE received_notes SET spent = 2 WHERE tx = 1;
-            INSERT INTO utxos (received_by_account, address, prevout_txid, prevout_idx, script, value_zat, height)
-            VALUES (0, 'taddr_tx2', 'tx2', 0, '', 1, 2);
-            INSERT INTO sent_notes (tx, output_pool, output_index, from_account, to_account, to_address, value)
-            VALUES (2, 2, 0, 0, 0, NULL, 1);
-            INSERT INTO sent_notes (tx, output_pool, output_index, from_account, to_account, to_address, value)
-            VALUES (2, 2, 1, 0, 1, NULL, 1);
-            INSERT INTO received_notes (tx, output_index, account, diversifier, value, rcm, nf, is_change)
-            VALUES (2, 0, 0, '', 1, '', 'nf_d', true);
-            INSERT INTO received_notes (tx, output_index, account, diversifier, value, rcm, nf, is_change)
-            VALUES (2, 1, 1, '', 1, '', 'nf_e', false);",
-        ).unwrap();
-
-        // - Tx 3 just receives transparent funds and does nothing else. For this to work, the
-        //   transaction must be retrieved by the wallet.
-        db_data.conn.execute_batch(
-            "INSERT INTO blocks (height, hash, time, sapling_tree) VALUES (3, 3, 3, x'00');
-            INSERT INTO transactions (block, id_tx, txid) VALUES (3, 3, 'tx3');
-
-            INSERT INTO utxos (received_by_account, address, prevout_txid, prevout_idx, script, value_zat, height)
-            VALUES (0, 'taddr_tx3', 'tx3', 0, '', 1, 3);").unwrap();
+        let (_data_file, mut db_data) = WalletFixture::new(&[add_transaction_views::MIGRATION_ID])
+            // Create two accounts in the wallet.
+            .with_account([0u8; 32])
+            .with_account([1u8; 32])
+            // - Tx 0 contains two received notes of 2 and 5 zatoshis that are controlled by
+            //   account 0.
+            .with_mined_tx(0)
+            .with_received_note(0, 0, 0, 2, "nf_a")
+            .with_received_note(0, 3, 0, 5, "nf_b")
+            // - Tx 1 creates two notes of 2 and 3 zatoshis for an external address, and a change
+            //   note of 2 zatoshis. This is representative of a historic transaction where no
+            //   `sent_notes` entry was created for the change value.
+            .with_mined_tx(1)
+            .with_notes_spent(0, 1)
+            .with_sent_note(1, 0, 0, FixtureRecipient::External("addra"), 2, None)
+            .with_sent_note(1, 1, 0, FixtureRecipient::External("addrb"), 3, Some(b"a"))
+            .with_received_change(1, 2, 0, 2, "nf_c")
+            // - Tx 2 sends the half of the wallet value from account 0 to account 1 and returns
+            //   the other half to the sending account as change. Also there's a random
+            //   transparent utxo, received, who knows where it came from but it's for account 0.
+            .with_mined_tx(2)
+            .with_notes_spent(1, 2)
+            .with_utxo(2, 0, 1)
+            .with_sent_note(2, 0, 0, FixtureRecipient::Internal(0), 1, None)
+            .with_sent_note(2, 1, 0, FixtureRecipient::Internal(1), 1, None)
+            .with_received_change(2, 0, 0, 1, "nf_d")
+            .with_received_note(2, 1, 1, 1, "nf_e")
+            // - Tx 3 just receives transparent funds and does nothing else. For this to work, the
+            //   transaction must be retrieved by the wallet.
+            .with_mined_tx(3)
+            .with_utxo(3, 0, 1)
+            .build();
 
         // Behavior prior to change:
         {
@@ -0,0 +1,56 @@
+//! This migration adds the `wallet_events` table, an append-only journal of significant changes
+//! to the wallet's state that is used to diagnose problems with synchronization.
+
+use std::collections::HashSet;
+
+use schemer_rusqlite::RusqliteMigration;
+use uuid::Uuid;
+
+use super::deprecated_view_shims;
+use crate::wallet::init::WalletMigrationError;
+
+pub(super) const MIGRATION_ID: Uuid = Uuid::from_u128(0x9e3f5b27_c8d4_4a61_b0f2_46a7e1d9c583);
+
+pub(super) struct Migration;
+
+impl schemer::Migration for Migration {
+    fn id(&self) -> Uuid {
+        MIGRATION_ID
+    }
+
+    fn dependencies(&self) -> HashSet<Uuid> {
+        [deprecated_view_shims::MIGRATION_ID].into_iter().collect()
+    }
+
+    fn description(&self) -> &'static str {
+        "Adds a journal of wallet events for debugging synchronization."
+    }
+}
+
+impl RusqliteMigration for Migration {
+    type Error = WalletMigrationError;
+
+    fn up(&self, transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        // Events are only recorded while the journal is enabled, which is the case when the
+        // most recent `journal_enabled` or `journal_disabled` event is `journal_enabled`.
+        transaction.execute_batch(
+            "CREATE TABLE wallet_events (
+                id INTEGER PRIMARY KEY,
+                recorded_at INTEGER NOT NULL,
+                kind TEXT NOT NULL,
+                start_height INTEGER,
+                end_height INTEGER,
+                txid BLOB,
+                detail TEXT
+            );
+            CREATE INDEX wallet_events_kind ON wallet_events (kind);",
+        )?;
+
+        Ok(())
+    }
+
+    fn down(&self, transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        transaction.execute_batch("DROP TABLE wallet_events;")?;
+        Ok(())
+    }
+}
@@ -0,0 +1,49 @@
+//! This migration adds the `wallet_settings` table, which stores named configuration values
+//! for the wallet.
+
+use std::collections::HashSet;
+
+use schemer_rusqlite::RusqliteMigration;
+use uuid::Uuid;
+
+use super::external_spends;
+use crate::wallet::init::WalletMigrationError;
+
+pub(super) const MIGRATION_ID: Uuid = Uuid::from_u128(0x2e84b7c1_9d3a_4f06_b15e_6a0c4d92f378);
+
+pub(super) struct Migration;
+
+impl schemer::Migration for Migration {
+    fn id(&self) -> Uuid {
+        MIGRATION_ID
+    }
+
+    fn dependencies(&self) -> HashSet<Uuid> {
+        [external_spends::MIGRATION_ID].into_iter().collect()
+    }
+
+    fn description(&self) -> &'static str {
+        "Adds a table of named wallet settings."
+    }
+}
+
+impl RusqliteMigration for Migration {
+    type Error = WalletMigrationError;
+
+    fn up(&self, transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        // The `value` column is declared without a type, so that each value retains the
+        // storage class with which it was written.
+        transaction.execute_batch(
+            "CREATE TABLE wallet_settings (
+                name TEXT NOT NULL PRIMARY KEY,
+                value NOT NULL
+            );",
+        )?;
+        Ok(())
+    }
+
+    fn down(&self, transaction: &rusqlite::Transaction<'_>) -> Result<(), Self::Error> {
+        transaction.execute_batch("DROP TABLE wallet_settings;")?;
+        Ok(())
+    }
+}
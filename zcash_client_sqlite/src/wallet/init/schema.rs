@@ -0,0 +1,560 @@
+//! Structured descriptions of the wallet database schema.
+//!
+//! Applications that query the wallet database directly depend upon the names and types of
+//! the tables, views and columns that they use. [`describe_schema`] reports the migrations
+//! that have been applied to a wallet database, along with the resulting tables, views and
+//! indexes, so that such dependencies can be checked programmatically (for example, in a test
+//! that is run when upgrading this crate). When the `serde` feature flag is enabled, the
+//! description can be serialized, for example to record it alongside an application's
+//! queries.
+//!
+//! When a view is replaced, it may be retained for a few releases as a thin wrapper over the
+//! new schema, so that applications that query it continue to work while they migrate. Such
+//! views are listed in the `deprecated_views` table, together with their replacement and the
+//! release of this crate in which they will be removed. [`deprecated_view_usage`] reports the
+//! deprecated views, along with any views or triggers created by the application that depend
+//! upon them.
+
+use std::collections::HashMap;
+
+use rusqlite::{named_params, types::ValueRef, Connection};
+use schemer::Migration;
+use uuid::Uuid;
+use zcash_primitives::consensus;
+
+use super::migrations::all_migrations;
+use crate::WalletDb;
+
+#[cfg(feature = "serde")]
+use serde::{Serialize, Serializer};
+
+#[cfg(feature = "serde")]
+fn serialize_uuid<S: Serializer>(id: &Uuid, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(id)
+}
+
+/// A description of the schema of a wallet database.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Schema {
+    migrations: Vec<AppliedMigration>,
+    tables: Vec<Table>,
+    views: Vec<View>,
+    indexes: Vec<Index>,
+}
+
+impl Schema {
+    /// Returns the migrations that have been applied to the database, in the order in which
+    /// they are registered by this crate. Migrations that are not known to this version of the crate
+    /// are listed last.
+    pub fn migrations(&self) -> &[AppliedMigration] {
+        &self.migrations
+    }
+
+    /// Returns the tables in the database, in order of name.
+    pub fn tables(&self) -> &[Table] {
+        &self.tables
+    }
+
+    /// Returns the views in the database, in order of name.
+    pub fn views(&self) -> &[View] {
+        &self.views
+    }
+
+    /// Returns the explicitly-created indexes in the database, in order of name.
+    pub fn indexes(&self) -> &[Index] {
+        &self.indexes
+    }
+
+    /// Returns the table with the given name, if it exists.
+    pub fn table(&self, name: &str) -> Option<&Table> {
+        self.tables.iter().find(|t| t.name == name)
+    }
+
+    /// Returns the view with the given name, if it exists.
+    pub fn view(&self, name: &str) -> Option<&View> {
+        self.views.iter().find(|v| v.name == name)
+    }
+}
+
+/// A migration that has been applied to the database.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct AppliedMigration {
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_uuid"))]
+    id: Uuid,
+    description: Option<&'static str>,
+}
+
+impl AppliedMigration {
+    /// Returns the unique identifier of the migration.
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    /// Returns the description of the migration, or `None` if the migration is not known to
+    /// this version of the crate.
+    pub fn description(&self) -> Option<&'static str> {
+        self.description
+    }
+}
+
+/// A column of a table or view.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Column {
+    name: String,
+    declared_type: String,
+    not_null: bool,
+    default_value: Option<String>,
+    primary_key: bool,
+}
+
+impl Column {
+    /// Returns the name of the column.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the type with which the column was declared. This is empty for columns without
+    /// a declared type, including most columns of views.
+    pub fn declared_type(&self) -> &str {
+        &self.declared_type
+    }
+
+    /// Returns whether the column has a `NOT NULL` constraint.
+    pub fn not_null(&self) -> bool {
+        self.not_null
+    }
+
+    /// Returns the SQL expression for the column's default value, if any.
+    pub fn default_value(&self) -> Option<&str> {
+        self.default_value.as_deref()
+    }
+
+    /// Returns whether the column is part of the primary key of its table.
+    pub fn primary_key(&self) -> bool {
+        self.primary_key
+    }
+}
+
+/// A table in the database.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Table {
+    name: String,
+    sql: String,
+    columns: Vec<Column>,
+}
+
+impl Table {
+    /// Returns the name of the table.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the SQL statement that created the table, as stored by SQLite.
+    pub fn sql(&self) -> &str {
+        &self.sql
+    }
+
+    /// Returns the columns of the table, in order.
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    /// Returns the column with the given name, if it exists.
+    pub fn column(&self, name: &str) -> Option<&Column> {
+        self.columns.iter().find(|c| c.name == name)
+    }
+}
+
+/// A view in the database.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct View {
+    name: String,
+    sql: String,
+    columns: Vec<Column>,
+}
+
+impl View {
+    /// Returns the name of the view.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the SQL statement that created the view, as stored by SQLite.
+    pub fn sql(&self) -> &str {
+        &self.sql
+    }
+
+    /// Returns the columns of the view, in order.
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    /// Returns the column with the given name, if it exists.
+    pub fn column(&self, name: &str) -> Option<&Column> {
+        self.columns.iter().find(|c| c.name == name)
+    }
+}
+
+/// An index in the database.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Index {
+    name: String,
+    table: String,
+    sql: String,
+    unique: bool,
+    columns: Vec<String>,
+}
+
+impl Index {
+    /// Returns the name of the index.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the name of the indexed table.
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+
+    /// Returns the SQL statement that created the index, as stored by SQLite.
+    pub fn sql(&self) -> &str {
+        &self.sql
+    }
+
+    /// Returns whether this is a `UNIQUE` index.
+    pub fn unique(&self) -> bool {
+        self.unique
+    }
+
+    /// Returns the names of the indexed columns, in order.
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+}
+
+/// Describes the schema of the given wallet database.
+///
+/// The database should have been initialized with [`init_wallet_db`]; if it has not, the
+/// description will be empty or will reflect an older version of the schema.
+///
+/// [`init_wallet_db`]: super::init_wallet_db
+pub fn describe_schema<P: consensus::Parameters + 'static>(
+    wdb: &WalletDb<Connection, P>,
+) -> Result<Schema, rusqlite::Error> {
+    let conn = &wdb.conn;
+
+    let known_migrations = all_migrations(&wdb.params, None)
+        .iter()
+        .enumerate()
+        .map(|(i, m)| (m.id(), (i, m.description())))
+        .collect::<HashMap<_, _>>();
+
+    let mut migrations = applied_migration_ids(conn)?
+        .into_iter()
+        .map(|id| AppliedMigration {
+            id,
+            description: known_migrations.get(&id).map(|(_, d)| *d),
+        })
+        .collect::<Vec<_>>();
+    migrations.sort_by_key(|m| {
+        known_migrations
+            .get(&m.id)
+            .map_or((1, 0, m.id), |(i, _)| (0, *i, Uuid::nil()))
+    });
+
+    let mut tables = vec![];
+    let mut views = vec![];
+    let mut indexes = vec![];
+    let mut stmt = conn.prepare(
+        "SELECT type, name, tbl_name, sql
+        FROM sqlite_schema
+        WHERE type IN ('table', 'view', 'index')
+        AND name NOT LIKE 'sqlite_%'
+        AND sql IS NOT NULL
+        ORDER BY name",
+    )?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let object_type: String = row.get(0)?;
+        let name: String = row.get(1)?;
+        let table: String = row.get(2)?;
+        let sql: String = row.get(3)?;
+        match object_type.as_str() {
+            "table" => tables.push(Table {
+                columns: get_columns(conn, &name)?,
+                name,
+                sql,
+            }),
+            "view" => views.push(View {
+                columns: get_columns(conn, &name)?,
+                name,
+                sql,
+            }),
+            _ => {
+                let unique = conn.query_row(
+                    "SELECT \"unique\" FROM pragma_index_list(:table) WHERE name = :name",
+                    named_params![":table": table, ":name": name],
+                    |row| row.get::<_, bool>(0),
+                )?;
+                let columns = conn
+                    .prepare("SELECT name FROM pragma_index_info(:name) ORDER BY seqno")?
+                    .query_map(named_params![":name": name], |row| {
+                        // Indexes on expressions have no column name.
+                        row.get::<_, Option<String>>(0)
+                            .map(|c| c.unwrap_or_else(|| "<expression>".to_owned()))
+                    })?
+                    .collect::<Result<_, _>>()?;
+                indexes.push(Index {
+                    name,
+                    table,
+                    sql,
+                    unique,
+                    columns,
+                });
+            }
+        }
+    }
+
+    Ok(Schema {
+        migrations,
+        tables,
+        views,
+        indexes,
+    })
+}
+
+/// A view that is retained only for compatibility with applications that query it directly.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct DeprecatedViewUsage {
+    view: String,
+    replacement: String,
+    removal_version: String,
+    dependents: Vec<String>,
+}
+
+impl DeprecatedViewUsage {
+    /// Returns the name of the deprecated view.
+    pub fn view(&self) -> &str {
+        &self.view
+    }
+
+    /// Returns the name of the view that should be queried instead.
+    pub fn replacement(&self) -> &str {
+        &self.replacement
+    }
+
+    /// Returns the version of this crate in which the view will be removed.
+    pub fn removal_version(&self) -> &str {
+        &self.removal_version
+    }
+
+    /// Returns the names of the views and triggers in the database that refer to the
+    /// deprecated view, and which will break when it is removed.
+    pub fn dependents(&self) -> &[String] {
+        &self.dependents
+    }
+}
+
+/// Returns the deprecated views that exist in the database, along with the views and triggers
+/// that depend upon them.
+///
+/// Queries that are issued directly by an application cannot be detected; applications
+/// should check their queries against the views returned here.
+pub fn deprecated_view_usage(
+    conn: &Connection,
+) -> Result<Vec<DeprecatedViewUsage>, rusqlite::Error> {
+    let table_exists = conn.query_row(
+        "SELECT EXISTS (
+            SELECT 1 FROM sqlite_schema WHERE type = 'table' AND name = 'deprecated_views'
+        )",
+        [],
+        |row| row.get::<_, bool>(0),
+    )?;
+    if !table_exists {
+        return Ok(vec![]);
+    }
+
+    let mut deprecated = conn
+        .prepare(
+            "SELECT view_name, replacement, removal_version
+            FROM deprecated_views
+            ORDER BY view_name",
+        )?
+        .query_map([], |row| {
+            Ok(DeprecatedViewUsage {
+                view: row.get(0)?,
+                replacement: row.get(1)?,
+                removal_version: row.get(2)?,
+                dependents: vec![],
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut stmt = conn.prepare(
+        "SELECT name, sql
+        FROM sqlite_schema
+        WHERE type IN ('view', 'trigger')
+        AND name NOT IN (SELECT view_name FROM deprecated_views)
+        ORDER BY name",
+    )?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let name: String = row.get(0)?;
+        let sql: String = row.get(1)?;
+        for usage in deprecated.iter_mut() {
+            if refers_to(&sql, &usage.view) {
+                usage.dependents.push(name.clone());
+            }
+        }
+    }
+
+    Ok(deprecated)
+}
+
+/// Returns whether `sql` contains `name` as a complete identifier.
+fn refers_to(sql: &str, name: &str) -> bool {
+    let is_ident = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    sql.match_indices(name).any(|(i, _)| {
+        !sql[..i].chars().next_back().map_or(false, is_ident)
+            && !sql[i + name.len()..].chars().next().map_or(false, is_ident)
+    })
+}
+
+/// Returns the IDs of the migrations that have been applied to the database, in no particular
+/// order.
+pub(super) fn applied_migration_ids(conn: &Connection) -> Result<Vec<Uuid>, rusqlite::Error> {
+    let migrations_exist = conn.query_row(
+        "SELECT EXISTS (
+            SELECT 1 FROM sqlite_schema WHERE type = 'table' AND name = 'schemer_migrations'
+        )",
+        [],
+        |row| row.get::<_, bool>(0),
+    )?;
+    if !migrations_exist {
+        return Ok(vec![]);
+    }
+
+    let mut stmt = conn.prepare("SELECT id FROM schemer_migrations")?;
+    let mut rows = stmt.query([])?;
+    let mut ids = vec![];
+    while let Some(row) = rows.next()? {
+        let value = row.get_ref(0)?;
+        let id = match value {
+            ValueRef::Blob(bytes) => Uuid::from_slice(bytes).ok(),
+            ValueRef::Text(text) => std::str::from_utf8(text)
+                .ok()
+                .and_then(|s| Uuid::parse_str(s).ok()),
+            _ => None,
+        }
+        .ok_or_else(|| {
+            rusqlite::Error::FromSqlConversionFailure(
+                0,
+                value.data_type(),
+                "invalid migration ID".into(),
+            )
+        })?;
+        ids.push(id);
+    }
+    Ok(ids)
+}
+
+fn get_columns(conn: &Connection, name: &str) -> Result<Vec<Column>, rusqlite::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT name, type, \"notnull\", dflt_value, pk
+        FROM pragma_table_info(:name)
+        ORDER BY cid",
+    )?;
+    let columns = stmt
+        .query_map(named_params![":name": name], |row| {
+            Ok(Column {
+                name: row.get(0)?,
+                declared_type: row.get(1)?,
+                not_null: row.get(2)?,
+                default_value: row.get(3)?,
+                primary_key: row.get::<_, u32>(4)? > 0,
+            })
+        })?
+        .collect::<Result<_, _>>()?;
+    Ok(columns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{deprecated_view_usage, describe_schema};
+    use crate::testing::TestBuilder;
+
+    #[test]
+    fn describes_initialized_wallet() {
+        let st = TestBuilder::new().build();
+        let schema = describe_schema(st.wallet()).unwrap();
+
+        // Every migration known to this crate has been applied.
+        assert!(!schema.migrations().is_empty());
+        assert!(schema
+            .migrations()
+            .iter()
+            .all(|m| m.description().is_some()));
+        assert_eq!(
+            schema.migrations()[0].description(),
+            Some("Initialize the wallet database.")
+        );
+
+        let transactions = schema.table("transactions").unwrap();
+        let txid = transactions.column("txid").unwrap();
+        assert_eq!(txid.declared_type(), "BLOB");
+        assert!(txid.not_null());
+        assert!(transactions.column("id_tx").unwrap().primary_key());
+
+        assert!(schema.view("v_transactions").is_some());
+        assert!(schema
+            .view("v_tx_outputs")
+            .and_then(|v| v.column("output_pool"))
+            .is_some());
+        assert!(schema.table("sqlite_sequence").is_none());
+
+        let ufvk_index = schema
+            .indexes()
+            .iter()
+            .find(|i| i.name() == "accounts_ufvk")
+            .unwrap();
+        assert_eq!(ufvk_index.table(), "accounts");
+        assert!(ufvk_index.unique());
+        assert_eq!(ufvk_index.columns(), &["ufvk".to_owned()]);
+    }
+
+    #[test]
+    fn reports_deprecated_view_dependents() {
+        let st = TestBuilder::new().build();
+        let conn = &st.wallet().conn;
+
+        let usage = deprecated_view_usage(conn).unwrap();
+        assert_eq!(
+            usage.iter().map(|u| u.view()).collect::<Vec<_>>(),
+            vec!["v_tx_received", "v_tx_sent"]
+        );
+        assert!(usage.iter().all(|u| u.replacement() == "v_tx_outputs"));
+        assert!(usage.iter().all(|u| u.dependents().is_empty()));
+
+        // The compatibility views can still be queried.
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM v_tx_sent", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 0);
+
+        conn.execute_batch(
+            "CREATE VIEW app_sent_totals AS SELECT txid, sent_total FROM v_tx_sent;
+            CREATE VIEW app_tx_ids AS SELECT id_tx AS v_tx_sent_id FROM transactions;",
+        )
+        .unwrap();
+        let usage = deprecated_view_usage(conn).unwrap();
+        assert!(usage[0].dependents().is_empty());
+        assert_eq!(usage[1].dependents(), &["app_sent_totals".to_owned()]);
+    }
+}
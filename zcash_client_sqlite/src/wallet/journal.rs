@@ -0,0 +1,292 @@
+//! A journal of significant changes to the wallet's state.
+//!
+//! When a user reports that their balance is wrong, the wallet database records where the wallet
+//! has ended up, but not how it got there. While the journal is enabled (see
+//! [`WalletDb::set_event_journal_enabled`]), the wallet appends a [`WalletEvent`] to the
+//! `wallet_events` table whenever it scans a range of blocks, detects notes in a transaction,
+//! rewinds or rescans the chain, stores a transaction that it created, or detects a spend of its
+//! notes by a transaction that it did not create. The journal can be exported with
+//! [`WalletDb::export_event_journal`] and attached to a support request.
+//!
+//! Events are never modified or removed once recorded; disabling the journal stops new events
+//! from being recorded but retains the existing ones.
+//!
+//! [`WalletDb::set_event_journal_enabled`]: crate::WalletDb::set_event_journal_enabled
+//! [`WalletDb::export_event_journal`]: crate::WalletDb::export_event_journal
+
+use std::fmt;
+
+use rusqlite::{named_params, Connection, OptionalExtension};
+use time::OffsetDateTime;
+use zcash_primitives::{consensus::BlockHeight, transaction::TxId};
+
+use crate::error::SqliteClientError;
+
+/// The kind of change recorded by a [`WalletEvent`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WalletEventKind {
+    /// The journal was enabled.
+    JournalEnabled,
+    /// The journal was disabled. No further events are recorded until it is enabled again.
+    JournalDisabled,
+    /// The blocks in the event's height range were scanned.
+    BlocksScanned,
+    /// Notes belonging to the wallet were received or spent by the event's transaction,
+    /// which was found while scanning the block at the event's start height.
+    NotesDetected,
+    /// The blocks from the event's start height (up to its end height, if present) were
+    /// removed from the wallet, for example because of a chain reorganization.
+    Rewind,
+    /// The blocks from the event's start height were queued to be scanned again.
+    Rescan,
+    /// The wallet stored a transaction that it created.
+    TransactionCreated,
+    /// The event's transaction, which was mined at the event's start height, spent notes
+    /// belonging to the wallet but was not created by the wallet; for example, it may have been
+    /// created by another device using the same seed.
+    ExternalSpendDetected,
+}
+
+impl WalletEventKind {
+    pub(crate) fn code(self) -> &'static str {
+        match self {
+            WalletEventKind::JournalEnabled => "journal_enabled",
+            WalletEventKind::JournalDisabled => "journal_disabled",
+            WalletEventKind::BlocksScanned => "blocks_scanned",
+            WalletEventKind::NotesDetected => "notes_detected",
+            WalletEventKind::Rewind => "rewind",
+            WalletEventKind::Rescan => "rescan",
+            WalletEventKind::TransactionCreated => "transaction_created",
+            WalletEventKind::ExternalSpendDetected => "external_spend_detected",
+        }
+    }
+
+    fn parse(code: &str) -> Option<Self> {
+        match code {
+            "journal_enabled" => Some(WalletEventKind::JournalEnabled),
+            "journal_disabled" => Some(WalletEventKind::JournalDisabled),
+            "blocks_scanned" => Some(WalletEventKind::BlocksScanned),
+            "notes_detected" => Some(WalletEventKind::NotesDetected),
+            "rewind" => Some(WalletEventKind::Rewind),
+            "rescan" => Some(WalletEventKind::Rescan),
+            "transaction_created" => Some(WalletEventKind::TransactionCreated),
+            "external_spend_detected" => Some(WalletEventKind::ExternalSpendDetected),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for WalletEventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+/// An entry in the wallet's event journal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalletEvent {
+    id: u64,
+    recorded_at: OffsetDateTime,
+    kind: WalletEventKind,
+    start_height: Option<BlockHeight>,
+    end_height: Option<BlockHeight>,
+    txid: Option<TxId>,
+    detail: Option<String>,
+}
+
+impl WalletEvent {
+    /// Returns the sequence number of the event. Events are numbered in the order in which
+    /// they were recorded.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Returns the time at which the event was recorded.
+    pub fn recorded_at(&self) -> OffsetDateTime {
+        self.recorded_at
+    }
+
+    /// Returns the kind of change recorded by the event.
+    pub fn kind(&self) -> WalletEventKind {
+        self.kind
+    }
+
+    /// Returns the first block height affected by the event, if any.
+    pub fn start_height(&self) -> Option<BlockHeight> {
+        self.start_height
+    }
+
+    /// Returns the height immediately after the last block affected by the event, if the
+    /// event affected a bounded range of blocks.
+    pub fn end_height(&self) -> Option<BlockHeight> {
+        self.end_height
+    }
+
+    /// Returns the ID of the transaction to which the event relates, if any.
+    pub fn txid(&self) -> Option<TxId> {
+        self.txid
+    }
+
+    /// Returns a human-readable description of the details of the event, if any.
+    pub fn detail(&self) -> Option<&str> {
+        self.detail.as_deref()
+    }
+}
+
+impl fmt::Display for WalletEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.id,
+            self.recorded_at.unix_timestamp(),
+            self.kind
+        )?;
+        match (self.start_height, self.end_height) {
+            (Some(start), Some(end)) => write!(f, " heights={}..{}", start, end)?,
+            (Some(start), None) => write!(f, " heights={}..", start)?,
+            _ => (),
+        }
+        if let Some(txid) = &self.txid {
+            write!(f, " txid={}", txid)?;
+        }
+        if let Some(detail) = &self.detail {
+            write!(f, " {}", detail)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns whether the event journal is enabled.
+pub(crate) fn is_enabled(conn: &Connection) -> Result<bool, SqliteClientError> {
+    Ok(conn
+        .query_row(
+            "SELECT kind FROM wallet_events
+            WHERE kind IN ('journal_enabled', 'journal_disabled')
+            ORDER BY id DESC
+            LIMIT 1",
+            [],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()?
+        .map_or(false, |kind| kind == WalletEventKind::JournalEnabled.code()))
+}
+
+/// Enables or disables the event journal, recording the change if the journal's state is
+/// changed.
+pub(crate) fn set_enabled(conn: &Connection, enabled: bool) -> Result<(), SqliteClientError> {
+    if is_enabled(conn)? != enabled {
+        insert_event(
+            conn,
+            if enabled {
+                WalletEventKind::JournalEnabled
+            } else {
+                WalletEventKind::JournalDisabled
+            },
+            None,
+            None,
+            None,
+            None,
+        )?;
+    }
+    Ok(())
+}
+
+/// Records an event in the journal, if the journal is enabled.
+pub(crate) fn record_event(
+    conn: &Connection,
+    kind: WalletEventKind,
+    start_height: Option<BlockHeight>,
+    end_height: Option<BlockHeight>,
+    txid: Option<&TxId>,
+    detail: Option<&str>,
+) -> Result<(), SqliteClientError> {
+    if is_enabled(conn)? {
+        insert_event(conn, kind, start_height, end_height, txid, detail)?;
+    }
+    Ok(())
+}
+
+fn insert_event(
+    conn: &Connection,
+    kind: WalletEventKind,
+    start_height: Option<BlockHeight>,
+    end_height: Option<BlockHeight>,
+    txid: Option<&TxId>,
+    detail: Option<&str>,
+) -> Result<(), SqliteClientError> {
+    conn.execute(
+        "INSERT INTO wallet_events (recorded_at, kind, start_height, end_height, txid, detail)
+        VALUES (:recorded_at, :kind, :start_height, :end_height, :txid, :detail)",
+        named_params![
+            ":recorded_at": OffsetDateTime::now_utc().unix_timestamp(),
+            ":kind": kind.code(),
+            ":start_height": start_height.map(u32::from),
+            ":end_height": end_height.map(u32::from),
+            ":txid": txid.map(|txid| txid.as_ref().to_vec()),
+            ":detail": detail,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Returns the events in the journal that were recorded at or after `since`, or all events if
+/// `since` is `None`, in the order in which they were recorded.
+pub(crate) fn get_events(
+    conn: &Connection,
+    since: Option<OffsetDateTime>,
+) -> Result<Vec<WalletEvent>, SqliteClientError> {
+    let mut stmt = conn.prepare(
+        "SELECT id, recorded_at, kind, start_height, end_height, txid, detail
+        FROM wallet_events
+        WHERE recorded_at >= :since
+        ORDER BY id",
+    )?;
+
+    let events = stmt
+        .query_and_then(
+            named_params![":since": since.map_or(i64::MIN, |t| t.unix_timestamp())],
+            |row| {
+                let recorded_at = row.get::<_, i64>(1)?;
+                let recorded_at =
+                    OffsetDateTime::from_unix_timestamp(recorded_at).map_err(|_| {
+                        SqliteClientError::CorruptedData(format!(
+                            "Invalid wallet event timestamp: {}",
+                            recorded_at
+                        ))
+                    })?;
+                let kind = row.get::<_, String>(2)?;
+                let kind = WalletEventKind::parse(&kind).ok_or_else(|| {
+                    SqliteClientError::CorruptedData(format!(
+                        "Unrecognized wallet event kind: {}",
+                        kind
+                    ))
+                })?;
+                let txid = row
+                    .get::<_, Option<Vec<u8>>>(5)?
+                    .map(|bytes| {
+                        <[u8; 32]>::try_from(&bytes[..])
+                            .map(TxId::from_bytes)
+                            .map_err(|_| {
+                                SqliteClientError::CorruptedData(
+                                    "Invalid transaction ID in wallet event".to_owned(),
+                                )
+                            })
+                    })
+                    .transpose()?;
+
+                Ok(WalletEvent {
+                    id: row.get(0)?,
+                    recorded_at,
+                    kind,
+                    start_height: row.get::<_, Option<u32>>(3)?.map(BlockHeight::from),
+                    end_height: row.get::<_, Option<u32>>(4)?.map(BlockHeight::from),
+                    txid,
+                    detail: row.get(6)?,
+                })
+            },
+        )?
+        .collect::<Result<Vec<_>, SqliteClientError>>()?;
+
+    Ok(events)
+}
@@ -0,0 +1,167 @@
+//! Double-entry views of the wallet's transactions.
+//!
+//! The `v_ledger` view presents the effect of each transaction known to the wallet as a set of
+//! [`LedgerEntry`] values, following the accounting convention that debits are positive and
+//! credits are negative. Receiving a note debits the receiving account, and spending a note
+//! credits the account that owned it. Payments to recipients outside the wallet, and the fee
+//! paid by the transaction, are debits that are not attributed to any wallet account. A
+//! transaction that spends nothing from the wallet was funded from outside it, so each of its
+//! other entries is matched by a [`LedgerEntryType::External`] credit for the same output.
+
+use rusqlite::{named_params, Connection};
+use zcash_client_backend::PoolType;
+use zcash_primitives::{
+    consensus::BlockHeight,
+    transaction::{components::amount::Amount, TxId},
+};
+
+use super::parse_pool_code;
+use crate::{error::SqliteClientError, AccountId};
+
+/// The kind of value movement recorded by a [`LedgerEntry`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LedgerEntryType {
+    /// An output of the transaction was received by a wallet account.
+    Receive,
+    /// A note or transparent output belonging to a wallet account was spent by the
+    /// transaction.
+    Spend,
+    /// An output of the transaction was sent to a recipient outside the wallet.
+    Send,
+    /// The fee paid by the transaction.
+    Fee,
+    /// Value that entered the transaction from outside the wallet, recorded against each
+    /// output and the fee of a transaction that spends nothing from the wallet.
+    External,
+}
+
+impl LedgerEntryType {
+    fn parse(code: &str) -> Option<Self> {
+        match code {
+            "receive" => Some(LedgerEntryType::Receive),
+            "spend" => Some(LedgerEntryType::Spend),
+            "send" => Some(LedgerEntryType::Send),
+            "fee" => Some(LedgerEntryType::Fee),
+            "external" => Some(LedgerEntryType::External),
+            _ => None,
+        }
+    }
+}
+
+/// A single debit or credit recorded against a transaction in the wallet's ledger.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LedgerEntry {
+    txid: TxId,
+    mined_height: Option<BlockHeight>,
+    account_id: Option<AccountId>,
+    entry_type: LedgerEntryType,
+    pool: Option<PoolType>,
+    output_index: Option<u32>,
+    value: Amount,
+}
+
+impl LedgerEntry {
+    /// Returns the ID of the transaction to which this entry belongs.
+    pub fn txid(&self) -> TxId {
+        self.txid
+    }
+
+    /// Returns the height at which the transaction was mined, if it is known to have been
+    /// mined.
+    pub fn mined_height(&self) -> Option<BlockHeight> {
+        self.mined_height
+    }
+
+    /// Returns the wallet account whose balance is changed by this entry.
+    ///
+    /// This is `None` for [`LedgerEntryType::Send`], [`LedgerEntryType::Fee`], and
+    /// [`LedgerEntryType::External`] entries.
+    pub fn account_id(&self) -> Option<AccountId> {
+        self.account_id
+    }
+
+    /// Returns the kind of value movement recorded by this entry.
+    pub fn entry_type(&self) -> LedgerEntryType {
+        self.entry_type
+    }
+
+    /// Returns the pool of the output that was received, sent, or spent.
+    ///
+    /// This is `None` for [`LedgerEntryType::Fee`] entries, and for the
+    /// [`LedgerEntryType::External`] entries that match them.
+    pub fn pool(&self) -> Option<PoolType> {
+        self.pool
+    }
+
+    /// Returns the index of the output within the transaction, for
+    /// [`LedgerEntryType::Receive`] and [`LedgerEntryType::Send`] entries and the
+    /// [`LedgerEntryType::External`] entries that match them.
+    pub fn output_index(&self) -> Option<u32> {
+        self.output_index
+    }
+
+    /// Returns the value of this entry; debits are positive and credits are negative.
+    pub fn value(&self) -> Amount {
+        self.value
+    }
+}
+
+/// Returns the ledger entries for the transaction with the given ID.
+///
+/// The returned entries sum to zero if the wallet's records of the transaction are complete. If
+/// the transaction is not known to the wallet, no entries are returned.
+pub(crate) fn get_ledger_entries(
+    conn: &Connection,
+    txid: TxId,
+) -> Result<Vec<LedgerEntry>, SqliteClientError> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT mined_height, account_id, entry_type, output_pool, output_index, value
+         FROM v_ledger
+         WHERE txid = :txid
+         ORDER BY entry_type, output_pool, output_index",
+    )?;
+
+    let entries = stmt
+        .query_and_then(named_params![":txid": txid.as_ref()], |row| {
+            let entry_type = row.get::<_, String>("entry_type")?;
+            let entry_type = LedgerEntryType::parse(&entry_type).ok_or_else(|| {
+                SqliteClientError::CorruptedData(format!(
+                    "Unrecognized ledger entry type: {}",
+                    entry_type
+                ))
+            })?;
+            let pool = row
+                .get::<_, Option<i64>>("output_pool")?
+                .map(|code| {
+                    parse_pool_code(code).ok_or_else(|| {
+                        SqliteClientError::CorruptedData(format!(
+                            "Unrecognized pool code: {}",
+                            code
+                        ))
+                    })
+                })
+                .transpose()?;
+            let raw_value = row.get::<_, i64>("value")?;
+            let value = Amount::from_i64(raw_value).map_err(|_| {
+                SqliteClientError::CorruptedData(format!(
+                    "Ledger entry value out of range: {}",
+                    raw_value
+                ))
+            })?;
+
+            Ok(LedgerEntry {
+                txid,
+                mined_height: row
+                    .get::<_, Option<u32>>("mined_height")?
+                    .map(BlockHeight::from),
+                account_id: row.get("account_id")?,
+                entry_type,
+                pool,
+                output_index: row.get("output_index")?,
+                value,
+            })
+        })?
+        .collect::<Result<Vec<_>, SqliteClientError>>()?;
+
+    Ok(entries)
+}
@@ -9,7 +9,7 @@ use rusqlite::{named_params, types::Value, Connection, Row, Transaction};
 
 use zcash_client_backend::{
     data_api::NullifierQuery,
-    wallet::{ReceivedNote, WalletOrchardOutput},
+    wallet::{NoteId, ReceivedNote, WalletOrchardOutput},
     DecryptedOutput, ShieldedProtocol, TransferType,
 };
 use zcash_keys::keys::UnifiedFullViewingKey;
@@ -21,7 +21,7 @@ use zcash_protocol::{
 };
 use zip32::Scope;
 
-use crate::{error::SqliteClientError, AccountId, ReceivedNoteId};
+use crate::{error::SqliteClientError, AccountId};
 
 use super::{memo_repr, parse_scope, scope_code};
 
@@ -98,10 +98,10 @@ impl ReceivedOrchardOutput for DecryptedOutput<Note, AccountId> {
 fn to_spendable_note<P: consensus::Parameters>(
     params: &P,
     row: &Row,
-) -> Result<Option<ReceivedNote<ReceivedNoteId, Note>>, SqliteClientError> {
-    let note_id = ReceivedNoteId(ShieldedProtocol::Orchard, row.get("id")?);
+) -> Result<Option<ReceivedNote<NoteId, Note>>, SqliteClientError> {
     let txid = row.get::<_, [u8; 32]>("txid").map(TxId::from_bytes)?;
     let action_index = row.get("action_index")?;
+    let note_id = NoteId::new(txid, ShieldedProtocol::Orchard, action_index);
     let diversifier = {
         let d: Vec<_> = row.get("diversifier")?;
         if d.len() != 11 {
@@ -188,7 +188,7 @@ pub(crate) fn get_spendable_orchard_note<P: consensus::Parameters>(
     params: &P,
     txid: &TxId,
     index: u32,
-) -> Result<Option<ReceivedNote<ReceivedNoteId, Note>>, SqliteClientError> {
+) -> Result<Option<ReceivedNote<NoteId, Note>>, SqliteClientError> {
     super::common::get_spendable_note(
         conn,
         params,
@@ -205,8 +205,8 @@ pub(crate) fn select_spendable_orchard_notes<P: consensus::Parameters>(
     account: AccountId,
     target_value: Zatoshis,
     anchor_height: BlockHeight,
-    exclude: &[ReceivedNoteId],
-) -> Result<Vec<ReceivedNote<ReceivedNoteId, Note>>, SqliteClientError> {
+    exclude: &[NoteId],
+) -> Result<Vec<ReceivedNote<NoteId, Note>>, SqliteClientError> {
     super::common::select_spendable_notes(
         conn,
         params,
@@ -265,7 +265,7 @@ pub(crate) fn put_received_note<T: ReceivedOrchardOutput>(
     let sql_args = named_params![
         ":tx": &tx_ref,
         ":action_index": i64::try_from(output.index()).expect("output indices are representable as i64"),
-        ":account_id": output.account_id().0,
+        ":account_id": output.account_id(),
         ":diversifier": diversifier.as_array(),
         ":value": output.note().value().inner(),
         ":rho": output.note().rho().to_bytes(),
@@ -329,7 +329,7 @@ pub(crate) fn get_orchard_nullifiers(
     };
 
     let nullifiers = stmt_fetch_nullifiers.query_and_then([], |row| {
-        let account = AccountId(row.get(0)?);
+        let account: AccountId = row.get(0)?;
         let nf_bytes: [u8; 32] = row.get(1)?;
         Ok::<_, rusqlite::Error>((account, Nullifier::from_bytes(&nf_bytes).unwrap()))
     })?;
@@ -396,16 +396,17 @@ pub(crate) mod tests {
     use shardtree::error::ShardTreeError;
     use zcash_client_backend::{
         data_api::{
-            chain::CommitmentTreeRoot, DecryptedTransaction, WalletCommitmentTrees, WalletSummary,
+            chain::CommitmentTreeRoot, BlockNullifiers, DecryptedTransaction, NullifierQuery,
+            WalletCommitmentTrees, WalletRead, WalletSummary,
         },
-        wallet::{Note, ReceivedNote},
+        wallet::{Note, NoteId, ReceivedNote},
     };
     use zcash_keys::{
         address::{Address, UnifiedAddress},
         keys::UnifiedSpendingKey,
     };
     use zcash_note_encryption::try_output_recovery_with_ovk;
-    use zcash_primitives::transaction::Transaction;
+    use zcash_primitives::transaction::{Transaction, TxId};
     use zcash_protocol::{consensus::BlockHeight, memo::MemoBytes, ShieldedProtocol};
 
     use super::select_spendable_orchard_notes;
@@ -497,9 +498,8 @@ pub(crate) mod tests {
             account: crate::AccountId,
             target_value: zcash_protocol::value::Zatoshis,
             anchor_height: BlockHeight,
-            exclude: &[crate::ReceivedNoteId],
-        ) -> Result<Vec<ReceivedNote<crate::ReceivedNoteId, orchard::note::Note>>, SqliteClientError>
-        {
+            exclude: &[NoteId],
+        ) -> Result<Vec<ReceivedNote<NoteId, orchard::note::Note>>, SqliteClientError> {
             select_spendable_orchard_notes(
                 &st.wallet().conn,
                 &st.wallet().params,
@@ -562,6 +562,22 @@ pub(crate) mod tests {
         ) -> usize {
             summary.received_orchard_note_count()
         }
+
+        fn spend_all_nullifiers<Cache>(
+            st: &TestState<Cache>,
+            height: BlockHeight,
+            txid: TxId,
+        ) -> BlockNullifiers {
+            let nfs = st
+                .wallet()
+                .get_orchard_nullifiers(NullifierQuery::Unspent)
+                .unwrap()
+                .into_iter()
+                .map(|(_, nf)| nf)
+                .collect();
+
+            BlockNullifiers::from_parts(height, vec![], vec![(txid, 0, nfs)])
+        }
     }
 
     #[test]
@@ -569,6 +585,26 @@ pub(crate) mod tests {
         testing::pool::send_single_step_proposed_transfer::<OrchardPoolTester>()
     }
 
+    #[test]
+    fn payment_disclosure() {
+        testing::pool::payment_disclosure::<OrchardPoolTester>()
+    }
+
+    #[test]
+    fn validate_for_broadcast_checks_expiry() {
+        testing::pool::validate_for_broadcast_checks_expiry::<OrchardPoolTester>()
+    }
+
+    #[test]
+    fn fee_guardrails_check_proposal() {
+        testing::pool::fee_guardrails_check_proposal::<OrchardPoolTester>()
+    }
+
+    #[test]
+    fn spend_policy_vetoes_proposal() {
+        testing::pool::spend_policy_vetoes_proposal::<OrchardPoolTester>()
+    }
+
     #[test]
     #[cfg(feature = "transparent-inputs")]
     fn send_multi_step_proposed_transfer() {
@@ -581,6 +617,11 @@ pub(crate) mod tests {
         testing::pool::create_to_address_fails_on_incorrect_usk::<OrchardPoolTester>()
     }
 
+    #[test]
+    fn spends_are_authorized_by_signer() {
+        testing::pool::spends_are_authorized_by_signer::<OrchardPoolTester>()
+    }
+
     #[test]
     #[allow(deprecated)]
     fn proposal_fails_with_no_blocks() {
@@ -592,6 +633,16 @@ pub(crate) mod tests {
         testing::pool::spend_fails_on_unverified_notes::<OrchardPoolTester>()
     }
 
+    #[test]
+    fn audit_bundle_export() {
+        testing::pool::audit_bundle_export::<OrchardPoolTester>()
+    }
+
+    #[test]
+    fn change_pending_scanning_is_reported_as_change() {
+        testing::pool::change_pending_scanning_is_reported_as_change::<OrchardPoolTester>()
+    }
+
     #[test]
     fn spend_fails_on_locked_notes() {
         testing::pool::spend_fails_on_locked_notes::<OrchardPoolTester>()
@@ -626,6 +677,126 @@ pub(crate) mod tests {
         testing::pool::zip317_spend::<OrchardPoolTester>()
     }
 
+    #[test]
+    fn dust_notes_ignored_by_filter() {
+        testing::pool::dust_notes_ignored_by_filter::<OrchardPoolTester>()
+    }
+
+    #[test]
+    fn spends_detected_by_nullifier_lookahead() {
+        testing::pool::spends_detected_by_nullifier_lookahead::<OrchardPoolTester>()
+    }
+
+    #[test]
+    fn lookahead_spends_removed_by_truncation() {
+        testing::pool::lookahead_spends_removed_by_truncation::<OrchardPoolTester>()
+    }
+
+    #[test]
+    fn account_balances_cache_invalidated() {
+        testing::pool::account_balances_cache_invalidated::<OrchardPoolTester>()
+    }
+
+    #[test]
+    fn account_balances_cache_retained() {
+        testing::pool::account_balances_cache_retained::<OrchardPoolTester>()
+    }
+
+    #[test]
+    fn truncate_scanned_data_requeues_range() {
+        testing::pool::truncate_scanned_data_requeues_range::<OrchardPoolTester>()
+    }
+
+    #[test]
+    fn rescan_from_rolls_back_spends() {
+        testing::pool::rescan_from_rolls_back_spends::<OrchardPoolTester>()
+    }
+
+    #[test]
+    fn rescan_from_retains_wallet_spends() {
+        testing::pool::rescan_from_retains_wallet_spends::<OrchardPoolTester>()
+    }
+
+    #[test]
+    fn export_import_tree_state() {
+        testing::pool::export_import_tree_state::<OrchardPoolTester>()
+    }
+
+    #[test]
+    fn repair_witnesses_rescans_damaged_shard() {
+        testing::pool::repair_witnesses_rescans_damaged_shard::<OrchardPoolTester>()
+    }
+
+    #[test]
+    fn prune_note_commitment_trees() {
+        testing::pool::prune_note_commitment_trees::<OrchardPoolTester>()
+    }
+
+    #[test]
+    fn view_snapshots() {
+        testing::pool::view_snapshots::<OrchardPoolTester>()
+    }
+
+    #[test]
+    fn balance_at_height() {
+        testing::pool::balance_at_height::<OrchardPoolTester>()
+    }
+
+    #[test]
+    fn account_statistics() {
+        testing::pool::account_statistics::<OrchardPoolTester>()
+    }
+
+    #[test]
+    fn scheduled_payments() {
+        testing::pool::scheduled_payments::<OrchardPoolTester>()
+    }
+
+    #[test]
+    fn batch_transfer() {
+        testing::pool::batch_transfer::<OrchardPoolTester>()
+    }
+
+    #[test]
+    fn transaction_size_estimate() {
+        testing::pool::transaction_size_estimate::<OrchardPoolTester>()
+    }
+
+    #[test]
+    fn conflicting_proposals() {
+        testing::pool::conflicting_proposals::<OrchardPoolTester>()
+    }
+
+    #[test]
+    fn external_spends_are_marked() {
+        testing::pool::external_spends_are_marked::<OrchardPoolTester>()
+    }
+
+    #[test]
+    fn rescanning_scanned_blocks_is_noop() {
+        testing::pool::rescanning_scanned_blocks_is_noop::<OrchardPoolTester>()
+    }
+
+    #[test]
+    fn sent_notes_record_change() {
+        testing::pool::sent_notes_record_change::<OrchardPoolTester>()
+    }
+
+    #[test]
+    fn ledger_entries_balance() {
+        testing::pool::ledger_entries_balance::<OrchardPoolTester>()
+    }
+
+    #[test]
+    fn decrypted_tx_fee_is_stored() {
+        testing::pool::decrypted_tx_fee_is_stored::<OrchardPoolTester>()
+    }
+
+    #[test]
+    fn transaction_status_requests() {
+        testing::pool::transaction_status_requests::<OrchardPoolTester>()
+    }
+
     #[test]
     #[cfg(feature = "transparent-inputs")]
     fn shield_transparent() {
@@ -642,6 +813,21 @@ pub(crate) mod tests {
         testing::pool::checkpoint_gaps::<OrchardPoolTester>()
     }
 
+    #[test]
+    fn unusable_recipients_rejected() {
+        testing::pool::unusable_recipients_rejected::<OrchardPoolTester>()
+    }
+
+    #[test]
+    fn privacy_policy_single_pool() {
+        testing::pool::privacy_policy_single_pool::<OrchardPoolTester>()
+    }
+
+    #[test]
+    fn tx_history_privacy_level() {
+        testing::pool::tx_history_privacy_level::<OrchardPoolTester>()
+    }
+
     #[test]
     fn scan_cached_blocks_detects_spends_out_of_order() {
         testing::pool::scan_cached_blocks_detects_spends_out_of_order::<OrchardPoolTester>()
@@ -652,6 +838,11 @@ pub(crate) mod tests {
         testing::pool::pool_crossing_required::<OrchardPoolTester, SaplingPoolTester>()
     }
 
+    #[test]
+    fn privacy_policy_pool_crossing() {
+        testing::pool::privacy_policy_pool_crossing::<OrchardPoolTester, SaplingPoolTester>()
+    }
+
     #[test]
     fn fully_funded_fully_private() {
         testing::pool::fully_funded_fully_private::<OrchardPoolTester, SaplingPoolTester>()
@@ -0,0 +1,254 @@
+//! Pruning of note commitment tree data that the wallet no longer requires.
+//!
+//! The wallet stores the full contents of every shard of the note commitment trees in which
+//! it has received notes, so that witnesses for those notes can be computed. Once every note
+//! in a shard has been spent in a block that can no longer be rolled back, only the root of
+//! the shard is required (to compute witnesses for notes in other shards). [`prune_tree`]
+//! replaces the data for such shards by their roots, and removes the checkpoints below the
+//! stability depth, so that the size of the trees remains bounded as the wallet ages.
+
+use std::io;
+
+use incrementalmerkletree::Hashable;
+use rusqlite::named_params;
+use shardtree::{error::ShardTreeError, PrunableTree, RetentionFlags};
+
+use zcash_client_backend::{serialization::shardtree::write_shard, ShieldedProtocol};
+use zcash_primitives::merkle_tree::HashSer;
+
+use super::commitment_tree;
+use crate::{error::SqliteClientError, PRUNING_DEPTH};
+
+/// Size metrics for the stored data of one of the wallet's note commitment trees.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TreeMetrics {
+    protocol: ShieldedProtocol,
+    shard_count: u64,
+    pruned_shard_count: u64,
+    shard_data_bytes: u64,
+    checkpoint_count: u64,
+}
+
+impl TreeMetrics {
+    /// Returns the protocol of the note commitment tree.
+    pub fn protocol(&self) -> ShieldedProtocol {
+        self.protocol
+    }
+
+    /// Returns the number of shards of the tree for which data is stored.
+    pub fn shard_count(&self) -> u64 {
+        self.shard_count
+    }
+
+    /// Returns the number of shards for which only the root is stored, either because they
+    /// have been pruned or because they have not yet been scanned.
+    pub fn pruned_shard_count(&self) -> u64 {
+        self.pruned_shard_count
+    }
+
+    /// Returns the total size of the stored shard data, in bytes.
+    pub fn shard_data_bytes(&self) -> u64 {
+        self.shard_data_bytes
+    }
+
+    /// Returns the number of checkpoints of the tree.
+    pub fn checkpoint_count(&self) -> u64 {
+        self.checkpoint_count
+    }
+}
+
+/// The changes made to one of the wallet's note commitment trees by
+/// [`WalletDb::prune_note_commitment_trees`].
+///
+/// [`WalletDb::prune_note_commitment_trees`]: crate::WalletDb::prune_note_commitment_trees
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TreePruning {
+    protocol: ShieldedProtocol,
+    checkpoints_removed: u64,
+    shards_pruned: u64,
+    bytes_freed: u64,
+}
+
+impl TreePruning {
+    /// Returns the protocol of the note commitment tree.
+    pub fn protocol(&self) -> ShieldedProtocol {
+        self.protocol
+    }
+
+    /// Returns the number of checkpoints that were removed.
+    pub fn checkpoints_removed(&self) -> u64 {
+        self.checkpoints_removed
+    }
+
+    /// Returns the number of shards whose data was replaced by their roots.
+    pub fn shards_pruned(&self) -> u64 {
+        self.shards_pruned
+    }
+
+    /// Returns the reduction in the size of the stored shard data, in bytes.
+    ///
+    /// The space is reused by SQLite for subsequent writes; the size of the database file is
+    /// only reduced by a `VACUUM`.
+    pub fn bytes_freed(&self) -> u64 {
+        self.bytes_freed
+    }
+}
+
+fn serialization_error(e: io::Error) -> SqliteClientError {
+    SqliteClientError::CommitmentTree(ShardTreeError::Storage(
+        commitment_tree::Error::Serialization(e),
+    ))
+}
+
+/// Returns the encoding of a shard that consists only of its root.
+fn root_only_shard<H: HashSer>(root: H) -> Result<Vec<u8>, SqliteClientError> {
+    let mut data = vec![];
+    write_shard(
+        &mut data,
+        &PrunableTree::leaf((root, RetentionFlags::EPHEMERAL)),
+    )
+    .map_err(serialization_error)?;
+    Ok(data)
+}
+
+/// Returns size metrics for the note commitment tree with the given table prefix.
+pub(crate) fn tree_metrics<H: Hashable + HashSer>(
+    conn: &rusqlite::Connection,
+    protocol: ShieldedProtocol,
+    table_prefix: &'static str,
+) -> Result<TreeMetrics, SqliteClientError> {
+    // Every root-only shard has an encoding of the same length.
+    let root_only_len = root_only_shard(H::empty_leaf())?.len();
+
+    let (shard_count, pruned_shard_count, shard_data_bytes) = conn.query_row(
+        &format!(
+            "SELECT COUNT(*),
+                    COALESCE(SUM(LENGTH(shard_data) = :root_only_len), 0),
+                    COALESCE(SUM(LENGTH(shard_data)), 0)
+            FROM {}_tree_shards",
+            table_prefix
+        ),
+        named_params![":root_only_len": root_only_len],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )?;
+    let checkpoint_count = conn.query_row(
+        &format!("SELECT COUNT(*) FROM {}_tree_checkpoints", table_prefix),
+        [],
+        |row| row.get(0),
+    )?;
+
+    Ok(TreeMetrics {
+        protocol,
+        shard_count,
+        pruned_shard_count,
+        shard_data_bytes,
+        checkpoint_count,
+    })
+}
+
+/// Prunes the note commitment tree with the given table prefix below the stable height, which
+/// is `stability_depth` blocks below the tree's latest checkpoint.
+///
+/// Checkpoints below the stable height are removed. The data for each fully-scanned shard that
+/// was completed below the stable height is replaced by the shard's root, provided that every
+/// note the wallet received in the shard was spent in a transaction mined below the stable
+/// height. A `stability_depth` of less than [`PRUNING_DEPTH`] is treated as [`PRUNING_DEPTH`],
+/// so that the wallet can still be rewound by up to that many blocks.
+pub(crate) fn prune_tree<H: HashSer, const SHARD_HEIGHT: u8>(
+    conn: &rusqlite::Transaction<'_>,
+    protocol: ShieldedProtocol,
+    table_prefix: &'static str,
+    stability_depth: u32,
+) -> Result<TreePruning, SqliteClientError> {
+    let mut result = TreePruning {
+        protocol,
+        checkpoints_removed: 0,
+        shards_pruned: 0,
+        bytes_freed: 0,
+    };
+
+    let stable_height = match commitment_tree::max_checkpoint_id(conn, table_prefix)
+        .map_err(|e| SqliteClientError::CommitmentTree(ShardTreeError::Storage(e)))?
+        .and_then(|h| u32::from(h).checked_sub(std::cmp::max(stability_depth, PRUNING_DEPTH)))
+    {
+        Some(h) => h,
+        None => return Ok(result),
+    };
+
+    conn.execute(
+        &format!(
+            "DELETE FROM {}_tree_checkpoint_marks_removed WHERE checkpoint_id < :stable_height",
+            table_prefix
+        ),
+        named_params![":stable_height": stable_height],
+    )?;
+    result.checkpoints_removed = conn.execute(
+        &format!(
+            "DELETE FROM {}_tree_checkpoints WHERE checkpoint_id < :stable_height",
+            table_prefix
+        ),
+        named_params![":stable_height": stable_height],
+    )? as u64;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT shard.shard_index, shard.root_hash, LENGTH(shard.shard_data)
+        FROM {table_prefix}_tree_shards shard
+        WHERE shard.root_hash IS NOT NULL
+        AND shard.shard_data IS NOT NULL
+        AND shard.subtree_end_height < :stable_height
+        AND NOT EXISTS (
+            SELECT 1 FROM v_{table_prefix}_shard_unscanned_ranges unscanned
+            WHERE unscanned.shard_index = shard.shard_index
+        )
+        AND NOT EXISTS (
+            SELECT 1 FROM {table_prefix}_received_notes rn
+            WHERE rn.commitment_tree_position >= shard.shard_index * :shard_size
+            AND rn.commitment_tree_position < (shard.shard_index + 1) * :shard_size
+            AND NOT EXISTS (
+                SELECT 1
+                FROM {table_prefix}_received_note_spends sp
+                JOIN transactions stx ON stx.id_tx = sp.transaction_id
+                WHERE sp.{table_prefix}_received_note_id = rn.id
+                AND stx.block < :stable_height
+            )
+        )"
+    ))?;
+    let candidates = stmt
+        .query_map(
+            named_params![
+                ":stable_height": stable_height,
+                ":shard_size": 1u64 << SHARD_HEIGHT,
+            ],
+            |row| {
+                Ok((
+                    row.get::<_, u64>(0)?,
+                    row.get::<_, Vec<u8>>(1)?,
+                    row.get::<_, u64>(2)?,
+                ))
+            },
+        )?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for (shard_index, root_hash, data_len) in candidates {
+        let root = H::read(&root_hash[..]).map_err(serialization_error)?;
+        let shard_data = root_only_shard(root)?;
+        if data_len <= shard_data.len() as u64 {
+            // The shard has already been pruned.
+            continue;
+        }
+
+        conn.execute(
+            &format!(
+                "UPDATE {}_tree_shards
+                SET shard_data = :shard_data, contains_marked = 0
+                WHERE shard_index = :shard_index",
+                table_prefix
+            ),
+            named_params![":shard_data": shard_data, ":shard_index": shard_index,],
+        )?;
+        result.shards_pruned += 1;
+        result.bytes_freed += data_len - shard_data.len() as u64;
+    }
+
+    Ok(result)
+}
@@ -0,0 +1,240 @@
+//! Repair of corrupted note commitment tree data.
+//!
+//! The witnesses for the wallet's notes are computed on demand from the note commitment trees.
+//! If the data stored for a shard of a tree is damaged, the notes in that shard can no longer
+//! be spent. Rather than requiring the whole chain to be rescanned, [`repair_witnesses`]
+//! discards the data for only the affected shards, and queues the blocks that contributed to
+//! those shards to be scanned again.
+
+use std::{collections::BTreeMap, ops::Range};
+
+use incrementalmerkletree::{Address, Hashable, Level, MerklePath, Position};
+use rusqlite::{named_params, OptionalExtension};
+use shardtree::{error::ShardTreeError, store::ShardStore, ShardTree};
+
+use zcash_client_backend::{
+    data_api::{
+        chain::CommitmentTreeRoot,
+        scanning::{ScanPriority, ScanRange},
+    },
+    wallet::NoteId,
+    ShieldedProtocol,
+};
+use zcash_primitives::{consensus::BlockHeight, merkle_tree::HashSer};
+
+use super::{
+    commitment_tree::{self, put_shard_roots, SqliteShardStore},
+    common::get_unspent_note_positions,
+    scan_queue_extrema,
+    scanning::{clear_account_backfill, replace_queue_entries},
+    wallet_birthday,
+};
+use crate::{error::SqliteClientError, PRUNING_DEPTH};
+
+/// Checks the witnesses of the wallet's unspent notes in the given tree that were mined in
+/// `range`, and repairs the shards containing any notes whose witnesses are invalid.
+///
+/// A witness is considered invalid if the note's commitment is not marked in the tree, if a
+/// witness cannot be computed as of the latest checkpoint, if the witness does not lead to the
+/// tree's root at that checkpoint, or if it does not lead to the root of the note's shard that
+/// was obtained from the server. The data for each affected shard is replaced by the shard's
+/// root (if known), and the range of blocks covered by the shard is queued for rescanning with
+/// [`ScanPriority::FoundNote`] priority.
+///
+/// Returns the identifiers of the notes whose witnesses were found to be invalid.
+pub(crate) fn repair_witnesses<
+    H: Hashable + HashSer + Clone + Eq,
+    const DEPTH: u8,
+    const SHARD_HEIGHT: u8,
+>(
+    conn: &rusqlite::Transaction<'_>,
+    protocol: ShieldedProtocol,
+    table_prefix: &'static str,
+    range: &Range<BlockHeight>,
+) -> Result<Vec<NoteId>, SqliteClientError> {
+    let anchor_height = match commitment_tree::max_checkpoint_id(conn, table_prefix)
+        .map_err(|e| SqliteClientError::CommitmentTree(ShardTreeError::Storage(e)))?
+    {
+        Some(h) => h,
+        None => return Ok(vec![]),
+    };
+    let notes = get_unspent_note_positions(conn, protocol, range, anchor_height)?;
+    if notes.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let tree: ShardTree<SqliteShardStore<_, H, SHARD_HEIGHT>, DEPTH, SHARD_HEIGHT> = ShardTree::new(
+        SqliteShardStore::from_connection(conn, table_prefix)?,
+        PRUNING_DEPTH.try_into().unwrap(),
+    );
+
+    let mut invalid = vec![];
+    let mut damaged_shards = BTreeMap::new();
+    for (note_id, position) in notes {
+        let shard_index = Address::above_position(SHARD_HEIGHT.into(), position).index();
+        let damaged = damaged_shards.contains_key(&shard_index) || {
+            let shard = get_shard_row::<H>(conn, table_prefix, shard_index)?;
+            let valid = match check_witness(
+                &tree,
+                position,
+                anchor_height,
+                shard.as_ref().and_then(|(_, root)| root.as_ref()),
+            ) {
+                Ok(valid) => valid,
+                // Failures to query the database are not evidence of damage to the tree.
+                Err(ShardTreeError::Storage(commitment_tree::Error::Query(e))) => {
+                    return Err(SqliteClientError::DbError(e))
+                }
+                Err(_) => false,
+            };
+            if !valid {
+                damaged_shards.insert(shard_index, shard);
+            }
+            !valid
+        };
+        if damaged {
+            invalid.push(note_id);
+        }
+    }
+
+    if damaged_shards.is_empty() {
+        return Ok(invalid);
+    }
+
+    let birthday_height = wallet_birthday(conn)?.expect("the wallet has notes, so has a birthday");
+    let chain_tip_height = scan_queue_extrema(conn)?.map_or(anchor_height, |r| *r.end());
+
+    let mut rescan_ranges = vec![];
+    for (shard_index, shard) in damaged_shards {
+        // Discard the stored shard, retaining only its root if it was obtained from the
+        // server, so that witnesses for notes in other shards can still be computed.
+        conn.execute(
+            &format!(
+                "DELETE FROM {}_tree_shards WHERE shard_index = :shard_index",
+                table_prefix
+            ),
+            named_params![":shard_index": shard_index],
+        )?;
+        let shard_end_height = shard.as_ref().and_then(|(end_height, _)| *end_height);
+        if let Some((Some(end_height), Some(root))) = shard {
+            put_shard_roots::<H, DEPTH, SHARD_HEIGHT>(
+                conn,
+                table_prefix,
+                shard_index,
+                &[CommitmentTreeRoot::from_parts(end_height, root)],
+            )?;
+        }
+
+        // The first block of the shard may also contain the last notes of the previous shard,
+        // so we start from the end of the previous shard.
+        let start = match shard_index.checked_sub(1) {
+            Some(prev) => {
+                get_shard_row::<H>(conn, table_prefix, prev)?.and_then(|(end_height, _)| end_height)
+            }
+            None => None,
+        }
+        .map_or(birthday_height, |h| std::cmp::max(h, birthday_height));
+        let end = shard_end_height.unwrap_or(chain_tip_height) + 1;
+        rescan_ranges.push(ScanRange::from_parts(start..end, ScanPriority::FoundNote));
+    }
+
+    // Notes belonging to any of the wallet's accounts may need to be re-marked.
+    for range in &rescan_ranges {
+        clear_account_backfill(conn, range.block_range(), false)?;
+    }
+
+    let query_range = rescan_ranges[0].block_range().start
+        ..rescan_ranges[rescan_ranges.len() - 1].block_range().end;
+    replace_queue_entries::<SqliteClientError>(
+        conn,
+        &query_range,
+        rescan_ranges.into_iter(),
+        true, // force rescan
+    )?;
+
+    Ok(invalid)
+}
+
+/// Returns the end height and root hash of the shard with the given index, if a row exists
+/// for it.
+fn get_shard_row<H: HashSer>(
+    conn: &rusqlite::Connection,
+    table_prefix: &'static str,
+    shard_index: u64,
+) -> Result<Option<(Option<BlockHeight>, Option<H>)>, SqliteClientError> {
+    conn.query_row(
+        &format!(
+            "SELECT subtree_end_height, root_hash
+            FROM {}_tree_shards
+            WHERE shard_index = :shard_index",
+            table_prefix
+        ),
+        named_params![":shard_index": shard_index],
+        |row| {
+            Ok((
+                row.get::<_, Option<u32>>(0)?.map(BlockHeight::from),
+                row.get::<_, Option<Vec<u8>>>(1)?,
+            ))
+        },
+    )
+    .optional()?
+    .map(|(end_height, root_hash)| {
+        let root = root_hash
+            .map(|bytes| H::read(&bytes[..]))
+            .transpose()
+            .map_err(|e| {
+                SqliteClientError::CommitmentTree(ShardTreeError::Storage(
+                    commitment_tree::Error::Serialization(e),
+                ))
+            })?;
+        Ok((end_height, root))
+    })
+    .transpose()
+}
+
+/// Checks that a valid witness for the note at `position` can be computed as of the
+/// checkpoint at `anchor_height`, and that it is consistent with `shard_root` if known.
+fn check_witness<H, S, const DEPTH: u8, const SHARD_HEIGHT: u8>(
+    tree: &ShardTree<S, DEPTH, SHARD_HEIGHT>,
+    position: Position,
+    anchor_height: BlockHeight,
+    shard_root: Option<&H>,
+) -> Result<bool, ShardTreeError<S::Error>>
+where
+    H: Hashable + Clone + PartialEq,
+    S: ShardStore<H = H, CheckpointId = BlockHeight>,
+{
+    let leaf = match tree.get_marked_leaf(position)? {
+        Some(leaf) => leaf,
+        None => return Ok(false),
+    };
+    let witness = tree.witness_at_checkpoint_id(position, &anchor_height)?;
+    if witness.root(leaf.clone()) != tree.root_at_checkpoint_id(&anchor_height)? {
+        return Ok(false);
+    }
+
+    Ok(shard_root.map_or(true, |root| {
+        &partial_root(&witness, leaf, Level::from(SHARD_HEIGHT)) == root
+    }))
+}
+
+/// Computes the root of the subtree of the given height that contains the witnessed leaf.
+fn partial_root<H: Hashable, const DEPTH: u8>(
+    witness: &MerklePath<H, DEPTH>,
+    leaf: H,
+    height: Level,
+) -> H {
+    let position = u64::from(witness.position());
+    witness
+        .path_elems()
+        .iter()
+        .take(u8::from(height).into())
+        .zip(0u8..)
+        .fold(leaf, |node, (sibling, level)| {
+            if (position >> level) & 1 == 0 {
+                H::combine(Level::from(level), &node, sibling)
+            } else {
+                H::combine(Level::from(level), sibling, &node)
+            }
+        })
+}
@@ -9,7 +9,7 @@ use rusqlite::{named_params, types::Value, Connection, Row, Transaction};
 use sapling::{self, Diversifier, Nullifier, Rseed};
 use zcash_client_backend::{
     data_api::NullifierQuery,
-    wallet::{ReceivedNote, WalletSaplingOutput},
+    wallet::{NoteId, ReceivedNote, WalletSaplingOutput},
     DecryptedOutput, ShieldedProtocol, TransferType,
 };
 use zcash_keys::keys::UnifiedFullViewingKey;
@@ -20,7 +20,7 @@ use zcash_protocol::{
 };
 use zip32::Scope;
 
-use crate::{error::SqliteClientError, AccountId, ReceivedNoteId};
+use crate::{error::SqliteClientError, AccountId};
 
 use super::{memo_repr, parse_scope, scope_code};
 
@@ -97,10 +97,10 @@ impl ReceivedSaplingOutput for DecryptedOutput<sapling::Note, AccountId> {
 fn to_spendable_note<P: consensus::Parameters>(
     params: &P,
     row: &Row,
-) -> Result<Option<ReceivedNote<ReceivedNoteId, sapling::Note>>, SqliteClientError> {
-    let note_id = ReceivedNoteId(ShieldedProtocol::Sapling, row.get("id")?);
+) -> Result<Option<ReceivedNote<NoteId, sapling::Note>>, SqliteClientError> {
     let txid = row.get::<_, [u8; 32]>("txid").map(TxId::from_bytes)?;
     let output_index = row.get("output_index")?;
+    let note_id = NoteId::new(txid, ShieldedProtocol::Sapling, output_index);
     let diversifier = {
         let d: Vec<_> = row.get("diversifier")?;
         if d.len() != 11 {
@@ -193,7 +193,7 @@ pub(crate) fn get_spendable_sapling_note<P: consensus::Parameters>(
     params: &P,
     txid: &TxId,
     index: u32,
-) -> Result<Option<ReceivedNote<ReceivedNoteId, sapling::Note>>, SqliteClientError> {
+) -> Result<Option<ReceivedNote<NoteId, sapling::Note>>, SqliteClientError> {
     super::common::get_spendable_note(
         conn,
         params,
@@ -215,8 +215,8 @@ pub(crate) fn select_spendable_sapling_notes<P: consensus::Parameters>(
     account: AccountId,
     target_value: NonNegativeAmount,
     anchor_height: BlockHeight,
-    exclude: &[ReceivedNoteId],
-) -> Result<Vec<ReceivedNote<ReceivedNoteId, sapling::Note>>, SqliteClientError> {
+    exclude: &[NoteId],
+) -> Result<Vec<ReceivedNote<NoteId, sapling::Note>>, SqliteClientError> {
     super::common::select_spendable_notes(
         conn,
         params,
@@ -263,7 +263,7 @@ pub(crate) fn get_sapling_nullifiers(
     }?;
 
     let nullifiers = stmt_fetch_nullifiers.query_and_then([], |row| {
-        let account = AccountId(row.get(0)?);
+        let account: AccountId = row.get(0)?;
         let nf_bytes: Vec<u8> = row.get(1)?;
         Ok::<_, rusqlite::Error>((account, sapling::Nullifier::from_slice(&nf_bytes).unwrap()))
     })?;
@@ -368,7 +368,7 @@ pub(crate) fn put_received_note<T: ReceivedSaplingOutput>(
     let sql_args = named_params![
         ":tx": &tx_ref,
         ":output_index": i64::try_from(output.index()).expect("output indices are representable as i64"),
-        ":account_id": output.account_id().0,
+        ":account_id": output.account_id(),
         ":diversifier": &diversifier.0.as_ref(),
         ":value": output.note().value().inner(),
         ":rcm": &rcm.as_ref(),
@@ -415,7 +415,7 @@ pub(crate) mod tests {
         memo::MemoBytes,
         transaction::{
             components::{amount::NonNegativeAmount, sapling::zip212_enforcement},
-            Transaction,
+            Transaction, TxId,
         },
         zip32::Scope,
     };
@@ -423,10 +423,11 @@ pub(crate) mod tests {
     use zcash_client_backend::{
         address::Address,
         data_api::{
-            chain::CommitmentTreeRoot, DecryptedTransaction, WalletCommitmentTrees, WalletSummary,
+            chain::CommitmentTreeRoot, BlockNullifiers, DecryptedTransaction, NullifierQuery,
+            WalletCommitmentTrees, WalletRead, WalletSummary,
         },
         keys::UnifiedSpendingKey,
-        wallet::{Note, ReceivedNote},
+        wallet::{Note, NoteId, ReceivedNote},
         ShieldedProtocol,
     };
 
@@ -438,7 +439,7 @@ pub(crate) mod tests {
             TestState,
         },
         wallet::{commitment_tree, sapling::select_spendable_sapling_notes},
-        AccountId, ReceivedNoteId, SAPLING_TABLES_PREFIX,
+        AccountId, SAPLING_TABLES_PREFIX,
     };
 
     pub(crate) struct SaplingPoolTester;
@@ -506,8 +507,8 @@ pub(crate) mod tests {
             account: AccountId,
             target_value: NonNegativeAmount,
             anchor_height: BlockHeight,
-            exclude: &[ReceivedNoteId],
-        ) -> Result<Vec<ReceivedNote<ReceivedNoteId, Self::Note>>, SqliteClientError> {
+            exclude: &[NoteId],
+        ) -> Result<Vec<ReceivedNote<NoteId, Self::Note>>, SqliteClientError> {
             select_spendable_sapling_notes(
                 &st.wallet().conn,
                 &st.wallet().params,
@@ -564,6 +565,27 @@ pub(crate) mod tests {
         ) -> usize {
             summary.received_sapling_note_count()
         }
+
+        fn spend_all_nullifiers<Cache>(
+            st: &TestState<Cache>,
+            height: BlockHeight,
+            txid: TxId,
+        ) -> BlockNullifiers {
+            let nfs = st
+                .wallet()
+                .get_sapling_nullifiers(NullifierQuery::Unspent)
+                .unwrap()
+                .into_iter()
+                .map(|(_, nf)| nf)
+                .collect();
+
+            BlockNullifiers::from_parts(
+                height,
+                vec![(txid, 0, nfs)],
+                #[cfg(feature = "orchard")]
+                vec![],
+            )
+        }
     }
 
     pub(crate) fn test_prover() -> impl SpendProver + OutputProver {
@@ -575,6 +597,26 @@ pub(crate) mod tests {
         testing::pool::send_single_step_proposed_transfer::<SaplingPoolTester>()
     }
 
+    #[test]
+    fn payment_disclosure() {
+        testing::pool::payment_disclosure::<SaplingPoolTester>()
+    }
+
+    #[test]
+    fn validate_for_broadcast_checks_expiry() {
+        testing::pool::validate_for_broadcast_checks_expiry::<SaplingPoolTester>()
+    }
+
+    #[test]
+    fn fee_guardrails_check_proposal() {
+        testing::pool::fee_guardrails_check_proposal::<SaplingPoolTester>()
+    }
+
+    #[test]
+    fn spend_policy_vetoes_proposal() {
+        testing::pool::spend_policy_vetoes_proposal::<SaplingPoolTester>()
+    }
+
     #[test]
     #[cfg(feature = "transparent-inputs")]
     fn send_multi_step_proposed_transfer() {
@@ -587,6 +629,11 @@ pub(crate) mod tests {
         testing::pool::create_to_address_fails_on_incorrect_usk::<SaplingPoolTester>()
     }
 
+    #[test]
+    fn spends_are_authorized_by_signer() {
+        testing::pool::spends_are_authorized_by_signer::<SaplingPoolTester>()
+    }
+
     #[test]
     #[allow(deprecated)]
     fn proposal_fails_with_no_blocks() {
@@ -598,6 +645,16 @@ pub(crate) mod tests {
         testing::pool::spend_fails_on_unverified_notes::<SaplingPoolTester>()
     }
 
+    #[test]
+    fn audit_bundle_export() {
+        testing::pool::audit_bundle_export::<SaplingPoolTester>()
+    }
+
+    #[test]
+    fn change_pending_scanning_is_reported_as_change() {
+        testing::pool::change_pending_scanning_is_reported_as_change::<SaplingPoolTester>()
+    }
+
     #[test]
     fn spend_fails_on_locked_notes() {
         testing::pool::spend_fails_on_locked_notes::<SaplingPoolTester>()
@@ -632,12 +689,148 @@ pub(crate) mod tests {
         testing::pool::zip317_spend::<SaplingPoolTester>()
     }
 
+    #[test]
+    fn dust_notes_ignored_by_filter() {
+        testing::pool::dust_notes_ignored_by_filter::<SaplingPoolTester>()
+    }
+
+    #[test]
+    fn spends_detected_by_nullifier_lookahead() {
+        testing::pool::spends_detected_by_nullifier_lookahead::<SaplingPoolTester>()
+    }
+
+    #[test]
+    fn lookahead_spends_removed_by_truncation() {
+        testing::pool::lookahead_spends_removed_by_truncation::<SaplingPoolTester>()
+    }
+
+    #[test]
+    fn account_balances_cache_invalidated() {
+        testing::pool::account_balances_cache_invalidated::<SaplingPoolTester>()
+    }
+
+    #[test]
+    fn account_balances_cache_retained() {
+        testing::pool::account_balances_cache_retained::<SaplingPoolTester>()
+    }
+
+    #[test]
+    fn truncate_scanned_data_requeues_range() {
+        testing::pool::truncate_scanned_data_requeues_range::<SaplingPoolTester>()
+    }
+
+    #[test]
+    fn rescan_from_rolls_back_spends() {
+        testing::pool::rescan_from_rolls_back_spends::<SaplingPoolTester>()
+    }
+
+    #[test]
+    fn rescan_from_retains_wallet_spends() {
+        testing::pool::rescan_from_retains_wallet_spends::<SaplingPoolTester>()
+    }
+
+    #[test]
+    fn export_import_tree_state() {
+        testing::pool::export_import_tree_state::<SaplingPoolTester>()
+    }
+
+    #[test]
+    fn repair_witnesses_rescans_damaged_shard() {
+        testing::pool::repair_witnesses_rescans_damaged_shard::<SaplingPoolTester>()
+    }
+
+    #[test]
+    fn prune_note_commitment_trees() {
+        testing::pool::prune_note_commitment_trees::<SaplingPoolTester>()
+    }
+
+    #[test]
+    fn view_snapshots() {
+        testing::pool::view_snapshots::<SaplingPoolTester>()
+    }
+
+    #[test]
+    fn balance_at_height() {
+        testing::pool::balance_at_height::<SaplingPoolTester>()
+    }
+
+    #[test]
+    fn account_statistics() {
+        testing::pool::account_statistics::<SaplingPoolTester>()
+    }
+
+    #[test]
+    fn scheduled_payments() {
+        testing::pool::scheduled_payments::<SaplingPoolTester>()
+    }
+
+    #[test]
+    fn batch_transfer() {
+        testing::pool::batch_transfer::<SaplingPoolTester>()
+    }
+
+    #[test]
+    fn transaction_size_estimate() {
+        testing::pool::transaction_size_estimate::<SaplingPoolTester>()
+    }
+
+    #[test]
+    fn conflicting_proposals() {
+        testing::pool::conflicting_proposals::<SaplingPoolTester>()
+    }
+
+    #[test]
+    fn external_spends_are_marked() {
+        testing::pool::external_spends_are_marked::<SaplingPoolTester>()
+    }
+
+    #[test]
+    fn rescanning_scanned_blocks_is_noop() {
+        testing::pool::rescanning_scanned_blocks_is_noop::<SaplingPoolTester>()
+    }
+
+    #[test]
+    fn sent_notes_record_change() {
+        testing::pool::sent_notes_record_change::<SaplingPoolTester>()
+    }
+
+    #[test]
+    fn ledger_entries_balance() {
+        testing::pool::ledger_entries_balance::<SaplingPoolTester>()
+    }
+
+    #[test]
+    fn decrypted_tx_fee_is_stored() {
+        testing::pool::decrypted_tx_fee_is_stored::<SaplingPoolTester>()
+    }
+
+    #[test]
+    fn transaction_status_requests() {
+        testing::pool::transaction_status_requests::<SaplingPoolTester>()
+    }
+
     #[test]
     #[cfg(feature = "transparent-inputs")]
     fn shield_transparent() {
         testing::pool::shield_transparent::<SaplingPoolTester>()
     }
 
+    #[test]
+    fn sweep_sapling_external_key() {
+        testing::pool::sweep_sapling_external_key()
+    }
+
+    #[test]
+    fn sweep_requires_funds_exceeding_fee() {
+        testing::pool::sweep_requires_funds_exceeding_fee()
+    }
+
+    #[test]
+    #[cfg(feature = "transparent-inputs")]
+    fn sweep_transparent_external_key() {
+        testing::pool::sweep_transparent_external_key()
+    }
+
     #[test]
     fn birthday_in_anchor_shard() {
         testing::pool::birthday_in_anchor_shard::<SaplingPoolTester>()
@@ -648,6 +841,21 @@ pub(crate) mod tests {
         testing::pool::checkpoint_gaps::<SaplingPoolTester>()
     }
 
+    #[test]
+    fn unusable_recipients_rejected() {
+        testing::pool::unusable_recipients_rejected::<SaplingPoolTester>()
+    }
+
+    #[test]
+    fn privacy_policy_single_pool() {
+        testing::pool::privacy_policy_single_pool::<SaplingPoolTester>()
+    }
+
+    #[test]
+    fn tx_history_privacy_level() {
+        testing::pool::tx_history_privacy_level::<SaplingPoolTester>()
+    }
+
     #[test]
     fn scan_cached_blocks_detects_spends_out_of_order() {
         testing::pool::scan_cached_blocks_detects_spends_out_of_order::<SaplingPoolTester>()
@@ -661,6 +869,14 @@ pub(crate) mod tests {
         testing::pool::pool_crossing_required::<SaplingPoolTester, OrchardPoolTester>()
     }
 
+    #[test]
+    #[cfg(feature = "orchard")]
+    fn privacy_policy_pool_crossing() {
+        use crate::wallet::orchard::tests::OrchardPoolTester;
+
+        testing::pool::privacy_policy_pool_crossing::<SaplingPoolTester, OrchardPoolTester>()
+    }
+
     #[test]
     #[cfg(feature = "orchard")]
     fn fully_funded_fully_private() {
@@ -2,7 +2,7 @@ use incrementalmerkletree::{Address, Position};
 use rusqlite::{self, named_params, types::Value, OptionalExtension};
 use shardtree::error::ShardTreeError;
 use std::cmp::{max, min};
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, HashSet};
 use std::ops::Range;
 use std::rc::Rc;
 use tracing::{debug, trace};
@@ -19,7 +19,7 @@ use zcash_primitives::consensus::{self, BlockHeight, NetworkUpgrade};
 use crate::{
     error::SqliteClientError,
     wallet::{block_height_extrema, commitment_tree, init::WalletMigrationError},
-    PRUNING_DEPTH, SAPLING_TABLES_PREFIX, VERIFY_LOOKAHEAD,
+    AccountId, PRUNING_DEPTH, SAPLING_TABLES_PREFIX, VERIFY_LOOKAHEAD,
 };
 
 use super::wallet_birthday;
@@ -111,6 +111,155 @@ pub(crate) fn insert_queue_entries<'a>(
     Ok(())
 }
 
+/// Queues the given range of blocks to be scanned for a single account.
+///
+/// The parts of the range that have already been scanned for the wallet's other accounts (or
+/// that are already awaiting a backfill scan for other accounts) are recorded in the
+/// `account_backfill_ranges` table, so that they can be scanned with the keys of only those
+/// accounts that have not yet scanned them. The remainder of the range is scanned for all
+/// accounts as usual.
+pub(crate) fn queue_account_backfill(
+    conn: &rusqlite::Transaction<'_>,
+    account: AccountId,
+    range: &Range<BlockHeight>,
+) -> Result<(), SqliteClientError> {
+    if range.is_empty() {
+        return Ok(());
+    }
+
+    conn.execute(
+        "INSERT INTO account_backfill_ranges (account_id, block_range_start, block_range_end)
+        SELECT :account_id, MAX(block_range_start, :start), MIN(block_range_end, :end)
+        FROM scan_queue
+        WHERE priority = :scanned_priority
+        AND block_range_start < :end
+        AND block_range_end > :start",
+        named_params![
+            ":account_id": account,
+            ":start": u32::from(range.start),
+            ":end": u32::from(range.end),
+            ":scanned_priority": priority_code(&ScanPriority::Scanned),
+        ],
+    )?;
+    conn.execute(
+        "INSERT INTO account_backfill_ranges (account_id, block_range_start, block_range_end)
+        SELECT :account_id, MAX(block_range_start, :start), MIN(block_range_end, :end)
+        FROM account_backfill_ranges
+        WHERE account_id != :account_id
+        AND block_range_start < :end
+        AND block_range_end > :start",
+        named_params![
+            ":account_id": account,
+            ":start": u32::from(range.start),
+            ":end": u32::from(range.end),
+        ],
+    )?;
+
+    replace_queue_entries::<SqliteClientError>(
+        conn,
+        range,
+        Some(ScanRange::from_parts(range.clone(), ScanPriority::Historic)).into_iter(),
+        true, // force rescan
+    )?;
+
+    Ok(())
+}
+
+/// Returns the accounts for which the blocks in the given range remain to be scanned, if every
+/// block in the range has already been scanned for the wallet's other accounts.
+///
+/// Returns `None` if any block in the range must be scanned for all accounts. Only accounts
+/// that are enabled for scanning are considered.
+pub(crate) fn backfill_accounts(
+    conn: &rusqlite::Connection,
+    range: &Range<BlockHeight>,
+) -> Result<Option<HashSet<AccountId>>, rusqlite::Error> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT b.account_id, b.block_range_start, b.block_range_end
+        FROM account_backfill_ranges b
+        JOIN accounts a ON a.id = b.account_id
+        WHERE a.enabled_for_scanning = 1
+        AND b.block_range_start < :end
+        AND b.block_range_end > :start
+        ORDER BY b.block_range_start",
+    )?;
+    let mut rows = stmt.query(named_params![
+        ":start": u32::from(range.start),
+        ":end": u32::from(range.end),
+    ])?;
+
+    let mut accounts = HashSet::new();
+    let mut covered_to = range.start;
+    while let Some(row) = rows.next()? {
+        let start = BlockHeight::from(row.get::<_, u32>(1)?);
+        if start > covered_to {
+            return Ok(None);
+        }
+        covered_to = max(covered_to, BlockHeight::from(row.get::<_, u32>(2)?));
+        accounts.insert(row.get(0)?);
+    }
+
+    Ok((covered_to >= range.end).then_some(accounts))
+}
+
+/// Removes the given range of blocks from the `account_backfill_ranges` table.
+///
+/// If `enabled_accounts_only` is set, the ranges recorded for accounts that are not enabled for
+/// scanning are retained.
+pub(crate) fn clear_account_backfill(
+    conn: &rusqlite::Connection,
+    range: &Range<BlockHeight>,
+    enabled_accounts_only: bool,
+) -> Result<(), rusqlite::Error> {
+    if range.is_empty() {
+        return Ok(());
+    }
+
+    let account_filter = if enabled_accounts_only {
+        "AND account_id IN (SELECT id FROM accounts WHERE enabled_for_scanning = 1)"
+    } else {
+        ""
+    };
+
+    // Retain the parts of any overlapping ranges that lie outside the cleared range.
+    conn.execute(
+        &format!(
+            "INSERT INTO account_backfill_ranges (account_id, block_range_start, block_range_end)
+            SELECT account_id, block_range_start, :start
+            FROM account_backfill_ranges
+            WHERE block_range_start < :start
+            AND block_range_end > :start
+            {account_filter}"
+        ),
+        named_params![":start": u32::from(range.start)],
+    )?;
+    conn.execute(
+        &format!(
+            "INSERT INTO account_backfill_ranges (account_id, block_range_start, block_range_end)
+            SELECT account_id, :end, block_range_end
+            FROM account_backfill_ranges
+            WHERE block_range_start < :end
+            AND block_range_end > :end
+            {account_filter}"
+        ),
+        named_params![":end": u32::from(range.end)],
+    )?;
+    conn.execute(
+        &format!(
+            "DELETE FROM account_backfill_ranges
+            WHERE block_range_start < :end
+            AND block_range_end > :start
+            {account_filter}"
+        ),
+        named_params![
+            ":start": u32::from(range.start),
+            ":end": u32::from(range.end),
+        ],
+    )?;
+
+    Ok(())
+}
+
 /// A trait that abstracts over the construction of wallet errors.
 ///
 /// In order to make it possible to use [`replace_queue_entries`] in database migrations as well as
@@ -311,6 +460,9 @@ pub(crate) fn scan_complete<P: consensus::Parameters>(
     // TODO: use per-pool birthdays?
     let wallet_birthday = wallet_birthday(conn)?;
 
+    // The range has now been scanned for every account that is enabled for scanning.
+    clear_account_backfill(conn, &range, true)?;
+
     // Determine the range of block heights for which we will be updating the scan queue.
     let extended_range = {
         // If notes have been detected in the scan, we need to extend any adjacent un-scanned
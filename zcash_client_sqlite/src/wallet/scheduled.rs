@@ -0,0 +1,209 @@
+//! Storage for payments that recur on a fixed schedule.
+//!
+//! Each scheduled payment is stored as a single-payment ZIP 321 URI, together with the interval
+//! at which it recurs, its first due time, and the number of payments that have been recorded
+//! as made. Times are stored as Unix timestamps, so due times are computed in UTC.
+
+use std::collections::BTreeMap;
+use std::num::NonZeroU32;
+
+use rusqlite::{named_params, Connection, OptionalExtension};
+use time::OffsetDateTime;
+use zcash_client_backend::{
+    data_api::scheduled::{PaymentInterval, ScheduledPayment, ScheduledPaymentId},
+    zip321::{Payment, TransactionRequest},
+};
+
+use crate::{error::SqliteClientError, AccountId};
+
+const INTERVAL_DAYS: u32 = 0;
+const INTERVAL_MONTHS: u32 = 1;
+
+fn interval_code(interval: PaymentInterval) -> (u32, u32) {
+    match interval {
+        PaymentInterval::Days(n) => (INTERVAL_DAYS, n.get()),
+        PaymentInterval::Months(n) => (INTERVAL_MONTHS, n.get()),
+    }
+}
+
+fn parse_interval(unit: u32, count: u32) -> Result<PaymentInterval, SqliteClientError> {
+    let count = NonZeroU32::new(count).ok_or_else(|| {
+        SqliteClientError::CorruptedData("Scheduled payment interval is zero".to_owned())
+    })?;
+    match unit {
+        INTERVAL_DAYS => Ok(PaymentInterval::Days(count)),
+        INTERVAL_MONTHS => Ok(PaymentInterval::Months(count)),
+        _ => Err(SqliteClientError::CorruptedData(format!(
+            "Unrecognized scheduled payment interval unit: {}",
+            unit
+        ))),
+    }
+}
+
+fn due_time(
+    interval: PaymentInterval,
+    first_due: OffsetDateTime,
+    n: u32,
+) -> Result<OffsetDateTime, SqliteClientError> {
+    interval.nth_after(first_due, n).ok_or_else(|| {
+        SqliteClientError::CorruptedData("Scheduled payment due time is out of range".to_owned())
+    })
+}
+
+fn from_timestamp(timestamp: i64) -> Result<OffsetDateTime, SqliteClientError> {
+    OffsetDateTime::from_unix_timestamp(timestamp).map_err(|_| {
+        SqliteClientError::CorruptedData(format!(
+            "Invalid scheduled payment timestamp: {}",
+            timestamp
+        ))
+    })
+}
+
+/// Adds a payment that is first due at `first_due` and recurs at the given interval.
+pub(crate) fn insert_scheduled_payment(
+    conn: &rusqlite::Transaction,
+    account_id: AccountId,
+    payment: &Payment,
+    interval: PaymentInterval,
+    first_due: OffsetDateTime,
+) -> Result<ScheduledPaymentId, SqliteClientError> {
+    let account_exists = conn
+        .query_row(
+            "SELECT 1 FROM accounts WHERE id = :account_id",
+            named_params![":account_id": account_id],
+            |_| Ok(()),
+        )
+        .optional()?
+        .is_some();
+    if !account_exists {
+        return Err(SqliteClientError::AccountUnknown);
+    }
+
+    let payment_uri = TransactionRequest::from_indexed(BTreeMap::from([(0, payment.clone())]))
+        .expect("a single payment has a valid index")
+        .to_uri();
+    let (interval_unit, interval_count) = interval_code(interval);
+
+    let id = conn.query_row(
+        "INSERT INTO scheduled_payments (
+            account_id, payment_uri, interval_unit, interval_count,
+            first_due_at, payments_made, next_due_at
+        )
+        VALUES (
+            :account_id, :payment_uri, :interval_unit, :interval_count,
+            :first_due_at, 0, :first_due_at
+        )
+        RETURNING id",
+        named_params![
+            ":account_id": account_id,
+            ":payment_uri": payment_uri,
+            ":interval_unit": interval_unit,
+            ":interval_count": interval_count,
+            ":first_due_at": first_due.unix_timestamp(),
+        ],
+        |row| row.get::<_, u64>(0),
+    )?;
+
+    Ok(ScheduledPaymentId::from(id))
+}
+
+/// Returns the scheduled payments that are due at or before `now`, in order of their next due
+/// times.
+pub(crate) fn payments_due(
+    conn: &Connection,
+    now: OffsetDateTime,
+) -> Result<Vec<ScheduledPayment<AccountId>>, SqliteClientError> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT id, account_id, payment_uri, interval_unit, interval_count,
+                first_due_at, payments_made
+        FROM scheduled_payments
+        WHERE next_due_at <= :now
+        ORDER BY next_due_at, id",
+    )?;
+
+    let payments = stmt
+        .query_and_then(named_params![":now": now.unix_timestamp()], |row| {
+            let payment_uri = row.get::<_, String>(2)?;
+            let payment = TransactionRequest::from_uri(&payment_uri)
+                .ok()
+                .and_then(|request| request.payments().get(&0).cloned())
+                .ok_or_else(|| {
+                    SqliteClientError::CorruptedData(format!(
+                        "Invalid scheduled payment URI: {}",
+                        payment_uri
+                    ))
+                })?;
+
+            Ok(ScheduledPayment::from_parts(
+                ScheduledPaymentId::from(row.get::<_, u64>(0)?),
+                row.get(1)?,
+                payment,
+                parse_interval(row.get(3)?, row.get(4)?)?,
+                from_timestamp(row.get(5)?)?,
+                row.get(6)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(payments)
+}
+
+/// Records that the next payment of the given schedule has been made, and returns the time at
+/// which the following payment is due.
+pub(crate) fn record_scheduled_payment(
+    conn: &rusqlite::Transaction,
+    id: ScheduledPaymentId,
+) -> Result<OffsetDateTime, SqliteClientError> {
+    let (interval_unit, interval_count, first_due_at, payments_made) = conn
+        .query_row(
+            "SELECT interval_unit, interval_count, first_due_at, payments_made
+            FROM scheduled_payments
+            WHERE id = :id",
+            named_params![":id": u64::from(id)],
+            |row| {
+                Ok((
+                    row.get::<_, u32>(0)?,
+                    row.get::<_, u32>(1)?,
+                    row.get::<_, i64>(2)?,
+                    row.get::<_, u32>(3)?,
+                ))
+            },
+        )
+        .optional()?
+        .ok_or(SqliteClientError::ScheduledPaymentUnknown(id))?;
+
+    let payments_made = payments_made + 1;
+    let next_due = due_time(
+        parse_interval(interval_unit, interval_count)?,
+        from_timestamp(first_due_at)?,
+        payments_made,
+    )?;
+
+    conn.execute(
+        "UPDATE scheduled_payments
+        SET payments_made = :payments_made, next_due_at = :next_due_at
+        WHERE id = :id",
+        named_params![
+            ":payments_made": payments_made,
+            ":next_due_at": next_due.unix_timestamp(),
+            ":id": u64::from(id),
+        ],
+    )?;
+
+    Ok(next_due)
+}
+
+/// Removes the given schedule, so that no further payments fall due.
+pub(crate) fn cancel_scheduled_payment(
+    conn: &rusqlite::Transaction,
+    id: ScheduledPaymentId,
+) -> Result<(), SqliteClientError> {
+    let removed = conn.execute(
+        "DELETE FROM scheduled_payments WHERE id = :id",
+        named_params![":id": u64::from(id)],
+    )?;
+    if removed == 0 {
+        return Err(SqliteClientError::ScheduledPaymentUnknown(id));
+    }
+    Ok(())
+}
@@ -0,0 +1,50 @@
+//! Per-wallet configuration, stored as named values in the `wallet_settings` table.
+//!
+//! Settings are stored in the wallet database so that configuration such as a preferred fee
+//! rule or the light wallet server last used for synchronization is backed up and restored
+//! with the data that it applies to. Values are read and written using the [`FromSql`] and
+//! [`ToSql`] implementations of their types, and keep the SQLite storage class with which they
+//! were written.
+
+use rusqlite::{
+    named_params,
+    types::{FromSql, ToSql},
+    Connection, OptionalExtension,
+};
+
+use crate::error::SqliteClientError;
+
+pub(crate) fn get_setting<T: FromSql>(
+    conn: &Connection,
+    name: &str,
+) -> Result<Option<T>, SqliteClientError> {
+    Ok(conn
+        .query_row(
+            "SELECT value FROM wallet_settings WHERE name = :name",
+            named_params![":name": name],
+            |row| row.get(0),
+        )
+        .optional()?)
+}
+
+pub(crate) fn put_setting<T: ToSql>(
+    conn: &Connection,
+    name: &str,
+    value: T,
+) -> Result<(), SqliteClientError> {
+    conn.execute(
+        "INSERT INTO wallet_settings (name, value)
+         VALUES (:name, :value)
+         ON CONFLICT (name) DO UPDATE SET value = :value",
+        named_params![":name": name, ":value": value],
+    )?;
+    Ok(())
+}
+
+pub(crate) fn remove_setting(conn: &Connection, name: &str) -> Result<(), SqliteClientError> {
+    conn.execute(
+        "DELETE FROM wallet_settings WHERE name = :name",
+        named_params![":name": name],
+    )?;
+    Ok(())
+}
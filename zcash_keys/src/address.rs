@@ -1,5 +1,7 @@
 //! Structs for handling supported address types.
 
+use std::fmt;
+
 use zcash_address::{
     unified::{self, Container, Encoding, Typecode},
     ConversionError, ToAddress, TryFromRawAddress, ZcashAddress,
@@ -416,6 +418,216 @@ impl Address {
     }
 }
 
+/// A diagnostic describing why a string could not be used as an address on a particular network.
+///
+/// Positions are zero-based character offsets into the address string after any leading and
+/// trailing whitespace has been removed.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ValidationError {
+    /// The string is empty, or contains only whitespace.
+    Empty,
+    /// The string is a valid address for a different network.
+    NetworkMismatch {
+        expected: NetworkType,
+        actual: NetworkType,
+    },
+    /// The string is validly encoded, but its prefix does not correspond to any kind of Zcash
+    /// address. For Bech32 encodings this is the human-readable part; for Base58Check
+    /// encodings it is the hex encoding of the two version bytes.
+    UnknownPrefix(String),
+    /// The string contains a character that is not permitted by its encoding.
+    InvalidCharacter { position: usize, character: char },
+    /// The Bech32 encoding mixes upper-case and lower-case characters.
+    MixedCase,
+    /// The checksum of the string is invalid. If the error can be corrected by replacing a
+    /// single character, `position` is the offset of that character.
+    InvalidChecksum { position: Option<usize> },
+    /// The string is a Unified Address that violates the encoding rules of [ZIP 316].
+    ///
+    /// [ZIP 316]: https://zips.z.cash/zip-0316
+    Unified(unified::ParseError),
+    /// The address is a Unified Address, none of whose receivers have types that are
+    /// supported. The typecodes of the unsupported receivers are provided.
+    UnsupportedTypecodes(Vec<u32>),
+    /// The address is of a kind that is recognized but not supported, such as a Sprout address.
+    UnsupportedAddress(String),
+    /// The address was decoded, but one of its receivers is not valid.
+    InvalidReceiver(&'static str),
+    /// The string is not a valid encoding of an address, for a reason not described by any of
+    /// the other variants (such as having an incorrect length).
+    InvalidEncoding,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::Empty => write!(f, "No address was provided"),
+            ValidationError::NetworkMismatch { expected, actual } => write!(
+                f,
+                "Address is for {:?} but {:?} was expected",
+                actual, expected
+            ),
+            ValidationError::UnknownPrefix(prefix) => {
+                write!(f, "Unrecognized address prefix: {}", prefix)
+            }
+            ValidationError::InvalidCharacter {
+                position,
+                character,
+            } => write!(
+                f,
+                "Invalid character {:?} at position {}",
+                character, position
+            ),
+            ValidationError::MixedCase => {
+                write!(f, "Address contains both upper- and lower-case characters")
+            }
+            ValidationError::InvalidChecksum {
+                position: Some(position),
+            } => write!(
+                f,
+                "Invalid checksum; the character at position {} may be incorrect",
+                position
+            ),
+            ValidationError::InvalidChecksum { position: None } => write!(f, "Invalid checksum"),
+            ValidationError::Unified(e) => write!(f, "Invalid unified address: {}", e),
+            ValidationError::UnsupportedTypecodes(typecodes) => write!(
+                f,
+                "Unified address contains no supported receivers (typecodes {:?})",
+                typecodes
+            ),
+            ValidationError::UnsupportedAddress(e) => write!(f, "{}", e),
+            ValidationError::InvalidReceiver(e) => write!(f, "{}", e),
+            ValidationError::InvalidEncoding => write!(f, "Invalid address encoding"),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// The Bech32 data characters, in lower case.
+const BECH32_CHARSET: &str = "qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+/// The Base58 alphabet used by Base58Check-encoded Zcash addresses.
+const BASE58_ALPHABET: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// Parses the given string as an address for the given network, returning a diagnostic
+/// describing the problem if it cannot be used.
+///
+/// Unlike [`Address::decode`], this distinguishes between the ways in which an address may be
+/// malformed, so that the reason can be reported to the user.
+pub fn validate<P: consensus::Parameters>(s: &str, params: &P) -> Result<Address, ValidationError> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Err(ValidationError::Empty);
+    }
+
+    let zaddr = match s.parse::<ZcashAddress>() {
+        Ok(zaddr) => zaddr,
+        Err(zcash_address::ParseError::Unified(e)) => return Err(ValidationError::Unified(e)),
+        Err(e) => {
+            return Err(diagnose_encoding(
+                s,
+                e == zcash_address::ParseError::NotZcash,
+            ))
+        }
+    };
+
+    let addr = Address::try_from_zcash_address(params, zaddr).map_err(|e| match e {
+        ConversionError::IncorrectNetwork { expected, actual } => {
+            ValidationError::NetworkMismatch { expected, actual }
+        }
+        ConversionError::Unsupported(e) => ValidationError::UnsupportedAddress(e.to_string()),
+        ConversionError::User(e) => ValidationError::InvalidReceiver(e),
+    })?;
+
+    match addr {
+        Address::Unified(ua) if !(ua.has_orchard() || ua.has_sapling() || ua.has_transparent()) => {
+            Err(ValidationError::UnsupportedTypecodes(
+                ua.unknown().iter().map(|(typecode, _)| *typecode).collect(),
+            ))
+        }
+        addr => Ok(addr),
+    }
+}
+
+/// Determines why a string that could not be parsed as a [`ZcashAddress`] is invalid.
+fn diagnose_encoding(s: &str, not_zcash: bool) -> ValidationError {
+    if looks_like_bech32(s) {
+        match bech32::decode(s) {
+            Ok((hrp, _, _)) if not_zcash => ValidationError::UnknownPrefix(hrp),
+            Ok(_) => ValidationError::InvalidEncoding,
+            Err(bech32::Error::InvalidChecksum) => ValidationError::InvalidChecksum {
+                position: locate_substitution(s, BECH32_CHARSET, |candidate| {
+                    bech32::decode(candidate).is_ok()
+                }),
+            },
+            Err(bech32::Error::InvalidChar(character)) => ValidationError::InvalidCharacter {
+                position: s.chars().position(|c| c == character).unwrap_or(0),
+                character,
+            },
+            Err(bech32::Error::MixedCase) => ValidationError::MixedCase,
+            Err(_) => ValidationError::InvalidEncoding,
+        }
+    } else {
+        match bs58::decode(s).with_check(None).into_vec() {
+            Ok(decoded) if not_zcash && decoded.len() >= 2 => {
+                ValidationError::UnknownPrefix(hex::encode(&decoded[..2]))
+            }
+            Ok(_) => ValidationError::InvalidEncoding,
+            Err(bs58::decode::Error::InvalidChecksum { .. }) => ValidationError::InvalidChecksum {
+                position: locate_substitution(s, BASE58_ALPHABET, |candidate| {
+                    bs58::decode(candidate).with_check(None).into_vec().is_ok()
+                }),
+            },
+            Err(bs58::decode::Error::InvalidCharacter { character, index }) => {
+                ValidationError::InvalidCharacter {
+                    position: index,
+                    character,
+                }
+            }
+            Err(_) => ValidationError::InvalidEncoding,
+        }
+    }
+}
+
+/// Returns whether the given string appears to be intended as a Bech32 or Bech32m encoding,
+/// rather than a Base58Check encoding.
+///
+/// All Zcash Bech32 human-readable parts are alphabetic. Transparent addresses begin with `t`
+/// and may contain the character `1`, so a prefix beginning with `t` is only treated as a
+/// human-readable part if it is one of the TEX address prefixes.
+fn looks_like_bech32(s: &str) -> bool {
+    s.rfind('1').map_or(false, |i| {
+        let hrp = &s[..i];
+        !hrp.is_empty()
+            && hrp.chars().all(|c| c.is_ascii_alphabetic())
+            && (!hrp.starts_with(['t', 'T'])
+                || ["tex", "textest", "texregtest"]
+                    .iter()
+                    .any(|tex| hrp.eq_ignore_ascii_case(tex)))
+    })
+}
+
+/// Returns the position of the first character of `s` that, when replaced by some other
+/// character of `alphabet`, produces a string accepted by `is_valid`.
+///
+/// For Bech32 strings, the replacement preserves the case of the original string.
+fn locate_substitution(s: &str, alphabet: &str, is_valid: impl Fn(&str) -> bool) -> Option<usize> {
+    let chars: Vec<char> = s.chars().collect();
+    let upper = alphabet == BECH32_CHARSET && !chars.iter().any(|c| c.is_ascii_lowercase());
+    (0..chars.len()).find(|&i| {
+        alphabet
+            .chars()
+            .map(|c| if upper { c.to_ascii_uppercase() } else { c })
+            .filter(|&c| c != chars[i])
+            .any(|c| {
+                let mut candidate = chars.clone();
+                candidate[i] = c;
+                is_valid(&candidate.iter().collect::<String>())
+            })
+    })
+}
+
 #[cfg(all(
     any(
         feature = "orchard",
@@ -466,7 +678,7 @@ mod tests {
     use zcash_address::test_vectors;
     use zcash_primitives::consensus::MAIN_NETWORK;
 
-    use super::{Address, UnifiedAddress};
+    use super::{validate, Address, UnifiedAddress, ValidationError};
 
     #[cfg(feature = "sapling")]
     use crate::keys::sapling;
@@ -544,4 +756,59 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn validate_diagnostics() {
+        use bech32::ToBase32;
+        use zcash_primitives::{consensus::TEST_NETWORK, legacy::TransparentAddress};
+        use zcash_protocol::consensus::NetworkType;
+
+        let taddr = Address::Transparent(TransparentAddress::PublicKeyHash([7; 20]));
+        let taddr_str = taddr.encode(&MAIN_NETWORK);
+        assert_eq!(
+            validate(&format!(" {}\n", taddr_str), &MAIN_NETWORK),
+            Ok(taddr)
+        );
+        assert_eq!(validate("  ", &MAIN_NETWORK), Err(ValidationError::Empty));
+        assert_eq!(
+            validate(&taddr_str, &TEST_NETWORK),
+            Err(ValidationError::NetworkMismatch {
+                expected: NetworkType::Test,
+                actual: NetworkType::Main,
+            })
+        );
+
+        // A single mistyped character is located in both encodings.
+        let mistype = |s: &str, position: usize, alphabet: &str| {
+            let mut chars: Vec<char> = s.chars().collect();
+            chars[position] = alphabet.chars().find(|c| *c != chars[position]).unwrap();
+            chars.into_iter().collect::<String>()
+        };
+        assert_eq!(
+            validate(&mistype(&taddr_str, 20, "abc"), &MAIN_NETWORK),
+            Err(ValidationError::InvalidChecksum { position: Some(20) })
+        );
+        let ua_str = test_vectors::UNIFIED[0].unified_addr;
+        assert_eq!(
+            validate(&mistype(ua_str, 30, "qpz"), &MAIN_NETWORK),
+            Err(ValidationError::InvalidChecksum { position: Some(30) })
+        );
+
+        let mut bad_char = ua_str.to_owned();
+        bad_char.replace_range(10..11, "b");
+        assert_eq!(
+            validate(&bad_char, &MAIN_NETWORK),
+            Err(ValidationError::InvalidCharacter {
+                position: 10,
+                character: 'b',
+            })
+        );
+
+        let unknown_hrp =
+            bech32::encode("zz", [0u8; 43].to_base32(), bech32::Variant::Bech32).unwrap();
+        assert_eq!(
+            validate(&unknown_hrp, &MAIN_NETWORK),
+            Err(ValidationError::UnknownPrefix("zz".to_owned()))
+        );
+    }
 }
@@ -0,0 +1,419 @@
+//! Introspection of the derivation paths used for wallet keys and addresses.
+//!
+//! The keys of a seed-derived account are obtained by hierarchical derivation from the seed:
+//! Sapling and Orchard keys are derived using [ZIP 32] at the path `m/32'/<coin_type>'/<account>'`,
+//! and transparent keys are derived using [BIP 44] at the path `m/44'/<coin_type>'/<account>'`,
+//! with each transparent address being derived at `m/44'/<coin_type>'/<account>'/<scope>/<index>`.
+//! Shielded addresses are not derived along a path; instead, they are obtained from the
+//! account-level key at a [`DiversifierIndex`].
+//!
+//! The types in this module describe these paths, both as structured data and in the string
+//! notation used by other wallets and by hardware wallets, so that the keys held by this wallet
+//! can be cross-checked against other tools.
+//!
+//! [ZIP 32]: https://zips.z.cash/zip-0032
+//! [BIP 44]: https://github.com/bitcoin/bips/blob/master/bip-0044.mediawiki
+
+use std::fmt;
+use std::str::FromStr;
+
+use zcash_protocol::consensus::{self, NetworkConstants};
+use zip32::{AccountId, DiversifierIndex};
+
+#[cfg(feature = "transparent-inputs")]
+use zcash_primitives::legacy::keys::{NonHardenedChildIndex, TransparentKeyScope};
+
+/// The purpose field of the ZIP 32 path used for Sapling and Orchard keys.
+const ZIP32_PURPOSE: u32 = 32;
+
+/// The purpose field of the BIP 44 path used for transparent keys.
+const BIP44_PURPOSE: u32 = 44;
+
+/// The flag that distinguishes hardened child indices from non-hardened ones.
+const HARDENED_FLAG: u32 = 1 << 31;
+
+/// A single step in a [`DerivationPath`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PathComponent {
+    index: u32,
+    hardened: bool,
+}
+
+impl PathComponent {
+    /// Constructs a hardened path component, returning `None` if `index` is not less than
+    /// 2^31.
+    pub fn hardened(index: u32) -> Option<Self> {
+        (index < HARDENED_FLAG).then_some(PathComponent {
+            index,
+            hardened: true,
+        })
+    }
+
+    /// Constructs a non-hardened path component, returning `None` if `index` is not less
+    /// than 2^31.
+    pub fn non_hardened(index: u32) -> Option<Self> {
+        (index < HARDENED_FLAG).then_some(PathComponent {
+            index,
+            hardened: false,
+        })
+    }
+
+    /// Returns the index of this component, without the hardened flag.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// Returns whether this component uses hardened derivation.
+    pub fn is_hardened(&self) -> bool {
+        self.hardened
+    }
+
+    /// Returns the child index as encoded in BIP 32 and ZIP 32, with the high bit set for
+    /// hardened derivation.
+    pub fn to_raw_index(&self) -> u32 {
+        if self.hardened {
+            self.index | HARDENED_FLAG
+        } else {
+            self.index
+        }
+    }
+
+    fn hardened_unchecked(index: u32) -> Self {
+        PathComponent::hardened(index).expect("index is less than 2^31")
+    }
+}
+
+impl From<AccountId> for PathComponent {
+    fn from(account: AccountId) -> Self {
+        PathComponent::hardened_unchecked(account.into())
+    }
+}
+
+impl fmt::Display for PathComponent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.index)?;
+        if self.hardened {
+            write!(f, "'")?;
+        }
+        Ok(())
+    }
+}
+
+/// A path from a master key to one of its descendants, such as `m/32'/133'/0'`.
+///
+/// The [`Display`] implementation produces the conventional string form of the path, using
+/// `'` to mark hardened components. The [`FromStr`] implementation accepts either `'` or `h`
+/// as the hardened marker.
+///
+/// [`Display`]: fmt::Display
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct DerivationPath {
+    components: Vec<PathComponent>,
+}
+
+impl DerivationPath {
+    /// Returns the path of the master key, `m`.
+    pub fn master() -> Self {
+        DerivationPath::default()
+    }
+
+    /// Constructs a path from its components.
+    pub fn from_components(components: Vec<PathComponent>) -> Self {
+        DerivationPath { components }
+    }
+
+    /// Returns the path to the given child of the key at this path.
+    pub fn child(mut self, component: PathComponent) -> Self {
+        self.components.push(component);
+        self
+    }
+
+    /// Returns the components of this path, starting from the master key.
+    pub fn components(&self) -> &[PathComponent] {
+        &self.components
+    }
+
+    /// Returns the ZIP 32 path `m/32'/<coin_type>'/<account>'` at which the Sapling and
+    /// Orchard spending keys of the given account are derived.
+    pub fn zip32_account(coin_type: u32, account: AccountId) -> Self {
+        DerivationPath::from_components(vec![
+            PathComponent::hardened_unchecked(ZIP32_PURPOSE),
+            PathComponent::hardened_unchecked(coin_type & !HARDENED_FLAG),
+            account.into(),
+        ])
+    }
+
+    /// Returns the BIP 44 path `m/44'/<coin_type>'/<account>'` at which the transparent
+    /// account key of the given account is derived.
+    pub fn bip44_account(coin_type: u32, account: AccountId) -> Self {
+        DerivationPath::from_components(vec![
+            PathComponent::hardened_unchecked(BIP44_PURPOSE),
+            PathComponent::hardened_unchecked(coin_type & !HARDENED_FLAG),
+            account.into(),
+        ])
+    }
+
+    /// Returns the BIP 44 path `m/44'/<coin_type>'/<account>'/<scope>/<address_index>` at
+    /// which the key for the given transparent address of the account is derived.
+    #[cfg(feature = "transparent-inputs")]
+    pub fn bip44_address(
+        coin_type: u32,
+        account: AccountId,
+        scope: TransparentKeyScope,
+        address_index: NonHardenedChildIndex,
+    ) -> Self {
+        DerivationPath::bip44_account(coin_type, account)
+            .child(PathComponent::non_hardened(scope.index()).expect("scope is non-hardened"))
+            .child(
+                PathComponent::non_hardened(address_index.index())
+                    .expect("address index is non-hardened"),
+            )
+    }
+}
+
+impl fmt::Display for DerivationPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "m")?;
+        for component in &self.components {
+            write!(f, "/{}", component)?;
+        }
+        Ok(())
+    }
+}
+
+/// Errors that can occur when parsing a [`DerivationPath`] from a string.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// The path did not begin with `m`.
+    MissingMaster,
+    /// The component at the given position was not a valid child index.
+    InvalidComponent(usize),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::MissingMaster => write!(f, "Derivation path must begin with \"m\""),
+            ParseError::InvalidComponent(i) => {
+                write!(f, "Component {} of the derivation path is invalid", i)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl FromStr for DerivationPath {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split('/');
+        if parts.next() != Some("m") {
+            return Err(ParseError::MissingMaster);
+        }
+
+        parts
+            .enumerate()
+            .map(|(i, part)| {
+                let (digits, hardened) =
+                    match part.strip_suffix('\'').or_else(|| part.strip_suffix('h')) {
+                        Some(digits) => (digits, true),
+                        None => (part, false),
+                    };
+                Some(digits)
+                    .filter(|digits| digits.chars().all(|c| c.is_ascii_digit()))
+                    .and_then(|digits| digits.parse::<u32>().ok())
+                    .and_then(|index| {
+                        if hardened {
+                            PathComponent::hardened(index)
+                        } else {
+                            PathComponent::non_hardened(index)
+                        }
+                    })
+                    .ok_or(ParseError::InvalidComponent(i))
+            })
+            .collect::<Result<_, _>>()
+            .map(DerivationPath::from_components)
+    }
+}
+
+/// The derivation of the keys of a single account from a seed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AccountDerivation {
+    coin_type: u32,
+    account_index: AccountId,
+}
+
+impl AccountDerivation {
+    /// Constructs the derivation of the account with the given index, using the coin type
+    /// of the given network.
+    pub fn new<P: consensus::Parameters>(params: &P, account_index: AccountId) -> Self {
+        AccountDerivation::from_coin_type(params.coin_type(), account_index)
+    }
+
+    /// Constructs the derivation of the account with the given index and [SLIP 44] coin
+    /// type.
+    ///
+    /// [SLIP 44]: https://github.com/satoshilabs/slips/blob/master/slip-0044.md
+    pub fn from_coin_type(coin_type: u32, account_index: AccountId) -> Self {
+        AccountDerivation {
+            coin_type,
+            account_index,
+        }
+    }
+
+    /// Returns the coin type used in the account's derivation paths.
+    pub fn coin_type(&self) -> u32 {
+        self.coin_type
+    }
+
+    /// Returns the ZIP 32 index of the account.
+    pub fn account_index(&self) -> AccountId {
+        self.account_index
+    }
+
+    /// Returns the path at which the account's Sapling and Orchard spending keys are derived.
+    pub fn zip32_path(&self) -> DerivationPath {
+        DerivationPath::zip32_account(self.coin_type, self.account_index)
+    }
+
+    /// Returns the path at which the account's transparent account key is derived.
+    pub fn bip44_path(&self) -> DerivationPath {
+        DerivationPath::bip44_account(self.coin_type, self.account_index)
+    }
+
+    /// Returns the path at which the key for the transparent address with the given scope
+    /// and index is derived.
+    #[cfg(feature = "transparent-inputs")]
+    pub fn transparent_address_path(
+        &self,
+        scope: TransparentKeyScope,
+        address_index: NonHardenedChildIndex,
+    ) -> DerivationPath {
+        DerivationPath::bip44_address(self.coin_type, self.account_index, scope, address_index)
+    }
+
+    /// Describes the derivation of the shielded receivers of the account's address at the
+    /// given diversifier index.
+    pub fn shielded_address(
+        &self,
+        diversifier_index: DiversifierIndex,
+    ) -> ShieldedAddressDerivation {
+        ShieldedAddressDerivation {
+            account_path: self.zip32_path(),
+            diversifier_index,
+        }
+    }
+}
+
+/// The derivation of a Sapling or Orchard address: the path of the account-level spending
+/// key, together with the diversifier index at which the address was generated.
+///
+/// The [`Display`] implementation formats the derivation as the account path followed by the
+/// diversifier index, for example `m/32'/133'/0'@5`.
+///
+/// [`Display`]: fmt::Display
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShieldedAddressDerivation {
+    account_path: DerivationPath,
+    diversifier_index: DiversifierIndex,
+}
+
+impl ShieldedAddressDerivation {
+    /// Returns the ZIP 32 path of the account-level spending key.
+    pub fn account_path(&self) -> &DerivationPath {
+        &self.account_path
+    }
+
+    /// Returns the diversifier index at which the address was generated.
+    pub fn diversifier_index(&self) -> DiversifierIndex {
+        self.diversifier_index
+    }
+}
+
+impl fmt::Display for ShieldedAddressDerivation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Diversifier indices are 88-bit little-endian integers.
+        let mut index = [0; 16];
+        index[..11].copy_from_slice(self.diversifier_index.as_bytes());
+        write!(f, "{}@{}", self.account_path, u128::from_le_bytes(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zcash_protocol::consensus::{MAIN_NETWORK, TEST_NETWORK};
+    use zip32::{AccountId, DiversifierIndex};
+
+    use super::{AccountDerivation, DerivationPath, ParseError, PathComponent};
+
+    #[test]
+    fn account_paths() {
+        let account = AccountId::try_from(3).unwrap();
+        let mainnet = AccountDerivation::new(&MAIN_NETWORK, account);
+        assert_eq!(mainnet.zip32_path().to_string(), "m/32'/133'/3'");
+        assert_eq!(mainnet.bip44_path().to_string(), "m/44'/133'/3'");
+
+        let testnet = AccountDerivation::new(&TEST_NETWORK, AccountId::ZERO);
+        assert_eq!(testnet.zip32_path().to_string(), "m/32'/1'/0'");
+        assert_eq!(
+            testnet
+                .shielded_address(DiversifierIndex::from(7u32))
+                .to_string(),
+            "m/32'/1'/0'@7"
+        );
+    }
+
+    #[cfg(feature = "transparent-inputs")]
+    #[test]
+    fn transparent_address_path() {
+        use zcash_primitives::legacy::keys::NonHardenedChildIndex;
+
+        let derivation = AccountDerivation::new(&MAIN_NETWORK, AccountId::ZERO);
+        let path = derivation.transparent_address_path(
+            zip32::Scope::External.into(),
+            NonHardenedChildIndex::from_index(3).unwrap(),
+        );
+        assert_eq!(path.to_string(), "m/44'/133'/0'/0/3");
+        assert_eq!(
+            path.components()
+                .iter()
+                .map(|c| c.to_raw_index())
+                .collect::<Vec<_>>(),
+            vec![0x8000_002c, 0x8000_0085, 0x8000_0000, 0, 3]
+        );
+    }
+
+    #[test]
+    fn parse() {
+        let path: DerivationPath = "m/44'/133h/0'/1/5".parse().unwrap();
+        assert_eq!(
+            path,
+            DerivationPath::master()
+                .child(PathComponent::hardened(44).unwrap())
+                .child(PathComponent::hardened(133).unwrap())
+                .child(PathComponent::hardened(0).unwrap())
+                .child(PathComponent::non_hardened(1).unwrap())
+                .child(PathComponent::non_hardened(5).unwrap())
+        );
+        assert_eq!(path.to_string(), "m/44'/133'/0'/1/5");
+        assert_eq!("m".parse(), Ok(DerivationPath::master()));
+
+        assert_eq!(
+            "44'/133'".parse::<DerivationPath>(),
+            Err(ParseError::MissingMaster)
+        );
+        assert_eq!(
+            "m/32'/+1'".parse::<DerivationPath>(),
+            Err(ParseError::InvalidComponent(1))
+        );
+        assert_eq!(
+            "m/2147483648".parse::<DerivationPath>(),
+            Err(ParseError::InvalidComponent(0))
+        );
+        assert_eq!(
+            "m/32'/".parse::<DerivationPath>(),
+            Err(ParseError::InvalidComponent(1))
+        );
+    }
+}
@@ -4,8 +4,10 @@ use std::{
     fmt::{self, Display},
 };
 
+use secrecy::zeroize::Zeroize;
 use zcash_address::unified::{self, Container, Encoding, Typecode, Ufvk, Uivk};
-use zcash_protocol::consensus;
+use zcash_primitives::zip339;
+use zcash_protocol::{consensus, ShieldedProtocol};
 use zip32::{AccountId, DiversifierIndex};
 
 use crate::address::UnifiedAddress;
@@ -37,6 +39,8 @@ use {
 #[cfg(feature = "orchard")]
 use orchard::{self, keys::Scope};
 
+pub mod diagnostics;
+
 #[cfg(feature = "sapling")]
 pub mod sapling {
     pub use sapling::zip32::{
@@ -195,6 +199,105 @@ impl Era {
     }
 }
 
+/// The languages in which a [ZIP 339] mnemonic phrase may be written.
+///
+/// [ZIP 339]: https://zips.z.cash/zip-0339
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MnemonicLanguage {
+    English,
+    ChineseSimplified,
+    ChineseTraditional,
+    Czech,
+    French,
+    Italian,
+    Japanese,
+    Korean,
+    Portuguese,
+    Spanish,
+}
+
+impl MnemonicLanguage {
+    /// All supported languages, with English first.
+    pub const ALL: [MnemonicLanguage; 10] = [
+        MnemonicLanguage::English,
+        MnemonicLanguage::ChineseSimplified,
+        MnemonicLanguage::ChineseTraditional,
+        MnemonicLanguage::Czech,
+        MnemonicLanguage::French,
+        MnemonicLanguage::Italian,
+        MnemonicLanguage::Japanese,
+        MnemonicLanguage::Korean,
+        MnemonicLanguage::Portuguese,
+        MnemonicLanguage::Spanish,
+    ];
+
+    /// Checks that `phrase` is a valid mnemonic in this language, including its checksum.
+    pub fn validate(self, phrase: &str) -> Result<(), zip339::Error> {
+        self.to_seed(phrase, "").map(|_| ())
+    }
+
+    /// Returns the language in which `phrase` is a valid mnemonic, or `None` if it is not
+    /// valid in exactly one of the supported languages.
+    pub fn detect(phrase: &str) -> Option<MnemonicLanguage> {
+        let mut valid = Self::ALL
+            .into_iter()
+            .filter(|language| language.validate(phrase).is_ok());
+        match (valid.next(), valid.next()) {
+            (Some(language), None) => Some(language),
+            _ => None,
+        }
+    }
+
+    /// Parses `phrase` as a mnemonic in this language, verifying its checksum, and derives
+    /// the corresponding 64-byte seed using the given passphrase.
+    fn to_seed(self, phrase: &str, passphrase: &str) -> Result<[u8; 64], zip339::Error> {
+        fn seed<L: zip339::Language>(
+            phrase: &str,
+            passphrase: &str,
+        ) -> Result<[u8; 64], zip339::Error> {
+            zip339::Mnemonic::<L>::from_phrase(phrase).map(|m| m.to_seed(passphrase))
+        }
+
+        match self {
+            MnemonicLanguage::English => seed::<zip339::English>(phrase, passphrase),
+            MnemonicLanguage::ChineseSimplified => {
+                seed::<zip339::ChineseSimplified>(phrase, passphrase)
+            }
+            MnemonicLanguage::ChineseTraditional => {
+                seed::<zip339::ChineseTraditional>(phrase, passphrase)
+            }
+            MnemonicLanguage::Czech => seed::<zip339::Czech>(phrase, passphrase),
+            MnemonicLanguage::French => seed::<zip339::French>(phrase, passphrase),
+            MnemonicLanguage::Italian => seed::<zip339::Italian>(phrase, passphrase),
+            MnemonicLanguage::Japanese => seed::<zip339::Japanese>(phrase, passphrase),
+            MnemonicLanguage::Korean => seed::<zip339::Korean>(phrase, passphrase),
+            MnemonicLanguage::Portuguese => seed::<zip339::Portuguese>(phrase, passphrase),
+            MnemonicLanguage::Spanish => seed::<zip339::Spanish>(phrase, passphrase),
+        }
+    }
+}
+
+/// Errors that can occur when deriving a [`UnifiedSpendingKey`] from a mnemonic phrase.
+#[derive(Debug)]
+pub enum MnemonicError {
+    /// The phrase was not a valid mnemonic in the requested language. This includes
+    /// phrases with an unknown word, an unsupported number of words, or an invalid checksum.
+    InvalidPhrase(zip339::Error),
+    /// Key derivation from the seed failed.
+    Derivation(DerivationError),
+}
+
+impl Display for MnemonicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MnemonicError::InvalidPhrase(e) => write!(f, "Invalid mnemonic phrase: {}", e),
+            MnemonicError::Derivation(e) => write!(f, "Key derivation failed: {}", e),
+        }
+    }
+}
+
+impl error::Error for MnemonicError {}
+
 /// A set of spending keys that are all associated with a single ZIP-0032 account identifier.
 #[derive(Clone, Debug)]
 pub struct UnifiedSpendingKey {
@@ -228,6 +331,30 @@ impl UnifiedSpendingKey {
         )
     }
 
+    /// Derives the unified spending key for the given account from a [ZIP 339] mnemonic
+    /// phrase and passphrase.
+    ///
+    /// The phrase is validated against the word list of `language`, including its checksum,
+    /// before the seed is derived; [`MnemonicLanguage::detect`] may be used to determine the
+    /// language of a phrase entered by a user. The passphrase is the optional extension word
+    /// described in BIP 39, and should be empty if none was used when the phrase was created.
+    ///
+    /// [ZIP 339]: https://zips.z.cash/zip-0339
+    pub fn from_mnemonic<P: consensus::Parameters>(
+        params: &P,
+        phrase: &str,
+        language: MnemonicLanguage,
+        passphrase: &str,
+        account: AccountId,
+    ) -> Result<UnifiedSpendingKey, MnemonicError> {
+        let mut seed = language
+            .to_seed(phrase, passphrase)
+            .map_err(MnemonicError::InvalidPhrase)?;
+        let usk = UnifiedSpendingKey::from_seed(params, &seed, account);
+        seed.zeroize();
+        usk.map_err(MnemonicError::Derivation)
+    }
+
     /// Construct a USK from its constituent parts, after verifying that UIVK derivation can
     /// succeed.
     fn from_checked_parts(
@@ -544,7 +671,7 @@ impl fmt::Display for AddressGenerationError {
 impl error::Error for AddressGenerationError {}
 
 /// Specification for how a unified address should be generated from a unified viewing key.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct UnifiedAddressRequest {
     has_orchard: bool,
     has_sapling: bool,
@@ -601,6 +728,79 @@ impl UnifiedAddressRequest {
             has_p2pkh,
         }
     }
+
+    /// Constructs a new unified address request that includes only the receivers requested
+    /// by both `self` and `other`.
+    ///
+    /// Returns `None` if the resulting unified address would not include at least one
+    /// shielded receiver.
+    pub fn intersect(&self, other: &UnifiedAddressRequest) -> Option<UnifiedAddressRequest> {
+        Self::new(
+            self.has_orchard && other.has_orchard,
+            self.has_sapling && other.has_sapling,
+            self.has_p2pkh && other.has_p2pkh,
+        )
+    }
+}
+
+/// Specification of the pools whose components should be retained when exporting a
+/// restricted unified viewing key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ViewingKeyRequest {
+    has_orchard: bool,
+    has_sapling: bool,
+    has_p2pkh: bool,
+}
+
+impl ViewingKeyRequest {
+    /// Constructs a new viewing key request from its constituent parts.
+    ///
+    /// Returns `None` if no shielded pool is requested, because a unified viewing key must
+    /// contain at least one shielded component.
+    pub fn new(has_orchard: bool, has_sapling: bool, has_p2pkh: bool) -> Option<Self> {
+        if !(has_orchard || has_sapling) {
+            None
+        } else {
+            Some(Self {
+                has_orchard,
+                has_sapling,
+                has_p2pkh,
+            })
+        }
+    }
+
+    /// Constructs a viewing key request that retains the components for every pool.
+    pub fn all() -> Self {
+        Self {
+            has_orchard: true,
+            has_sapling: true,
+            has_p2pkh: true,
+        }
+    }
+}
+
+/// A viewing capability that can be exported from a [`UnifiedFullViewingKey`], for example
+/// in order to give an auditor access to an account's transactions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ViewingCapability {
+    /// Full viewing capability for the requested pools, encoded as a [ZIP 316] unified
+    /// full viewing key. This reveals both incoming and outgoing transactions, and allows
+    /// detection of spends.
+    ///
+    /// [ZIP 316]: https://zips.z.cash/zip-0316
+    Full(ViewingKeyRequest),
+    /// Incoming viewing capability for the requested pools, encoded as a [ZIP 316] unified
+    /// incoming viewing key. This reveals only the funds received at the account's external
+    /// addresses.
+    ///
+    /// [ZIP 316]: https://zips.z.cash/zip-0316
+    Incoming(ViewingKeyRequest),
+    /// Outgoing viewing capability for a single shielded pool. This allows recovery of the
+    /// outputs of transactions sent from the account, but not of funds it receives.
+    ///
+    /// There is no standard string encoding for outgoing viewing keys; the external-scope
+    /// key is exported as 64 lowercase hex characters.
+    Outgoing(ShieldedProtocol),
 }
 
 #[cfg(feature = "transparent-inputs")]
@@ -839,32 +1039,104 @@ impl UnifiedFullViewingKey {
         self.orchard.as_ref()
     }
 
+    /// Returns a copy of this key that contains only the components for the requested
+    /// pools. Unknown components are not retained.
+    ///
+    /// Returns `None` if the restricted key would not contain any shielded component.
+    pub fn restrict(&self, request: ViewingKeyRequest) -> Option<UnifiedFullViewingKey> {
+        let _ = request;
+        let restricted = UnifiedFullViewingKey {
+            #[cfg(feature = "transparent-inputs")]
+            transparent: self.transparent.clone().filter(|_| request.has_p2pkh),
+            #[cfg(feature = "sapling")]
+            sapling: self.sapling.clone().filter(|_| request.has_sapling),
+            #[cfg(feature = "orchard")]
+            orchard: self.orchard.clone().filter(|_| request.has_orchard),
+            unknown: vec![],
+        };
+
+        restricted
+            .to_unified_incoming_viewing_key()
+            .has_shielded_component()
+            .then_some(restricted)
+    }
+
+    /// Exports the given viewing capability of this key, encoded for the given network.
+    ///
+    /// Returns `None` if this key has no components for the requested pools.
+    pub fn export_viewing_key<P: consensus::Parameters>(
+        &self,
+        params: &P,
+        capability: ViewingCapability,
+    ) -> Option<String> {
+        match capability {
+            ViewingCapability::Full(request) => {
+                self.restrict(request).map(|ufvk| ufvk.encode(params))
+            }
+            ViewingCapability::Incoming(request) => self
+                .to_unified_incoming_viewing_key()
+                .restrict(request)
+                .map(|uivk| uivk.encode(params)),
+            ViewingCapability::Outgoing(protocol) => {
+                let ovk: Option<[u8; 32]> = match protocol {
+                    #[cfg(feature = "sapling")]
+                    ShieldedProtocol::Sapling => self
+                        .sapling
+                        .as_ref()
+                        .map(|dfvk| dfvk.to_ovk(zip32::Scope::External).0),
+                    #[cfg(feature = "orchard")]
+                    ShieldedProtocol::Orchard => self
+                        .orchard
+                        .as_ref()
+                        .map(|fvk| *fvk.to_ovk(Scope::External).as_ref()),
+                    #[allow(unreachable_patterns)]
+                    _ => None,
+                };
+                ovk.map(hex::encode)
+            }
+        }
+    }
+
     /// Attempts to derive the Unified Address for the given diversifier index and
     /// receiver types.
     ///
-    /// Returns `None` if the specified index does not produce a valid diversifier.
+    /// Returns [`AddressGenerationError::InvalidSaplingDiversifierIndex`] if a Sapling
+    /// receiver is requested and the specified index does not produce a valid diversifier.
+    /// Use [`Self::find_address`] to search forward from `j` for a valid index instead.
+    pub fn address_at(
+        &self,
+        j: impl Into<DiversifierIndex>,
+        request: UnifiedAddressRequest,
+    ) -> Result<UnifiedAddress, AddressGenerationError> {
+        self.to_unified_incoming_viewing_key()
+            .address_at(j, request)
+    }
+
+    /// Attempts to derive the Unified Address for the given diversifier index and
+    /// receiver types.
+    #[deprecated(note = "use `UnifiedFullViewingKey::address_at` instead")]
     pub fn address(
         &self,
         j: DiversifierIndex,
         request: UnifiedAddressRequest,
     ) -> Result<UnifiedAddress, AddressGenerationError> {
-        self.to_unified_incoming_viewing_key().address(j, request)
+        self.address_at(j, request)
     }
 
-    /// Searches the diversifier space starting at diversifier index `j` for one which will
-    /// produce a valid diversifier, and return the Unified Address constructed using that
-    /// diversifier along with the index at which the valid diversifier was found.
+    /// Searches the diversifier space starting at diversifier index `from_j` for one which
+    /// will produce a valid diversifier for every requested receiver type, and returns the
+    /// Unified Address constructed using that diversifier along with the index at which the
+    /// valid diversifier was found.
     ///
     /// Returns an `Err(AddressGenerationError)` if no valid diversifier exists or if the features
     /// required to satisfy the unified address request are not properly enabled.
-    #[allow(unused_mut)]
     pub fn find_address(
         &self,
-        mut j: DiversifierIndex,
+        from_j: DiversifierIndex,
         request: UnifiedAddressRequest,
     ) -> Result<(UnifiedAddress, DiversifierIndex), AddressGenerationError> {
         self.to_unified_incoming_viewing_key()
-            .find_address(j, request)
+            .find_address(from_j, request)
     }
 
     /// Find the Unified Address corresponding to the smallest valid diversifier index, along with
@@ -1062,15 +1334,69 @@ impl UnifiedIncomingViewingKey {
         &self.orchard
     }
 
+    /// Returns `true` if this key contains a Sapling or Orchard component.
+    fn has_shielded_component(&self) -> bool {
+        let _has_shielded = false;
+        #[cfg(feature = "sapling")]
+        let _has_shielded = _has_shielded || self.sapling.is_some();
+        #[cfg(feature = "orchard")]
+        let _has_shielded = _has_shielded || self.orchard.is_some();
+        _has_shielded
+    }
+
+    /// Returns a copy of this key that contains only the components for the requested
+    /// pools. Unknown components are not retained.
+    ///
+    /// Returns `None` if the restricted key would not contain any shielded component.
+    pub fn restrict(&self, request: ViewingKeyRequest) -> Option<UnifiedIncomingViewingKey> {
+        let _ = request;
+        let restricted = UnifiedIncomingViewingKey {
+            #[cfg(feature = "transparent-inputs")]
+            transparent: self.transparent.clone().filter(|_| request.has_p2pkh),
+            #[cfg(feature = "sapling")]
+            sapling: self.sapling.clone().filter(|_| request.has_sapling),
+            #[cfg(feature = "orchard")]
+            orchard: self.orchard.clone().filter(|_| request.has_orchard),
+            unknown: vec![],
+        };
+
+        restricted.has_shielded_component().then_some(restricted)
+    }
+
+    /// Returns the address request for a unified address with a receiver for each of the
+    /// components of this key.
+    ///
+    /// Returns `None` if this key has no shielded component.
+    pub fn to_address_request(&self) -> Option<UnifiedAddressRequest> {
+        let _has_orchard = false;
+        #[cfg(feature = "orchard")]
+        let _has_orchard = self.orchard.is_some();
+
+        let _has_sapling = false;
+        #[cfg(feature = "sapling")]
+        let _has_sapling = self.sapling.is_some();
+
+        let _has_p2pkh = false;
+        #[cfg(feature = "transparent-inputs")]
+        let _has_p2pkh = self.transparent.is_some();
+
+        UnifiedAddressRequest::new(_has_orchard, _has_sapling, _has_p2pkh)
+    }
+
     /// Attempts to derive the Unified Address for the given diversifier index and
     /// receiver types.
     ///
-    /// Returns `None` if the specified index does not produce a valid diversifier.
-    pub fn address(
+    /// Returns [`AddressGenerationError::InvalidSaplingDiversifierIndex`] if a Sapling
+    /// receiver is requested and the specified index does not produce a valid diversifier.
+    /// Use [`Self::find_address`] to search forward from `j` for a valid index instead.
+    pub fn address_at(
         &self,
-        _j: DiversifierIndex,
+        j: impl Into<DiversifierIndex>,
         request: UnifiedAddressRequest,
     ) -> Result<UnifiedAddress, AddressGenerationError> {
+        // The index is unused if no receiver types are enabled.
+        #[allow(unused_variables)]
+        let j: DiversifierIndex = j.into();
         #[cfg(feature = "orchard")]
         let mut orchard = None;
         if request.has_orchard {
@@ -1081,7 +1407,7 @@ impl UnifiedIncomingViewingKey {
 
             #[cfg(feature = "orchard")]
             if let Some(oivk) = &self.orchard {
-                let orchard_j = orchard::keys::DiversifierIndex::from(*_j.as_bytes());
+                let orchard_j = orchard::keys::DiversifierIndex::from(*j.as_bytes());
                 orchard = Some(oivk.address_at(orchard_j))
             } else {
                 return Err(AddressGenerationError::KeyNotAvailable(Typecode::Orchard));
@@ -1102,8 +1428,8 @@ impl UnifiedIncomingViewingKey {
                 // address; if we're unable to do so, then no Unified Address exists at this
                 // diversifier and we use `?` to early-return from this method.
                 sapling = Some(
-                    divk.address_at(_j)
-                        .ok_or(AddressGenerationError::InvalidSaplingDiversifierIndex(_j))?,
+                    divk.address_at(j)
+                        .ok_or(AddressGenerationError::InvalidSaplingDiversifierIndex(j))?,
                 );
             } else {
                 return Err(AddressGenerationError::KeyNotAvailable(Typecode::Sapling));
@@ -1123,12 +1449,12 @@ impl UnifiedIncomingViewingKey {
                 // If a transparent receiver type is requested, we must be able to construct an
                 // address; if we're unable to do so, then no Unified Address exists at this
                 // diversifier.
-                let transparent_j = to_transparent_child_index(_j)
-                    .ok_or(AddressGenerationError::InvalidTransparentChildIndex(_j))?;
+                let transparent_j = to_transparent_child_index(j)
+                    .ok_or(AddressGenerationError::InvalidTransparentChildIndex(j))?;
 
                 transparent = Some(
                     tivk.derive_address(transparent_j)
-                        .map_err(|_| AddressGenerationError::InvalidTransparentChildIndex(_j))?,
+                        .map_err(|_| AddressGenerationError::InvalidTransparentChildIndex(j))?,
                 );
             } else {
                 return Err(AddressGenerationError::KeyNotAvailable(Typecode::P2pkh));
@@ -1147,18 +1473,31 @@ impl UnifiedIncomingViewingKey {
         .ok_or(AddressGenerationError::ShieldedReceiverRequired)
     }
 
-    /// Searches the diversifier space starting at diversifier index `j` for one which will
-    /// produce a valid diversifier, and return the Unified Address constructed using that
-    /// diversifier along with the index at which the valid diversifier was found.
+    /// Attempts to derive the Unified Address for the given diversifier index and
+    /// receiver types.
+    #[deprecated(note = "use `UnifiedIncomingViewingKey::address_at` instead")]
+    pub fn address(
+        &self,
+        j: DiversifierIndex,
+        request: UnifiedAddressRequest,
+    ) -> Result<UnifiedAddress, AddressGenerationError> {
+        self.address_at(j, request)
+    }
+
+    /// Searches the diversifier space starting at diversifier index `from_j` for one which
+    /// will produce a valid diversifier for every requested receiver type, and returns the
+    /// Unified Address constructed using that diversifier along with the index at which the
+    /// valid diversifier was found.
     ///
     /// Returns an `Err(AddressGenerationError)` if no valid diversifier exists or if the features
     /// required to satisfy the unified address request are not properly enabled.
-    #[allow(unused_mut)]
     pub fn find_address(
         &self,
-        mut j: DiversifierIndex,
+        from_j: DiversifierIndex,
         request: UnifiedAddressRequest,
     ) -> Result<(UnifiedAddress, DiversifierIndex), AddressGenerationError> {
+        #[allow(unused_mut)]
+        let mut j = from_j;
         // If we need to generate a transparent receiver, check that the user has not
         // specified an invalid transparent child index, from which we can never search to
         // find a valid index.
@@ -1172,7 +1511,7 @@ impl UnifiedIncomingViewingKey {
 
         // Find a working diversifier and construct the associated address.
         loop {
-            let res = self.address(j, request);
+            let res = self.address_at(j, request);
             match res {
                 Ok(ua) => {
                     return Ok((ua, j));
@@ -1265,6 +1604,56 @@ mod tests {
         hex::decode(seed_hex).unwrap()
     }
 
+    #[test]
+    fn usk_from_mnemonic() {
+        use super::{MnemonicError, MnemonicLanguage, UnifiedSpendingKey};
+
+        // BIP 39 test vector.
+        let phrase = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let seed = hex::decode("c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04").unwrap();
+
+        assert_eq!(
+            MnemonicLanguage::detect(phrase),
+            Some(MnemonicLanguage::English)
+        );
+        let from_mnemonic = UnifiedSpendingKey::from_mnemonic(
+            &MAIN_NETWORK,
+            phrase,
+            MnemonicLanguage::English,
+            "TREZOR",
+            AccountId::ZERO,
+        )
+        .unwrap();
+        let from_seed =
+            UnifiedSpendingKey::from_seed(&MAIN_NETWORK, &seed, AccountId::ZERO).unwrap();
+        assert_eq!(
+            from_mnemonic
+                .to_unified_full_viewing_key()
+                .encode(&MAIN_NETWORK),
+            from_seed
+                .to_unified_full_viewing_key()
+                .encode(&MAIN_NETWORK),
+        );
+
+        // The final word determines the checksum.
+        let bad_checksum = phrase.replace("about", "abandon");
+        assert!(MnemonicLanguage::English.validate(&bad_checksum).is_err());
+        assert_eq!(MnemonicLanguage::detect(&bad_checksum), None);
+        assert!(matches!(
+            UnifiedSpendingKey::from_mnemonic(
+                &MAIN_NETWORK,
+                &bad_checksum,
+                MnemonicLanguage::English,
+                "",
+                AccountId::ZERO,
+            ),
+            Err(MnemonicError::InvalidPhrase(_))
+        ));
+
+        // The phrase is not valid in any other language.
+        assert!(MnemonicLanguage::French.validate(phrase).is_err());
+    }
+
     #[test]
     #[should_panic]
     #[cfg(feature = "sapling")]
@@ -1435,7 +1824,7 @@ mod tests {
             }
 
             let ua = ufvk
-                .address(d_idx, UnifiedAddressRequest::unsafe_new(false, true, true))
+                .address_at(d_idx, UnifiedAddressRequest::unsafe_new(false, true, true))
                 .unwrap_or_else(|err| {
                     panic!(
                         "unified address generation failed for account {}: {:?}",
@@ -1616,7 +2005,7 @@ mod tests {
             }
 
             let ua = uivk
-                .address(d_idx, UnifiedAddressRequest::unsafe_new(false, true, true))
+                .address_at(d_idx, UnifiedAddressRequest::unsafe_new(false, true, true))
                 .unwrap_or_else(|err| {
                     panic!(
                         "unified address generation failed for account {}: {:?}",
@@ -1646,6 +2035,89 @@ mod tests {
         }
     }
 
+    #[test]
+    #[cfg(all(
+        feature = "transparent-inputs",
+        feature = "sapling",
+        feature = "orchard"
+    ))]
+    fn restricted_viewing_key_export() {
+        use zcash_protocol::ShieldedProtocol;
+
+        use super::{UnifiedSpendingKey, ViewingCapability, ViewingKeyRequest};
+
+        let usk = UnifiedSpendingKey::from_seed(&MAIN_NETWORK, &seed(), AccountId::ZERO).unwrap();
+        let ufvk = usk.to_unified_full_viewing_key();
+
+        // At least one shielded pool must be requested.
+        assert_eq!(ViewingKeyRequest::new(false, false, true), None);
+        let sapling_only = ViewingKeyRequest::new(false, true, false).unwrap();
+
+        let encoded = ufvk
+            .export_viewing_key(&MAIN_NETWORK, ViewingCapability::Full(sapling_only))
+            .unwrap();
+        let restricted = UnifiedFullViewingKey::decode(&MAIN_NETWORK, &encoded).unwrap();
+        assert!(restricted.transparent().is_none());
+        assert!(restricted.orchard().is_none());
+        assert_eq!(
+            restricted.sapling().unwrap().to_bytes(),
+            ufvk.sapling().unwrap().to_bytes()
+        );
+
+        let encoded = ufvk
+            .export_viewing_key(&MAIN_NETWORK, ViewingCapability::Incoming(sapling_only))
+            .unwrap();
+        let uivk = UnifiedIncomingViewingKey::decode(&MAIN_NETWORK, &encoded).unwrap();
+        assert!(uivk.transparent().is_none());
+        assert!(uivk.orchard().is_none());
+        assert_eq!(
+            uivk.sapling().as_ref().unwrap().to_bytes(),
+            ufvk.sapling().unwrap().to_external_ivk().to_bytes()
+        );
+        assert_eq!(
+            uivk.to_address_request()
+                .and_then(|r| r.intersect(&super::UnifiedAddressRequest::all().unwrap())),
+            Some(super::UnifiedAddressRequest::unsafe_new(false, true, false))
+        );
+
+        // The full request retains every component.
+        let encoded = ufvk
+            .export_viewing_key(
+                &MAIN_NETWORK,
+                ViewingCapability::Full(ViewingKeyRequest::all()),
+            )
+            .unwrap();
+        assert_eq!(encoded, ufvk.encode(&MAIN_NETWORK));
+
+        let ovk = ufvk
+            .export_viewing_key(
+                &MAIN_NETWORK,
+                ViewingCapability::Outgoing(ShieldedProtocol::Orchard),
+            )
+            .unwrap();
+        assert_eq!(
+            hex::decode(ovk).unwrap(),
+            ufvk.orchard()
+                .unwrap()
+                .to_ovk(Scope::External)
+                .as_ref()
+                .to_vec()
+        );
+
+        // A key without the requested components cannot be exported.
+        let restricted = ufvk.restrict(sapling_only).unwrap();
+        assert_eq!(
+            restricted.export_viewing_key(
+                &MAIN_NETWORK,
+                ViewingCapability::Outgoing(ShieldedProtocol::Orchard)
+            ),
+            None
+        );
+        assert!(restricted
+            .restrict(ViewingKeyRequest::new(true, false, true).unwrap())
+            .is_none());
+    }
+
     proptest! {
         #[test]
         #[cfg(feature = "unstable")]
@@ -1679,4 +2151,34 @@ mod tests {
             assert_eq!(decoded.transparent().to_bytes(), usk.transparent().to_bytes());
         }
     }
+
+    #[test]
+    #[cfg(all(feature = "transparent-inputs", feature = "sapling"))]
+    fn address_search() {
+        use super::{AddressGenerationError, UnifiedAddressRequest};
+
+        let usk = UnifiedSpendingKey::from_seed(&MAIN_NETWORK, &seed(), AccountId::ZERO).unwrap();
+        let ufvk = usk.to_unified_full_viewing_key();
+        let request = UnifiedAddressRequest::unsafe_new(false, true, true);
+
+        // Find an index that does not produce a valid Sapling diversifier.
+        let dfvk = ufvk.sapling().unwrap();
+        let invalid_j = (0u32..)
+            .map(DiversifierIndex::from)
+            .find(|j| dfvk.address(*j).is_none())
+            .unwrap();
+        assert!(matches!(
+            ufvk.address_at(invalid_j, request),
+            Err(AddressGenerationError::InvalidSaplingDiversifierIndex(j)) if j == invalid_j
+        ));
+
+        // Searching forward from that index skips to the next valid diversifier.
+        let (ua, found_j) = ufvk.find_address(invalid_j, request).unwrap();
+        assert_ne!(found_j, invalid_j);
+        assert_eq!(ufvk.address_at(found_j, request).unwrap(), ua);
+        assert_eq!(ua.sapling(), dfvk.address(found_j).as_ref());
+
+        // Searching from a valid index returns that index unchanged.
+        assert_eq!(ufvk.find_address(found_j, request).unwrap(), (ua, found_j));
+    }
 }
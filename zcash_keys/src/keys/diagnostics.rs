@@ -0,0 +1,273 @@
+//! Diagnostics for checking that a seed is compatible with other wallets.
+//!
+//! Wallets that have been restored from the same seed should derive the same addresses.
+//! Before restoring a wallet that was created by another application, the addresses produced
+//! by [`enumerate_addresses`] can be compared with those shown by the other application, to
+//! confirm that both use the same [ZIP 32] and [BIP 44] derivation and to find the accounts
+//! and address indices that are in use.
+//!
+//! Some wallets derive each Sapling address from a separate account, while others derive
+//! diversified addresses from a single account; enumerating several addresses for each of
+//! several accounts covers both conventions.
+//!
+//! [ZIP 32]: https://zips.z.cash/zip-0032
+//! [BIP 44]: https://github.com/bitcoin/bips/blob/master/bip-0044.mediawiki
+
+use std::{error, fmt};
+
+use zcash_protocol::consensus;
+use zip32::{AccountId, DiversifierIndex};
+
+use super::{AddressGenerationError, DerivationError, UnifiedAddressRequest, UnifiedSpendingKey};
+use crate::address::UnifiedAddress;
+
+#[cfg(feature = "transparent-inputs")]
+use zcash_primitives::legacy::{
+    keys::{IncomingViewingKey, NonHardenedChildIndex},
+    TransparentAddress,
+};
+
+/// The minimum length of a ZIP 32 seed, in bytes.
+pub const MIN_SEED_LENGTH: usize = 32;
+
+/// The maximum length of a ZIP 32 seed, in bytes.
+pub const MAX_SEED_LENGTH: usize = 252;
+
+/// Errors that can occur while checking a seed or enumerating its addresses.
+#[derive(Debug)]
+pub enum Error {
+    /// The seed was not between [`MIN_SEED_LENGTH`] and [`MAX_SEED_LENGTH`] bytes long.
+    InvalidSeedLength(usize),
+    /// Key derivation failed.
+    Derivation(DerivationError),
+    /// A unified address could not be generated.
+    AddressGeneration(AddressGenerationError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidSeedLength(len) => write!(
+                f,
+                "Seed is {} bytes long; ZIP 32 seeds must be between {} and {} bytes",
+                len, MIN_SEED_LENGTH, MAX_SEED_LENGTH
+            ),
+            Error::Derivation(e) => write!(f, "Key derivation failed: {}", e),
+            Error::AddressGeneration(e) => write!(f, "Address generation failed: {}", e),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::AddressGeneration(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<DerivationError> for Error {
+    fn from(e: DerivationError) -> Self {
+        Error::Derivation(e)
+    }
+}
+
+impl From<AddressGenerationError> for Error {
+    fn from(e: AddressGenerationError) -> Self {
+        Error::AddressGeneration(e)
+    }
+}
+
+/// Checks that `seed` has a length permitted by [ZIP 32].
+///
+/// Seeds derived from [ZIP 339] mnemonic phrases are always 64 bytes long.
+///
+/// [ZIP 32]: https://zips.z.cash/zip-0032#specification-key-generation
+/// [ZIP 339]: https://zips.z.cash/zip-0339
+pub fn check_seed(seed: &[u8]) -> Result<(), Error> {
+    if (MIN_SEED_LENGTH..=MAX_SEED_LENGTH).contains(&seed.len()) {
+        Ok(())
+    } else {
+        Err(Error::InvalidSeedLength(seed.len()))
+    }
+}
+
+/// The first addresses derived for a single account.
+#[derive(Clone, Debug)]
+pub struct AccountAddresses {
+    account: AccountId,
+    unified: Vec<(DiversifierIndex, UnifiedAddress)>,
+    #[cfg(feature = "sapling")]
+    sapling: Vec<(DiversifierIndex, ::sapling::PaymentAddress)>,
+    #[cfg(feature = "transparent-inputs")]
+    transparent: Vec<(NonHardenedChildIndex, TransparentAddress)>,
+}
+
+impl AccountAddresses {
+    /// Returns the ZIP 32 index of the account.
+    pub fn account(&self) -> AccountId {
+        self.account
+    }
+
+    /// Returns the account's external unified addresses, containing a receiver for each
+    /// protocol enabled by this crate's feature flags, in order of increasing diversifier
+    /// index.
+    pub fn unified(&self) -> &[(DiversifierIndex, UnifiedAddress)] {
+        &self.unified
+    }
+
+    /// Returns the account's external Sapling addresses in order of increasing diversifier
+    /// index, skipping indices that do not produce a valid diversifier.
+    #[cfg(feature = "sapling")]
+    pub fn sapling(&self) -> &[(DiversifierIndex, ::sapling::PaymentAddress)] {
+        &self.sapling
+    }
+
+    /// Returns the account's external transparent addresses, derived at the BIP 44 paths
+    /// `m/44'/<coin_type>'/<account>'/0/<index>`.
+    #[cfg(feature = "transparent-inputs")]
+    pub fn transparent(&self) -> &[(NonHardenedChildIndex, TransparentAddress)] {
+        &self.transparent
+    }
+}
+
+/// Derives the first `count` external addresses of each kind for each of the given accounts
+/// of `seed`.
+///
+/// Unified addresses are only produced if a shielded protocol is enabled.
+pub fn enumerate_addresses<P: consensus::Parameters>(
+    params: &P,
+    seed: &[u8],
+    accounts: impl IntoIterator<Item = AccountId>,
+    count: u32,
+) -> Result<Vec<AccountAddresses>, Error> {
+    check_seed(seed)?;
+
+    accounts
+        .into_iter()
+        .map(|account| {
+            let usk = UnifiedSpendingKey::from_seed(params, seed, account)?;
+            let ufvk = usk.to_unified_full_viewing_key();
+
+            let mut unified = vec![];
+            if let Some(request) = UnifiedAddressRequest::all() {
+                let mut j = DiversifierIndex::new();
+                while unified.len() < count as usize {
+                    let (ua, found) = ufvk.find_address(j, request)?;
+                    unified.push((found, ua));
+                    j = found;
+                    if j.increment().is_err() {
+                        break;
+                    }
+                }
+            }
+
+            #[cfg(feature = "sapling")]
+            let sapling = {
+                let dfvk = usk.sapling().to_diversifiable_full_viewing_key();
+                let mut addrs = vec![];
+                let mut j = DiversifierIndex::new();
+                while addrs.len() < count as usize {
+                    match dfvk.find_address(j) {
+                        Some((found, addr)) => {
+                            addrs.push((found, addr));
+                            j = found;
+                            if j.increment().is_err() {
+                                break;
+                            }
+                        }
+                        None => break,
+                    }
+                }
+                addrs
+            };
+
+            #[cfg(feature = "transparent-inputs")]
+            let transparent = {
+                let ivk = usk
+                    .transparent()
+                    .to_account_pubkey()
+                    .derive_external_ivk()
+                    .map_err(DerivationError::from)?;
+                (0..count)
+                    .filter_map(NonHardenedChildIndex::from_index)
+                    .map(|i| {
+                        ivk.derive_address(i)
+                            .map(|addr| (i, addr))
+                            .map_err(|e| Error::Derivation(e.into()))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?
+            };
+
+            Ok(AccountAddresses {
+                account,
+                unified,
+                #[cfg(feature = "sapling")]
+                sapling,
+                #[cfg(feature = "transparent-inputs")]
+                transparent,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use zcash_protocol::consensus::MAIN_NETWORK;
+    use zip32::AccountId;
+
+    use super::{check_seed, enumerate_addresses, Error};
+    use crate::keys::{UnifiedAddressRequest, UnifiedSpendingKey};
+
+    #[test]
+    fn seed_length() {
+        assert!(matches!(
+            check_seed(&[0; 31]),
+            Err(Error::InvalidSeedLength(31))
+        ));
+        assert!(check_seed(&[0; 32]).is_ok());
+        assert!(check_seed(&[0; 252]).is_ok());
+        assert!(matches!(
+            enumerate_addresses(&MAIN_NETWORK, &[0; 253], [AccountId::ZERO], 1),
+            Err(Error::InvalidSeedLength(253))
+        ));
+    }
+
+    #[test]
+    fn enumerates_addresses() {
+        let seed = [7; 32];
+        let accounts = [AccountId::ZERO, AccountId::try_from(1).unwrap()];
+        let report = enumerate_addresses(&MAIN_NETWORK, &seed, accounts, 3).unwrap();
+        assert_eq!(report.len(), 2);
+
+        for (addresses, account) in report.iter().zip(accounts) {
+            assert_eq!(addresses.account(), account);
+            let usk = UnifiedSpendingKey::from_seed(&MAIN_NETWORK, &seed, account).unwrap();
+
+            if let Some(request) = UnifiedAddressRequest::all() {
+                assert_eq!(addresses.unified().len(), 3);
+                assert_eq!(addresses.unified()[0], {
+                    let (ua, j) = usk.default_address(request);
+                    (j, ua)
+                });
+                assert!(addresses.unified().windows(2).all(|w| w[0].0 != w[1].0));
+            }
+
+            #[cfg(feature = "sapling")]
+            {
+                let dfvk = usk.sapling().to_diversifiable_full_viewing_key();
+                assert_eq!(addresses.sapling().len(), 3);
+                assert_eq!(addresses.sapling()[0], dfvk.default_address());
+                assert!(addresses.sapling().windows(2).all(|w| w[0].0 != w[1].0));
+            }
+
+            #[cfg(feature = "transparent-inputs")]
+            {
+                assert_eq!(addresses.transparent().len(), 3);
+                let (taddr, index) = usk.default_transparent_address();
+                assert_eq!(addresses.transparent()[0], (index, taddr));
+            }
+        }
+    }
+}
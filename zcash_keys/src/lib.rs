@@ -15,6 +15,7 @@
 #![allow(clippy::result_unit_err)]
 
 pub mod address;
+pub mod derivation;
 pub mod encoding;
 
 #[cfg(any(
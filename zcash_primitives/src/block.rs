@@ -1,4 +1,4 @@
-//! Structs and methods for handling Zcash block headers.
+//! Structs and methods for handling Zcash blocks and block headers.
 
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use memuse::DynamicUsage;
@@ -8,11 +8,23 @@ use std::io::{self, Read, Write};
 use std::ops::Deref;
 use zcash_encoding::Vector;
 
+use crate::{
+    consensus::{self, BlockHeight, BranchId, NetworkType, NetworkUpgrade},
+    legacy::script::{Instruction, OpCode},
+    transaction::{Transaction, TxId},
+};
+
 pub use equihash;
 
 /// The identifier for a Zcash block.
 ///
 /// This is the SHA-256d hash of the encoded [`BlockHeader`].
+///
+/// When the `serde` feature flag is enabled, a `BlockHash` is serialized as a hex string in
+/// the (byte-flipped) order used by its [`Display`] implementation, RPC methods, and block
+/// explorers.
+///
+/// [`Display`]: fmt::Display
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub struct BlockHash(pub [u8; 32]);
 
@@ -59,6 +71,37 @@ impl BlockHash {
     }
 }
 
+#[cfg(feature = "serde")]
+impl serde::Serialize for BlockHash {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for BlockHash {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error as _;
+
+        let encoded = String::deserialize(deserializer)?;
+        let mut hash = <[u8; 32]>::try_from(hex::decode(encoded).map_err(D::Error::custom)?)
+            .map_err(|_| D::Error::custom("block hash must be 32 bytes"))?;
+        hash.reverse();
+        Ok(BlockHash(hash))
+    }
+}
+
+/// The length of the portion of an encoded block header that is used as the Equihash input.
+const EQUIHASH_INPUT_LEN: usize = 4 + 32 + 32 + 32 + 4 + 4;
+
+/// Returns the Equihash parameters used by the given network.
+pub fn equihash_params(network: NetworkType) -> equihash::Params {
+    match network {
+        NetworkType::Main | NetworkType::Test => equihash::Params::ZCASH_MAINNET,
+        NetworkType::Regtest => equihash::Params::ZCASH_REGTEST,
+    }
+}
+
 /// A Zcash block header.
 pub struct BlockHeader {
     hash: BlockHash,
@@ -78,17 +121,62 @@ pub struct BlockHeaderData {
     pub version: i32,
     pub prev_block: BlockHash,
     pub merkle_root: [u8; 32],
+    /// The `hashBlockCommitments` field of the header. Its meaning depends on the network
+    /// upgrade that is active at the height of the block; see [`BlockHeaderData::commitments`].
     pub final_sapling_root: [u8; 32],
     pub time: u32,
     pub bits: u32,
     pub nonce: [u8; 32],
+    /// The Equihash solution for this header.
     pub solution: Vec<u8>,
 }
 
+/// The interpretation of the `hashBlockCommitments` field of a block header, which has been
+/// redefined by several network upgrades.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockCommitments {
+    /// Prior to Sapling activation, the field is reserved and must be all zeroes.
+    Reserved([u8; 32]),
+    /// From Sapling activation until Heartwood activation, the root of the Sapling note
+    /// commitment tree after the block has been applied.
+    FinalSaplingRoot([u8; 32]),
+    /// From Heartwood activation until NU5 activation, the [ZIP 221] chain history root
+    /// committing to the preceding blocks. The block at which Heartwood activates commits
+    /// to an all-zeroes value instead.
+    ///
+    /// [ZIP 221]: https://zips.z.cash/zip-0221
+    ChainHistoryRoot([u8; 32]),
+    /// From NU5 activation, the [ZIP 244] `hashBlockCommitments` value, which commits to
+    /// the ZIP 221 chain history root and to the authorizing data of the block's
+    /// transactions.
+    ///
+    /// [ZIP 244]: https://zips.z.cash/zip-0244#block-header-changes
+    BlockCommitmentsHash([u8; 32]),
+}
+
 impl BlockHeaderData {
     pub fn freeze(self) -> io::Result<BlockHeader> {
         BlockHeader::from_data(self)
     }
+
+    /// Returns the `hashBlockCommitments` field of this header, interpreted according to
+    /// the consensus rules that are active at the given height.
+    pub fn commitments<P: consensus::Parameters>(
+        &self,
+        params: &P,
+        height: BlockHeight,
+    ) -> BlockCommitments {
+        let bytes = self.final_sapling_root;
+        if params.is_nu_active(NetworkUpgrade::Nu5, height) {
+            BlockCommitments::BlockCommitmentsHash(bytes)
+        } else if params.is_nu_active(NetworkUpgrade::Heartwood, height) {
+            BlockCommitments::ChainHistoryRoot(bytes)
+        } else if params.is_nu_active(NetworkUpgrade::Sapling, height) {
+            BlockCommitments::FinalSaplingRoot(bytes)
+        } else {
+            BlockCommitments::Reserved(bytes)
+        }
+    }
 }
 
 impl BlockHeader {
@@ -111,6 +199,19 @@ impl BlockHeader {
         self.hash
     }
 
+    /// Checks that the Equihash solution in this header is valid for the given parameters.
+    ///
+    /// The parameters used by each Zcash network can be obtained from [`equihash_params`].
+    /// This does not check that the header meets its difficulty target.
+    pub fn verify_solution(&self, params: equihash::Params) -> Result<(), equihash::Error> {
+        let mut data = vec![];
+        self.write(&mut data)
+            .expect("writing to a Vec<u8> cannot fail");
+
+        // The Equihash input is the header up to and excluding the nonce.
+        params.is_valid_solution(&data[..EQUIHASH_INPUT_LEN], &self.nonce, &self.solution)
+    }
+
     pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
         let version = reader.read_i32::<LittleEndian>()?;
 
@@ -157,9 +258,329 @@ impl BlockHeader {
     }
 }
 
+/// A Zcash block.
+pub struct Block {
+    header: BlockHeader,
+    transactions: Vec<Transaction>,
+}
+
+impl Block {
+    /// Constructs a block from its header and transactions.
+    ///
+    /// No checks are performed on the consistency of the header with the transactions.
+    pub fn from_parts(header: BlockHeader, transactions: Vec<Transaction>) -> Self {
+        Block {
+            header,
+            transactions,
+        }
+    }
+
+    /// Returns the hash of this block's header.
+    pub fn hash(&self) -> BlockHash {
+        self.header.hash()
+    }
+
+    /// Returns the header of this block.
+    pub fn header(&self) -> &BlockHeader {
+        &self.header
+    }
+
+    /// Returns the transactions in this block, starting with the coinbase transaction.
+    pub fn transactions(&self) -> &[Transaction] {
+        &self.transactions
+    }
+
+    /// Returns the header and transactions of this block.
+    pub fn into_parts(self) -> (BlockHeader, Vec<Transaction>) {
+        (self.header, self.transactions)
+    }
+
+    /// Returns the height of this block as encoded in the `scriptSig` of its coinbase
+    /// transaction, as required by [BIP 34].
+    ///
+    /// Returns `None` if the block does not begin with a coinbase transaction, or if its
+    /// `scriptSig` does not begin with a valid height.
+    ///
+    /// [BIP 34]: https://github.com/bitcoin/bips/blob/master/bip-0034.mediawiki
+    pub fn coinbase_height(&self) -> Option<BlockHeight> {
+        let bundle = self.transactions.first()?.transparent_bundle()?;
+        if !bundle.is_coinbase() {
+            return None;
+        }
+
+        match bundle.vin[0].script_sig.instructions().next()? {
+            Ok(Instruction::PushBytes(bytes)) if bytes.len() <= 4 => {
+                // Heights are encoded as minimally-encoded little-endian script numbers,
+                // and so are never negative.
+                if bytes.last().map_or(false, |b| b & 0x80 != 0) {
+                    return None;
+                }
+                let height = bytes
+                    .iter()
+                    .rev()
+                    .fold(0u32, |acc, b| (acc << 8) | u32::from(*b));
+                Some(BlockHeight::from_u32(height))
+            }
+            Ok(Instruction::Op(op))
+                if (OpCode::Op1 as u8..=OpCode::Op16 as u8).contains(&(op as u8)) =>
+            {
+                Some(BlockHeight::from_u32(u32::from(
+                    op as u8 - OpCode::Op1 as u8 + 1,
+                )))
+            }
+            _ => None,
+        }
+    }
+
+    /// Reads a block in its consensus serialization.
+    ///
+    /// `consensus_branch_id` is the consensus branch ID in effect at the height of the
+    /// block. It is used to interpret transaction versions that do not commit to a consensus
+    /// branch ID, in the same way as [`Transaction::read`].
+    pub fn read<R: Read>(mut reader: R, consensus_branch_id: BranchId) -> io::Result<Self> {
+        let header = BlockHeader::read(&mut reader)?;
+        let transactions =
+            Vector::read(&mut reader, |r| Transaction::read(r, consensus_branch_id))?;
+
+        Ok(Block {
+            header,
+            transactions,
+        })
+    }
+
+    /// Writes this block in its consensus serialization.
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        self.header.write(&mut writer)?;
+        Vector::write(&mut writer, &self.transactions, |w, tx| tx.write(w))
+    }
+
+    fn txid_leaves(&self) -> Vec<[u8; 32]> {
+        self.transactions
+            .iter()
+            .map(|tx| *tx.txid().as_ref())
+            .collect()
+    }
+
+    fn auth_data_leaves(&self) -> Vec<[u8; 32]> {
+        let mut leaves = self
+            .transactions
+            .iter()
+            .map(|tx| {
+                if tx.version().has_orchard() {
+                    *tx.auth_commitment().as_array()
+                } else {
+                    // Transactions prior to v5 have no authorizing data commitment.
+                    [0xff; 32]
+                }
+            })
+            .collect::<Vec<_>>();
+        leaves.resize(leaves.len().next_power_of_two(), [0; 32]);
+        leaves
+    }
+
+    /// Computes the root of the Merkle tree of transaction IDs in this block, which is
+    /// committed to by the `merkle_root` field of a valid block header.
+    pub fn merkle_root(&self) -> [u8; 32] {
+        merkle_root(self.txid_leaves(), txid_merkle_node)
+    }
+
+    /// Computes the [ZIP 244] `hashAuthDataRoot` of this block: the root of the Merkle tree
+    /// of transaction authorizing data commitments.
+    ///
+    /// [ZIP 244]: https://zips.z.cash/zip-0244#block-header-changes
+    pub fn auth_data_root(&self) -> [u8; 32] {
+        merkle_root(self.auth_data_leaves(), auth_data_merkle_node)
+    }
+
+    /// Returns the Merkle branch proving the inclusion of the transaction at the given index
+    /// in the tree of transaction IDs of this block, for use with [`verify_tx_inclusion`].
+    pub fn merkle_branch(&self, index: usize) -> Option<MerkleBranch> {
+        merkle_branch(self.txid_leaves(), index, txid_merkle_node)
+    }
+
+    /// Returns the Merkle branch proving the inclusion of the authorizing data commitment of
+    /// the transaction at the given index in the tree committed to by
+    /// [`Block::auth_data_root`], for use with [`verify_auth_data_inclusion`].
+    pub fn auth_data_branch(&self, index: usize) -> Option<MerkleBranch> {
+        if index >= self.transactions.len() {
+            return None;
+        }
+        merkle_branch(self.auth_data_leaves(), index, auth_data_merkle_node)
+    }
+}
+
+/// A Merkle branch, proving that a leaf is included at a given position in one of the
+/// Merkle trees committed to by a block header.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MerkleBranch {
+    position: u32,
+    siblings: Vec<[u8; 32]>,
+}
+
+impl MerkleBranch {
+    /// Constructs a Merkle branch from the position of its leaf within the tree, and the
+    /// sibling of each node on the path from the leaf to the root, starting with the sibling
+    /// of the leaf.
+    pub fn from_parts(position: u32, siblings: Vec<[u8; 32]>) -> Self {
+        MerkleBranch { position, siblings }
+    }
+
+    /// Returns the position of the leaf within the tree.
+    pub fn position(&self) -> u32 {
+        self.position
+    }
+
+    /// Returns the siblings of the nodes on the path from the leaf to the root.
+    pub fn siblings(&self) -> &[[u8; 32]] {
+        &self.siblings
+    }
+
+    /// Computes the root of the tree implied by this branch for the given leaf, or `None` if
+    /// the position of the leaf does not fit within a tree of the branch's depth.
+    fn root(&self, leaf: [u8; 32], node: fn(&[u8; 32], &[u8; 32]) -> [u8; 32]) -> Option<[u8; 32]> {
+        if self.siblings.len() < 32 && self.position >> self.siblings.len() != 0 {
+            return None;
+        }
+
+        Some(
+            self.siblings
+                .iter()
+                .enumerate()
+                .fold(leaf, |cur, (level, sibling)| {
+                    if (self.position >> level) & 1 == 0 {
+                        node(&cur, sibling)
+                    } else {
+                        node(sibling, &cur)
+                    }
+                }),
+        )
+    }
+}
+
+/// Verifies that the transaction with the given ID is included in the block with the given
+/// header, using a Merkle branch of the block's transaction ID tree.
+///
+/// This only establishes that the transaction is committed to by the header; callers must
+/// separately verify that the header is part of the chain they are following.
+pub fn verify_tx_inclusion(txid: &TxId, branch: &MerkleBranch, header: &BlockHeader) -> bool {
+    branch.root(*txid.as_ref(), txid_merkle_node) == Some(header.merkle_root)
+}
+
+/// Verifies that the given [ZIP 244] authorizing data commitment of a transaction is included
+/// in the block with the given header, using a Merkle branch of the block's authorizing data
+/// tree.
+///
+/// The header commits to the authorizing data root together with the [ZIP 221] chain history
+/// root for the block, which must be provided. This can only succeed for blocks at heights
+/// where NU5 is active; see [`BlockCommitments::BlockCommitmentsHash`].
+///
+/// [ZIP 244]: https://zips.z.cash/zip-0244
+/// [ZIP 221]: https://zips.z.cash/zip-0221
+pub fn verify_auth_data_inclusion(
+    auth_commitment: &[u8; 32],
+    branch: &MerkleBranch,
+    header: &BlockHeader,
+    chain_history_root: &[u8; 32],
+) -> bool {
+    branch
+        .root(*auth_commitment, auth_data_merkle_node)
+        .map_or(false, |auth_data_root| {
+            block_commitments_hash(chain_history_root, &auth_data_root) == header.final_sapling_root
+        })
+}
+
+/// Computes the [ZIP 244] `hashBlockCommitments` value from the chain history root and the
+/// authorizing data root of a block.
+///
+/// [ZIP 244]: https://zips.z.cash/zip-0244#block-header-changes
+pub fn block_commitments_hash(
+    chain_history_root: &[u8; 32],
+    auth_data_root: &[u8; 32],
+) -> [u8; 32] {
+    let mut h = blake2b_simd::Params::new()
+        .hash_length(32)
+        .personal(b"ZcashBlockCommit")
+        .to_state();
+    h.update(chain_history_root);
+    h.update(auth_data_root);
+    h.update(&[0; 32]);
+    *h.finalize().as_array()
+}
+
+fn txid_merkle_node(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut data = [0; 64];
+    data[..32].copy_from_slice(left);
+    data[32..].copy_from_slice(right);
+    Sha256::digest(Sha256::digest(data)).into()
+}
+
+fn auth_data_merkle_node(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut h = blake2b_simd::Params::new()
+        .hash_length(32)
+        .personal(b"ZcashAuthDatHash")
+        .to_state();
+    h.update(left);
+    h.update(right);
+    *h.finalize().as_array()
+}
+
+/// Computes the next level of a Merkle tree, duplicating the last node of a level with an
+/// odd number of nodes as in Bitcoin.
+fn merkle_parents(level: &[[u8; 32]], node: fn(&[u8; 32], &[u8; 32]) -> [u8; 32]) -> Vec<[u8; 32]> {
+    level
+        .chunks(2)
+        .map(|pair| node(&pair[0], pair.get(1).unwrap_or(&pair[0])))
+        .collect()
+}
+
+fn merkle_root(mut level: Vec<[u8; 32]>, node: fn(&[u8; 32], &[u8; 32]) -> [u8; 32]) -> [u8; 32] {
+    if level.is_empty() {
+        return [0; 32];
+    }
+    while level.len() > 1 {
+        level = merkle_parents(&level, node);
+    }
+    level[0]
+}
+
+fn merkle_branch(
+    mut level: Vec<[u8; 32]>,
+    index: usize,
+    node: fn(&[u8; 32], &[u8; 32]) -> [u8; 32],
+) -> Option<MerkleBranch> {
+    let position = u32::try_from(index).ok()?;
+    if index >= level.len() {
+        return None;
+    }
+
+    let mut siblings = vec![];
+    let mut i = index;
+    while level.len() > 1 {
+        siblings.push(*level.get(i ^ 1).unwrap_or(&level[i]));
+        level = merkle_parents(&level, node);
+        i >>= 1;
+    }
+
+    Some(MerkleBranch { position, siblings })
+}
+
 #[cfg(test)]
 mod tests {
-    use super::BlockHeader;
+    use super::{
+        block_commitments_hash, equihash_params, verify_auth_data_inclusion, verify_tx_inclusion,
+        Block, BlockCommitments, BlockHash, BlockHeader, BlockHeaderData, MerkleBranch,
+    };
+    use crate::{
+        consensus::{BlockHeight, BranchId, NetworkType, MAIN_NETWORK},
+        legacy::{script::OpCode, Script, TransparentAddress},
+        transaction::{
+            components::{
+                amount::NonNegativeAmount,
+                transparent::{self, OutPoint, TxIn, TxOut},
+            },
+            Transaction, TransactionData, TxVersion,
+        },
+    };
 
     const HEADER_MAINNET_415000: [u8; 1487] = [
         0x04, 0x00, 0x00, 0x00, 0x52, 0x74, 0xb4, 0x3b, 0x9e, 0x4a, 0xd8, 0xf4, 0x3e, 0x93, 0xf7,
@@ -275,4 +696,215 @@ mod tests {
         header.write(&mut encoded).unwrap();
         assert_eq!(&HEADER_MAINNET_415000[..], &encoded[..]);
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn block_hash_serde() {
+        let hash = BlockHeader::read(&HEADER_MAINNET_415000[..])
+            .unwrap()
+            .hash();
+        let json = serde_json::to_string(&hash).unwrap();
+        assert_eq!(
+            json,
+            "\"0000000001ab37793ce771262b2ffa082519aa3fe891250a1adb43baaf856168\""
+        );
+        assert_eq!(serde_json::from_str::<BlockHash>(&json).unwrap(), hash);
+        assert!(serde_json::from_str::<BlockHash>("\"0001ab\"").is_err());
+    }
+
+    #[test]
+    fn header_solution() {
+        let header = BlockHeader::read(&HEADER_MAINNET_415000[..]).unwrap();
+        header
+            .verify_solution(equihash_params(NetworkType::Main))
+            .unwrap();
+        assert!(header
+            .verify_solution(equihash_params(NetworkType::Regtest))
+            .is_err());
+
+        let mut data = HEADER_MAINNET_415000;
+        // Modify the timestamp.
+        data[100] ^= 1;
+        let header = BlockHeader::read(&data[..]).unwrap();
+        assert!(header
+            .verify_solution(equihash::Params::ZCASH_MAINNET)
+            .is_err());
+    }
+
+    /// Constructs a transaction with a single transparent input and output.
+    fn transparent_tx(
+        version: TxVersion,
+        consensus_branch_id: BranchId,
+        prevout: OutPoint,
+        script_sig: Script,
+    ) -> Transaction {
+        TransactionData::from_parts(
+            version,
+            consensus_branch_id,
+            0,
+            BlockHeight::from_u32(0),
+            Some(transparent::Bundle {
+                vin: vec![TxIn {
+                    prevout,
+                    script_sig,
+                    sequence: u32::MAX,
+                }],
+                vout: vec![TxOut {
+                    value: NonNegativeAmount::const_from_u64(1_250_000_000),
+                    script_pubkey: TransparentAddress::PublicKeyHash([7; 20]).script(),
+                }],
+                authorization: transparent::Authorized,
+            }),
+            None,
+            None,
+            None,
+        )
+        .freeze()
+        .unwrap()
+    }
+
+    #[test]
+    fn block_read_write() {
+        let coinbase = |script_sig: Script| {
+            transparent_tx(
+                TxVersion::Sapling,
+                BranchId::Sapling,
+                OutPoint::new([0; 32], u32::MAX),
+                script_sig,
+            )
+        };
+
+        // 415000 = 0x065518
+        let header = BlockHeader::read(&HEADER_MAINNET_415000[..]).unwrap();
+        let block = Block::from_parts(
+            header,
+            vec![coinbase(Script::default() << &[0x18, 0x55, 0x06][..])],
+        );
+        assert_eq!(block.coinbase_height(), Some(BlockHeight::from_u32(415000)));
+        assert_eq!(
+            block
+                .header()
+                .commitments(&MAIN_NETWORK, BlockHeight::from_u32(415000)),
+            BlockCommitments::FinalSaplingRoot(block.header().final_sapling_root)
+        );
+
+        let mut encoded = vec![];
+        block.write(&mut encoded).unwrap();
+        let decoded = Block::read(&encoded[..], BranchId::Sapling).unwrap();
+        assert_eq!(decoded.hash(), block.hash());
+        assert_eq!(decoded.transactions(), block.transactions());
+        assert_eq!(decoded.coinbase_height(), block.coinbase_height());
+
+        let mut reencoded = vec![];
+        decoded.write(&mut reencoded).unwrap();
+        assert_eq!(encoded, reencoded);
+
+        // Heights of up to 16 are encoded using small-integer opcodes.
+        let header = BlockHeader::read(&HEADER_MAINNET_415000[..]).unwrap();
+        let block = Block::from_parts(header, vec![coinbase(Script(vec![OpCode::Op5 as u8]))]);
+        assert_eq!(block.coinbase_height(), Some(BlockHeight::from_u32(5)));
+
+        // Negative heights are rejected.
+        let header = BlockHeader::read(&HEADER_MAINNET_415000[..]).unwrap();
+        let block = Block::from_parts(header, vec![coinbase(Script::default() << &[0x80][..])]);
+        assert_eq!(block.coinbase_height(), None);
+    }
+
+    #[test]
+    fn tx_inclusion() {
+        let header = |merkle_root, final_sapling_root| {
+            BlockHeaderData {
+                version: 4,
+                prev_block: BlockHash([0; 32]),
+                merkle_root,
+                final_sapling_root,
+                time: 0,
+                bits: 0,
+                nonce: [0; 32],
+                solution: vec![],
+            }
+            .freeze()
+            .unwrap()
+        };
+
+        let coinbase = transparent_tx(
+            TxVersion::Sapling,
+            BranchId::Sapling,
+            OutPoint::new([0; 32], u32::MAX),
+            Script::default() << &[0x01, 0x02][..],
+        );
+        let spends = (1..=2).map(|i| {
+            transparent_tx(
+                TxVersion::Zip225,
+                BranchId::Nu5,
+                OutPoint::new([i; 32], 0),
+                Script::default() << &[i][..],
+            )
+        });
+
+        // The Merkle root of a block containing a single transaction is its txid.
+        let block = Block::from_parts(
+            header([0; 32], [0; 32]),
+            vec![transparent_tx(
+                TxVersion::Sapling,
+                BranchId::Sapling,
+                OutPoint::new([0; 32], u32::MAX),
+                Script::default() << &[0x01, 0x02][..],
+            )],
+        );
+        assert_eq!(
+            &block.merkle_root(),
+            block.transactions()[0].txid().as_ref()
+        );
+
+        let transactions = std::iter::once(coinbase).chain(spends).collect::<Vec<_>>();
+        let unsealed = Block::from_parts(header([0; 32], [0; 32]), transactions);
+        let chain_history_root = [7; 32];
+        let header = header(
+            unsealed.merkle_root(),
+            block_commitments_hash(&chain_history_root, &unsealed.auth_data_root()),
+        );
+        let (_, transactions) = unsealed.into_parts();
+        let block = Block::from_parts(header, transactions);
+
+        for (i, tx) in block.transactions().iter().enumerate() {
+            let branch = block.merkle_branch(i).unwrap();
+            assert!(verify_tx_inclusion(&tx.txid(), &branch, block.header()));
+
+            // The branch does not prove inclusion at any other position.
+            let moved = MerkleBranch::from_parts(branch.position() ^ 1, branch.siblings().to_vec());
+            assert!(!verify_tx_inclusion(&tx.txid(), &moved, block.header()));
+            let moved = MerkleBranch::from_parts(
+                branch.position() + (1 << branch.siblings().len()),
+                branch.siblings().to_vec(),
+            );
+            assert!(!verify_tx_inclusion(&tx.txid(), &moved, block.header()));
+
+            let auth_commitment = if tx.version() == TxVersion::Zip225 {
+                *tx.auth_commitment().as_array()
+            } else {
+                [0xff; 32]
+            };
+            let branch = block.auth_data_branch(i).unwrap();
+            assert!(verify_auth_data_inclusion(
+                &auth_commitment,
+                &branch,
+                block.header(),
+                &chain_history_root,
+            ));
+            assert!(!verify_auth_data_inclusion(
+                &auth_commitment,
+                &branch,
+                block.header(),
+                &[0; 32],
+            ));
+        }
+
+        let other = block.transactions()[1].txid();
+        let branch = block.merkle_branch(2).unwrap();
+        assert!(!verify_tx_inclusion(&other, &branch, block.header()));
+
+        assert_eq!(block.merkle_branch(3), None);
+        assert_eq!(block.auth_data_branch(3), None);
+    }
 }
@@ -29,6 +29,11 @@ impl TransparentKeyScope {
             None
         }
     }
+
+    /// Returns the index of this scope at the `change` level of the BIP 44 key path.
+    pub fn index(&self) -> u32 {
+        self.0
+    }
 }
 
 impl From<zip32::Scope> for TransparentKeyScope {
@@ -205,6 +210,21 @@ impl AccountPubKey {
             .map(InternalIvk)
     }
 
+    /// Derives the BIP44 public key for the child path
+    /// `m/44'/<coin_type>'/<account>'/<scope>/<child_index>`.
+    ///
+    /// This is the public key corresponding to [`AccountPrivKey::derive_secret_key`].
+    pub fn derive_address_pubkey(
+        &self,
+        scope: TransparentKeyScope,
+        child_index: NonHardenedChildIndex,
+    ) -> Result<secp256k1::PublicKey, hdwallet::error::Error> {
+        self.0
+            .derive_public_key(scope.into())?
+            .derive_public_key(child_index.into())
+            .map(|k| k.public_key)
+    }
+
     /// Derives the internal ovk and external ovk corresponding to this
     /// transparent fvk. As specified in [ZIP 316][transparent-ovk].
     ///
@@ -393,6 +413,9 @@ mod tests {
 
     use super::AccountPubKey;
     use super::NonHardenedChildIndex;
+    use super::{AccountPrivKey, TransparentKeyScope};
+    use crate::consensus::MAIN_NETWORK;
+    use zip32::{AccountId, Scope};
 
     #[test]
     fn check_ovk_test_vectors() {
@@ -640,6 +663,26 @@ mod tests {
         }
     }
 
+    #[test]
+    fn derive_address_pubkey_matches_secret_key() {
+        let secp = secp256k1::Secp256k1::new();
+        let account_sk =
+            AccountPrivKey::from_seed(&MAIN_NETWORK, &[0; 32], AccountId::ZERO).unwrap();
+        let account_pk = account_sk.to_account_pubkey();
+
+        for scope in [
+            TransparentKeyScope::from(Scope::External),
+            TransparentKeyScope::from(Scope::Internal),
+        ] {
+            let index = NonHardenedChildIndex::from_index(5).unwrap();
+            let sk = account_sk.derive_secret_key(scope, index).unwrap();
+            assert_eq!(
+                account_pk.derive_address_pubkey(scope, index).unwrap(),
+                secp256k1::PublicKey::from_secret_key(&secp, &sk),
+            );
+        }
+    }
+
     #[test]
     fn nonhardened_indexes_accepted() {
         assert_eq!(0, NonHardenedChildIndex::from_index(0).unwrap().index());
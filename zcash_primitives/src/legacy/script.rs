@@ -0,0 +1,1059 @@
+//! Parsing, classification, and construction of transparent scripts.
+
+use byteorder::{ReadBytesExt, WriteBytesExt};
+
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::ops::Shl;
+
+use zcash_encoding::Vector;
+
+use super::TransparentAddress;
+
+/// Defined script opcodes.
+///
+/// Most of the opcodes are unused by this crate, but we define them so that the alternate
+/// `Debug` impl for [`Script`] renders correctly for unexpected scripts, and so that
+/// callers can construct arbitrary scripts using the `<<` operator on [`Script`].
+///
+/// The single-byte pushes `0x01..=0x4b` are not opcodes in their own right; they are
+/// represented by [`Instruction::PushBytes`] when iterating over a script.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OpCode {
+    // push value
+    Op0 = 0x00, // False
+    PushData1 = 0x4c,
+    PushData2 = 0x4d,
+    PushData4 = 0x4e,
+    Negative1 = 0x4f,
+    Reserved = 0x50,
+    Op1 = 0x51, // True
+    Op2 = 0x52,
+    Op3 = 0x53,
+    Op4 = 0x54,
+    Op5 = 0x55,
+    Op6 = 0x56,
+    Op7 = 0x57,
+    Op8 = 0x58,
+    Op9 = 0x59,
+    Op10 = 0x5a,
+    Op11 = 0x5b,
+    Op12 = 0x5c,
+    Op13 = 0x5d,
+    Op14 = 0x5e,
+    Op15 = 0x5f,
+    Op16 = 0x60,
+
+    // control
+    Nop = 0x61,
+    Ver = 0x62,
+    If = 0x63,
+    NotIf = 0x64,
+    VerIf = 0x65,
+    VerNotIf = 0x66,
+    Else = 0x67,
+    EndIf = 0x68,
+    Verify = 0x69,
+    Return = 0x6a,
+
+    // stack ops
+    ToAltStack = 0x6b,
+    FromAltStack = 0x6c,
+    Drop2 = 0x6d,
+    Dup2 = 0x6e,
+    Dup3 = 0x6f,
+    Over2 = 0x70,
+    Rot2 = 0x71,
+    Swap2 = 0x72,
+    IfDup = 0x73,
+    Depth = 0x74,
+    Drop = 0x75,
+    Dup = 0x76,
+    Nip = 0x77,
+    Over = 0x78,
+    Pick = 0x79,
+    Roll = 0x7a,
+    Rot = 0x7b,
+    Swap = 0x7c,
+    Tuck = 0x7d,
+
+    // splice ops
+    Cat = 0x7e,    // Disabled
+    Substr = 0x7f, // Disabled
+    Left = 0x80,   // Disabled
+    Right = 0x81,  // Disabled
+    Size = 0x82,
+
+    // bit logic
+    Invert = 0x83, // Disabled
+    And = 0x84,    // Disabled
+    Or = 0x85,     // Disabled
+    Xor = 0x86,    // Disabled
+    Equal = 0x87,
+    EqualVerify = 0x88,
+    Reserved1 = 0x89,
+    Reserved2 = 0x8a,
+
+    // numeric
+    Add1 = 0x8b,
+    Sub1 = 0x8c,
+    Mul2 = 0x8d, // Disabled
+    Div2 = 0x8e, // Disabled
+    Negate = 0x8f,
+    Abs = 0x90,
+    Not = 0x91,
+    NotEqual0 = 0x92,
+
+    Add = 0x93,
+    Sub = 0x94,
+    Mul = 0x95,    // Disabled
+    Div = 0x96,    // Disabled
+    Mod = 0x97,    // Disabled
+    LShift = 0x98, // Disabled
+    RShift = 0x99, // Disabled
+
+    BoolAnd = 0x9a,
+    BoolOr = 0x9b,
+    NumEqual = 0x9c,
+    NumEqualVerify = 0x9d,
+    NumNotEqual = 0x9e,
+    LessThan = 0x9f,
+    GreaterThan = 0xa0,
+    LessThanOrEqual = 0xa1,
+    GreaterThanOrEqual = 0xa2,
+    Min = 0xa3,
+    Max = 0xa4,
+
+    Within = 0xa5,
+
+    // crypto
+    Ripemd160 = 0xa6,
+    Sha1 = 0xa7,
+    Sha256 = 0xa8,
+    Hash160 = 0xa9,
+    Hash256 = 0xaa,
+    CodeSeparator = 0xab, // Disabled
+    CheckSig = 0xac,
+    CheckSigVerify = 0xad,
+    CheckMultisig = 0xae,
+    CheckMultisigVerify = 0xaf,
+
+    // expansion
+    Nop1 = 0xb0,
+    CheckLockTimeVerify = 0xb1,
+    Nop3 = 0xb2,
+    Nop4 = 0xb3,
+    Nop5 = 0xb4,
+    Nop6 = 0xb5,
+    Nop7 = 0xb6,
+    Nop8 = 0xb7,
+    Nop9 = 0xb8,
+    Nop10 = 0xb9,
+
+    InvalidOpCode = 0xff,
+}
+
+impl OpCode {
+    /// Parses an opcode from its byte encoding, returning `None` if the byte is not a
+    /// defined opcode.
+    pub fn parse(b: u8) -> Option<Self> {
+        match b {
+            0x00 => Some(OpCode::Op0),
+            0x4c => Some(OpCode::PushData1),
+            0x4d => Some(OpCode::PushData2),
+            0x4e => Some(OpCode::PushData4),
+            0x4f => Some(OpCode::Negative1),
+            0x50 => Some(OpCode::Reserved),
+            0x51 => Some(OpCode::Op1),
+            0x52 => Some(OpCode::Op2),
+            0x53 => Some(OpCode::Op3),
+            0x54 => Some(OpCode::Op4),
+            0x55 => Some(OpCode::Op5),
+            0x56 => Some(OpCode::Op6),
+            0x57 => Some(OpCode::Op7),
+            0x58 => Some(OpCode::Op8),
+            0x59 => Some(OpCode::Op9),
+            0x5a => Some(OpCode::Op10),
+            0x5b => Some(OpCode::Op11),
+            0x5c => Some(OpCode::Op12),
+            0x5d => Some(OpCode::Op13),
+            0x5e => Some(OpCode::Op14),
+            0x5f => Some(OpCode::Op15),
+            0x60 => Some(OpCode::Op16),
+            0x61 => Some(OpCode::Nop),
+            0x62 => Some(OpCode::Ver),
+            0x63 => Some(OpCode::If),
+            0x64 => Some(OpCode::NotIf),
+            0x65 => Some(OpCode::VerIf),
+            0x66 => Some(OpCode::VerNotIf),
+            0x67 => Some(OpCode::Else),
+            0x68 => Some(OpCode::EndIf),
+            0x69 => Some(OpCode::Verify),
+            0x6a => Some(OpCode::Return),
+            0x6b => Some(OpCode::ToAltStack),
+            0x6c => Some(OpCode::FromAltStack),
+            0x6d => Some(OpCode::Drop2),
+            0x6e => Some(OpCode::Dup2),
+            0x6f => Some(OpCode::Dup3),
+            0x70 => Some(OpCode::Over2),
+            0x71 => Some(OpCode::Rot2),
+            0x72 => Some(OpCode::Swap2),
+            0x73 => Some(OpCode::IfDup),
+            0x74 => Some(OpCode::Depth),
+            0x75 => Some(OpCode::Drop),
+            0x76 => Some(OpCode::Dup),
+            0x77 => Some(OpCode::Nip),
+            0x78 => Some(OpCode::Over),
+            0x79 => Some(OpCode::Pick),
+            0x7a => Some(OpCode::Roll),
+            0x7b => Some(OpCode::Rot),
+            0x7c => Some(OpCode::Swap),
+            0x7d => Some(OpCode::Tuck),
+            0x7e => Some(OpCode::Cat),
+            0x7f => Some(OpCode::Substr),
+            0x80 => Some(OpCode::Left),
+            0x81 => Some(OpCode::Right),
+            0x82 => Some(OpCode::Size),
+            0x83 => Some(OpCode::Invert),
+            0x84 => Some(OpCode::And),
+            0x85 => Some(OpCode::Or),
+            0x86 => Some(OpCode::Xor),
+            0x87 => Some(OpCode::Equal),
+            0x88 => Some(OpCode::EqualVerify),
+            0x89 => Some(OpCode::Reserved1),
+            0x8a => Some(OpCode::Reserved2),
+            0x8b => Some(OpCode::Add1),
+            0x8c => Some(OpCode::Sub1),
+            0x8d => Some(OpCode::Mul2),
+            0x8e => Some(OpCode::Div2),
+            0x8f => Some(OpCode::Negate),
+            0x90 => Some(OpCode::Abs),
+            0x91 => Some(OpCode::Not),
+            0x92 => Some(OpCode::NotEqual0),
+            0x93 => Some(OpCode::Add),
+            0x94 => Some(OpCode::Sub),
+            0x95 => Some(OpCode::Mul),
+            0x96 => Some(OpCode::Div),
+            0x97 => Some(OpCode::Mod),
+            0x98 => Some(OpCode::LShift),
+            0x99 => Some(OpCode::RShift),
+            0x9a => Some(OpCode::BoolAnd),
+            0x9b => Some(OpCode::BoolOr),
+            0x9c => Some(OpCode::NumEqual),
+            0x9d => Some(OpCode::NumEqualVerify),
+            0x9e => Some(OpCode::NumNotEqual),
+            0x9f => Some(OpCode::LessThan),
+            0xa0 => Some(OpCode::GreaterThan),
+            0xa1 => Some(OpCode::LessThanOrEqual),
+            0xa2 => Some(OpCode::GreaterThanOrEqual),
+            0xa3 => Some(OpCode::Min),
+            0xa4 => Some(OpCode::Max),
+            0xa5 => Some(OpCode::Within),
+            0xa6 => Some(OpCode::Ripemd160),
+            0xa7 => Some(OpCode::Sha1),
+            0xa8 => Some(OpCode::Sha256),
+            0xa9 => Some(OpCode::Hash160),
+            0xaa => Some(OpCode::Hash256),
+            0xab => Some(OpCode::CodeSeparator),
+            0xac => Some(OpCode::CheckSig),
+            0xad => Some(OpCode::CheckSigVerify),
+            0xae => Some(OpCode::CheckMultisig),
+            0xaf => Some(OpCode::CheckMultisigVerify),
+            0xb0 => Some(OpCode::Nop1),
+            0xb1 => Some(OpCode::CheckLockTimeVerify),
+            0xb2 => Some(OpCode::Nop3),
+            0xb3 => Some(OpCode::Nop4),
+            0xb4 => Some(OpCode::Nop5),
+            0xb5 => Some(OpCode::Nop6),
+            0xb6 => Some(OpCode::Nop7),
+            0xb7 => Some(OpCode::Nop8),
+            0xb8 => Some(OpCode::Nop9),
+            0xb9 => Some(OpCode::Nop10),
+            0xff => Some(OpCode::InvalidOpCode),
+            _ => None,
+        }
+    }
+
+    /// Returns the opcode that pushes the given small integer, `OP_1` through `OP_16`.
+    ///
+    /// Panics if `n` is not in the range `1..=16`.
+    pub(crate) fn small_int(n: usize) -> Self {
+        assert!((1..=16).contains(&n));
+        OpCode::parse(OpCode::Op1 as u8 + (n - 1) as u8).expect("OP_1..=OP_16 are defined")
+    }
+
+    /// Returns the integer pushed by this opcode, if it is one of `OP_1` through `OP_16`.
+    pub(crate) fn small_int_value(self) -> Option<usize> {
+        let b = self as u8;
+        (OpCode::Op1 as u8..=OpCode::Op16 as u8)
+            .contains(&b)
+            .then(|| (b - OpCode::Op1 as u8) as usize + 1)
+    }
+}
+
+/// A serialized script, used inside transparent inputs and outputs of a transaction.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Script(pub Vec<u8>);
+
+impl fmt::Debug for Script {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        struct ScriptPrinter<'s>(&'s [u8]);
+        impl<'s> fmt::Debug for ScriptPrinter<'s> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                let mut l = f.debug_list();
+                let mut unknown: Option<String> = None;
+                for b in self.0 {
+                    if let Some(opcode) = OpCode::parse(*b) {
+                        if let Some(s) = unknown.take() {
+                            l.entry(&s);
+                        }
+                        l.entry(&opcode);
+                    } else {
+                        let encoded = format!("{:02x}", b);
+                        if let Some(s) = &mut unknown {
+                            s.push_str(&encoded);
+                        } else {
+                            unknown = Some(encoded);
+                        }
+                    }
+                }
+                l.finish()
+            }
+        }
+
+        if f.alternate() {
+            f.debug_tuple("Script")
+                .field(&ScriptPrinter(&self.0))
+                .finish()
+        } else {
+            f.debug_tuple("Script")
+                .field(&hex::encode(&self.0))
+                .finish()
+        }
+    }
+}
+
+impl Script {
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        let script = Vector::read(&mut reader, |r| r.read_u8())?;
+        Ok(Script(script))
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        Vector::write(&mut writer, &self.0, |w, e| w.write_u8(*e))
+    }
+
+    /// Returns an iterator over the instructions in this script.
+    pub fn instructions(&self) -> Instructions<'_> {
+        Instructions {
+            script: &self.0,
+            offset: 0,
+        }
+    }
+
+    /// Returns `true` if this script consists solely of data pushes.
+    ///
+    /// As in `zcashd`, any opcode up to and including `OP_16` counts as a push, so `OP_1NEGATE`
+    /// and `OP_1` through `OP_16` are push-only. Scripts that fail to parse are not considered
+    /// push-only.
+    pub fn is_push_only(&self) -> bool {
+        self.instructions().all(|i| match i {
+            Ok(Instruction::PushBytes(_)) => true,
+            Ok(Instruction::Op(op)) => op as u8 <= OpCode::Op16 as u8,
+            _ => false,
+        })
+    }
+
+    /// Constructs a provably unspendable `OP_RETURN <data>` script, which can be used to
+    /// commit to arbitrary data in a transparent output.
+    ///
+    /// Note that scripts with more than [`MAX_NULL_DATA_SCRIPT_SIZE`] bytes will not be
+    /// relayed by nodes.
+    pub fn null_data(data: &[u8]) -> Self {
+        Script::default() << OpCode::Return << data
+    }
+
+    /// Returns the data pushed by this script, if it is a null-data script.
+    ///
+    /// If the script contains several data pushes after `OP_RETURN`, their contents are
+    /// concatenated. The small integer pushes `OP_1NEGATE` and `OP_1` through `OP_16`
+    /// contribute the minimal encoding of the value they push.
+    pub fn null_data_payload(&self) -> Option<Vec<u8>> {
+        match self.kind() {
+            ScriptKind::NullData => Some(
+                self.instructions()
+                    .skip(1)
+                    .flat_map(|i| match i {
+                        Ok(Instruction::PushBytes(data)) => data.to_vec(),
+                        Ok(Instruction::Op(OpCode::Negative1)) => vec![0x81],
+                        Ok(Instruction::Op(op)) => op
+                            .small_int_value()
+                            .map(|n| vec![n as u8])
+                            .unwrap_or_default(),
+                        _ => unreachable!("checked by ScriptKind::NullData"),
+                    })
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+
+    /// Constructs an `m`-of-`n` multisig script,
+    /// `OP_m <pubkey_1> ... <pubkey_n> OP_n OP_CHECKMULTISIG`, requiring signatures from
+    /// `threshold` of the given compressed public keys.
+    ///
+    /// This is intended for use as the redeem script of a P2SH output. Returns `None` if
+    /// `threshold` is zero or greater than the number of keys, if more than 16 keys are
+    /// given, or if the resulting script would exceed [`MAX_SCRIPT_ELEMENT_SIZE`] and so
+    /// could not be pushed in a P2SH `scriptSig`.
+    pub fn multisig(threshold: usize, pubkeys: &[[u8; 33]]) -> Option<Self> {
+        if threshold == 0 || threshold > pubkeys.len() || pubkeys.len() > 16 {
+            return None;
+        }
+
+        let script = pubkeys.iter().fold(
+            Script::default() << OpCode::small_int(threshold),
+            |s, pk| s << &pk[..],
+        ) << OpCode::small_int(pubkeys.len())
+            << OpCode::CheckMultisig;
+
+        (script.0.len() <= MAX_SCRIPT_ELEMENT_SIZE).then_some(script)
+    }
+
+    /// Returns the signature threshold and the public keys of this script, if it is an
+    /// `m`-of-`n` multisig script of the form constructed by [`Script::multisig`].
+    pub fn multisig_keys(&self) -> Option<(usize, Vec<[u8; 33]>)> {
+        let instructions = self.instructions().collect::<Result<Vec<_>, _>>().ok()?;
+        match &instructions[..] {
+            [Instruction::Op(m), keys @ .., Instruction::Op(n), Instruction::Op(OpCode::CheckMultisig)] =>
+            {
+                let threshold = m.small_int_value()?;
+                let keys = keys
+                    .iter()
+                    .map(|i| match i {
+                        Instruction::PushBytes(key) => <[u8; 33]>::try_from(*key).ok(),
+                        _ => None,
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+
+                (threshold > 0 && threshold <= keys.len() && n.small_int_value()? == keys.len())
+                    .then_some((threshold, keys))
+            }
+            _ => None,
+        }
+    }
+
+    /// Constructs the redeem script of a hash time-locked contract with the given terms:
+    ///
+    /// ```text
+    /// OP_IF
+    ///     OP_SIZE 32 OP_EQUALVERIFY OP_SHA256 <payment_hash> OP_EQUALVERIFY
+    ///     OP_DUP OP_HASH160 <recipient>
+    /// OP_ELSE
+    ///     <lock_time> OP_CHECKLOCKTIMEVERIFY OP_DROP
+    ///     OP_DUP OP_HASH160 <refund>
+    /// OP_ENDIF
+    /// OP_EQUALVERIFY OP_CHECKSIG
+    /// ```
+    ///
+    /// This is intended for use as the redeem script of a P2SH output.
+    pub fn htlc(htlc: &Htlc) -> Self {
+        let script = Script::default()
+            << OpCode::If
+            << OpCode::Size
+            << &[HTLC_PREIMAGE_SIZE as u8][..]
+            << OpCode::EqualVerify
+            << OpCode::Sha256
+            << &htlc.payment_hash[..]
+            << OpCode::EqualVerify
+            << OpCode::Dup
+            << OpCode::Hash160
+            << &htlc.recipient[..]
+            << OpCode::Else;
+        let script = match htlc.lock_time {
+            0 => script << &[][..],
+            n @ 1..=16 => script << OpCode::small_int(n as usize),
+            n => script << &script_num(n)[..],
+        };
+        script
+            << OpCode::CheckLockTimeVerify
+            << OpCode::Drop
+            << OpCode::Dup
+            << OpCode::Hash160
+            << &htlc.refund[..]
+            << OpCode::EndIf
+            << OpCode::EqualVerify
+            << OpCode::CheckSig
+    }
+
+    /// Returns the terms of this script, if it is a hash time-locked contract of the form
+    /// constructed by [`Script::htlc`].
+    pub fn htlc_terms(&self) -> Option<Htlc> {
+        use Instruction::{Op, PushBytes};
+
+        let instructions = self.instructions().collect::<Result<Vec<_>, _>>().ok()?;
+        match &instructions[..] {
+            [Op(OpCode::If), Op(OpCode::Size), PushBytes(_), Op(OpCode::EqualVerify), Op(OpCode::Sha256), PushBytes(payment_hash), Op(OpCode::EqualVerify), Op(OpCode::Dup), Op(OpCode::Hash160), PushBytes(recipient), Op(OpCode::Else), lock_time, Op(OpCode::CheckLockTimeVerify), Op(OpCode::Drop), Op(OpCode::Dup), Op(OpCode::Hash160), PushBytes(refund), Op(OpCode::EndIf), Op(OpCode::EqualVerify), Op(OpCode::CheckSig)] =>
+            {
+                let lock_time = match lock_time {
+                    PushBytes(data) => parse_script_num(data)?,
+                    Op(op) => op.small_int_value()? as u32,
+                    Instruction::Unknown(_) => return None,
+                };
+                let htlc = Htlc::new(
+                    <[u8; 32]>::try_from(*payment_hash).ok()?,
+                    <[u8; 20]>::try_from(*recipient).ok()?,
+                    <[u8; 20]>::try_from(*refund).ok()?,
+                    lock_time,
+                );
+
+                // Reject non-canonical encodings, such as a different preimage size.
+                (&Script::htlc(&htlc) == self).then_some(htlc)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the terms of the contract spent by this script, and the branch through which
+    /// it was spent, if this is a `scriptSig` that spends a P2SH hash time-locked contract.
+    ///
+    /// The signature, and the preimage of a claim, are not checked.
+    pub fn htlc_spend(&self) -> Option<(Htlc, HtlcSpend)> {
+        use Instruction::{Op, PushBytes};
+
+        let instructions = self.instructions().collect::<Result<Vec<_>, _>>().ok()?;
+        let (spend, redeem_script) = match &instructions[..] {
+            [PushBytes(_), PushBytes(_), PushBytes(preimage), Op(OpCode::Op1), PushBytes(redeem_script)] => {
+                (
+                    HtlcSpend::Claim(<[u8; 32]>::try_from(*preimage).ok()?),
+                    redeem_script,
+                )
+            }
+            [PushBytes(_), PushBytes(_), PushBytes([]), PushBytes(redeem_script)] => {
+                (HtlcSpend::Refund, redeem_script)
+            }
+            _ => return None,
+        };
+
+        Script(redeem_script.to_vec())
+            .htlc_terms()
+            .map(|htlc| (htlc, spend))
+    }
+
+    /// Classifies this script as one of the standard `scriptPubKey` forms.
+    pub fn kind(&self) -> ScriptKind {
+        if self.0.len() == 25
+            && self.0[0..3] == [OpCode::Dup as u8, OpCode::Hash160 as u8, 0x14]
+            && self.0[23..25] == [OpCode::EqualVerify as u8, OpCode::CheckSig as u8]
+        {
+            let mut hash = [0; 20];
+            hash.copy_from_slice(&self.0[3..23]);
+            ScriptKind::PubKeyHash(hash)
+        } else if self.0.len() == 23
+            && self.0[0..2] == [OpCode::Hash160 as u8, 0x14]
+            && self.0[22] == OpCode::Equal as u8
+        {
+            let mut hash = [0; 20];
+            hash.copy_from_slice(&self.0[2..22]);
+            ScriptKind::ScriptHash(hash)
+        } else if self.0.first() == Some(&(OpCode::Return as u8))
+            && Script(self.0[1..].to_vec()).is_push_only()
+        {
+            ScriptKind::NullData
+        } else {
+            ScriptKind::Nonstandard
+        }
+    }
+
+    /// Returns the address that this Script contains, if any.
+    pub fn address(&self) -> Option<TransparentAddress> {
+        match self.kind() {
+            ScriptKind::PubKeyHash(hash) => Some(TransparentAddress::PublicKeyHash(hash)),
+            ScriptKind::ScriptHash(hash) => Some(TransparentAddress::ScriptHash(hash)),
+            ScriptKind::NullData | ScriptKind::Nonstandard => None,
+        }
+    }
+}
+
+impl Shl<OpCode> for Script {
+    type Output = Self;
+
+    fn shl(mut self, rhs: OpCode) -> Self {
+        self.0.push(rhs as u8);
+        self
+    }
+}
+
+impl Shl<&[u8]> for Script {
+    type Output = Self;
+
+    fn shl(mut self, data: &[u8]) -> Self {
+        if data.len() < OpCode::PushData1 as usize {
+            self.0.push(data.len() as u8);
+        } else if data.len() <= 0xff {
+            self.0.push(OpCode::PushData1 as u8);
+            self.0.push(data.len() as u8);
+        } else if data.len() <= 0xffff {
+            self.0.push(OpCode::PushData2 as u8);
+            self.0.extend((data.len() as u16).to_le_bytes());
+        } else {
+            self.0.push(OpCode::PushData4 as u8);
+            self.0.extend((data.len() as u32).to_le_bytes());
+        }
+        self.0.extend(data);
+        self
+    }
+}
+
+/// The maximum size in bytes of a null-data `scriptPubKey` (including the `OP_RETURN`
+/// and pushdata opcodes) that will be relayed by `zcashd` under its default policy.
+pub const MAX_NULL_DATA_SCRIPT_SIZE: usize = 223;
+
+/// The maximum size in bytes of a single data push. This bounds the size of a P2SH
+/// redeem script, which must be pushed in full by the spending `scriptSig`.
+pub const MAX_SCRIPT_ELEMENT_SIZE: usize = 520;
+
+/// The size in bytes of the preimage that unlocks a hash time-locked contract.
+pub const HTLC_PREIMAGE_SIZE: usize = 32;
+
+/// The terms of a hash time-locked contract (HTLC), as used to implement atomic swaps.
+///
+/// Coins sent to the P2SH address of the contract's redeem script (see [`Script::htlc`])
+/// can be claimed by the holder of the `recipient` key upon revealing a preimage of
+/// `payment_hash`, or refunded to the holder of the `refund` key once the chain has
+/// reached `lock_time`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Htlc {
+    payment_hash: [u8; 32],
+    recipient: [u8; 20],
+    refund: [u8; 20],
+    lock_time: u32,
+}
+
+impl Htlc {
+    /// Constructs the terms of a hash time-locked contract.
+    ///
+    /// - `payment_hash` is the SHA-256 hash of the [`HTLC_PREIMAGE_SIZE`]-byte preimage.
+    /// - `recipient` and `refund` are the hashes of the public keys that may claim the
+    ///   coins and be refunded the coins respectively, as used in P2PKH addresses.
+    /// - `lock_time` is the block height or Unix timestamp (following the interpretation
+    ///   of a transaction's `nLockTime` field) from which the coins may be refunded.
+    pub fn new(
+        payment_hash: [u8; 32],
+        recipient: [u8; 20],
+        refund: [u8; 20],
+        lock_time: u32,
+    ) -> Self {
+        Htlc {
+            payment_hash,
+            recipient,
+            refund,
+            lock_time,
+        }
+    }
+
+    /// Returns the SHA-256 hash of the preimage that unlocks the contract.
+    pub fn payment_hash(&self) -> &[u8; 32] {
+        &self.payment_hash
+    }
+
+    /// Returns the hash of the public key that may claim the coins.
+    pub fn recipient(&self) -> &[u8; 20] {
+        &self.recipient
+    }
+
+    /// Returns the hash of the public key to which the coins may be refunded.
+    pub fn refund(&self) -> &[u8; 20] {
+        &self.refund
+    }
+
+    /// Returns the `nLockTime` value from which the coins may be refunded.
+    pub fn lock_time(&self) -> u32 {
+        self.lock_time
+    }
+
+    /// Returns whether `preimage` unlocks the contract.
+    pub fn is_preimage(&self, preimage: &[u8; HTLC_PREIMAGE_SIZE]) -> bool {
+        use sha2::{Digest, Sha256};
+
+        Sha256::digest(preimage)[..] == self.payment_hash[..]
+    }
+
+    /// Returns the P2SH address to which coins locked by this contract are sent.
+    #[cfg(feature = "transparent-inputs")]
+    pub fn address(&self) -> TransparentAddress {
+        TransparentAddress::from_redeem_script(&Script::htlc(self))
+    }
+}
+
+/// The branch of a hash time-locked contract through which it is spent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HtlcSpend {
+    /// The recipient claims the coins by revealing the given preimage.
+    Claim([u8; HTLC_PREIMAGE_SIZE]),
+    /// The coins are refunded after the contract's lock time.
+    Refund,
+}
+
+/// Encodes a non-negative integer as a minimally-encoded script number.
+fn script_num(n: u32) -> Vec<u8> {
+    let mut bytes = n.to_le_bytes().to_vec();
+    while bytes.last() == Some(&0) {
+        bytes.pop();
+    }
+    // The most significant bit is the sign bit.
+    if bytes.last().map_or(false, |b| b & 0x80 != 0) {
+        bytes.push(0);
+    }
+    bytes
+}
+
+/// Decodes a non-negative script number that fits in a `u32`, as accepted by
+/// `OP_CHECKLOCKTIMEVERIFY` (which permits operands of up to five bytes).
+fn parse_script_num(data: &[u8]) -> Option<u32> {
+    if data.len() > 5 || data.last().map_or(false, |b| b & 0x80 != 0) {
+        return None;
+    }
+    let value = data
+        .iter()
+        .rev()
+        .fold(0u64, |acc, b| (acc << 8) | u64::from(*b));
+    u32::try_from(value).ok()
+}
+
+/// The standard forms of `scriptPubKey` that can be recognised by [`Script::kind`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScriptKind {
+    /// A P2PKH script, `OP_DUP OP_HASH160 <pubkey hash> OP_EQUALVERIFY OP_CHECKSIG`.
+    PubKeyHash([u8; 20]),
+    /// A P2SH script, `OP_HASH160 <script hash> OP_EQUAL`.
+    ScriptHash([u8; 20]),
+    /// A provably unspendable data-carrier script, `OP_RETURN` followed only by data
+    /// pushes.
+    NullData,
+    /// Any script that does not match one of the standard forms above.
+    Nonstandard,
+}
+
+/// A single parsed element of a [`Script`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Instruction<'a> {
+    /// A data push. `OP_0` is represented as a push of the empty byte string.
+    PushBytes(&'a [u8]),
+    /// A defined opcode other than a data push.
+    Op(OpCode),
+    /// A byte that does not correspond to any defined opcode.
+    Unknown(u8),
+}
+
+/// Errors that can occur while iterating over the instructions of a [`Script`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The data push starting at the given offset extends past the end of the script.
+    TruncatedPush { offset: usize },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::TruncatedPush { offset } => write!(
+                f,
+                "Data push at offset {} extends past the end of the script",
+                offset
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// An iterator over the instructions of a [`Script`], created by [`Script::instructions`].
+///
+/// Iteration stops after the first error.
+#[derive(Clone, Debug)]
+pub struct Instructions<'a> {
+    script: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Instructions<'a> {
+    fn read_push(&mut self, len_bytes: usize) -> Result<Instruction<'a>, Error> {
+        let start = self.offset;
+        let err = Error::TruncatedPush { offset: start };
+
+        let data_start = start + 1 + len_bytes;
+        let len = match self.script.get(start + 1..data_start).ok_or(err)? {
+            // Opcodes 0x01..=0x4b push that many bytes.
+            [] => self.script[start] as usize,
+            [l] => *l as usize,
+            [l0, l1] => u16::from_le_bytes([*l0, *l1]) as usize,
+            [l0, l1, l2, l3] => u32::from_le_bytes([*l0, *l1, *l2, *l3]) as usize,
+            _ => unreachable!(),
+        };
+        let data_end = data_start.checked_add(len).ok_or(err)?;
+        let data = self.script.get(data_start..data_end).ok_or(err)?;
+
+        self.offset = data_end;
+        Ok(Instruction::PushBytes(data))
+    }
+}
+
+impl<'a> Iterator for Instructions<'a> {
+    type Item = Result<Instruction<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let b = *self.script.get(self.offset)?;
+        let res = match b {
+            0x00 => {
+                self.offset += 1;
+                Ok(Instruction::PushBytes(&[]))
+            }
+            0x01..=0x4b => self.read_push(0),
+            0x4c => self.read_push(1),
+            0x4d => self.read_push(2),
+            0x4e => self.read_push(4),
+            _ => {
+                self.offset += 1;
+                Ok(OpCode::parse(b).map_or(Instruction::Unknown(b), Instruction::Op))
+            }
+        };
+
+        if res.is_err() {
+            // Stop iterating after an error.
+            self.offset = self.script.len();
+        }
+        Some(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Error, Htlc, HtlcSpend, Instruction, OpCode, Script, ScriptKind};
+    use crate::legacy::TransparentAddress;
+
+    #[test]
+    fn script_opcode() {
+        {
+            let script = Script::default() << OpCode::PushData1;
+            assert_eq!(&script.0, &[OpCode::PushData1 as u8]);
+        }
+    }
+
+    #[test]
+    fn script_pushdata() {
+        {
+            let script = Script::default() << &[1, 2, 3, 4][..];
+            assert_eq!(&script.0, &[4, 1, 2, 3, 4]);
+        }
+
+        {
+            let short_data = vec![2; 100];
+            let script = Script::default() << &short_data[..];
+            assert_eq!(script.0[0], OpCode::PushData1 as u8);
+            assert_eq!(script.0[1] as usize, 100);
+            assert_eq!(&script.0[2..], &short_data[..]);
+        }
+
+        {
+            let medium_data = vec![7; 1024];
+            let script = Script::default() << &medium_data[..];
+            assert_eq!(script.0[0], OpCode::PushData2 as u8);
+            assert_eq!(&script.0[1..3], &[0x00, 0x04][..]);
+            assert_eq!(&script.0[3..], &medium_data[..]);
+        }
+
+        {
+            let long_data = vec![42; 1_000_000];
+            let script = Script::default() << &long_data[..];
+            assert_eq!(script.0[0], OpCode::PushData4 as u8);
+            assert_eq!(&script.0[1..5], &[0x40, 0x42, 0x0f, 0x00][..]);
+            assert_eq!(&script.0[5..], &long_data[..]);
+        }
+    }
+
+    #[test]
+    fn instructions() {
+        let mut script = Script::default()
+            << OpCode::Dup
+            << &[0xab; 3][..]
+            << &[0xcd; 100][..]
+            << OpCode::Op0
+            << OpCode::Nop10;
+        script.0.push(0xc0);
+
+        assert_eq!(
+            script.instructions().collect::<Result<Vec<_>, _>>(),
+            Ok(vec![
+                Instruction::Op(OpCode::Dup),
+                Instruction::PushBytes(&[0xab; 3]),
+                Instruction::PushBytes(&[0xcd; 100]),
+                Instruction::PushBytes(&[]),
+                Instruction::Op(OpCode::Nop10),
+                Instruction::Unknown(0xc0),
+            ])
+        );
+        assert!(!script.is_push_only());
+        assert!((Script::default() << &[1, 2][..] << OpCode::Op0).is_push_only());
+    }
+
+    #[test]
+    fn truncated_push() {
+        for bytes in [
+            &[0x02, 0x01][..],
+            &[0x4c][..],
+            &[0x4c, 0x05, 0x00],
+            &[0x4d, 0x01],
+            &[0x4e, 0xff, 0xff, 0xff, 0xff, 0x00],
+        ] {
+            let script = Script(
+                vec![OpCode::Dup as u8]
+                    .into_iter()
+                    .chain(bytes.iter().copied())
+                    .collect(),
+            );
+            let mut instructions = script.instructions();
+            assert_eq!(instructions.next(), Some(Ok(Instruction::Op(OpCode::Dup))));
+            assert_eq!(
+                instructions.next(),
+                Some(Err(Error::TruncatedPush { offset: 1 }))
+            );
+            assert_eq!(instructions.next(), None);
+            assert!(!script.is_push_only());
+        }
+    }
+
+    #[test]
+    fn kind() {
+        let p2pkh = TransparentAddress::PublicKeyHash([4; 20]);
+        assert_eq!(p2pkh.script().kind(), ScriptKind::PubKeyHash([4; 20]));
+
+        let p2sh = TransparentAddress::ScriptHash([7; 20]);
+        assert_eq!(p2sh.script().kind(), ScriptKind::ScriptHash([7; 20]));
+
+        // A P2PKH-shaped script with a short hash is not standard.
+        let short = Script::default()
+            << OpCode::Dup
+            << OpCode::Hash160
+            << &[4; 19][..]
+            << OpCode::EqualVerify
+            << OpCode::CheckSig;
+        assert_eq!(short.kind(), ScriptKind::Nonstandard);
+        assert_eq!(short.address(), None);
+
+        assert_eq!(Script::default().kind(), ScriptKind::Nonstandard);
+    }
+
+    #[test]
+    fn null_data() {
+        let script = Script::null_data(b"payment reference");
+        assert_eq!(script.0[0], OpCode::Return as u8);
+        assert_eq!(script.kind(), ScriptKind::NullData);
+        assert_eq!(script.address(), None);
+        assert_eq!(
+            script.null_data_payload().as_deref(),
+            Some(&b"payment reference"[..])
+        );
+
+        // Multiple pushes are concatenated.
+        let script = Script::null_data(b"ab") << &b"cd"[..];
+        assert_eq!(script.null_data_payload().as_deref(), Some(&b"abcd"[..]));
+
+        // A bare OP_RETURN carries no data.
+        let script = Script::default() << OpCode::Return;
+        assert_eq!(script.null_data_payload(), Some(vec![]));
+
+        // Small integer pushes are data pushes.
+        let script = Script::default() << OpCode::Return << OpCode::Op1;
+        assert!(Script(script.0[1..].to_vec()).is_push_only());
+        assert_eq!(script.kind(), ScriptKind::NullData);
+        assert_eq!(script.null_data_payload(), Some(vec![1]));
+        let script = Script::null_data(b"ab") << OpCode::Op16 << OpCode::Negative1;
+        assert_eq!(script.kind(), ScriptKind::NullData);
+        assert_eq!(script.null_data_payload(), Some(vec![b'a', b'b', 16, 0x81]));
+
+        // Opcodes after OP_RETURN make the script nonstandard.
+        let script = Script::null_data(b"ab") << OpCode::Dup;
+        assert_eq!(script.kind(), ScriptKind::Nonstandard);
+        assert_eq!(script.null_data_payload(), None);
+    }
+
+    #[test]
+    fn multisig() {
+        let keys = [[2; 33], [3; 33], [4; 33]];
+        let script = Script::multisig(2, &keys).unwrap();
+        assert_eq!(script.0[0], OpCode::Op2 as u8);
+        assert_eq!(script.0[script.0.len() - 2], OpCode::Op3 as u8);
+        assert_eq!(script.0[script.0.len() - 1], OpCode::CheckMultisig as u8);
+        assert_eq!(script.multisig_keys(), Some((2, keys.to_vec())));
+        assert_eq!(script.kind(), ScriptKind::Nonstandard);
+
+        // Invalid thresholds.
+        assert_eq!(Script::multisig(0, &keys), None);
+        assert_eq!(Script::multisig(4, &keys), None);
+
+        // 15 compressed keys fit in a redeem script, but 16 do not.
+        assert!(Script::multisig(1, &[[2; 33]; 15]).is_some());
+        assert_eq!(Script::multisig(1, &[[2; 33]; 16]), None);
+
+        // Mismatched key count.
+        let script = Script::default()
+            << OpCode::Op1
+            << &[2; 33][..]
+            << OpCode::Op2
+            << OpCode::CheckMultisig;
+        assert_eq!(script.multisig_keys(), None);
+
+        // Uncompressed keys are not supported.
+        let script = Script::default()
+            << OpCode::Op1
+            << &[4; 65][..]
+            << OpCode::Op1
+            << OpCode::CheckMultisig;
+        assert_eq!(script.multisig_keys(), None);
+    }
+
+    #[test]
+    fn htlc() {
+        use sha2::{Digest, Sha256};
+
+        let preimage = [9; 32];
+        let payment_hash: [u8; 32] = Sha256::digest(preimage).into();
+        let htlc = Htlc::new(payment_hash, [1; 20], [2; 20], 2_000_000);
+        assert!(htlc.is_preimage(&preimage));
+        assert!(!htlc.is_preimage(&[8; 32]));
+
+        let script = Script::htlc(&htlc);
+        assert_eq!(script.htlc_terms(), Some(htlc));
+        assert_eq!(script.kind(), ScriptKind::Nonstandard);
+
+        // Lock times are encoded as minimal script numbers, including those that require
+        // a fifth byte for the sign bit.
+        for lock_time in [0, 1, 16, 17, 0x80, 500_000_000, u32::MAX] {
+            let htlc = Htlc::new(payment_hash, [1; 20], [2; 20], lock_time);
+            assert_eq!(Script::htlc(&htlc).htlc_terms(), Some(htlc));
+        }
+
+        // A contract with a different preimage size is not recognized.
+        let mut modified = script.clone();
+        modified.0[3] = 33;
+        assert_eq!(modified.htlc_terms(), None);
+
+        // Claim and refund scriptSigs.
+        let claim = Script::default()
+            << &[0x30; 71][..]
+            << &[2; 33][..]
+            << &preimage[..]
+            << OpCode::Op1
+            << &script.0[..];
+        assert_eq!(claim.htlc_spend(), Some((htlc, HtlcSpend::Claim(preimage))));
+        let refund =
+            Script::default() << &[0x30; 71][..] << &[2; 33][..] << &[][..] << &script.0[..];
+        assert_eq!(refund.htlc_spend(), Some((htlc, HtlcSpend::Refund)));
+
+        // A scriptSig for a different redeem script is not an HTLC spend.
+        let multisig = Script::multisig(1, &[[2; 33]]).unwrap();
+        let other =
+            Script::default() << &[0x30; 71][..] << &[2; 33][..] << &[][..] << &multisig.0[..];
+        assert_eq!(other.htlc_spend(), None);
+    }
+}
@@ -1,6 +1,7 @@
 //! Structs for building transactions.
 
 use std::cmp::Ordering;
+use std::convert::Infallible;
 use std::error;
 use std::fmt;
 use std::sync::mpsc::Sender;
@@ -30,10 +31,9 @@ use crate::{
 };
 
 #[cfg(feature = "transparent-inputs")]
-use crate::transaction::components::transparent::builder::TransparentInputInfo;
-
-#[cfg(not(feature = "transparent-inputs"))]
-use std::convert::Infallible;
+use crate::transaction::{
+    components::transparent::builder::TransparentInputInfo, sighash::SignatureHash,
+};
 
 #[cfg(zcash_unstable = "zfuture")]
 use crate::{
@@ -71,8 +71,11 @@ impl<FE: fmt::Display> fmt::Display for FeeError<FE> {
 }
 
 /// Errors that can occur during transaction construction.
+///
+/// `AE` is the error type of the [`SpendAuthorizer`], if any, with which the transaction is
+/// authorized.
 #[derive(Debug)]
-pub enum Error<FE> {
+pub enum Error<FE, AE = Infallible> {
     /// Insufficient funds were provided to the transaction builder; the given
     /// additional amount is required in order to construct the transaction.
     InsufficientFunds(Amount),
@@ -99,12 +102,17 @@ pub enum Error<FE> {
     /// The builder was constructed with a target height before NU5 activation, but an Orchard
     /// spend or output was added.
     OrchardBuilderNotAvailable,
+    /// The requested transaction version is not valid under the consensus rules in effect
+    /// at the target height, or cannot represent the bundles of the transaction.
+    UnsupportedTxVersion(TxVersion, BranchId),
+    /// The [`SpendAuthorizer`] was unable to authorize a spend.
+    SpendAuthorization(AE),
     /// An error occurred in constructing the TZE parts of a transaction.
     #[cfg(zcash_unstable = "zfuture")]
     TzeBuild(tze::builder::Error),
 }
 
-impl<FE: fmt::Display> fmt::Display for Error<FE> {
+impl<FE: fmt::Display, AE: fmt::Display> fmt::Display for Error<FE, AE> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Error::InsufficientFunds(amount) => write!(
@@ -132,38 +140,161 @@ impl<FE: fmt::Display> fmt::Display for Error<FE> {
                 f,
                 "Cannot create Orchard transactions without an Orchard anchor, or before NU5 activation"
             ),
+            Error::UnsupportedTxVersion(version, branch_id) => write!(
+                f,
+                "Transaction version {:?} cannot be used for this transaction under consensus branch {:?}",
+                version, branch_id
+            ),
+            Error::SpendAuthorization(err) => write!(f, "Could not authorize a spend: {}", err),
             #[cfg(zcash_unstable = "zfuture")]
             Error::TzeBuild(err) => err.fmt(f),
         }
     }
 }
 
-impl<FE: fmt::Debug + fmt::Display> error::Error for Error<FE> {}
+impl<FE: fmt::Debug + fmt::Display, AE: fmt::Debug + fmt::Display> error::Error for Error<FE, AE> {}
 
-impl<FE> From<BalanceError> for Error<FE> {
+impl<FE, AE> Error<FE, AE> {
+    /// Returns the error produced by the [`SpendAuthorizer`], if this is an
+    /// [`Error::SpendAuthorization`], or otherwise this error with the authorization error
+    /// type erased.
+    pub fn authorization_error(self) -> Result<AE, Error<FE>> {
+        match self {
+            Error::SpendAuthorization(e) => Ok(e),
+            Error::InsufficientFunds(amount) => Err(Error::InsufficientFunds(amount)),
+            Error::ChangeRequired(amount) => Err(Error::ChangeRequired(amount)),
+            Error::Fee(e) => Err(Error::Fee(e)),
+            Error::Balance(e) => Err(Error::Balance(e)),
+            Error::TransparentBuild(e) => Err(Error::TransparentBuild(e)),
+            Error::SaplingBuild(e) => Err(Error::SaplingBuild(e)),
+            Error::OrchardBuild(e) => Err(Error::OrchardBuild(e)),
+            Error::OrchardSpend(e) => Err(Error::OrchardSpend(e)),
+            Error::OrchardRecipient(e) => Err(Error::OrchardRecipient(e)),
+            Error::SaplingBuilderNotAvailable => Err(Error::SaplingBuilderNotAvailable),
+            Error::OrchardBuilderNotAvailable => Err(Error::OrchardBuilderNotAvailable),
+            Error::UnsupportedTxVersion(version, branch_id) => {
+                Err(Error::UnsupportedTxVersion(version, branch_id))
+            }
+            #[cfg(zcash_unstable = "zfuture")]
+            Error::TzeBuild(e) => Err(Error::TzeBuild(e)),
+        }
+    }
+}
+
+impl<FE, AE> From<BalanceError> for Error<FE, AE> {
     fn from(e: BalanceError) -> Self {
         Error::Balance(e)
     }
 }
 
-impl<FE> From<FeeError<FE>> for Error<FE> {
+impl<FE, AE> From<FeeError<FE>> for Error<FE, AE> {
     fn from(e: FeeError<FE>) -> Self {
         Error::Fee(e)
     }
 }
 
-impl<FE> From<sapling::builder::Error> for Error<FE> {
+impl<FE, AE> From<sapling::builder::Error> for Error<FE, AE> {
     fn from(e: sapling::builder::Error) -> Self {
         Error::SaplingBuild(e)
     }
 }
 
-impl<FE> From<orchard::builder::SpendError> for Error<FE> {
+impl<FE, AE> From<orchard::builder::SpendError> for Error<FE, AE> {
     fn from(e: orchard::builder::SpendError) -> Self {
         Error::OrchardSpend(e)
     }
 }
 
+/// A Sapling bundle that has been proven, and whose spends are ready to be signed.
+pub type SaplingPartiallyAuthorized = sapling::Bundle<
+    sapling::builder::InProgress<sapling::builder::Proven, sapling::builder::PartiallyAuthorized>,
+    Amount,
+>;
+
+/// An Orchard bundle that has been proven, and whose spends are ready to be signed.
+pub type OrchardPartiallyAuthorized = orchard::Bundle<
+    orchard::builder::InProgress<orchard::circuit::Proof, orchard::builder::PartiallyAuthorized>,
+    Amount,
+>;
+
+/// A holder of spending keys that authorizes the spends of a transaction on behalf of the
+/// [`Builder`], without providing those keys to it.
+///
+/// Spends added with [`Builder::add_unsigned_sapling_spend`],
+/// [`Builder::add_unsigned_orchard_spend`] or [`Builder::add_unsigned_transparent_input`]
+/// are signed by the authorizer passed to [`Builder::build_with_authorizer`], once all of
+/// the effecting data of the transaction has been fixed.
+pub trait SpendAuthorizer {
+    /// The error returned when a spend cannot be authorized.
+    type Error;
+
+    /// Signs the signature hash of a transparent input spending a coin sent to the P2PKH
+    /// address of `pubkey`.
+    #[cfg(feature = "transparent-inputs")]
+    fn sign_transparent(
+        &self,
+        pubkey: &secp256k1::PublicKey,
+        sighash: &SignatureHash,
+    ) -> Result<secp256k1::ecdsa::Signature, Self::Error>;
+
+    /// Signs those Sapling spends of `bundle` that spend notes received by this
+    /// authorizer's keys, over the transaction's shielded signature hash `sighash`.
+    ///
+    /// Spends that were signed with keys held by the builder are already signed.
+    fn sign_sapling<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        sighash: &[u8; 32],
+        bundle: SaplingPartiallyAuthorized,
+    ) -> Result<SaplingPartiallyAuthorized, Self::Error>;
+
+    /// Signs those Orchard spends of `bundle` that spend notes received by this
+    /// authorizer's keys, over the transaction's shielded signature hash `sighash`.
+    ///
+    /// Spends that were signed with keys held by the builder are already signed.
+    fn sign_orchard<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        sighash: &[u8; 32],
+        bundle: OrchardPartiallyAuthorized,
+    ) -> Result<OrchardPartiallyAuthorized, Self::Error>;
+}
+
+/// The type of the absent authorizer when a transaction is built without one. Its methods
+/// are never called.
+struct NoAuthorizer;
+
+impl SpendAuthorizer for NoAuthorizer {
+    type Error = Infallible;
+
+    #[cfg(feature = "transparent-inputs")]
+    fn sign_transparent(
+        &self,
+        _: &secp256k1::PublicKey,
+        _: &SignatureHash,
+    ) -> Result<secp256k1::ecdsa::Signature, Self::Error> {
+        unreachable!("no authorizer was provided")
+    }
+
+    fn sign_sapling<R: RngCore + CryptoRng>(
+        &self,
+        _: &mut R,
+        _: &[u8; 32],
+        _: SaplingPartiallyAuthorized,
+    ) -> Result<SaplingPartiallyAuthorized, Self::Error> {
+        unreachable!("no authorizer was provided")
+    }
+
+    fn sign_orchard<R: RngCore + CryptoRng>(
+        &self,
+        _: &mut R,
+        _: &[u8; 32],
+        _: OrchardPartiallyAuthorized,
+    ) -> Result<OrchardPartiallyAuthorized, Self::Error> {
+        unreachable!("no authorizer was provided")
+    }
+}
+
 /// Reports on the progress made by the builder towards building a transaction.
 pub struct Progress {
     /// The number of steps completed.
@@ -275,6 +406,8 @@ pub struct Builder<'a, P, U: sapling::builder::ProverProgress> {
     build_config: BuildConfig,
     target_height: BlockHeight,
     expiry_height: BlockHeight,
+    lock_time: u32,
+    tx_version: Option<TxVersion>,
     transparent_builder: TransparentBuilder,
     sapling_builder: Option<sapling::builder::Builder>,
     orchard_builder: Option<orchard::builder::Builder>,
@@ -302,6 +435,17 @@ impl<'a, P, U: sapling::builder::ProverProgress> Builder<'a, P, U> {
         self.target_height
     }
 
+    /// Returns the `nLockTime` value of the transaction under construction.
+    pub fn lock_time(&self) -> u32 {
+        self.lock_time
+    }
+
+    /// Returns the transaction version that has been requested for the transaction under
+    /// construction, or `None` if the version suggested for the target height will be used.
+    pub fn tx_version(&self) -> Option<TxVersion> {
+        self.tx_version
+    }
+
     /// Returns the set of transparent inputs currently committed to be consumed
     /// by the transaction.
     #[cfg(feature = "transparent-inputs")]
@@ -339,7 +483,8 @@ impl<'a, P: consensus::Parameters> Builder<'a, P, ()> {
     /// # Default values
     ///
     /// The expiry height will be set to the given height plus the default transaction
-    /// expiry delta (20 blocks).
+    /// expiry delta (20 blocks). The lock time will be set to zero, and the transaction
+    /// version will be that suggested for the consensus branch active at the given height.
     pub fn new(params: P, target_height: BlockHeight, build_config: BuildConfig) -> Self {
         let orchard_builder = if params.is_nu_active(NetworkUpgrade::Nu5, target_height) {
             build_config
@@ -364,6 +509,8 @@ impl<'a, P: consensus::Parameters> Builder<'a, P, ()> {
             build_config,
             target_height,
             expiry_height: target_height + DEFAULT_TX_EXPIRY_DELTA,
+            lock_time: 0,
+            tx_version: None,
             transparent_builder: TransparentBuilder::empty(),
             sapling_builder,
             orchard_builder,
@@ -392,6 +539,8 @@ impl<'a, P: consensus::Parameters> Builder<'a, P, ()> {
             build_config: self.build_config,
             target_height: self.target_height,
             expiry_height: self.expiry_height,
+            lock_time: self.lock_time,
+            tx_version: self.tx_version,
             transparent_builder: self.transparent_builder,
             sapling_builder: self.sapling_builder,
             orchard_builder: self.orchard_builder,
@@ -404,6 +553,27 @@ impl<'a, P: consensus::Parameters> Builder<'a, P, ()> {
 }
 
 impl<'a, P: consensus::Parameters, U: sapling::builder::ProverProgress> Builder<'a, P, U> {
+    /// Sets the `nLockTime` field of the transaction.
+    pub fn set_lock_time(&mut self, lock_time: u32) {
+        self.lock_time = lock_time;
+    }
+
+    /// Requests that the transaction be constructed using the given transaction version,
+    /// instead of the version suggested for the consensus branch active at the target height.
+    ///
+    /// Returns an error if the version is not valid under the consensus rules in effect at the
+    /// target height. Building will also fail if the version cannot represent the bundles of
+    /// the transaction; for example, a v4 transaction cannot contain Orchard actions.
+    pub fn set_tx_version<FE>(&mut self, version: TxVersion) -> Result<(), Error<FE>> {
+        let consensus_branch_id = BranchId::for_height(&self.params, self.target_height);
+        if version.valid_in_branch(consensus_branch_id) {
+            self.tx_version = Some(version);
+            Ok(())
+        } else {
+            Err(Error::UnsupportedTxVersion(version, consensus_branch_id))
+        }
+    }
+
     /// Adds an Orchard note to be spent in this bundle.
     ///
     /// Returns an error if the given Merkle path does not have the required anchor for
@@ -426,6 +596,28 @@ impl<'a, P: consensus::Parameters, U: sapling::builder::ProverProgress> Builder<
         }
     }
 
+    /// Adds an Orchard note received by `fvk` to be spent in this bundle, without providing
+    /// its spending key.
+    ///
+    /// The spend must be signed by the [`SpendAuthorizer`] passed to
+    /// [`Builder::build_with_authorizer`].
+    ///
+    /// Returns an error if the given Merkle path does not have the required anchor for
+    /// the given note.
+    pub fn add_unsigned_orchard_spend<FE, AE>(
+        &mut self,
+        fvk: &orchard::keys::FullViewingKey,
+        note: orchard::Note,
+        merkle_path: orchard::tree::MerklePath,
+    ) -> Result<(), Error<FE, AE>> {
+        self.orchard_builder
+            .as_mut()
+            .ok_or(Error::OrchardBuilderNotAvailable)?
+            .add_spend(fvk.clone(), note, merkle_path)?;
+
+        Ok(())
+    }
+
     /// Adds an Orchard recipient to the transaction.
     pub fn add_orchard_output<FE>(
         &mut self,
@@ -466,6 +658,29 @@ impl<'a, P: consensus::Parameters, U: sapling::builder::ProverProgress> Builder<
         }
     }
 
+    /// Adds a Sapling note to be spent in this transaction, without retaining the spend
+    /// authorizing key of `extsk`.
+    ///
+    /// `extsk` is used only to derive the proof generation key for the spend, as the
+    /// Sapling builder requires; the spend must be signed by the [`SpendAuthorizer`] passed
+    /// to [`Builder::build_with_authorizer`].
+    ///
+    /// Returns an error if the given Merkle path does not have the same anchor as the
+    /// paths for previous Sapling notes.
+    pub fn add_unsigned_sapling_spend<FE, AE>(
+        &mut self,
+        extsk: &sapling::zip32::ExtendedSpendingKey,
+        note: Note,
+        merkle_path: sapling::MerklePath,
+    ) -> Result<(), Error<FE, AE>> {
+        self.sapling_builder
+            .as_mut()
+            .ok_or(Error::SaplingBuilderNotAvailable)?
+            .add_spend(extsk, note, merkle_path)?;
+
+        Ok(())
+    }
+
     /// Adds a Sapling address to send funds to.
     pub fn add_sapling_output<FE>(
         &mut self,
@@ -497,6 +712,75 @@ impl<'a, P: consensus::Parameters, U: sapling::builder::ProverProgress> Builder<
         self.transparent_builder.add_input(sk, utxo, coin)
     }
 
+    /// Adds a transparent coin sent to the P2PKH address of `pubkey` to be spent in this
+    /// transaction, without providing its signing key.
+    ///
+    /// The input must be signed by the [`SpendAuthorizer`] passed to
+    /// [`Builder::build_with_authorizer`].
+    #[cfg(feature = "transparent-inputs")]
+    pub fn add_unsigned_transparent_input(
+        &mut self,
+        pubkey: secp256k1::PublicKey,
+        utxo: transparent::OutPoint,
+        coin: TxOut,
+    ) -> Result<(), transparent::builder::Error> {
+        self.transparent_builder
+            .add_unsigned_input(pubkey, utxo, coin)
+    }
+
+    /// Adds a transparent coin sent to a P2SH multisig address to be spent in this
+    /// transaction.
+    ///
+    /// The input is signed with those of `sks` that are keys of `redeem_script`, up to its
+    /// threshold. If fewer keys are provided, the built transaction is partially signed;
+    /// see [`TransparentBuilder::add_p2sh_multisig_input`] for how to complete it.
+    #[cfg(feature = "transparent-inputs")]
+    pub fn add_transparent_p2sh_multisig_input(
+        &mut self,
+        redeem_script: crate::legacy::Script,
+        sks: &[secp256k1::SecretKey],
+        utxo: transparent::OutPoint,
+        coin: TxOut,
+    ) -> Result<(), transparent::builder::Error> {
+        self.transparent_builder
+            .add_p2sh_multisig_input(redeem_script, sks, utxo, coin)
+    }
+
+    /// Adds a transparent coin locked by a hash time-locked contract to be spent in this
+    /// transaction, either claiming it with the contract's preimage or refunding it.
+    ///
+    /// When refunding the coin, the transaction's `nLockTime` is raised to the contract's
+    /// lock time if it is lower; it must not subsequently be lowered with
+    /// [`Builder::set_lock_time`]. See [`TransparentBuilder::add_htlc_input`] for the
+    /// requirements on the other arguments.
+    #[cfg(feature = "transparent-inputs")]
+    pub fn add_transparent_htlc_input(
+        &mut self,
+        htlc: &crate::legacy::script::Htlc,
+        spend: crate::legacy::script::HtlcSpend,
+        sk: secp256k1::SecretKey,
+        utxo: transparent::OutPoint,
+        coin: TxOut,
+    ) -> Result<(), transparent::builder::Error> {
+        self.transparent_builder
+            .add_htlc_input(htlc, spend, sk, utxo, coin)?;
+        if let Some(lock_time) = self.transparent_builder.min_lock_time() {
+            self.lock_time = std::cmp::max(self.lock_time, lock_time);
+        }
+        Ok(())
+    }
+
+    /// Adds an output that locks funds with the given hash time-locked contract, which can
+    /// be claimed by its recipient or refunded after its lock time.
+    #[cfg(feature = "transparent-inputs")]
+    pub fn add_transparent_htlc_output(
+        &mut self,
+        htlc: &crate::legacy::script::Htlc,
+        value: NonNegativeAmount,
+    ) -> Result<(), transparent::builder::Error> {
+        self.transparent_builder.add_htlc_output(htlc, value)
+    }
+
     /// Adds a transparent address to send funds to.
     pub fn add_transparent_output(
         &mut self,
@@ -506,6 +790,19 @@ impl<'a, P: consensus::Parameters, U: sapling::builder::ProverProgress> Builder<
         self.transparent_builder.add_output(to, value)
     }
 
+    /// Adds a zero-valued `OP_RETURN` output that commits to the given data, for example a
+    /// payment reference.
+    ///
+    /// The output is counted towards the transaction fee according to its serialized size.
+    /// Returns an error if the data is too large to be relayed, or if a data output has
+    /// already been added to this transaction.
+    pub fn add_transparent_data_output(
+        &mut self,
+        data: &[u8],
+    ) -> Result<(), transparent::builder::Error> {
+        self.transparent_builder.add_null_data_output(data)
+    }
+
     /// Returns the sum of the transparent, Sapling, Orchard, and TZE value balances.
     fn value_balance(&self) -> Result<Amount, BalanceError> {
         let value_balances = [
@@ -622,6 +919,99 @@ impl<'a, P: consensus::Parameters, U: sapling::builder::ProverProgress> Builder<
             .map_err(FeeError::FeeRule)
     }
 
+    /// Checks whether a transaction can be built from the configured spends and outputs,
+    /// without generating any proofs or signatures.
+    ///
+    /// This validates the shape of each bundle against its bundle type (including any
+    /// padding requirements), the fee computed by `fee_rule`, the value balance of the
+    /// transaction, and the compatibility of the transaction version with the bundles that
+    /// will be produced. All violated constraints are returned, so that callers can report
+    /// every problem at once before performing the expensive proving step.
+    ///
+    /// Fee and value balance constraints can only be checked once the shape of each bundle
+    /// is known to be valid, so they are not reported if a bundle error is returned.
+    pub fn check<FR: FeeRule>(&self, fee_rule: &FR) -> Result<(), Vec<Error<FR::Error>>> {
+        self.check_internal(|builder| builder.get_fee(fee_rule))
+    }
+
+    /// Checks whether a transaction can be built from the configured spends and outputs,
+    /// without generating any proofs or signatures.
+    ///
+    /// See [`Builder::check`] for details.
+    #[cfg(zcash_unstable = "zfuture")]
+    pub fn check_zfuture<FR: FeeRule + FutureFeeRule>(
+        &self,
+        fee_rule: &FR,
+    ) -> Result<(), Vec<Error<FR::Error>>> {
+        self.check_internal(|builder| builder.get_fee_zfuture(fee_rule))
+    }
+
+    fn check_internal<FE>(
+        &self,
+        get_fee: impl FnOnce(&Self) -> Result<NonNegativeAmount, FeeError<FE>>,
+    ) -> Result<(), Vec<Error<FE>>> {
+        let mut errors = vec![];
+
+        let sapling_outputs = self
+            .sapling_builder
+            .as_ref()
+            .zip(self.build_config.sapling_builder_config())
+            .map_or(Ok(0), |(builder, (bundle_type, _))| {
+                bundle_type.num_outputs(builder.inputs().len(), builder.outputs().len())
+            });
+        let orchard_actions = self
+            .orchard_builder
+            .as_ref()
+            .zip(self.build_config.orchard_builder_config())
+            .map_or(Ok(0), |(builder, (bundle_type, _))| {
+                bundle_type.num_actions(builder.spends().len(), builder.outputs().len())
+            });
+
+        match (sapling_outputs, orchard_actions) {
+            (Ok(_), Ok(_)) => match get_fee(self) {
+                Ok(fee) => {
+                    if let Err(e) = self.check_balance(fee) {
+                        errors.push(e);
+                    }
+                }
+                Err(e) => errors.push(Error::Fee(e)),
+            },
+            (sapling_outputs, orchard_actions) => errors.extend(
+                [sapling_outputs.err(), orchard_actions.err()]
+                    .into_iter()
+                    .flatten()
+                    .map(|e| Error::Fee(FeeError::Bundle(e))),
+            ),
+        }
+
+        let consensus_branch_id = BranchId::for_height(&self.params, self.target_height);
+        let version = self
+            .tx_version
+            .unwrap_or_else(|| TxVersion::suggested_for_branch(consensus_branch_id));
+        if matches!(orchard_actions, Ok(n) if n > 0) && !version.has_orchard() {
+            errors.push(Error::UnsupportedTxVersion(version, consensus_branch_id));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Checks that the value balance of the transaction is zero after the given fee is
+    /// accounted for.
+    fn check_balance<FE, AE>(&self, fee: NonNegativeAmount) -> Result<(), Error<FE, AE>> {
+        let balance_after_fees =
+            (self.value_balance()? - fee.into()).ok_or(BalanceError::Underflow)?;
+
+        match balance_after_fees.cmp(&Amount::zero()) {
+            Ordering::Less => Err(Error::InsufficientFunds(-balance_after_fees)),
+            Ordering::Greater => Err(Error::ChangeRequired(balance_after_fees)),
+            Ordering::Equal => Ok(()),
+        }
+    }
+
     /// Builds a transaction from the configured spends and outputs.
     ///
     /// Upon success, returns a tuple containing the final transaction, and the
@@ -634,7 +1024,30 @@ impl<'a, P: consensus::Parameters, U: sapling::builder::ProverProgress> Builder<
         fee_rule: &FR,
     ) -> Result<BuildResult, Error<FR::Error>> {
         let fee = self.get_fee(fee_rule).map_err(Error::Fee)?;
-        self.build_internal(rng, spend_prover, output_prover, fee)
+        self.build_internal(rng, spend_prover, output_prover, fee, None::<&NoAuthorizer>)
+    }
+
+    /// Builds a transaction from the configured spends and outputs, obtaining the
+    /// signatures for spends added without their keys from `authorizer`.
+    ///
+    /// Upon success, returns a tuple containing the final transaction, and the
+    /// [`SaplingMetadata`] generated during the build process.
+    pub fn build_with_authorizer<
+        R: RngCore + CryptoRng,
+        SP: SpendProver,
+        OP: OutputProver,
+        FR: FeeRule,
+        A: SpendAuthorizer,
+    >(
+        self,
+        rng: R,
+        spend_prover: &SP,
+        output_prover: &OP,
+        fee_rule: &FR,
+        authorizer: &A,
+    ) -> Result<BuildResult, Error<FR::Error, A::Error>> {
+        let fee = self.get_fee(fee_rule).map_err(Error::Fee)?;
+        self.build_internal(rng, spend_prover, output_prover, fee, Some(authorizer))
     }
 
     /// Builds a transaction from the configured spends and outputs.
@@ -655,38 +1068,35 @@ impl<'a, P: consensus::Parameters, U: sapling::builder::ProverProgress> Builder<
         fee_rule: &FR,
     ) -> Result<BuildResult, Error<FR::Error>> {
         let fee = self.get_fee_zfuture(fee_rule).map_err(Error::Fee)?;
-        self.build_internal(rng, spend_prover, output_prover, fee)
+        self.build_internal(rng, spend_prover, output_prover, fee, None::<&NoAuthorizer>)
     }
 
-    fn build_internal<R: RngCore + CryptoRng, SP: SpendProver, OP: OutputProver, FE>(
+    fn build_internal<
+        R: RngCore + CryptoRng,
+        SP: SpendProver,
+        OP: OutputProver,
+        FE,
+        A: SpendAuthorizer,
+    >(
         self,
         mut rng: R,
         spend_prover: &SP,
         output_prover: &OP,
         fee: NonNegativeAmount,
-    ) -> Result<BuildResult, Error<FE>> {
+        authorizer: Option<&A>,
+    ) -> Result<BuildResult, Error<FE, A::Error>> {
         let consensus_branch_id = BranchId::for_height(&self.params, self.target_height);
 
         // determine transaction version
-        let version = TxVersion::suggested_for_branch(consensus_branch_id);
+        let version = self
+            .tx_version
+            .unwrap_or_else(|| TxVersion::suggested_for_branch(consensus_branch_id));
 
         //
         // Consistency checks
         //
 
-        // After fees are accounted for, the value balance of the transaction must be zero.
-        let balance_after_fees =
-            (self.value_balance()? - fee.into()).ok_or(BalanceError::Underflow)?;
-
-        match balance_after_fees.cmp(&Amount::zero()) {
-            Ordering::Less => {
-                return Err(Error::InsufficientFunds(-balance_after_fees));
-            }
-            Ordering::Greater => {
-                return Err(Error::ChangeRequired(balance_after_fees));
-            }
-            Ordering::Equal => (),
-        };
+        self.check_balance(fee)?;
 
         let transparent_bundle = self.transparent_builder.build();
 
@@ -734,13 +1144,17 @@ impl<'a, P: consensus::Parameters, U: sapling::builder::ProverProgress> Builder<
             None => (None, orchard::builder::BundleMetadata::empty()),
         };
 
+        if orchard_bundle.is_some() && !version.has_orchard() {
+            return Err(Error::UnsupportedTxVersion(version, consensus_branch_id));
+        }
+
         #[cfg(zcash_unstable = "zfuture")]
         let (tze_bundle, tze_signers) = self.tze_builder.build();
 
         let unauthed_tx: TransactionData<Unauthorized> = TransactionData {
             version,
-            consensus_branch_id: BranchId::for_height(&self.params, self.target_height),
-            lock_time: 0,
+            consensus_branch_id,
+            lock_time: self.lock_time,
             expiry_height: self.expiry_height,
             transparent_bundle,
             sprout_bundle: None,
@@ -750,82 +1164,166 @@ impl<'a, P: consensus::Parameters, U: sapling::builder::ProverProgress> Builder<
             tze_bundle,
         };
 
-        //
-        // Signatures -- everything but the signatures must already have been added.
-        //
-        let txid_parts = unauthed_tx.digest(TxIdDigester);
+        let transaction = authorize_with(
+            unauthed_tx,
+            rng,
+            &self.sapling_asks,
+            &orchard::circuit::ProvingKey::build(),
+            &self.orchard_saks,
+            authorizer,
+            #[cfg(zcash_unstable = "zfuture")]
+            tze_signers,
+        )?;
+
+        Ok(BuildResult {
+            transaction,
+            sapling_meta,
+            orchard_meta,
+        })
+    }
+}
 
-        let transparent_bundle = unauthed_tx.transparent_bundle.clone().map(|b| {
-            b.apply_signatures(
+/// Creates the remaining proofs and signatures for an unauthorized transaction, producing a
+/// complete [`Transaction`].
+///
+/// This makes it possible to assemble a transaction from bundles that were constructed
+/// independently of [`Builder`]; for example, an Orchard bundle produced by
+/// [`orchard::builder::Builder`] may be combined with a transparent bundle produced by
+/// [`TransparentBuilder`] using [`TransactionData::from_parts`]. All effecting data must be
+/// present in `unauthed_tx`, because the signatures created here commit to the entire
+/// transaction:
+///
+/// - transparent inputs are signed with the keys recorded in the transparent bundle;
+/// - Sapling spends are signed with the corresponding keys among `sapling_asks`;
+/// - the Orchard bundle is proven using `orchard_proving_key`, and its spends are signed
+///   with the corresponding keys among `orchard_saks`.
+///
+/// Binding signatures for the shielded bundles are derived from the randomness recorded in
+/// each bundle, and so are computed correctly regardless of where the bundle was built.
+pub fn authorize_transaction<R: RngCore + CryptoRng, FE>(
+    unauthed_tx: TransactionData<Unauthorized>,
+    rng: R,
+    sapling_asks: &[sapling::keys::SpendAuthorizingKey],
+    orchard_proving_key: &orchard::circuit::ProvingKey,
+    orchard_saks: &[orchard::keys::SpendAuthorizingKey],
+    #[cfg(zcash_unstable = "zfuture")] tze_signers: Vec<
+        tze::builder::TzeSigner<'_, TransactionData<Unauthorized>>,
+    >,
+) -> Result<Transaction, Error<FE>> {
+    authorize_with(
+        unauthed_tx,
+        rng,
+        sapling_asks,
+        orchard_proving_key,
+        orchard_saks,
+        None::<&NoAuthorizer>,
+        #[cfg(zcash_unstable = "zfuture")]
+        tze_signers,
+    )
+}
+
+/// Creates the remaining proofs and signatures for an unauthorized transaction, as for
+/// [`authorize_transaction`], additionally obtaining signatures from `authorizer` for the
+/// spends whose keys were not provided.
+fn authorize_with<R: RngCore + CryptoRng, FE, A: SpendAuthorizer>(
+    unauthed_tx: TransactionData<Unauthorized>,
+    mut rng: R,
+    sapling_asks: &[sapling::keys::SpendAuthorizingKey],
+    orchard_proving_key: &orchard::circuit::ProvingKey,
+    orchard_saks: &[orchard::keys::SpendAuthorizingKey],
+    authorizer: Option<&A>,
+    #[cfg(zcash_unstable = "zfuture")] tze_signers: Vec<
+        tze::builder::TzeSigner<'_, TransactionData<Unauthorized>>,
+    >,
+) -> Result<Transaction, Error<FE, A::Error>> {
+    //
+    // Signatures -- everything but the signatures must already have been added.
+    //
+    let txid_parts = unauthed_tx.digest(TxIdDigester);
+
+    let transparent_bundle = unauthed_tx
+        .transparent_bundle
+        .clone()
+        .map(|b| match authorizer {
+            #[cfg(feature = "transparent-inputs")]
+            Some(authorizer) => b
+                .apply_external_signatures(&unauthed_tx, &txid_parts, authorizer)
+                .map_err(Error::SpendAuthorization),
+            _ => Ok(b.apply_signatures(
                 #[cfg(feature = "transparent-inputs")]
                 &unauthed_tx,
                 #[cfg(feature = "transparent-inputs")]
                 &txid_parts,
-            )
-        });
+            )),
+        })
+        .transpose()?;
 
+    #[cfg(zcash_unstable = "zfuture")]
+    let tze_bundle = unauthed_tx
+        .tze_bundle
+        .clone()
+        .map(|b| b.into_authorized(&unauthed_tx, tze_signers))
+        .transpose()
+        .map_err(Error::TzeBuild)?;
+
+    // the commitment being signed is shared across all Sapling inputs; once
+    // V4 transactions are deprecated this should just be the txid, but
+    // for now we need to continue to compute it here.
+    let shielded_sig_commitment =
+        signature_hash(&unauthed_tx, &SignableInput::Shielded, &txid_parts);
+    let sighash = *shielded_sig_commitment.as_ref();
+
+    let sapling_bundle = unauthed_tx
+        .sapling_bundle
+        .map(|b| {
+            let b = sapling_asks
+                .iter()
+                .fold(b.prepare(&mut rng, sighash), |b, ask| b.sign(&mut rng, ask));
+            let b = match authorizer {
+                Some(authorizer) => authorizer
+                    .sign_sapling(&mut rng, &sighash, b)
+                    .map_err(Error::SpendAuthorization)?,
+                None => b,
+            };
+            b.finalize().map_err(Error::SaplingBuild)
+        })
+        .transpose()?;
+
+    let orchard_bundle = unauthed_tx
+        .orchard_bundle
+        .map(|b| {
+            let b = b
+                .create_proof(orchard_proving_key, &mut rng)
+                .map_err(Error::OrchardBuild)?;
+            let b = orchard_saks
+                .iter()
+                .fold(b.prepare(&mut rng, sighash), |b, ask| b.sign(&mut rng, ask));
+            let b = match authorizer {
+                Some(authorizer) => authorizer
+                    .sign_orchard(&mut rng, &sighash, b)
+                    .map_err(Error::SpendAuthorization)?,
+                None => b,
+            };
+            b.finalize().map_err(Error::OrchardBuild)
+        })
+        .transpose()?;
+
+    let authorized_tx = TransactionData {
+        version: unauthed_tx.version,
+        consensus_branch_id: unauthed_tx.consensus_branch_id,
+        lock_time: unauthed_tx.lock_time,
+        expiry_height: unauthed_tx.expiry_height,
+        transparent_bundle,
+        sprout_bundle: unauthed_tx.sprout_bundle,
+        sapling_bundle,
+        orchard_bundle,
         #[cfg(zcash_unstable = "zfuture")]
-        let tze_bundle = unauthed_tx
-            .tze_bundle
-            .clone()
-            .map(|b| b.into_authorized(&unauthed_tx, tze_signers))
-            .transpose()
-            .map_err(Error::TzeBuild)?;
-
-        // the commitment being signed is shared across all Sapling inputs; once
-        // V4 transactions are deprecated this should just be the txid, but
-        // for now we need to continue to compute it here.
-        let shielded_sig_commitment =
-            signature_hash(&unauthed_tx, &SignableInput::Shielded, &txid_parts);
-
-        let sapling_bundle = unauthed_tx
-            .sapling_bundle
-            .map(|b| {
-                b.apply_signatures(
-                    &mut rng,
-                    *shielded_sig_commitment.as_ref(),
-                    &self.sapling_asks,
-                )
-            })
-            .transpose()
-            .map_err(Error::SaplingBuild)?;
-
-        let orchard_bundle = unauthed_tx
-            .orchard_bundle
-            .map(|b| {
-                b.create_proof(&orchard::circuit::ProvingKey::build(), &mut rng)
-                    .and_then(|b| {
-                        b.apply_signatures(
-                            &mut rng,
-                            *shielded_sig_commitment.as_ref(),
-                            &self.orchard_saks,
-                        )
-                    })
-            })
-            .transpose()
-            .map_err(Error::OrchardBuild)?;
-
-        let authorized_tx = TransactionData {
-            version: unauthed_tx.version,
-            consensus_branch_id: unauthed_tx.consensus_branch_id,
-            lock_time: unauthed_tx.lock_time,
-            expiry_height: unauthed_tx.expiry_height,
-            transparent_bundle,
-            sprout_bundle: unauthed_tx.sprout_bundle,
-            sapling_bundle,
-            orchard_bundle,
-            #[cfg(zcash_unstable = "zfuture")]
-            tze_bundle,
-        };
+        tze_bundle,
+    };
 
-        // The unwrap() here is safe because the txid hashing
-        // of freeze() should be infalliable.
-        Ok(BuildResult {
-            transaction: authorized_tx.freeze().unwrap(),
-            sapling_meta,
-            orchard_meta,
-        })
-    }
+    // The unwrap() here is safe because the txid hashing
+    // of freeze() should be infalliable.
+    Ok(authorized_tx.freeze().unwrap())
 }
 
 #[cfg(zcash_unstable = "zfuture")]
@@ -941,7 +1439,9 @@ mod tests {
     #[cfg(feature = "transparent-inputs")]
     use crate::{
         legacy::keys::{AccountPrivKey, IncomingViewingKey},
-        transaction::{builder::DEFAULT_TX_EXPIRY_DELTA, OutPoint, TxOut},
+        transaction::{
+            builder::DEFAULT_TX_EXPIRY_DELTA, sighash::SIGHASH_ALL, OutPoint, Transaction, TxOut,
+        },
         zip32::AccountId,
     };
 
@@ -967,6 +1467,8 @@ mod tests {
             },
             target_height: sapling_activation_height,
             expiry_height: sapling_activation_height + DEFAULT_TX_EXPIRY_DELTA,
+            lock_time: 0,
+            tx_version: None,
             transparent_builder: TransparentBuilder::empty(),
             sapling_builder: None,
             #[cfg(zcash_unstable = "zfuture")]
@@ -1012,6 +1514,179 @@ mod tests {
         assert!(res.transaction().sapling_bundle.is_none());
     }
 
+    /// Returns an output held at the first external transparent address of a test account,
+    /// along with its outpoint and the key that spends it.
+    #[cfg(feature = "transparent-inputs")]
+    fn transparent_prev_coin() -> (secp256k1::SecretKey, OutPoint, TxOut) {
+        use crate::legacy::keys::NonHardenedChildIndex;
+
+        let tsk = AccountPrivKey::from_seed(&TEST_NETWORK, &[0u8; 32], AccountId::ZERO).unwrap();
+        let prev_coin = TxOut {
+            value: NonNegativeAmount::const_from_u64(50000),
+            script_pubkey: tsk
+                .to_account_pubkey()
+                .derive_external_ivk()
+                .unwrap()
+                .derive_address(NonHardenedChildIndex::ZERO)
+                .unwrap()
+                .script(),
+        };
+        let sk = tsk
+            .derive_external_secret_key(NonHardenedChildIndex::ZERO)
+            .unwrap();
+        (sk, OutPoint::new([0u8; 32], 1), prev_coin)
+    }
+
+    /// Returns an external Orchard address of a spending key that no test holds funds for.
+    fn orchard_recipient() -> orchard::Address {
+        orchard::keys::FullViewingKey::from(
+            &orchard::keys::SpendingKey::from_bytes([7; 32]).unwrap(),
+        )
+        .address_at(0u32, orchard::keys::Scope::External)
+    }
+
+    #[test]
+    fn check_reports_all_violations() {
+        use crate::transaction::{fees::fixed, TxVersion};
+
+        let nu5_height = TEST_NETWORK.activation_height(NetworkUpgrade::Nu5).unwrap();
+        let build_config = BuildConfig::Standard {
+            sapling_anchor: None,
+            orchard_anchor: Some(orchard::Anchor::empty_tree()),
+        };
+        let fee_rule = fixed::FeeRule::non_standard(NonNegativeAmount::const_from_u64(10000));
+
+        // An empty transaction must still pay the fee.
+        let mut builder = Builder::new(TEST_NETWORK, nu5_height, build_config);
+        assert_matches!(
+            &builder.check(&fee_rule).unwrap_err()[..],
+            [Error::InsufficientFunds(amount)] if *amount == Amount::const_from_i64(10000)
+        );
+
+        // A v4 transaction cannot contain the Orchard output, and nothing pays for it.
+        let recipient = orchard_recipient();
+        builder
+            .add_orchard_output::<Infallible>(None, recipient, 50000, MemoBytes::empty())
+            .unwrap();
+        builder
+            .set_tx_version::<Infallible>(TxVersion::Sapling)
+            .unwrap();
+        assert_matches!(
+            &builder.check(&fee_rule).unwrap_err()[..],
+            [
+                Error::InsufficientFunds(amount),
+                Error::UnsupportedTxVersion(TxVersion::Sapling, _),
+            ] if *amount == Amount::const_from_i64(60000)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "transparent-inputs")]
+    fn authorize_independently_built_bundles() {
+        use crate::consensus::BranchId;
+        use crate::transaction::{
+            builder::{authorize_transaction, TransparentBuilder},
+            TransactionData, TxVersion, Unauthorized,
+        };
+
+        let nu5_height = TEST_NETWORK.activation_height(NetworkUpgrade::Nu5).unwrap();
+
+        let (sk, outpoint, prev_coin) = transparent_prev_coin();
+        let mut transparent_builder = TransparentBuilder::empty();
+        transparent_builder
+            .add_input(sk, outpoint, prev_coin)
+            .unwrap();
+
+        // An Orchard bundle constructed without the transaction builder.
+        let recipient = orchard_recipient();
+        let mut orchard_builder = orchard::builder::Builder::new(
+            orchard::builder::BundleType::DEFAULT,
+            orchard::Anchor::empty_tree(),
+        );
+        orchard_builder
+            .add_output(
+                None,
+                recipient,
+                orchard::value::NoteValue::from_raw(40000),
+                None,
+            )
+            .unwrap();
+        let (orchard_bundle, _) = orchard_builder.build::<Amount>(OsRng).unwrap().unwrap();
+
+        let unauthed_tx = TransactionData::<Unauthorized>::from_parts(
+            TxVersion::Zip225,
+            BranchId::Nu5,
+            0,
+            nu5_height + DEFAULT_TX_EXPIRY_DELTA,
+            transparent_builder.build(),
+            None,
+            None,
+            Some(orchard_bundle),
+        );
+        let tx = authorize_transaction::<_, Infallible>(
+            unauthed_tx,
+            OsRng,
+            &[],
+            &orchard::circuit::ProvingKey::build(),
+            &[],
+            #[cfg(zcash_unstable = "zfuture")]
+            vec![],
+        )
+        .unwrap();
+
+        assert_eq!(tx.transparent_bundle().unwrap().vin.len(), 1);
+        assert_eq!(
+            tx.orchard_bundle().unwrap().value_balance(),
+            &Amount::const_from_i64(-40000)
+        );
+
+        let mut data = vec![];
+        tx.write(&mut data).unwrap();
+        let parsed = Transaction::read(&data[..], BranchId::Nu5).unwrap();
+        assert_eq!(parsed.txid(), tx.txid());
+    }
+
+    #[test]
+    #[cfg(feature = "transparent-inputs")]
+    fn lock_time_and_version_selection() {
+        use crate::transaction::TxVersion;
+
+        let nu5_height = TEST_NETWORK.activation_height(NetworkUpgrade::Nu5).unwrap();
+        let build_config = BuildConfig::Standard {
+            sapling_anchor: None,
+            orchard_anchor: None,
+        };
+
+        // Versions that are not valid at the target height are rejected.
+        let mut builder = Builder::new(TEST_NETWORK, nu5_height, build_config);
+        assert_matches!(
+            builder.set_tx_version::<Infallible>(TxVersion::Overwinter),
+            Err(Error::UnsupportedTxVersion(TxVersion::Overwinter, _))
+        );
+        assert_eq!(builder.tx_version(), None);
+
+        // A v4 transaction may still be constructed after NU5 activation.
+        builder
+            .set_tx_version::<Infallible>(TxVersion::Sapling)
+            .unwrap();
+        builder.set_lock_time(nu5_height.into());
+
+        let (sk, outpoint, prev_coin) = transparent_prev_coin();
+        builder
+            .add_transparent_input(sk, outpoint, prev_coin)
+            .unwrap();
+        builder
+            .add_transparent_output(
+                &TransparentAddress::PublicKeyHash([0; 20]),
+                NonNegativeAmount::const_from_u64(40000),
+            )
+            .unwrap();
+
+        let res = builder.mock_build(OsRng).unwrap();
+        assert_eq!(res.transaction().version(), TxVersion::Sapling);
+        assert_eq!(res.transaction().lock_time(), u32::from(nu5_height));
+    }
+
     #[test]
     fn binding_sig_present_if_shielded_spend() {
         let extsk = ExtendedSpendingKey::master(&[]);
@@ -1056,6 +1731,161 @@ mod tests {
         assert!(res.transaction().sapling_bundle().is_some());
     }
 
+    #[test]
+    #[cfg(feature = "transparent-inputs")]
+    fn unsigned_spends_are_signed_by_authorizer() {
+        use rand::{CryptoRng, RngCore};
+        use ripemd::Ripemd160;
+        use sha2::{Digest, Sha256};
+
+        use crate::{
+            legacy::script::Instruction,
+            sapling::prover::mock::{MockOutputProver, MockSpendProver},
+            transaction::{
+                builder::{
+                    OrchardPartiallyAuthorized, SaplingPartiallyAuthorized, SpendAuthorizer,
+                },
+                fees::fixed,
+                sighash::SignatureHash,
+            },
+        };
+
+        struct TestAuthorizer {
+            ask: Option<sapling::keys::SpendAuthorizingKey>,
+            sk: secp256k1::SecretKey,
+        }
+
+        impl SpendAuthorizer for TestAuthorizer {
+            type Error = &'static str;
+
+            fn sign_transparent(
+                &self,
+                pubkey: &secp256k1::PublicKey,
+                sighash: &SignatureHash,
+            ) -> Result<secp256k1::ecdsa::Signature, Self::Error> {
+                let secp = secp256k1::Secp256k1::new();
+                if pubkey != &secp256k1::PublicKey::from_secret_key(&secp, &self.sk) {
+                    return Err("unknown transparent key");
+                }
+                let msg = secp256k1::Message::from_slice(sighash.as_ref()).unwrap();
+                Ok(secp.sign_ecdsa(&msg, &self.sk))
+            }
+
+            fn sign_sapling<R: RngCore + CryptoRng>(
+                &self,
+                rng: &mut R,
+                _: &[u8; 32],
+                bundle: SaplingPartiallyAuthorized,
+            ) -> Result<SaplingPartiallyAuthorized, Self::Error> {
+                self.ask
+                    .as_ref()
+                    .map(|ask| bundle.sign(rng, ask))
+                    .ok_or("declined")
+            }
+
+            fn sign_orchard<R: RngCore + CryptoRng>(
+                &self,
+                _: &mut R,
+                _: &[u8; 32],
+                bundle: OrchardPartiallyAuthorized,
+            ) -> Result<OrchardPartiallyAuthorized, Self::Error> {
+                Ok(bundle)
+            }
+        }
+
+        let extsk = ExtendedSpendingKey::master(&[]);
+        let to = extsk
+            .to_diversifiable_full_viewing_key()
+            .default_address()
+            .1;
+        let note = to.create_note(
+            sapling::value::NoteValue::from_raw(50000),
+            Rseed::BeforeZip212(jubjub::Fr::random(OsRng)),
+        );
+        let mut tree = CommitmentTree::<Node, 32>::empty();
+        tree.append(Node::from_cmu(&note.cmu())).unwrap();
+        let witness = IncrementalWitness::from_tree(tree);
+
+        let secp = secp256k1::Secp256k1::new();
+        let sk = secp256k1::SecretKey::from_slice(&[1; 32]).unwrap();
+        let pubkey = secp256k1::PublicKey::from_secret_key(&secp, &sk);
+        let coin = TxOut {
+            value: NonNegativeAmount::const_from_u64(50000),
+            script_pubkey: TransparentAddress::PublicKeyHash(
+                Ripemd160::digest(Sha256::digest(pubkey.serialize())).into(),
+            )
+            .script(),
+        };
+
+        let tx_height = TEST_NETWORK
+            .activation_height(NetworkUpgrade::Sapling)
+            .unwrap();
+        let new_builder = || {
+            let build_config = BuildConfig::Standard {
+                sapling_anchor: Some(witness.root().into()),
+                orchard_anchor: None,
+            };
+            let mut builder = Builder::new(TEST_NETWORK, tx_height, build_config);
+            builder
+                .add_unsigned_sapling_spend::<Infallible, Infallible>(
+                    &extsk,
+                    note.clone(),
+                    witness.path().unwrap(),
+                )
+                .unwrap();
+            builder
+                .add_unsigned_transparent_input(pubkey, OutPoint::new([0u8; 32], 1), coin.clone())
+                .unwrap();
+            builder
+                .add_transparent_output(
+                    &TransparentAddress::PublicKeyHash([0; 20]),
+                    NonNegativeAmount::const_from_u64(90000),
+                )
+                .unwrap();
+            builder
+        };
+        let build = |authorizer: &TestAuthorizer| {
+            new_builder().build_with_authorizer(
+                OsRng,
+                &MockSpendProver,
+                &MockOutputProver,
+                &fixed::FeeRule::standard(),
+                authorizer,
+            )
+        };
+
+        // The builder does not hold the spend authorizing key, so it cannot sign the
+        // Sapling spend by itself.
+        assert_matches!(new_builder().mock_build(OsRng), Err(Error::SaplingBuild(_)));
+
+        // Errors from the authorizer are propagated.
+        assert_matches!(
+            build(&TestAuthorizer { ask: None, sk }),
+            Err(Error::SpendAuthorization("declined"))
+        );
+
+        let tx = build(&TestAuthorizer {
+            ask: Some(extsk.expsk.ask.clone()),
+            sk,
+        })
+        .unwrap()
+        .transaction;
+        assert!(tx.sapling_bundle().is_some());
+
+        // The transparent input carries the authorizer's signature and public key.
+        let pushes = tx.transparent_bundle().unwrap().vin[0]
+            .script_sig
+            .instructions()
+            .map(|i| match i.unwrap() {
+                Instruction::PushBytes(data) => data.to_vec(),
+                _ => panic!("scriptSig must be push-only"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(pushes.len(), 2);
+        assert_eq!(pushes[0].last(), Some(&SIGHASH_ALL));
+        assert_eq!(pushes[1], pubkey.serialize());
+    }
+
     #[test]
     fn fails_on_negative_change() {
         use crate::transaction::fees::zip317::MINIMUM_FEE;
@@ -1213,4 +2043,315 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn transparent_data_output() {
+        use crate::{
+            legacy::script::ScriptKind,
+            transaction::{components::transparent::builder, fees::zip317},
+        };
+
+        let tx_height = TEST_NETWORK.activation_height(NetworkUpgrade::Nu5).unwrap();
+        let build_config = BuildConfig::Standard {
+            sapling_anchor: None,
+            orchard_anchor: None,
+        };
+        let mut builder = Builder::new(TEST_NETWORK, tx_height, build_config);
+
+        assert_eq!(
+            builder.add_transparent_data_output(&[0; 221]),
+            Err(builder::Error::NullDataTooLarge(221))
+        );
+
+        builder
+            .add_transparent_output(
+                &TransparentAddress::PublicKeyHash([0; 20]),
+                NonNegativeAmount::const_from_u64(20000),
+            )
+            .unwrap();
+        builder.add_transparent_data_output(&[7; 220]).unwrap();
+        assert_eq!(
+            builder.add_transparent_data_output(b"again"),
+            Err(builder::Error::MultipleNullDataOutputs)
+        );
+
+        let outputs = builder.transparent_outputs();
+        assert_eq!(outputs.len(), 2);
+        assert_eq!(outputs[1].value, NonNegativeAmount::ZERO);
+        assert_eq!(outputs[1].script_pubkey.kind(), ScriptKind::NullData);
+
+        // The P2PKH output is 34 bytes, and the data output is 8 + 1 + 223 = 232 bytes,
+        // so the outputs account for ceil(266 / 34) = 8 logical actions.
+        assert_eq!(
+            builder.get_fee(&zip317::FeeRule::standard()).unwrap(),
+            (zip317::MARGINAL_FEE * 8).unwrap()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "transparent-inputs")]
+    fn p2sh_multisig_input() {
+        use crate::consensus::NetworkUpgrade;
+        use crate::legacy::{script::Instruction, Script};
+        use crate::transaction::components::transparent::builder::{
+            self, add_p2sh_multisig_signature, p2sh_multisig_signature_hash,
+            sign_p2sh_multisig_input,
+        };
+
+        let secp = secp256k1::Secp256k1::new();
+        let sks =
+            [[1; 32], [2; 32], [3; 32]].map(|k| secp256k1::SecretKey::from_slice(&k).unwrap());
+        let pubkeys = sks.map(|sk| secp256k1::PublicKey::from_secret_key(&secp, &sk).serialize());
+        let redeem_script = Script::multisig(2, &pubkeys).unwrap();
+        let prevouts = vec![TxOut {
+            value: NonNegativeAmount::const_from_u64(50000),
+            script_pubkey: TransparentAddress::from_redeem_script(&redeem_script).script(),
+        }];
+
+        let tx_height = TEST_NETWORK.activation_height(NetworkUpgrade::Nu5).unwrap();
+        let build_config = BuildConfig::Standard {
+            sapling_anchor: None,
+            orchard_anchor: None,
+        };
+        let mut builder = Builder::new(TEST_NETWORK, tx_height, build_config);
+
+        // The coin must be sent to the P2SH address of the redeem script.
+        let wrong_script = Script::multisig(1, &pubkeys).unwrap();
+        assert_eq!(
+            builder.add_transparent_p2sh_multisig_input(
+                wrong_script,
+                &sks[..1],
+                OutPoint::new([0u8; 32], 1),
+                prevouts[0].clone(),
+            ),
+            Err(builder::Error::InvalidAddress)
+        );
+
+        // Sign with the last key only; the input is only partially signed.
+        builder
+            .add_transparent_p2sh_multisig_input(
+                redeem_script.clone(),
+                &sks[2..],
+                OutPoint::new([0u8; 32], 1),
+                prevouts[0].clone(),
+            )
+            .unwrap();
+        builder
+            .add_transparent_output(
+                &TransparentAddress::PublicKeyHash([0; 20]),
+                NonNegativeAmount::const_from_u64(40000),
+            )
+            .unwrap();
+        let tx = builder.mock_build(OsRng).unwrap().transaction;
+
+        let signature_count = |tx: &Transaction| {
+            let script_sig = &tx.transparent_bundle().unwrap().vin[0].script_sig;
+            let pushes = script_sig
+                .instructions()
+                .map(|i| match i.unwrap() {
+                    Instruction::PushBytes(data) => data.to_vec(),
+                    _ => panic!("scriptSig must be push-only"),
+                })
+                .collect::<Vec<_>>();
+            assert!(pushes[0].is_empty());
+            assert_eq!(pushes[pushes.len() - 1], redeem_script.0);
+            pushes.len() - 2
+        };
+        assert_eq!(signature_count(&tx), 1);
+
+        // A key that is not part of the redeem script cannot sign.
+        let other = secp256k1::SecretKey::from_slice(&[4; 32]).unwrap();
+        assert_matches!(
+            sign_p2sh_multisig_input(&tx, 0, &prevouts, &redeem_script, &other),
+            Err(builder::Error::InvalidSignature)
+        );
+        assert_matches!(
+            sign_p2sh_multisig_input(&tx, 1, &prevouts, &redeem_script, &sks[0]),
+            Err(builder::Error::InvalidInputIndex(1))
+        );
+
+        // A second signer produces its signature externally.
+        let sighash = p2sh_multisig_signature_hash(&tx, 0, &prevouts, &redeem_script).unwrap();
+        let msg = secp256k1::Message::from_slice(sighash.as_ref()).unwrap();
+        let mut sig = secp.sign_ecdsa(&msg, &sks[0]).serialize_der().to_vec();
+        sig.push(SIGHASH_ALL);
+        let tx = add_p2sh_multisig_signature(&tx, 0, &prevouts, &redeem_script, &sig).unwrap();
+        assert_eq!(signature_count(&tx), 2);
+
+        // Signatures are ordered by the position of their keys in the redeem script.
+        let script_sig = &tx.transparent_bundle().unwrap().vin[0].script_sig;
+        assert_eq!(script_sig.0[1] as usize, sig.len());
+        assert_eq!(&script_sig.0[2..2 + sig.len()], &sig[..]);
+
+        // Once the threshold is met, further signatures are not added.
+        let complete =
+            sign_p2sh_multisig_input(&tx, 0, &prevouts, &redeem_script, &sks[1]).unwrap();
+        assert_eq!(complete.txid(), tx.txid());
+    }
+
+    #[test]
+    #[cfg(feature = "transparent-inputs")]
+    fn p2sh_multisig_input_zip317_fee() {
+        use crate::legacy::Script;
+        use crate::transaction::fees::zip317::{FeeRule, MARGINAL_FEE};
+
+        let secp = secp256k1::Secp256k1::new();
+        let sks =
+            [[1; 32], [2; 32], [3; 32]].map(|k| secp256k1::SecretKey::from_slice(&k).unwrap());
+        let pubkeys = sks.map(|sk| secp256k1::PublicKey::from_secret_key(&secp, &sk).serialize());
+        let redeem_script = Script::multisig(2, &pubkeys).unwrap();
+        let coin = TxOut {
+            value: NonNegativeAmount::const_from_u64(50000),
+            script_pubkey: TransparentAddress::from_redeem_script(&redeem_script).script(),
+        };
+
+        let tx_height = TEST_NETWORK.activation_height(NetworkUpgrade::Nu5).unwrap();
+        let build_config = BuildConfig::Standard {
+            sapling_anchor: None,
+            orchard_anchor: None,
+        };
+        let mut builder = Builder::new(TEST_NETWORK, tx_height, build_config);
+        for n in 0..2 {
+            builder
+                .add_transparent_p2sh_multisig_input(
+                    redeem_script.clone(),
+                    &sks[..2],
+                    OutPoint::new([0u8; 32], n),
+                    coin.clone(),
+                )
+                .unwrap();
+        }
+        builder
+            .add_transparent_output(
+                &TransparentAddress::PublicKeyHash([0; 20]),
+                NonNegativeAmount::const_from_u64(80000),
+            )
+            .unwrap();
+
+        // Each fully signed 2-of-3 input is `OP_0 <sig> <sig> <redeem_script>`, at most
+        // 299 bytes, so the two inputs together account for ceil(598 / 150) = 4 logical
+        // actions rather than the 2 that would be charged for P2PKH inputs.
+        assert_eq!(
+            builder.get_fee(&FeeRule::standard()).unwrap(),
+            (MARGINAL_FEE * 4).unwrap()
+        );
+
+        // The signed inputs are no larger than the sizes used for the fee.
+        let tx = builder.mock_build(OsRng).unwrap().transaction;
+        for t_in in &tx.transparent_bundle().unwrap().vin {
+            assert!(t_in.script_sig.0.len() <= 256);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "transparent-inputs")]
+    fn htlc_input() {
+        use crate::consensus::NetworkUpgrade;
+        use crate::legacy::script::{Htlc, HtlcSpend};
+        use crate::transaction::components::transparent::builder;
+        use ripemd::Ripemd160;
+        use sha2::{Digest, Sha256};
+
+        let secp = secp256k1::Secp256k1::new();
+        let [recipient_sk, refund_sk] =
+            [[1; 32], [2; 32]].map(|k| secp256k1::SecretKey::from_slice(&k).unwrap());
+        let [recipient, refund] = [recipient_sk, refund_sk].map(|sk| {
+            let pubkey = secp256k1::PublicKey::from_secret_key(&secp, &sk).serialize();
+            <[u8; 20]>::from(Ripemd160::digest(Sha256::digest(pubkey)))
+        });
+        let preimage = [9; 32];
+        let tx_height = TEST_NETWORK.activation_height(NetworkUpgrade::Nu5).unwrap();
+        let lock_time = u32::from(tx_height) + 100;
+        let htlc = Htlc::new(
+            Sha256::digest(preimage).into(),
+            recipient,
+            refund,
+            lock_time,
+        );
+
+        let build_config = BuildConfig::Standard {
+            sapling_anchor: None,
+            orchard_anchor: None,
+        };
+
+        // Fund the contract.
+        let mut builder = Builder::new(TEST_NETWORK, tx_height, build_config);
+        builder
+            .add_transparent_htlc_output(&htlc, NonNegativeAmount::const_from_u64(50000))
+            .unwrap();
+        let funded = builder.transparent_builder.outputs()[0].clone();
+        assert_eq!(funded.script_pubkey.address(), Some(htlc.address()));
+
+        // The recipient claims the coin with the preimage.
+        let mut builder = Builder::new(TEST_NETWORK, tx_height, build_config);
+        assert_eq!(
+            builder.add_transparent_htlc_input(
+                &htlc,
+                HtlcSpend::Claim([8; 32]),
+                recipient_sk,
+                OutPoint::new([0u8; 32], 0),
+                funded.clone(),
+            ),
+            Err(builder::Error::InvalidPreimage)
+        );
+        assert_eq!(
+            builder.add_transparent_htlc_input(
+                &htlc,
+                HtlcSpend::Claim(preimage),
+                refund_sk,
+                OutPoint::new([0u8; 32], 0),
+                funded.clone(),
+            ),
+            Err(builder::Error::InvalidSignature)
+        );
+        builder
+            .add_transparent_htlc_input(
+                &htlc,
+                HtlcSpend::Claim(preimage),
+                recipient_sk,
+                OutPoint::new([0u8; 32], 0),
+                funded.clone(),
+            )
+            .unwrap();
+        builder
+            .add_transparent_output(
+                &TransparentAddress::PublicKeyHash(recipient),
+                NonNegativeAmount::const_from_u64(40000),
+            )
+            .unwrap();
+        let tx = builder.mock_build(OsRng).unwrap().transaction;
+        let txin = &tx.transparent_bundle().unwrap().vin[0];
+        assert_eq!(txin.sequence, u32::MAX);
+        assert_eq!(
+            txin.script_sig.htlc_spend(),
+            Some((htlc, HtlcSpend::Claim(preimage)))
+        );
+        assert_eq!(tx.lock_time(), 0);
+
+        // The refund sets the lock time of the transaction and a non-final sequence number.
+        let mut builder = Builder::new(TEST_NETWORK, tx_height, build_config);
+        builder
+            .add_transparent_htlc_input(
+                &htlc,
+                HtlcSpend::Refund,
+                refund_sk,
+                OutPoint::new([0u8; 32], 0),
+                funded,
+            )
+            .unwrap();
+        builder
+            .add_transparent_output(
+                &TransparentAddress::PublicKeyHash(refund),
+                NonNegativeAmount::const_from_u64(40000),
+            )
+            .unwrap();
+        let tx = builder.mock_build(OsRng).unwrap().transaction;
+        let txin = &tx.transparent_bundle().unwrap().vin[0];
+        assert_eq!(txin.sequence, u32::MAX - 1);
+        assert_eq!(
+            txin.script_sig.htlc_spend(),
+            Some((htlc, HtlcSpend::Refund))
+        );
+        assert_eq!(tx.lock_time(), lock_time);
+    }
 }
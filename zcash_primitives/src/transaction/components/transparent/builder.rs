@@ -1,9 +1,13 @@
 //! Types and functions for building transparent transaction components.
 
+use std::convert::Infallible;
 use std::fmt;
 
 use crate::{
-    legacy::{Script, TransparentAddress},
+    legacy::{
+        script::{ScriptKind, MAX_NULL_DATA_SCRIPT_SIZE},
+        Script, TransparentAddress,
+    },
     transaction::{
         components::{
             amount::{Amount, BalanceError, NonNegativeAmount},
@@ -15,20 +19,53 @@ use crate::{
 
 #[cfg(feature = "transparent-inputs")]
 use {
-    crate::transaction::{
-        self as tx,
-        components::transparent::OutPoint,
-        sighash::{signature_hash, SignableInput, SIGHASH_ALL},
-        TransactionData, TxDigests,
+    crate::{
+        legacy::script::{Htlc, HtlcSpend, Instruction, OpCode, HTLC_PREIMAGE_SIZE},
+        sapling,
+        transaction::{
+            self as tx,
+            builder::SpendAuthorizer,
+            components::transparent::OutPoint,
+            fees::zip317::P2PKH_STANDARD_INPUT_SIZE,
+            sighash::{signature_hash, SignableInput, SignatureHash, SIGHASH_ALL},
+            txid::TxIdDigester,
+            Transaction, TransactionData, TxDigests,
+        },
     },
     blake2b_simd::Hash as Blake2bHash,
     sha2::Digest,
+    zcash_encoding::CompactSize,
 };
 
+#[cfg(all(feature = "transparent-inputs", zcash_unstable = "zfuture"))]
+use crate::transaction::components::tze;
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum Error {
     InvalidAddress,
     InvalidAmount,
+    /// The null-data script for the given payload would exceed
+    /// [`MAX_NULL_DATA_SCRIPT_SIZE`] bytes.
+    NullDataTooLarge(usize),
+    /// The transaction already contains a null-data output; transactions with more than
+    /// one such output are not relayed.
+    MultipleNullDataOutputs,
+    /// The redeem script of a P2SH input is not a supported multisig script.
+    InvalidRedeemScript,
+    /// A signing key or signature does not correspond to any of the public keys of a
+    /// multisig redeem script.
+    InvalidSignature,
+    /// The transaction does not have a transparent input at the given index.
+    InvalidInputIndex(usize),
+    /// The number of previous outputs provided does not match the number of transparent
+    /// inputs of the transaction.
+    PrevoutCountMismatch {
+        expected: usize,
+        actual: usize,
+    },
+    /// The preimage provided to claim a hash time-locked contract does not match its
+    /// payment hash.
+    InvalidPreimage,
 }
 
 impl fmt::Display for Error {
@@ -36,15 +73,80 @@ impl fmt::Display for Error {
         match self {
             Error::InvalidAddress => write!(f, "Invalid address"),
             Error::InvalidAmount => write!(f, "Invalid amount"),
+            Error::NullDataTooLarge(len) => write!(
+                f,
+                "Null-data payload of {} bytes exceeds the maximum script size of {} bytes",
+                len, MAX_NULL_DATA_SCRIPT_SIZE
+            ),
+            Error::MultipleNullDataOutputs => {
+                write!(f, "A transaction may contain at most one null-data output")
+            }
+            Error::InvalidRedeemScript => {
+                write!(f, "Redeem script is not a supported multisig script")
+            }
+            Error::InvalidSignature => write!(
+                f,
+                "Signature or signing key does not match any key of the redeem script"
+            ),
+            Error::InvalidInputIndex(index) => {
+                write!(f, "Transaction has no transparent input at index {}", index)
+            }
+            Error::PrevoutCountMismatch { expected, actual } => write!(
+                f,
+                "Expected {} previous outputs, but {} were provided",
+                expected, actual
+            ),
+            Error::InvalidPreimage => {
+                write!(
+                    f,
+                    "Preimage does not match the payment hash of the contract"
+                )
+            }
         }
     }
 }
 
+/// The maximum size of a DER-encoded ECDSA signature with the sighash type appended.
+#[cfg(feature = "transparent-inputs")]
+const MAX_SIGNATURE_SIZE: usize = 73;
+
+/// The keys with which a transparent input will be signed.
+#[cfg(feature = "transparent-inputs")]
+#[derive(Debug, Clone)]
+enum InputSigner {
+    /// A P2PKH input, spent with a single key.
+    PubKeyHash {
+        sk: secp256k1::SecretKey,
+        pubkey: [u8; secp256k1::constants::PUBLIC_KEY_SIZE],
+    },
+    /// A P2PKH input whose signing key is not held by the builder. The signature is
+    /// obtained from a [`SpendAuthorizer`] when the transaction is authorized.
+    ///
+    /// [`SpendAuthorizer`]: crate::transaction::builder::SpendAuthorizer
+    ExternalPubKeyHash { pubkey: secp256k1::PublicKey },
+    /// A P2SH input with a multisig redeem script. `sks` holds the keys available to this
+    /// builder, ordered by the position of their public keys in the redeem script; it may
+    /// contain fewer than `threshold` keys, in which case the remaining signatures must be
+    /// added by the other signers using [`add_p2sh_multisig_signature`].
+    Multisig {
+        redeem_script: Script,
+        threshold: usize,
+        sks: Vec<secp256k1::SecretKey>,
+    },
+    /// A P2SH input with a hash time-locked contract redeem script, spent through the
+    /// given branch with the key of its recipient or refund address.
+    Htlc {
+        redeem_script: Script,
+        spend: HtlcSpend,
+        sk: secp256k1::SecretKey,
+        pubkey: [u8; secp256k1::constants::PUBLIC_KEY_SIZE],
+    },
+}
+
 #[cfg(feature = "transparent-inputs")]
 #[derive(Debug, Clone)]
 pub struct TransparentInputInfo {
-    sk: secp256k1::SecretKey,
-    pubkey: [u8; secp256k1::constants::PUBLIC_KEY_SIZE],
+    signer: InputSigner,
     utxo: OutPoint,
     coin: TxOut,
 }
@@ -58,6 +160,97 @@ impl TransparentInputInfo {
     pub fn coin(&self) -> &TxOut {
         &self.coin
     }
+
+    /// Returns an upper bound on the size of this input once it has been fully signed,
+    /// in bytes.
+    ///
+    /// P2PKH inputs are assumed to have the standard size defined by ZIP 317.
+    pub fn serialized_size(&self) -> usize {
+        match &self.signer {
+            InputSigner::PubKeyHash { .. } | InputSigner::ExternalPubKeyHash { .. } => {
+                P2PKH_STANDARD_INPUT_SIZE
+            }
+            InputSigner::Multisig {
+                redeem_script,
+                threshold,
+                ..
+            } => {
+                // `OP_0 <sig>... <redeem_script>`
+                let script_sig_len =
+                    1 + threshold * (1 + MAX_SIGNATURE_SIZE) + push_size(redeem_script.0.len());
+                // outpoint, scriptSig, sequence
+                36 + CompactSize::serialized_size(script_sig_len) + script_sig_len + 4
+            }
+            InputSigner::Htlc {
+                redeem_script,
+                spend,
+                ..
+            } => {
+                // `<sig> <pubkey> <preimage> OP_1 <redeem_script>` or
+                // `<sig> <pubkey> OP_0 <redeem_script>`
+                let branch_len = match spend {
+                    HtlcSpend::Claim(_) => 1 + HTLC_PREIMAGE_SIZE + 1,
+                    HtlcSpend::Refund => 1,
+                };
+                let script_sig_len = 1
+                    + MAX_SIGNATURE_SIZE
+                    + 1
+                    + secp256k1::constants::PUBLIC_KEY_SIZE
+                    + branch_len
+                    + push_size(redeem_script.0.len());
+                // outpoint, scriptSig, sequence
+                36 + CompactSize::serialized_size(script_sig_len) + script_sig_len + 4
+            }
+        }
+    }
+
+    /// Returns the `nSequence` value of this input.
+    fn sequence(&self) -> u32 {
+        match &self.signer {
+            // `OP_CHECKLOCKTIMEVERIFY` fails for inputs with a final sequence number.
+            InputSigner::Htlc {
+                spend: HtlcSpend::Refund,
+                ..
+            } => u32::MAX - 1,
+            _ => u32::MAX,
+        }
+    }
+}
+
+/// Returns the number of bytes used by a push of `len` bytes of data.
+#[cfg(feature = "transparent-inputs")]
+fn push_size(len: usize) -> usize {
+    let prefix = if len < 0x4c {
+        1
+    } else if len <= 0xff {
+        2
+    } else if len <= 0xffff {
+        3
+    } else {
+        5
+    };
+    prefix + len
+}
+
+/// Ensures that the RIPEMD-160 digest of the given public key matches that of the P2PKH
+/// address to which `coin` may be spent.
+#[cfg(feature = "transparent-inputs")]
+fn check_pubkey_hash(
+    pubkey: &[u8; secp256k1::constants::PUBLIC_KEY_SIZE],
+    coin: &TxOut,
+) -> Result<(), Error> {
+    match coin.script_pubkey.address() {
+        Some(TransparentAddress::PublicKeyHash(hash)) => {
+            use ripemd::Ripemd160;
+            use sha2::Sha256;
+
+            if hash[..] != Ripemd160::digest(Sha256::digest(pubkey))[..] {
+                return Err(Error::InvalidAddress);
+            }
+            Ok(())
+        }
+        _ => Err(Error::InvalidAddress),
+    }
 }
 
 pub struct TransparentBuilder {
@@ -112,25 +305,141 @@ impl TransparentBuilder {
         utxo: OutPoint,
         coin: TxOut,
     ) -> Result<(), Error> {
-        // Ensure that the RIPEMD-160 digest of the public key associated with the
-        // provided secret key matches that of the address to which the provided
-        // output may be spent.
         let pubkey = secp256k1::PublicKey::from_secret_key(&self.secp, &sk).serialize();
-        match coin.script_pubkey.address() {
-            Some(TransparentAddress::PublicKeyHash(hash)) => {
-                use ripemd::Ripemd160;
-                use sha2::Sha256;
+        check_pubkey_hash(&pubkey, &coin)?;
+
+        self.inputs.push(TransparentInputInfo {
+            signer: InputSigner::PubKeyHash { sk, pubkey },
+            utxo,
+            coin,
+        });
+
+        Ok(())
+    }
+
+    /// Adds a coin sent to the P2PKH address of `pubkey` to be spent by the transaction,
+    /// without providing its signing key.
+    ///
+    /// The input is signed by the [`SpendAuthorizer`] given to
+    /// [`Bundle::apply_external_signatures`]; [`Bundle::apply_signatures`] leaves it with
+    /// an empty `scriptSig`.
+    ///
+    /// [`SpendAuthorizer`]: crate::transaction::builder::SpendAuthorizer
+    #[cfg(feature = "transparent-inputs")]
+    pub fn add_unsigned_input(
+        &mut self,
+        pubkey: secp256k1::PublicKey,
+        utxo: OutPoint,
+        coin: TxOut,
+    ) -> Result<(), Error> {
+        check_pubkey_hash(&pubkey.serialize(), &coin)?;
+
+        self.inputs.push(TransparentInputInfo {
+            signer: InputSigner::ExternalPubKeyHash { pubkey },
+            utxo,
+            coin,
+        });
+
+        Ok(())
+    }
+
+    /// Adds a coin sent to a P2SH multisig address to be spent by the transaction.
+    ///
+    /// `redeem_script` must be a multisig script as constructed by [`Script::multisig`],
+    /// and `coin` must be sent to the corresponding P2SH address. `sks` are the keys held
+    /// by this signer, each of which must correspond to one of the public keys of the
+    /// redeem script. At most `threshold` of them will be used to sign the input; if fewer
+    /// are given, the built transaction will be only partially signed, and the remaining
+    /// signatures must be added with [`add_p2sh_multisig_signature`] or
+    /// [`sign_p2sh_multisig_input`] before it can be broadcast.
+    #[cfg(feature = "transparent-inputs")]
+    pub fn add_p2sh_multisig_input(
+        &mut self,
+        redeem_script: Script,
+        sks: &[secp256k1::SecretKey],
+        utxo: OutPoint,
+        coin: TxOut,
+    ) -> Result<(), Error> {
+        if coin.script_pubkey.address()
+            != Some(TransparentAddress::from_redeem_script(&redeem_script))
+        {
+            return Err(Error::InvalidAddress);
+        }
+        let (threshold, pubkeys) = redeem_script
+            .multisig_keys()
+            .ok_or(Error::InvalidRedeemScript)?;
+
+        let mut keyed = sks
+            .iter()
+            .map(|sk| {
+                let pubkey = secp256k1::PublicKey::from_secret_key(&self.secp, sk).serialize();
+                pubkeys
+                    .iter()
+                    .position(|pk| pk == &pubkey)
+                    .map(|i| (i, *sk))
+                    .ok_or(Error::InvalidSignature)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        keyed.sort_by_key(|(i, _)| *i);
+        keyed.dedup_by_key(|(i, _)| *i);
+
+        self.inputs.push(TransparentInputInfo {
+            signer: InputSigner::Multisig {
+                redeem_script,
+                threshold,
+                sks: keyed.into_iter().map(|(_, sk)| sk).collect(),
+            },
+            utxo,
+            coin,
+        });
+
+        Ok(())
+    }
+
+    /// Adds a coin locked by a hash time-locked contract to be spent by the transaction.
+    ///
+    /// `coin` must be sent to the P2SH address of `htlc` (see [`Htlc::address`]). When
+    /// claiming the coin, `sk` must be the key of the contract's recipient and the preimage
+    /// must match its payment hash; when refunding the coin, `sk` must be the key of the
+    /// contract's refund address, and the transaction's `nLockTime` must be at least the
+    /// contract's lock time.
+    #[cfg(feature = "transparent-inputs")]
+    pub fn add_htlc_input(
+        &mut self,
+        htlc: &Htlc,
+        spend: HtlcSpend,
+        sk: secp256k1::SecretKey,
+        utxo: OutPoint,
+        coin: TxOut,
+    ) -> Result<(), Error> {
+        use ripemd::Ripemd160;
+        use sha2::Sha256;
+
+        if coin.script_pubkey.address() != Some(htlc.address()) {
+            return Err(Error::InvalidAddress);
+        }
 
-                if hash[..] != Ripemd160::digest(Sha256::digest(pubkey))[..] {
-                    return Err(Error::InvalidAddress);
+        let pubkey = secp256k1::PublicKey::from_secret_key(&self.secp, &sk).serialize();
+        let key_hash = match spend {
+            HtlcSpend::Claim(preimage) => {
+                if !htlc.is_preimage(&preimage) {
+                    return Err(Error::InvalidPreimage);
                 }
+                htlc.recipient()
             }
-            _ => return Err(Error::InvalidAddress),
+            HtlcSpend::Refund => htlc.refund(),
+        };
+        if key_hash[..] != Ripemd160::digest(Sha256::digest(pubkey))[..] {
+            return Err(Error::InvalidSignature);
         }
 
         self.inputs.push(TransparentInputInfo {
-            sk,
-            pubkey,
+            signer: InputSigner::Htlc {
+                redeem_script: Script::htlc(htlc),
+                spend,
+                sk,
+                pubkey,
+            },
             utxo,
             coin,
         });
@@ -138,6 +447,30 @@ impl TransparentBuilder {
         Ok(())
     }
 
+    /// Returns the minimum `nLockTime` value with which the transaction's inputs can be
+    /// spent, which is the greatest lock time of any hash time-locked contract that is
+    /// being refunded.
+    #[cfg(feature = "transparent-inputs")]
+    pub fn min_lock_time(&self) -> Option<u32> {
+        self.inputs
+            .iter()
+            .filter_map(|input| match &input.signer {
+                InputSigner::Htlc {
+                    redeem_script,
+                    spend: HtlcSpend::Refund,
+                    ..
+                } => redeem_script.htlc_terms().map(|htlc| htlc.lock_time()),
+                _ => None,
+            })
+            .max()
+    }
+
+    /// Adds an output that locks `value` with the given hash time-locked contract.
+    #[cfg(feature = "transparent-inputs")]
+    pub fn add_htlc_output(&mut self, htlc: &Htlc, value: NonNegativeAmount) -> Result<(), Error> {
+        self.add_output(&htlc.address(), value)
+    }
+
     pub fn add_output(
         &mut self,
         to: &TransparentAddress,
@@ -151,6 +484,33 @@ impl TransparentBuilder {
         Ok(())
     }
 
+    /// Adds a zero-valued, provably unspendable `OP_RETURN` output committing to the given
+    /// data.
+    ///
+    /// Returns an error if the resulting script would not be relayed under the default
+    /// policy of `zcashd`, either because it is larger than [`MAX_NULL_DATA_SCRIPT_SIZE`]
+    /// or because a null-data output has already been added.
+    pub fn add_null_data_output(&mut self, data: &[u8]) -> Result<(), Error> {
+        let script_pubkey = Script::null_data(data);
+        if script_pubkey.0.len() > MAX_NULL_DATA_SCRIPT_SIZE {
+            return Err(Error::NullDataTooLarge(data.len()));
+        }
+        if self
+            .vout
+            .iter()
+            .any(|out| out.script_pubkey.kind() == ScriptKind::NullData)
+        {
+            return Err(Error::MultipleNullDataOutputs);
+        }
+
+        self.vout.push(TxOut {
+            value: NonNegativeAmount::ZERO,
+            script_pubkey,
+        });
+
+        Ok(())
+    }
+
     pub fn value_balance(&self) -> Result<Amount, BalanceError> {
         #[cfg(feature = "transparent-inputs")]
         let input_sum = self
@@ -178,7 +538,10 @@ impl TransparentBuilder {
         let vin: Vec<TxIn<Unauthorized>> = self
             .inputs
             .iter()
-            .map(|i| TxIn::new(i.utxo.clone()))
+            .map(|i| TxIn {
+                sequence: i.sequence(),
+                ..TxIn::new(i.utxo.clone())
+            })
             .collect();
 
         #[cfg(not(feature = "transparent-inputs"))]
@@ -239,11 +602,57 @@ impl TransparentAuthorizingContext for Unauthorized {
 }
 
 impl Bundle<Unauthorized> {
+    /// Signs the transparent inputs of the bundle with the keys provided to the builder.
+    ///
+    /// Inputs added with [`TransparentBuilder::add_unsigned_input`] are left with an empty
+    /// `scriptSig`.
     pub fn apply_signatures(
         self,
         #[cfg(feature = "transparent-inputs")] mtx: &TransactionData<tx::Unauthorized>,
         #[cfg(feature = "transparent-inputs")] txid_parts_cache: &TxDigests<Blake2bHash>,
     ) -> Bundle<Authorized> {
+        #[cfg(feature = "transparent-inputs")]
+        let result = self.sign_inputs::<Infallible, _>(mtx, txid_parts_cache, |_, _| Ok(None));
+
+        #[cfg(not(feature = "transparent-inputs"))]
+        let result = self.sign_inputs::<Infallible>();
+
+        match result {
+            Ok(bundle) => bundle,
+            Err(e) => match e {},
+        }
+    }
+
+    /// Signs the transparent inputs of the bundle with the keys provided to the builder,
+    /// obtaining the signatures for inputs added with
+    /// [`TransparentBuilder::add_unsigned_input`] from `authorizer`.
+    #[cfg(feature = "transparent-inputs")]
+    pub fn apply_external_signatures<A: SpendAuthorizer>(
+        self,
+        mtx: &TransactionData<tx::Unauthorized>,
+        txid_parts_cache: &TxDigests<Blake2bHash>,
+        authorizer: &A,
+    ) -> Result<Bundle<Authorized>, A::Error> {
+        self.sign_inputs(mtx, txid_parts_cache, |pubkey, sighash| {
+            authorizer.sign_transparent(pubkey, sighash).map(Some)
+        })
+    }
+
+    /// Signs the transparent inputs of the bundle, using `sign_external` to sign those
+    /// inputs for which the builder holds no key. An input is left with an empty
+    /// `scriptSig` if `sign_external` returns `None` for it.
+    fn sign_inputs<
+        E,
+        #[cfg(feature = "transparent-inputs")] F: FnMut(
+            &secp256k1::PublicKey,
+            &SignatureHash,
+        ) -> Result<Option<secp256k1::ecdsa::Signature>, E>,
+    >(
+        self,
+        #[cfg(feature = "transparent-inputs")] mtx: &TransactionData<tx::Unauthorized>,
+        #[cfg(feature = "transparent-inputs")] txid_parts_cache: &TxDigests<Blake2bHash>,
+        #[cfg(feature = "transparent-inputs")] mut sign_external: F,
+    ) -> Result<Bundle<Authorized>, E> {
         #[cfg(feature = "transparent-inputs")]
         let script_sigs = self
             .authorization
@@ -251,33 +660,83 @@ impl Bundle<Unauthorized> {
             .iter()
             .enumerate()
             .map(|(index, info)| {
+                // For P2PKH the script code is the scriptPubKey; for P2SH it is the
+                // redeem script.
+                let script_code = match &info.signer {
+                    InputSigner::PubKeyHash { .. } | InputSigner::ExternalPubKeyHash { .. } => {
+                        &info.coin.script_pubkey
+                    }
+                    InputSigner::Multisig { redeem_script, .. }
+                    | InputSigner::Htlc { redeem_script, .. } => redeem_script,
+                };
                 let sighash = signature_hash(
                     mtx,
                     &SignableInput::Transparent {
                         hash_type: SIGHASH_ALL,
                         index,
-                        script_code: &info.coin.script_pubkey, // for p2pkh, always the same as script_pubkey
+                        script_code,
                         script_pubkey: &info.coin.script_pubkey,
                         value: info.coin.value,
                     },
                     txid_parts_cache,
                 );
-
                 let msg = secp256k1::Message::from_slice(sighash.as_ref()).expect("32 bytes");
-                let sig = self.authorization.secp.sign_ecdsa(&msg, &info.sk);
 
-                // Signature has to have "SIGHASH_ALL" appended to it
-                let mut sig_bytes: Vec<u8> = sig.serialize_der()[..].to_vec();
-                sig_bytes.extend([SIGHASH_ALL]);
+                Ok(match &info.signer {
+                    InputSigner::PubKeyHash { sk, pubkey } => {
+                        let sig_bytes = sign(&self.authorization.secp, &msg, sk);
 
-                // P2PKH scriptSig
-                Script::default() << &sig_bytes[..] << &info.pubkey[..]
-            });
+                        // P2PKH scriptSig
+                        Script::default() << &sig_bytes[..] << &pubkey[..]
+                    }
+                    InputSigner::ExternalPubKeyHash { pubkey } => {
+                        match sign_external(pubkey, &sighash)? {
+                            Some(sig) => {
+                                Script::default()
+                                    << &encode_signature(&sig)[..]
+                                    << &pubkey.serialize()[..]
+                            }
+                            None => Script::default(),
+                        }
+                    }
+                    InputSigner::Multisig {
+                        redeem_script,
+                        threshold,
+                        sks,
+                    } => {
+                        let sigs = sks
+                            .iter()
+                            .take(*threshold)
+                            .map(|sk| sign(&self.authorization.secp, &msg, sk))
+                            .collect::<Vec<_>>();
+
+                        p2sh_multisig_script_sig(&sigs, redeem_script)
+                    }
+                    InputSigner::Htlc {
+                        redeem_script,
+                        spend,
+                        sk,
+                        pubkey,
+                    } => {
+                        let sig_bytes = sign(&self.authorization.secp, &msg, sk);
+
+                        let script_sig = Script::default() << &sig_bytes[..] << &pubkey[..];
+                        let script_sig = match spend {
+                            HtlcSpend::Claim(preimage) => {
+                                script_sig << &preimage[..] << OpCode::Op1
+                            }
+                            HtlcSpend::Refund => script_sig << &[][..],
+                        };
+                        script_sig << &redeem_script.0[..]
+                    }
+                })
+            })
+            .collect::<Result<Vec<_>, E>>()?;
 
         #[cfg(not(feature = "transparent-inputs"))]
-        let script_sigs = std::iter::empty::<Script>();
+        let script_sigs = Vec::<Script>::new();
 
-        transparent::Bundle {
+        Ok(transparent::Bundle {
             vin: self
                 .vin
                 .iter()
@@ -290,6 +749,355 @@ impl Bundle<Unauthorized> {
                 .collect(),
             vout: self.vout,
             authorization: Authorized,
+        })
+    }
+}
+
+/// Signs the given message, returning the DER-encoded signature with the sighash type
+/// appended.
+#[cfg(feature = "transparent-inputs")]
+fn sign<C: secp256k1::Signing>(
+    secp: &secp256k1::Secp256k1<C>,
+    msg: &secp256k1::Message,
+    sk: &secp256k1::SecretKey,
+) -> Vec<u8> {
+    encode_signature(&secp.sign_ecdsa(msg, sk))
+}
+
+/// Returns the DER encoding of the given signature with the sighash type appended.
+#[cfg(feature = "transparent-inputs")]
+fn encode_signature(sig: &secp256k1::ecdsa::Signature) -> Vec<u8> {
+    // Signature has to have "SIGHASH_ALL" appended to it
+    let mut sig_bytes: Vec<u8> = sig.serialize_der()[..].to_vec();
+    sig_bytes.extend([SIGHASH_ALL]);
+    sig_bytes
+}
+
+/// Constructs the scriptSig `OP_0 <sig>... <redeem_script>` for a P2SH multisig input.
+///
+/// The leading `OP_0` is consumed by the off-by-one bug in `OP_CHECKMULTISIG`. The
+/// signatures must be in the same order as their public keys in the redeem script.
+#[cfg(feature = "transparent-inputs")]
+fn p2sh_multisig_script_sig(sigs: &[Vec<u8>], redeem_script: &Script) -> Script {
+    sigs.iter()
+        .fold(Script::default() << &[][..], |s, sig| s << &sig[..])
+        << &redeem_script.0[..]
+}
+
+/// A transparent authorization that carries the previous outputs spent by an already
+/// signed transaction, so that signature hashes can be recomputed for it.
+#[cfg(feature = "transparent-inputs")]
+#[derive(Debug)]
+struct PrevoutsAuth {
+    prevouts: Vec<TxOut>,
+}
+
+#[cfg(feature = "transparent-inputs")]
+impl Authorization for PrevoutsAuth {
+    type ScriptSig = Script;
+}
+
+#[cfg(feature = "transparent-inputs")]
+impl TransparentAuthorizingContext for PrevoutsAuth {
+    fn input_amounts(&self) -> Vec<NonNegativeAmount> {
+        self.prevouts.iter().map(|prevout| prevout.value).collect()
+    }
+
+    fn input_scriptpubkeys(&self) -> Vec<Script> {
+        self.prevouts
+            .iter()
+            .map(|prevout| prevout.script_pubkey.clone())
+            .collect()
+    }
+}
+
+#[cfg(feature = "transparent-inputs")]
+struct PartiallySigned;
+
+#[cfg(feature = "transparent-inputs")]
+impl tx::Authorization for PartiallySigned {
+    type TransparentAuth = PrevoutsAuth;
+    type SaplingAuth = sapling::bundle::Authorized;
+    type OrchardAuth = orchard::bundle::Authorized;
+
+    #[cfg(zcash_unstable = "zfuture")]
+    type TzeAuth = tze::Authorized;
+}
+
+/// An authorization with fully authorized shielded components, differing from
+/// [`tx::Authorized`] at most in its transparent authorization.
+#[cfg(all(feature = "transparent-inputs", not(zcash_unstable = "zfuture")))]
+trait ShieldedAuthorized:
+    tx::Authorization<
+    SaplingAuth = sapling::bundle::Authorized,
+    OrchardAuth = orchard::bundle::Authorized,
+>
+{
+}
+
+#[cfg(all(feature = "transparent-inputs", zcash_unstable = "zfuture"))]
+trait ShieldedAuthorized:
+    tx::Authorization<
+    SaplingAuth = sapling::bundle::Authorized,
+    OrchardAuth = orchard::bundle::Authorized,
+    TzeAuth = tze::Authorized,
+>
+{
+}
+
+#[cfg(feature = "transparent-inputs")]
+impl ShieldedAuthorized for tx::Authorized {}
+
+#[cfg(feature = "transparent-inputs")]
+impl ShieldedAuthorized for PartiallySigned {}
+
+/// Rebuilds the given transaction with the transparent bundle produced by `f`.
+#[cfg(feature = "transparent-inputs")]
+fn with_transparent_bundle<A: ShieldedAuthorized>(
+    tx: &Transaction,
+    f: impl FnOnce(&Bundle<Authorized>) -> Bundle<A::TransparentAuth>,
+) -> TransactionData<A> {
+    #[cfg(not(zcash_unstable = "zfuture"))]
+    return TransactionData::from_parts(
+        tx.version(),
+        tx.consensus_branch_id(),
+        tx.lock_time(),
+        tx.expiry_height(),
+        tx.transparent_bundle().map(f),
+        tx.sprout_bundle().cloned(),
+        tx.sapling_bundle().cloned(),
+        tx.orchard_bundle().cloned(),
+    );
+    #[cfg(zcash_unstable = "zfuture")]
+    return TransactionData::from_parts_zfuture(
+        tx.version(),
+        tx.consensus_branch_id(),
+        tx.lock_time(),
+        tx.expiry_height(),
+        tx.transparent_bundle().map(f),
+        tx.sprout_bundle().cloned(),
+        tx.sapling_bundle().cloned(),
+        tx.orchard_bundle().cloned(),
+        tx.tze_bundle().cloned(),
+    );
+}
+
+/// A P2SH multisig input of an existing transaction, with the signatures it already
+/// carries matched against the public keys of its redeem script.
+#[cfg(feature = "transparent-inputs")]
+struct MultisigInput<'a> {
+    tx: &'a Transaction,
+    index: usize,
+    redeem_script: &'a Script,
+    sighash: SignatureHash,
+    pubkeys: Vec<secp256k1::PublicKey>,
+    threshold: usize,
+    sigs: Vec<Option<Vec<u8>>>,
+}
+
+#[cfg(feature = "transparent-inputs")]
+impl<'a> MultisigInput<'a> {
+    fn parse(
+        tx: &'a Transaction,
+        index: usize,
+        prevouts: &[TxOut],
+        redeem_script: &'a Script,
+    ) -> Result<Self, Error> {
+        let (threshold, keys) = redeem_script
+            .multisig_keys()
+            .ok_or(Error::InvalidRedeemScript)?;
+        let pubkeys = keys
+            .iter()
+            .map(|pk| secp256k1::PublicKey::from_slice(pk))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| Error::InvalidRedeemScript)?;
+
+        let vin = tx.transparent_bundle().map_or(&[][..], |b| &b.vin[..]);
+        let txin = vin.get(index).ok_or(Error::InvalidInputIndex(index))?;
+        if prevouts.len() != vin.len() {
+            return Err(Error::PrevoutCountMismatch {
+                expected: vin.len(),
+                actual: prevouts.len(),
+            });
+        }
+        let prevout = &prevouts[index];
+        if prevout.script_pubkey.address()
+            != Some(TransparentAddress::from_redeem_script(redeem_script))
+        {
+            return Err(Error::InvalidAddress);
+        }
+
+        let txdata = with_transparent_bundle::<PartiallySigned>(tx, |b| Bundle {
+            vin: b
+                .vin
+                .iter()
+                .map(|txin| TxIn {
+                    prevout: txin.prevout.clone(),
+                    script_sig: txin.script_sig.clone(),
+                    sequence: txin.sequence,
+                })
+                .collect(),
+            vout: b.vout.clone(),
+            authorization: PrevoutsAuth {
+                prevouts: prevouts.to_vec(),
+            },
+        });
+        let sighash = signature_hash(
+            &txdata,
+            &SignableInput::Transparent {
+                hash_type: SIGHASH_ALL,
+                index,
+                script_code: redeem_script,
+                script_pubkey: &prevout.script_pubkey,
+                value: prevout.value,
+            },
+            &txdata.digest(TxIdDigester),
+        );
+
+        let mut input = MultisigInput {
+            tx,
+            index,
+            redeem_script,
+            sighash,
+            pubkeys,
+            threshold,
+            sigs: vec![None; keys.len()],
+        };
+
+        // Match each existing signature to the public key it was made with. An empty
+        // scriptSig has no signatures yet.
+        let pushes = txin
+            .script_sig
+            .instructions()
+            .map(|i| match i {
+                Ok(Instruction::PushBytes(data)) => Ok(data),
+                _ => Err(Error::InvalidSignature),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        match &pushes[..] {
+            [] => (),
+            [dummy, sigs @ .., script] if dummy.is_empty() && script == &&redeem_script.0[..] => {
+                for sig in sigs {
+                    input.insert(sig)?;
+                }
+            }
+            _ => return Err(Error::InvalidSignature),
         }
+
+        Ok(input)
+    }
+
+    fn message(&self) -> secp256k1::Message {
+        secp256k1::Message::from_slice(self.sighash.as_ref()).expect("32 bytes")
+    }
+
+    /// Adds a signature to the input, in the position of the key that made it.
+    fn insert(&mut self, sig: &[u8]) -> Result<(), Error> {
+        let secp = secp256k1::Secp256k1::verification_only();
+        let ecdsa_sig = match sig {
+            [der @ .., SIGHASH_ALL] => {
+                secp256k1::ecdsa::Signature::from_der(der).map_err(|_| Error::InvalidSignature)?
+            }
+            _ => return Err(Error::InvalidSignature),
+        };
+        let msg = self.message();
+        let slot = self
+            .pubkeys
+            .iter()
+            .position(|pk| secp.verify_ecdsa(&msg, &ecdsa_sig, pk).is_ok())
+            .ok_or(Error::InvalidSignature)?;
+        self.sigs[slot] = Some(sig.to_vec());
+        Ok(())
+    }
+
+    fn into_transaction(self) -> Transaction {
+        let sigs = self
+            .sigs
+            .into_iter()
+            .flatten()
+            .take(self.threshold)
+            .collect::<Vec<_>>();
+        let script_sig = p2sh_multisig_script_sig(&sigs, self.redeem_script);
+
+        with_transparent_bundle::<tx::Authorized>(self.tx, |b| Bundle {
+            vin: b
+                .vin
+                .iter()
+                .enumerate()
+                .map(|(i, txin)| TxIn {
+                    prevout: txin.prevout.clone(),
+                    script_sig: if i == self.index {
+                        script_sig.clone()
+                    } else {
+                        txin.script_sig.clone()
+                    },
+                    sequence: txin.sequence,
+                })
+                .collect(),
+            vout: b.vout.clone(),
+            authorization: Authorized,
+        })
+        .freeze()
+        .expect("the transaction was previously serializable")
+    }
+}
+
+/// Computes the signature hash that the signers of the P2SH multisig input at `index`
+/// must sign, for signers that produce signatures externally.
+///
+/// `prevouts` must contain the outputs spent by each of the transaction's transparent
+/// inputs, in order, and `redeem_script` the multisig script for the input being signed.
+#[cfg(feature = "transparent-inputs")]
+pub fn p2sh_multisig_signature_hash(
+    tx: &Transaction,
+    index: usize,
+    prevouts: &[TxOut],
+    redeem_script: &Script,
+) -> Result<SignatureHash, Error> {
+    MultisigInput::parse(tx, index, prevouts, redeem_script).map(|input| input.sighash)
+}
+
+/// Adds a signature to the P2SH multisig input at `index` of a partially signed
+/// transaction, returning the updated transaction.
+///
+/// `signature` must be a DER-encoded ECDSA signature over the input's
+/// [`p2sh_multisig_signature_hash`] with `SIGHASH_ALL` appended, made by one of the keys
+/// of `redeem_script`. Signatures already present in the input are retained, and all
+/// signatures are ordered by the position of their keys in the redeem script. If the
+/// input already carries enough signatures to meet the threshold, the transaction is
+/// returned unchanged.
+#[cfg(feature = "transparent-inputs")]
+pub fn add_p2sh_multisig_signature(
+    tx: &Transaction,
+    index: usize,
+    prevouts: &[TxOut],
+    redeem_script: &Script,
+    signature: &[u8],
+) -> Result<Transaction, Error> {
+    let mut input = MultisigInput::parse(tx, index, prevouts, redeem_script)?;
+    if input.sigs.iter().flatten().count() < input.threshold {
+        input.insert(signature)?;
+    }
+    Ok(input.into_transaction())
+}
+
+/// Signs the P2SH multisig input at `index` of a partially signed transaction with the
+/// given key, returning the updated transaction.
+///
+/// This is equivalent to signing the input's [`p2sh_multisig_signature_hash`] and
+/// passing the result to [`add_p2sh_multisig_signature`].
+#[cfg(feature = "transparent-inputs")]
+pub fn sign_p2sh_multisig_input(
+    tx: &Transaction,
+    index: usize,
+    prevouts: &[TxOut],
+    redeem_script: &Script,
+    sk: &secp256k1::SecretKey,
+) -> Result<Transaction, Error> {
+    let mut input = MultisigInput::parse(tx, index, prevouts, redeem_script)?;
+    if input.sigs.iter().flatten().count() < input.threshold {
+        let sig = sign(&secp256k1::Secp256k1::signing_only(), &input.message(), sk);
+        input.insert(&sig)?;
     }
+    Ok(input.into_transaction())
 }
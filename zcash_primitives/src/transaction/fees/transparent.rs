@@ -3,9 +3,14 @@
 
 use std::convert::Infallible;
 
+use zcash_encoding::CompactSize;
+
 use crate::{
-    legacy::Script,
-    transaction::components::{amount::NonNegativeAmount, transparent::TxOut, OutPoint},
+    legacy::{Script, TransparentAddress},
+    transaction::{
+        components::{amount::NonNegativeAmount, transparent::TxOut, OutPoint},
+        fees::zip317::P2PKH_STANDARD_INPUT_SIZE,
+    },
 };
 
 #[cfg(feature = "transparent-inputs")]
@@ -18,6 +23,18 @@ pub trait InputView: std::fmt::Debug {
     fn outpoint(&self) -> &OutPoint;
     /// The previous output being spent.
     fn coin(&self) -> &TxOut;
+
+    /// Returns the size of the input when serialized in a transaction, in bytes, or `None`
+    /// if it cannot be determined from this view.
+    ///
+    /// By default, inputs spending P2PKH coins are assumed to have the standard P2PKH
+    /// input size defined by ZIP 317, and the size of any other input is unknown.
+    fn serialized_size(&self) -> Option<usize> {
+        match self.coin().script_pubkey.address() {
+            Some(TransparentAddress::PublicKeyHash(_)) => Some(P2PKH_STANDARD_INPUT_SIZE),
+            _ => None,
+        }
+    }
 }
 
 #[cfg(feature = "transparent-inputs")]
@@ -29,6 +46,10 @@ impl InputView for TransparentInputInfo {
     fn coin(&self) -> &TxOut {
         self.coin()
     }
+
+    fn serialized_size(&self) -> Option<usize> {
+        Some(self.serialized_size())
+    }
 }
 
 impl InputView for Infallible {
@@ -47,6 +68,12 @@ pub trait OutputView: std::fmt::Debug {
     fn value(&self) -> NonNegativeAmount;
     /// Returns the script corresponding to the newly created output.
     fn script_pubkey(&self) -> &Script;
+
+    /// Returns the size of the output when serialized in a transaction, in bytes.
+    fn serialized_size(&self) -> usize {
+        let script_len = self.script_pubkey().0.len();
+        8 + CompactSize::serialized_size(script_len) + script_len
+    }
 }
 
 impl OutputView for TxOut {
@@ -6,7 +6,6 @@ use core::cmp::max;
 
 use crate::{
     consensus::{self, BlockHeight},
-    legacy::TransparentAddress,
     transaction::{
         components::{
             amount::{BalanceError, NonNegativeAmount},
@@ -117,7 +116,8 @@ pub enum FeeError {
     /// An overflow or underflow of amount computation occurred.
     Balance(BalanceError),
     /// Transparent inputs provided to the fee calculation included coins that do not pay to
-    /// standard P2pkh scripts.
+    /// standard P2PKH scripts, and whose serialized size is not known (such as P2SH coins
+    /// for which the redeem script is not available).
     NonP2pkhInputs(Vec<OutPoint>),
 }
 
@@ -135,7 +135,10 @@ impl std::fmt::Display for FeeError {
                 "A balance calculation violated amount validity bounds: {}.",
                 e
             ),
-            FeeError::NonP2pkhInputs(_) => write!(f, "Only P2PKH inputs are supported."),
+            FeeError::NonP2pkhInputs(_) => write!(
+                f,
+                "Only P2PKH inputs and P2SH inputs with known redeem scripts are supported."
+            ),
         }
     }
 }
@@ -153,21 +156,25 @@ impl super::FeeRule for FeeRule {
         sapling_output_count: usize,
         orchard_action_count: usize,
     ) -> Result<NonNegativeAmount, Self::Error> {
-        let non_p2pkh_inputs: Vec<_> = transparent_inputs
+        let unsized_inputs: Vec<_> = transparent_inputs
             .iter()
-            .filter_map(|t_in| match t_in.coin().script_pubkey.address() {
-                Some(TransparentAddress::PublicKeyHash(_)) => None,
-                _ => Some(t_in.outpoint()),
-            })
+            .filter(|t_in| t_in.serialized_size().is_none())
+            .map(|t_in| t_in.outpoint())
             .cloned()
             .collect();
 
-        if !non_p2pkh_inputs.is_empty() {
-            return Err(FeeError::NonP2pkhInputs(non_p2pkh_inputs));
+        if !unsized_inputs.is_empty() {
+            return Err(FeeError::NonP2pkhInputs(unsized_inputs));
         }
 
-        let t_in_total_size = transparent_inputs.len() * 150;
-        let t_out_total_size = transparent_outputs.len() * 34;
+        let t_in_total_size = transparent_inputs
+            .iter()
+            .filter_map(|t_in| t_in.serialized_size())
+            .sum::<usize>();
+        let t_out_total_size = transparent_outputs
+            .iter()
+            .map(|t_out| t_out.serialized_size())
+            .sum::<usize>();
 
         let ceildiv = |num: usize, den: usize| (num + den - 1) / den;
 
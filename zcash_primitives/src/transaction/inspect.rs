@@ -0,0 +1,310 @@
+//! Structured descriptions of raw transactions.
+//!
+//! This module decodes a serialized transaction into a [`TransactionInfo`], a summary of its
+//! header fields and the contents of each of its bundles. It is intended for debugging tools
+//! and block explorers; when the `serde` feature flag is enabled, all of the types in this
+//! module can be serialized (for example, to JSON).
+//!
+//! Amounts are reported in zatoshis. Transparent input values, and therefore the fee paid by
+//! the transaction, are only known if the outputs being spent are provided.
+
+use std::fmt;
+use std::io;
+
+use crate::consensus::BranchId;
+
+use super::{
+    components::{
+        amount::{Amount, BalanceError},
+        transparent, OutPoint, TxOut,
+    },
+    Transaction,
+};
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+/// Errors that can occur while inspecting a transaction.
+#[derive(Debug)]
+pub enum Error {
+    /// The transaction could not be parsed.
+    Parse(io::Error),
+    /// The transaction was followed by unexpected additional data.
+    TrailingData(usize),
+    /// The number of previous outputs provided did not match the number of transparent
+    /// inputs of the transaction.
+    PrevoutCountMismatch { expected: usize, actual: usize },
+    /// An overflow or underflow occurred when computing value balances.
+    Balance(BalanceError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Parse(e) => write!(f, "Transaction could not be parsed: {}", e),
+            Error::TrailingData(len) => write!(
+                f,
+                "Transaction is followed by {} bytes of trailing data",
+                len
+            ),
+            Error::PrevoutCountMismatch { expected, actual } => write!(
+                f,
+                "Expected {} previous outputs, one per transparent input, but {} were provided",
+                expected, actual
+            ),
+            Error::Balance(e) => write!(f, "Invalid value balance: {:?}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Parse(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<BalanceError> for Error {
+    fn from(e: BalanceError) -> Self {
+        Error::Balance(e)
+    }
+}
+
+/// A description of a transaction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct TransactionInfo {
+    /// The transaction ID, in the byte order used for display.
+    pub txid: String,
+    /// The size of the serialized transaction, in bytes.
+    pub size: usize,
+    /// The transaction version number, excluding the `fOverwintered` flag.
+    pub version: u32,
+    /// Whether the `fOverwintered` flag is set.
+    pub overwintered: bool,
+    /// The version group ID, for transactions that have one.
+    pub version_group_id: Option<u32>,
+    /// The consensus branch ID, for transactions that commit to one. Earlier transaction
+    /// versions are interpreted under the branch ID with which they were inspected.
+    pub consensus_branch_id: u32,
+    pub lock_time: u32,
+    pub expiry_height: u32,
+    pub transparent: Option<TransparentInfo>,
+    pub sprout: Option<SproutInfo>,
+    pub sapling: Option<SaplingInfo>,
+    pub orchard: Option<OrchardInfo>,
+    /// The fee paid by the transaction, if the values of all of its transparent inputs are
+    /// known. This is always `None` for coinbase transactions.
+    pub fee: Option<i64>,
+}
+
+/// A description of the transparent part of a transaction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct TransparentInfo {
+    pub coinbase: bool,
+    pub inputs: Vec<TransparentInputInfo>,
+    pub outputs: Vec<TransparentOutputInfo>,
+}
+
+/// A description of a transparent input.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct TransparentInputInfo {
+    /// The ID of the transaction containing the output being spent, in the byte order used
+    /// for display.
+    pub prevout_txid: String,
+    pub prevout_index: u32,
+    /// The hex-encoded `scriptSig`.
+    pub script_sig: String,
+    pub sequence: u32,
+    /// The value of the output being spent, if it is known.
+    pub value: Option<u64>,
+}
+
+/// A description of a transparent output.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct TransparentOutputInfo {
+    pub value: u64,
+    /// The hex-encoded `scriptPubKey`.
+    pub script_pubkey: String,
+}
+
+/// A description of the Sprout part of a transaction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct SproutInfo {
+    pub joinsplits: usize,
+    /// The net value transferred into the transparent value pool by the JoinSplits.
+    pub value_balance: i64,
+}
+
+/// A description of the Sapling part of a transaction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct SaplingInfo {
+    pub spends: usize,
+    pub outputs: usize,
+    /// The net value transferred into the transparent value pool by the bundle.
+    pub value_balance: i64,
+}
+
+/// A description of the Orchard part of a transaction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct OrchardInfo {
+    pub actions: usize,
+    pub spends_enabled: bool,
+    pub outputs_enabled: bool,
+    /// The hex-encoded anchor of the bundle.
+    pub anchor: String,
+    /// The net value transferred into the transparent value pool by the bundle.
+    pub value_balance: i64,
+}
+
+/// Parses a serialized transaction and describes its contents.
+///
+/// `consensus_branch_id` is used to interpret transaction versions that do not commit to a
+/// consensus branch ID; v5 and later transactions are interpreted under the branch ID that
+/// they contain. If `prevouts` is provided, it must contain the outputs spent by the
+/// transparent inputs of the transaction, in the same order as the inputs.
+pub fn inspect(
+    tx_bytes: &[u8],
+    consensus_branch_id: BranchId,
+    prevouts: Option<&[TxOut]>,
+) -> Result<TransactionInfo, Error> {
+    let mut reader = tx_bytes;
+    let tx = Transaction::read(&mut reader, consensus_branch_id).map_err(Error::Parse)?;
+    if !reader.is_empty() {
+        return Err(Error::TrailingData(reader.len()));
+    }
+
+    TransactionInfo::from_transaction(&tx, prevouts)
+}
+
+impl TransactionInfo {
+    /// Describes the given transaction.
+    ///
+    /// If `prevouts` is provided, it must contain the outputs spent by the transparent inputs
+    /// of the transaction, in the same order as the inputs.
+    pub fn from_transaction(tx: &Transaction, prevouts: Option<&[TxOut]>) -> Result<Self, Error> {
+        let transparent_bundle = tx.transparent_bundle();
+        let coinbase = transparent_bundle.map_or(false, |b| b.is_coinbase());
+
+        let prevouts = match prevouts {
+            Some(prevouts) if !coinbase => {
+                let expected = transparent_bundle.map_or(0, |b| b.vin.len());
+                if prevouts.len() != expected {
+                    return Err(Error::PrevoutCountMismatch {
+                        expected,
+                        actual: prevouts.len(),
+                    });
+                }
+                Some(prevouts)
+            }
+            _ => None,
+        };
+
+        let fee = prevouts
+            .map(|prevouts| {
+                tx.fee_paid(|outpoint: &OutPoint| {
+                    let vin = &transparent_bundle.expect("inputs are present").vin;
+                    let index = vin
+                        .iter()
+                        .position(|txin| &txin.prevout == outpoint)
+                        .expect("outpoint is spent by this transaction");
+                    Ok::<_, Error>(prevouts[index].value.into())
+                })
+            })
+            .transpose()?;
+
+        let mut size = 0;
+        tx.write(CountingWriter(&mut size))
+            .expect("counting the size of a transaction cannot fail");
+
+        Ok(TransactionInfo {
+            txid: tx.txid().to_string(),
+            size,
+            version: tx.version().header() & 0x7FFF_FFFF,
+            overwintered: tx.version().has_overwinter(),
+            version_group_id: Some(tx.version().version_group_id())
+                .filter(|_| tx.version().has_overwinter()),
+            consensus_branch_id: u32::from(tx.consensus_branch_id()),
+            lock_time: tx.lock_time(),
+            expiry_height: u32::from(tx.expiry_height()),
+            transparent: transparent_bundle.map(|b| describe_transparent(b, prevouts)),
+            sprout: tx
+                .sprout_bundle()
+                .map(|b| {
+                    Ok::<_, Error>(SproutInfo {
+                        joinsplits: b.joinsplits.len(),
+                        value_balance: b.value_balance().ok_or(BalanceError::Overflow)?.into(),
+                    })
+                })
+                .transpose()?,
+            sapling: tx.sapling_bundle().map(|b| SaplingInfo {
+                spends: b.shielded_spends().len(),
+                outputs: b.shielded_outputs().len(),
+                value_balance: (*b.value_balance()).into(),
+            }),
+            orchard: tx.orchard_bundle().map(|b| OrchardInfo {
+                actions: b.actions().len(),
+                spends_enabled: b.flags().spends_enabled(),
+                outputs_enabled: b.flags().outputs_enabled(),
+                anchor: hex::encode(b.anchor().to_bytes()),
+                value_balance: (*b.value_balance()).into(),
+            }),
+            fee: fee.map(i64::from),
+        })
+    }
+}
+
+fn describe_transparent(
+    bundle: &transparent::Bundle<transparent::Authorized>,
+    prevouts: Option<&[TxOut]>,
+) -> TransparentInfo {
+    TransparentInfo {
+        coinbase: bundle.is_coinbase(),
+        inputs: bundle
+            .vin
+            .iter()
+            .enumerate()
+            .map(|(i, txin)| {
+                let mut prevout_txid = *txin.prevout.hash();
+                prevout_txid.reverse();
+                TransparentInputInfo {
+                    prevout_txid: hex::encode(prevout_txid),
+                    prevout_index: txin.prevout.n(),
+                    script_sig: hex::encode(&txin.script_sig.0),
+                    sequence: txin.sequence,
+                    value: prevouts.map(|prevouts| prevouts[i].value.into()),
+                }
+            })
+            .collect(),
+        outputs: bundle
+            .vout
+            .iter()
+            .map(|txout| TransparentOutputInfo {
+                value: txout.value.into(),
+                script_pubkey: hex::encode(&txout.script_pubkey.0),
+            })
+            .collect(),
+    }
+}
+
+/// A writer that discards its input, counting the number of bytes written.
+struct CountingWriter<'a>(&'a mut usize);
+
+impl io::Write for CountingWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        *self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -2,6 +2,7 @@
 pub mod builder;
 pub mod components;
 pub mod fees;
+pub mod inspect;
 pub mod sighash;
 pub mod sighash_v4;
 pub mod sighash_v5;
@@ -67,6 +68,12 @@ const ZFUTURE_TX_VERSION: u32 = 0x0000FFFF;
 ///   that have been mined.
 /// - For v5 transactions onwards, this identifier is derived only from "effecting" data,
 ///   and is non-malleable in all contexts.
+///
+/// When the `serde` feature flag is enabled, a `TxId` is serialized as a hex string in the
+/// (byte-flipped) order used by its [`Display`] implementation, RPC methods, and block
+/// explorers.
+///
+/// [`Display`]: fmt::Display
 #[derive(Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash)]
 pub struct TxId([u8; 32]);
 
@@ -101,6 +108,26 @@ impl From<TxId> for [u8; 32] {
     }
 }
 
+#[cfg(feature = "serde")]
+impl serde::Serialize for TxId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for TxId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error as _;
+
+        let encoded = String::deserialize(deserializer)?;
+        let mut bytes = <[u8; 32]>::try_from(hex::decode(encoded).map_err(D::Error::custom)?)
+            .map_err(|_| D::Error::custom("transaction ID must be 32 bytes"))?;
+        bytes.reverse();
+        Ok(TxId(bytes))
+    }
+}
+
 impl TxId {
     pub fn from_bytes(bytes: [u8; 32]) -> Self {
         TxId(bytes)
@@ -256,9 +283,44 @@ impl TxVersion {
             BranchId::ZFuture => TxVersion::ZFuture,
         }
     }
+
+    /// Returns whether transactions of this version may be mined under the consensus rules
+    /// identified by the given branch ID.
+    ///
+    /// Following [ZIP 225], v4 transactions remain valid after the activation of NU5, so
+    /// more than one version may be valid for a given branch.
+    ///
+    /// [ZIP 225]: https://zips.z.cash/zip-0225
+    pub fn valid_in_branch(&self, consensus_branch_id: BranchId) -> bool {
+        match self {
+            TxVersion::Sprout(_) => consensus_branch_id == BranchId::Sprout,
+            TxVersion::Overwinter => consensus_branch_id == BranchId::Overwinter,
+            TxVersion::Sapling => {
+                !matches!(consensus_branch_id, BranchId::Sprout | BranchId::Overwinter)
+            }
+            TxVersion::Zip225 => !matches!(
+                consensus_branch_id,
+                BranchId::Sprout
+                    | BranchId::Overwinter
+                    | BranchId::Sapling
+                    | BranchId::Blossom
+                    | BranchId::Heartwood
+                    | BranchId::Canopy
+            ),
+            #[cfg(zcash_unstable = "zfuture")]
+            TxVersion::ZFuture => consensus_branch_id == BranchId::ZFuture,
+        }
+    }
 }
 
 /// Authorization state for a bundle of transaction data.
+///
+/// Each bundle of a transaction carries authorizing data (proofs and signatures) whose type
+/// reflects how far the bundle has progressed towards being fully authorized. An
+/// `Authorization` type selects the state of every bundle at once, which determines the
+/// operations available on [`TransactionData`]: for example, [`TransactionData::freeze`]
+/// requires [`Authorized`] bundles, while [`builder::authorize_transaction`] turns a
+/// [`TransactionData<Unauthorized>`] into a complete [`Transaction`].
 pub trait Authorization {
     type TransparentAuth: transparent::Authorization;
     type SaplingAuth: sapling::bundle::Authorization;
@@ -285,6 +347,17 @@ impl Authorization for Authorized {
 ///
 /// Currently this includes Sapling proofs because the types in this crate support v4
 /// transactions, which commit to the Sapling proofs in the transaction digest.
+///
+/// Bundles in this state can be produced independently of one another:
+/// - the transparent bundle by [`TransparentBuilder::build`];
+/// - the Sapling bundle by [`sapling::builder::Builder::build`], followed by
+///   [`sapling::Bundle::create_proofs`];
+/// - the Orchard bundle by [`orchard::builder::Builder::build`].
+///
+/// They can then be combined with [`TransactionData::from_parts`], and authorized together
+/// using [`builder::authorize_transaction`].
+///
+/// [`TransparentBuilder::build`]: transparent::builder::TransparentBuilder::build
 pub struct Unauthorized;
 
 impl Authorization for Unauthorized {
@@ -336,6 +409,11 @@ pub struct TransactionData<A: Authorization> {
 
 impl<A: Authorization> TransactionData<A> {
     /// Constructs a `TransactionData` from its constituent parts.
+    ///
+    /// The bundles may have been constructed independently of one another, but must all be
+    /// in the authorization state given by `A`. No checks are performed on the consistency
+    /// of the parts; in particular, callers are responsible for ensuring that `version` is
+    /// valid under `consensus_branch_id` and can represent the provided bundles.
     #[allow(clippy::too_many_arguments)]
     pub fn from_parts(
         version: TxVersion,
@@ -979,6 +1057,8 @@ pub enum DigestError {
 pub mod testing {
     use proptest::prelude::*;
 
+    pub mod vectors;
+
     use crate::consensus::BranchId;
 
     use super::{
@@ -2,11 +2,12 @@ use blake2b_simd::Hash as Blake2bHash;
 
 use super::{
     components::{amount::NonNegativeAmount, transparent},
-    sighash_v4::v4_signature_hash,
-    sighash_v5::v5_signature_hash,
+    sighash_v4::v4_signature_hash_for_branch,
+    sighash_v5::v5_signature_hash_for_branch,
     Authorization, TransactionData, TxDigests, TxVersion,
 };
 use crate::{
+    consensus::BranchId,
     legacy::Script,
     sapling::{self, bundle::GrothProofBytes},
 };
@@ -84,15 +85,41 @@ pub fn signature_hash<
     tx: &TransactionData<A>,
     signable_input: &SignableInput<'a>,
     txid_parts: &TxDigests<Blake2bHash>,
+) -> SignatureHash {
+    signature_hash_for_branch(tx, tx.consensus_branch_id, signable_input, txid_parts)
+}
+
+/// Computes the signature hash for an input to a transaction as though the transaction were
+/// being signed under the consensus rules identified by `consensus_branch_id`.
+///
+/// The branch ID recorded in `tx` is ignored. This is useful when re-signing or verifying the
+/// signatures of a historical transaction, for which the relevant branch ID is that of the
+/// consensus rules in effect at the height at which it was mined (see
+/// [`BranchId::for_height`]) and may differ from the branch ID with which the transaction
+/// data was parsed.
+pub fn signature_hash_for_branch<
+    'a,
+    TA: TransparentAuthorizingContext,
+    SA: sapling::bundle::Authorization<SpendProof = GrothProofBytes, OutputProof = GrothProofBytes>,
+    A: Authorization<SaplingAuth = SA, TransparentAuth = TA>,
+>(
+    tx: &TransactionData<A>,
+    consensus_branch_id: BranchId,
+    signable_input: &SignableInput<'a>,
+    txid_parts: &TxDigests<Blake2bHash>,
 ) -> SignatureHash {
     SignatureHash(match tx.version {
         TxVersion::Sprout(_) | TxVersion::Overwinter | TxVersion::Sapling => {
-            v4_signature_hash(tx, signable_input)
+            v4_signature_hash_for_branch(tx, consensus_branch_id, signable_input)
         }
 
-        TxVersion::Zip225 => v5_signature_hash(tx, signable_input, txid_parts),
+        TxVersion::Zip225 => {
+            v5_signature_hash_for_branch(tx, consensus_branch_id, signable_input, txid_parts)
+        }
 
         #[cfg(zcash_unstable = "zfuture")]
-        TxVersion::ZFuture => v5_signature_hash(tx, signable_input, txid_parts),
+        TxVersion::ZFuture => {
+            v5_signature_hash_for_branch(tx, consensus_branch_id, signable_input, txid_parts)
+        }
     })
 }
@@ -138,12 +138,26 @@ pub fn v4_signature_hash<
 >(
     tx: &TransactionData<A>,
     signable_input: &SignableInput<'_>,
+) -> Blake2bHash {
+    v4_signature_hash_for_branch(tx, tx.consensus_branch_id, signable_input)
+}
+
+/// Computes the signature hash for an input to a pre-v5 transaction as though the transaction
+/// were being signed under the given consensus branch, regardless of the branch ID recorded
+/// in `tx`.
+pub fn v4_signature_hash_for_branch<
+    SA: sapling::bundle::Authorization<SpendProof = GrothProofBytes, OutputProof = GrothProofBytes>,
+    A: Authorization<SaplingAuth = SA>,
+>(
+    tx: &TransactionData<A>,
+    consensus_branch_id: BranchId,
+    signable_input: &SignableInput<'_>,
 ) -> Blake2bHash {
     let hash_type = signable_input.hash_type();
     if tx.version.has_overwinter() {
         let mut personal = [0; 16];
         personal[..12].copy_from_slice(ZCASH_SIGHASH_PERSONALIZATION_PREFIX);
-        personal[12..].copy_from_slice(&u32::from(tx.consensus_branch_id).to_le_bytes());
+        personal[12..].copy_from_slice(&u32::from(consensus_branch_id).to_le_bytes());
 
         let mut h = Blake2bParams::new()
             .hash_length(32)
@@ -203,7 +217,7 @@ pub fn v4_signature_hash<
             {
                 let bundle = tx.sprout_bundle.as_ref().unwrap();
                 joinsplits_hash(
-                    tx.consensus_branch_id,
+                    consensus_branch_id,
                     &bundle.joinsplits,
                     &bundle.joinsplit_pubkey,
                 )
@@ -3,17 +3,21 @@ use std::io::Write;
 use blake2b_simd::{Hash as Blake2bHash, Params, State};
 use zcash_encoding::Array;
 
-use crate::transaction::{
-    components::transparent::{self, TxOut},
-    sighash::{
-        SignableInput, TransparentAuthorizingContext, SIGHASH_ANYONECANPAY, SIGHASH_MASK,
-        SIGHASH_NONE, SIGHASH_SINGLE,
+use crate::{
+    consensus::BranchId,
+    transaction::{
+        components::transparent::{self, TxOut},
+        sighash::{
+            SignableInput, TransparentAuthorizingContext, SIGHASH_ANYONECANPAY, SIGHASH_MASK,
+            SIGHASH_NONE, SIGHASH_SINGLE,
+        },
+        txid::{
+            hash_header_txid_data, hash_transparent_txid_data, to_hash, transparent_outputs_hash,
+            transparent_prevout_hash, transparent_sequence_hash,
+            ZCASH_TRANSPARENT_HASH_PERSONALIZATION,
+        },
+        Authorization, TransactionData, TransparentDigests, TxDigests,
     },
-    txid::{
-        hash_transparent_txid_data, to_hash, transparent_outputs_hash, transparent_prevout_hash,
-        transparent_sequence_hash, ZCASH_TRANSPARENT_HASH_PERSONALIZATION,
-    },
-    Authorization, TransactionData, TransparentDigests, TxDigests,
 };
 
 #[cfg(zcash_unstable = "zfuture")]
@@ -175,6 +179,21 @@ pub fn v5_signature_hash<
     tx: &TransactionData<A>,
     signable_input: &SignableInput<'_>,
     txid_parts: &TxDigests<Blake2bHash>,
+) -> Blake2bHash {
+    v5_signature_hash_for_branch(tx, tx.consensus_branch_id, signable_input, txid_parts)
+}
+
+/// Computes the ZIP 244 signature digest for an input to a transaction as though the
+/// transaction's header committed to the given consensus branch, regardless of the branch ID
+/// recorded in `tx`.
+pub fn v5_signature_hash_for_branch<
+    TA: TransparentAuthorizingContext,
+    A: Authorization<TransparentAuth = TA>,
+>(
+    tx: &TransactionData<A>,
+    consensus_branch_id: BranchId,
+    signable_input: &SignableInput<'_>,
+    txid_parts: &TxDigests<Blake2bHash>,
 ) -> Blake2bHash {
     // The caller must provide the transparent digests if and only if the transaction has a
     // transparent component.
@@ -183,10 +202,23 @@ pub fn v5_signature_hash<
         txid_parts.transparent_digests.is_some()
     );
 
+    // The header digest commits to the consensus branch ID, so it must be recomputed if the
+    // branch differs from the one recorded in the transaction.
+    let header_digest = if consensus_branch_id == tx.consensus_branch_id {
+        txid_parts.header_digest
+    } else {
+        hash_header_txid_data(
+            tx.version,
+            consensus_branch_id,
+            tx.lock_time,
+            tx.expiry_height,
+        )
+    };
+
     to_hash(
         tx.version,
-        tx.consensus_branch_id,
-        txid_parts.header_digest,
+        consensus_branch_id,
+        header_digest,
         transparent_sig_digest(
             tx.transparent_bundle
                 .as_ref()
@@ -0,0 +1,529 @@
+//! Loaders and checkers for the shared [`zcash-test-vectors`] transaction vectors.
+//!
+//! The `zcash-test-vectors` repository publishes its vectors as JSON files in which the
+//! first row is a comment describing the source of the vectors, the second row is a
+//! comma-separated list of field names, and every subsequent row is a single vector.
+//! This module can parse the sighash and txid vector files for v3 (ZIP 143), v4
+//! (ZIP 243), and v5 (ZIP 244) transactions, and check each vector against this crate's
+//! transaction parser and signature hash implementations. Downstream signing tools can
+//! use the same loaders to validate their own implementations against identical data.
+//!
+//! [`zcash-test-vectors`]: https://github.com/zcash/zcash-test-vectors
+
+use std::fmt;
+use std::ops::Deref;
+
+use blake2b_simd::Hash as Blake2bHash;
+use serde_json::Value;
+
+use crate::{
+    consensus::BranchId,
+    legacy::Script,
+    transaction::{
+        components::{amount::NonNegativeAmount, transparent, TxIn},
+        sighash::{
+            SignableInput, TransparentAuthorizingContext, SIGHASH_ALL, SIGHASH_ANYONECANPAY,
+            SIGHASH_NONE, SIGHASH_SINGLE,
+        },
+        sighash_v4::v4_signature_hash,
+        sighash_v5::v5_signature_hash,
+        txid::TxIdDigester,
+        Authorization, Transaction, TransactionData, TxDigests,
+    },
+};
+
+#[cfg(zcash_unstable = "zfuture")]
+use crate::transaction::components::tze;
+
+/// Errors that can occur while loading a test vector file.
+#[derive(Debug)]
+pub enum Error {
+    /// The input could not be parsed as JSON.
+    Json(serde_json::Error),
+    /// The file does not have the comment, header, and vector rows that
+    /// `zcash-test-vectors` files are expected to contain.
+    Format(&'static str),
+    /// A field required for this kind of vector is missing from the header row.
+    MissingField(&'static str),
+    /// The value of a field in the given vector row could not be decoded.
+    InvalidField { row: usize, field: &'static str },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Json(e) => write!(f, "Test vector file is not valid JSON: {}", e),
+            Error::Format(msg) => write!(f, "Malformed test vector file: {}", msg),
+            Error::MissingField(field) => {
+                write!(f, "Test vector file does not contain field \"{}\"", field)
+            }
+            Error::InvalidField { row, field } => write!(
+                f,
+                "Test vector {} has an invalid value for field \"{}\"",
+                row, field
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Json(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json(e)
+    }
+}
+
+/// A discrepancy between a test vector and the behaviour of this crate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mismatch {
+    /// The vector's transaction could not be parsed.
+    Parse(String),
+    /// The vector's input amount is not a valid non-negative amount.
+    InvalidAmount(i64),
+    /// The computed txid does not match the expected txid.
+    TxId {
+        expected: [u8; 32],
+        actual: [u8; 32],
+    },
+    /// The computed authorizing data commitment does not match the expected value.
+    AuthDigest {
+        expected: [u8; 32],
+        actual: [u8; 32],
+    },
+    /// The computed signature hash does not match the expected value. `hash_type` is
+    /// `None` for the shielded (non-transparent-input) signature hash.
+    Sighash {
+        hash_type: Option<u8>,
+        expected: [u8; 32],
+        actual: [u8; 32],
+    },
+    /// A sighash that should exist for the given hash type was absent from the vector,
+    /// or vice versa.
+    MissingSighash { hash_type: u8 },
+}
+
+/// A ZIP 143 (v3) or ZIP 243 (v4) signature hash test vector.
+#[derive(Debug, Clone)]
+pub struct SighashVector {
+    pub tx: Vec<u8>,
+    pub script_code: Script,
+    pub transparent_input: Option<u32>,
+    pub hash_type: u32,
+    pub amount: i64,
+    pub consensus_branch_id: BranchId,
+    pub sighash: [u8; 32],
+}
+
+impl SighashVector {
+    /// Checks that the signature hash computed by this crate for this vector matches the
+    /// expected value.
+    pub fn check(&self) -> Result<(), Mismatch> {
+        let tx = Transaction::read(&self.tx[..], self.consensus_branch_id)
+            .map_err(|e| Mismatch::Parse(e.to_string()))?;
+        let value = NonNegativeAmount::from_nonnegative_i64(self.amount)
+            .map_err(|_| Mismatch::InvalidAmount(self.amount))?;
+        let signable_input = match self.transparent_input {
+            Some(n) => SignableInput::Transparent {
+                hash_type: self.hash_type as u8,
+                index: n as usize,
+                script_code: &self.script_code,
+                script_pubkey: &self.script_code,
+                value,
+            },
+            _ => SignableInput::Shielded,
+        };
+
+        let actual = v4_signature_hash(tx.deref(), &signable_input);
+        check_hash(
+            self.transparent_input.map(|_| self.hash_type as u8),
+            &self.sighash,
+            actual.as_bytes(),
+        )
+    }
+}
+
+/// A ZIP 244 (v5) txid, authorizing data commitment, and signature hash test vector.
+#[derive(Debug, Clone)]
+pub struct Zip244Vector {
+    pub tx: Vec<u8>,
+    pub txid: [u8; 32],
+    pub auth_digest: [u8; 32],
+    pub amounts: Vec<i64>,
+    pub script_pubkeys: Vec<Vec<u8>>,
+    pub transparent_input: Option<u32>,
+    pub sighash_shielded: [u8; 32],
+    pub sighash_all: Option<[u8; 32]>,
+    pub sighash_none: Option<[u8; 32]>,
+    pub sighash_single: Option<[u8; 32]>,
+    pub sighash_all_anyone: Option<[u8; 32]>,
+    pub sighash_none_anyone: Option<[u8; 32]>,
+    pub sighash_single_anyone: Option<[u8; 32]>,
+}
+
+impl Zip244Vector {
+    /// Checks that the txid, authorizing data commitment, and every signature hash
+    /// computed by this crate for this vector match the expected values.
+    pub fn check(&self) -> Result<(), Mismatch> {
+        let tx = Transaction::read(&self.tx[..], BranchId::Nu5)
+            .map_err(|e| Mismatch::Parse(e.to_string()))?;
+
+        if tx.txid.as_ref() != &self.txid {
+            return Err(Mismatch::TxId {
+                expected: self.txid,
+                actual: *tx.txid.as_ref(),
+            });
+        }
+        let auth_digest = tx.auth_commitment();
+        if auth_digest.as_bytes() != self.auth_digest {
+            return Err(Mismatch::AuthDigest {
+                expected: self.auth_digest,
+                actual: auth_digest.as_bytes().try_into().unwrap(),
+            });
+        }
+
+        let (txdata, txid_parts) = self.to_txdata(&tx)?;
+
+        if let Some(index) = self.transparent_input {
+            // nIn is a u32, but to actually use it we need a usize.
+            let index = index as usize;
+            let bundle = txdata.transparent_bundle().unwrap();
+            let value = bundle.authorization.input_amounts[index];
+            let script_pubkey = &bundle.authorization.input_scriptpubkeys[index];
+            let signable_input = |hash_type| SignableInput::Transparent {
+                hash_type,
+                index,
+                script_code: script_pubkey,
+                script_pubkey,
+                value,
+            };
+            let has_corresponding_output = index < bundle.vout.len();
+
+            for (hash_type, expected) in [
+                (SIGHASH_ALL, self.sighash_all),
+                (SIGHASH_NONE, self.sighash_none),
+                (SIGHASH_SINGLE, self.sighash_single),
+                (SIGHASH_ALL | SIGHASH_ANYONECANPAY, self.sighash_all_anyone),
+                (
+                    SIGHASH_NONE | SIGHASH_ANYONECANPAY,
+                    self.sighash_none_anyone,
+                ),
+                (
+                    SIGHASH_SINGLE | SIGHASH_ANYONECANPAY,
+                    self.sighash_single_anyone,
+                ),
+            ] {
+                // SIGHASH_SINGLE is only defined for inputs with a corresponding output.
+                let defined = has_corresponding_output || hash_type & 0x1f != SIGHASH_SINGLE;
+                match (defined, expected) {
+                    (true, Some(expected)) => {
+                        let actual =
+                            v5_signature_hash(&txdata, &signable_input(hash_type), &txid_parts);
+                        check_hash(Some(hash_type), &expected, actual.as_bytes())?;
+                    }
+                    (false, None) => (),
+                    _ => return Err(Mismatch::MissingSighash { hash_type }),
+                }
+            }
+        }
+
+        let actual = v5_signature_hash(&txdata, &SignableInput::Shielded, &txid_parts);
+        check_hash(None, &self.sighash_shielded, actual.as_bytes())
+    }
+
+    /// Rebuilds the transaction data with a transparent authorizing context containing
+    /// the vector's input amounts and scriptPubKeys, which ZIP 244 commits to.
+    fn to_txdata(
+        &self,
+        tx: &Transaction,
+    ) -> Result<(TransactionData<VectorUnauthorized>, TxDigests<Blake2bHash>), Mismatch> {
+        let txdata = tx.deref();
+
+        let input_amounts = self
+            .amounts
+            .iter()
+            .map(|amount| {
+                NonNegativeAmount::from_nonnegative_i64(*amount)
+                    .map_err(|_| Mismatch::InvalidAmount(*amount))
+            })
+            .collect::<Result<_, _>>()?;
+        let input_scriptpubkeys = self
+            .script_pubkeys
+            .iter()
+            .map(|s| Script(s.clone()))
+            .collect();
+
+        let vector_bundle = txdata
+            .transparent_bundle
+            .as_ref()
+            .map(|b| transparent::Bundle {
+                // we have to do this map/clone to make the types line up, since the
+                // Authorization::ScriptSig type is bound to transparent::Authorized, and we need
+                // it to be bound to VectorTransparentAuth.
+                vin: b
+                    .vin
+                    .iter()
+                    .map(|vin| TxIn {
+                        prevout: vin.prevout.clone(),
+                        script_sig: vin.script_sig.clone(),
+                        sequence: vin.sequence,
+                    })
+                    .collect(),
+                vout: b.vout.clone(),
+                authorization: VectorTransparentAuth {
+                    input_amounts,
+                    input_scriptpubkeys,
+                },
+            });
+
+        #[cfg(not(zcash_unstable = "zfuture"))]
+        let tdata = TransactionData::from_parts(
+            txdata.version(),
+            txdata.consensus_branch_id(),
+            txdata.lock_time(),
+            txdata.expiry_height(),
+            vector_bundle,
+            txdata.sprout_bundle().cloned(),
+            txdata.sapling_bundle().cloned(),
+            txdata.orchard_bundle().cloned(),
+        );
+        #[cfg(zcash_unstable = "zfuture")]
+        let tdata = TransactionData::from_parts_zfuture(
+            txdata.version(),
+            txdata.consensus_branch_id(),
+            txdata.lock_time(),
+            txdata.expiry_height(),
+            vector_bundle,
+            txdata.sprout_bundle().cloned(),
+            txdata.sapling_bundle().cloned(),
+            txdata.orchard_bundle().cloned(),
+            txdata.tze_bundle().cloned(),
+        );
+        Ok((tdata, txdata.digest(TxIdDigester)))
+    }
+}
+
+fn check_hash(hash_type: Option<u8>, expected: &[u8; 32], actual: &[u8]) -> Result<(), Mismatch> {
+    if actual == expected {
+        Ok(())
+    } else {
+        Err(Mismatch::Sighash {
+            hash_type,
+            expected: *expected,
+            actual: actual.try_into().unwrap(),
+        })
+    }
+}
+
+#[derive(Debug)]
+struct VectorTransparentAuth {
+    input_amounts: Vec<NonNegativeAmount>,
+    input_scriptpubkeys: Vec<Script>,
+}
+
+impl transparent::Authorization for VectorTransparentAuth {
+    type ScriptSig = Script;
+}
+
+impl TransparentAuthorizingContext for VectorTransparentAuth {
+    fn input_amounts(&self) -> Vec<NonNegativeAmount> {
+        self.input_amounts.clone()
+    }
+
+    fn input_scriptpubkeys(&self) -> Vec<Script> {
+        self.input_scriptpubkeys.clone()
+    }
+}
+
+struct VectorUnauthorized;
+
+impl Authorization for VectorUnauthorized {
+    type TransparentAuth = VectorTransparentAuth;
+    type SaplingAuth = sapling::bundle::Authorized;
+    type OrchardAuth = orchard::bundle::Authorized;
+
+    #[cfg(zcash_unstable = "zfuture")]
+    type TzeAuth = tze::Authorized;
+}
+
+/// Parses the contents of `zip_0143.json` (v3 transaction signature hashes).
+pub fn read_zip_0143(json: &str) -> Result<Vec<SighashVector>, Error> {
+    read_sighash_vectors(json)
+}
+
+/// Parses the contents of `zip_0243.json` (v4 transaction signature hashes).
+pub fn read_zip_0243(json: &str) -> Result<Vec<SighashVector>, Error> {
+    read_sighash_vectors(json)
+}
+
+fn read_sighash_vectors(json: &str) -> Result<Vec<SighashVector>, Error> {
+    VectorFile::parse(json)?
+        .rows()
+        .map(|row| {
+            Ok(SighashVector {
+                tx: row.bytes("tx")?,
+                script_code: Script(row.bytes("script_code")?),
+                transparent_input: row.opt_u32("transparent_input")?,
+                hash_type: row.u32("hash_type")?,
+                amount: row.i64("amount")?,
+                consensus_branch_id: BranchId::try_from(row.u32("consensus_branch_id")?)
+                    .map_err(|_| row.invalid("consensus_branch_id"))?,
+                sighash: row.array("sighash")?,
+            })
+        })
+        .collect()
+}
+
+/// Parses the contents of `zip_0244.json` (v5 transaction txids, authorizing data
+/// commitments, and signature hashes).
+pub fn read_zip_0244(json: &str) -> Result<Vec<Zip244Vector>, Error> {
+    VectorFile::parse(json)?
+        .rows()
+        .map(|row| {
+            Ok(Zip244Vector {
+                tx: row.bytes("tx")?,
+                txid: row.array("txid")?,
+                auth_digest: row.array("auth_digest")?,
+                amounts: row
+                    .list("amounts")?
+                    .iter()
+                    .map(|v| v.as_i64().ok_or_else(|| row.invalid("amounts")))
+                    .collect::<Result<_, _>>()?,
+                script_pubkeys: row
+                    .list("script_pubkeys")?
+                    .iter()
+                    .map(|v| decode_hex(v).ok_or_else(|| row.invalid("script_pubkeys")))
+                    .collect::<Result<_, _>>()?,
+                transparent_input: row.opt_u32("transparent_input")?,
+                sighash_shielded: row.array("sighash_shielded")?,
+                sighash_all: row.opt_array("sighash_all")?,
+                sighash_none: row.opt_array("sighash_none")?,
+                sighash_single: row.opt_array("sighash_single")?,
+                sighash_all_anyone: row.opt_array("sighash_all_anyone")?,
+                sighash_none_anyone: row.opt_array("sighash_none_anyone")?,
+                sighash_single_anyone: row.opt_array("sighash_single_anyone")?,
+            })
+        })
+        .collect()
+}
+
+/// A parsed `zcash-test-vectors` JSON file.
+struct VectorFile {
+    fields: Vec<String>,
+    rows: Vec<Vec<Value>>,
+}
+
+impl VectorFile {
+    fn parse(json: &str) -> Result<Self, Error> {
+        let mut rows = match serde_json::from_str::<Value>(json)? {
+            Value::Array(rows) => rows.into_iter(),
+            _ => return Err(Error::Format("expected a top-level array")),
+        };
+
+        // The first row identifies the generator script, and is informational only.
+        rows.next()
+            .ok_or(Error::Format("missing source comment row"))?;
+        let fields = match rows.next() {
+            Some(Value::Array(header)) => match header.as_slice() {
+                [Value::String(names)] => names.split(',').map(|s| s.trim().to_owned()).collect(),
+                _ => return Err(Error::Format("header row must be a single string")),
+            },
+            _ => return Err(Error::Format("missing header row")),
+        };
+        let rows = rows
+            .map(|row| match row {
+                Value::Array(values) => Ok(values),
+                _ => Err(Error::Format("vector rows must be arrays")),
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(VectorFile { fields, rows })
+    }
+
+    fn rows(&self) -> impl Iterator<Item = Row<'_>> {
+        self.rows
+            .iter()
+            .enumerate()
+            .map(move |(index, values)| Row {
+                fields: &self.fields,
+                index,
+                values,
+            })
+    }
+}
+
+struct Row<'a> {
+    fields: &'a [String],
+    index: usize,
+    values: &'a [Value],
+}
+
+impl<'a> Row<'a> {
+    fn invalid(&self, field: &'static str) -> Error {
+        Error::InvalidField {
+            row: self.index,
+            field,
+        }
+    }
+
+    fn get(&self, field: &'static str) -> Result<&'a Value, Error> {
+        let pos = self
+            .fields
+            .iter()
+            .position(|f| f == field)
+            .ok_or(Error::MissingField(field))?;
+        self.values.get(pos).ok_or_else(|| self.invalid(field))
+    }
+
+    fn bytes(&self, field: &'static str) -> Result<Vec<u8>, Error> {
+        decode_hex(self.get(field)?).ok_or_else(|| self.invalid(field))
+    }
+
+    fn array(&self, field: &'static str) -> Result<[u8; 32], Error> {
+        self.bytes(field)?
+            .try_into()
+            .map_err(|_| self.invalid(field))
+    }
+
+    fn opt_array(&self, field: &'static str) -> Result<Option<[u8; 32]>, Error> {
+        match self.get(field)? {
+            Value::Null => Ok(None),
+            _ => self.array(field).map(Some),
+        }
+    }
+
+    fn list(&self, field: &'static str) -> Result<&'a Vec<Value>, Error> {
+        self.get(field)?
+            .as_array()
+            .ok_or_else(|| self.invalid(field))
+    }
+
+    fn i64(&self, field: &'static str) -> Result<i64, Error> {
+        self.get(field)?.as_i64().ok_or_else(|| self.invalid(field))
+    }
+
+    fn u32(&self, field: &'static str) -> Result<u32, Error> {
+        self.get(field)?
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| self.invalid(field))
+    }
+
+    fn opt_u32(&self, field: &'static str) -> Result<Option<u32>, Error> {
+        match self.get(field)? {
+            Value::Null => Ok(None),
+            _ => self.u32(field).map(Some),
+        }
+    }
+}
+
+fn decode_hex(value: &Value) -> Option<Vec<u8>> {
+    value.as_str().and_then(|s| hex::decode(s).ok())
+}
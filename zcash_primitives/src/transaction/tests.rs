@@ -1,29 +1,13 @@
-use blake2b_simd::Hash as Blake2bHash;
-use std::ops::Deref;
-
+use assert_matches::assert_matches;
 use proptest::prelude::*;
 
-use crate::{
-    consensus::BranchId, legacy::Script, transaction::components::amount::NonNegativeAmount,
-};
+use crate::consensus::BranchId;
 
 use super::{
-    sapling,
-    sighash::{
-        SignableInput, TransparentAuthorizingContext, SIGHASH_ALL, SIGHASH_ANYONECANPAY,
-        SIGHASH_NONE, SIGHASH_SINGLE,
-    },
-    sighash_v4::v4_signature_hash,
-    sighash_v5::v5_signature_hash,
-    testing::arb_tx,
-    transparent::{self},
-    txid::TxIdDigester,
-    Authorization, Transaction, TransactionData, TxDigests, TxIn,
+    testing::{arb_tx, vectors},
+    Transaction,
 };
 
-#[cfg(zcash_unstable = "zfuture")]
-use super::components::tze;
-
 #[test]
 fn tx_read_write() {
     let data = &self::data::tx_read_write::TX_READ_WRITE;
@@ -38,6 +22,22 @@ fn tx_read_write() {
     assert_eq!(&data[..], &encoded[..]);
 }
 
+#[cfg(feature = "serde")]
+#[test]
+fn txid_serde() {
+    let data = &self::data::tx_read_write::TX_READ_WRITE;
+    let txid = Transaction::read(&data[..], BranchId::Canopy)
+        .unwrap()
+        .txid();
+    let json = serde_json::to_string(&txid).unwrap();
+    assert_eq!(
+        json,
+        "\"64f0bd7fe30ce23753358fe3a2dc835b8fba9c0274c4e2c54a6f73114cb55639\""
+    );
+    assert_eq!(serde_json::from_str::<super::TxId>(&json).unwrap(), txid);
+    assert!(serde_json::from_str::<super::TxId>("\"64f0bd\"").is_err());
+}
+
 fn check_roundtrip(tx: Transaction) -> Result<(), TestCaseError> {
     let mut txn_bytes = vec![];
     tx.write(&mut txn_bytes).unwrap();
@@ -134,29 +134,39 @@ mod data;
 #[test]
 fn zip_0143() {
     for tv in self::data::zip_0143::make_test_vectors() {
-        let tx = Transaction::read(&tv.tx[..], tv.consensus_branch_id).unwrap();
-        let signable_input = match tv.transparent_input {
-            Some(n) => SignableInput::Transparent {
-                hash_type: tv.hash_type as u8,
-                index: n as usize,
-                script_code: &tv.script_code,
-                script_pubkey: &tv.script_code,
-                value: NonNegativeAmount::from_nonnegative_i64(tv.amount).unwrap(),
-            },
-            _ => SignableInput::Shielded,
-        };
-
-        assert_eq!(
-            v4_signature_hash(tx.deref(), &signable_input).as_ref(),
-            tv.sighash
-        );
+        assert_eq!(tv.check(), Ok(()));
     }
 }
 
 #[test]
 fn zip_0243() {
     for tv in self::data::zip_0243::make_test_vectors() {
-        let tx = Transaction::read(&tv.tx[..], tv.consensus_branch_id).unwrap();
+        assert_eq!(tv.check(), Ok(()));
+    }
+}
+
+#[test]
+fn zip_0244() {
+    for tv in self::data::zip_0244::make_test_vectors() {
+        assert_eq!(tv.check(), Ok(()));
+    }
+}
+
+#[test]
+fn sighash_branch_override() {
+    use std::ops::Deref;
+
+    use super::{
+        components::amount::NonNegativeAmount,
+        sighash::SignableInput,
+        sighash_v4::{v4_signature_hash, v4_signature_hash_for_branch},
+    };
+
+    for tv in self::data::zip_0243::make_test_vectors() {
+        // Parse the transaction as though it had been created under a later consensus branch
+        // that uses the same transaction format.
+        assert_eq!(tv.consensus_branch_id, BranchId::Sapling);
+        let tx = Transaction::read(&tv.tx[..], BranchId::Canopy).unwrap();
         let signable_input = match tv.transparent_input {
             Some(n) => SignableInput::Transparent {
                 hash_type: tv.hash_type as u8,
@@ -165,194 +175,212 @@ fn zip_0243() {
                 script_pubkey: &tv.script_code,
                 value: NonNegativeAmount::from_nonnegative_i64(tv.amount).unwrap(),
             },
-            _ => SignableInput::Shielded,
+            None => SignableInput::Shielded,
         };
 
+        assert_ne!(
+            v4_signature_hash(tx.deref(), &signable_input).as_bytes(),
+            &tv.sighash
+        );
         assert_eq!(
-            v4_signature_hash(tx.deref(), &signable_input).as_ref(),
-            tv.sighash
+            v4_signature_hash_for_branch(tx.deref(), tv.consensus_branch_id, &signable_input)
+                .as_bytes(),
+            &tv.sighash
         );
     }
 }
 
-#[derive(Debug)]
-struct TestTransparentAuth {
-    input_amounts: Vec<NonNegativeAmount>,
-    input_scriptpubkeys: Vec<Script>,
+/// Renders test vectors in the JSON layout used by `zcash-test-vectors`.
+fn to_vector_json(fields: &str, rows: Vec<serde_json::Value>) -> String {
+    let mut file = vec![
+        serde_json::json!(["From https://github.com/zcash/zcash-test-vectors"]),
+        serde_json::json!([fields]),
+    ];
+    file.extend(rows);
+    serde_json::Value::Array(file).to_string()
 }
 
-impl transparent::Authorization for TestTransparentAuth {
-    type ScriptSig = Script;
-}
+#[test]
+fn sighash_vector_json_roundtrip() {
+    let fields =
+        "tx, script_code, transparent_input, hash_type, amount, consensus_branch_id, sighash";
+    for (vectors, read) in [
+        (
+            self::data::zip_0143::make_test_vectors(),
+            vectors::read_zip_0143 as fn(&str) -> Result<_, _>,
+        ),
+        (
+            self::data::zip_0243::make_test_vectors(),
+            vectors::read_zip_0243,
+        ),
+    ] {
+        let json = to_vector_json(
+            fields,
+            vectors
+                .iter()
+                .map(|tv| {
+                    serde_json::json!([
+                        hex::encode(&tv.tx),
+                        hex::encode(&tv.script_code.0),
+                        tv.transparent_input,
+                        tv.hash_type,
+                        tv.amount,
+                        u32::from(tv.consensus_branch_id),
+                        hex::encode(tv.sighash),
+                    ])
+                })
+                .collect(),
+        );
 
-impl TransparentAuthorizingContext for TestTransparentAuth {
-    fn input_amounts(&self) -> Vec<NonNegativeAmount> {
-        self.input_amounts.clone()
+        let parsed = read(&json).unwrap();
+        assert_eq!(parsed.len(), vectors.len());
+        for (tv, expected) in parsed.iter().zip(vectors.iter()) {
+            assert_eq!(tv.tx, expected.tx);
+            assert_eq!(tv.sighash, expected.sighash);
+            assert_eq!(tv.check(), Ok(()));
+        }
     }
+}
 
-    fn input_scriptpubkeys(&self) -> Vec<Script> {
-        self.input_scriptpubkeys.clone()
+#[test]
+fn zip_0244_vector_json_roundtrip() {
+    let vectors = self::data::zip_0244::make_test_vectors();
+    let opt_hex = |h: Option<[u8; 32]>| h.map(hex::encode);
+    let json = to_vector_json(
+        "tx, txid, auth_digest, amounts, script_pubkeys, transparent_input, sighash_shielded, \
+         sighash_all, sighash_none, sighash_single, sighash_all_anyone, sighash_none_anyone, \
+         sighash_single_anyone",
+        vectors
+            .iter()
+            .map(|tv| {
+                serde_json::json!([
+                    hex::encode(&tv.tx),
+                    hex::encode(tv.txid),
+                    hex::encode(tv.auth_digest),
+                    tv.amounts,
+                    tv.script_pubkeys
+                        .iter()
+                        .map(hex::encode)
+                        .collect::<Vec<_>>(),
+                    tv.transparent_input,
+                    hex::encode(tv.sighash_shielded),
+                    opt_hex(tv.sighash_all),
+                    opt_hex(tv.sighash_none),
+                    opt_hex(tv.sighash_single),
+                    opt_hex(tv.sighash_all_anyone),
+                    opt_hex(tv.sighash_none_anyone),
+                    opt_hex(tv.sighash_single_anyone),
+                ])
+            })
+            .collect(),
+    );
+
+    let parsed = vectors::read_zip_0244(&json).unwrap();
+    assert_eq!(parsed.len(), vectors.len());
+    for (tv, expected) in parsed.iter().zip(vectors.iter()) {
+        assert_eq!(tv.txid, expected.txid);
+        assert_eq!(tv.check(), Ok(()));
     }
 }
 
-struct TestUnauthorized;
-
-impl Authorization for TestUnauthorized {
-    type TransparentAuth = TestTransparentAuth;
-    type SaplingAuth = sapling::bundle::Authorized;
-    type OrchardAuth = orchard::bundle::Authorized;
+#[test]
+fn vector_json_errors() {
+    assert_matches!(vectors::read_zip_0244("{}"), Err(vectors::Error::Format(_)));
+    assert_matches!(
+        vectors::read_zip_0243(r#"[["source"], ["tx, sighash"], ["00", "00"]]"#),
+        Err(vectors::Error::MissingField("script_code"))
+    );
+    assert_matches!(
+        vectors::read_zip_0244(r#"[["source"], ["tx, txid"], ["zz", "00"]]"#),
+        Err(vectors::Error::InvalidField {
+            row: 0,
+            field: "tx"
+        })
+    );
 
-    #[cfg(zcash_unstable = "zfuture")]
-    type TzeAuth = tze::Authorized;
+    let mut tv = self::data::zip_0244::make_test_vectors().remove(0);
+    tv.sighash_shielded[0] ^= 1;
+    assert_matches!(
+        tv.check(),
+        Err(vectors::Mismatch::Sighash {
+            hash_type: None,
+            ..
+        })
+    );
 }
 
 #[test]
-fn zip_0244() {
-    fn to_test_txdata(
-        tv: &self::data::zip_0244::TestVector,
-    ) -> (TransactionData<TestUnauthorized>, TxDigests<Blake2bHash>) {
-        let tx = Transaction::read(&tv.tx[..], BranchId::Nu5).unwrap();
+fn inspect_transaction() {
+    use super::{
+        components::{
+            amount::{BalanceError, NonNegativeAmount},
+            TxOut,
+        },
+        inspect::{inspect, Error},
+    };
+    use crate::legacy::Script;
 
-        assert_eq!(tx.txid.as_ref(), &tv.txid);
-        assert_eq!(tx.auth_commitment().as_ref(), &tv.auth_digest);
-
-        let txdata = tx.deref();
+    let data = &self::data::tx_read_write::TX_READ_WRITE;
+    let info = inspect(&data[..], BranchId::Canopy, None).unwrap();
+    assert_eq!(
+        info.txid,
+        "64f0bd7fe30ce23753358fe3a2dc835b8fba9c0274c4e2c54a6f73114cb55639"
+    );
+    assert_eq!(info.size, data.len());
+    assert_eq!(info.fee, None);
+
+    let mut trailing = data.to_vec();
+    trailing.push(0);
+    assert_matches!(
+        inspect(&trailing, BranchId::Canopy, None),
+        Err(Error::TrailingData(1))
+    );
 
-        let input_amounts = tv
-            .amounts
-            .iter()
-            .map(|amount| NonNegativeAmount::from_nonnegative_i64(*amount).unwrap())
-            .collect();
-        let input_scriptpubkeys = tv
-            .script_pubkeys
-            .iter()
-            .map(|s| Script(s.clone()))
-            .collect();
-
-        let test_bundle = txdata
-            .transparent_bundle
-            .as_ref()
-            .map(|b| transparent::Bundle {
-                // we have to do this map/clone to make the types line up, since the
-                // Authorization::ScriptSig type is bound to transparent::Authorized, and we need
-                // it to be bound to TestTransparentAuth.
-                vin: b
-                    .vin
-                    .iter()
-                    .map(|vin| TxIn {
-                        prevout: vin.prevout.clone(),
-                        script_sig: vin.script_sig.clone(),
-                        sequence: vin.sequence,
-                    })
-                    .collect(),
-                vout: b.vout.clone(),
-                authorization: TestTransparentAuth {
-                    input_amounts,
-                    input_scriptpubkeys,
-                },
-            });
-
-        #[cfg(not(zcash_unstable = "zfuture"))]
-        let tdata = TransactionData::from_parts(
-            txdata.version(),
-            txdata.consensus_branch_id(),
-            txdata.lock_time(),
-            txdata.expiry_height(),
-            test_bundle,
-            txdata.sprout_bundle().cloned(),
-            txdata.sapling_bundle().cloned(),
-            txdata.orchard_bundle().cloned(),
-        );
-        #[cfg(zcash_unstable = "zfuture")]
-        let tdata = TransactionData::from_parts_zfuture(
-            txdata.version(),
-            txdata.consensus_branch_id(),
-            txdata.lock_time(),
-            txdata.expiry_height(),
-            test_bundle,
-            txdata.sprout_bundle().cloned(),
-            txdata.sapling_bundle().cloned(),
-            txdata.orchard_bundle().cloned(),
-            txdata.tze_bundle().cloned(),
+    for tv in self::data::zip_0244::make_test_vectors() {
+        let tx = Transaction::read(&tv.tx[..], BranchId::Nu5).unwrap();
+        let info = inspect(&tv.tx[..], BranchId::Nu5, None).unwrap();
+        assert_eq!(info.txid, tx.txid().to_string());
+        assert_eq!(info.size, tv.tx.len());
+        assert_eq!(info.version, 5);
+        assert_eq!(
+            info.orchard.map(|o| o.actions),
+            tx.orchard_bundle().map(|b| b.actions().len())
         );
-        (tdata, txdata.digest(TxIdDigester))
-    }
 
-    for tv in self::data::zip_0244::make_test_vectors() {
-        let (txdata, txid_parts) = to_test_txdata(&tv);
-
-        if let Some(index) = tv.transparent_input {
-            // nIn is a u32, but to actually use it we need a usize.
-            let index = index as usize;
-            let bundle = txdata.transparent_bundle().unwrap();
-            let value = bundle.authorization.input_amounts[index];
-            let script_pubkey = &bundle.authorization.input_scriptpubkeys[index];
-            let signable_input = |hash_type| SignableInput::Transparent {
-                hash_type,
-                index,
-                script_code: script_pubkey,
-                script_pubkey,
-                value,
-            };
-
-            assert_eq!(
-                v5_signature_hash(&txdata, &signable_input(SIGHASH_ALL), &txid_parts).as_ref(),
-                &tv.sighash_all.unwrap()
-            );
-
-            assert_eq!(
-                v5_signature_hash(&txdata, &signable_input(SIGHASH_NONE), &txid_parts).as_ref(),
-                &tv.sighash_none.unwrap()
-            );
-
-            if index < bundle.vout.len() {
-                assert_eq!(
-                    v5_signature_hash(&txdata, &signable_input(SIGHASH_SINGLE), &txid_parts)
-                        .as_ref(),
-                    &tv.sighash_single.unwrap()
-                );
-            } else {
-                assert_eq!(tv.sighash_single, None);
-            }
-
-            assert_eq!(
-                v5_signature_hash(
-                    &txdata,
-                    &signable_input(SIGHASH_ALL | SIGHASH_ANYONECANPAY),
-                    &txid_parts,
-                )
-                .as_ref(),
-                &tv.sighash_all_anyone.unwrap()
-            );
-
-            assert_eq!(
-                v5_signature_hash(
-                    &txdata,
-                    &signable_input(SIGHASH_NONE | SIGHASH_ANYONECANPAY),
-                    &txid_parts,
-                )
-                .as_ref(),
-                &tv.sighash_none_anyone.unwrap()
-            );
-
-            if index < bundle.vout.len() {
-                assert_eq!(
-                    v5_signature_hash(
-                        &txdata,
-                        &signable_input(SIGHASH_SINGLE | SIGHASH_ANYONECANPAY),
-                        &txid_parts,
-                    )
-                    .as_ref(),
-                    &tv.sighash_single_anyone.unwrap()
-                );
-            } else {
-                assert_eq!(tv.sighash_single_anyone, None);
-            }
+        let transparent = match tx.transparent_bundle() {
+            Some(b) if !b.is_coinbase() && !b.vin.is_empty() => b,
+            _ => continue,
         };
-
-        assert_eq!(
-            v5_signature_hash(&txdata, &SignableInput::Shielded, &txid_parts).as_ref(),
-            tv.sighash_shielded
+        let prevouts = tv
+            .amounts
+            .iter()
+            .zip(tv.script_pubkeys.iter())
+            .map(|(value, script)| TxOut {
+                value: NonNegativeAmount::from_nonnegative_i64(*value).unwrap(),
+                script_pubkey: Script(script.clone()),
+            })
+            .collect::<Vec<_>>();
+        assert_matches!(
+            inspect(&tv.tx[..], BranchId::Nu5, Some(&prevouts[1..])),
+            Err(Error::PrevoutCountMismatch { expected, .. }) if expected == transparent.vin.len()
         );
+        // The randomly generated input values in the test vectors do not necessarily
+        // produce a valid value balance.
+        let expected_fee = tx.fee_paid(|outpoint| {
+            let index = transparent
+                .vin
+                .iter()
+                .position(|txin| &txin.prevout == outpoint)
+                .unwrap();
+            Ok::<_, BalanceError>(prevouts[index].value.into())
+        });
+        match (
+            inspect(&tv.tx[..], BranchId::Nu5, Some(&prevouts)),
+            expected_fee,
+        ) {
+            (Ok(info), Ok(fee)) => assert_eq!(info.fee, Some(fee.into())),
+            (Err(Error::Balance(e)), Err(expected)) => assert_eq!(e, expected),
+            (res, expected) => panic!("unexpected result {:?}, expected {:?}", res, expected),
+        }
     }
 }
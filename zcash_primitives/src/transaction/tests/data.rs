@@ -140,22 +140,12 @@ pub mod tx_read_write {
 }
 
 pub mod zip_0143 {
-    use crate::{consensus, legacy::Script};
+    use crate::{consensus, legacy::Script, transaction::testing::vectors::SighashVector};
 
-    pub struct Test0143Vector {
-        pub tx: Vec<u8>,
-        pub script_code: Script,
-        pub transparent_input: Option<u32>,
-        pub hash_type: u32,
-        pub amount: i64,
-        pub consensus_branch_id: consensus::BranchId,
-        pub sighash: [u8; 32],
-    }
-
-    pub fn make_test_vectors() -> Vec<Test0143Vector> {
+    pub fn make_test_vectors() -> Vec<SighashVector> {
         // From https://github.com/zcash-hackworks/zcash-test-vectors/blob/master/zip_0143.py
         vec![
-            Test0143Vector {
+            SighashVector {
                 tx: vec![
                     0x03, 0x00, 0x00, 0x80, 0x70, 0x82, 0xc4, 0x03, 0x00, 0x02, 0xe7, 0x71, 0x98,
                     0x11, 0x89, 0x3e, 0x00, 0x00, 0x09, 0x52, 0x00, 0xac, 0x65, 0x51, 0xac, 0x63,
@@ -173,7 +163,7 @@ pub mod zip_0143 {
                     0xa9, 0x35, 0xb0, 0xa1, 0x90, 0x73,
                 ],
             },
-            Test0143Vector {
+            SighashVector {
                 tx: vec![
                     0x03, 0x00, 0x00, 0x80, 0x70, 0x82, 0xc4, 0x03, 0x02, 0x42, 0x01, 0xcf, 0xb1,
                     0xcd, 0x8d, 0xbf, 0x69, 0xb8, 0x25, 0x0c, 0x18, 0xef, 0x41, 0x29, 0x4c, 0xa9,
@@ -483,7 +473,7 @@ pub mod zip_0143 {
                     0x64, 0xe6, 0xe6, 0x0b, 0xb4, 0xf7,
                 ],
             },
-            Test0143Vector {
+            SighashVector {
                 tx: vec![
                     0x03, 0x00, 0x00, 0x80, 0x70, 0x82, 0xc4, 0x03, 0x02, 0x99, 0xa6, 0x9f, 0xdf,
                     0x1c, 0x5a, 0xc7, 0x73, 0x21, 0x46, 0xee, 0x5e, 0x1d, 0x6b, 0x6c, 0xa9, 0xb9,
@@ -654,7 +644,7 @@ pub mod zip_0143 {
                     0x99, 0x8e, 0x9e, 0xd9, 0x2b, 0x47,
                 ],
             },
-            Test0143Vector {
+            SighashVector {
                 tx: vec![
                     0x03, 0x00, 0x00, 0x80, 0x70, 0x82, 0xc4, 0x03, 0x00, 0x02, 0x0d, 0x38, 0x6a,
                     0xe3, 0x0d, 0xd3, 0x01, 0x00, 0x07, 0x00, 0x65, 0x51, 0x65, 0x53, 0x53, 0x6a,
@@ -957,7 +947,7 @@ pub mod zip_0143 {
                     0xa3, 0xd6, 0x86, 0x61, 0xec, 0x0a,
                 ],
             },
-            Test0143Vector {
+            SighashVector {
                 tx: vec![
                     0x03, 0x00, 0x00, 0x80, 0x70, 0x82, 0xc4, 0x03, 0x02, 0x24, 0x9d, 0xf0, 0x57,
                     0x01, 0xda, 0xb0, 0x31, 0xc4, 0xba, 0xc1, 0xea, 0x26, 0x7a, 0x29, 0x96, 0xa2,
@@ -1127,7 +1117,7 @@ pub mod zip_0143 {
                     0xf2, 0xd7, 0x0d, 0xba, 0x01, 0xad,
                 ],
             },
-            Test0143Vector {
+            SighashVector {
                 tx: vec![
                     0x03, 0x00, 0x00, 0x80, 0x70, 0x82, 0xc4, 0x03, 0x01, 0x87, 0xda, 0xa7, 0x31,
                     0xf5, 0x70, 0xa7, 0xa4, 0x06, 0x0a, 0xf0, 0xce, 0x70, 0x0d, 0x31, 0xbc, 0xa7,
@@ -1148,7 +1138,7 @@ pub mod zip_0143 {
                     0xea, 0xee, 0x4d, 0xe4, 0x4a, 0x8c,
                 ],
             },
-            Test0143Vector {
+            SighashVector {
                 tx: vec![
                     0x03, 0x00, 0x00, 0x80, 0x70, 0x82, 0xc4, 0x03, 0x02, 0x7c, 0x82, 0x97, 0x7c,
                     0x0f, 0xf7, 0x97, 0x09, 0x3e, 0x2c, 0x1f, 0x3a, 0xe8, 0x55, 0xf6, 0x5a, 0xea,
@@ -1172,7 +1162,7 @@ pub mod zip_0143 {
                     0xfc, 0x6d, 0xad, 0x2e, 0x82, 0x03,
                 ],
             },
-            Test0143Vector {
+            SighashVector {
                 tx: vec![
                     0x03, 0x00, 0x00, 0x80, 0x70, 0x82, 0xc4, 0x03, 0x00, 0x02, 0xe9, 0x6a, 0xa7,
                     0x3c, 0xd9, 0xd1, 0x04, 0x00, 0x02, 0x00, 0x53, 0x06, 0xf6, 0x99, 0xe0, 0xb1,
@@ -1475,7 +1465,7 @@ pub mod zip_0143 {
                     0x00, 0x43, 0x38, 0x6a, 0x14, 0x18,
                 ],
             },
-            Test0143Vector {
+            SighashVector {
                 tx: vec![
                     0x03, 0x00, 0x00, 0x80, 0x70, 0x82, 0xc4, 0x03, 0x02, 0x13, 0xe5, 0x6c, 0x77,
                     0x2f, 0x2c, 0x3b, 0x86, 0x0e, 0xa5, 0xb0, 0x3a, 0x88, 0x54, 0xbc, 0x6e, 0x65,
@@ -1644,7 +1634,7 @@ pub mod zip_0143 {
                     0xf3, 0xfa, 0x82, 0x2c, 0x7d, 0x8a,
                 ],
             },
-            Test0143Vector {
+            SighashVector {
                 tx: vec![
                     0x03, 0x00, 0x00, 0x80, 0x70, 0x82, 0xc4, 0x03, 0x01, 0x59, 0x07, 0x92, 0x9a,
                     0x2f, 0x3f, 0xdb, 0x0d, 0x8f, 0x79, 0x14, 0xc4, 0x2d, 0xde, 0x2d, 0x20, 0x00,
@@ -1953,21 +1943,12 @@ pub mod zip_0143 {
 }
 
 pub mod zip_0243 {
-    use crate::{consensus, legacy::Script};
+    use crate::{consensus, legacy::Script, transaction::testing::vectors::SighashVector};
 
-    pub struct Test0243Vector {
-        pub tx: Vec<u8>,
-        pub script_code: Script,
-        pub transparent_input: Option<u32>,
-        pub hash_type: u32,
-        pub amount: i64,
-        pub consensus_branch_id: consensus::BranchId,
-        pub sighash: [u8; 32],
-    }
-    pub fn make_test_vectors() -> Vec<Test0243Vector> {
+    pub fn make_test_vectors() -> Vec<SighashVector> {
         // From https://github.com/zcash-hackworks/zcash-test-vectors/blob/master/zip_0243.py
         vec![
-            Test0243Vector {
+            SighashVector {
                 tx: vec![
                     0x04, 0x00, 0x00, 0x80, 0x85, 0x20, 0x2f, 0x89, 0x00, 0x02, 0xe7, 0x71, 0x98,
                     0x11, 0x89, 0x3e, 0x00, 0x00, 0x09, 0x52, 0x00, 0xac, 0x65, 0x51, 0xac, 0x63,
@@ -2298,7 +2279,7 @@ pub mod zip_0243 {
                     0x7a, 0xff, 0xa1, 0xdd, 0x3f, 0xf3,
                 ],
             },
-            Test0243Vector {
+            SighashVector {
                 tx: vec![
                     0x04, 0x00, 0x00, 0x80, 0x85, 0x20, 0x2f, 0x89, 0x02, 0x0b, 0xbe, 0x32, 0xa5,
                     0x98, 0xc2, 0x2a, 0xdf, 0xb4, 0x8c, 0xef, 0x72, 0xba, 0x5d, 0x42, 0x87, 0xc0,
@@ -2548,7 +2529,7 @@ pub mod zip_0243 {
                     0xc8, 0x9c, 0x47, 0xb1, 0xa1, 0xdb,
                 ],
             },
-            Test0243Vector {
+            SighashVector {
                 tx: vec![
                     0x04, 0x00, 0x00, 0x80, 0x85, 0x20, 0x2f, 0x89, 0x01, 0x46, 0xcf, 0x28, 0x9b,
                     0x7d, 0x89, 0x13, 0x07, 0xbb, 0xa3, 0x70, 0x54, 0xcf, 0x91, 0xb3, 0x1f, 0xc8,
@@ -3178,7 +3159,7 @@ pub mod zip_0243 {
                     0x08, 0xac, 0xb4, 0x6f, 0xa2, 0x4b,
                 ],
             },
-            Test0243Vector {
+            SighashVector {
                 tx: vec![
                     0x04, 0x00, 0x00, 0x80, 0x85, 0x20, 0x2f, 0x89, 0x00, 0x01, 0x13, 0xf9, 0x12,
                     0xa5, 0xdc, 0x0e, 0x00, 0x00, 0x09, 0x53, 0x53, 0xac, 0x63, 0x6a, 0x53, 0x63,
@@ -3522,7 +3503,7 @@ pub mod zip_0243 {
                     0x34, 0xf9, 0x73, 0x44, 0x0d, 0xdb,
                 ],
             },
-            Test0243Vector {
+            SighashVector {
                 tx: vec![
                     0x04, 0x00, 0x00, 0x80, 0x85, 0x20, 0x2f, 0x89, 0x02, 0xdc, 0xf7, 0x58, 0x76,
                     0xdc, 0xa6, 0x09, 0xf9, 0xd2, 0x84, 0x71, 0xf9, 0x97, 0xfa, 0x11, 0xf9, 0x9d,
@@ -3683,7 +3664,7 @@ pub mod zip_0243 {
                     0x13, 0x59, 0x55, 0x30, 0x65, 0xe9,
                 ],
             },
-            Test0243Vector {
+            SighashVector {
                 tx: vec![
                     0x04, 0x00, 0x00, 0x80, 0x85, 0x20, 0x2f, 0x89, 0x01, 0xa4, 0x96, 0x69, 0x60,
                     0x21, 0x82, 0x08, 0x46, 0x69, 0x61, 0x12, 0x94, 0x90, 0xa7, 0xd8, 0xb6, 0x5c,
@@ -4112,7 +4093,7 @@ pub mod zip_0243 {
                     0xba, 0x17, 0xff, 0xe2, 0xcc, 0x8d,
                 ],
             },
-            Test0243Vector {
+            SighashVector {
                 tx: vec![
                     0x04, 0x00, 0x00, 0x80, 0x85, 0x20, 0x2f, 0x89, 0x02, 0xb5, 0xcb, 0x96, 0x49,
                     0x97, 0x9e, 0x3c, 0xcf, 0x75, 0xa8, 0xda, 0xd0, 0x54, 0x60, 0x26, 0x1f, 0xcd,
@@ -4287,7 +4268,7 @@ pub mod zip_0243 {
                     0x60, 0x65, 0x4b, 0x20, 0xf3, 0x60,
                 ],
             },
-            Test0243Vector {
+            SighashVector {
                 tx: vec![
                     0x04, 0x00, 0x00, 0x80, 0x85, 0x20, 0x2f, 0x89, 0x00, 0x02, 0x12, 0x9a, 0x03,
                     0xd5, 0x7d, 0x32, 0x07, 0x00, 0x07, 0x52, 0x51, 0xac, 0x51, 0x65, 0xac, 0x00,
@@ -4930,7 +4911,7 @@ pub mod zip_0243 {
                     0x52, 0x09, 0x60, 0xa1, 0x2a, 0x2b,
                 ],
             },
-            Test0243Vector {
+            SighashVector {
                 tx: vec![
                     0x04, 0x00, 0x00, 0x80, 0x85, 0x20, 0x2f, 0x89, 0x02, 0xa0, 0x1e, 0x64, 0x49,
                     0xae, 0x43, 0x5c, 0x24, 0xbe, 0x7b, 0x9d, 0x28, 0x8a, 0xd7, 0x57, 0x12, 0xc9,
@@ -5477,7 +5458,7 @@ pub mod zip_0243 {
                     0xf1, 0xa8, 0x93, 0xac, 0x71, 0x14,
                 ],
             },
-            Test0243Vector {
+            SighashVector {
                 tx: vec![
                     0x04, 0x00, 0x00, 0x80, 0x85, 0x20, 0x2f, 0x89, 0x02, 0x88, 0x1d, 0xdf, 0x4f,
                     0x95, 0x78, 0x97, 0x34, 0xfc, 0xc1, 0x65, 0xee, 0x1e, 0x04, 0x40, 0x85, 0xb6,
@@ -5686,26 +5667,12 @@ pub mod zip_0243 {
 }
 
 pub mod zip_0244 {
-    pub struct TestVector {
-        pub tx: Vec<u8>,
-        pub txid: [u8; 32],
-        pub auth_digest: [u8; 32],
-        pub amounts: Vec<i64>,
-        pub script_pubkeys: Vec<Vec<u8>>,
-        pub transparent_input: Option<u32>,
-        pub sighash_shielded: [u8; 32],
-        pub sighash_all: Option<[u8; 32]>,
-        pub sighash_none: Option<[u8; 32]>,
-        pub sighash_single: Option<[u8; 32]>,
-        pub sighash_all_anyone: Option<[u8; 32]>,
-        pub sighash_none_anyone: Option<[u8; 32]>,
-        pub sighash_single_anyone: Option<[u8; 32]>,
-    }
+    use crate::transaction::testing::vectors::Zip244Vector;
 
     // From https://github.com/zcash-hackworks/zcash-test-vectors/blob/master/zip_0244.py
-    pub fn make_test_vectors() -> Vec<TestVector> {
+    pub fn make_test_vectors() -> Vec<Zip244Vector> {
         vec![
-            TestVector {
+            Zip244Vector {
                 tx: vec![
                     0x05, 0x00, 0x00, 0x80, 0x0a, 0x27, 0xa7, 0x26, 0xb4, 0xd0, 0xd6, 0xc2, 0x7a,
                     0x8f, 0x73, 0x9a, 0x2d, 0x6f, 0x2c, 0x02, 0x01, 0xe1, 0x52, 0xa8, 0x04, 0x9e,
@@ -6017,7 +5984,7 @@ pub mod zip_0244 {
                 ]),
                 sighash_single_anyone: None,
             },
-            TestVector {
+            Zip244Vector {
                 tx: vec![
                     0x05, 0x00, 0x00, 0x80, 0x0a, 0x27, 0xa7, 0x26, 0xb4, 0xd0, 0xd6, 0xc2, 0x1f,
                     0xc9, 0x98, 0xc3, 0x1f, 0x4d, 0xd2, 0x08, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00,
@@ -6131,7 +6098,7 @@ pub mod zip_0244 {
                 sighash_none_anyone: None,
                 sighash_single_anyone: None,
             },
-            TestVector {
+            Zip244Vector {
                 tx: vec![
                     0x05, 0x00, 0x00, 0x80, 0x0a, 0x27, 0xa7, 0x26, 0xb4, 0xd0, 0xd6, 0xc2, 0xc2,
                     0xeb, 0x51, 0x8f, 0x68, 0x98, 0x4d, 0x02, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00,
@@ -6165,7 +6132,7 @@ pub mod zip_0244 {
                 sighash_none_anyone: None,
                 sighash_single_anyone: None,
             },
-            TestVector {
+            Zip244Vector {
                 tx: vec![
                     0x05, 0x00, 0x00, 0x80, 0x0a, 0x27, 0xa7, 0x26, 0xb4, 0xd0, 0xd6, 0xc2, 0x5e,
                     0x3d, 0xba, 0xf7, 0xae, 0x12, 0x67, 0x0d, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00,
@@ -6200,7 +6167,7 @@ pub mod zip_0244 {
                 sighash_none_anyone: None,
                 sighash_single_anyone: None,
             },
-            TestVector {
+            Zip244Vector {
                 tx: vec![
                     0x05, 0x00, 0x00, 0x80, 0x0a, 0x27, 0xa7, 0x26, 0xb4, 0xd0, 0xd6, 0xc2, 0xff,
                     0x6a, 0xcc, 0x0f, 0xfc, 0x2e, 0x49, 0x0d, 0x03, 0x14, 0x6b, 0x9d, 0x49, 0xdd,
@@ -6268,7 +6235,7 @@ pub mod zip_0244 {
                     0x9a, 0x4e, 0x77, 0xa4, 0xf3, 0x5b,
                 ]),
             },
-            TestVector {
+            Zip244Vector {
                 tx: vec![
                     0x05, 0x00, 0x00, 0x80, 0x0a, 0x27, 0xa7, 0x26, 0xb4, 0xd0, 0xd6, 0xc2, 0x23,
                     0xe1, 0x19, 0xf6, 0x35, 0xef, 0x1d, 0x05, 0x02, 0x4b, 0x21, 0x6b, 0x70, 0x23,
@@ -6335,7 +6302,7 @@ pub mod zip_0244 {
                     0x86, 0xa3, 0x10, 0x61, 0xdf, 0xa8,
                 ]),
             },
-            TestVector {
+            Zip244Vector {
                 tx: vec![
                     0x05, 0x00, 0x00, 0x80, 0x0a, 0x27, 0xa7, 0x26, 0xb4, 0xd0, 0xd6, 0xc2, 0x47,
                     0x23, 0x62, 0x29, 0x87, 0xd8, 0xd7, 0x04, 0x00, 0x00, 0x02, 0xac, 0xa3, 0xc4,
@@ -6736,7 +6703,7 @@ pub mod zip_0244 {
                 sighash_none_anyone: None,
                 sighash_single_anyone: None,
             },
-            TestVector {
+            Zip244Vector {
                 tx: vec![
                     0x05, 0x00, 0x00, 0x80, 0x0a, 0x27, 0xa7, 0x26, 0xb4, 0xd0, 0xd6, 0xc2, 0x1b,
                     0xfe, 0xb9, 0x1b, 0x0b, 0x31, 0x69, 0x1c, 0x03, 0xc2, 0xe8, 0x25, 0xa5, 0x97,
@@ -7016,7 +6983,7 @@ pub mod zip_0244 {
                 ]),
                 sighash_single_anyone: None,
             },
-            TestVector {
+            Zip244Vector {
                 tx: vec![
                     0x05, 0x00, 0x00, 0x80, 0x0a, 0x27, 0xa7, 0x26, 0xb4, 0xd0, 0xd6, 0xc2, 0x81,
                     0x83, 0x6c, 0x3b, 0xe9, 0x9a, 0x08, 0x17, 0x03, 0xa4, 0x60, 0xe9, 0x68, 0xaa,
@@ -7077,7 +7044,7 @@ pub mod zip_0244 {
                 ]),
                 sighash_single_anyone: None,
             },
-            TestVector {
+            Zip244Vector {
                 tx: vec![
                     0x05, 0x00, 0x00, 0x80, 0x0a, 0x27, 0xa7, 0x26, 0xb4, 0xd0, 0xd6, 0xc2, 0x57,
                     0xb8, 0x57, 0x51, 0x23, 0x5d, 0xbc, 0x10, 0x02, 0x52, 0xe4, 0x1e, 0x00, 0x29,
@@ -221,7 +221,7 @@ fn tze_digests<A: tze::Authorization>(bundle: &tze::Bundle<A>) -> TzeDigests<Bla
 }
 
 /// Implements [ZIP 244 section T.1](https://zips.z.cash/zip-0244#t-1-header-digest)
-fn hash_header_txid_data(
+pub(crate) fn hash_header_txid_data(
     version: TxVersion,
     // we commit to the consensus branch ID with the header
     consensus_branch_id: BranchId,
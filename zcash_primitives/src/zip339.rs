@@ -3,3 +3,9 @@
 //! [ZIP 339]: https://zips.z.cash/zip-0339
 
 pub use bip0039::{Count, Error, Language, Mnemonic};
+
+/// The word lists in which mnemonic phrases may be written.
+pub use bip0039::{
+    ChineseSimplified, ChineseTraditional, Czech, English, French, Italian, Japanese, Korean,
+    Portuguese, Spanish,
+};
@@ -0,0 +1,96 @@
+//! Benchmarks of Sapling spend and output proof creation, using the bundled parameters.
+//!
+//! The proven notes and keys are generated from a fixed seed; only the randomness used
+//! within each proof differs between runs.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use group::ff::{Field, PrimeField};
+use incrementalmerkletree::{Hashable, Level, Position};
+use rand_core::{OsRng, RngCore, SeedableRng};
+use rand_xorshift::XorShiftRng;
+use sapling::{
+    prover::{OutputProver, SpendProver},
+    value::{NoteValue, ValueCommitTrapdoor},
+    zip32::ExtendedSpendingKey,
+    MerklePath, Node, Rseed, NOTE_COMMITMENT_TREE_DEPTH,
+};
+use zcash_proofs::prover::LocalTxProver;
+
+#[cfg(unix)]
+use pprof::criterion::{Output, PProfProfiler};
+
+const SEED: [u8; 16] = [
+    0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06, 0xbc, 0xe5,
+];
+
+fn bench_sapling_proving(c: &mut Criterion) {
+    let prover = LocalTxProver::bundled();
+    let mut rng = XorShiftRng::from_seed(SEED);
+
+    let extsk = ExtendedSpendingKey::master(&[0; 32]);
+    let (_, address) = extsk.default_address();
+    let value = NoteValue::from_raw(100_000);
+    let rseed = {
+        let mut bytes = [0; 32];
+        rng.fill_bytes(&mut bytes);
+        Rseed::AfterZip212(bytes)
+    };
+    let note = sapling::Note::from_parts(address, value, rseed);
+
+    // The note is the first leaf of an otherwise empty tree.
+    let merkle_path = MerklePath::from_parts(
+        (0..NOTE_COMMITMENT_TREE_DEPTH)
+            .map(|level| Node::empty_root(Level::from(level)))
+            .collect(),
+        Position::from(0),
+    )
+    .expect("path has the tree depth");
+    let anchor = sapling::Anchor::from(merkle_path.root(Node::from_cmu(&note.cmu())));
+    let anchor = bls12_381::Scalar::from_repr(anchor.to_bytes())
+        .expect("an anchor is a canonical scalar encoding");
+
+    let mut group = c.benchmark_group("sapling-proving");
+    group.sample_size(10);
+
+    group.bench_function("spend", |b| {
+        b.iter(|| {
+            let circuit = <LocalTxProver as SpendProver>::prepare_circuit(
+                extsk.expsk.proof_generation_key(),
+                *address.diversifier(),
+                rseed,
+                value,
+                jubjub::Fr::random(&mut rng),
+                ValueCommitTrapdoor::random(&mut rng),
+                anchor,
+                merkle_path.clone(),
+            )
+            .expect("note is spendable");
+            SpendProver::create_proof(&prover, circuit, &mut OsRng)
+        })
+    });
+
+    group.bench_function("output", |b| {
+        b.iter(|| {
+            let circuit = <LocalTxProver as OutputProver>::prepare_circuit(
+                jubjub::Fr::random(&mut rng),
+                address,
+                jubjub::Fr::random(&mut rng),
+                value,
+                ValueCommitTrapdoor::random(&mut rng),
+            );
+            OutputProver::create_proof(&prover, circuit, &mut OsRng)
+        })
+    });
+
+    group.finish();
+}
+
+#[cfg(unix)]
+criterion_group! {
+    name = benches;
+    config = Criterion::default().with_profiler(PProfProfiler::new(100, Output::Flamegraph(None)));
+    targets = bench_sapling_proving
+}
+#[cfg(not(unix))]
+criterion_group!(benches, bench_sapling_proving);
+criterion_main!(benches);